@@ -89,6 +89,12 @@ pub fn rust_tmp_main(hart_id: usize, device_tree: usize) {
     shutdown();
 }
 
+/// 保持关中断等待中断挂起, 返回后由调用者轮询处理
+#[inline]
+pub fn wait_for_interrupt() {
+    aarch64_cpu::asm::wfi();
+}
+
 pub fn kernel_page_table() -> PageTable {
     PageTable(crate::addr::PhysAddr(TTBR0_EL1.get_baddr() as _))
 }
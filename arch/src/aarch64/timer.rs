@@ -16,6 +16,17 @@ impl Time {
     pub fn now() -> Self {
         Self(CNTPCT_EL0.get() as _)
     }
+
+    /// 设置一次性定时器在 `deadline` 触发
+    pub fn set_deadline(deadline: Time) {
+        let delta = deadline.0.saturating_sub(Self::now().0).min(u32::MAX as usize);
+        CNTP_TVAL_EL0.set(delta as _);
+    }
+
+    /// 取消一次性定时器
+    pub fn clear_deadline() {
+        CNTP_TVAL_EL0.set(u32::MAX as _);
+    }
 }
 
 pub fn set_next_timer() {
@@ -46,6 +46,12 @@ pub fn rust_tmp_main(hart_id: usize) {
     shutdown();
 }
 
+/// 保持关中断等待中断挂起, 返回后由调用者轮询处理
+#[inline]
+pub fn wait_for_interrupt() {
+    unsafe { loongarch64::asm::idle() };
+}
+
 pub fn shutdown() -> ! {
     error!("shutdown!");
     loop {
@@ -1,5 +1,5 @@
 use loongarch64::register::ecfg::{self, LineBasedInterrupt};
-use loongarch64::register::tcfg;
+use loongarch64::register::{tcfg, ticlr};
 /// Returns the current clock time in hardware ticks.
 use loongarch64::time::{get_timer_freq, Time};
 use spin::Lazy;
@@ -18,6 +18,23 @@ impl crate::time::Time {
     pub fn now() -> Self {
         Self(Time::read())
     }
+
+    /// 设置一次性定时器在 `deadline` 触发, 同时清除已挂起的时钟中断
+    pub fn set_deadline(deadline: crate::time::Time) {
+        let now = Self::now().0;
+        // 倒计时初值须为 4 的倍数且非零
+        let ticks = (deadline.0.saturating_sub(now).max(4) + 3) & !3;
+        ticlr::clear_timer_interrupt();
+        tcfg::set_periodic(false);
+        tcfg::set_init_val(ticks);
+        tcfg::set_en(true);
+    }
+
+    /// 取消一次性定时器
+    pub fn clear_deadline() {
+        tcfg::set_en(false);
+        ticlr::clear_timer_interrupt();
+    }
 }
 
 pub fn init_timer() {
@@ -125,7 +125,8 @@ fn kernel_callback(context: &mut TrapFrame) -> TrapType {
         Trap::Exception(Exception::UserEnvCall) => TrapType::UserEnvCall,
         // 时钟中断
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
-            timer::set_next_timeout();
+            // 由内核在处理完到期定时器后重新编程
+            crate::time::Time::clear_deadline();
             add_irq(5);
             TrapType::Time
         }
@@ -150,6 +150,12 @@ pub fn wfi() {
     }
 }
 
+/// 保持关中断等待中断挂起, 返回后由调用者轮询处理
+#[inline]
+pub fn wait_for_interrupt() {
+    unsafe { riscv::asm::wfi() };
+}
+
 pub fn hart_id() -> usize {
     CPU_ID.read_current()
 }
@@ -1,8 +1,12 @@
+use core::sync::atomic::{AtomicBool, Ordering};
 use crate::currrent_arch::boards::CLOCK_FREQ;
 use crate::set_timer;
 use crate::time::Time;
 use riscv::register::{sie, time};
 
+/// 是否可以直接写 stimecmp, 由内核根据设备树中的 ISA 扩展打开
+static SSTC: AtomicBool = AtomicBool::new(false);
+
 impl Time {
     #[inline]
     pub fn get_freq() -> usize {
@@ -13,6 +17,58 @@ impl Time {
     pub fn now() -> Self {
         Self(time::read())
     }
+
+    /// 设置一次性定时器在 `deadline` 触发, 同时清除已挂起的时钟中断
+    #[inline]
+    pub fn set_deadline(deadline: Time) {
+        if SSTC.load(Ordering::Relaxed) {
+            unsafe { core::arch::asm!("csrw 0x14d, {}", in(reg) deadline.0) };
+        } else {
+            set_timer(deadline.0);
+        }
+    }
+
+    /// 取消一次性定时器
+    #[inline]
+    pub fn clear_deadline() {
+        Self::set_deadline(Time(usize::MAX));
+    }
+}
+
+/// 设备树声明了 Sstc 时调用: 固件打开了 menvcfg.STCE 就改用 stimecmp 编程定时器, 省去一次 SBI 调用,
+/// 否则继续用 SBI. 返回是否启用
+pub fn enable_sstc() -> bool {
+    let usable = stimecmp_accessible();
+    SSTC.store(usable, Ordering::Relaxed);
+    usable
+}
+
+/// 试读一次 stimecmp. STCE 没打开时这条指令触发非法指令异常,
+/// 试读期间把陷入入口临时换成只跳过这条指令并做标记的桩
+fn stimecmp_accessible() -> bool {
+    let trapped: usize;
+    unsafe {
+        core::arch::asm!(
+            "la {tmp}, 1f",
+            "csrrw {old}, stvec, {tmp}",
+            "li {trapped}, 0",
+            "csrr {tmp}, 0x14d",
+            "j 2f",
+            ".balign 4",
+            "1:",
+            "csrr {tmp}, sepc",
+            "addi {tmp}, {tmp}, 4",
+            "csrw sepc, {tmp}",
+            "li {trapped}, 1",
+            "sret",
+            "2:",
+            "csrw stvec, {old}",
+            tmp = out(reg) _,
+            old = out(reg) _,
+            trapped = out(reg) trapped,
+        );
+    }
+    trapped == 0
 }
 
 // 设置下一次时钟中断触发时间
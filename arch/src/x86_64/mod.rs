@@ -35,6 +35,12 @@ use crate::{api::ArchInterface, currrent_arch::multiboot::use_multiboot};
 #[percpu::def_percpu]
 static CPU_ID: usize = 1;
 
+/// 关中断时 hlt 无法被唤醒, 这里只做忙等提示
+#[inline]
+pub fn wait_for_interrupt() {
+    core::hint::spin_loop();
+}
+
 pub fn shutdown() -> ! {
     unsafe { PortWriteOnly::new(0x604).write(0x2000u16) };
 
@@ -15,6 +15,12 @@ impl Time {
     pub fn now() -> Self {
         Self(unsafe { core::arch::x86_64::_rdtsc() as _ })
     }
+
+    /// LAPIC 定时器仍工作在周期模式, 到期定时器在下一个时钟中断处理
+    pub fn set_deadline(_deadline: Time) {}
+
+    /// 同上, 周期模式下无需取消
+    pub fn clear_deadline() {}
 }
 
 pub(super) fn init_early() {
@@ -1,10 +1,17 @@
 use core::num::NonZeroUsize;
 use super::{BLOCK_SZ,DISK_BLOCK_SZ};
-use device::BlockDevice;
+use device::{BlockDevice, RequestQueue};
+use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use lazy_static::*;
 use spin::Mutex;
 use lru::LruCache;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Advanced by every writeback round, dirty blocks remember the epoch
+/// they became dirty in so that old ones can be written first
+static WRITEBACK_EPOCH: AtomicUsize = AtomicUsize::new(0);
 /// Cached block inside memory
 pub struct BlockCache {
     /// cached block data
@@ -15,6 +22,8 @@ pub struct BlockCache {
     block_device: Arc<dyn BlockDevice>,
     /// whether the block is dirty
     modified: bool,
+    /// writeback epoch when the block became dirty
+    dirty_epoch: usize,
 }
 
 impl BlockCache {
@@ -27,6 +36,7 @@ impl BlockCache {
             block_id,
             block_device,
             modified: false,
+            dirty_epoch: 0,
         }
     }
     /// Get the address of an offset inside the cached block data
@@ -50,6 +60,9 @@ impl BlockCache {
     {
         let type_size = core::mem::size_of::<T>();
         assert!(offset + type_size <= BLOCK_SZ);
+        if !self.modified {
+            self.dirty_epoch = WRITEBACK_EPOCH.load(Ordering::Relaxed);
+        }
         self.modified = true;
         let addr = self.addr_of_offset(offset);
         unsafe { &mut *(addr as *mut T) }
@@ -69,6 +82,14 @@ impl BlockCache {
             self.block_device.write_block(self.block_id, &self.cache);
         }
     }
+
+    /// Queue the block for writing instead of writing it right away
+    fn sync_to(&mut self, queue: &mut RequestQueue) {
+        if self.modified {
+            self.modified = false;
+            queue.write(self.block_id, &self.cache);
+        }
+    }
 }
 
 impl Drop for BlockCache {
@@ -79,8 +100,14 @@ impl Drop for BlockCache {
 /// Use a block cache of ? blocks
 const BLOCK_CACHE_SIZE: usize = 4096;
 
+/// Identifies a device by the address of the object behind the `Arc`
+fn dev_key(dev: &Arc<dyn BlockDevice>) -> usize {
+    Arc::as_ptr(dev) as *const () as usize
+}
+
 pub struct BlockCacheManager {
-    queue: LruCache<usize, Arc<Mutex<BlockCache>>>,
+    /// keyed by (device, block id), different devices never share a cache
+    queue: LruCache<(usize, usize), Arc<Mutex<BlockCache>>>,
 }
 
 impl BlockCacheManager {
@@ -90,22 +117,8 @@ impl BlockCacheManager {
         }
     }
 
-    pub fn get_block_cache(
-        &mut self,
-        block_id: usize,
-        block_device: Arc<dyn BlockDevice>,
-    ) -> Arc<Mutex<BlockCache>> {
-        if let Some(block) = self.queue.get(&block_id) {
-            block.clone()
-        } else {
-            // load block into mem and push in
-            let block_cache = Arc::new(Mutex::new(BlockCache::new(
-                block_id,
-                Arc::clone(&block_device),
-            )));
-            self.queue.push(block_id, Arc::clone(&block_cache));
-            block_cache
-        }
+    fn lookup(&mut self, key: (usize, usize)) -> Option<Arc<Mutex<BlockCache>>> {
+        self.queue.get(&key).cloned()
     }
 }
 
@@ -115,18 +128,94 @@ lazy_static! {
         Mutex::new(BlockCacheManager::new());
 }
 /// Get the block cache corresponding to the given block id and block device
+///
+/// Disk I/O is done without holding the manager lock: a loop device reads
+/// and writes through the filesystem of its backing file, which comes back
+/// to this cache.
 pub fn get_block_cache(
     block_id: usize,
     block_device: Arc<dyn BlockDevice>,
 ) -> Arc<Mutex<BlockCache>> {
-    BLOCK_CACHE_MANAGER
+    let key = (dev_key(&block_device), block_id);
+    if let Some(block) = BLOCK_CACHE_MANAGER.lock().lookup(key) {
+        return block;
+    }
+    // load block into mem and push in
+    let block_cache = Arc::new(Mutex::new(BlockCache::new(block_id, block_device)));
+    let mut manager = BLOCK_CACHE_MANAGER.lock();
+    if let Some(block) = manager.lookup(key) {
+        return block;
+    }
+    let evicted = manager.queue.push(key, Arc::clone(&block_cache));
+    drop(manager);
+    // the evicted block writes itself back when dropped
+    drop(evicted);
+    block_cache
+}
+
+/// Write back and forget every cached block of `dev`, used when the
+/// contents behind a device change (e.g. a loop device is detached)
+pub fn block_cache_invalidate_dev(dev: &Arc<dyn BlockDevice>) {
+    let key = dev_key(dev);
+    let mut manager = BLOCK_CACHE_MANAGER.lock();
+    let keys: Vec<_> = manager
+        .queue
+        .iter()
+        .filter(|((dev, _), _)| *dev == key)
+        .map(|(key, _)| *key)
+        .collect();
+    let removed: Vec<_> = keys.iter().filter_map(|key| manager.queue.pop(key)).collect();
+    drop(manager);
+    drop(removed);
+}
+
+/// Write back the cached blocks accepted by `filter` (given the device key),
+/// one request queue per device so that adjacent blocks are merged.
+/// Returns the devices that were written to.
+fn sync_caches(
+    mut filter: impl FnMut(usize, &BlockCache) -> bool,
+) -> Vec<Arc<dyn BlockDevice>> {
+    let caches: Vec<_> = BLOCK_CACHE_MANAGER
         .lock()
-        .get_block_cache(block_id, block_device)
+        .queue
+        .iter()
+        .map(|(&(dev, _), cache)| (dev, cache.clone()))
+        .collect();
+    let mut queues: BTreeMap<usize, RequestQueue> = BTreeMap::new();
+    for (dev, cache) in caches {
+        let mut cache = cache.lock();
+        if cache.modified && filter(dev, &cache) {
+            let queue = queues
+                .entry(dev)
+                .or_insert_with(|| RequestQueue::new(cache.block_device.clone()));
+            cache.sync_to(queue);
+        }
+    }
+    queues
+        .into_values()
+        .map(|mut queue| {
+            queue.submit();
+            queue.device().clone()
+        })
+        .collect()
 }
+
 /// Sync all block cache to block device
 pub fn block_cache_sync_all() {
-    let manager = BLOCK_CACHE_MANAGER.lock();
-    for (_, cache) in manager.queue.iter() {
-        cache.lock().sync();
-    }
+    sync_caches(|_, _| true);
+}
+
+/// Sync the cached blocks of one device, then flush the device
+pub fn block_cache_sync_dev(dev: &Arc<dyn BlockDevice>) {
+    let key = dev_key(dev);
+    sync_caches(|dev, _| dev == key);
+    dev.flush();
+}
+
+/// One round of background writeback: start a new epoch and write back
+/// blocks that have stayed dirty for at least `age` rounds.
+/// Returns the devices that were written to.
+pub fn block_cache_writeback(age: usize) -> Vec<Arc<dyn BlockDevice>> {
+    let epoch = WRITEBACK_EPOCH.fetch_add(1, Ordering::Relaxed) + 1;
+    sync_caches(|_, cache| cache.dirty_epoch + age <= epoch)
 }
@@ -9,4 +9,4 @@ use config::DISK_BLOCK_SZ;
 pub type DataBlock = [u8; DISK_BLOCK_SZ];
 
 //pub use block_cache::BLOCK_CACHE_MANAGER;
-pub use block_cache::{block_cache_sync_all, get_block_cache};
+pub use block_cache::{block_cache_invalidate_dev, block_cache_sync_all, block_cache_sync_dev, block_cache_writeback, get_block_cache};
@@ -11,6 +11,8 @@ pub const USER_STACK_TOP: usize = 0x13_0000_0000;
 pub const USER_MMAP_TOP: usize = 0x11_0000_0000;
 
 pub const DL_INTERP_OFFSET: usize = 0x15_0000_0000;
+/// vDSO 数据页与映像的起始地址
+pub const USER_VDSO_BASE: usize = 0x16_0000_0000;
 
 pub const MAX_FD:usize = 1024;
 //pub const PAGE_SIZE: usize = 0x1000;
@@ -1,4 +1,7 @@
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+use config::DISK_BLOCK_SZ;
 use core::any::Any;
+use spin::Mutex;
 /// Trait for block devices
 /// which reads and writes data in the unit of blocks
 pub trait BlockDevice: Send + Sync + Any {
@@ -6,5 +9,55 @@ pub trait BlockDevice: Send + Sync + Any {
     fn read_block(&self, block_id: usize, buf: &mut [u8]);
     ///Write data from buffer to block
     fn write_block(&self, block_id: usize, buf: &[u8]);
- //   fn handle_irq(&self);
-}
\ No newline at end of file
+    /// 从 `start_block` 起连续读 `buf.len() / DISK_BLOCK_SZ` 块, 能一次下发多块的设备应当重写
+    fn read_blocks(&self, start_block: usize, buf: &mut [u8]) {
+        for (i, block) in buf.chunks_mut(DISK_BLOCK_SZ).enumerate() {
+            self.read_block(start_block + i, block);
+        }
+    }
+    /// 从 `start_block` 起连续写多块
+    fn write_blocks(&self, start_block: usize, buf: &[u8]) {
+        for (i, block) in buf.chunks(DISK_BLOCK_SZ).enumerate() {
+            self.write_block(start_block + i, block);
+        }
+    }
+    /// 设备容量, 以 DISK_BLOCK_SZ 为单位, 不知道时为 0
+    fn num_blocks(&self) -> usize {
+        0
+    }
+    /// 只读设备只能只读挂载
+    fn read_only(&self) -> bool {
+        false
+    }
+    /// 把设备自己的写缓存刷到介质上, 没有写缓存的设备什么也不做
+    fn flush(&self) {}
+    /// 设备的中断处理, 由中断控制器的分发代码调用
+    fn handle_irq(&self) {}
+}
+
+/// 已注册的块设备, 以 /dev 下的名字索引
+static BLOCK_DEVICES: Mutex<BTreeMap<String, Arc<dyn BlockDevice>>> = Mutex::new(BTreeMap::new());
+
+/// 注册块设备, 名字如 "vda"
+pub fn register_block_device(name: &str, dev: Arc<dyn BlockDevice>) {
+    BLOCK_DEVICES.lock().insert(String::from(name), dev);
+}
+
+/// 注销块设备, 返回原来注册的设备
+pub fn unregister_block_device(name: &str) -> Option<Arc<dyn BlockDevice>> {
+    BLOCK_DEVICES.lock().remove(name)
+}
+
+/// 按名字查找块设备
+pub fn find_block_device(name: &str) -> Option<Arc<dyn BlockDevice>> {
+    BLOCK_DEVICES.lock().get(name).cloned()
+}
+
+/// 所有已注册的块设备
+pub fn block_devices() -> Vec<(String, Arc<dyn BlockDevice>)> {
+    BLOCK_DEVICES
+        .lock()
+        .iter()
+        .map(|(name, dev)| (name.clone(), dev.clone()))
+        .collect()
+}
@@ -1,10 +1,19 @@
 #![no_std]
-#![no_main]
+#![cfg_attr(not(test), no_main)]
 extern crate alloc;
 use alloc::sync::Arc;
 use spin::Once;
 pub mod block_dev;
+pub mod rtc_dev;
+pub mod request;
+pub mod partition;
+pub mod ninep_dev;
 
-pub use block_dev::BlockDevice;
+pub use block_dev::{BlockDevice, register_block_device, unregister_block_device, find_block_device, block_devices};
+pub use rtc_dev::RtcDevice;
+pub use request::RequestQueue;
+pub use partition::{partition_name, register_partitions, scan_partitions, Partition};
+pub use ninep_dev::{NinePDevice, register_9p_device, find_9p_device};
 
 pub static BLOCK_DEVICE: Once<Arc<dyn BlockDevice>> = Once::new();
+pub static RTC_DEVICE: Once<Arc<dyn RtcDevice>> = Once::new();
@@ -0,0 +1,24 @@
+use alloc::{collections::BTreeMap, string::String, sync::Arc};
+use core::any::Any;
+use spin::Mutex;
+/// Trait for 9P transports
+/// which carry one 9P request and its reply at a time, e.g. virtio-9p
+pub trait NinePDevice: Send + Sync + Any {
+    /// 一条消息的最大长度, 挂载时据此协商 msize
+    fn max_message(&self) -> usize;
+    /// 发出请求 `req`, 把应答写进 `resp`, 返回应答的长度. 设备出错时返回 None
+    fn request(&self, req: &[u8], resp: &mut [u8]) -> Option<usize>;
+}
+
+/// 已注册的 9P 通道, 以挂载标签索引
+static NINEP_DEVICES: Mutex<BTreeMap<String, Arc<dyn NinePDevice>>> = Mutex::new(BTreeMap::new());
+
+/// 注册 9P 通道, `tag` 是 mount(2) 时用作挂载源的标签
+pub fn register_9p_device(tag: &str, dev: Arc<dyn NinePDevice>) {
+    NINEP_DEVICES.lock().insert(String::from(tag), dev);
+}
+
+/// 按挂载标签查找 9P 通道
+pub fn find_9p_device(tag: &str) -> Option<Arc<dyn NinePDevice>> {
+    NINEP_DEVICES.lock().get(tag).cloned()
+}
@@ -0,0 +1,405 @@
+//! 分区表: 解析 MBR (含扩展分区) 与 GPT, 每个分区作为一个独立的块设备
+use crate::{register_block_device, BlockDevice};
+use alloc::{format, string::String, sync::Arc, vec, vec::Vec};
+use config::DISK_BLOCK_SZ;
+
+/// 分区表里的一项
+#[derive(Clone, Copy, Debug)]
+pub struct PartitionEntry {
+    /// 分区号, MBR 的逻辑分区从 5 开始
+    pub number: usize,
+    /// 起始块号
+    pub start: usize,
+    /// 块数
+    pub len: usize,
+}
+
+/// 父设备上的一段连续块, 块号从 0 开始
+pub struct Partition {
+    parent: Arc<dyn BlockDevice>,
+    start: usize,
+    len: usize,
+}
+
+impl Partition {
+    /// 在 `parent` 上开一个 [start, start + len) 的窗口
+    pub fn new(parent: Arc<dyn BlockDevice>, start: usize, len: usize) -> Self {
+        Self { parent, start, len }
+    }
+    /// `bytes` 字节中落在分区内的部分. 越界的部分读出 0, 写入丢弃, 不能碰到相邻分区
+    fn clamp(&self, block_id: usize, bytes: usize) -> usize {
+        bytes.min(self.len.saturating_sub(block_id) * DISK_BLOCK_SZ)
+    }
+}
+
+impl BlockDevice for Partition {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        self.read_blocks(block_id, buf)
+    }
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        self.write_blocks(block_id, buf)
+    }
+    fn read_blocks(&self, start_block: usize, buf: &mut [u8]) {
+        let (inside, outside) = buf.split_at_mut(self.clamp(start_block, buf.len()));
+        outside.fill(0);
+        if !inside.is_empty() {
+            self.parent.read_blocks(self.start + start_block, inside)
+        }
+    }
+    fn write_blocks(&self, start_block: usize, buf: &[u8]) {
+        let inside = &buf[..self.clamp(start_block, buf.len())];
+        if !inside.is_empty() {
+            self.parent.write_blocks(self.start + start_block, inside)
+        }
+    }
+    fn num_blocks(&self) -> usize {
+        self.len
+    }
+    fn flush(&self) {
+        self.parent.flush()
+    }
+}
+
+/// MBR 中表示扩展分区的类型
+const MBR_EXTENDED: [u8; 3] = [0x05, 0x0f, 0x85];
+/// 保护性 MBR, 真正的分区表是 GPT
+const MBR_GPT_PROTECTIVE: u8 = 0xee;
+/// 扩展分区链最多跟多少个 EBR, 防止坏表成环
+const MAX_LOGICAL: usize = 128;
+
+fn read_sector(dev: &Arc<dyn BlockDevice>, block_id: usize) -> Vec<u8> {
+    let mut buf = vec![0u8; DISK_BLOCK_SZ];
+    dev.read_block(block_id, &mut buf);
+    buf
+}
+
+fn le_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+fn le_u64(buf: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap())
+}
+
+/// MBR/EBR 的一个表项: (类型, 相对起始块, 块数)
+fn mbr_entry(sector: &[u8], index: usize) -> (u8, usize, usize) {
+    let entry = 446 + index * 16;
+    (sector[entry + 4], le_u32(sector, entry + 8) as usize, le_u32(sector, entry + 12) as usize)
+}
+
+fn has_mbr_signature(sector: &[u8]) -> bool {
+    sector[510] == 0x55 && sector[511] == 0xaa
+}
+
+/// FAT 等文件系统的引导扇区也带 0x55AA, 表项的引导标志只能是 0 或 0x80 才当作分区表
+fn is_mbr(sector: &[u8]) -> bool {
+    has_mbr_signature(sector) && (0..4).all(|i| matches!(sector[446 + i * 16], 0x00 | 0x80))
+}
+
+/// 读出设备上的分区, 没有可识别的分区表时为空
+pub fn scan_partitions(dev: &Arc<dyn BlockDevice>) -> Vec<PartitionEntry> {
+    let mbr = read_sector(dev, 0);
+    if !is_mbr(&mbr) {
+        return Vec::new();
+    }
+    if (0..4).any(|i| mbr_entry(&mbr, i).0 == MBR_GPT_PROTECTIVE) {
+        return scan_gpt(dev).unwrap_or_default();
+    }
+    scan_mbr(dev, &mbr)
+}
+
+fn scan_mbr(dev: &Arc<dyn BlockDevice>, mbr: &[u8]) -> Vec<PartitionEntry> {
+    let mut parts = Vec::new();
+    let mut extended = None;
+    for i in 0..4 {
+        let (kind, start, len) = mbr_entry(mbr, i);
+        if kind == 0 || len == 0 {
+            continue;
+        }
+        // 扩展分区本身只是逻辑分区的容器, 不单独作为设备
+        if MBR_EXTENDED.contains(&kind) {
+            extended.get_or_insert(start);
+            continue;
+        }
+        parts.push(PartitionEntry { number: i + 1, start, len });
+    }
+    // 扩展分区是一串 EBR: 第一项是相对本 EBR 的逻辑分区, 第二项是相对扩展分区起点的下一个 EBR
+    if let Some(ext_base) = extended {
+        let mut ebr = ext_base;
+        for number in 5..5 + MAX_LOGICAL {
+            let sector = read_sector(dev, ebr);
+            if !has_mbr_signature(&sector) {
+                break;
+            }
+            let (kind, start, len) = mbr_entry(&sector, 0);
+            if kind != 0 && len != 0 {
+                parts.push(PartitionEntry { number, start: ebr + start, len });
+            }
+            let (next_kind, next, _) = mbr_entry(&sector, 1);
+            if !MBR_EXTENDED.contains(&next_kind) || next == 0 {
+                break;
+            }
+            ebr = ext_base + next;
+        }
+    }
+    parts
+}
+
+/// 先读主 GPT 头, 校验失败再试磁盘末尾的备份头
+fn scan_gpt(dev: &Arc<dyn BlockDevice>) -> Option<Vec<PartitionEntry>> {
+    let backup = dev.num_blocks().checked_sub(1).filter(|&lba| lba > 1);
+    let header = gpt_header(dev, 1).or_else(|| backup.and_then(|lba| gpt_header(dev, lba)))?;
+    let entries_lba = le_u64(&header, 72) as usize;
+    let count = le_u32(&header, 80) as usize;
+    let entry_size = le_u32(&header, 84) as usize;
+    if entry_size < 128 || entry_size % 8 != 0 || count > 1024 {
+        return None;
+    }
+    let bytes = count * entry_size;
+    let mut table = vec![0u8; bytes.div_ceil(DISK_BLOCK_SZ) * DISK_BLOCK_SZ];
+    dev.read_blocks(entries_lba, &mut table);
+    if crc32(&table[..bytes]) != le_u32(&header, 88) {
+        return None;
+    }
+    let parts = table[..bytes]
+        .chunks(entry_size)
+        .enumerate()
+        .filter(|(_, entry)| entry[..16].iter().any(|&b| b != 0))
+        .filter_map(|(i, entry)| {
+            let first = le_u64(entry, 32) as usize;
+            let last = le_u64(entry, 40) as usize;
+            (last >= first).then(|| PartitionEntry {
+                number: i + 1,
+                start: first,
+                len: last - first + 1,
+            })
+        })
+        .collect();
+    Some(parts)
+}
+
+/// 读出 `lba` 处的 GPT 头并校验签名和头部 CRC
+fn gpt_header(dev: &Arc<dyn BlockDevice>, lba: usize) -> Option<Vec<u8>> {
+    let mut header = read_sector(dev, lba);
+    if &header[0..8] != b"EFI PART" {
+        return None;
+    }
+    let size = le_u32(&header, 12) as usize;
+    if !(92..=DISK_BLOCK_SZ).contains(&size) || le_u64(&header, 24) as usize != lba {
+        return None;
+    }
+    let crc = le_u32(&header, 16);
+    header[16..20].fill(0);
+    (crc32(&header[..size]) == crc).then_some(header)
+}
+
+/// GPT 使用的 CRC-32 (IEEE 802.3, 反射多项式 0xEDB88320)
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+/// 分区设备名: vda -> vda1, 名字以数字结尾时加 p, 如 loop0 -> loop0p1
+pub fn partition_name(disk: &str, number: usize) -> String {
+    if disk.ends_with(|c: char| c.is_ascii_digit()) {
+        format!("{}p{}", disk, number)
+    } else {
+        format!("{}{}", disk, number)
+    }
+}
+
+/// 扫描 `dev` 的分区表, 把每个分区注册为块设备, 返回注册的分区
+pub fn register_partitions(disk: &str, dev: &Arc<dyn BlockDevice>) -> Vec<(String, Arc<dyn BlockDevice>)> {
+    let capacity = dev.num_blocks();
+    let mut parts = scan_partitions(dev);
+    // 超出磁盘的表项是坏的
+    parts.retain(|part| capacity == 0 || part.start + part.len <= capacity);
+    parts
+        .iter()
+        .map(|part| {
+            let name = partition_name(disk, part.number);
+            let partition: Arc<dyn BlockDevice> = Arc::new(Partition::new(dev.clone(), part.start, part.len));
+            register_block_device(&name, partition.clone());
+            (name, partition)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spin::Mutex;
+
+    /// 内存里的磁盘
+    struct RamDisk(Mutex<Vec<u8>>);
+
+    impl RamDisk {
+        fn new(blocks: usize) -> Arc<Self> {
+            Arc::new(Self(Mutex::new(vec![0u8; blocks * DISK_BLOCK_SZ])))
+        }
+        fn put(&self, offset: usize, data: &[u8]) {
+            self.0.lock()[offset..offset + data.len()].copy_from_slice(data);
+        }
+    }
+
+    impl BlockDevice for RamDisk {
+        fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+            let start = block_id * DISK_BLOCK_SZ;
+            buf.copy_from_slice(&self.0.lock()[start..start + buf.len()]);
+        }
+        fn write_block(&self, block_id: usize, buf: &[u8]) {
+            self.put(block_id * DISK_BLOCK_SZ, buf);
+        }
+        fn num_blocks(&self) -> usize {
+            self.0.lock().len() / DISK_BLOCK_SZ
+        }
+    }
+
+    /// 在 `sector` 块写一个 MBR/EBR 表项并补上签名
+    fn put_mbr_entry(disk: &RamDisk, sector: usize, index: usize, kind: u8, start: u32, len: u32) {
+        let base = sector * DISK_BLOCK_SZ;
+        let entry = base + 446 + index * 16;
+        disk.put(entry + 4, &[kind]);
+        disk.put(entry + 8, &start.to_le_bytes());
+        disk.put(entry + 12, &len.to_le_bytes());
+        disk.put(base + 510, &[0x55, 0xaa]);
+    }
+
+    fn starts(parts: &[PartitionEntry]) -> Vec<(usize, usize, usize)> {
+        parts.iter().map(|p| (p.number, p.start, p.len)).collect()
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn mbr_follows_ebr_chain() {
+        let disk = RamDisk::new(64);
+        put_mbr_entry(&disk, 0, 0, 0x83, 1, 4);
+        put_mbr_entry(&disk, 0, 1, 0x05, 10, 40);
+        // EBR 里逻辑分区相对本 EBR, 下一个 EBR 相对扩展分区起点
+        put_mbr_entry(&disk, 10, 0, 0x83, 2, 3);
+        put_mbr_entry(&disk, 10, 1, 0x05, 10, 10);
+        put_mbr_entry(&disk, 20, 0, 0x83, 1, 5);
+        let dev: Arc<dyn BlockDevice> = disk;
+        assert_eq!(starts(&scan_partitions(&dev)), [(1, 1, 4), (5, 12, 3), (6, 21, 5)]);
+    }
+
+    #[test]
+    fn mbr_ebr_loop_is_bounded() {
+        let disk = RamDisk::new(64);
+        put_mbr_entry(&disk, 0, 0, 0x0f, 10, 40);
+        put_mbr_entry(&disk, 10, 0, 0x83, 1, 1);
+        put_mbr_entry(&disk, 10, 1, 0x05, 5, 5);
+        put_mbr_entry(&disk, 15, 0, 0x83, 1, 1);
+        put_mbr_entry(&disk, 15, 1, 0x05, 10, 5);
+        // 20 号块指回 15 号块, 链成环
+        put_mbr_entry(&disk, 20, 0, 0x83, 1, 1);
+        put_mbr_entry(&disk, 20, 1, 0x05, 5, 5);
+        let dev: Arc<dyn BlockDevice> = disk;
+        assert_eq!(scan_partitions(&dev).len(), MAX_LOGICAL);
+    }
+
+    #[test]
+    fn fat_boot_sector_is_not_mbr() {
+        let disk = RamDisk::new(8);
+        put_mbr_entry(&disk, 0, 0, 0x83, 1, 4);
+        // FAT 引导扇区在表项的引导标志处是引导代码
+        disk.put(446, &[0x31]);
+        let dev: Arc<dyn BlockDevice> = disk;
+        assert!(scan_partitions(&dev).is_empty());
+    }
+
+    const GPT_ENTRIES: usize = 4;
+    const GPT_ENTRY_SZ: usize = 128;
+
+    /// 在 `lba` 写 GPT 头, 分区表放在 `entries_lba`, 每项为 (序号, 起始块, 结束块)
+    fn put_gpt(disk: &RamDisk, lba: usize, entries_lba: usize, parts: &[(usize, u64, u64)]) {
+        let mut table = vec![0u8; GPT_ENTRIES * GPT_ENTRY_SZ];
+        for &(index, first, last) in parts {
+            let entry = &mut table[index * GPT_ENTRY_SZ..(index + 1) * GPT_ENTRY_SZ];
+            entry[..16].fill(0xaf);
+            entry[32..40].copy_from_slice(&first.to_le_bytes());
+            entry[40..48].copy_from_slice(&last.to_le_bytes());
+        }
+        let mut header = vec![0u8; DISK_BLOCK_SZ];
+        header[0..8].copy_from_slice(b"EFI PART");
+        header[8..12].copy_from_slice(&0x0001_0000u32.to_le_bytes());
+        header[12..16].copy_from_slice(&92u32.to_le_bytes());
+        header[24..32].copy_from_slice(&(lba as u64).to_le_bytes());
+        header[72..80].copy_from_slice(&(entries_lba as u64).to_le_bytes());
+        header[80..84].copy_from_slice(&(GPT_ENTRIES as u32).to_le_bytes());
+        header[84..88].copy_from_slice(&(GPT_ENTRY_SZ as u32).to_le_bytes());
+        header[88..92].copy_from_slice(&crc32(&table).to_le_bytes());
+        let crc = crc32(&header[..92]);
+        header[16..20].copy_from_slice(&crc.to_le_bytes());
+        disk.put(lba * DISK_BLOCK_SZ, &header);
+        disk.put(entries_lba * DISK_BLOCK_SZ, &table);
+    }
+
+    fn gpt_disk() -> Arc<RamDisk> {
+        let disk = RamDisk::new(64);
+        put_mbr_entry(&disk, 0, 0, MBR_GPT_PROTECTIVE, 1, 63);
+        put_gpt(&disk, 1, 2, &[(0, 10, 19), (2, 30, 30)]);
+        put_gpt(&disk, 63, 62, &[(0, 10, 19), (2, 30, 30)]);
+        disk
+    }
+
+    #[test]
+    fn gpt_primary_header() {
+        let dev: Arc<dyn BlockDevice> = gpt_disk();
+        assert_eq!(starts(&scan_partitions(&dev)), [(1, 10, 10), (3, 30, 1)]);
+    }
+
+    #[test]
+    fn gpt_falls_back_to_backup_header() {
+        let disk = gpt_disk();
+        // 主头的 CRC 对不上
+        disk.put(DISK_BLOCK_SZ + 40, &[1]);
+        let dev: Arc<dyn BlockDevice> = disk.clone();
+        assert_eq!(starts(&scan_partitions(&dev)), [(1, 10, 10), (3, 30, 1)]);
+        // 备份头也坏了
+        disk.put(63 * DISK_BLOCK_SZ, b"NOT PART");
+        assert!(scan_partitions(&dev).is_empty());
+    }
+
+    #[test]
+    fn gpt_rejects_bad_entry_crc() {
+        let disk = RamDisk::new(64);
+        put_mbr_entry(&disk, 0, 0, MBR_GPT_PROTECTIVE, 1, 63);
+        put_gpt(&disk, 1, 2, &[(0, 10, 19)]);
+        disk.put(2 * DISK_BLOCK_SZ + 32, &[11]);
+        let dev: Arc<dyn BlockDevice> = disk;
+        assert!(scan_partitions(&dev).is_empty());
+    }
+
+    #[test]
+    fn partition_clamps_to_its_window() {
+        let disk = RamDisk::new(8);
+        disk.put(4 * DISK_BLOCK_SZ, &[0x5a; DISK_BLOCK_SZ]);
+        let part = Partition::new(disk.clone(), 2, 2);
+        // 越过分区末尾的块读出 0
+        let mut buf = vec![0xffu8; 3 * DISK_BLOCK_SZ];
+        part.read_blocks(1, &mut buf);
+        assert!(buf.iter().all(|&b| b == 0));
+        // 越界写不能写到相邻的块
+        part.write_blocks(1, &[0x11; 3 * DISK_BLOCK_SZ]);
+        let mut block = vec![0u8; DISK_BLOCK_SZ];
+        disk.read_block(3, &mut block);
+        assert!(block.iter().all(|&b| b == 0x11));
+        disk.read_block(4, &mut block);
+        assert!(block.iter().all(|&b| b == 0x5a));
+        part.write_block(5, &[0x22; DISK_BLOCK_SZ]);
+        disk.read_block(4, &mut block);
+        assert!(block.iter().all(|&b| b == 0x5a));
+    }
+}
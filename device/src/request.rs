@@ -0,0 +1,143 @@
+//! 块设备请求队列: 先攒下一批写请求, 下发时按块号排序,
+//! 把相邻的块合并成一次多块请求
+use crate::BlockDevice;
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use config::DISK_BLOCK_SZ;
+
+/// 一次合并请求最多包含的块数
+pub const MAX_MERGE_BLOCKS: usize = 128;
+
+/// 一个设备上待下发的写请求
+pub struct RequestQueue {
+    dev: Arc<dyn BlockDevice>,
+    /// 块号到数据, 同一块后写的覆盖先写的
+    writes: BTreeMap<usize, Vec<u8>>,
+}
+
+impl RequestQueue {
+    /// 为 `dev` 新建空队列
+    pub fn new(dev: Arc<dyn BlockDevice>) -> Self {
+        Self {
+            dev,
+            writes: BTreeMap::new(),
+        }
+    }
+    /// 队列所属的设备
+    pub fn device(&self) -> &Arc<dyn BlockDevice> {
+        &self.dev
+    }
+    /// 排入一块的写请求, `data` 长度为 DISK_BLOCK_SZ
+    pub fn write(&mut self, block_id: usize, data: &[u8]) {
+        assert_eq!(data.len(), DISK_BLOCK_SZ);
+        self.writes.insert(block_id, data.to_vec());
+    }
+    /// 没有待下发的请求
+    pub fn is_empty(&self) -> bool {
+        self.writes.is_empty()
+    }
+    /// 下发所有请求, 返回实际发给设备的请求数
+    pub fn submit(&mut self) -> usize {
+        let writes = core::mem::take(&mut self.writes);
+        let mut requests = 0;
+        let mut run: Vec<u8> = Vec::new();
+        let mut run_start = 0;
+        for (block_id, data) in writes {
+            let run_len = run.len() / DISK_BLOCK_SZ;
+            if run_len > 0 && (block_id != run_start + run_len || run_len == MAX_MERGE_BLOCKS) {
+                self.dev.write_blocks(run_start, &run);
+                requests += 1;
+                run.clear();
+            }
+            if run.is_empty() {
+                run_start = block_id;
+            }
+            run.extend_from_slice(&data);
+        }
+        if !run.is_empty() {
+            self.dev.write_blocks(run_start, &run);
+            requests += 1;
+        }
+        requests
+    }
+}
+
+impl Drop for RequestQueue {
+    fn drop(&mut self) {
+        self.submit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use spin::Mutex;
+
+    /// 只记下收到的多块写请求: (起始块, 块数, 首字节)
+    struct Recorder(Mutex<Vec<(usize, usize, u8)>>);
+
+    impl BlockDevice for Recorder {
+        fn read_block(&self, _block_id: usize, _buf: &mut [u8]) {}
+        fn write_block(&self, block_id: usize, buf: &[u8]) {
+            self.write_blocks(block_id, buf)
+        }
+        fn write_blocks(&self, start_block: usize, buf: &[u8]) {
+            self.0.lock().push((start_block, buf.len() / DISK_BLOCK_SZ, buf[0]));
+        }
+    }
+
+    fn queue() -> (Arc<Recorder>, RequestQueue) {
+        let dev = Arc::new(Recorder(Mutex::new(Vec::new())));
+        (dev.clone(), RequestQueue::new(dev))
+    }
+
+    fn block(byte: u8) -> Vec<u8> {
+        vec![byte; DISK_BLOCK_SZ]
+    }
+
+    #[test]
+    fn merges_adjacent_blocks() {
+        let (dev, mut queue) = queue();
+        for id in [7, 5, 6, 9, 10] {
+            queue.write(id, &block(id as u8));
+        }
+        assert_eq!(queue.submit(), 2);
+        assert!(queue.is_empty());
+        assert_eq!(*dev.0.lock(), [(5, 3, 5), (9, 2, 9)]);
+    }
+
+    #[test]
+    fn later_write_wins() {
+        let (dev, mut queue) = queue();
+        queue.write(3, &block(1));
+        queue.write(3, &block(2));
+        assert_eq!(queue.submit(), 1);
+        assert_eq!(*dev.0.lock(), [(3, 1, 2)]);
+    }
+
+    #[test]
+    fn splits_at_max_merge_blocks() {
+        let (dev, mut queue) = queue();
+        for id in 0..2 * MAX_MERGE_BLOCKS + 1 {
+            queue.write(100 + id, &block(0));
+        }
+        assert_eq!(queue.submit(), 3);
+        assert_eq!(
+            *dev.0.lock(),
+            [
+                (100, MAX_MERGE_BLOCKS, 0),
+                (100 + MAX_MERGE_BLOCKS, MAX_MERGE_BLOCKS, 0),
+                (100 + 2 * MAX_MERGE_BLOCKS, 1, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_queue_and_drop() {
+        let (dev, mut queue) = queue();
+        assert_eq!(queue.submit(), 0);
+        queue.write(0, &block(4));
+        drop(queue);
+        assert_eq!(*dev.0.lock(), [(0, 1, 4)]);
+    }
+}
@@ -0,0 +1,9 @@
+use core::any::Any;
+/// Trait for real time clocks
+/// which keep wall-clock time across power cycles
+pub trait RtcDevice: Send + Sync + Any {
+    ///Read the current time in nanoseconds since the Unix epoch
+    fn read_time(&self) -> u64;
+    ///Set the current time in nanoseconds since the Unix epoch
+    fn set_time(&self, nsec: u64);
+}
@@ -108,23 +108,35 @@ fn easy_fs_pack() -> std::io::Result<()> {
     Ok(())
 }
 
+// 测试共用同一个镜像和全局 vfs, 只初始化一次
+#[cfg(test)]
+static TEST_FS: Once = Once::new();
+
+#[cfg(test)]
+fn test_fs_init() {
+    TEST_FS.call_once(|| {
+        let block_file = Arc::new(BlockFile(Mutex::new({
+            let f = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .open("target/fs.img")
+                .unwrap();
+            f.set_len(8192 * 512).unwrap();
+            f
+        })));
+        EasyFileSystem::create(block_file.clone(), 4096, 1);
+      //  let efs = EasyFileSystem::open(block_file.clone());
+        device::BLOCK_DEVICE.call_once(||block_file);
+        vfs::init();
+    });
+}
+
 #[test]
 fn efs_test() -> std::io::Result<()> {
     let size = mem::size_of::<easy_fs::DiskInode>();
     assert_eq!(size,128);
-    let block_file = Arc::new(BlockFile(Mutex::new({
-        let f = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open("target/fs.img")?;
-        f.set_len(8192 * 512).unwrap();
-        f
-    })));
-    EasyFileSystem::create(block_file.clone(), 4096, 1);
-  //  let efs = EasyFileSystem::open(block_file.clone());
-    device::BLOCK_DEVICE.call_once(||block_file);
-    vfs::init();
+    test_fs_init();
     let root_dentry = get_root_dentry();
     root_dentry.create("filea", DiskInodeType::File);
     root_dentry.create("fileb", DiskInodeType::File);
@@ -195,3 +207,42 @@ fn efs_test() -> std::io::Result<()> {
     random_str_test(2000 * BLOCK_SZ);
     Ok(())
 }
+
+#[test]
+fn efs_shrink_across_indirect2_group() {
+    use easy_fs::{INDIRECT1_BOUND, INODE_DIRECT_COUNT, INODE_INDIRECT1_COUNT};
+    use std::convert::TryInto;
+    use vfs_defs::Inode;
+    test_fs_init();
+    let root_dentry = get_root_dentry();
+    let den = root_dentry.create("shrink", DiskInodeType::File).unwrap();
+    let inode = den.get_inode().unwrap().downcast_arc::<EfsInode>().map_err(|_| SysError::ENOTDIR).unwrap();
+    // 占满第一个二级索引组, 再伸进第二组若干块
+    let old_blocks = INDIRECT1_BOUND + INODE_INDIRECT1_COUNT + 10;
+    let data: Vec<u8> = (0..old_blocks * BLOCK_SZ).map(|i| (i % 251) as u8).collect();
+    assert_eq!(inode.write_at(0, &data), data.len());
+    // 截到第一组中间, 第二组整组释放
+    let new_size = (INDIRECT1_BOUND + INODE_INDIRECT1_COUNT / 2) * BLOCK_SZ + BLOCK_SZ / 3;
+    inode.truncate(new_size).unwrap();
+
+    // 超级块 (0 号块) 不能被当成一级索引块清零
+    let dev = device::BLOCK_DEVICE.get().unwrap().clone();
+    let mut block = [0u8; BLOCK_SZ];
+    dev.read_block(0, &mut block);
+    let magic = u32::from_le_bytes(block[0..4].try_into().unwrap());
+    let total_blocks = u32::from_le_bytes(block[4..8].try_into().unwrap());
+    assert_eq!(magic, 0x3b800001);
+    assert_eq!(total_blocks, 4096);
+
+    // 剩下的数据完好
+    let mut read_back = vec![0u8; new_size];
+    assert_eq!(inode.read_at(0, &mut read_back), new_size);
+    assert_eq!(read_back.as_slice(), &data[..new_size]);
+    // 再截到一级索引以内, 二级索引整体释放
+    inode.truncate(INODE_DIRECT_COUNT * BLOCK_SZ).unwrap();
+    dev.read_block(0, &mut block);
+    assert_eq!(u32::from_le_bytes(block[4..8].try_into().unwrap()), 4096);
+    let mut read_back = vec![0u8; INODE_DIRECT_COUNT * BLOCK_SZ];
+    assert_eq!(inode.read_at(0, &mut read_back), read_back.len());
+    assert_eq!(read_back.as_slice(), &data[..read_back.len()]);
+}
@@ -1,10 +1,11 @@
 use crate::EasyFileSystem;
 
-use super::{get_block_cache, BlockDevice, BLOCK_SZ,EfsInode,INODE_MANAGER};
+use super::{get_block_cache, BlockDevice, BLOCK_SZ,EfsInode,INODE_MANAGER,inode_cache_sync_all};
+use system_result::SysResult;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::fmt::{Debug, Formatter, Result};
-use vfs_defs::{SuperBlock, SuperBlockInner,Inode,DiskInodeType};
+use vfs_defs::{SuperBlock, SuperBlockInner,Inode,DiskInodeType,StatFs};
 use spin::Mutex;
 
 /// Magic number for sanity check
@@ -116,8 +117,39 @@ impl SuperBlock for EfsSuperBlock{
     fn get_inner(&self) -> &SuperBlockInner {
         &self.inner
     }
- 
-
+    fn sync_fs(&self) -> SysResult<()> {
+        // 先把缓存的 inode 写进块缓存
+        inode_cache_sync_all();
+        if let Some(dev) = self.inner.dev.as_ref() {
+            buffer::block_cache_sync_dev(dev);
+        }
+        Ok(())
+    }
+    fn statfs(&self) -> SysResult<StatFs> {
+        let fs = self.fs.lock();
+        let (data_blocks, inode_blocks) = get_block_cache(0, Arc::clone(&fs.block_device))
+            .lock()
+            .read(0, |super_block: &DiskSuperBlock| {
+                (super_block.data_area_blocks as u64, super_block.inode_area_blocks as usize)
+            });
+        let inodes = (inode_blocks * (BLOCK_SZ / core::mem::size_of::<DiskInode>()))
+            .min(fs.inode_bitmap.maximum()) as u64;
+        let used_data = fs.data_bitmap.count_allocated(&fs.block_device) as u64;
+        let used_inodes = fs.inode_bitmap.count_allocated(&fs.block_device) as u64;
+        let free = data_blocks.saturating_sub(used_data);
+        Ok(StatFs {
+            f_type: EFS_MAGIC as i64,
+            f_bsize: BLOCK_SZ as i64,
+            f_blocks: data_blocks,
+            f_bfree: free,
+            f_bavail: free,
+            f_files: inodes,
+            f_ffree: inodes.saturating_sub(used_inodes),
+            f_namelen: NAME_LENGTH_LIMIT as isize,
+            f_frsize: BLOCK_SZ as isize,
+            ..Default::default()
+        })
+    }
 }
 /*
 /// Type of a disk inode
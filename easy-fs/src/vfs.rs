@@ -5,6 +5,7 @@ use super::{
 };
 use alloc::string::String;
 use alloc::sync::Arc;
+use alloc::vec;
 use alloc::vec::Vec;
 use lazy_static::*;
 use spin::{Mutex, MutexGuard};
@@ -71,6 +72,24 @@ impl Inode for EfsInode{
         }
         block_cache_sync_all();
     }
+    fn truncate(&self, size: usize) -> SysResult<()> {
+        if size > INDIRECT2_BOUND * BLOCK_SZ {
+            return Err(SysError::EFBIG);
+        }
+        let (mut inner,mut meta) = self.lock_inner();
+        let old_size = meta.size as usize;
+        if size < old_size {
+            for data_block in self.shrink_size(size as u32,&mut inner,&mut meta) {
+                self.get_super().dealloc_data(data_block);
+            }
+        } else if size > old_size {
+            // 块上可能留着以前的数据, 新增的部分要真正写 0
+            let zeros = vec![0u8; size - old_size];
+            self.write_at_with_lock(old_size, &zeros,&mut inner,&mut meta);
+        }
+        block_cache_sync_all();
+        Ok(())
+    }
     fn load_from_disk(&self) {
         self.lock_inner();
     }
@@ -609,6 +628,69 @@ impl EfsInode {
         v
     }
 
+    /// Shrink size to `new_size` and return data blocks beyond it, together
+    /// with index blocks no longer needed, which should be deallocated.
+    pub fn shrink_size(&self,new_size:u32,inner:&mut MutexGuard<InodeInner>,meta:&mut MutexGuard<InodeMetaInner>) -> Vec<u32> {
+        let old_blocks = self.data_blocks(meta) as usize;
+        let new_blocks = Self::_data_blocks(new_size) as usize;
+        meta.size = new_size;
+        let mut v: Vec<u32> = Vec::new();
+        for inner_id in new_blocks..old_blocks.min(INDIRECT1_BOUND) {
+            if inner_id < INODE_DIRECT_COUNT {
+                v.push(inner.direct[inner_id]);
+                inner.direct[inner_id] = 0;
+            } else if inner.indirect1 != 0 {
+                get_block_cache(inner.indirect1 as usize, Arc::clone(&self.get_dev()))
+                    .lock()
+                    .modify(0, |indirect1: &mut IndirectBlock| {
+                        v.push(indirect1[inner_id - INODE_DIRECT_COUNT]);
+                        indirect1[inner_id - INODE_DIRECT_COUNT] = 0;
+                    });
+            }
+        }
+        if old_blocks > INDIRECT1_BOUND && inner.indirect2 != 0 {
+            // indirect2: 按一级索引块分组释放, 整组都被截掉时才释放该一级索引块
+            let start = new_blocks.max(INDIRECT1_BOUND) - INDIRECT1_BOUND;
+            let end = old_blocks - INDIRECT1_BOUND;
+            get_block_cache(inner.indirect2 as usize, Arc::clone(&self.get_dev()))
+                .lock()
+                .modify(0, |indirect2: &mut IndirectBlock| {
+                    for group in start / INODE_INDIRECT1_COUNT..=(end - 1) / INODE_INDIRECT1_COUNT {
+                        let group_start = group * INODE_INDIRECT1_COUNT;
+                        let from = start.max(group_start) - group_start;
+                        let to = end.min(group_start + INODE_INDIRECT1_COUNT) - group_start;
+                        let indirect1 = indirect2[group];
+                        // 稀疏文件中未分配的一级索引块
+                        if indirect1 == 0 {
+                            continue;
+                        }
+                        get_block_cache(indirect1 as usize, Arc::clone(&self.get_dev()))
+                            .lock()
+                            .modify(0, |indirect1: &mut IndirectBlock| {
+                                for entry in indirect1[from..to].iter_mut() {
+                                    v.push(*entry);
+                                    *entry = 0;
+                                }
+                            });
+                        if from == 0 {
+                            v.push(indirect1);
+                            indirect2[group] = 0;
+                        }
+                    }
+                });
+        }
+        if new_blocks <= INDIRECT1_BOUND && old_blocks > INDIRECT1_BOUND {
+            v.push(inner.indirect2);
+            inner.indirect2 = 0;
+        }
+        if new_blocks <= INODE_DIRECT_COUNT && old_blocks > INODE_DIRECT_COUNT {
+            v.push(inner.indirect1);
+            inner.indirect1 = 0;
+        }
+        v.retain(|block| *block != 0);
+        v
+    }
+
     ///
     pub fn is_dir_empty(&self,inner:&mut MutexGuard<InodeInner>,meta:&mut MutexGuard<InodeMetaInner>)->bool{
         let file_count = (meta.size as usize) / DIRENT_SZ;
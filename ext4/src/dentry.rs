@@ -5,9 +5,11 @@ use alloc::string::{String,ToString};
 use system_result::{SysResult,SysError};
 use crate::superblock::Ext4Superblock;
 use crate::file::Ext4ImplFile;
+use crate::xattr;
 use ext4_rs::*;
 
 use super::Ext4Inode;
+use crate::inode::{mode_is_symlink, mode_is_device, mode_is_special, is_fast_symlink, encode_rdev, FAST_SYMLINK_MAX, EXT4_EXTENTS_FL};
 const MODULE_LEVEL:log::Level = log::Level::Debug;
 pub const EXT_MAX_BLOCKS: u32 = u32::MAX;
 pub struct Ext4Dentry{
@@ -30,7 +32,7 @@ impl Dentry for Ext4Dentry{
     fn concrete_create(self: Arc<Self>, name: &str, _type:DiskInodeType) -> SysResult<Arc<dyn Dentry>> {
         let sblock = self.get_superblock().downcast_arc::<Ext4Superblock>().map_err(|_| SysError::ENOENT)?;
         let child_dir = self.get_child(name).unwrap();
-        let path = child_dir.path();
+        let path = child_dir.fs_path();
         let child_ino;
         match _type{
             DiskInodeType::File=>{
@@ -44,8 +46,76 @@ impl Dentry for Ext4Dentry{
                 return Err(SysError::EISDIR);
             }
         }
-        let child_inode = Ext4Inode::new(InodeMeta::new(InodeMode::from_type(_type),child_ino.unwrap() as usize, sblock),);
+        let child_ino = child_ino.unwrap();
+        let inode_ref = sblock.ext4fs.get_inode_ref(child_ino);
+        let child_inode = Ext4Inode::new(InodeMeta::new(InodeMode::from_type(_type),child_ino as usize, sblock),);
         child_inode.set_type(_type);
+        child_inode.load_perm(&inode_ref.inode);
+        child_dir.set_inode(Arc::new(child_inode));
+        *child_dir.get_state() = DentryState::Valid;
+        Ok(child_dir)
+    }
+    fn concrete_symlink(self: Arc<Self>, name: &str, target: &str) -> SysResult<Arc<dyn Dentry>> {
+        let sblock = self.get_superblock().downcast_arc::<Ext4Superblock>().map_err(|_| SysError::ENOENT)?;
+        let child_dir = self.get_child(name).unwrap();
+        let path = child_dir.fs_path();
+        let child_ino = sblock.ext4fs.generic_open(path.as_str(), &mut 2, true, InodeFileType::S_IFLNK.bits(), &mut 0)
+            .map_err(|_| SysError::ENOSPC)?;
+        let mut inode_ref = sblock.ext4fs.get_inode_ref(child_ino);
+        inode_ref.inode.mode = InodeFileType::S_IFLNK.bits() | 0o777;
+        if target.len() < FAST_SYMLINK_MAX {
+            // fast symlink: 内容直接写进 i_block, 不再是 extent 树
+            let mut raw = [0u8; FAST_SYMLINK_MAX];
+            raw[..target.len()].copy_from_slice(target.as_bytes());
+            let mut block = [0u32; FAST_SYMLINK_MAX / 4];
+            for (i, chunk) in raw.chunks_exact(4).enumerate() {
+                block[i] = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+            }
+            inode_ref.inode.block = block;
+            inode_ref.inode.flags &= !EXT4_EXTENTS_FL;
+            inode_ref.inode.set_size(target.len() as u64);
+            sblock.ext4fs.write_back_inode(&mut inode_ref);
+        } else {
+            sblock.ext4fs.write_back_inode(&mut inode_ref);
+            if let Err(e) = sblock.ext4fs.ext4_file_write(child_ino as u64, 0, target.as_bytes()) {
+                // 内容没写全的链接不能留下
+                let _ = sblock.ext4fs.file_remove(path.as_str());
+                return Err(match e.error() {
+                    Errno::ENOSPC => SysError::ENOSPC,
+                    _ => SysError::EIO,
+                });
+            }
+            inode_ref = sblock.ext4fs.get_inode_ref(child_ino);
+        }
+        let mode = InodeMode::LINK | InodeMode::OWNER_MASK | InodeMode::GROUP_MASK | InodeMode::OTHER_MASK;
+        let child_inode = Ext4Inode::new(InodeMeta::new(mode, child_ino as usize, sblock));
+        child_inode.set_type(DiskInodeType::File);
+        child_inode.load_perm(&inode_ref.inode);
+        child_dir.set_inode(Arc::new(child_inode));
+        *child_dir.get_state() = DentryState::Valid;
+        Ok(child_dir)
+    }
+    fn concrete_mknod(self: Arc<Self>, name: &str, mode: InodeMode, rdev: u64) -> SysResult<Arc<dyn Dentry>> {
+        let sblock = self.get_superblock().downcast_arc::<Ext4Superblock>().map_err(|_| SysError::ENOENT)?;
+        let child_dir = self.get_child(name).unwrap();
+        let path = child_dir.fs_path();
+        let file_type = mode & InodeMode::TYPE_MASK;
+        let child_ino = sblock.ext4fs.generic_open(path.as_str(), &mut 2, true, file_type.bits() as u16, &mut 0)
+            .map_err(|_| SysError::ENOSPC)?;
+        let mut inode_ref = sblock.ext4fs.get_inode_ref(child_ino);
+        inode_ref.inode.mode = mode.bits() as u16;
+        // 特殊文件没有数据, i_block 只用来存设备号
+        let mut block = [0u32; FAST_SYMLINK_MAX / 4];
+        if mode_is_device(inode_ref.inode.mode) {
+            block[..2].copy_from_slice(&encode_rdev(rdev));
+        }
+        inode_ref.inode.block = block;
+        inode_ref.inode.flags &= !EXT4_EXTENTS_FL;
+        inode_ref.inode.set_size(0);
+        sblock.ext4fs.write_back_inode(&mut inode_ref);
+        let child_inode = Ext4Inode::new(InodeMeta::new(file_type, child_ino as usize, sblock));
+        child_inode.set_type(DiskInodeType::File);
+        child_inode.load_perm(&inode_ref.inode);
         child_dir.set_inode(Arc::new(child_inode));
         *child_dir.get_state() = DentryState::Valid;
         Ok(child_dir)
@@ -95,7 +165,7 @@ impl Dentry for Ext4Dentry{
         let sblock = self.get_superblock().downcast_arc::<Ext4Superblock>().map_err(|_| SysError::ENOENT)?;
         let mut ino = self.get_inode().unwrap().get_meta().ino as u32;
         let child = self.get_child(name).unwrap();
-        let path = child.path();
+        let path = child.fs_path();
         let mut r;
         r = sblock.ext4fs.ext4_dir_open(path.as_str());
         if let Err(_e) = r {
@@ -109,7 +179,19 @@ impl Dentry for Ext4Dentry{
             }
         }            
         let inode_ref = sblock.ext4fs.get_inode_ref(r.unwrap());
-        if inode_ref.inode.is_file(){             
+        if mode_is_symlink(inode_ref.inode.mode()){
+            let mode = InodeMode::LINK | InodeMode::OWNER_MASK | InodeMode::GROUP_MASK | InodeMode::OTHER_MASK;
+            let child_inode = Arc::new(Ext4Inode::new(InodeMeta::new(mode,r.unwrap() as usize, sblock)));
+            child_inode.set_type(DiskInodeType::File);
+            child.set_inode(child_inode);
+        }
+        else if mode_is_special(inode_ref.inode.mode()){
+            let mode = InodeMode::from_bits_truncate(inode_ref.inode.mode() as u32) & InodeMode::TYPE_MASK;
+            let child_inode = Arc::new(Ext4Inode::new(InodeMeta::new(mode,r.unwrap() as usize, sblock)));
+            child_inode.set_type(DiskInodeType::File);
+            child.set_inode(child_inode);
+        }
+        else if inode_ref.inode.is_file(){             
             let child_inode = Arc::new(Ext4Inode::new(InodeMeta::new(InodeMode::FILE,r.unwrap() as usize, sblock)));
             child_inode.set_type(DiskInodeType::File);
             child.set_inode(child_inode);
@@ -119,6 +201,7 @@ impl Dentry for Ext4Dentry{
             child_inode.set_type(DiskInodeType::Directory);
             child.set_inode(child_inode);            
         }
+        child.get_inode()?.downcast_arc::<Ext4Inode>().map_err(|_| SysError::ENOENT)?.load_perm(&inode_ref.inode);
         Ok(child)
 
     }
@@ -133,12 +216,18 @@ impl Dentry for Ext4Dentry{
         let inode_num = self.get_inode()?.downcast_arc::<Ext4Inode>().map_err(|_| SysError::ENOENT)?.get_meta().ino as u32;
         let child_ino = old.get_inode()?.downcast_arc::<Ext4Inode>().map_err(|_| SysError::ENOENT)?.get_meta().ino as u32;
         let mut inode_ref = sblock.ext4fs.get_inode_ref(child_ino);
-        if inode_ref.inode.is_file(){ 
+        let is_link = mode_is_symlink(inode_ref.inode.mode());
+        if inode_ref.inode.is_file() || is_link || mode_is_special(inode_ref.inode.mode()) { 
             let child_link_cnt = inode_ref.inode.links_count();
             if child_link_cnt == 1 {
+                // 属性块不在 extent 树里, 要单独释放; 这会改写磁盘上的 inode, 之后重新读
+                xattr::release(&sblock, child_ino)?;
+                inode_ref = sblock.ext4fs.get_inode_ref(child_ino);
                 let old_size = inode_ref.inode.size();
+                // fast symlink 的 i_block 里是链接内容, 没有可释放的块
+                let fast = is_link && is_fast_symlink(inode_ref.inode.flags, old_size);
         
-                if old_size != 0 {
+                if old_size != 0 && !fast {
                     let block_size = BLOCK_SIZE as u64;
                     let new_blocks_cnt = ((0 + block_size - 1) / block_size) as u32;
                     let old_blocks_cnt = ((old_size + block_size - 1) / block_size) as u32;
@@ -167,6 +256,8 @@ impl Dentry for Ext4Dentry{
             }
         }
         else{
+            // 目录的属性块同样要在删除前释放
+            xattr::release(&sblock, child_ino)?;
             let _ = sblock.ext4fs.dir_remove(inode_num, old.get_name_str());
         }
         self.get_inner().children.lock().remove(&old.get_name_string());
@@ -187,9 +278,9 @@ impl Dentry for Ext4Dentry{
             match new_type {
                 DiskInodeType::Directory => {
                     let parent = new.get_father().unwrap().get_inode()?.get_meta().ino;
-                    let _ = sblock.ext4fs.dir_remove(parent as u32, new.path().as_str());
+                    let _ = sblock.ext4fs.dir_remove(parent as u32, new.fs_path().as_str());
                 },
-                DiskInodeType::File => {let _ = sblock.ext4fs.file_remove(new.path().as_str());},
+                DiskInodeType::File => {let _ = sblock.ext4fs.file_remove(new.fs_path().as_str());},
                 _ => todo!(),
             };
         }
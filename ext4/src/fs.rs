@@ -32,18 +32,9 @@ impl FileSystemType for Ext4ImplFsType{
         let root_ino= 2;
         let root_inode = Arc::new(Ext4Inode::new(InodeMeta::new(InodeMode::DIR,root_ino, superblock.clone())));
         root_inode.set_type(vfs_defs::DiskInodeType::Directory);
-        let root_dentry;
-        let abs_mount_path;
-        let mut path = String::new();
-        if parent.is_none(){
-            root_dentry = Arc::new(Ext4Dentry::new(DentryInner::new(name.to_string(), superblock.clone(),None)));
-            abs_mount_path = "/";
-        }
-        else{
-            path = parent.as_ref().unwrap().path()+name;
-            abs_mount_path = path.as_str();
-            root_dentry = Arc::new(Ext4Dentry::new(DentryInner::new(name.to_string(), superblock.clone(),Some(parent.unwrap()))));
-        }
+        root_inode.load_perm(&superblock.ext4fs.get_inode_ref(root_ino as u32).inode);
+        let root_dentry = Arc::new(Ext4Dentry::new(DentryInner::new(name.to_string(), superblock.clone(),parent)));
+        let abs_mount_path = root_dentry.path();
         log_debug!("abs_m_path:{}",abs_mount_path);
         root_dentry.set_inode(root_inode);
         superblock.set_root_dentry(root_dentry.clone());
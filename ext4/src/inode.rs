@@ -1,9 +1,58 @@
 use ext4_rs::Ext4Error;
-use vfs_defs::{Inode,InodeMeta, Kstat};
+use alloc::string::String;
+use alloc::vec::Vec;
+use vfs_defs::{Inode,InodeMeta,InodeMode,Kstat,XattrFlags,makedev,major,minor};
 use super::Ext4Superblock;
-use system_result::SysError;
-use ext4_rs::Errno;
+use system_result::{SysError,SysResult};
+use ext4_rs::{Errno,InodeFileType,BLOCK_SIZE};
+use alloc::vec;
+use crate::dentry::EXT_MAX_BLOCKS;
+use crate::xattr;
 const MODULE_LEVEL:log::Level = log::Level::Trace;
+/// 短于它的符号链接直接存放在 i_block 中(fast symlink)
+pub const FAST_SYMLINK_MAX: usize = 60;
+/// i_flags 中表示 i_block 是 extent 树的位
+pub const EXT4_EXTENTS_FL: u32 = 0x80000;
+const S_IFMT: u16 = 0xF000;
+/// 扩大文件时每次写入的 0 的长度
+const ZERO_CHUNK: usize = 64 * 1024;
+
+/// 磁盘 inode 的 mode 是否为符号链接
+pub fn mode_is_symlink(mode: u16) -> bool {
+    mode & S_IFMT == InodeFileType::S_IFLNK.bits()
+}
+/// 符号链接的内容是否直接存放在 i_block 中
+pub fn is_fast_symlink(flags: u32, size: u64) -> bool {
+    flags & EXT4_EXTENTS_FL == 0 && (size as usize) < FAST_SYMLINK_MAX
+}
+/// 磁盘 inode 的 mode 是否为字符/块设备
+pub fn mode_is_device(mode: u16) -> bool {
+    let file_type = InodeMode::from_bits_truncate((mode & S_IFMT) as u32);
+    file_type == InodeMode::CHAR || file_type == InodeMode::BLOCK
+}
+/// FIFO、套接字和设备节点都没有数据块
+pub fn mode_is_special(mode: u16) -> bool {
+    let file_type = InodeMode::from_bits_truncate((mode & S_IFMT) as u32);
+    file_type == InodeMode::FIFO || file_type == InodeMode::SOCKET || mode_is_device(mode)
+}
+/// 设备号存在 i_block 里: 主次号都小于 256 时用旧格式放在 i_block[0], 否则放在 i_block[1]
+pub fn encode_rdev(rdev: u64) -> [u32; 2] {
+    let (major, minor) = (major(rdev), minor(rdev));
+    if major < 256 && minor < 256 {
+        [(major << 8) | minor, 0]
+    } else {
+        [0, (minor & 0xff) | (major << 8) | ((minor & !0xff) << 12)]
+    }
+}
+/// [`encode_rdev`] 的逆过程
+pub fn decode_rdev(block: &[u32]) -> u64 {
+    if block[0] != 0 {
+        makedev((block[0] >> 8) & 0xff, block[0] & 0xff)
+    } else {
+        let dev = block[1];
+        makedev((dev & 0xfff00) >> 8, (dev & 0xff) | ((dev >> 12) & 0xfff00))
+    }
+}
 pub struct Ext4Inode{
     meta:InodeMeta,
 }
@@ -14,6 +63,16 @@ impl Ext4Inode{
             meta,
         }
     }
+    /// 权限检查用的 mode 和属主以磁盘 inode 为准
+    pub fn load_perm(&self, disk: &ext4_rs::Ext4Inode) {
+        let mut inner = self.meta.inner.lock();
+        inner.mode = InodeMode::from_bits_truncate(disk.mode as u32);
+        inner.uid = disk.uid as u32 | (disk.osd2.l_i_uid_high as u32) << 16;
+        inner.gid = disk.gid as u32 | (disk.osd2.l_i_gid_high as u32) << 16;
+        if mode_is_device(disk.mode) {
+            inner.rdev = decode_rdev(&disk.block);
+        }
+    }
 }
 
 
@@ -42,7 +101,7 @@ impl Inode for Ext4Inode{
                 st_nlink: attr.nlink,
                 st_uid: attr.uid,
                 st_gid: attr.gid,
-                st_rdev: attr.rdev as u64,
+                st_rdev: inner.rdev,
                 __pad: 0,
                 st_size: attr.size,
                 st_blksize: attr.blksize,
@@ -64,10 +123,98 @@ impl Inode for Ext4Inode{
     }
     fn clear(&self) {
         
+    }
+    fn set_perm(&self, perm: InodeMode) -> SysResult<()> {
+        let sb = self.get_meta().superblock.upgrade().unwrap().downcast_arc::<Ext4Superblock>().map_err(|_| SysError::ENOENT)?;
+        let mut inoderef = sb.ext4fs.get_inode_ref(self.meta.ino as u32);
+        inoderef.inode.mode = (inoderef.inode.mode & S_IFMT) | (perm.bits() as u16 & !S_IFMT);
+        sb.ext4fs.write_back_inode(&mut inoderef);
+        self.load_perm(&inoderef.inode);
+        Ok(())
+    }
+    fn set_owner(&self, uid: u32, gid: u32) -> SysResult<()> {
+        let sb = self.get_meta().superblock.upgrade().unwrap().downcast_arc::<Ext4Superblock>().map_err(|_| SysError::ENOENT)?;
+        let mut inoderef = sb.ext4fs.get_inode_ref(self.meta.ino as u32);
+        // 高 16 位放在 osd2 里
+        inoderef.inode.uid = uid as u16;
+        inoderef.inode.gid = gid as u16;
+        inoderef.inode.osd2.l_i_uid_high = (uid >> 16) as u16;
+        inoderef.inode.osd2.l_i_gid_high = (gid >> 16) as u16;
+        sb.ext4fs.write_back_inode(&mut inoderef);
+        self.load_perm(&inoderef.inode);
+        Ok(())
+    }
+    fn read_link(&self) -> SysResult<String> {
+        let sb = self.get_meta().superblock.upgrade().unwrap().downcast_arc::<Ext4Superblock>().map_err(|_| SysError::ENOENT)?;
+        let ino = self.meta.ino as u32;
+        let mut inoderef = sb.ext4fs.get_inode_ref(ino);
+        if !mode_is_symlink(inoderef.inode.mode()) {
+            return Err(SysError::EINVAL);
+        }
+        let size = inoderef.inode.size();
+        let data = if is_fast_symlink(inoderef.inode.flags, size) {
+            let block = inoderef.inode.block;
+            let mut raw = Vec::with_capacity(FAST_SYMLINK_MAX);
+            for word in block.iter() {
+                raw.extend_from_slice(&word.to_le_bytes());
+            }
+            raw.truncate(size as usize);
+            raw
+        } else {
+            sb.ext4fs.ext4_file_read(ino as u64, size as u32, 0).map_err(|_| SysError::EIO)?
+        };
+        String::from_utf8(data).map_err(|_| SysError::EINVAL)
+    }
+    fn truncate(&self, size: usize) -> SysResult<()> {
+        let sb = self.get_meta().superblock.upgrade().unwrap().downcast_arc::<Ext4Superblock>().map_err(|_| SysError::ENOENT)?;
+        let ino = self.meta.ino as u32;
+        let old_size = sb.ext4fs.get_inode_ref(ino).inode.size() as usize;
+        if size < old_size {
+            // 保留下来的最后一块中超出新大小的部分先清零, 以后再变大时读到的才是 0
+            let tail_end = old_size.min((size + BLOCK_SIZE - 1) / BLOCK_SIZE * BLOCK_SIZE);
+            if tail_end > size {
+                let zeros = vec![0u8; tail_end - size];
+                sb.ext4fs.ext4_file_write(ino as u64, size as i64, &zeros).map_err(|_| SysError::EIO)?;
+            }
+            let mut inode_ref = sb.ext4fs.get_inode_ref(ino);
+            let new_blocks = ((size + BLOCK_SIZE - 1) / BLOCK_SIZE) as u32;
+            let old_blocks = ((old_size + BLOCK_SIZE - 1) / BLOCK_SIZE) as u32;
+            if new_blocks < old_blocks {
+                sb.ext4fs.extent_remove_space(&mut inode_ref, new_blocks, EXT_MAX_BLOCKS).map_err(|_| SysError::EIO)?;
+            }
+            inode_ref.inode.set_size(size as u64);
+            sb.ext4fs.write_back_inode(&mut inode_ref);
+        } else if size > old_size {
+            // 不留空洞, 直接写 0 把块分配出来
+            let zeros = vec![0u8; ZERO_CHUNK.min(size - old_size)];
+            let mut pos = old_size;
+            while pos < size {
+                let len = zeros.len().min(size - pos);
+                sb.ext4fs.ext4_file_write(ino as u64, pos as i64, &zeros[..len]).map_err(|_| SysError::EIO)?;
+                pos += len;
+            }
+        }
+        Ok(())
     }
     fn get_size(&self) -> u32 {
         let sb = self.get_meta().superblock.upgrade().unwrap().downcast_arc::<Ext4Superblock>().map_err(|_| SysError::ENOENT).unwrap();
         let inoderef = sb.ext4fs.get_inode_ref(self.meta.ino as u32);
         inoderef.inode.size
     }
+    fn get_xattr(&self, name: &str) -> SysResult<Vec<u8>> {
+        let sb = self.get_meta().superblock.upgrade().unwrap().downcast_arc::<Ext4Superblock>().map_err(|_| SysError::ENOENT)?;
+        xattr::get(&sb, self.meta.ino as u32, name)
+    }
+    fn set_xattr(&self, name: &str, value: &[u8], flags: XattrFlags) -> SysResult<()> {
+        let sb = self.get_meta().superblock.upgrade().unwrap().downcast_arc::<Ext4Superblock>().map_err(|_| SysError::ENOENT)?;
+        xattr::set(&sb, self.meta.ino as u32, name, Some(value), flags)
+    }
+    fn list_xattr(&self) -> SysResult<Vec<String>> {
+        let sb = self.get_meta().superblock.upgrade().unwrap().downcast_arc::<Ext4Superblock>().map_err(|_| SysError::ENOENT)?;
+        xattr::list(&sb, self.meta.ino as u32)
+    }
+    fn remove_xattr(&self, name: &str) -> SysResult<()> {
+        let sb = self.get_meta().superblock.upgrade().unwrap().downcast_arc::<Ext4Superblock>().map_err(|_| SysError::ENOENT)?;
+        xattr::set(&sb, self.meta.ino as u32, name, None, XattrFlags::empty())
+    }
 }
\ No newline at end of file
@@ -10,6 +10,7 @@ mod block;
 mod superblock;
 mod inode;
 mod file;
+mod xattr;
 
 pub use block::Ext4Disk;
 pub use inode::Ext4Inode;
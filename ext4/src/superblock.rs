@@ -1,18 +1,32 @@
-use vfs_defs::{SuperBlock,SuperBlockInner,Dentry};
+use vfs_defs::{SuperBlock,SuperBlockInner,Dentry,StatFs};
 use alloc::sync::Arc;
-use ext4_rs::Ext4;
+use ext4_rs::{BlockDevice,Ext4};
 use super::Ext4Disk;
+use buffer::block_cache_sync_dev;
+use system_result::SysResult;
+use spin::Mutex;
+
+/// ext4 的魔数, 也是 statfs 的 f_type
+const EXT4_SUPER_MAGIC: i64 = 0xef53;
+/// 磁盘超级块在设备上的偏移
+const SUPERBLOCK_OFFSET: usize = 1024;
+/// 超级块 feature_incompat 中的 64 位块号标志
+const INCOMPAT_64BIT: u32 = 0x80;
 
 pub struct Ext4Superblock{
     inner:SuperBlockInner,
+    pub(crate) disk:Arc<Ext4Disk>,
+    /// 读写扩展属性时持有, 见 [`crate::xattr`]
+    pub(crate) xattr_lock:Mutex<()>,
     pub ext4fs:Ext4
 }
 
 impl Ext4Superblock{
     pub fn new(inner:SuperBlockInner)->Self{
         let dev = inner.dev.as_ref().cloned().unwrap();
-        let ext4fs = Ext4::open(Arc::new(Ext4Disk::new(dev)));
-        Self { inner, ext4fs }
+        let disk = Arc::new(Ext4Disk::new(dev));
+        let ext4fs = Ext4::open(disk.clone());
+        Self { inner, disk, xattr_lock: Mutex::new(()), ext4fs }
     }
 
 }
@@ -21,4 +35,39 @@ impl SuperBlock for Ext4Superblock {
     fn get_inner(&self) -> &SuperBlockInner {
         &self.inner
     }
+    fn sync_fs(&self) -> SysResult<()> {
+        // ext4_rs 不缓存数据, 所有写入都落在块缓存里
+        if let Some(dev) = self.inner.dev.as_ref() {
+            block_cache_sync_dev(dev);
+        }
+        Ok(())
+    }
+    fn statfs(&self) -> SysResult<StatFs> {
+        // ext4_rs 分配和释放时会把计数写回磁盘超级块, 这里直接读盘上的值
+        let sb = self.disk.read_offset(SUPERBLOCK_OFFSET);
+        let le32 = |off: usize| u32::from_le_bytes(sb[off..off + 4].try_into().unwrap());
+        let wide = le32(0x60) & INCOMPAT_64BIT != 0;
+        let count = |lo: usize, hi: usize| {
+            let hi = if wide { le32(hi) as u64 } else { 0 };
+            hi << 32 | le32(lo) as u64
+        };
+        let blocks = count(0x04, 0x150);
+        let reserved = count(0x08, 0x154);
+        let free = count(0x0c, 0x158);
+        let bsize = 1024i64 << le32(0x18);
+        let uuid: [i32; 4] = core::array::from_fn(|i| le32(0x68 + i * 4) as i32);
+        Ok(StatFs {
+            f_type: EXT4_SUPER_MAGIC,
+            f_bsize: bsize,
+            f_blocks: blocks,
+            f_bfree: free,
+            f_bavail: free.saturating_sub(reserved),
+            f_files: le32(0x00) as u64,
+            f_ffree: le32(0x10) as u64,
+            f_fsid: [uuid[0] ^ uuid[2], uuid[1] ^ uuid[3]],
+            f_namelen: 255,
+            f_frsize: bsize as isize,
+            ..Default::default()
+        })
+    }
 }
\ No newline at end of file
@@ -0,0 +1,424 @@
+//! ext4 的扩展属性
+//!
+//! 属性放在两个地方: inode 中 i_extra_isize 之后到 inode 末尾的空间, 和 i_file_acl 指向的属性块.
+//! ext4_rs 不认识它们, 这里直接读写磁盘上的 inode 和属性块. 修改时把两处的属性全部读出来,
+//! 改完后重新排布: 按顺序尽量放进 inode, 放不下的放进属性块. 开启 metadata_csum 时
+//! 重新计算 inode 和属性块的校验和. 值放在单独 inode 里的属性 (ea_inode) 不支持.
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use ext4_rs::BlockDevice;
+use system_result::{SysError, SysResult};
+use vfs_defs::XattrFlags;
+
+use super::{Ext4Disk, Ext4Superblock};
+
+/// inode 内属性区和属性块的魔数
+const XATTR_MAGIC: u32 = 0xea02_0000;
+/// 旧格式 inode 的大小, i_extra_isize 从这里算起
+const GOOD_OLD_INODE_SIZE: usize = 128;
+/// 属性块头的大小
+const BLOCK_HEADER_SIZE: usize = 32;
+/// 属性项中名字之前的部分
+const ENTRY_HEADER_SIZE: usize = 16;
+const SUPERBLOCK_OFFSET: usize = 1024;
+const INCOMPAT_64BIT: u32 = 0x80;
+const INCOMPAT_CSUM_SEED: u32 = 0x2000;
+const RO_COMPAT_METADATA_CSUM: u32 = 0x400;
+
+/// 磁盘上的名字前缀编号. posix ACL 两项整个名字就是前缀, 必须排在 system. 之前
+const PREFIXES: [(u8, &str); 6] = [
+    (2, "system.posix_acl_access"),
+    (3, "system.posix_acl_default"),
+    (1, "user."),
+    (4, "trusted."),
+    (6, "security."),
+    (7, "system."),
+];
+
+/// 一个属性, 名字不含前缀
+#[derive(Clone)]
+struct Entry {
+    index: u8,
+    name: Vec<u8>,
+    value: Vec<u8>,
+}
+
+impl Entry {
+    fn full_name(&self) -> Option<String> {
+        let (_, prefix) = PREFIXES.iter().find(|(index, _)| *index == self.index)?;
+        let mut name = String::from(*prefix);
+        name.push_str(core::str::from_utf8(&self.name).ok()?);
+        Some(name)
+    }
+    /// 属性项占的空间, 按 4 字节对齐
+    fn entry_size(&self) -> usize {
+        (ENTRY_HEADER_SIZE + self.name.len() + 3) & !3
+    }
+    fn value_size(&self) -> usize {
+        (self.value.len() + 3) & !3
+    }
+    /// 与 Linux 的 ext4_xattr_hash_entry 相同
+    fn hash(&self) -> u32 {
+        let mut hash = 0u32;
+        for &c in self.name.iter() {
+            hash = (hash << 5) ^ (hash >> 27) ^ c as u32;
+        }
+        let mut padded = self.value.clone();
+        padded.resize(self.value_size(), 0);
+        for word in padded.chunks(4) {
+            hash = (hash << 16) ^ (hash >> 16) ^ le32(word, 0);
+        }
+        hash
+    }
+}
+
+/// 把带前缀的名字拆成前缀编号和剩下的部分
+fn split_name(name: &str) -> SysResult<(u8, &[u8])> {
+    let (index, prefix) = PREFIXES
+        .iter()
+        .find(|(index, prefix)| match index {
+            2 | 3 => name == *prefix,
+            _ => name.starts_with(prefix),
+        })
+        .ok_or(SysError::EOPNOTSUPP)?;
+    let rest = &name.as_bytes()[prefix.len()..];
+    if rest.len() > u8::MAX as usize {
+        return Err(SysError::ERANGE);
+    }
+    Ok((*index, rest))
+}
+
+fn le16(buf: &[u8], off: usize) -> u32 {
+    u16::from_le_bytes([buf[off], buf[off + 1]]) as u32
+}
+
+fn le32(buf: &[u8], off: usize) -> u32 {
+    u32::from_le_bytes(buf[off..off + 4].try_into().unwrap())
+}
+
+fn put16(buf: &mut [u8], off: usize, v: u32) {
+    buf[off..off + 2].copy_from_slice(&(v as u16).to_le_bytes());
+}
+
+fn put32(buf: &mut [u8], off: usize, v: u32) {
+    buf[off..off + 4].copy_from_slice(&v.to_le_bytes());
+}
+
+/// 不做首尾取反的 crc32c, 与 Linux 的 ext4_chksum 相同
+fn crc32c(mut crc: u32, data: &[u8]) -> u32 {
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0x82f6_3b78 & (crc & 1).wrapping_neg());
+        }
+    }
+    crc
+}
+
+/// 定位 inode 和计算校验和要用到的超级块字段
+struct Geometry {
+    block_size: usize,
+    inode_size: usize,
+    inodes_per_group: u32,
+    desc_size: usize,
+    /// 组描述符表所在的块
+    gdt_block: u64,
+    /// 没有开启 metadata_csum 时为 None
+    csum_seed: Option<u32>,
+}
+
+impl Geometry {
+    fn read(disk: &Ext4Disk) -> Self {
+        let sb = disk.read_offset(SUPERBLOCK_OFFSET);
+        let incompat = le32(&sb, 0x60);
+        let block_size = 1024usize << le32(&sb, 0x18);
+        let csum_seed = (le32(&sb, 0x64) & RO_COMPAT_METADATA_CSUM != 0).then(|| {
+            if incompat & INCOMPAT_CSUM_SEED != 0 {
+                le32(&sb, 0x270)
+            } else {
+                crc32c(!0, &sb[0x68..0x78])
+            }
+        });
+        Self {
+            block_size,
+            inode_size: le16(&sb, 0x58) as usize,
+            inodes_per_group: le32(&sb, 0x28),
+            desc_size: if incompat & INCOMPAT_64BIT != 0 { le16(&sb, 0xfe) as usize } else { 32 },
+            gdt_block: le32(&sb, 0x14) as u64 + 1,
+            csum_seed,
+        }
+    }
+    /// inode 在设备上的字节偏移
+    fn inode_pos(&self, disk: &Ext4Disk, ino: u32) -> usize {
+        let group = ((ino - 1) / self.inodes_per_group) as usize;
+        let index = ((ino - 1) % self.inodes_per_group) as usize;
+        let desc = disk.read_offset(self.gdt_block as usize * self.block_size + group * self.desc_size);
+        let mut table = le32(&desc, 0x08) as u64;
+        if self.desc_size >= 64 {
+            table |= (le32(&desc, 0x28) as u64) << 32;
+        }
+        table as usize * self.block_size + index * self.inode_size
+    }
+    /// inode 内属性区的起点 (魔数的位置), 没有这块空间时为 None
+    fn ibody_start(&self, raw: &[u8]) -> Option<usize> {
+        if self.inode_size <= GOOD_OLD_INODE_SIZE {
+            return None;
+        }
+        let start = GOOD_OLD_INODE_SIZE + le16(raw, 0x80) as usize;
+        (start + 4 + 4 <= self.inode_size).then_some(start)
+    }
+    fn inode_checksum(&self, seed: u32, ino: u32, raw: &mut [u8]) {
+        let has_hi = self.inode_size > GOOD_OLD_INODE_SIZE && le16(raw, 0x80) >= 4;
+        put16(raw, 0x7c, 0);
+        if has_hi {
+            put16(raw, 0x82, 0);
+        }
+        let seed = crc32c(crc32c(seed, &ino.to_le_bytes()), &raw[0x64..0x68]);
+        let csum = crc32c(seed, raw);
+        put16(raw, 0x7c, csum & 0xffff);
+        if has_hi {
+            put16(raw, 0x82, csum >> 16);
+        }
+    }
+    fn block_checksum(&self, seed: u32, block: u64, buf: &mut [u8]) {
+        put32(buf, 0x10, 0);
+        let csum = crc32c(crc32c(seed, &block.to_le_bytes()), buf);
+        put32(buf, 0x10, csum);
+    }
+}
+
+/// 从 `start` 起解析属性项, 值的偏移相对于 `value_base`
+fn parse_entries(buf: &[u8], start: usize, value_base: usize) -> SysResult<Vec<Entry>> {
+    let mut entries = Vec::new();
+    let mut pos = start;
+    while pos + 4 <= buf.len() && le32(buf, pos) != 0 {
+        if pos + ENTRY_HEADER_SIZE > buf.len() {
+            return Err(SysError::EIO);
+        }
+        let name_len = buf[pos] as usize;
+        let value_offs = value_base + le16(buf, pos + 2) as usize;
+        let value_size = le32(buf, pos + 8) as usize;
+        if le32(buf, pos + 4) != 0 {
+            return Err(SysError::EOPNOTSUPP);
+        }
+        let name_end = pos + ENTRY_HEADER_SIZE + name_len;
+        if name_end > buf.len() || value_offs + value_size > buf.len() {
+            return Err(SysError::EIO);
+        }
+        let entry = Entry {
+            index: buf[pos + 1],
+            name: buf[pos + ENTRY_HEADER_SIZE..name_end].to_vec(),
+            value: buf[value_offs..value_offs + value_size].to_vec(),
+        };
+        pos += entry.entry_size();
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// 属性项和值在 `[start, end)` 里需要的空间, 含结尾的 4 字节 0
+fn space_needed(entries: &[Entry]) -> usize {
+    entries.iter().map(|e| e.entry_size() + e.value_size()).sum::<usize>() + 4
+}
+
+/// 把属性项从 `start` 往后写, 值从 `end` 往前放, 调用者保证放得下
+fn write_entries(buf: &mut [u8], start: usize, end: usize, value_base: usize, entries: &[Entry]) {
+    buf[start..end].fill(0);
+    let mut pos = start;
+    let mut value_end = end;
+    for entry in entries {
+        value_end -= entry.value_size();
+        buf[pos] = entry.name.len() as u8;
+        buf[pos + 1] = entry.index;
+        put16(buf, pos + 2, if entry.value.is_empty() { 0 } else { (value_end - value_base) as u32 });
+        put32(buf, pos + 8, entry.value.len() as u32);
+        put32(buf, pos + 12, entry.hash());
+        buf[pos + ENTRY_HEADER_SIZE..pos + ENTRY_HEADER_SIZE + entry.name.len()].copy_from_slice(&entry.name);
+        buf[value_end..value_end + entry.value.len()].copy_from_slice(&entry.value);
+        pos += entry.entry_size();
+    }
+}
+
+/// 一个 inode 上的全部属性和它们的出处
+struct Xattrs {
+    geo: Geometry,
+    pos: usize,
+    raw: Vec<u8>,
+    /// i_file_acl, 0 表示没有属性块
+    block: u64,
+    entries: Vec<Entry>,
+}
+
+impl Xattrs {
+    fn read(disk: &Ext4Disk, ino: u32) -> SysResult<Self> {
+        let geo = Geometry::read(disk);
+        let pos = geo.inode_pos(disk, ino);
+        let mut raw = disk.read_offset(pos);
+        raw.truncate(geo.inode_size);
+        let mut entries = Vec::new();
+        if let Some(start) = geo.ibody_start(&raw) {
+            if le32(&raw, start) == XATTR_MAGIC {
+                entries = parse_entries(&raw, start + 4, start + 4)?;
+            }
+        }
+        let block = le32(&raw, 0x68) as u64 | (le16(&raw, 0x76) as u64) << 32;
+        if block != 0 {
+            let buf = read_block(disk, &geo, block);
+            if le32(&buf, 0) != XATTR_MAGIC {
+                return Err(SysError::EIO);
+            }
+            entries.extend(parse_entries(&buf, BLOCK_HEADER_SIZE, 0)?);
+        }
+        Ok(Self { geo, pos, raw, block, entries })
+    }
+    fn find(&self, index: u8, name: &[u8]) -> Option<usize> {
+        self.entries.iter().position(|e| e.index == index && e.name == name)
+    }
+}
+
+fn read_block(disk: &Ext4Disk, geo: &Geometry, block: u64) -> Vec<u8> {
+    let mut buf = disk.read_offset(block as usize * geo.block_size);
+    buf.truncate(geo.block_size);
+    buf
+}
+
+/// 读属性 `name` 的值
+pub fn get(sb: &Ext4Superblock, ino: u32, name: &str) -> SysResult<Vec<u8>> {
+    let (index, rest) = split_name(name)?;
+    let _guard = sb.xattr_lock.lock();
+    let xattrs = Xattrs::read(&sb.disk, ino)?;
+    let i = xattrs.find(index, rest).ok_or(SysError::ENODATA)?;
+    Ok(xattrs.entries[i].value.clone())
+}
+
+/// 所有属性的名字, 不认识前缀编号的属性不列出
+pub fn list(sb: &Ext4Superblock, ino: u32) -> SysResult<Vec<String>> {
+    let _guard = sb.xattr_lock.lock();
+    let xattrs = Xattrs::read(&sb.disk, ino)?;
+    Ok(xattrs.entries.iter().filter_map(Entry::full_name).collect())
+}
+
+/// 设置属性, `value` 为 None 时删除
+pub fn set(sb: &Ext4Superblock, ino: u32, name: &str, value: Option<&[u8]>, flags: XattrFlags) -> SysResult<()> {
+    let (index, rest) = split_name(name)?;
+    let _guard = sb.xattr_lock.lock();
+    let mut xattrs = Xattrs::read(&sb.disk, ino)?;
+    let found = xattrs.find(index, rest);
+    match value {
+        Some(value) => {
+            flags.check(found.is_some())?;
+            let entry = Entry { index, name: rest.to_vec(), value: value.to_vec() };
+            match found {
+                Some(i) => xattrs.entries[i] = entry,
+                None => xattrs.entries.push(entry),
+            }
+        }
+        None => {
+            xattrs.entries.remove(found.ok_or(SysError::ENODATA)?);
+        }
+    }
+    store(sb, ino, xattrs)
+}
+
+/// 删除 inode 前释放它的属性块
+pub fn release(sb: &Ext4Superblock, ino: u32) -> SysResult<()> {
+    let _guard = sb.xattr_lock.lock();
+    let mut xattrs = Xattrs::read(&sb.disk, ino)?;
+    if xattrs.block == 0 {
+        return Ok(());
+    }
+    xattrs.entries.clear();
+    store(sb, ino, xattrs)
+}
+
+/// 重新排布属性并写回 inode 和属性块
+fn store(sb: &Ext4Superblock, ino: u32, xattrs: Xattrs) -> SysResult<()> {
+    let Xattrs { geo, pos, raw, block: old_block, entries } = xattrs;
+    let ibody = geo.ibody_start(&raw).map(|start| (start, geo.inode_size));
+    let ibody_space = ibody.map_or(0, |(start, end)| end - start - 4);
+    let mut in_inode = Vec::new();
+    let mut in_block = Vec::new();
+    for entry in entries {
+        let mut candidate = in_inode.clone();
+        candidate.push(entry.clone());
+        if space_needed(&candidate) <= ibody_space {
+            in_inode = candidate;
+        } else {
+            in_block.push(entry);
+        }
+    }
+    if space_needed(&in_block) > geo.block_size - BLOCK_HEADER_SIZE {
+        return Err(SysError::ENOSPC);
+    }
+    // 属性块按 (前缀编号, 名字长度, 名字) 排序, Linux 在块里查找时依赖这个顺序
+    in_block.sort_by(|a, b| (a.index, a.name.len(), &a.name).cmp(&(b.index, b.name.len(), &b.name)));
+
+    let old_refcount = match old_block {
+        0 => 0,
+        block => le32(&read_block(&sb.disk, &geo, block), 4),
+    };
+    // 只有自己用的块可以直接改写, 和别的 inode 共享的块要换一个新块
+    let new_block = if in_block.is_empty() {
+        0
+    } else if old_refcount == 1 {
+        old_block
+    } else {
+        let mut inode_ref = sb.ext4fs.get_inode_ref(ino);
+        sb.ext4fs.balloc_alloc_block(&mut inode_ref, None).map_err(|_| SysError::ENOSPC)?
+    };
+    if old_block != 0 && old_block != new_block {
+        if old_refcount <= 1 {
+            let mut inode_ref = sb.ext4fs.get_inode_ref(ino);
+            let _ = sb.ext4fs.balloc_free_blocks(&mut inode_ref, old_block, 1);
+        } else {
+            let mut buf = read_block(&sb.disk, &geo, old_block);
+            put32(&mut buf, 4, old_refcount - 1);
+            if let Some(seed) = geo.csum_seed {
+                geo.block_checksum(seed, old_block, &mut buf);
+            }
+            sb.disk.write_offset(old_block as usize * geo.block_size, &buf);
+        }
+    }
+    if new_block != 0 {
+        let mut buf = vec![0u8; geo.block_size];
+        put32(&mut buf, 0, XATTR_MAGIC);
+        put32(&mut buf, 4, 1);
+        put32(&mut buf, 8, 1);
+        write_entries(&mut buf, BLOCK_HEADER_SIZE, geo.block_size, 0, &in_block);
+        // 与 Linux 的 ext4_xattr_rehash 相同, 有一项的哈希为 0 时整块的哈希也为 0
+        let mut hash = 0u32;
+        for entry in in_block.iter() {
+            match entry.hash() {
+                0 => {
+                    hash = 0;
+                    break;
+                }
+                h => hash = (hash << 16) ^ (hash >> 16) ^ h,
+            }
+        }
+        put32(&mut buf, 0x0c, hash);
+        if let Some(seed) = geo.csum_seed {
+            geo.block_checksum(seed, new_block, &mut buf);
+        }
+        sb.disk.write_offset(new_block as usize * geo.block_size, &buf);
+    }
+
+    // 分配和释放块时 ext4_rs 会改写 inode, 重新读出来再改
+    let mut raw = sb.disk.read_offset(pos);
+    raw.truncate(geo.inode_size);
+    if let Some((start, end)) = ibody {
+        raw[start..end].fill(0);
+        if !in_inode.is_empty() {
+            put32(&mut raw, start, XATTR_MAGIC);
+            write_entries(&mut raw, start + 4, end, start + 4, &in_inode);
+        }
+    }
+    put32(&mut raw, 0x68, new_block as u32);
+    put16(&mut raw, 0x76, (new_block >> 32) as u32);
+    if let Some(seed) = geo.csum_seed {
+        geo.inode_checksum(seed, ino, &mut raw);
+    }
+    sb.disk.write_offset(pos, &raw);
+    Ok(())
+}
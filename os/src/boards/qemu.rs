@@ -15,7 +15,7 @@ pub const MMIO: &[(usize, usize)] = &[
 //pub const VIRT_UART: usize = 0x1000_0000;
 //pub const VIRT_PLIC: usize = 0xC00_0000;
 
-use crate::drivers::block::BLOCK_DEVICE;
+//use crate::drivers::block::BLOCK_DEVICE;
 //use crate::drivers::chardevice::{CharDevice, UART};
 //use crate::drivers::plic::{IntrTargetPriority, PLIC};
 /* 
@@ -0,0 +1,33 @@
+//! 内核命令行, 取自设备树 /chosen 节点的 bootargs
+use alloc::string::String;
+use spin::Once;
+
+static CMDLINE: Once<String> = Once::new();
+
+/// 记下 bootargs, 只有第一次生效
+pub fn init(bootargs: &[u8]) {
+    let args = core::str::from_utf8(bootargs).unwrap_or("");
+    CMDLINE.call_once(|| String::from(args.trim_end_matches('\0').trim()));
+}
+
+/// 整条命令行, 没有时为空串
+pub fn cmdline() -> &'static str {
+    CMDLINE.get().map_or("", |args| args.as_str())
+}
+
+/// `name=value` 形式参数的值, 同名参数以最后一个为准
+pub fn param(name: &str) -> Option<&'static str> {
+    cmdline()
+        .split_ascii_whitespace()
+        .filter_map(|arg| arg.split_once('='))
+        .filter(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+        .last()
+}
+
+/// /proc/cmdline
+pub fn proc_cmdline() -> String {
+    let mut text = String::from(cmdline());
+    text.push('\n');
+    text
+}
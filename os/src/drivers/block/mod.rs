@@ -6,25 +6,71 @@ mod virtio_blk;
 #[cfg(any(target_arch = "riscv64", target_arch = "aarch64"))]
 pub use virtio_blk::VirtIOBlock;
 
+use alloc::format;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use device::BlockDevice;
-use lazy_static::*;
+use spin::Mutex;
 
 #[cfg(any(target_arch = "x86_64", target_arch = "loongarch64"))]
 pub use pci_virtio_blk::VirtIOBlock;
 
-#[cfg(any(target_arch = "riscv64", target_arch = "aarch64"))]
-lazy_static! {
-    pub static ref BLOCK_DEVICE: Arc<dyn BlockDevice> = Arc::new(VirtIOBlock::new());
+/// 接在中断控制器上的块设备, 中断号到设备
+static BLOCK_IRQS: Mutex<Vec<(usize, Arc<dyn BlockDevice>)>> = Mutex::new(Vec::new());
+
+/// 探测所有块设备, 依次注册为 vda, vdb, ..., 再扫描各自的分区表注册 vda1 这样的分区.
+/// 根设备由命令行的 root=/dev/xxx 指定, 没有指定时用第一个盘
+pub fn init() {
+    let mut irqs = Vec::new();
+    let mut disks = Vec::new();
+    for (index, blk) in VirtIOBlock::probe().into_iter().enumerate() {
+        let irq = blk.irq();
+        let dev: Arc<dyn BlockDevice> = Arc::new(blk);
+        let name = format!("vd{}", (b'a' + index as u8) as char);
+        println!("block device /dev/{}: {} sectors", name, dev.num_blocks());
+        device::register_block_device(&name, dev.clone());
+        if let Some(irq) = irq {
+            BLOCK_IRQS.lock().push((irq, dev.clone()));
+            irqs.push(irq);
+        }
+        disks.push((name, dev));
+    }
+    // 扫描分区表要读盘, 等中断控制器接好之后再做
+    #[cfg(target_arch = "riscv64")]
+    super::plic::init(&irqs);
+    for (name, dev) in disks.iter() {
+        let parts = device::register_partitions(name, dev);
+        if !parts.is_empty() {
+            println!("/dev/{}: {} partitions", name, parts.len());
+        }
+    }
+    let root = match crate::cmdline::param("root") {
+        Some(root) => device::find_block_device(root.trim_start_matches("/dev/"))
+            .unwrap_or_else(|| panic!("root device {} not found", root)),
+        None => disks.first().expect("no block device found").1.clone(),
+    };
+    device::BLOCK_DEVICE.call_once(|| root);
 }
 
-#[cfg(any(target_arch = "x86_64", target_arch = "loongarch64"))]
-lazy_static! {
-    pub static ref BLOCK_DEVICE: Arc<dyn BlockDevice> = Arc::new(VirtIOBlock::new());
+/// 把中断分给对应的块设备, 不是块设备的中断返回 false
+pub fn handle_irq(irq: usize) -> bool {
+    let dev = BLOCK_IRQS
+        .lock()
+        .iter()
+        .find(|(dev_irq, _)| *dev_irq == irq)
+        .map(|(_, dev)| dev.clone());
+    match dev {
+        Some(dev) => {
+            dev.handle_irq();
+            true
+        }
+        None => false,
+    }
 }
+
 #[allow(unused)]
 pub fn block_device_test() {
-    let block_device = BLOCK_DEVICE.clone();
+    let block_device = device::BLOCK_DEVICE.get().unwrap().clone();
     let mut write_buffer = [0u8; 512];
     let mut read_buffer = [0u8; 512];
     for i in 0..512 {
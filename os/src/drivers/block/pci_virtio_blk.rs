@@ -44,6 +44,23 @@ impl BlockDevice for VirtIOBlock {
             .write_blocks(block_id, buf)
             .expect("Error when writing VirtIOBlk");
     }
+    fn read_blocks(&self, start_block: usize, buf: &mut [u8]) {
+        // 驱动一次请求可以带多个扇区
+        self.read_block(start_block, buf)
+    }
+    fn write_blocks(&self, start_block: usize, buf: &[u8]) {
+        self.write_block(start_block, buf)
+    }
+    fn num_blocks(&self) -> usize {
+        self.0.lock().capacity() as usize
+    }
+    fn flush(&self) {
+        // 设备没有协商 VIRTIO_BLK_F_FLUSH 时驱动直接返回
+        self.0
+            .lock()
+            .flush()
+            .expect("Error when flushing VirtIOBlk");
+    }
 }
 
 pub struct PciRangeAllocator {
@@ -82,12 +99,15 @@ const fn align_up(addr: usize, align: usize) -> usize {
 
 
 
-fn enumerate_pci()->Option<PciTransport> {
+/// 找出总线上所有 virtio 块设备, 按总线上的顺序返回
+fn enumerate_pci()->Vec<PciTransport> {
     let mmconfig_base = VIRTIO0 as *mut u8;
 
     let mut pci_root = unsafe { PciRoot::new(mmconfig_base, Cam::Ecam) };
 
-    let mut transport = None;
+    let mut transports = Vec::new();
+    // 所有设备的 BAR 从同一段地址里分, 免得互相重叠
+    let mut pci_range_allocator = PciRangeAllocator::new(VIRT_PCI_BASE, VIRT_PCI_SIZE);
 
     for (device_function, info) in pci_root.enumerate_bus(0) {
         let (_status, _command) = pci_root.get_status_command(device_function);
@@ -95,7 +115,6 @@ fn enumerate_pci()->Option<PciTransport> {
         if let Some(_virtio_type) = virtio_device_type(&info) {
       //      println!("type:{:?}",_virtio_type);
             if _virtio_type != DeviceType::Block {continue;}
-            let mut pci_range_allocator = PciRangeAllocator::new(VIRT_PCI_BASE, VIRT_PCI_SIZE);
             let mut bar_index = 0;
             while bar_index < 6{
                 let bar_info = pci_root.bar_info(device_function, bar_index).unwrap();
@@ -122,12 +141,10 @@ fn enumerate_pci()->Option<PciTransport> {
             );
          //   dump_bar_contents(&mut pci_root, device_function, 1);
 
-            transport =
-                Some(PciTransport::new::<VirtioHal>(&mut pci_root, device_function).unwrap());
-            break;
+            transports.push(PciTransport::new::<VirtioHal>(&mut pci_root, device_function).unwrap());
         }
     }
-    return transport;
+    transports
 }
 #[allow(unused)]
 fn dump_bar_contents(
@@ -153,17 +170,23 @@ fn dump_bar_contents(
 }
 
 impl VirtIOBlock {
+    /// 探测 PCI 总线上的所有 virtio 块设备. 走 PCI 的平台没有接中断控制器, 请求同步完成
     #[allow(unused)]
-    pub fn new() -> Self {
-        unsafe {
-            Self(Mutex::new(
-                VirtIOBlk::<VirtioHal, PciTransport>::new(
-                    enumerate_pci().unwrap()
-                    )
-                    .expect("this is not a valid virtio device"),
-                )
-            )
-        }
+    pub fn probe() -> Vec<Self> {
+        enumerate_pci()
+            .into_iter()
+            .map(|transport| {
+                Self(Mutex::new(
+                    VirtIOBlk::<VirtioHal, PciTransport>::new(transport)
+                        .expect("this is not a valid virtio device"),
+                ))
+            })
+            .collect()
+    }
+    /// PCI 设备的中断不经过 PLIC
+    #[allow(unused)]
+    pub fn irq(&self) -> Option<usize> {
+        None
     }
 }
 
@@ -3,21 +3,50 @@ use core::ptr::NonNull;
 use super::BlockDevice;
 use crate::mm::{frame_alloc, frame_dealloc, FrameTracker};
 use spin::Mutex;
+use alloc::collections::BTreeMap;
 use alloc::vec::Vec;
 use arch::addr::{PhysAddr, PhysPage};
 use arch::VIRT_ADDR_START;
 use lazy_static::*;
 //use log::debug;
-use virtio_drivers::device::blk::VirtIOBlk;
+use virtio_drivers::device::blk::{BlkReq, BlkResp, VirtIOBlk};
 use virtio_drivers::transport::mmio::{MmioTransport, VirtIOHeader};
+use virtio_drivers::transport::{DeviceType, Transport};
 use virtio_drivers::{BufferDirection, Hal};
 
-#[allow(unused)]
+/// QEMU virt 上 virtio-mmio 槽位的起始地址、间距与个数, 第 i 个槽位的中断号为 1 + i
 #[cfg(target_arch = "riscv64")]
-const VIRTIO0: usize = 0x10001000;
+const VIRTIO_MMIO_BASE: usize = 0x10001000;
+#[cfg(target_arch = "riscv64")]
+const VIRTIO_MMIO_STRIDE: usize = 0x1000;
+#[cfg(target_arch = "riscv64")]
+const VIRTIO_MMIO_SLOTS: usize = 8;
+#[cfg(target_arch = "riscv64")]
+const VIRTIO_MMIO_IRQ_BASE: usize = 1;
+
+
+pub struct VirtIOBlock {
+    blk: Mutex<VirtIOBlk<VirtioHal, MmioTransport>>,
+    /// PLIC 上的中断号
+    irq: usize,
+    /// 已提交的请求, 按令牌索引, 由中断处理完成
+    pending: Mutex<BTreeMap<u16, Pending>>,
+}
 
+/// 请求用到的缓冲区, 都在请求方的栈上, 请求方拿到结果之前不会返回
+enum PendingBuf {
+    Read(*mut [u8]),
+    Write(*const [u8]),
+}
 
-pub struct VirtIOBlock(Mutex<VirtIOBlk<VirtioHal, MmioTransport>>);
+/// 一个已提交的请求
+struct Pending {
+    req: *const BlkReq,
+    resp: *mut BlkResp,
+    buf: PendingBuf,
+    /// 中断处理完成请求后填入
+    result: Option<virtio_drivers::Result<()>>,
+}
 
 lazy_static! {
     static ref QUEUE_FRAMES: Mutex<Vec<FrameTracker>> = Mutex::new(Vec::new()) ;
@@ -28,32 +57,119 @@ unsafe impl Send for VirtIOBlock {}
 
 impl BlockDevice for VirtIOBlock {
     fn read_block(&self, block_id: usize, buf: &mut [u8]) {
-        self.0
-            .lock()
-            .read_blocks(block_id, buf)
-            .expect("Error when reading VirtIOBlk");
+        self.read_blocks(block_id, buf)
     }
     fn write_block(&self, block_id: usize, buf: &[u8]) {
-        self.0
+        self.write_blocks(block_id, buf)
+    }
+    fn read_blocks(&self, start_block: usize, buf: &mut [u8]) {
+        let mut req = BlkReq::default();
+        let mut resp = BlkResp::default();
+        let token = {
+            let mut blk = self.blk.lock();
+            let token = unsafe { blk.read_blocks_nb(start_block, &mut req, buf, &mut resp) }
+                .expect("Error when reading VirtIOBlk");
+            self.add_pending(token, &req, &mut resp, PendingBuf::Read(buf));
+            token
+        };
+        self.wait_for_completion(token).expect("Error when reading VirtIOBlk");
+    }
+    fn write_blocks(&self, start_block: usize, buf: &[u8]) {
+        let mut req = BlkReq::default();
+        let mut resp = BlkResp::default();
+        let token = {
+            let mut blk = self.blk.lock();
+            let token = unsafe { blk.write_blocks_nb(start_block, &mut req, buf, &mut resp) }
+                .expect("Error when writing VirtIOBlk");
+            self.add_pending(token, &req, &mut resp, PendingBuf::Write(buf));
+            token
+        };
+        self.wait_for_completion(token).expect("Error when writing VirtIOBlk");
+    }
+    fn num_blocks(&self) -> usize {
+        self.blk.lock().capacity() as usize
+    }
+    fn flush(&self) {
+        // 设备没有协商 VIRTIO_BLK_F_FLUSH 时驱动直接返回
+        self.blk
             .lock()
-            .write_blocks(block_id, buf)
-            .expect("Error when writing VirtIOBlk");
+            .flush()
+            .expect("Error when flushing VirtIOBlk");
+    }
+    /// 按已用环的顺序完成请求, 把结果交给等待方
+    fn handle_irq(&self) {
+        let mut blk = self.blk.lock();
+        blk.ack_interrupt();
+        let mut pending = self.pending.lock();
+        while let Some(token) = blk.peek_used() {
+            let Some(request) = pending.get_mut(&token) else {
+                log::warn!("virtio-blk: used token {} has no pending request", token);
+                break;
+            };
+            let result = unsafe {
+                match request.buf {
+                    PendingBuf::Read(buf) => blk.complete_read_blocks(token, &*request.req, &mut *buf, &mut *request.resp),
+                    PendingBuf::Write(buf) => blk.complete_write_blocks(token, &*request.req, &*buf, &mut *request.resp),
+                }
+            };
+            request.result = Some(result);
+        }
     }
 }
 
 impl VirtIOBlock {
+    /// 探测所有 virtio-mmio 槽位, 按槽位顺序返回其中的块设备
     #[allow(unused)]
-    pub fn new() -> Self {
-        unsafe {
-            Self(Mutex::new(
-                VirtIOBlk::<VirtioHal, MmioTransport>::new(
-                    MmioTransport::new(NonNull::new_unchecked(
-                        (VIRTIO0 | VIRT_ADDR_START) as *mut VirtIOHeader,
-                    ))
-                    .expect("this is not a valid virtio device"),
-                )
-                .unwrap(),
-            ))
+    pub fn probe() -> Vec<Self> {
+        let mut devices = Vec::new();
+        #[cfg(target_arch = "riscv64")]
+        for slot in 0..VIRTIO_MMIO_SLOTS {
+            let header = (VIRTIO_MMIO_BASE + slot * VIRTIO_MMIO_STRIDE) | VIRT_ADDR_START;
+            // 空槽位的设备号为 0, 创建传输层时就会失败
+            let transport = match unsafe { MmioTransport::new(NonNull::new_unchecked(header as *mut VirtIOHeader)) } {
+                Ok(transport) if transport.device_type() == DeviceType::Block => transport,
+                _ => continue,
+            };
+            let blk = VirtIOBlk::<VirtioHal, MmioTransport>::new(transport).expect("this is not a valid virtio device");
+            devices.push(Self {
+                blk: Mutex::new(blk),
+                irq: VIRTIO_MMIO_IRQ_BASE + slot,
+                pending: Mutex::new(BTreeMap::new()),
+            });
+        }
+        devices
+    }
+    /// 设备在中断控制器上的中断号
+    #[allow(unused)]
+    pub fn irq(&self) -> Option<usize> {
+        Some(self.irq)
+    }
+    /// 记下刚提交的请求, 调用方持有设备锁, 中断处理不会先看到它完成
+    fn add_pending(&self, token: u16, req: &BlkReq, resp: &mut BlkResp, buf: PendingBuf) {
+        let request = Pending {
+            req,
+            resp,
+            buf,
+            result: None,
+        };
+        self.pending.lock().insert(token, request);
+    }
+    /// 等中断处理完成令牌为 `token` 的请求, 取走结果.
+    /// 文件系统在 I/O 期间持有自旋锁, 内核又不可抢占, 切到别的任务后它抢同一把锁会在单核上一直自旋,
+    /// 所以等待方不让出 CPU, 而是不持设备锁停在 wfi 上; 内核态不开中断, 醒来后由这里认领并分发挂起的中断
+    fn wait_for_completion(&self, token: u16) -> virtio_drivers::Result<()> {
+        loop {
+            {
+                let mut pending = self.pending.lock();
+                if pending.get(&token).is_some_and(|request| request.result.is_some()) {
+                    return pending.remove(&token).unwrap().result.unwrap();
+                }
+            }
+            #[cfg(target_arch = "riscv64")]
+            {
+                arch::wait_for_interrupt();
+                crate::drivers::plic::handle_irqs();
+            }
         }
     }
 }
@@ -1,5 +1,19 @@
 //mod virtio;
 pub mod block;
+pub mod ninep;
+pub mod rtc;
 //pub mod chardevice;
-//pub mod plic;
-pub use block::BLOCK_DEVICE;
+#[cfg(target_arch = "riscv64")]
+pub mod plic;
+
+/// 探测设备并把它们的中断接到中断控制器上
+pub fn init() {
+    block::init();
+    ninep::init();
+}
+
+/// 外部中断入口
+pub fn handle_irq() {
+    #[cfg(target_arch = "riscv64")]
+    plic::handle_irqs();
+}
@@ -0,0 +1,11 @@
+#[cfg(target_arch = "riscv64")]
+mod virtio_9p;
+
+/// 探测 9P 通道, 以各自的挂载标签注册, 之后可以 mount -t 9p <标签> <目录>
+pub fn init() {
+    #[cfg(target_arch = "riscv64")]
+    for (tag, dev) in virtio_9p::VirtIO9p::probe() {
+        println!("9p device: mount tag {}", tag);
+        device::register_9p_device(&tag, alloc::sync::Arc::new(dev));
+    }
+}
@@ -0,0 +1,272 @@
+//! virtio-mmio 上的 virtio-9p 设备, 即 QEMU 的 `-virtfs local,...,mount_tag=<标签>`.
+//!
+//! virtio-drivers 没有 9P 设备, 这里直接操作 virtio-mmio 寄存器, 同时支持旧版 (version 1)
+//! 与新版 (version 2) 的寄存器布局. 只用一个请求队列, 同一时间只有一个请求在途:
+//! 请求和应答各用一个描述符, 提交后轮询已用环等设备处理完.
+use alloc::{string::String, vec::Vec};
+use core::hint::spin_loop;
+use core::ptr::{read_volatile, write_volatile};
+use core::sync::atomic::{fence, Ordering};
+
+use arch::VIRT_ADDR_START;
+use device::NinePDevice;
+use spin::Mutex;
+
+use crate::mm::{frame_alloc_more, FrameTracker};
+
+/// QEMU virt 上 virtio-mmio 槽位的起始地址、间距与个数
+const VIRTIO_MMIO_BASE: usize = 0x10001000;
+const VIRTIO_MMIO_STRIDE: usize = 0x1000;
+const VIRTIO_MMIO_SLOTS: usize = 8;
+
+/// virtio-mmio 寄存器
+const MAGIC_VALUE: usize = 0x000;
+const VERSION: usize = 0x004;
+const DEVICE_ID: usize = 0x008;
+const DEVICE_FEATURES: usize = 0x010;
+const DEVICE_FEATURES_SEL: usize = 0x014;
+const DRIVER_FEATURES: usize = 0x020;
+const DRIVER_FEATURES_SEL: usize = 0x024;
+const GUEST_PAGE_SIZE: usize = 0x028;
+const QUEUE_SEL: usize = 0x030;
+const QUEUE_NUM_MAX: usize = 0x034;
+const QUEUE_NUM: usize = 0x038;
+const QUEUE_ALIGN: usize = 0x03c;
+const QUEUE_PFN: usize = 0x040;
+const QUEUE_READY: usize = 0x044;
+const QUEUE_NOTIFY: usize = 0x050;
+const INTERRUPT_STATUS: usize = 0x060;
+const INTERRUPT_ACK: usize = 0x064;
+const STATUS: usize = 0x070;
+const QUEUE_DESC_LOW: usize = 0x080;
+const QUEUE_DESC_HIGH: usize = 0x084;
+const QUEUE_DRIVER_LOW: usize = 0x090;
+const QUEUE_DRIVER_HIGH: usize = 0x094;
+const QUEUE_DEVICE_LOW: usize = 0x0a0;
+const QUEUE_DEVICE_HIGH: usize = 0x0a4;
+/// 设备配置: 2 字节的标签长度, 后面是标签
+const CONFIG: usize = 0x100;
+
+const VIRTIO_MAGIC: u32 = 0x7472_6976;
+const DEVICE_ID_9P: u32 = 9;
+
+const STATUS_ACKNOWLEDGE: u32 = 1;
+const STATUS_DRIVER: u32 = 2;
+const STATUS_DRIVER_OK: u32 = 4;
+const STATUS_FEATURES_OK: u32 = 8;
+const STATUS_NEEDS_RESET: u32 = 64;
+
+/// 设备配置里有挂载标签
+const VIRTIO_9P_MOUNT_TAG: u64 = 1 << 0;
+/// 新版设备必须协商的特性
+const VIRTIO_F_VERSION_1: u64 = 1 << 32;
+
+const PAGE_SIZE: usize = 4096;
+/// 队列长度, 一个请求只用两个描述符
+const QUEUE_SIZE: u16 = 4;
+/// 已用环在队列内存中的偏移, 旧版布局要求它按页对齐
+const USED_OFFSET: usize = PAGE_SIZE;
+/// 可用环在队列内存中的偏移, 紧跟描述符表
+const AVAIL_OFFSET: usize = 16 * QUEUE_SIZE as usize;
+/// 一条 9P 消息的最大长度, 请求和应答缓冲区各占这么多连续的物理页
+const MSG_SIZE: usize = 128 * 1024;
+
+const DESC_F_NEXT: u16 = 1;
+const DESC_F_WRITE: u16 = 2;
+
+/// 一段物理上连续的内存
+struct DmaRegion {
+    _frames: Vec<FrameTracker>,
+    paddr: usize,
+}
+
+impl DmaRegion {
+    fn new(pages: usize) -> Option<Self> {
+        let frames = frame_alloc_more(pages)?;
+        // frame_alloc_more 从高地址往低地址排列
+        let paddr = frames.last()?.ppn.to_addr();
+        Some(Self { _frames: frames, paddr })
+    }
+    fn vaddr(&self) -> usize {
+        self.paddr | VIRT_ADDR_START
+    }
+    fn slice(&mut self, len: usize) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self.vaddr() as *mut u8, len) }
+    }
+}
+
+/// 请求队列和收发缓冲区
+struct Queue {
+    ring: DmaRegion,
+    req: DmaRegion,
+    resp: DmaRegion,
+    /// 下一个要放进可用环的位置
+    avail_idx: u16,
+    /// 下一个要从已用环取出的位置
+    used_idx: u16,
+}
+
+impl Queue {
+    unsafe fn write_desc(&self, index: usize, addr: usize, len: usize, flags: u16, next: u16) {
+        let desc = self.ring.vaddr() + 16 * index;
+        write_volatile(desc as *mut u64, addr as u64);
+        write_volatile((desc + 8) as *mut u32, len as u32);
+        write_volatile((desc + 12) as *mut u16, flags);
+        write_volatile((desc + 14) as *mut u16, next);
+    }
+    /// 把描述符链 0 放进可用环
+    unsafe fn submit(&mut self) {
+        let avail = self.ring.vaddr() + AVAIL_OFFSET;
+        let slot = (self.avail_idx % QUEUE_SIZE) as usize;
+        write_volatile((avail + 4 + 2 * slot) as *mut u16, 0);
+        self.avail_idx = self.avail_idx.wrapping_add(1);
+        fence(Ordering::SeqCst);
+        write_volatile((avail + 2) as *mut u16, self.avail_idx);
+        fence(Ordering::SeqCst);
+    }
+    /// 设备用完一个请求时返回写进应答缓冲区的长度
+    unsafe fn pop_used(&mut self) -> Option<usize> {
+        let used = self.ring.vaddr() + USED_OFFSET;
+        fence(Ordering::SeqCst);
+        if read_volatile((used + 2) as *const u16) == self.used_idx {
+            return None;
+        }
+        let slot = (self.used_idx % QUEUE_SIZE) as usize;
+        let len = read_volatile((used + 4 + 8 * slot + 4) as *const u32);
+        self.used_idx = self.used_idx.wrapping_add(1);
+        Some(len as usize)
+    }
+}
+
+pub struct VirtIO9p {
+    /// 寄存器的虚拟地址
+    base: usize,
+    queue: Mutex<Queue>,
+}
+
+impl VirtIO9p {
+    /// 探测所有 virtio-mmio 槽位, 返回其中的 9P 设备和它们的挂载标签
+    pub fn probe() -> Vec<(String, Self)> {
+        let mut devices = Vec::new();
+        for slot in 0..VIRTIO_MMIO_SLOTS {
+            let base = (VIRTIO_MMIO_BASE + slot * VIRTIO_MMIO_STRIDE) | VIRT_ADDR_START;
+            let read = |offset: usize| unsafe { read_volatile((base + offset) as *const u32) };
+            if read(MAGIC_VALUE) != VIRTIO_MAGIC || read(DEVICE_ID) != DEVICE_ID_9P {
+                continue;
+            }
+            match Self::new(base) {
+                Some(dev) => devices.push(dev),
+                None => log::warn!("virtio-9p at slot {}: initialization failed", slot),
+            }
+        }
+        devices
+    }
+
+    fn read_reg(&self, offset: usize) -> u32 {
+        unsafe { read_volatile((self.base + offset) as *const u32) }
+    }
+
+    fn write_reg(&self, offset: usize, val: u32) {
+        unsafe { write_volatile((self.base + offset) as *mut u32, val) }
+    }
+
+    /// 按 virtio 规范的步骤初始化设备, 读出挂载标签
+    fn new(base: usize) -> Option<(String, Self)> {
+        let ring = DmaRegion::new(2)?;
+        let dev = Self {
+            base,
+            queue: Mutex::new(Queue {
+                ring,
+                req: DmaRegion::new(MSG_SIZE / PAGE_SIZE)?,
+                resp: DmaRegion::new(MSG_SIZE / PAGE_SIZE)?,
+                avail_idx: 0,
+                used_idx: 0,
+            }),
+        };
+        let version = dev.read_reg(VERSION);
+        if version != 1 && version != 2 {
+            return None;
+        }
+        dev.write_reg(STATUS, 0);
+        let mut status = STATUS_ACKNOWLEDGE | STATUS_DRIVER;
+        dev.write_reg(STATUS, status);
+        dev.write_reg(DEVICE_FEATURES_SEL, 0);
+        let mut features = dev.read_reg(DEVICE_FEATURES) as u64;
+        dev.write_reg(DEVICE_FEATURES_SEL, 1);
+        features |= (dev.read_reg(DEVICE_FEATURES) as u64) << 32;
+        if features & VIRTIO_9P_MOUNT_TAG == 0 {
+            return None;
+        }
+        let driver_features = VIRTIO_9P_MOUNT_TAG | if version == 2 { VIRTIO_F_VERSION_1 } else { 0 };
+        dev.write_reg(DRIVER_FEATURES_SEL, 0);
+        dev.write_reg(DRIVER_FEATURES, driver_features as u32);
+        dev.write_reg(DRIVER_FEATURES_SEL, 1);
+        dev.write_reg(DRIVER_FEATURES, (driver_features >> 32) as u32);
+        if version == 2 {
+            status |= STATUS_FEATURES_OK;
+            dev.write_reg(STATUS, status);
+            if dev.read_reg(STATUS) & STATUS_FEATURES_OK == 0 {
+                return None;
+            }
+        } else {
+            dev.write_reg(GUEST_PAGE_SIZE, PAGE_SIZE as u32);
+        }
+        dev.write_reg(QUEUE_SEL, 0);
+        if dev.read_reg(QUEUE_NUM_MAX) < QUEUE_SIZE as u32 {
+            return None;
+        }
+        dev.write_reg(QUEUE_NUM, QUEUE_SIZE as u32);
+        let ring = dev.queue.lock().ring.paddr;
+        if version == 2 {
+            let (desc, avail, used) = (ring, ring + AVAIL_OFFSET, ring + USED_OFFSET);
+            dev.write_reg(QUEUE_DESC_LOW, desc as u32);
+            dev.write_reg(QUEUE_DESC_HIGH, (desc as u64 >> 32) as u32);
+            dev.write_reg(QUEUE_DRIVER_LOW, avail as u32);
+            dev.write_reg(QUEUE_DRIVER_HIGH, (avail as u64 >> 32) as u32);
+            dev.write_reg(QUEUE_DEVICE_LOW, used as u32);
+            dev.write_reg(QUEUE_DEVICE_HIGH, (used as u64 >> 32) as u32);
+            dev.write_reg(QUEUE_READY, 1);
+        } else {
+            dev.write_reg(QUEUE_ALIGN, PAGE_SIZE as u32);
+            dev.write_reg(QUEUE_PFN, (ring / PAGE_SIZE) as u32);
+        }
+        dev.write_reg(STATUS, status | STATUS_DRIVER_OK);
+        let config = |offset: usize| unsafe { read_volatile((base + CONFIG + offset) as *const u8) };
+        let tag_len = u16::from_le_bytes([config(0), config(1)]) as usize;
+        let tag: Vec<u8> = (0..tag_len).map(|i| config(2 + i)).collect();
+        Some((String::from_utf8_lossy(&tag).into_owned(), dev))
+    }
+}
+
+impl NinePDevice for VirtIO9p {
+    fn max_message(&self) -> usize {
+        MSG_SIZE
+    }
+    fn request(&self, req: &[u8], resp: &mut [u8]) -> Option<usize> {
+        if req.len() > MSG_SIZE {
+            return None;
+        }
+        let resp_len = resp.len().min(MSG_SIZE);
+        let mut queue = self.queue.lock();
+        queue.req.slice(req.len()).copy_from_slice(req);
+        unsafe {
+            queue.write_desc(0, queue.req.paddr, req.len(), DESC_F_NEXT, 1);
+            queue.write_desc(1, queue.resp.paddr, resp_len, DESC_F_WRITE, 0);
+            queue.submit();
+        }
+        self.write_reg(QUEUE_NOTIFY, 0);
+        let len = loop {
+            if let Some(len) = unsafe { queue.pop_used() } {
+                break len.min(resp_len);
+            }
+            if self.read_reg(STATUS) & STATUS_NEEDS_RESET != 0 {
+                return None;
+            }
+            spin_loop();
+        };
+        // 设备的中断没有接到中断控制器上, 这里只清掉中断状态
+        self.write_reg(INTERRUPT_ACK, self.read_reg(INTERRUPT_STATUS));
+        resp[..len].copy_from_slice(queue.resp.slice(len));
+        Some(len)
+    }
+}
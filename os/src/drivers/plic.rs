@@ -3,6 +3,7 @@ pub struct PLIC {
     base_addr: usize,
 }
 
+#[allow(unused)]
 #[derive(Copy, Clone)]
 pub enum IntrTargetPriority {
     Machine = 0,
@@ -125,3 +126,38 @@ impl PLIC {
         }
     }
 }
+
+/// QEMU virt 上 PLIC 的物理地址
+const VIRT_PLIC: usize = 0xC00_0000;
+
+fn plic() -> PLIC {
+    unsafe { PLIC::new(VIRT_PLIC | arch::VIRT_ADDR_START) }
+}
+
+/// 在 0 号核的 S 态上打开这些中断源
+pub fn init(irqs: &[usize]) {
+    let mut plic = plic();
+    let supervisor = IntrTargetPriority::Supervisor;
+    plic.set_threshold(0, supervisor, 0);
+    for &irq in irqs {
+        plic.enable(0, supervisor, irq);
+        plic.set_priority(irq, 1);
+    }
+    arch::enable_external_irq();
+}
+
+/// 认领并分发所有挂起的外部中断
+pub fn handle_irqs() {
+    let mut plic = plic();
+    let supervisor = IntrTargetPriority::Supervisor;
+    loop {
+        let irq = plic.claim(0, supervisor);
+        if irq == 0 {
+            break;
+        }
+        if !super::block::handle_irq(irq as usize) {
+            log::warn!("unexpected external irq {}", irq);
+        }
+        plic.complete(0, supervisor, irq);
+    }
+}
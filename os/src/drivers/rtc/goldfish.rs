@@ -0,0 +1,41 @@
+//! Goldfish RTC, provided by the QEMU virt machine.
+use core::ptr::{read_volatile, write_volatile};
+use device::RtcDevice;
+
+/// Low 32 bits of the time in ns; reading it latches TIME_HIGH.
+const TIME_LOW: usize = 0x00;
+/// High 32 bits of the time in ns.
+const TIME_HIGH: usize = 0x04;
+
+pub struct GoldfishRtc {
+    base: usize,
+}
+
+impl GoldfishRtc {
+    /// `base` is the virtual address of the register window.
+    pub fn new(base: usize) -> Self {
+        Self { base }
+    }
+
+    fn read_reg(&self, offset: usize) -> u32 {
+        unsafe { read_volatile((self.base + offset) as *const u32) }
+    }
+
+    fn write_reg(&self, offset: usize, val: u32) {
+        unsafe { write_volatile((self.base + offset) as *mut u32, val) }
+    }
+}
+
+impl RtcDevice for GoldfishRtc {
+    fn read_time(&self) -> u64 {
+        let low = self.read_reg(TIME_LOW) as u64;
+        let high = self.read_reg(TIME_HIGH) as u64;
+        (high << 32) | low
+    }
+
+    fn set_time(&self, nsec: u64) {
+        // the write to TIME_LOW commits both halves
+        self.write_reg(TIME_HIGH, (nsec >> 32) as u32);
+        self.write_reg(TIME_LOW, nsec as u32);
+    }
+}
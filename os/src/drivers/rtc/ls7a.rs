@@ -0,0 +1,67 @@
+//! RTC in the LS7A bridge used by the LoongArch virt machine.
+use core::ptr::{read_volatile, write_volatile};
+use arch::VIRT_ADDR_START;
+use device::RtcDevice;
+use time::{RtcTime, NSEC_PER_SEC};
+
+const RTC_BASE: usize = 0x100d_0100 | VIRT_ADDR_START;
+
+const TOY_WRITE0: usize = 0x24;
+const TOY_WRITE1: usize = 0x28;
+const TOY_READ0: usize = 0x2c;
+const TOY_READ1: usize = 0x30;
+const RTC_CTRL: usize = 0x40;
+
+const CTRL_TOYEN: u32 = 1 << 11;
+const CTRL_EO: u32 = 1 << 8;
+
+pub struct Ls7aRtc;
+
+impl Ls7aRtc {
+    pub fn new() -> Self {
+        let rtc = Self;
+        let ctrl = rtc.read_reg(RTC_CTRL);
+        rtc.write_reg(RTC_CTRL, ctrl | CTRL_TOYEN | CTRL_EO);
+        rtc
+    }
+
+    fn read_reg(&self, offset: usize) -> u32 {
+        unsafe { read_volatile((RTC_BASE + offset) as *const u32) }
+    }
+
+    fn write_reg(&self, offset: usize, val: u32) {
+        unsafe { write_volatile((RTC_BASE + offset) as *mut u32, val) }
+    }
+}
+
+impl RtcDevice for Ls7aRtc {
+    fn read_time(&self) -> u64 {
+        // TOY_READ0: mon[31:26] day[25:21] hour[20:16] min[15:10] sec[9:4]
+        let v = self.read_reg(TOY_READ0);
+        let mut year = self.read_reg(TOY_READ1) as i32;
+        if year < 70 {
+            year += 100;
+        }
+        let tm = RtcTime {
+            tm_sec: ((v >> 4) & 0x3f) as i32,
+            tm_min: ((v >> 10) & 0x3f) as i32,
+            tm_hour: ((v >> 16) & 0x1f) as i32,
+            tm_mday: ((v >> 21) & 0x1f) as i32,
+            tm_mon: ((v >> 26) & 0x3f) as i32 - 1,
+            tm_year: year,
+            ..Default::default()
+        };
+        tm.to_unix_secs().unwrap_or(0) * NSEC_PER_SEC as u64
+    }
+
+    fn set_time(&self, nsec: u64) {
+        let tm = RtcTime::from_unix_secs(nsec / NSEC_PER_SEC as u64);
+        let v = ((tm.tm_mon as u32 + 1) << 26)
+            | ((tm.tm_mday as u32) << 21)
+            | ((tm.tm_hour as u32) << 16)
+            | ((tm.tm_min as u32) << 10)
+            | ((tm.tm_sec as u32) << 4);
+        self.write_reg(TOY_WRITE0, v);
+        self.write_reg(TOY_WRITE1, tm.tm_year as u32);
+    }
+}
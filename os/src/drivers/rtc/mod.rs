@@ -0,0 +1,34 @@
+//! Real time clock drivers.
+//!
+//! Whichever RTC is found first is registered as `device::RTC_DEVICE`; its
+//! reading seeds the kernel wall clock once at boot.
+mod goldfish;
+#[cfg(target_arch = "loongarch64")]
+mod ls7a;
+
+pub use goldfish::GoldfishRtc;
+#[cfg(target_arch = "loongarch64")]
+pub use ls7a::Ls7aRtc;
+
+use alloc::sync::Arc;
+use arch::VIRT_ADDR_START;
+
+/// Register the goldfish RTC found at physical address `paddr`.
+pub fn init_goldfish(paddr: usize) {
+    device::RTC_DEVICE.call_once(|| Arc::new(GoldfishRtc::new(paddr | VIRT_ADDR_START)));
+}
+
+/// Register the RTC built into the LS7A bridge.
+#[cfg(target_arch = "loongarch64")]
+pub fn init_ls7a() {
+    device::RTC_DEVICE.call_once(|| Arc::new(Ls7aRtc::new()));
+}
+
+/// Seed the wall clock from the RTC, if there is one.
+pub fn init_realtime() {
+    if let Some(rtc) = device::RTC_DEVICE.get() {
+        let now = rtc.read_time() as usize;
+        time::set_realtime_nsec(now);
+        println!("[kernel] rtc: {} seconds since epoch", now / time::NSEC_PER_SEC);
+    }
+}
@@ -0,0 +1,122 @@
+//! 匿名 inode: epoll、eventfd 这类只存在于文件描述符里的文件
+//!
+//! 与 Linux 的 anon_inode 一样, 它们不属于任何目录, 名字形如 `anon_inode:[eventpoll]`.
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use system_result::{SysError, SysResult};
+use arch::PAGE_SIZE;
+use vfs::NAME_MAX;
+use vfs_defs::{
+    ino_alloc, Dentry, DentryInner, DentryState, DiskInodeType, File, Inode, InodeMeta, InodeMode, Kstat, OpenFlags,
+    RenameFlags, StatFs, SuperBlock,
+};
+
+use super::PipeDentry;
+
+/// statfs 报告的 f_type, 和 Linux 的 PIPEFS_MAGIC、ANON_INODE_FS_MAGIC 一致
+const PIPEFS_MAGIC: i64 = 0x50495045;
+const ANON_INODE_FS_MAGIC: i64 = 0x09041934;
+
+/// 管道和匿名 inode 借用工作目录的超级块, 不属于任何挂载的文件系统.
+/// 它们的 statfs 与 Linux 一样只报告伪文件系统的类型, 容量都为 0
+pub fn pseudo_statfs(dentry: &Arc<dyn Dentry>) -> Option<StatFs> {
+    let f_type = if dentry.is::<PipeDentry>() {
+        PIPEFS_MAGIC
+    } else if dentry.is::<AnonDentry>() {
+        ANON_INODE_FS_MAGIC
+    } else {
+        return None;
+    };
+    Some(StatFs {
+        f_type,
+        f_bsize: PAGE_SIZE as i64,
+        f_namelen: NAME_MAX as isize,
+        f_frsize: PAGE_SIZE as isize,
+        ..Default::default()
+    })
+}
+
+/// 新建一个名为 `anon_inode:[name]` 的目录项, 权限 0600, 没有文件类型位
+pub fn anon_dentry(name: &str, superblock: Arc<dyn SuperBlock>) -> Arc<dyn Dentry> {
+    let dentry = Arc::new(AnonDentry {
+        inner: DentryInner::new(format!("anon_inode:[{}]", name), superblock.clone(), None),
+    });
+    let inode = Arc::new(AnonInode {
+        meta: InodeMeta::new(InodeMode::OWNER_READ | InodeMode::OWNER_WRITE, ino_alloc(), superblock),
+    });
+    *inode.meta._type.lock() = DiskInodeType::File;
+    dentry.set_inode(inode);
+    *dentry.get_state() = DentryState::Valid;
+    dentry
+}
+
+pub struct AnonDentry {
+    inner: DentryInner,
+}
+
+impl Dentry for AnonDentry {
+    fn get_inner(&self) -> &DentryInner {
+        &self.inner
+    }
+    fn open(self: Arc<Self>, _flags: OpenFlags) -> Arc<dyn File> {
+        unreachable!()
+    }
+    fn concrete_create(self: Arc<Self>, _name: &str, _type: DiskInodeType) -> SysResult<Arc<dyn Dentry>> {
+        Err(SysError::ENOTDIR)
+    }
+    fn concrete_lookup(self: Arc<Self>, _name: &str) -> SysResult<Arc<dyn Dentry>> {
+        Err(SysError::ENOTDIR)
+    }
+    fn concrete_link(self: Arc<Self>, _new: &Arc<dyn Dentry>) -> SysResult<()> {
+        Err(SysError::ENOTDIR)
+    }
+    fn concrete_unlink(self: Arc<Self>, _old: &Arc<dyn Dentry>) -> SysResult<()> {
+        Err(SysError::ENOTDIR)
+    }
+    fn load_dir(self: Arc<Self>) -> SysResult<()> {
+        Err(SysError::ENOTDIR)
+    }
+    fn concrete_new_child(self: Arc<Self>, _name: &str) -> Arc<dyn Dentry> {
+        unimplemented!()
+    }
+    fn concrete_rename(self: Arc<Self>, _new: Arc<dyn Dentry>, _flags: RenameFlags) -> SysResult<()> {
+        Err(SysError::ENOTDIR)
+    }
+    fn concrete_getchild(self: Arc<Self>, _name: &str) -> Option<Arc<dyn Dentry>> {
+        None
+    }
+    fn self_arc(self: Arc<Self>) -> Arc<dyn Dentry> {
+        self
+    }
+    fn path(&self) -> String {
+        self.inner.name.clone()
+    }
+}
+
+pub struct AnonInode {
+    meta: InodeMeta,
+}
+
+impl Inode for AnonInode {
+    fn get_meta(&self) -> &InodeMeta {
+        &self.meta
+    }
+    fn get_attr(&self) -> SysResult<Kstat> {
+        let inner = self.meta.inner.lock();
+        Ok(Kstat {
+            st_ino: self.meta.ino as u64,
+            st_mode: inner.mode.bits(),
+            st_nlink: 1,
+            st_uid: inner.uid,
+            st_gid: inner.gid,
+            st_blksize: 4096,
+            ..Kstat::default()
+        })
+    }
+    fn load_from_disk(&self) {}
+    fn clear(&self) {}
+    fn get_size(&self) -> u32 {
+        0
+    }
+}
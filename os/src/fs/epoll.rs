@@ -0,0 +1,280 @@
+//! epoll 实例
+//!
+//! 每个被监视的文件挂一个 [`ItemWaiter`] 到它的等待队列上, 文件有事件时标记该项
+//! 并唤醒 epoll 自己的等待队列. 水平触发的项每次都重新 `poll`, 边沿触发的项只在
+//! 上次报告之后收到过通知时才报告; 没有等待队列的文件无从得知边沿, 退化为水平触发.
+use super::anon_dentry;
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use bitflags::*;
+use core::sync::atomic::{AtomicBool, Ordering};
+use sync::Mutex;
+use system_result::{SysError, SysResult};
+use vfs_defs::{File, FileInner, PollEvents, PollQueue, PollWaiter, SuperBlock};
+
+/// epoll 最多嵌套的层数
+const EPOLL_MAX_NESTS: usize = 4;
+
+bitflags! {
+    pub struct EpollEvents: u32 {
+        const EPOLLIN = 0x001;
+        const EPOLLPRI = 0x002;
+        const EPOLLOUT = 0x004;
+        const EPOLLERR = 0x008;
+        const EPOLLHUP = 0x010;
+        const EPOLLRDNORM = 0x040;
+        const EPOLLRDBAND = 0x080;
+        const EPOLLWRNORM = 0x100;
+        const EPOLLWRBAND = 0x200;
+        const EPOLLMSG = 0x400;
+        const EPOLLRDHUP = 0x2000;
+        /// 多个 epoll 监视同一文件时每次只唤醒一个
+        const EPOLLEXCLUSIVE = 1 << 28;
+        const EPOLLWAKEUP = 1 << 29;
+        /// 报告一次后停用, 直到 EPOLL_CTL_MOD 重新启用
+        const EPOLLONESHOT = 1 << 30;
+        /// 边沿触发
+        const EPOLLET = 1 << 31;
+    }
+}
+
+/// 用户态的 struct epoll_event, x86_64 上是紧凑布局
+#[repr(C)]
+#[cfg_attr(target_arch = "x86_64", repr(packed))]
+#[derive(Clone, Copy, Default)]
+pub struct EpollEvent {
+    pub events: u32,
+    pub data: u64,
+}
+
+/// 挂在被监视文件上的回调
+struct ItemWaiter {
+    /// 上次报告之后收到过通知
+    pending: AtomicBool,
+    epoll: Weak<PollQueue>,
+}
+
+impl PollWaiter for ItemWaiter {
+    fn wake(&self, _events: PollEvents) {
+        self.pending.store(true, Ordering::SeqCst);
+        if let Some(queue) = self.epoll.upgrade() {
+            queue.notify(PollEvents::POLLIN);
+        }
+    }
+}
+
+struct EpollItem {
+    /// 文件关闭后该项自动失效
+    file: Weak<dyn File>,
+    events: EpollEvents,
+    data: u64,
+    waiter: Arc<dyn PollWaiter>,
+    item_waiter: Arc<ItemWaiter>,
+    /// EPOLLONESHOT 的项报告过一次
+    disabled: bool,
+}
+
+impl EpollItem {
+    /// 当前就绪的事件, `consume` 时清掉边沿标记
+    fn ready(&self, consume: bool) -> EpollEvents {
+        if self.disabled {
+            return EpollEvents::empty();
+        }
+        let file = match self.file.upgrade() {
+            Some(file) => file,
+            None => return EpollEvents::empty(),
+        };
+        let edge = self.events.contains(EpollEvents::EPOLLET) && file.poll_queue().is_some();
+        if edge {
+            let pending = if consume {
+                self.item_waiter.pending.swap(false, Ordering::SeqCst)
+            } else {
+                self.item_waiter.pending.load(Ordering::SeqCst)
+            };
+            if !pending {
+                return EpollEvents::empty();
+            }
+        }
+        let wanted = self.events | EpollEvents::EPOLLERR | EpollEvents::EPOLLHUP;
+        let revents = file.poll(PollEvents::from_bits_truncate(wanted.bits() as i16));
+        EpollEvents::from_bits_truncate(revents.bits() as u16 as u32) & wanted
+    }
+}
+
+/// 以 (fd, 文件地址) 区分被监视的文件
+type ItemKey = (i32, usize);
+
+fn file_key(file: &Arc<dyn File>) -> usize {
+    Arc::as_ptr(file) as *const () as usize
+}
+
+pub struct EpollFile {
+    inner: FileInner,
+    items: Mutex<BTreeMap<ItemKey, EpollItem>>,
+    /// 上次报告的最后一项, 下次从它后面开始扫描, 避免低编号的 fd 饿死其他项
+    cursor: Mutex<Option<ItemKey>>,
+    queue: Arc<PollQueue>,
+}
+
+impl EpollFile {
+    pub fn new(superblock: Arc<dyn SuperBlock>) -> Arc<Self> {
+        Arc::new(Self {
+            inner: FileInner::new(anon_dentry("eventpoll", superblock)),
+            items: Mutex::new(BTreeMap::new()),
+            cursor: Mutex::new(None),
+            queue: Arc::new(PollQueue::new()),
+        })
+    }
+    /// 从 self 出发能否走到 `target`, 过深的嵌套同样视为成环
+    fn reaches(&self, target: *const EpollFile, depth: usize) -> bool {
+        if core::ptr::eq(self, target) || depth > EPOLL_MAX_NESTS {
+            return true;
+        }
+        let children: Vec<Arc<dyn File>> = self.items.lock().values().filter_map(|item| item.file.upgrade()).collect();
+        children.into_iter().any(|file| match file.downcast_arc::<EpollFile>() {
+            Ok(epoll) => epoll.reaches(target, depth + 1),
+            Err(_) => false,
+        })
+    }
+    pub fn ctl_add(&self, fd: i32, file: &Arc<dyn File>, events: EpollEvents, data: u64) -> SysResult<()> {
+        if let Ok(epoll) = file.clone().downcast_arc::<EpollFile>() {
+            if core::ptr::eq(Arc::as_ptr(&epoll), self) {
+                return Err(SysError::EINVAL);
+            }
+            if epoll.reaches(self, 1) {
+                return Err(SysError::ELOOP);
+            }
+        }
+        let mut items = self.items.lock();
+        let key = (fd, file_key(file));
+        if items.contains_key(&key) {
+            return Err(SysError::EEXIST);
+        }
+        let item_waiter = Arc::new(ItemWaiter {
+            // 加入时文件可能已经就绪, 边沿触发的项也要报告一次
+            pending: AtomicBool::new(true),
+            epoll: Arc::downgrade(&self.queue),
+        });
+        let waiter: Arc<dyn PollWaiter> = item_waiter.clone();
+        if let Some(queue) = file.poll_queue() {
+            queue.register(&waiter, events.contains(EpollEvents::EPOLLEXCLUSIVE));
+        }
+        items.insert(key, EpollItem {
+            file: Arc::downgrade(file),
+            events,
+            data,
+            waiter,
+            item_waiter,
+            disabled: false,
+        });
+        drop(items);
+        self.queue.notify(PollEvents::POLLIN);
+        Ok(())
+    }
+    pub fn ctl_mod(&self, fd: i32, file: &Arc<dyn File>, events: EpollEvents, data: u64) -> SysResult<()> {
+        let mut items = self.items.lock();
+        let item = items.get_mut(&(fd, file_key(file))).ok_or(SysError::ENOENT)?;
+        if item.events.contains(EpollEvents::EPOLLEXCLUSIVE) {
+            return Err(SysError::EINVAL);
+        }
+        item.events = events;
+        item.data = data;
+        item.disabled = false;
+        item.item_waiter.pending.store(true, Ordering::SeqCst);
+        drop(items);
+        self.queue.notify(PollEvents::POLLIN);
+        Ok(())
+    }
+    pub fn ctl_del(&self, fd: i32, file: &Arc<dyn File>) -> SysResult<()> {
+        let item = self.items.lock().remove(&(fd, file_key(file))).ok_or(SysError::ENOENT)?;
+        if let Some(queue) = file.poll_queue() {
+            queue.unregister(&item.waiter);
+        }
+        Ok(())
+    }
+    /// 取出最多 `max` 个就绪事件
+    pub fn collect(&self, max: usize) -> Vec<EpollEvent> {
+        let mut items = self.items.lock();
+        items.retain(|_, item| item.file.strong_count() > 0);
+        let mut cursor = self.cursor.lock();
+        let mut keys: Vec<ItemKey> = items.keys().copied().collect();
+        if let Some(last) = *cursor {
+            let start = keys.partition_point(|key| *key <= last);
+            keys.rotate_left(start);
+        }
+        let mut ready = Vec::new();
+        for key in keys {
+            if ready.len() >= max {
+                break;
+            }
+            let item = items.get_mut(&key).unwrap();
+            let revents = item.ready(true);
+            if revents.is_empty() {
+                continue;
+            }
+            if item.events.contains(EpollEvents::EPOLLONESHOT) {
+                item.disabled = true;
+            }
+            ready.push(EpollEvent {
+                events: revents.bits(),
+                data: item.data,
+            });
+            *cursor = Some(key);
+        }
+        ready
+    }
+    /// 只有所有被监视的文件都能通知时, 等待 epoll 才能真正阻塞
+    fn can_block(&self) -> bool {
+        self.items
+            .lock()
+            .values()
+            .filter_map(|item| item.file.upgrade())
+            .all(|file| file.poll_queue().is_some())
+    }
+}
+
+impl File for EpollFile {
+    fn get_inner(&self) -> &FileInner {
+        &self.inner
+    }
+    fn readable(&self) -> bool {
+        false
+    }
+    fn writable(&self) -> bool {
+        false
+    }
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> usize {
+        0
+    }
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> usize {
+        0
+    }
+    fn pread(&self, _offset: usize, _buf: &mut [u8]) -> SysResult<usize> {
+        Err(SysError::ESPIPE)
+    }
+    fn pwrite(&self, _offset: usize, _buf: &[u8]) -> SysResult<usize> {
+        Err(SysError::ESPIPE)
+    }
+    fn read_checked(&self, _buf: &mut [u8]) -> SysResult<usize> {
+        Err(SysError::EINVAL)
+    }
+    fn write_checked(&self, _buf: &[u8]) -> SysResult<usize> {
+        Err(SysError::EINVAL)
+    }
+    fn poll(&self, events: PollEvents) -> PollEvents {
+        let ready = self.items.lock().values().any(|item| !item.ready(false).is_empty());
+        if ready {
+            (PollEvents::POLLIN | PollEvents::POLLRDNORM) & events
+        } else {
+            PollEvents::empty()
+        }
+    }
+    fn poll_queue(&self) -> Option<&PollQueue> {
+        if self.can_block() {
+            Some(&self.queue)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! eventfd: 由内核维护的 64 位计数器
+use super::{anon_dentry, wait_event};
+use alloc::sync::Arc;
+use sync::Mutex;
+use system_result::{SysError, SysResult};
+use vfs_defs::{File, FileInner, OpenFlags, PollEvents, PollQueue, SuperBlock};
+
+/// 每次读只取 1
+pub const EFD_SEMAPHORE: u32 = 1;
+
+/// 计数器上限, 达到后写者阻塞
+const EVENTFD_MAX: u64 = u64::MAX - 1;
+
+pub struct EventFd {
+    inner: FileInner,
+    count: Mutex<u64>,
+    semaphore: bool,
+    queue: PollQueue,
+}
+
+impl EventFd {
+    pub fn new(initval: u64, semaphore: bool, superblock: Arc<dyn SuperBlock>) -> Arc<Self> {
+        Arc::new(Self {
+            inner: FileInner::new(anon_dentry("eventfd", superblock)),
+            count: Mutex::new(initval),
+            semaphore,
+            queue: PollQueue::new(),
+        })
+    }
+    fn nonblock(&self) -> bool {
+        self.inner.flags.lock().contains(OpenFlags::NONBLOCK)
+    }
+    fn try_read(&self) -> Option<u64> {
+        let mut count = self.count.lock();
+        if *count == 0 {
+            return None;
+        }
+        let value = if self.semaphore { 1 } else { *count };
+        *count -= value;
+        Some(value)
+    }
+    fn try_write(&self, value: u64) -> bool {
+        let mut count = self.count.lock();
+        if EVENTFD_MAX - *count < value {
+            return false;
+        }
+        *count += value;
+        true
+    }
+}
+
+impl File for EventFd {
+    fn get_inner(&self) -> &FileInner {
+        &self.inner
+    }
+    fn readable(&self) -> bool {
+        true
+    }
+    fn writable(&self) -> bool {
+        true
+    }
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> usize {
+        0
+    }
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> usize {
+        0
+    }
+    fn pread(&self, _offset: usize, _buf: &mut [u8]) -> SysResult<usize> {
+        Err(SysError::ESPIPE)
+    }
+    fn pwrite(&self, _offset: usize, _buf: &[u8]) -> SysResult<usize> {
+        Err(SysError::ESPIPE)
+    }
+    fn read_checked(&self, buf: &mut [u8]) -> SysResult<usize> {
+        if buf.len() < 8 {
+            return Err(SysError::EINVAL);
+        }
+        let mut value = 0;
+        match self.try_read() {
+            Some(v) => value = v,
+            None if self.nonblock() => return Err(SysError::EAGAIN),
+            None => {
+                wait_event(&self.queue, || match self.try_read() {
+                    Some(v) => {
+                        value = v;
+                        1
+                    }
+                    None => 0,
+                })?;
+            }
+        }
+        buf[..8].copy_from_slice(&value.to_ne_bytes());
+        self.queue.notify(PollEvents::POLLOUT);
+        Ok(8)
+    }
+    fn write_checked(&self, buf: &[u8]) -> SysResult<usize> {
+        if buf.len() < 8 {
+            return Err(SysError::EINVAL);
+        }
+        let value = u64::from_ne_bytes(buf[..8].try_into().unwrap());
+        if value == u64::MAX {
+            return Err(SysError::EINVAL);
+        }
+        if !self.try_write(value) {
+            if self.nonblock() {
+                return Err(SysError::EAGAIN);
+            }
+            wait_event(&self.queue, || self.try_write(value) as usize)?;
+        }
+        self.queue.notify(PollEvents::POLLIN);
+        Ok(8)
+    }
+    fn poll(&self, events: PollEvents) -> PollEvents {
+        let count = *self.count.lock();
+        let mut revents = PollEvents::empty();
+        if count > 0 {
+            revents |= PollEvents::POLLIN | PollEvents::POLLRDNORM;
+        }
+        if count < EVENTFD_MAX {
+            revents |= PollEvents::POLLOUT | PollEvents::POLLWRNORM;
+        }
+        revents & events
+    }
+    fn poll_queue(&self) -> Option<&PollQueue> {
+        Some(&self.queue)
+    }
+}
@@ -7,7 +7,7 @@
 use core::str::ParseBoolError;
 
 //use super::File;
-use crate::{drivers::BLOCK_DEVICE, task::current_task};
+use crate::task::current_task;
 use crate::sync::UPSafeCell;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
@@ -16,8 +16,9 @@ use easy_fs::{EasyFileSystem,DIRENT_SZ,INODE_MANAGER};
 use lazy_static::*;
 use spin::Mutex;
 use alloc::string::String;
-use vfs::get_root_dentry;
-use vfs_defs::{Inode,DiskInodeType,File,OpenFlags,Dentry};
+use super::open_fifo;
+use vfs::{check_writable, find_device, get_root_dentry, inode_permission, is_symlink, may_modify_dir, mount_flags, namei, namei_create, namei_parent, FsCred, MAY_READ, MAY_WRITE};
+use vfs_defs::{Inode,DiskInodeType,File,OpenFlags,Dentry,InodeMode,MountFlags};
 use system_result::{SysResult,SysError};
 
 
@@ -74,14 +75,80 @@ pub fn list_apps() {
     }
    println!("**************/");*/
 }
-///
-pub fn create_file(path:&str,type_:DiskInodeType)->SysResult<Arc<dyn Dentry>>{
-    if let Ok(inode) = path_to_dentry(path){
-        return Ok(inode);
+/// 当前任务的根目录和工作目录, 内核初始化时还没有任务, 都取全局根目录
+/// 同时取出文件系统访问身份
+fn current_root_cwd() -> (Arc<dyn Dentry>, Arc<dyn Dentry>, FsCred) {
+    match current_task() {
+        Some(task) => {
+            let inner = task.inner_exclusive_access();
+            (inner.root.clone(), inner.cwd.clone(), inner.cred.fs_cred())
+        }
+        None => {
+            let root = get_root_dentry();
+            (root.clone(), root, FsCred::root())
+        }
+    }
+}
+/// 当前任务访问文件系统使用的身份
+pub fn current_fs_cred() -> FsCred {
+    current_root_cwd().2
+}
+/// 解析 `path`, 相对路径从 `base` 开始, `base` 为空时从工作目录开始
+pub fn lookup_at(base: Option<Arc<dyn Dentry>>, path: &str, follow: bool) -> SysResult<Arc<dyn Dentry>> {
+    let (root, cwd, cred) = current_root_cwd();
+    namei(&root, &base.unwrap_or(cwd), path, follow, &cred)
+}
+/// 解析 `path` 的父目录, 返回父目录和最后一个分量
+pub fn lookup_parent_at(base: Option<Arc<dyn Dentry>>, path: &str) -> SysResult<(Arc<dyn Dentry>, String)> {
+    let (root, cwd, cred) = current_root_cwd();
+    namei_parent(&root, &base.unwrap_or(cwd), path, &cred)
+}
+/// 当前任务的 umask, 内核自己建的文件不受限制
+fn current_umask() -> u32 {
+    current_task().map_or(0, |task| task.inner_exclusive_access().umask)
+}
+/// 新建的文件归当前任务所有, 权限位去掉 umask, 在 setgid 目录下继承目录的属组.
+/// 符号链接的权限位没有意义, 不受 umask 影响
+pub fn set_new_owner(parent: &Arc<dyn Dentry>, dentry: &Arc<dyn Dentry>, perm: InodeMode) -> SysResult<()> {
+    let cred = current_fs_cred();
+    let (parent_mode, parent_gid) = {
+        let inode = parent.get_inode()?;
+        let inner = inode.get_meta().inner.lock();
+        (inner.mode, inner.gid)
+    };
+    let inode = dentry.get_inode()?;
+    let mut perm = perm;
+    if !is_symlink(dentry) {
+        perm.remove(InodeMode::from_bits_truncate(current_umask()));
+    }
+    let gid = if parent_mode.contains(InodeMode::SET_GID) {
+        if inode.is_dir() {
+            perm |= InodeMode::SET_GID;
+        }
+        parent_gid
+    } else {
+        cred.gid
+    };
+    inode.set_perm(perm)?;
+    inode.set_owner(cred.uid, gid)
+}
+/// 在 `path` 处新建文件, `perm` 为新建文件的权限位. 已存在时返回 EEXIST
+pub fn create_file_at(base: Option<Arc<dyn Dentry>>, path:&str, type_:DiskInodeType, perm: InodeMode)->SysResult<Arc<dyn Dentry>>{
+    let (parent, name) = lookup_parent_at(base, path)?;
+    create_in(&parent, &name, type_, perm)
+}
+/// 在 `parent` 下新建 `name`
+fn create_in(parent: &Arc<dyn Dentry>, name: &str, type_:DiskInodeType, perm: InodeMode)->SysResult<Arc<dyn Dentry>>{
+    if name == "." || name == ".." {
+        return Err(SysError::EEXIST);
+    }
+    if parent.lookup(name).is_ok() {
+        return Err(SysError::EEXIST);
     }
-    let mut name = String::new();
-    let parent = path_to_father_dentry(path,&mut name)?;
-    let dentry = parent.create(name.as_str(),type_).unwrap();
+    check_writable(parent)?;
+    may_modify_dir(parent, &current_fs_cred())?;
+    let dentry = parent.create(name,type_)?;
+    set_new_owner(parent, &dentry, perm)?;
     if type_ == DiskInodeType::Directory{
         let current = dentry.find_or_create(".", DiskInodeType::Directory);
         let mut res = dentry.link(&current);
@@ -96,24 +163,87 @@ pub fn create_file(path:&str,type_:DiskInodeType)->SysResult<Arc<dyn Dentry>>{
     }
     return Ok(dentry);
 }
-///Open file with flags
-pub fn open_file(path: &str, flags: OpenFlags) -> SysResult<Arc<dyn File>>{//还需增加对设备文件的支持
-    let ret;
-    if flags.contains(OpenFlags::CREATE) {// create file
-        let dentry = create_file(path, DiskInodeType::File)?;
-        dentry.get_inode().unwrap().clear();
-        ret = dentry.open(flags);
-    } else {
-        let dentry = path_to_dentry(path)?;
-        if dentry.is_dir() && ((flags.bits()&OpenFlags::RDONLY.bits()) != OpenFlags::RDONLY.bits()){
-            return Err(SysError::EACCES);
+///
+pub fn create_file(path:&str,type_:DiskInodeType)->SysResult<Arc<dyn Dentry>>{
+    create_file_at(None, path, type_, InodeMode::from_type(type_))
+}
+///Open file with flags, 相对路径从 `base` 开始
+pub fn open_file_at(base: Option<Arc<dyn Dentry>>, path: &str, flags: OpenFlags, perm: InodeMode) -> SysResult<Arc<dyn File>>{
+    let create = flags.contains(OpenFlags::CREATE);
+    let excl = create && flags.contains(OpenFlags::EXCL);
+    // O_CREAT|O_EXCL 不跟随末尾的符号链接
+    let follow = !flags.contains(OpenFlags::NOFOLLOW) && !excl;
+    let dentry = match lookup_at(base.clone(), path, follow) {
+        Ok(dentry) => {
+            if excl {
+                return Err(SysError::EEXIST);
+            }
+            dentry
         }
-        if flags.contains(OpenFlags::TRUNC) && dentry.is_file(){
-            dentry.get_inode().unwrap().clear();
+        Err(SysError::ENOENT) if create => {
+            // 末尾是悬空的符号链接时新建链接指向的文件
+            let (root, cwd, cred) = current_root_cwd();
+            let (parent, name) = namei_create(&root, &base.unwrap_or(cwd), path, &cred)?;
+            match create_in(&parent, &name, DiskInodeType::File, perm) {
+                Ok(dentry) => {
+                    dentry.get_inode()?.clear();
+                    return Ok(dentry.open(flags));
+                }
+                // 别人抢先建好了, 按已存在的文件做权限检查后打开
+                Err(SysError::EEXIST) => parent.lookup(name.as_str())?,
+                Err(e) => return Err(e),
+            }
         }
-        ret = dentry.open(flags);
-    }  
-    Ok(ret)
+        Err(e) => return Err(e),
+    };
+    if is_symlink(&dentry) {
+        return Err(SysError::ELOOP);
+    }
+    if flags.contains(OpenFlags::DIRECTORY) && !dentry.is_dir() {
+        return Err(SysError::ENOTDIR);
+    }
+    if dentry.is_dir() && ((flags.bits()&OpenFlags::RDONLY.bits()) != OpenFlags::RDONLY.bits()){
+        return Err(SysError::EACCES);
+    }
+    let write = flags.intersects(OpenFlags::WRONLY | OpenFlags::RDWR) || flags.contains(OpenFlags::TRUNC);
+    if write {
+        check_writable(&dentry)?;
+    }
+    let mut mask = if write { MAY_WRITE } else { 0 };
+    if !flags.contains(OpenFlags::WRONLY) {
+        mask |= MAY_READ;
+    }
+    inode_permission(&dentry.get_inode()?, &current_fs_cred(), mask)?;
+    // nodev 挂载上的设备文件不可打开
+    let file_type = dentry.get_inode()?.get_meta().mode & InodeMode::TYPE_MASK;
+    if (file_type == InodeMode::CHAR || file_type == InodeMode::BLOCK)
+        && mount_flags(&dentry).contains(MountFlags::MS_NODEV)
+    {
+        return Err(SysError::EACCES);
+    }
+    if file_type == InodeMode::FIFO {
+        return open_fifo(dentry, flags);
+    }
+    if file_type == InodeMode::SOCKET {
+        return Err(SysError::ENXIO);
+    }
+    if file_type == InodeMode::CHAR || file_type == InodeMode::BLOCK {
+        // 其它文件系统上的设备节点按设备号转到 devfs 里的驱动
+        let rdev = dentry.get_inode()?.get_meta().inner.lock().rdev;
+        if rdev != 0 {
+            return Ok(find_device(rdev).ok_or(SysError::ENXIO)?.open(flags));
+        }
+    }
+    if flags.contains(OpenFlags::TRUNC) && file_type == InodeMode::FILE {
+        let inode = dentry.get_inode()?;
+        let _guard = inode.get_meta().write_lock.lock();
+        inode.truncate(0)?;
+    }
+    Ok(dentry.open(flags))
+}
+///Open file with flags
+pub fn open_file(path: &str, flags: OpenFlags) -> SysResult<Arc<dyn File>>{
+    open_file_at(None, path, flags, InodeMode::from_type(DiskInodeType::File))
 }
 /*
 impl File for OSInode {
@@ -149,80 +279,13 @@ impl File for OSInode {
     }
 }
  */
-fn skipelem<'a>(path: &'a str, name: &mut String) -> Option<&'a str> {
-    // 跳过开头的斜杠
-    let mut path = path.trim_start_matches('/');
-    
-    if path.is_empty() {
-        return None;
-    }
-
-    // 找到下一个斜杠或者结束
-    let end_pos = path.find('/').unwrap_or(path.len());
-
-    // 获取路径元素并存入 `name`
-    let elem = &path[..end_pos];
-    if elem.len() >= 32 {
-        // 需要截断
-        name.clear();
-        name.push_str(&elem[..32]);
-    } else {
-        name.clear();
-        name.push_str(elem);
-    }
-
-    // 返回剩余的路径部分，跳过一个斜杠
-    path = &path[end_pos..].trim_start_matches('/');
-
-    Some(path)
-}
-fn path_to_dirent_(path:&str,
-                  to_father:bool,
-                  name:&mut String,
-                )->SysResult<Arc<dyn Dentry>>{
-  //  let efs =  fs.lock();
-    let mut dentry ;
-    let mut current = path;
-    if path.chars().next() == Some('/'){
-        dentry = get_root_dentry();
-    }
-    else{
-            //获得当前进程的cwd
-        if let Some(current_task) = current_task(){
-            dentry = current_task.inner_exclusive_access().cwd.clone();
-        }
-        else{
-            dentry = get_root_dentry();
-        }
-    }
-    while let Some(new_path) = skipelem(current,name){
-        current = new_path;
-        if name == "."{
-            continue;
-        }
-        if name == ".."{
-            if let Some(father) = dentry.get_father(){
-                dentry = father;
-                continue;
-            }else{
-                return Err(SysError::ENOENT);
-            }
-        }
-        if to_father && current.len() == 0 {
-            return Ok(dentry);
-        }
-        dentry = dentry.lookup(name)?;
-    }
-    return Ok(dentry);
-
-}
 /// get inode from path,get a clone of inode's Arc
 pub fn path_to_dentry(path:&str)->SysResult<Arc<dyn Dentry>>{
-    let mut name = String::new();
-    path_to_dirent_(path, false,&mut name)
+    lookup_at(None, path, true)
 }
 /// get father inode from path ,get a clone of inode's Arc
 pub fn path_to_father_dentry(path:&str,name:&mut String)->SysResult<Arc<dyn Dentry>>{
-    path_to_dirent_(path, true,name)
-
+    let (parent, last) = lookup_parent_at(None, path)?;
+    *name = last;
+    Ok(parent)
 }
@@ -0,0 +1,391 @@
+//! 建议锁: flock 整文件锁与 POSIX/OFD 记录锁
+//!
+//! 每个被加过锁的 inode 对应一张锁表. flock 锁和记录锁互不影响.
+//! POSIX 锁属于进程, 进程关闭任一指向该 inode 的描述符或退出时释放;
+//! flock 锁和 OFD 锁属于打开文件, 最后一个引用消失时释放.
+use super::wait_event;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use sync::Mutex;
+use system_result::{SysError, SysResult};
+use vfs_defs::{major, minor, File, PollEvents, PollQueue};
+
+pub const LOCK_SH: u32 = 1;
+pub const LOCK_EX: u32 = 2;
+pub const LOCK_NB: u32 = 4;
+pub const LOCK_UN: u32 = 8;
+
+/// 死锁检测沿等待链最多走的步数
+const MAX_DEADLK_ITERATIONS: usize = 10;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LockKind {
+    Flock,
+    Posix,
+    Ofd,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Owner {
+    /// POSIX 锁的持有者是进程
+    Process(usize),
+    /// flock 和 OFD 锁的持有者是打开文件
+    File(usize),
+}
+
+#[derive(Clone)]
+struct FileLock {
+    kind: LockKind,
+    owner: Owner,
+    /// 打开文件已释放的 flock/OFD 锁视为不存在
+    file: Weak<dyn File>,
+    pid: usize,
+    exclusive: bool,
+    start: u64,
+    /// 闭区间, u64::MAX 表示直到文件末尾
+    end: u64,
+}
+
+impl FileLock {
+    fn is_record(&self) -> bool {
+        self.kind != LockKind::Flock
+    }
+    fn alive(&self) -> bool {
+        matches!(self.owner, Owner::Process(_)) || self.file.strong_count() > 0
+    }
+    fn overlaps(&self, start: u64, end: u64) -> bool {
+        self.start <= end && start <= self.end
+    }
+    fn conflicts(&self, other: &FileLock) -> bool {
+        self.is_record() == other.is_record()
+            && self.owner != other.owner
+            && (self.exclusive || other.exclusive)
+            && self.overlaps(other.start, other.end)
+    }
+}
+
+/// 一个 inode 上的锁
+struct InodeLocks {
+    dev: u64,
+    ino: u64,
+    locks: Mutex<Vec<FileLock>>,
+    /// 有锁被释放时唤醒等待者
+    queue: PollQueue,
+}
+
+/// 以 (设备号, inode 号) 为键的锁表
+static LOCKS: Mutex<BTreeMap<(u64, u64), Arc<InodeLocks>>> = Mutex::new(BTreeMap::new());
+/// 正在等待 POSIX 锁的进程 -> 挡住它的进程, 用于死锁检测
+static BLOCKED: Mutex<BTreeMap<usize, usize>> = Mutex::new(BTreeMap::new());
+
+fn inode_key(file: &Arc<dyn File>) -> SysResult<(u64, u64)> {
+    let stat = file.get_dentry().get_inode()?.get_attr()?;
+    Ok((stat.st_dev, stat.st_ino))
+}
+
+fn file_addr(file: &Arc<dyn File>) -> usize {
+    Arc::as_ptr(file) as *const () as usize
+}
+
+fn inode_locks(file: &Arc<dyn File>) -> SysResult<Arc<InodeLocks>> {
+    let (dev, ino) = inode_key(file)?;
+    let mut table = LOCKS.lock();
+    Ok(table
+        .entry((dev, ino))
+        .or_insert_with(|| {
+            Arc::new(InodeLocks {
+                dev,
+                ino,
+                locks: Mutex::new(Vec::new()),
+                queue: PollQueue::new(),
+            })
+        })
+        .clone())
+}
+
+/// 释放满足 `pred` 的锁并唤醒等待者, 锁表空了且没人等待时删掉
+fn release_where(key: (u64, u64), pred: impl Fn(&FileLock) -> bool) {
+    let mut table = LOCKS.lock();
+    let list = match table.get(&key) {
+        Some(list) => list.clone(),
+        None => return,
+    };
+    let mut locks = list.locks.lock();
+    let before = locks.len();
+    locks.retain(|lock| lock.alive() && !pred(lock));
+    let released = locks.len() != before;
+    let empty = locks.is_empty();
+    drop(locks);
+    // 表里一份, 这里一份, 再多就是有人在等
+    if empty && Arc::strong_count(&list) == 2 {
+        table.remove(&key);
+    }
+    drop(table);
+    if released {
+        list.queue.notify(PollEvents::POLLIN);
+    }
+}
+
+/// 把 `new` 放进锁表. 记录锁先挖掉同一持有者重叠的部分, 再与相邻的同类锁合并;
+/// `unlock` 时只挖不放
+fn insert_lock(locks: &mut Vec<FileLock>, new: FileLock, unlock: bool) {
+    if !new.is_record() {
+        locks.retain(|lock| !(lock.kind == LockKind::Flock && lock.owner == new.owner));
+        if !unlock {
+            locks.push(new);
+        }
+        return;
+    }
+    let mut kept = Vec::new();
+    for lock in locks.drain(..) {
+        if !lock.is_record() || lock.owner != new.owner || !lock.overlaps(new.start, new.end) {
+            kept.push(lock);
+            continue;
+        }
+        if lock.start < new.start {
+            kept.push(FileLock { end: new.start - 1, ..lock.clone() });
+        }
+        if lock.end > new.end {
+            kept.push(FileLock { start: new.end + 1, ..lock });
+        }
+    }
+    if !unlock {
+        let mut merged = new;
+        kept.retain(|lock| {
+            let adjacent = lock.start <= merged.end.saturating_add(1) && merged.start <= lock.end.saturating_add(1);
+            if lock.is_record() && lock.owner == merged.owner && lock.exclusive == merged.exclusive && adjacent {
+                merged.start = merged.start.min(lock.start);
+                merged.end = merged.end.max(lock.end);
+                false
+            } else {
+                true
+            }
+        });
+        kept.push(merged);
+    }
+    *locks = kept;
+}
+
+/// 沿等待链从 `blocker` 出发能否回到 `pid`
+fn would_deadlock(pid: usize, blocker: usize) -> bool {
+    let blocked = BLOCKED.lock();
+    let mut owner = blocker;
+    for _ in 0..MAX_DEADLK_ITERATIONS {
+        if owner == pid {
+            return true;
+        }
+        match blocked.get(&owner) {
+            Some(next) => owner = *next,
+            None => return false,
+        }
+    }
+    false
+}
+
+/// 尝试加锁一次. 需要等待时返回 None
+fn try_acquire(list: &InodeLocks, lock: &FileLock, wait: bool) -> Option<SysResult<()>> {
+    let mut locks = list.locks.lock();
+    locks.retain(|held| held.alive());
+    let blocker = match locks.iter().find(|held| held.conflicts(lock)) {
+        None => {
+            insert_lock(&mut locks, lock.clone(), false);
+            return Some(Ok(()));
+        }
+        Some(_) if !wait => return Some(Err(SysError::EAGAIN)),
+        Some(held) => held.owner,
+    };
+    if let (LockKind::Posix, Owner::Process(blocker)) = (lock.kind, blocker) {
+        if would_deadlock(lock.pid, blocker) {
+            return Some(Err(SysError::EDEADLK));
+        }
+        BLOCKED.lock().insert(lock.pid, blocker);
+    }
+    None
+}
+
+fn acquire(list: &Arc<InodeLocks>, lock: FileLock, wait: bool) -> SysResult<()> {
+    if let Some(result) = try_acquire(list, &lock, wait) {
+        return result;
+    }
+    let mut outcome = Ok(());
+    let ret = wait_event(&list.queue, || match try_acquire(list, &lock, true) {
+        Some(result) => {
+            outcome = result;
+            1
+        }
+        None => 0,
+    });
+    if lock.kind == LockKind::Posix {
+        BLOCKED.lock().remove(&lock.pid);
+    }
+    ret?;
+    outcome
+}
+
+/// flock(2)
+pub fn flock(file: &Arc<dyn File>, op: u32) -> SysResult<()> {
+    let exclusive = match op & !LOCK_NB {
+        LOCK_SH => false,
+        LOCK_EX => true,
+        LOCK_UN => {
+            let addr = file_addr(file);
+            release_where(inode_key(file)?, |lock| lock.kind == LockKind::Flock && lock.owner == Owner::File(addr));
+            return Ok(());
+        }
+        _ => return Err(SysError::EINVAL),
+    };
+    let list = inode_locks(file)?;
+    let lock = FileLock {
+        kind: LockKind::Flock,
+        owner: Owner::File(file_addr(file)),
+        file: Arc::downgrade(file),
+        pid: crate::task::current_task().unwrap().getpid(),
+        exclusive,
+        start: 0,
+        end: u64::MAX,
+    };
+    {
+        let mut locks = list.locks.lock();
+        let held = locks
+            .iter()
+            .find(|held| held.kind == LockKind::Flock && held.owner == lock.owner)
+            .map(|held| held.exclusive);
+        match held {
+            Some(held) if held == exclusive => return Ok(()),
+            Some(_) => {
+                // 与 Linux 一样, 转换锁类型时先放掉原来的锁, 不是原子的
+                insert_lock(&mut locks, lock.clone(), true);
+                drop(locks);
+                list.queue.notify(PollEvents::POLLIN);
+            }
+            None => {}
+        }
+    }
+    acquire(&list, lock, op & LOCK_NB == 0)
+}
+
+fn record_lock(file: &Arc<dyn File>, kind: LockKind, pid: usize, exclusive: bool, start: u64, end: u64) -> FileLock {
+    let owner = match kind {
+        LockKind::Posix => Owner::Process(pid),
+        _ => Owner::File(file_addr(file)),
+    };
+    FileLock {
+        kind,
+        owner,
+        file: Arc::downgrade(file),
+        pid,
+        exclusive,
+        start,
+        end,
+    }
+}
+
+/// F_GETLK: 返回第一个会挡住该请求的锁 (独占, 起点, 终点, 进程号), OFD 锁的进程号为 -1
+pub fn test_record_lock(
+    file: &Arc<dyn File>,
+    kind: LockKind,
+    pid: usize,
+    exclusive: bool,
+    start: u64,
+    end: u64,
+) -> SysResult<Option<(bool, u64, u64, isize)>> {
+    let request = record_lock(file, kind, pid, exclusive, start, end);
+    let key = inode_key(file)?;
+    let list = match LOCKS.lock().get(&key) {
+        Some(list) => list.clone(),
+        None => return Ok(None),
+    };
+    let locks = list.locks.lock();
+    Ok(locks.iter().find(|held| held.alive() && held.conflicts(&request)).map(|held| {
+        let pid = if held.kind == LockKind::Ofd { -1 } else { held.pid as isize };
+        (held.exclusive, held.start, held.end, pid)
+    }))
+}
+
+/// F_SETLK/F_SETLKW. `exclusive` 为 None 表示解锁
+pub fn set_record_lock(
+    file: &Arc<dyn File>,
+    kind: LockKind,
+    pid: usize,
+    exclusive: Option<bool>,
+    start: u64,
+    end: u64,
+    wait: bool,
+) -> SysResult<()> {
+    let lock = record_lock(file, kind, pid, exclusive.unwrap_or(false), start, end);
+    match exclusive {
+        Some(_) => acquire(&inode_locks(file)?, lock, wait),
+        None => {
+            let list = inode_locks(file)?;
+            insert_lock(&mut list.locks.lock(), lock, true);
+            list.queue.notify(PollEvents::POLLIN);
+            let key = (list.dev, list.ino);
+            drop(list);
+            // 顺便删掉空了的锁表
+            release_where(key, |_| false);
+            Ok(())
+        }
+    }
+}
+
+/// 进程关闭了指向该文件 inode 的描述符, 释放它在这个 inode 上的 POSIX 锁
+pub fn release_posix_locks(file: &Arc<dyn File>, pid: usize) {
+    if LOCKS.lock().is_empty() {
+        return;
+    }
+    if let Ok(key) = inode_key(file) {
+        release_where(key, |lock| lock.owner == Owner::Process(pid));
+    }
+}
+
+/// 进程退出, 释放它的所有 POSIX 锁
+pub fn release_process_locks(pid: usize) {
+    let keys: Vec<(u64, u64)> = LOCKS.lock().keys().copied().collect();
+    for key in keys {
+        release_where(key, |lock| lock.owner == Owner::Process(pid));
+    }
+}
+
+/// 打开文件的最后一个引用将要消失, 释放它持有的 flock 和 OFD 锁
+pub fn release_file_locks(file: &Arc<dyn File>) {
+    let addr = file_addr(file);
+    let keys: Vec<(u64, u64)> = LOCKS.lock().keys().copied().collect();
+    for key in keys {
+        release_where(key, |lock| lock.owner == Owner::File(addr));
+    }
+}
+
+/// /proc/locks
+pub fn proc_locks() -> String {
+    let lists: Vec<Arc<InodeLocks>> = LOCKS.lock().values().cloned().collect();
+    let mut out = String::new();
+    let mut id = 0;
+    for list in lists {
+        for lock in list.locks.lock().iter().filter(|lock| lock.alive()) {
+            id += 1;
+            let class = match lock.kind {
+                LockKind::Flock => "FLOCK ",
+                LockKind::Posix => "POSIX ",
+                LockKind::Ofd => "OFDLCK",
+            };
+            let pid = if lock.kind == LockKind::Ofd { -1 } else { lock.pid as isize };
+            let end = if lock.end == u64::MAX { String::from("EOF") } else { format!("{}", lock.end) };
+            out += &format!(
+                "{}: {} ADVISORY  {} {} {:02x}:{:02x}:{} {} {}\n",
+                id,
+                class,
+                if lock.exclusive { "WRITE" } else { "READ " },
+                pid,
+                major(list.dev),
+                minor(list.dev),
+                list.ino,
+                lock.start,
+                end
+            );
+        }
+    }
+    out
+}
@@ -15,8 +15,27 @@ pub trait File: Send + Sync {
     fn write(&self, buf: UserBuffer) -> usize;
 } */
 
-pub use inode::{list_apps, open_file,path_to_dentry,path_to_father_dentry,create_file};
+pub use inode::{list_apps, open_file,open_file_at,path_to_dentry,path_to_father_dentry,create_file,create_file_at,lookup_at,lookup_parent_at,current_fs_cred,set_new_owner};
 pub use stdio::{Stdin, Stdout,StdioDentry,StdioInode,Stderr};
 /// pipe mod
 pub mod pipe;
-pub use pipe::{make_pipe,PipeDentry,PipeInode}; // 导出 make_pipe 函数
+pub use pipe::{make_pipe,open_fifo,splice_file_to_pipe,splice_pipe_to_file,splice_pipe_to_pipe,tee_pipe,Pipe,PipeDentry,PipeInode,PIPE_BUF}; // 导出 make_pipe 函数
+mod anon;
+pub use anon::{anon_dentry,pseudo_statfs};
+mod poll;
+pub use poll::{poll_wait,wait_event};
+mod epoll;
+pub use epoll::{EpollEvent,EpollEvents,EpollFile};
+mod eventfd;
+pub use eventfd::{EventFd,EFD_SEMAPHORE};
+mod signalfd;
+pub use signalfd::SignalFd;
+mod timerfd;
+pub use timerfd::TimerFd;
+mod lock;
+pub use lock::{
+    flock, proc_locks, release_file_locks, release_posix_locks, release_process_locks, set_record_lock, test_record_lock,
+    LockKind,
+};
+mod writeback;
+pub use writeback::init as writeback_init;
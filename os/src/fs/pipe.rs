@@ -1,24 +1,35 @@
 extern crate alloc; 
-use core::task::Poll;
 
+use crate::mm::{frame_alloc, FrameTracker};
+use crate::task::{current_has_pending_signal, send_signal_to_current, SignalFlags};
 use crate::suspend_current_and_run_next;
+use super::wait_event;
 use alloc::sync::{Arc,Weak}; 
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::vec::Vec;
+use arch::PAGE_SIZE;
 use sync::Mutex;
 use vfs_defs::DentryState;
 use vfs_defs::File;
-use vfs_defs::UserBuffer;
 use vfs_defs::FileInner;
-use vfs_defs::{Dentry,PollEvents,Inode,InodeMeta,DentryInner,OpenFlags,DiskInodeType,RenameFlags,Kstat,ino_alloc,InodeMode,SuperBlock};
+use vfs_defs::{Dentry,PollEvents,PollQueue,Inode,InodeMeta,DentryInner,OpenFlags,DiskInodeType,RenameFlags,Kstat,ino_alloc,InodeMode,SuperBlock};
 use alloc::string::String;
 use system_result::{SysError,SysResult};
-use crate::sync::UPSafeCell;
-const RING_BUFFER_SIZE: usize = 2048;
+
+/// 默认 16 页, 即 64 KiB
+pub const PIPE_DEF_BUFFERS: usize = 16;
+/// 不超过 PIPE_BUF 字节的写入不会与其它写者交错
+pub const PIPE_BUF: usize = PAGE_SIZE;
+/// 非特权进程能设置的最大容量, 与 /proc/sys/fs/pipe-max-size 的默认值相同
+pub const PIPE_MAX_SIZE: usize = 1 << 20;
 
 /// pipe
 pub struct Pipe {
     readable: bool,
     writable: bool,
     buffer: Arc<Mutex<PipeRingBuffer>>,
+    /// 两端共用的等待队列
+    queue: Arc<PollQueue>,
     inner: FileInner,
 }
 
@@ -28,6 +39,7 @@ impl Pipe {
         Self {
             readable: true,
             writable: false,
+            queue: buffer.lock().queue.clone(),
             buffer,
             inner: FileInner::new(dentry),
         }
@@ -37,42 +49,327 @@ impl Pipe {
         Self {
             readable: false,
             writable: true,
+            queue: buffer.lock().queue.clone(),
             buffer,
             inner: FileInner::new(dentry),
         }
     }
+    fn nonblock(&self) -> bool {
+        self.inner.flags.lock().contains(OpenFlags::NONBLOCK)
+    }
+    /// 两端是否共用同一个缓冲区
+    pub fn same_pipe(&self, other: &Pipe) -> bool {
+        Arc::ptr_eq(&self.buffer, &other.buffer)
+    }
+    /// F_GETPIPE_SZ
+    pub fn capacity(&self) -> usize {
+        self.buffer.lock().max_bufs * PAGE_SIZE
+    }
+    /// F_SETPIPE_SZ: 按页向上取到 2 的幂, 返回新的容量
+    pub fn set_capacity(&self, size: usize, privileged: bool) -> SysResult<usize> {
+        if size > i32::MAX as usize {
+            return Err(SysError::EINVAL);
+        }
+        let pages = size.div_ceil(PAGE_SIZE).max(1).next_power_of_two();
+        if pages * PAGE_SIZE > PIPE_MAX_SIZE && !privileged {
+            return Err(SysError::EPERM);
+        }
+        let mut ring_buffer = self.buffer.lock();
+        if ring_buffer.bufs.len() > pages {
+            return Err(SysError::EBUSY);
+        }
+        ring_buffer.max_bufs = pages;
+        Ok(pages * PAGE_SIZE)
+    }
+    /// 读管道, `nonblock` 为真时管道空了返回 EAGAIN
+    pub fn pipe_read(&self, buf: &mut [u8], nonblock: bool) -> SysResult<usize> {
+        if !self.readable {
+            return Err(SysError::EBADF);
+        }
+        if buf.is_empty() || !self.wait_for_data(nonblock)? {
+            return Ok(0);
+        }
+        let read = self.buffer.lock().read_bytes(buf);
+        self.queue.notify(PollEvents::POLLOUT);
+        Ok(read)
+    }
+    /// 写管道, `nonblock` 为真时管道满了返回 EAGAIN
+    pub fn pipe_write(&self, buf: &[u8], nonblock: bool) -> SysResult<usize> {
+        if !self.writable {
+            return Err(SysError::EBADF);
+        }
+        // 不超过 PIPE_BUF 的写入要等到能一次写完
+        let atomic = buf.len() <= PIPE_BUF;
+        let mut written = 0;
+        while written < buf.len() {
+            let mut ring_buffer = self.buffer.lock();
+            if ring_buffer.all_read_ends_closed() {
+                drop(ring_buffer);
+                send_signal_to_current(SignalFlags::SIGPIPE);
+                return if written > 0 { Ok(written) } else { Err(SysError::EPIPE) };
+            }
+            let room = ring_buffer.available_write();
+            if room > 0 && (!atomic || room >= buf.len()) {
+                let once = ring_buffer.write_bytes(&buf[written..]);
+                drop(ring_buffer);
+                if once == 0 {
+                    return if written > 0 { Ok(written) } else { Err(SysError::ENOMEM) };
+                }
+                self.queue.notify(PollEvents::POLLIN);
+                written += once;
+                continue;
+            }
+            drop(ring_buffer);
+            if nonblock {
+                return if written > 0 { Ok(written) } else { Err(SysError::EAGAIN) };
+            }
+            if current_has_pending_signal() {
+                return if written > 0 { Ok(written) } else { Err(SysError::EINTR) };
+            }
+            suspend_current_and_run_next();
+        }
+        Ok(written)
+    }
+    /// 等到管道中有数据, 返回 false 表示写端已经全部关闭
+    fn wait_for_data(&self, nonblock: bool) -> SysResult<bool> {
+        loop {
+            let ring_buffer = self.buffer.lock();
+            if ring_buffer.available_read() > 0 {
+                return Ok(true);
+            }
+            if ring_buffer.all_write_ends_closed() {
+                return Ok(false);
+            }
+            drop(ring_buffer);
+            if nonblock {
+                return Err(SysError::EAGAIN);
+            }
+            if current_has_pending_signal() {
+                return Err(SysError::EINTR);
+            }
+            suspend_current_and_run_next();
+        }
+    }
+    /// 等到管道中有空闲的页槽, 返回空闲槽数. 没有读者时发送 SIGPIPE 并返回 EPIPE
+    fn wait_for_room(&self, nonblock: bool) -> SysResult<usize> {
+        loop {
+            let ring_buffer = self.buffer.lock();
+            if ring_buffer.all_read_ends_closed() {
+                drop(ring_buffer);
+                send_signal_to_current(SignalFlags::SIGPIPE);
+                return Err(SysError::EPIPE);
+            }
+            let slots = ring_buffer.free_slots();
+            if slots > 0 {
+                return Ok(slots);
+            }
+            drop(ring_buffer);
+            if nonblock {
+                return Err(SysError::EAGAIN);
+            }
+            if current_has_pending_signal() {
+                return Err(SysError::EINTR);
+            }
+            suspend_current_and_run_next();
+        }
+    }
 }
 
-#[derive(Copy, Clone, PartialEq)]
-enum RingBufferStatus {
-    //FULL,
-    EMPTY,
-    NORMAL,
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        let mut ring_buffer = self.buffer.lock();
+        if self.readable {
+            ring_buffer.readers -= 1;
+        }
+        if self.writable {
+            ring_buffer.writers -= 1;
+        }
+        drop(ring_buffer);
+        // 对端等着的可能是 POLLHUP/POLLERR
+        self.queue.notify(PollEvents::POLLHUP | PollEvents::POLLERR);
+    }
+}
+
+/// 管道中的一段数据. tee 之后同一页可能挂在多个管道里, 共享的页不再追加写入
+#[derive(Clone)]
+pub struct PipeBuf {
+    page: Arc<FrameTracker>,
+    offset: usize,
+    len: usize,
 }
-///带有一定大小缓冲区的字节队列，我们抽象为 PipeRingBuffer 类型
+
+impl PipeBuf {
+    fn new() -> Option<Self> {
+        Some(Self {
+            page: Arc::new(frame_alloc()?),
+            offset: 0,
+            len: 0,
+        })
+    }
+    fn bytes(&self) -> &[u8] {
+        &self.page.ppn.get_buffer()[self.offset..self.offset + self.len]
+    }
+    /// 只有独占的页才能继续往后写
+    fn can_merge(&self) -> bool {
+        Arc::strong_count(&self.page) == 1 && self.offset + self.len < PAGE_SIZE
+    }
+    fn append(&mut self, buf: &[u8]) -> usize {
+        let start = self.offset + self.len;
+        let len = buf.len().min(PAGE_SIZE - start);
+        self.page.ppn.get_buffer()[start..start + len].copy_from_slice(&buf[..len]);
+        self.len += len;
+        len
+    }
+    /// 切出前 `len` 字节, 两半共享同一页
+    fn split_front(&mut self, len: usize) -> PipeBuf {
+        let front = PipeBuf {
+            page: self.page.clone(),
+            offset: self.offset,
+            len,
+        };
+        self.offset += len;
+        self.len -= len;
+        front
+    }
+}
+
+///按页组织的管道缓冲区, 最多容纳 `max_bufs` 页
 pub struct PipeRingBuffer {
-    arr: [u8; RING_BUFFER_SIZE],
-    head: usize,
-    tail: usize,
-    // a ring queue
-    status: RingBufferStatus,
-    write_end: Option<Weak<Pipe>>,
+    bufs: VecDeque<PipeBuf>,
+    max_bufs: usize,
+    queue: Arc<PollQueue>,
+    /// 当前打开的读端/写端数量
+    readers: usize,
+    writers: usize,
+    /// 历史上打开过的读端/写端次数, 打开 FIFO 时据此判断对端是否来过
+    read_opens: usize,
+    write_opens: usize,
 }
 
 impl PipeRingBuffer {
     /// new method
     pub fn new() -> Self {
         Self {
-            arr: [0; RING_BUFFER_SIZE],
-            head: 0,
-            tail: 0,
-            status: RingBufferStatus::EMPTY,
-            write_end: None,
+            bufs: VecDeque::new(),
+            max_bufs: PIPE_DEF_BUFFERS,
+            queue: Arc::new(PollQueue::new()),
+            readers: 0,
+            writers: 0,
+            read_opens: 0,
+            write_opens: 0,
+        }
+    }
+    /// 登记新打开的一端
+    fn open_end(&mut self, readable: bool, writable: bool) {
+        if readable {
+            self.readers += 1;
+            self.read_opens += 1;
+        }
+        if writable {
+            self.writers += 1;
+            self.write_opens += 1;
         }
     }
-    ///keep weak-ptr in write-end
-    pub fn set_write_end(&mut self, write_end: &Arc<Pipe>) {
-        self.write_end = Some(Arc::downgrade(write_end));
+    /// return available number of bytes
+    pub fn available_read(&self) -> usize {
+        self.bufs.iter().map(|buf| buf.len).sum()
+    }
+    /// 还能写入的字节数: 空闲页槽加上末页的剩余空间
+    pub fn available_write(&self) -> usize {
+        let tail_room = self.bufs.back()
+            .filter(|buf| buf.can_merge())
+            .map_or(0, |buf| PAGE_SIZE - buf.offset - buf.len);
+        self.free_slots() * PAGE_SIZE + tail_room
+    }
+    /// 空闲的页槽数
+    pub fn free_slots(&self) -> usize {
+        self.max_bufs.saturating_sub(self.bufs.len())
+    }
+    /// try to destroy pipe
+    pub fn all_write_ends_closed(&self) -> bool {
+        self.writers == 0
+    }
+    /// 读端全部关闭后写入的数据再也不会被读走
+    pub fn all_read_ends_closed(&self) -> bool {
+        self.readers == 0
+    }
+    /// 从队首拷出数据
+    fn read_bytes(&mut self, buf: &mut [u8]) -> usize {
+        let mut read = 0;
+        while read < buf.len() {
+            let Some(front) = self.bufs.front_mut() else {
+                break;
+            };
+            let len = front.len.min(buf.len() - read);
+            buf[read..read + len].copy_from_slice(&front.bytes()[..len]);
+            front.offset += len;
+            front.len -= len;
+            read += len;
+            if front.len == 0 {
+                self.bufs.pop_front();
+            }
+        }
+        read
+    }
+    /// 追加数据, 先填满末页再分配新页. 页分配失败时可能只写入一部分
+    fn write_bytes(&mut self, buf: &[u8]) -> usize {
+        let mut written = 0;
+        if let Some(back) = self.bufs.back_mut().filter(|back| back.can_merge()) {
+            written += back.append(buf);
+        }
+        while written < buf.len() && self.free_slots() > 0 {
+            let Some(mut page) = PipeBuf::new() else {
+                break;
+            };
+            written += page.append(&buf[written..]);
+            self.bufs.push_back(page);
+        }
+        written
+    }
+    /// 取走队首最多 `len` 字节的页
+    fn take_bufs(&mut self, mut len: usize, max_bufs: usize) -> Vec<PipeBuf> {
+        let mut taken = Vec::new();
+        while len > 0 && taken.len() < max_bufs {
+            let Some(front) = self.bufs.front_mut() else {
+                break;
+            };
+            if front.len > len {
+                taken.push(front.split_front(len));
+                break;
+            }
+            len -= front.len;
+            taken.push(self.bufs.pop_front().unwrap());
+        }
+        taken
+    }
+    /// 复制队首最多 `len` 字节的页引用, 不消耗数据
+    fn peek_bufs(&self, mut len: usize, max_bufs: usize) -> Vec<PipeBuf> {
+        let mut peeked = Vec::new();
+        for buf in self.bufs.iter().take(max_bufs) {
+            if len == 0 {
+                break;
+            }
+            let mut buf = buf.clone();
+            buf.len = buf.len.min(len);
+            len -= buf.len;
+            peeked.push(buf);
+        }
+        peeked
+    }
+    /// 消耗 `written` 中还在管道里的部分. `written` 是之前从队首复制的页引用,
+    /// 其间别的读者可能已经读走了开头一段; 我们还持有这些页, 它们不会被合并写入或换成别的数据
+    fn take_written(&mut self, written: &[PipeBuf]) {
+        let Some(front) = self.bufs.front() else {
+            return;
+        };
+        let Some(i) = written.iter().position(|buf| {
+            Arc::ptr_eq(&buf.page, &front.page) && (buf.offset..buf.offset + buf.len).contains(&front.offset)
+        }) else {
+            return;
+        };
+        let left = written[i].offset + written[i].len - front.offset;
+        let left = left + written[i + 1..].iter().map(|buf| buf.len).sum::<usize>();
+        self.take_bufs(left, usize::MAX);
     }
 }
 
@@ -83,86 +380,199 @@ pub fn make_pipe(superblock:Arc<dyn SuperBlock>) -> (Arc<Pipe>, Arc<Pipe>) {
     pipe_dentry.set_inode(pipe_inode);
     *pipe_dentry.get_state() = DentryState::Valid;
     let buffer = Arc::new(Mutex::new(PipeRingBuffer::new()));
+    buffer.lock().open_end(true, true);
     let read_end = Arc::new(
         Pipe::read_end_with_buffer(buffer.clone(), pipe_dentry.clone())
     );
     let write_end = Arc::new(
         Pipe::write_end_with_buffer(buffer.clone(), pipe_dentry)
     );
-    buffer.lock().set_write_end(&write_end);// 调用 PipeRingBuffer::set_write_end 在管道中保留它的写端的弱引用计数
     (read_end, write_end)
 }
 
-impl PipeRingBuffer {
-    /// read first byte(head++)
-    pub fn read_byte(&mut self) -> u8 {
-        self.status = RingBufferStatus::NORMAL;
-        let c = self.arr[self.head];
-        self.head = (self.head + 1) % RING_BUFFER_SIZE;
-        if self.head == self.tail {
-            self.status = RingBufferStatus::EMPTY;
+/// 以 inode 地址为键的 FIFO 缓冲区, 只要还有一端打开就保持存活
+static FIFOS: Mutex<BTreeMap<usize, Weak<Mutex<PipeRingBuffer>>>> = Mutex::new(BTreeMap::new());
+
+/// 打开命名管道. 只读打开会等到有写者, 只写打开会等到有读者;
+/// O_NONBLOCK 时只读立即返回, 没有读者的只写打开返回 ENXIO. 读写打开从不阻塞
+pub fn open_fifo(dentry: Arc<dyn Dentry>, flags: OpenFlags) -> SysResult<Arc<dyn File>> {
+    let inode = dentry.get_inode()?;
+    let key = Arc::as_ptr(&inode) as *const () as usize;
+    let buffer = {
+        let mut fifos = FIFOS.lock();
+        match fifos.get(&key).and_then(|buffer| buffer.upgrade()) {
+            Some(buffer) => buffer,
+            None => {
+                let buffer = Arc::new(Mutex::new(PipeRingBuffer::new()));
+                fifos.retain(|_, buffer| buffer.strong_count() > 0);
+                fifos.insert(key, Arc::downgrade(&buffer));
+                buffer
+            }
         }
-        c
-    }
-    /// return available number of bytes
-    pub fn available_read(&self) -> usize {
-        if self.status == RingBufferStatus::EMPTY {
-            0
-        } else {
-            if self.tail > self.head {
-                self.tail - self.head
+    };
+    let readable = !flags.contains(OpenFlags::WRONLY);
+    let writable = flags.intersects(OpenFlags::WRONLY | OpenFlags::RDWR);
+    let nonblock = flags.contains(OpenFlags::NONBLOCK);
+    let (read_opens, write_opens) = {
+        let mut ring_buffer = buffer.lock();
+        if writable && !readable && nonblock && ring_buffer.readers == 0 {
+            return Err(SysError::ENXIO);
+        }
+        ring_buffer.open_end(readable, writable);
+        (ring_buffer.read_opens, ring_buffer.write_opens)
+    };
+    let queue = buffer.lock().queue.clone();
+    // 叫醒在另一端等我们的 open
+    queue.notify(PollEvents::POLLIN | PollEvents::POLLOUT);
+    let pipe = Arc::new(Pipe {
+        readable,
+        writable,
+        queue: queue.clone(),
+        buffer: buffer.clone(),
+        inner: FileInner::new(dentry),
+    });
+    *pipe.inner.flags.lock() = flags;
+    if !(readable && writable) && !nonblock {
+        // 被信号打断时 `pipe` 随之释放, 这一端也就关掉了
+        wait_event(&queue, || {
+            let ring_buffer = buffer.lock();
+            // 对端在我们等待期间打开过(哪怕已经关闭)也算等到了
+            let peer_came = if readable {
+                ring_buffer.writers > 0 || ring_buffer.write_opens != write_opens
             } else {
-                self.tail + RING_BUFFER_SIZE - self.head
+                ring_buffer.readers > 0 || ring_buffer.read_opens != read_opens
+            };
+            peer_came as usize
+        })?;
+    }
+    Ok(pipe)
+}
+
+
+/// splice: 把 `src` 中最多 `len` 字节的页移到 `dst`, 不拷贝数据
+pub fn splice_pipe_to_pipe(src: &Pipe, dst: &Pipe, len: usize, nonblock: bool) -> SysResult<usize> {
+    if src.same_pipe(dst) {
+        return Err(SysError::EINVAL);
+    }
+    if !src.wait_for_data(nonblock || src.nonblock())? {
+        return Ok(0);
+    }
+    let slots = dst.wait_for_room(nonblock || dst.nonblock())?;
+    let bufs = src.buffer.lock().take_bufs(len, slots);
+    let moved = bufs.iter().map(|buf| buf.len).sum();
+    dst.buffer.lock().bufs.extend(bufs);
+    src.queue.notify(PollEvents::POLLOUT);
+    dst.queue.notify(PollEvents::POLLIN);
+    Ok(moved)
+}
+
+/// tee: 把 `src` 中最多 `len` 字节的页引用复制到 `dst`, `src` 中的数据保持不变
+pub fn tee_pipe(src: &Pipe, dst: &Pipe, len: usize, nonblock: bool) -> SysResult<usize> {
+    if src.same_pipe(dst) {
+        return Err(SysError::EINVAL);
+    }
+    if !src.wait_for_data(nonblock || src.nonblock())? {
+        return Ok(0);
+    }
+    let slots = dst.wait_for_room(nonblock || dst.nonblock())?;
+    let bufs = src.buffer.lock().peek_bufs(len, slots);
+    let copied = bufs.iter().map(|buf| buf.len).sum();
+    dst.buffer.lock().bufs.extend(bufs);
+    dst.queue.notify(PollEvents::POLLIN);
+    Ok(copied)
+}
+
+/// splice: 从文件直接读进新页再挂到管道上. `offset` 为 None 时使用并推进文件自身的偏移
+pub fn splice_file_to_pipe(file: &Arc<dyn File>, offset: Option<&mut usize>, pipe: &Pipe, len: usize, nonblock: bool) -> SysResult<usize> {
+    let slots = pipe.wait_for_room(nonblock || pipe.nonblock())?;
+    // 读文件可能阻塞, 期间不持有管道的锁
+    let mut bufs = Vec::new();
+    let mut total = 0;
+    let mut pos = offset.as_ref().map(|offset| **offset);
+    while total < len && bufs.len() < slots {
+        let mut page = PipeBuf::new().ok_or(SysError::ENOMEM)?;
+        let want = (len - total).min(PAGE_SIZE);
+        let buf = &mut page.page.ppn.get_buffer()[..want];
+        let read = match pos.as_mut() {
+            Some(pos) => {
+                let read = file.read_at(*pos, buf);
+                *pos += read;
+                read
             }
+            None => file.read(buf),
+        };
+        if read == 0 {
+            break;
+        }
+        page.len = read;
+        bufs.push(page);
+        total += read;
+        if read < want {
+            break;
         }
     }
-    /// try to destroy pipe
-    pub fn all_write_ends_closed(&self) -> bool {
-        self.write_end.as_ref().unwrap().upgrade().is_none()
+    if let (Some(offset), Some(pos)) = (offset, pos) {
+        *offset = pos;
     }
+    pipe.buffer.lock().bufs.extend(bufs);
+    pipe.queue.notify(PollEvents::POLLIN);
+    Ok(total)
 }
 
-impl File for Pipe {
-    //fn read(&self, buf: UserBuffer) -> usize {
-    fn read(&self, buf: &mut [u8]) -> usize {
-        assert!(self.readable());
-        let want_to_read = buf.len();
-        let mut buf_iter = buf.into_iter(); // iterator for reading bytes 
-        let mut already_read = 0usize;
-        // change task when data inadequate
-
-            //let mut ring_buffer = self.buffer.exclusive_access();
-        loop {
-            let mut ring_buffer = self.buffer.lock();
-            let loop_read = ring_buffer.available_read();
-            if loop_read == 0 {
-                if ring_buffer.all_write_ends_closed() {
-                    return already_read;
-                }
-                drop(ring_buffer);
-                suspend_current_and_run_next(); //change task: empty pipe
-                continue;
-            }    
-            let want_to_read = want_to_read.min(loop_read);
-            for _ in 0..loop_read {
-                if let Some(byte_ref) = buf_iter.next() {
-                    *byte_ref = ring_buffer.read_byte();
-                    already_read += 1;
-                    if already_read == want_to_read {
-                        return want_to_read;
-                    }
-                } else {
-                    return already_read;
-                }
-            }
-            return already_read;
+/// splice: 把管道中的页直接写到文件. `offset` 为 None 时使用并推进文件自身的偏移
+pub fn splice_pipe_to_file(pipe: &Pipe, file: &Arc<dyn File>, offset: Option<&mut usize>, len: usize, nonblock: bool) -> SysResult<usize> {
+    if !pipe.wait_for_data(nonblock || pipe.nonblock())? {
+        return Ok(0);
+    }
+    // 先复制页引用写文件, 写成功多少再从管道里消耗多少; 写文件可能阻塞, 期间不持有管道的锁
+    let mut bufs = pipe.buffer.lock().peek_bufs(len, usize::MAX);
+    let mut total = 0;
+    let mut error = None;
+    let mut pos = offset.as_ref().map(|offset| **offset);
+    let mut written_bufs = 0;
+    for buf in bufs.iter_mut() {
+        let result = match pos.as_mut() {
+            Some(pos) => file.pwrite(*pos, buf.bytes()).inspect(|written| *pos += written),
+            None => file.write_checked(buf.bytes()),
+        };
+        let written = result.unwrap_or_else(|err| {
+            error = Some(err);
+            0
+        });
+        total += written;
+        written_bufs += 1;
+        // 短写说明文件写满或出错, 剩下的数据留在管道里
+        if written < buf.len {
+            buf.len = written;
+            break;
         }
     }
-    //需要返回什么？
+    bufs.truncate(written_bufs);
+    if let (Some(offset), Some(pos)) = (offset, pos) {
+        *offset = pos;
+    }
+    if total > 0 {
+        pipe.buffer.lock().take_written(&bufs);
+        pipe.queue.notify(PollEvents::POLLOUT);
+    }
+    match error {
+        Some(err) if total == 0 => Err(err),
+        _ => Ok(total),
+    }
+}
+
+impl File for Pipe {
     fn get_inner(&self) -> &FileInner { 
         &self.inner
     }
 
+    fn pread(&self, _offset: usize, _buf: &mut [u8]) -> SysResult<usize> {
+        Err(SysError::ESPIPE)
+    }
+    fn pwrite(&self, _offset: usize, _buf: &[u8]) -> SysResult<usize> {
+        Err(SysError::ESPIPE)
+    }
+
     fn readable(&self) -> bool { 
         self.readable 
     }
@@ -172,82 +582,53 @@ impl File for Pipe {
     }
 
     fn read_at(&self, _offset: usize, buf: &mut [u8]) -> usize {
-        assert!(self.readable());
-
-        let want_to_read = buf.len();
-        let mut buf_iter = buf.into_iter();
-        let mut already_read = 0usize;
+        self.read_checked(buf).unwrap_or(0)
+    }
 
-        loop {
-            let mut ring_buffer = self.buffer.lock();
-            let loop_read = ring_buffer.available_read();
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> usize {
+        self.write_checked(buf).unwrap_or(0)
+    }
 
-            if loop_read == 0 {
-                if ring_buffer.all_write_ends_closed() {
-                    return already_read; // 如果写端已关闭，返回已读取的字节数
-                }
-                drop(ring_buffer);
-                suspend_current_and_run_next(); // 切换任务：管道为空
-                continue;
-            }
-            let want_to_read = want_to_read.min(loop_read);
-            for _ in 0..loop_read {
-                if let Some(byte_ref) = buf_iter.next() {
-                    *byte_ref = ring_buffer.read_byte(); // 从缓冲区读取一个字节
-                    already_read += 1;
-
-                    if already_read == want_to_read {
-                        return want_to_read; // 读取完成
-                    }
-                } else {
-                    return already_read; // 缓冲区已读完
-                }
-            }
-        }
+    fn read(&self, buf: &mut [u8]) -> usize {
+        self.read_checked(buf).unwrap_or(0)
     }
-    
-    fn write_at(&self, _offset: usize, buf: &[u8]) -> usize {
-        assert!(self.writable());
 
-        let want_to_write = buf.len();
-        let mut buf_iter = buf.iter(); // 迭代器用于写入字节
-        let mut already_written = 0usize;
+    fn write(&self, buf: &[u8]) -> usize {
+        self.write_checked(buf).unwrap_or(0)
+    }
 
-        // change task while buffer is full
-        loop {
-            let mut ring_buffer = self.buffer.lock();
+    fn read_checked(&self, buf: &mut [u8]) -> SysResult<usize> {
+        self.pipe_read(buf, self.nonblock())
+    }
 
-            // 计算可写入的空间
-            let available_write = RING_BUFFER_SIZE - ring_buffer.available_read();
+    fn write_checked(&self, buf: &[u8]) -> SysResult<usize> {
+        self.pipe_write(buf, self.nonblock())
+    }
 
-            if available_write == 0 {
-                drop(ring_buffer);
-                suspend_current_and_run_next();
-                continue;
+    fn poll(&self, events: PollEvents) -> PollEvents {
+        let ring_buffer = self.buffer.lock();
+        let mut ready = PollEvents::empty();
+        if self.readable {
+            if ring_buffer.available_read() > 0 {
+                ready |= PollEvents::POLLIN | PollEvents::POLLRDNORM;
             }
-            // 完成一轮写重新计算可写量
-            for _ in 0..available_write {
-                if let Some(&byte) = buf_iter.next() {
-                    let tail=ring_buffer.tail;
-                    ring_buffer.arr[tail] = byte; // 写入一个字节
-                    ring_buffer.tail = (ring_buffer.tail + 1) % RING_BUFFER_SIZE;
-                    ring_buffer.status = RingBufferStatus::NORMAL;
-                    already_written += 1;
-
-                    if already_written == want_to_write {
-                        return want_to_write; // 写入完成
-                    }
-                } else {
-                    return already_written; // 数据已写完
-                }
+            if ring_buffer.all_write_ends_closed() {
+                ready |= PollEvents::POLLHUP;
             }
         }
-    }
-    fn poll(&self, _events: PollEvents) -> PollEvents {
-        if self.readable{
-            return PollEvents::POLLIN;
+        if self.writable {
+            if ring_buffer.available_write() >= PIPE_BUF {
+                ready |= PollEvents::POLLOUT | PollEvents::POLLWRNORM;
+            }
+            if ring_buffer.all_read_ends_closed() {
+                ready |= PollEvents::POLLERR;
+            }
         }
-        return PollEvents::POLLOUT;
+        ready & (events | PollEvents::POLLHUP | PollEvents::POLLERR)
+    }
+
+    fn poll_queue(&self) -> Option<&PollQueue> {
+        Some(&self.queue)
     }
 }
 
@@ -0,0 +1,93 @@
+//! poll/select/epoll 共用的阻塞等待
+use crate::task::{
+    block_current_and_run_next, current_has_pending_signal, current_task, suspend_current_and_run_next,
+    wakeup_blocked_task, TaskControlBlock,
+};
+use crate::timer::{add_timer, cancel_timer, TimerEvent};
+use ::time::monotonic_nsec;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+use system_result::{SysError, SysResult};
+use vfs_defs::{File, PollEvents, PollQueue, PollWaiter};
+
+/// 挂在文件等待队列上的任务
+struct TaskWaiter {
+    task: Weak<TaskControlBlock>,
+    woken: AtomicBool,
+}
+
+impl PollWaiter for TaskWaiter {
+    fn wake(&self, _events: PollEvents) {
+        self.woken.store(true, Ordering::SeqCst);
+        if let Some(task) = self.task.upgrade() {
+            wakeup_blocked_task(task);
+        }
+    }
+}
+
+/// 反复调用 `check` 直到它返回非零、到达 `deadline`(单调时钟纳秒, 返回 0)或有未屏蔽的信号(EINTR).
+/// 所有文件都有等待队列时真正阻塞, 否则只能让出 CPU 后轮询
+pub fn poll_wait(
+    files: &[Arc<dyn File>],
+    deadline: Option<usize>,
+    check: impl FnMut() -> usize,
+) -> SysResult<usize> {
+    let queues: Vec<&PollQueue> = files.iter().filter_map(|file| file.poll_queue()).collect();
+    let blockable = queues.len() == files.len();
+    wait_on(&queues, blockable, deadline, check)
+}
+
+/// 在一个等待队列上阻塞到 `check` 返回非零, 被信号打断时返回 EINTR.
+/// eventfd、timerfd 这类文件的阻塞读写用它
+pub fn wait_event(queue: &PollQueue, check: impl FnMut() -> usize) -> SysResult<usize> {
+    wait_on(&[queue], true, None, check)
+}
+
+fn wait_on(
+    queues: &[&PollQueue],
+    blockable: bool,
+    deadline: Option<usize>,
+    mut check: impl FnMut() -> usize,
+) -> SysResult<usize> {
+    let task = current_task().unwrap();
+    let waiter = Arc::new(TaskWaiter {
+        task: Arc::downgrade(&task),
+        woken: AtomicBool::new(false),
+    });
+    drop(task);
+    let dyn_waiter: Arc<dyn PollWaiter> = waiter.clone();
+    for queue in queues.iter() {
+        queue.register(&dyn_waiter, false);
+    }
+    let ret = loop {
+        // 先清标志再检查, 检查之后到来的通知不会丢
+        waiter.woken.store(false, Ordering::SeqCst);
+        let ready = check();
+        if ready > 0 {
+            break Ok(ready);
+        }
+        if deadline.is_some_and(|deadline| monotonic_nsec() >= deadline) {
+            break Ok(0);
+        }
+        if current_has_pending_signal() {
+            break Err(SysError::EINTR);
+        }
+        if !blockable {
+            suspend_current_and_run_next();
+            continue;
+        }
+        if waiter.woken.load(Ordering::SeqCst) {
+            continue;
+        }
+        let timer = deadline.map(|deadline| add_timer(deadline, TimerEvent::Wake(waiter.task.clone())));
+        block_current_and_run_next();
+        if let Some(timer) = timer {
+            cancel_timer(timer);
+        }
+    };
+    for queue in queues.iter() {
+        queue.unregister(&dyn_waiter);
+    }
+    ret
+}
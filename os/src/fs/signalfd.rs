@@ -0,0 +1,140 @@
+//! signalfd: 以读文件的方式接收信号
+//!
+//! 读取和 poll 都作用于调用者自己的信号队列. 信号到来时没有通知,
+//! 阻塞读只能让出 CPU 轮询.
+use super::anon_dentry;
+use crate::task::{
+    current_has_pending_signal, dequeue_signal, has_queued_signal, suspend_current_and_run_next, SigDetails, SigInfo,
+    SignalFlags,
+};
+use alloc::sync::Arc;
+use core::mem;
+use sync::Mutex;
+use system_result::{SysError, SysResult};
+use vfs_defs::{File, FileInner, OpenFlags, PollEvents, SuperBlock};
+
+/// struct signalfd_siginfo
+#[repr(C)]
+#[derive(Default)]
+#[allow(dead_code)]
+struct SignalfdSiginfo {
+    ssi_signo: u32,
+    ssi_errno: i32,
+    ssi_code: i32,
+    ssi_pid: u32,
+    ssi_uid: u32,
+    ssi_fd: i32,
+    ssi_tid: u32,
+    ssi_band: u32,
+    ssi_overrun: u32,
+    ssi_trapno: u32,
+    ssi_status: i32,
+    ssi_int: i32,
+    ssi_ptr: u64,
+    ssi_utime: u64,
+    ssi_stime: u64,
+    ssi_addr: u64,
+    ssi_addr_lsb: u16,
+    __pad2: u16,
+    ssi_syscall: i32,
+    ssi_call_addr: u64,
+    ssi_arch: u32,
+    __pad: [u8; 28],
+}
+
+impl From<SigInfo> for SignalfdSiginfo {
+    fn from(info: SigInfo) -> Self {
+        let pid = match info.details {
+            SigDetails::Kill { pid } => pid as u32,
+            SigDetails::None => 0,
+        };
+        Self {
+            ssi_signo: info.signum as u32,
+            ssi_code: info.code,
+            ssi_pid: pid,
+            ..Default::default()
+        }
+    }
+}
+
+pub struct SignalFd {
+    inner: FileInner,
+    mask: Mutex<SignalFlags>,
+}
+
+impl SignalFd {
+    pub fn new(mask: SignalFlags, superblock: Arc<dyn SuperBlock>) -> Arc<Self> {
+        Arc::new(Self {
+            inner: FileInner::new(anon_dentry("signalfd", superblock)),
+            mask: Mutex::new(mask),
+        })
+    }
+    pub fn set_mask(&self, mask: SignalFlags) {
+        *self.mask.lock() = mask;
+    }
+    fn nonblock(&self) -> bool {
+        self.inner.flags.lock().contains(OpenFlags::NONBLOCK)
+    }
+}
+
+impl File for SignalFd {
+    fn get_inner(&self) -> &FileInner {
+        &self.inner
+    }
+    fn readable(&self) -> bool {
+        true
+    }
+    fn writable(&self) -> bool {
+        false
+    }
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> usize {
+        0
+    }
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> usize {
+        0
+    }
+    fn pread(&self, _offset: usize, _buf: &mut [u8]) -> SysResult<usize> {
+        Err(SysError::ESPIPE)
+    }
+    fn pwrite(&self, _offset: usize, _buf: &[u8]) -> SysResult<usize> {
+        Err(SysError::ESPIPE)
+    }
+    fn read_checked(&self, buf: &mut [u8]) -> SysResult<usize> {
+        const SIZE: usize = mem::size_of::<SignalfdSiginfo>();
+        if buf.len() < SIZE {
+            return Err(SysError::EINVAL);
+        }
+        let mut read = 0;
+        loop {
+            while read + SIZE <= buf.len() {
+                let info = match dequeue_signal(*self.mask.lock()) {
+                    Some(info) => SignalfdSiginfo::from(info),
+                    None => break,
+                };
+                let bytes = unsafe { core::slice::from_raw_parts(&info as *const SignalfdSiginfo as *const u8, SIZE) };
+                buf[read..read + SIZE].copy_from_slice(bytes);
+                read += SIZE;
+            }
+            if read > 0 {
+                return Ok(read);
+            }
+            if self.nonblock() {
+                return Err(SysError::EAGAIN);
+            }
+            if current_has_pending_signal() {
+                return Err(SysError::EINTR);
+            }
+            suspend_current_and_run_next();
+        }
+    }
+    fn write_checked(&self, _buf: &[u8]) -> SysResult<usize> {
+        Err(SysError::EINVAL)
+    }
+    fn poll(&self, events: PollEvents) -> PollEvents {
+        if has_queued_signal(*self.mask.lock()) {
+            (PollEvents::POLLIN | PollEvents::POLLRDNORM) & events
+        } else {
+            PollEvents::empty()
+        }
+    }
+}
@@ -4,10 +4,11 @@ use arch::console_getchar;
 use alloc::{sync::Arc, vec::Vec};
 use alloc::string::String;
 use spin::{Mutex, MutexGuard};
-use crate::task::suspend_current_and_run_next;
+use crate::task::{current_task, suspend_current_and_run_next};
 use vfs_defs::{Dentry, DentryInner, DiskInodeType, File, FileInner, Inode, InodeMeta, InodeMetaInner, Kstat, OpenFlags, PollEvents, SuperBlock, UserBuffer,RenameFlags};
 use lazy_static::*;
 use system_result::{SysResult,SysError};
+use vfs::{read_user, write_user};
 ///Standard input
 pub struct Stdin{
     buf:Mutex<Option<u8>>,
@@ -58,7 +59,97 @@ impl StdIO{
         }
     }
 }
+const TCGETS: usize = 0x5401;
+const TCSETS: usize = 0x5402;
+const TCSETSW: usize = 0x5403;
+const TCSETSF: usize = 0x5404;
+const TIOCGPGRP: usize = 0x540f;
+const TIOCSPGRP: usize = 0x5410;
+const TIOCGWINSZ: usize = 0x5413;
+const TIOCSWINSZ: usize = 0x5414;
+
+/// TCGETS/TCSETS 使用的内核 struct termios
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Termios {
+    pub iflag: u32,
+    pub oflag: u32,
+    pub cflag: u32,
+    pub lflag: u32,
+    pub line: u8,
+    pub cc: [u8; 19],
+}
+
+/// TIOCGWINSZ/TIOCSWINSZ 使用的 struct winsize
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct WinSize {
+    pub row: u16,
+    pub col: u16,
+    pub xpixel: u16,
+    pub ypixel: u16,
+}
+
+/// 控制台终端的状态, 标准输入输出共用一份. 控制台本身不做回显和行编辑, 设置只是记下来
+struct Tty {
+    termios: Termios,
+    winsize: WinSize,
+    /// 前台进程组, 没设置过时取调用者
+    pgrp: Option<i32>,
+}
+
+lazy_static! {
+    static ref TTY: Mutex<Tty> = Mutex::new(Tty {
+        // 与 Linux 新建终端的默认值相同: ICRNL|IXON, OPOST|ONLCR, B38400|CS8|CREAD|HUPCL,
+        // ISIG|ICANON|ECHO|ECHOE|ECHOK|ECHOCTL|ECHOKE|IEXTEN
+        termios: Termios {
+            iflag: 0o2400,
+            oflag: 0o5,
+            cflag: 0o2277,
+            lflag: 0o105073,
+            line: 0,
+            cc: [3, 28, 127, 21, 4, 0, 1, 0, 17, 19, 26, 0, 18, 15, 23, 22, 0, 0, 0],
+        },
+        winsize: WinSize { row: 24, col: 80, xpixel: 0, ypixel: 0 },
+        pgrp: None,
+    });
+}
+
+/// 控制台终端的 ioctl, 不是终端请求的返回 ENOTTY
+fn tty_ioctl(cmd: usize, arg: usize) -> SysResult<isize> {
+    if !matches!(cmd, TCGETS | TCSETS | TCSETSW | TCSETSF | TIOCGPGRP | TIOCSPGRP | TIOCGWINSZ | TIOCSWINSZ) {
+        return Err(SysError::ENOTTY);
+    }
+    // 参数都是指针, 不持 TTY 锁访问用户内存
+    match cmd {
+        TCGETS => {
+            let termios = TTY.lock().termios;
+            write_user(arg, &termios)?;
+        }
+        TCSETS | TCSETSW | TCSETSF => TTY.lock().termios = read_user(arg)?,
+        TIOCGWINSZ => {
+            let winsize = TTY.lock().winsize;
+            write_user(arg, &winsize)?;
+        }
+        TIOCSWINSZ => TTY.lock().winsize = read_user(arg)?,
+        TIOCGPGRP => {
+            let pgrp = TTY.lock().pgrp;
+            let pgrp = pgrp.unwrap_or_else(|| current_task().unwrap().pid as i32);
+            write_user(arg, &pgrp)?;
+        }
+        TIOCSPGRP => TTY.lock().pgrp = Some(read_user(arg)?),
+        _ => unreachable!(),
+    }
+    Ok(0)
+}
+
 impl File for Stdin {
+    fn pread(&self, _offset: usize, _buf: &mut [u8]) -> SysResult<usize> {
+        Err(SysError::ESPIPE)
+    }
+    fn pwrite(&self, _offset: usize, _buf: &[u8]) -> SysResult<usize> {
+        Err(SysError::ESPIPE)
+    }
     fn readable(&self) -> bool {
         true
     }
@@ -106,25 +197,31 @@ impl File for Stdin {
     fn get_offset(&self)->MutexGuard<usize> {
         self.get_inner().offset.lock()
     }
+    fn ioctl(&self, cmd: usize, arg: usize) -> SysResult<isize> {
+        tty_ioctl(cmd, arg)
+    }
     fn poll(&self, events: PollEvents) -> PollEvents {
-        if  ! events.contains(PollEvents::POLLIN) { 
-            return PollEvents::empty();
+        // 控制台没有输入中断, 只能在这里查一次, 读到的字符先存起来
+        let mut buf = self.buf.lock();
+        if buf.is_none() {
+            *buf = console_getchar();
         }
-        loop {
-            if let Some(ch) = console_getchar() {
-                let mut buf = self.buf.lock();
-                *buf = Some(ch);
-                drop(buf);
-                break;
-            }
-            suspend_current_and_run_next();
+        let mut ready = PollEvents::empty();
+        if buf.is_some() {
+            ready |= PollEvents::POLLIN | PollEvents::POLLRDNORM;
         }
-        return PollEvents::POLLIN;
+        ready & events
     }
 }
 
 
 impl File for Stdout {
+    fn pread(&self, _offset: usize, _buf: &mut [u8]) -> SysResult<usize> {
+        Err(SysError::ESPIPE)
+    }
+    fn pwrite(&self, _offset: usize, _buf: &[u8]) -> SysResult<usize> {
+        Err(SysError::ESPIPE)
+    }
     fn readable(&self) -> bool {
         false
     }
@@ -171,12 +268,21 @@ impl File for Stdout {
     fn get_offset(&self)->MutexGuard<usize> {
         self.get_inner().offset.lock()
     }
+    fn ioctl(&self, cmd: usize, arg: usize) -> SysResult<isize> {
+        tty_ioctl(cmd, arg)
+    }
     fn poll(&self, _events: PollEvents) -> PollEvents {
         return PollEvents::POLLOUT;
     }
 }
 
 impl File for Stderr {
+    fn pread(&self, _offset: usize, _buf: &mut [u8]) -> SysResult<usize> {
+        Err(SysError::ESPIPE)
+    }
+    fn pwrite(&self, _offset: usize, _buf: &[u8]) -> SysResult<usize> {
+        Err(SysError::ESPIPE)
+    }
     fn readable(&self) -> bool {
         false
     }
@@ -201,12 +307,21 @@ impl File for Stderr {
     fn get_offset(&self)->MutexGuard<usize> {
         self.get_inner().offset.lock()
     }
+    fn ioctl(&self, cmd: usize, arg: usize) -> SysResult<isize> {
+        tty_ioctl(cmd, arg)
+    }
     fn poll(&self, _events: PollEvents) -> PollEvents {
         return PollEvents::POLLOUT;
     }
 }
 
 impl File for StdIO {
+    fn pread(&self, _offset: usize, _buf: &mut [u8]) -> SysResult<usize> {
+        Err(SysError::ESPIPE)
+    }
+    fn pwrite(&self, _offset: usize, _buf: &[u8]) -> SysResult<usize> {
+        Err(SysError::ESPIPE)
+    }
     fn readable(&self) -> bool {
         true
     }
@@ -256,20 +371,20 @@ impl File for StdIO {
     fn get_offset(&self)->MutexGuard<usize> {
         self.get_inner().offset.lock()
     }
+    fn ioctl(&self, cmd: usize, arg: usize) -> SysResult<isize> {
+        tty_ioctl(cmd, arg)
+    }
     fn poll(&self, events: PollEvents) -> PollEvents {
-        if  ! events.contains(PollEvents::POLLIN) { 
-            return PollEvents::empty();
+        // 控制台没有输入中断, 只能在这里查一次, 读到的字符先存起来
+        let mut buf = self.buf.lock();
+        if buf.is_none() {
+            *buf = console_getchar();
         }
-        loop {
-            if let Some(ch) = console_getchar() {
-                let mut buf = self.buf.lock();
-                *buf = Some(ch);
-                drop(buf);
-                break;
-            }
-            suspend_current_and_run_next();
+        let mut ready = PollEvents::POLLOUT | PollEvents::POLLWRNORM;
+        if buf.is_some() {
+            ready |= PollEvents::POLLIN | PollEvents::POLLRDNORM;
         }
-        return PollEvents::POLLIN;
+        ready & events
     }
 }
 
@@ -352,7 +467,7 @@ impl Inode for StdioInode{
                 st_nlink: 0,
                 st_uid: 0,
                 st_gid: 0,
-                st_rdev: 0,
+                st_rdev: self.meta.inner.lock().rdev,
                 __pad: 0,
                 st_size: self.get_size() as u64,
                 st_blksize: 0,
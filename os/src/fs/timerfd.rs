@@ -0,0 +1,185 @@
+//! timerfd: 读出到期次数的定时器, 由内核定时器驱动
+//!
+//! 内部一律使用单调时钟, 其他时钟的绝对时间由系统调用层换算.
+use super::{anon_dentry, wait_event};
+use crate::timer::{add_timer, cancel_timer, TimerEvent, TimerHandler, TimerId};
+use ::time::monotonic_nsec;
+use alloc::sync::{Arc, Weak};
+use sync::Mutex;
+use system_result::{SysError, SysResult};
+use vfs_defs::{File, FileInner, OpenFlags, PollEvents, PollQueue, SuperBlock};
+
+struct TimerState {
+    /// 下次到期的单调时钟时刻, None 表示未启动
+    next: Option<usize>,
+    /// 周期, 0 表示只触发一次
+    interval: usize,
+    /// 尚未读走的到期次数
+    ticks: u64,
+    timer: Option<TimerId>,
+}
+
+impl TimerState {
+    /// 结算到 `now` 为止的到期次数, 有新的到期时返回真
+    fn update(&mut self, now: usize) -> bool {
+        let next = match self.next {
+            Some(next) if next <= now => next,
+            _ => return false,
+        };
+        if self.interval == 0 {
+            self.ticks += 1;
+            self.next = None;
+        } else {
+            let count = (now - next) / self.interval + 1;
+            self.ticks += count as u64;
+            self.next = Some(next + count * self.interval);
+        }
+        true
+    }
+    /// (距下次到期的时间, 周期)
+    fn remaining(&self, now: usize) -> (usize, usize) {
+        (self.next.map_or(0, |next| next.saturating_sub(now)), self.interval)
+    }
+}
+
+pub struct TimerFd {
+    inner: FileInner,
+    /// 创建时指定的时钟
+    clock: usize,
+    this: Weak<TimerFd>,
+    state: Mutex<TimerState>,
+    queue: PollQueue,
+}
+
+impl TimerFd {
+    pub fn new(clock: usize, superblock: Arc<dyn SuperBlock>) -> Arc<Self> {
+        Arc::new_cyclic(|this| Self {
+            inner: FileInner::new(anon_dentry("timerfd", superblock)),
+            clock,
+            this: this.clone(),
+            state: Mutex::new(TimerState {
+                next: None,
+                interval: 0,
+                ticks: 0,
+                timer: None,
+            }),
+            queue: PollQueue::new(),
+        })
+    }
+    pub fn clock(&self) -> usize {
+        self.clock
+    }
+    fn nonblock(&self) -> bool {
+        self.inner.flags.lock().contains(OpenFlags::NONBLOCK)
+    }
+    fn arm(&self, state: &mut TimerState) {
+        if let Some(id) = state.timer.take() {
+            cancel_timer(id);
+        }
+        if let Some(next) = state.next {
+            let handler: Weak<dyn TimerHandler> = self.this.clone();
+            state.timer = Some(add_timer(next, TimerEvent::Callback(handler)));
+        }
+    }
+    /// 重新设置定时器, `next` 为 None 时停止. 返回原来的 (剩余时间, 周期)
+    pub fn settime(&self, next: Option<usize>, interval: usize) -> (usize, usize) {
+        let now = monotonic_nsec();
+        let mut state = self.state.lock();
+        state.update(now);
+        let old = state.remaining(now);
+        state.next = next;
+        state.interval = interval;
+        state.ticks = 0;
+        self.arm(&mut state);
+        old
+    }
+    /// (距下次到期的时间, 周期)
+    pub fn gettime(&self) -> (usize, usize) {
+        let now = monotonic_nsec();
+        let mut state = self.state.lock();
+        state.update(now);
+        state.remaining(now)
+    }
+    fn take_ticks(&self) -> u64 {
+        let mut state = self.state.lock();
+        state.update(monotonic_nsec());
+        core::mem::replace(&mut state.ticks, 0)
+    }
+}
+
+impl TimerHandler for TimerFd {
+    fn expire(&self, now: usize) {
+        let mut state = self.state.lock();
+        state.timer = None;
+        let fired = state.update(now);
+        self.arm(&mut state);
+        drop(state);
+        if fired {
+            self.queue.notify(PollEvents::POLLIN);
+        }
+    }
+}
+
+impl Drop for TimerFd {
+    fn drop(&mut self) {
+        if let Some(id) = self.state.lock().timer.take() {
+            cancel_timer(id);
+        }
+    }
+}
+
+impl File for TimerFd {
+    fn get_inner(&self) -> &FileInner {
+        &self.inner
+    }
+    fn readable(&self) -> bool {
+        true
+    }
+    fn writable(&self) -> bool {
+        false
+    }
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> usize {
+        0
+    }
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> usize {
+        0
+    }
+    fn pread(&self, _offset: usize, _buf: &mut [u8]) -> SysResult<usize> {
+        Err(SysError::ESPIPE)
+    }
+    fn pwrite(&self, _offset: usize, _buf: &[u8]) -> SysResult<usize> {
+        Err(SysError::ESPIPE)
+    }
+    fn read_checked(&self, buf: &mut [u8]) -> SysResult<usize> {
+        if buf.len() < 8 {
+            return Err(SysError::EINVAL);
+        }
+        let mut ticks = self.take_ticks();
+        if ticks == 0 {
+            if self.nonblock() {
+                return Err(SysError::EAGAIN);
+            }
+            wait_event(&self.queue, || {
+                ticks = self.take_ticks();
+                ticks as usize
+            })?;
+        }
+        buf[..8].copy_from_slice(&ticks.to_ne_bytes());
+        Ok(8)
+    }
+    fn write_checked(&self, _buf: &[u8]) -> SysResult<usize> {
+        Err(SysError::EINVAL)
+    }
+    fn poll(&self, events: PollEvents) -> PollEvents {
+        let mut state = self.state.lock();
+        state.update(monotonic_nsec());
+        if state.ticks > 0 {
+            (PollEvents::POLLIN | PollEvents::POLLRDNORM) & events
+        } else {
+            PollEvents::empty()
+        }
+    }
+    fn poll_queue(&self) -> Option<&PollQueue> {
+        Some(&self.queue)
+    }
+}
@@ -0,0 +1,39 @@
+//! 后台回写: 周期性地把脏了足够久的缓存块写回设备
+//!
+//! 内核没有内核线程, 回写由内核定时器回调驱动, 回调结束前重新挂上下一轮.
+use crate::timer::{add_timer, TimerEvent, TimerHandler};
+use ::time::{monotonic_nsec, NSEC_PER_SEC};
+use alloc::sync::{Arc, Weak};
+use lazy_static::*;
+
+/// 两轮回写之间的间隔
+const WRITEBACK_INTERVAL_NSEC: usize = 5 * NSEC_PER_SEC;
+/// 脏块至少要经过的回写轮数, 约 30 秒
+const DIRTY_EXPIRE_ROUNDS: usize = 6;
+
+struct Flusher;
+
+lazy_static! {
+    static ref FLUSHER: Arc<Flusher> = Arc::new(Flusher);
+}
+
+impl Flusher {
+    fn arm(now: usize) {
+        let handler: Weak<dyn TimerHandler> = Arc::downgrade(&*FLUSHER) as Weak<dyn TimerHandler>;
+        add_timer(now + WRITEBACK_INTERVAL_NSEC, TimerEvent::Callback(handler));
+    }
+}
+
+impl TimerHandler for Flusher {
+    fn expire(&self, now: usize) {
+        for dev in buffer::block_cache_writeback(DIRTY_EXPIRE_ROUNDS) {
+            dev.flush();
+        }
+        Self::arm(now);
+    }
+}
+
+/// 启动后台回写
+pub fn init() {
+    Flusher::arm(monotonic_nsec());
+}
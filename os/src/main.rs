@@ -25,6 +25,7 @@
 #![no_main]
 #![feature(panic_info_message)]
 #![feature(alloc_error_handler)]
+#![feature(asm_const)]
 
 extern crate alloc;
 
@@ -39,6 +40,7 @@ mod console;
 //#[macro_use]
 //mod logging;
 //mod config;
+mod cmdline;
 mod drivers;
 pub mod fs;
 pub mod lang_items;
@@ -47,6 +49,7 @@ pub mod sync;
 pub mod syscall;
 pub mod task;
 pub mod timer;
+mod vdso;
 
 #[macro_use]
 extern  crate logger;
@@ -165,7 +168,12 @@ impl ArchInterface for ArchInterfaceImpl {
                 println!("IllegalInstruction!");
                 exit_current_and_run_next(-1);
             }
+            SupervisorExternal => {
+                drivers::handle_irq();
+            }
             Time => {   
+                vdso::update();
+                timer::handle_timer_irq();
                 suspend_current_and_run_next();
             }
             _ => {
@@ -203,11 +211,21 @@ impl ArchInterface for ArchInterfaceImpl {
         //timer::set_next_trigger();
     //    board::device_init();
         println!("intr init");
-        device::BLOCK_DEVICE.call_once(||drivers::BLOCK_DEVICE.clone());
+        drivers::init();
         println!("device added");
+        drivers::rtc::init_realtime();
+        vdso::init();
+        #[cfg(feature = "syscall-stats")]
+        vfs::register_proc_file("syscalls", syscall::syscall_stats);
+        vfs::register_proc_file("locks", fs::proc_locks);
+        vfs::register_proc_file("cmdline", cmdline::proc_cmdline);
+        vfs::register_exe_resolver(task::current_exe_path);
+        vfs::register_fd_resolver(task::current_file);
+        vfs::register_user_copy(task::copy_from_user, task::copy_to_user);
         vfs::init();
+        fs::writeback_init();
         let superblock = vfs::get_root_dentry().get_superblock();
-        let dev = vfs::get_root_dentry().lookup("dev").unwrap();
+        let dev = vfs::lookup_path("/dev").unwrap();
         let ttyinner = vfs_defs::DentryInner::new(alloc::string::String::from("tty"), superblock.clone(),Some(dev));
         let ttydentry = fs::StdioDentry::new(ttyinner);
         let ttyinode = fs::StdioInode::new(vfs_defs::InodeMeta::new(vfs_defs::InodeMode::CHAR, vfs_defs::ino_alloc() as usize, superblock));
@@ -231,17 +249,44 @@ impl ArchInterface for ArchInterfaceImpl {
     }
     /// Preprare drivers.
     fn prepare_drivers(){
-
+        #[cfg(target_arch = "loongarch64")]
+        drivers::rtc::init_ls7a();
     }
     /// Try to add device through FdtNode
-    fn try_to_add_device(_fdt_node: &FdtNode){
-
+    fn try_to_add_device(fdt_node: &FdtNode){
+        // cpu 节点声明了 Sstc 扩展时直接写 stimecmp
+        #[cfg(target_arch = "riscv64")]
+        if fdt_node.name.starts_with("cpu@") {
+            let sstc = ["riscv,isa", "riscv,isa-extensions"].iter().any(|name| {
+                fdt_node.property(name).map_or(false, |prop| {
+                    prop.value
+                        .split(|&c| c == 0 || c == b'_')
+                        .any(|ext| ext == b"sstc")
+                })
+            });
+            if sstc && !arch::enable_sstc() {
+                println!("sstc listed but stimecmp is not accessible, using the SBI timer");
+            }
+        }
+        if fdt_node.name == "chosen" {
+            if let Some(bootargs) = fdt_node.property("bootargs") {
+                cmdline::init(bootargs.value);
+            }
+        }
+        let Some(compatible) = fdt_node.compatible() else {
+            return;
+        };
+        if compatible.all().any(|c| c == "google,goldfish-rtc") {
+            if let Some(reg) = fdt_node.reg().and_then(|mut reg| reg.next()) {
+                drivers::rtc::init_goldfish(reg.starting_address as usize);
+            }
+        }
     }
 }
 
 #[allow(unused)]
 fn create_testcase(){
-    let testfile = open_file("/testcase.sh", OpenFlags::RDWR | OpenFlags::CREATE).unwrap();
+    let testfile = open_file("/testcase.sh", OpenFlags::RDWR | OpenFlags::CREATE | OpenFlags::TRUNC).unwrap();
     let busyboxcmd = open_file("/glibc/busybox_cmd.txt", OpenFlags::RDWR).unwrap();
     busyboxcmd.write(BUSYBOX_SCRIPT.as_bytes());
     drop(busyboxcmd);
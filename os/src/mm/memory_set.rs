@@ -397,6 +397,8 @@ impl MemorySet {
 
         // 为程序映像转储 elf 程序头
 
+        crate::vdso::map(&mut memory_set);
+
         let heap_start:usize =  max_virt_mem.try_into().unwrap();
         let heap_top: usize = heap_start + USER_HEAP_SIZE;
         memory_set.push_into_area_lazy(
@@ -533,6 +535,7 @@ impl MemorySet {
                 MapAreaType::Heap=>{println!("heap");},
                 MapAreaType::Mmap=>{println!("mmap");},
                 MapAreaType::Stack=>{println!("Stack");},
+                MapAreaType::Vdso=>{println!("vdso");},
             }
         }
     }
@@ -769,7 +772,9 @@ pub enum MapAreaType {
     ///
     Mmap,
     ///
-    Stack
+    Stack,
+    /// vDSO 数据页与映像, 各进程共享
+    Vdso,
 }
 
 bitflags! {
@@ -1,5 +1,5 @@
 //! Implementation of [`PageTableEntry`] and [`PageTable`].
-use arch::addr::VirtPage;
+use arch::addr::{PhysAddr, VirtPage};
 //use super::{frame_alloc, FrameTracker, PhysAddr, PhysPageNum, StepByOne, VirtAddr, VirtPageNum};
 use arch::pagetable::{MappingFlags, MappingSize, PageTable};
 use arch::{TrapType, PAGE_SIZE};
@@ -10,6 +10,7 @@ use bitflags::*;
 use super::{MemorySet,VirtAddr};
 use alloc::sync::Arc;
 use sync::Mutex;
+use system_result::{SysError, SysResult};
 
 //const MODULE_LEVEL:log::Level = log::Level::Info;
 
@@ -76,6 +77,50 @@ pub fn safe_translated_byte_buffer(
 }
 
 
+/// 检查用户缓冲区 [ptr, ptr + len) 并按需处理延迟分配和写时复制, `write` 表示内核要写它.
+/// 不在用户地址空间里或没有相应权限时返回 EFAULT, 不会 panic
+pub fn checked_translated_byte_buffer(
+    memory_set: Arc<Mutex<MemorySet>>,
+    ptr: *mut u8,
+    len: usize,
+    write: bool,
+) -> SysResult<&'static mut [u8]> {
+    let start = ptr as usize;
+    let end = start.checked_add(len).ok_or(SysError::EFAULT)?;
+    if start == 0 {
+        return Err(SysError::EFAULT);
+    }
+    let mut memory_set = memory_set.lock();
+    let mut page = start / PAGE_SIZE * PAGE_SIZE;
+    while page < end {
+        let in_user_area = memory_set
+            .areas
+            .iter()
+            .any(|area| area.vpn_range.get_start().to_addr() <= page && area.vpn_range.get_end().to_addr() > page);
+        if !in_user_area {
+            return Err(SysError::EFAULT);
+        }
+        let present = |mp: Option<(PhysAddr, MappingFlags)>| {
+            mp.is_some_and(|(pa, mp)| pa.addr() != 0 && mp.contains(MappingFlags::P))
+        };
+        if !present(memory_set.page_table.translate(page.into())) {
+            memory_set
+                .handle_lazy_addr(page, TrapType::StorePageFault(page))
+                .map_err(|_| SysError::EFAULT)?;
+        }
+        let (_, mp) = memory_set.page_table.translate(page.into()).ok_or(SysError::EFAULT)?;
+        if write && mp.contains(MappingFlags::cow) {
+            memory_set.handle_cow_addr(page).map_err(|_| SysError::EFAULT)?;
+        }
+        let mp = memory_set.page_table.translate(page.into());
+        if !present(mp) || (write && !mp.is_some_and(|(_, mp)| mp.contains(MappingFlags::W))) {
+            return Err(SysError::EFAULT);
+        }
+        page += PAGE_SIZE;
+    }
+    Ok(unsafe { core::slice::from_raw_parts_mut(ptr, len) })
+}
+
 unsafe fn str_len(ptr: *const u8) -> usize {
     let mut i = 0;
     loop {
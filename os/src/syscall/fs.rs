@@ -1,22 +1,25 @@
 //! File and filesystem-related syscalls
 use config::USER_STACK_TOP;
-use crate::fs::{open_file,create_file};
-use crate::fs::make_pipe;
-use crate::fs::path_to_dentry;
-use crate::fs::path_to_father_dentry;
+use crate::fs::{open_file_at,create_file_at,lookup_at,lookup_parent_at,current_fs_cred,set_new_owner};
+use crate::fs::{make_pipe,splice_file_to_pipe,splice_pipe_to_file,splice_pipe_to_pipe,tee_pipe,Pipe};
+use crate::fs::{poll_wait,EpollEvent,EpollEvents,EpollFile,EventFd,SignalFd,TimerFd,EFD_SEMAPHORE};
+use super::process::{clock_now,CLOCK_REALTIME,CLOCK_MONOTONIC,CLOCK_BOOTTIME,CLOCK_REALTIME_ALARM,CLOCK_BOOTTIME_ALARM};
+use crate::fs::{flock,release_posix_locks,set_record_lock,test_record_lock,LockKind};
+use crate::fs::{path_to_dentry,pseudo_statfs};
 use crate::mm::{safe_translated_refmut, translated_byte_buffer, translated_ref, translated_refmut, translated_str,safe_translated_byte_buffer,MmapFlags,MapAreaType};
-use crate::task::{current_task, current_user_token, Fd, FdFlags, TimeSpec};
+use crate::task::{all_tasks, check_privileged, current_file, current_task, current_user_token, send_signal_to_current, Fd, FdFlags, SignalFlags, TimeSpec};
+use ::time::{monotonic_nsec, NSEC_PER_SEC};
+use config::MAX_FD;
 use alloc::string::String;
 
 use arch::addr::{VirtAddr, VirtPage};
 use arch::PAGE_SIZE;
 use arch::time::Time;
-use device::BLOCK_DEVICE;
-use vfs_defs::{Kstat, PollEvents};
+use vfs_defs::{Dentry, File, FileSeals, Inode, InodeMode, Kstat, PollEvents};
 use vfs_defs::MountFlags;
 
-use vfs_defs::{OpenFlags,UserBuffer,StatFs,SeekFlags,RenameFlags};
-use vfs::FILE_SYSTEMS;
+use vfs_defs::{OpenFlags,UserBuffer,StatFs,SeekFlags,RenameFlags,XattrFlags};
+use vfs::{BlockDevInode, Mount};
 //
 use crate::mm::frame_alloc_more;
 use crate::mm::MapArea;
@@ -34,6 +37,10 @@ use system_result::{SysError,SysResult};
 //const MODULE_LEVEL:log::Level = log::Level::Debug;
 //const HEAP_MAX: usize = 0;
 pub const AT_FDCWD: isize = -100;
+/// *at 调用的 flags
+pub const AT_SYMLINK_NOFOLLOW: i32 = 0x100;
+pub const AT_SYMLINK_FOLLOW: i32 = 0x400;
+pub const AT_EMPTY_PATH: i32 = 0x1000;
 
 #[derive(Debug, Clone, Copy)]
 #[repr(C)]
@@ -57,7 +64,9 @@ pub fn sys_write(fd: usize, buf: *mut u8, len: usize) -> SysResult<isize> {
     let file = file.clone();
     // release current task TCB manually to avoid multi-borrow
     drop(inner);
-    Ok(file.write(translated_byte_buffer(token, buf, len)) as isize)
+    let len = fsize_allowed(&file, None, len)?;
+    let written = file.write_checked(translated_byte_buffer(token, buf, len))?;
+    Ok(sync_after_write(&file, written, 0) as isize)
 }
 
 
@@ -74,27 +83,17 @@ pub fn sys_read(fd: usize, buf: *mut u8, len: usize) -> SysResult<isize> {
     }
     // release current task TCB manually to avoid multi-borrow
     drop(inner);
-    Ok(file.read(translated_byte_buffer(token, buf, len)) as isize)
+    Ok(file.read_checked(translated_byte_buffer(token, buf, len))? as isize)
 }
 
-pub fn sys_openat(pfd:isize,path: *const u8, flags: u32,_mode:u32) -> SysResult<isize> {
+pub fn sys_openat(pfd:isize,path: *const u8, flags: u32,mode:u32) -> SysResult<isize> {
     let task = current_task().unwrap();
     let token = current_user_token();
     let path = translated_str(token, path);
-    if path.chars().next() == Some('/') || pfd == AT_FDCWD{
-        let inode = open_file(path.as_str(), OpenFlags::from_bits(flags).unwrap())?;
-        let inner = task.inner_exclusive_access();
-        let fd = inner.fd_table.lock().insert(Some(Fd::new(inode, FdFlags::empty())))?;
-        return Ok(fd as isize);
-    }
+    let base = dirfd_base(pfd, &path)?;
+    let perm = InodeMode::from_bits_truncate(mode & 0o7777);
+    let inode = open_file_at(base, path.as_str(), OpenFlags::from_bits_truncate(flags), perm)?;
     let inner = task.inner_exclusive_access();
-    let fdtable = inner.fd_table.lock();
-    let file = fdtable.get(pfd as usize)?;
-    let file = file.file();
-    drop(fdtable);
-    let father_path = file.get_dentry().path();
-    let child_path = father_path+&path;
-    let inode = open_file(child_path.as_str(), OpenFlags::from_bits(flags).unwrap())?;
     let fd = inner.fd_table.lock().insert(Some(Fd::new(inode, FdFlags::empty())))?;
     return Ok(fd as isize);
 }
@@ -102,40 +101,43 @@ pub fn sys_openat(pfd:isize,path: *const u8, flags: u32,_mode:u32) -> SysResult<
 pub fn sys_close(fd: usize) -> SysResult<isize> {
     let task = current_task().unwrap();
     let inner = task.inner_exclusive_access();
-    let _ = inner.fd_table.lock().remove(fd)?;
+    let mut fdtable = inner.fd_table.lock();
+    let file = fdtable.get_file(fd)?;
+    fdtable.remove(fd)?;
+    drop(fdtable);
+    drop(inner);
+    // 关闭任一描述符都会释放本进程在该 inode 上的 POSIX 锁
+    release_posix_locks(&file, task.getpid());
     return Ok(0);
 }
 
 ///
-pub fn sys_mkdirat(pfd:isize,path: *const u8,_mode:u32) -> SysResult<isize> {
-    let task = current_task().unwrap();
+pub fn sys_mkdirat(pfd:isize,path: *const u8,mode:u32) -> SysResult<isize> {
     let token = current_user_token();
     let path = translated_str(token, path);
-    if path.chars().next() == Some('/') || pfd == AT_FDCWD{
-        let _inode = create_file(path.as_str(), vfs_defs::DiskInodeType::Directory)?;
-        return Ok(0);
-    }
-    let inner = task.inner_exclusive_access();
-    let fdtable = inner.fd_table.lock();
-    let file = fdtable.get(pfd as usize)?;
-    let file = file.file();
-    drop(fdtable);
-    let father_path = file.get_dentry().path();
-    let child_path = father_path+&path;
-    let _inode = create_file(child_path.as_str(), vfs_defs::DiskInodeType::Directory)?;
+    let base = dirfd_base(pfd, &path)?;
+    let perm = InodeMode::from_bits_truncate(mode & 0o7777);
+    let _inode = create_file_at(base, path.as_str(), vfs_defs::DiskInodeType::Directory, perm)?;
     return Ok(0);
 }
 
-pub fn sys_pipe(pipe: *mut i32) -> SysResult<isize> {
+/// 创建管道, 只接受 O_CLOEXEC 和 O_NONBLOCK
+pub fn sys_pipe2(pipe: *mut i32, flags: u32) -> SysResult<isize> {
+    let flags = OpenFlags::from_bits(flags).ok_or(SysError::EINVAL)?;
+    if !(OpenFlags::CLOEXEC | OpenFlags::NONBLOCK).contains(flags) {
+        return Err(SysError::EINVAL);
+    }
     let task = current_task().unwrap();
     let token = current_user_token();
-    //let mut inner = task.acquire_inner_lock();
     let inner = task.inner_exclusive_access();
     //自身目录项
     let self_super = inner.cwd.get_superblock();
     let (pipe_read, pipe_write) = make_pipe(self_super); //创建一个管道并获取其读端和写端
-    let read_fd = inner.fd_table.lock().insert(Some(Fd::new(pipe_read, FdFlags::empty())))?;
-    let write_fd = inner.fd_table.lock().insert(Some(Fd::new(pipe_write, FdFlags::empty())));
+    *pipe_read.get_inner().flags.lock() = flags & OpenFlags::NONBLOCK;
+    *pipe_write.get_inner().flags.lock() = (flags & OpenFlags::NONBLOCK) | OpenFlags::WRONLY;
+    let fd_flags = FdFlags::from(flags);
+    let read_fd = inner.fd_table.lock().insert(Some(Fd::new(pipe_read, fd_flags)))?;
+    let write_fd = inner.fd_table.lock().insert(Some(Fd::new(pipe_write, fd_flags)));
     if let Err(e) = write_fd{
         let _ = inner.fd_table.lock().remove(read_fd);
         return Err(e);
@@ -184,47 +186,92 @@ pub fn sys_dup3(old: usize, new: usize, _flags: usize) -> SysResult<isize> {
     }
     let binding = current_task().unwrap();
     let task_inner = binding.inner_exclusive_access();
-    let fd_table = &task_inner.fd_table;
-    return  fd_table.lock().dup3(old, new, _flags);
+    let replaced = task_inner.fd_table.lock().dup3(old, new, _flags)?;
+    drop(task_inner);
+    // 顶替掉的描述符相当于被 close, 同样释放本进程的 POSIX 锁
+    if let Some(replaced) = replaced {
+        release_posix_locks(&replaced.file(), binding.getpid());
+    }
+    return Ok(new as isize);
 }
 
-pub fn sys_mount(_special:*const u8,dir:*const u8,fstype:*const u8,_flags:u32,_data:*const u8)->SysResult<isize>{
+pub fn sys_mount(special:*const u8,dir:*const u8,fstype:*const u8,flags:u32,data:*const u8)->SysResult<isize>{
+    check_privileged()?;
     let token = current_user_token();
     let dir = translated_str(token, dir);
-    let fstype = translated_str(token, fstype);
-    let ext4fstype = FILE_SYSTEMS.lock().find_fs(&String::from("Ext4")).unwrap();
-    if fstype == "vfat"{
-        let mut name = String::new();
-        let parent = path_to_father_dentry(dir.as_str(), &mut name);
-        let mount_parent;
-        let device = BLOCK_DEVICE.get().unwrap().clone();
-        if let Ok(p) = parent{
-            mount_parent = Some(p);
-        }
-        else {
-            mount_parent = None;
-        }
-        let r = ext4fstype.mount(name.as_str(), mount_parent, MountFlags::empty(), Some(device));
-        if let Err(e) = r{
-            return Err(e);
-        }
+    let flags = MountFlags::from_bits_truncate(flags);
+    let target = path_to_dentry(dir.as_str())?;
+    if flags.contains(MountFlags::MS_REMOUNT) {
+        vfs::do_remount(&target, flags)?;
         return Ok(0);
     }
-    else{
+    let special = translated_str(token, special);
+    if flags.contains(MountFlags::MS_BIND) {
+        let source = path_to_dentry(special.as_str())?;
+        vfs::do_bind(&source, &target, flags)?;
+        return Ok(0);
+    }
+    if flags.contains(MountFlags::MS_MOVE) {
         return Err(SysError::EINVAL);
     }
+    let fstype = if fstype.is_null() {
+        String::from("auto")
+    } else {
+        translated_str(token, fstype)
+    };
+    // 只有需要块设备的文件系统才关心 special 能否解析
+    let device = path_to_dentry(special.as_str()).and_then(|dentry| {
+        dentry
+            .get_inode()?
+            .downcast_arc::<BlockDevInode>()
+            .map(|inode| inode.dev.clone())
+            .map_err(|_| SysError::ENOTBLK)
+    });
+    let data = if data.is_null() {
+        String::new()
+    } else {
+        translated_str(token, data)
+    };
+    vfs::do_mount(special.as_str(), &target, fstype.as_str(), flags, device, data.as_str())?;
+    Ok(0)
+}
+
+/// umount2 的 flags
+const MNT_FORCE: u32 = 1;
+const MNT_DETACH: u32 = 2;
+const MNT_EXPIRE: u32 = 4;
+const UMOUNT_NOFOLLOW: u32 = 8;
+
+/// 是否有任务的工作目录或打开的文件位于挂载之下
+fn mount_in_use(mount: &Mount) -> bool {
+    all_tasks().iter().any(|task| {
+        let inner = task.inner_exclusive_access();
+        mount.contains(&inner.cwd)
+            || inner
+                .fd_table
+                .lock()
+                .fd_table
+                .iter()
+                .flatten()
+                .any(|fd| mount.contains(&fd.file().get_dentry()))
+    })
 }
 
-pub fn sys_umount(special:*const u8,_flags:u32)->SysResult<isize>{
+pub fn sys_umount(special:*const u8,flags:u32)->SysResult<isize>{
+    if flags & !(MNT_FORCE | MNT_DETACH | MNT_EXPIRE | UMOUNT_NOFOLLOW) != 0 {
+        return Err(SysError::EINVAL);
+    }
+    check_privileged()?;
     let token = current_user_token();
     let path = translated_str(token, special);
-    let ext4fstype = FILE_SYSTEMS.lock().find_fs(&String::from("Ext4")).unwrap();
-    let dentry = path_to_dentry(&path)?;
-    if let Err(_e) = ext4fstype.umount(dentry.path().as_str(), MountFlags::empty()){
-        return Ok(0);
-       // return Err(e);
+    let dentry = lookup_at(None, &path, flags & UMOUNT_NOFOLLOW == 0)?;
+    let mount = vfs::find_mount(&dentry).ok_or(SysError::EINVAL)?;
+    // MNT_DETACH 立即摘下挂载点, 已打开的文件继续持有各自的目录项
+    if flags & MNT_DETACH == 0 && mount_in_use(&mount) {
+        return Err(SysError::EBUSY);
     }
-    return Ok(0);
+    vfs::do_umount(&mount, MountFlags::empty())?;
+    Ok(0)
 }
 
 pub fn sys_fstat(fd:usize,kst:*mut Kstat)->SysResult<isize>{
@@ -241,11 +288,17 @@ pub fn sys_fstat(fd:usize,kst:*mut Kstat)->SysResult<isize>{
     return Ok(0);
 }
 
-pub fn sys_fstatat(dirfd:usize,path:*const u8,kst:*mut Kstat,_flags:i32)->SysResult<isize>{
-    let path = parse_fd_path(dirfd as isize, path)?;
-    let old = path_to_dentry(&path)?;
-    let attr = old.get_inode().unwrap().get_attr()?;
+/// 参数由各个文件按请求自己复制, 见 [`vfs::read_user`]
+pub fn sys_ioctl(fd:usize,cmd:usize,arg:usize)->SysResult<isize>{
+    let file = current_file(fd)?;
+    file.ioctl(cmd, arg)
+}
+
+pub fn sys_fstatat(dirfd:usize,path:*const u8,kst:*mut Kstat,flags:i32)->SysResult<isize>{
     let token = current_user_token();
+    let path = translated_str(token, path);
+    let old = lookup_dirfd(dirfd as isize, &path, flags)?;
+    let attr = old.get_inode()?.get_attr()?;
     let kst = translated_refmut(token, kst);
     *kst = attr;
     return Ok(0);
@@ -307,6 +360,18 @@ pub fn sys_mmap(
         if !file.readable() {
             return Err(SysError::EACCES);
         }
+        if prot.contains(MapPermission::X)
+            && vfs::mount_flags(&file.get_dentry()).contains(MountFlags::MS_NOEXEC)
+        {
+            return Err(SysError::EPERM);
+        }
+        // 禁止写的 memfd 不能建立可写的共享映射
+        if flags.contains(MmapFlags::MAP_SHARED) && prot.contains(MapPermission::W) {
+            let seals = file.get_dentry().get_inode()?.get_meta().inner.lock().seals;
+            if seals.is_some_and(|seals| seals.intersects(FileSeals::WRITE | FileSeals::FUTURE_WRITE)) {
+                return Err(SysError::EPERM);
+            }
+        }
         map_file = Some(file);
             // release current task TCB manually to avoid multi-borrow
         drop(inner);
@@ -383,13 +448,8 @@ pub fn sys_getdents(fd:usize,buf:*mut u8,len:usize)->SysResult<isize>{
         let d_off = *offset as u64;
         let inode = dentry.get_inode().unwrap();
         let d_ino = inode.get_meta().ino as u64;
-        let mut d_type:u8 = 0;
-        if inode.is_dir(){
-            d_type = 4;
-        }
-        else if inode.is_file(){
-            d_type = 8;
-        }
+        // DT_* 恰好是 st_mode 的类型位右移 12 位
+        let d_type = ((inode.get_meta().mode & InodeMode::TYPE_MASK).bits() >> 12) as u8;
         let syscall_dirent = SyscallDirent{
             d_ino,
             d_off,
@@ -411,16 +471,24 @@ pub fn sys_getdents(fd:usize,buf:*mut u8,len:usize)->SysResult<isize>{
 }
 
 ///
-pub fn sys_link(old_dirfd:isize,old_path: *const u8,new_dirfd:isize,new_path:*const u8,_flags:u32) -> SysResult<isize> {
-    let old_path = parse_fd_path(old_dirfd, old_path)?;
-    let new_path = parse_fd_path(new_dirfd, new_path)?;
-    let dentry = path_to_dentry(&old_path)?;
+pub fn sys_link(old_dirfd:isize,old_path: *const u8,new_dirfd:isize,new_path:*const u8,flags:u32) -> SysResult<isize> {
+    let flags = flags as i32;
+    if flags & !(AT_SYMLINK_FOLLOW | AT_EMPTY_PATH) != 0 {
+        return Err(SysError::EINVAL);
+    }
+    let token = current_user_token();
+    let old_path = translated_str(token, old_path);
+    let new_path = translated_str(token, new_path);
+    // linkat 默认不跟随符号链接, 与 fstatat 相反
+    let follow = if flags & AT_SYMLINK_FOLLOW != 0 { 0 } else { AT_SYMLINK_NOFOLLOW };
+    let dentry = lookup_dirfd(old_dirfd, &old_path, (flags & AT_EMPTY_PATH) | follow)?;
     if dentry.is_dir(){
         drop(dentry);
-        return Err(SysError::EISDIR);
+        return Err(SysError::EPERM);
     }
-    let mut name = String::new();
-    let father_dentry = path_to_father_dentry(&new_path,&mut name)?;
+    let (father_dentry, name) = lookup_parent_at(dirfd_base(new_dirfd, &new_path)?, &new_path)?;
+    vfs::check_writable(&father_dentry)?;
+    vfs::may_modify_dir(&father_dentry, &current_fs_cred())?;
     let r = father_dentry.lookup(name.as_str());
     if r.is_ok(){//EEXIST
         return Err(SysError::EEXIST);
@@ -430,297 +498,1282 @@ pub fn sys_link(old_dirfd:isize,old_path: *const u8,new_dirfd:isize,new_path:*co
     return Ok(0);
 }
 
+/// 在 `linkpath` 处创建指向 `target` 的符号链接, `target` 原样保存, 不检查是否存在
+pub fn sys_symlinkat(target:*const u8,newdirfd:isize,linkpath:*const u8)->SysResult<isize>{
+    let token = current_user_token();
+    let target = translated_str(token, target);
+    let linkpath = translated_str(token, linkpath);
+    if target.is_empty() {
+        return Err(SysError::ENOENT);
+    }
+    if target.len() >= vfs::PATH_MAX {
+        return Err(SysError::ENAMETOOLONG);
+    }
+    let (father, name) = lookup_parent_at(dirfd_base(newdirfd, &linkpath)?, &linkpath)?;
+    if name == "." || name == ".." {
+        return Err(SysError::EEXIST);
+    }
+    vfs::check_writable(&father)?;
+    vfs::may_modify_dir(&father, &current_fs_cred())?;
+    let link = father.symlink(&name, &target)?;
+    let perm = InodeMode::OWNER_MASK | InodeMode::GROUP_MASK | InodeMode::OTHER_MASK;
+    set_new_owner(&father, &link, perm)?;
+    Ok(0)
+}
+
+/// 创建 FIFO、套接字或设备节点, 类型为 0 时创建普通文件. 设备节点只有 root 能建
+pub fn sys_mknodat(dirfd:isize,path:*const u8,mode:u32,dev:u64)->SysResult<isize>{
+    let token = current_user_token();
+    let path = translated_str(token, path);
+    let file_type = InodeMode::from_bits_truncate(mode) & InodeMode::TYPE_MASK;
+    let perm = InodeMode::from_bits_truncate(mode & 0o7777);
+    let regular = file_type.is_empty() || file_type == InodeMode::FILE;
+    let cred = current_fs_cred();
+    if file_type == InodeMode::CHAR || file_type == InodeMode::BLOCK {
+        if !cred.is_root() {
+            return Err(SysError::EPERM);
+        }
+    } else if file_type == InodeMode::DIR {
+        return Err(SysError::EPERM);
+    } else if !regular && file_type != InodeMode::FIFO && file_type != InodeMode::SOCKET {
+        return Err(SysError::EINVAL);
+    }
+    let (father, name) = lookup_parent_at(dirfd_base(dirfd, &path)?, &path)?;
+    if name == "." || name == ".." {
+        return Err(SysError::EEXIST);
+    }
+    vfs::check_writable(&father)?;
+    vfs::may_modify_dir(&father, &cred)?;
+    let node = if regular {
+        if father.lookup(&name).is_ok() {
+            return Err(SysError::EEXIST);
+        }
+        father.create(&name, vfs_defs::DiskInodeType::File)?
+    } else {
+        father.mknod(&name, file_type | perm, dev)?
+    };
+    set_new_owner(&father, &node, perm)?;
+    Ok(0)
+}
+
+/// 读出符号链接的内容, 不补结尾的 0, 超出 `bufsiz` 的部分截断
+pub fn sys_readlinkat(dirfd:isize,path:*const u8,buf:*mut u8,bufsiz:isize)->SysResult<isize>{
+    if bufsiz <= 0 {
+        return Err(SysError::EINVAL);
+    }
+    let token = current_user_token();
+    let path = translated_str(token, path);
+    let dentry = lookup_dirfd(dirfd, &path, AT_SYMLINK_NOFOLLOW | AT_EMPTY_PATH)?;
+    let target = dentry.get_inode()?.read_link()?;
+    let len = target.len().min(bufsiz as usize);
+    let task = current_task().unwrap();
+    let memory_set = task.inner_exclusive_access().memory_set.clone();
+    let buf = safe_translated_byte_buffer(memory_set, buf, len);
+    buf.copy_from_slice(&target.as_bytes()[..len]);
+    Ok(len as isize)
+}
+
 ///
 pub fn sys_unlink(dirfd:isize,path: *const u8,_flags:u32) -> SysResult<isize> {
-    let path = parse_fd_path(dirfd, path)?;
-    let mut name = String::new();
-    let father = path_to_father_dentry(&path,&mut name)?;
+    let token = current_user_token();
+    let path = translated_str(token, path);
+    let (father, name) = lookup_parent_at(dirfd_base(dirfd, &path)?, &path)?;
     if name.eq(".") || name.eq(".."){
         return Err(SysError::EINVAL);
     }
-    let old = path_to_dentry(&path)?;
+    vfs::check_writable(&father)?;
+    let cred = current_fs_cred();
+    vfs::may_modify_dir(&father, &cred)?;
+    // 删除的是链接本身, 不跟随
+    let old = father.lookup(name.as_str())?;
+    if vfs::lookup_mount(&old).is_some() {
+        return Err(SysError::EBUSY);
+    }
+    vfs::check_sticky(&father, &old, &cred)?;
     father.unlink(&old)?;
     return Ok(0);
 }
 
-fn parse_fd_path(fd: isize,path:*const u8)->SysResult<String>{
-   // if fd == 0 || fd == 1{
-  //      return None;
-  //  }
-    let token = current_user_token();
-    let path = translated_str(token, path);
-    if path.chars().next() == Some('/') || fd == AT_FDCWD{
-        return Ok(path);
+/// *at 调用中相对路径的起点, 为空时从工作目录开始
+fn dirfd_base(dirfd: isize, path: &str) -> SysResult<Option<Arc<dyn Dentry>>> {
+    if path.starts_with('/') || dirfd == AT_FDCWD {
+        return Ok(None);
     }
     let task = current_task().unwrap();
     let inner = task.inner_exclusive_access();
-    let fdtable = inner.fd_table.lock();
-    let file = fdtable.get(fd as usize)?;
-    let file = file.file();
-    drop(fdtable);
-    let father_path = file.get_dentry().path();
-    let child_path = father_path+&path;
-    drop(inner);
-    return Ok(child_path);
+    let dentry = inner.fd_table.lock().get_file(dirfd as usize)?.get_dentry();
+    Ok(Some(dentry))
 }
 
-pub fn sys_writev(fd:isize,iov:*const IoVec,iovcnt:usize)->SysResult<isize>{
-    let token = current_user_token();
-    let task = current_task().unwrap();
-    let inner = task.inner_exclusive_access();
-    let fdtable = inner.fd_table.lock();
-    let file = fdtable.get(fd as usize)?;
-    let file = file.file();
-    drop(fdtable);
-    let mut offset = file.get_offset();
-    let mut total_write_size = 0;
-    let mut iov_iter = iov;
-   
-    for _i in 0..iovcnt{ 
-    //    println!("iov:{:?}",iov_iter);
-        let iovs = translated_ref(token, iov_iter);
-        if iovs.len == 0{
-            unsafe {
-                let _ = iov_iter.add(1);
-            }
-            continue;
-        }
-    //    println!("writev:write len:{}",iovs.len);
-        let ptr = iovs.base;
-        let buf = translated_byte_buffer(token, ptr as *mut u8, iovs.len);
-        let write_size = file.write_at(*offset, buf);
-        total_write_size += write_size;
-        *offset += write_size;
-        unsafe {
-            iov_iter = iov_iter.add(1);
+/// 按 *at 调用的约定解析路径: 带 AT_EMPTY_PATH 的空路径指 dirfd 本身,
+/// 带 AT_SYMLINK_NOFOLLOW 时不展开末尾的符号链接
+fn lookup_dirfd(dirfd: isize, path: &str, flags: i32) -> SysResult<Arc<dyn Dentry>> {
+    if path.is_empty() && flags & AT_EMPTY_PATH != 0 {
+        let task = current_task().unwrap();
+        let inner = task.inner_exclusive_access();
+        if dirfd == AT_FDCWD {
+            return Ok(inner.cwd.clone());
         }
+        let dentry = inner.fd_table.lock().get_file(dirfd as usize)?.get_dentry();
+        return Ok(dentry);
     }
-    drop(offset);
-    file.seek(total_write_size as i64, vfs_defs::SeekFlags::SEEK_CUR)?;
-    return Ok(total_write_size as isize);
+    let base = dirfd_base(dirfd, path)?;
+    lookup_at(base, path, flags & AT_SYMLINK_NOFOLLOW == 0)
+}
+
+pub fn sys_writev(fd:isize,iov:*const IoVec,iovcnt:usize)->SysResult<isize>{
+    let file = rw_file(fd, false)?;
+    let bufs = user_iovecs(iov, iovcnt)?;
+    // 写管道可能阻塞, 取完文件就不再持有任务的锁
+    Ok(do_pwritev(&file, bufs, None, 0)? as isize)
 }
 
 pub fn sys_readv(fd:isize,iov:*const IoVec,iovcnt:usize)->SysResult<isize>{
-    let token = current_user_token();
+    let file = rw_file(fd, true)?;
+    let bufs = user_iovecs(iov, iovcnt)?;
+    Ok(do_preadv(&file, bufs, None, 0)? as isize)
+}
+
+/// readv/writev 最多接受的 iovec 个数
+const IOV_MAX: usize = 1024;
+/// preadv2/pwritev2 的 flags
+const RWF_HIPRI: u32 = 1;
+const RWF_DSYNC: u32 = 2;
+const RWF_SYNC: u32 = 4;
+const RWF_NOWAIT: u32 = 8;
+const RWF_APPEND: u32 = 16;
+
+/// 取出要读写的文件, 检查打开方式
+fn rw_file(fd:isize,read:bool)->SysResult<Arc<dyn File>>{
     let task = current_task().unwrap();
-    let inner = task.inner_exclusive_access();
-    let fdtable = inner.fd_table.lock();
-    let file = fdtable.get(fd as usize)?;
-    let file = file.file();
-    drop(fdtable);
-    let mut offset = file.get_offset();
-    let mut total_read_size = 0;
-    let mut iov_iter = iov;
-   
-    for _i in 0..iovcnt{ 
-    //    println!("iov:{:?}",iov_iter);
-        let iovs = translated_ref(token, iov_iter);
-        if iovs.len == 0{
-            unsafe {
-                let _ = iov_iter.add(1);
-            }
-            continue;
-        }
-    //    println!("writev:write len:{}",iovs.len);
-        let ptr = iovs.base;
-        let buf = translated_byte_buffer(token, ptr as *mut u8, iovs.len);
-        let read_size = file.read_at(*offset, buf);
-        total_read_size += read_size;
-        *offset += read_size;
-        unsafe {
-            iov_iter = iov_iter.add(1);
-        }
+    let file = task.inner_exclusive_access().fd_table.lock().get_file(fd as usize)?;
+    if (read && !file.readable()) || (!read && !file.writable()) {
+        return Err(SysError::EBADF);
     }
-    drop(offset);
-    file.seek(total_read_size as i64, vfs_defs::SeekFlags::SEEK_CUR)?;
-    return Ok(total_read_size as isize);
+    Ok(file)
 }
 
-pub fn sys_statfs(_path:*const u8,buf:*mut StatFs)->SysResult<isize>{
+/// 翻译用户的 iovec 数组, 跳过长度为 0 的项
+fn user_iovecs(iov:*const IoVec,iovcnt:usize)->SysResult<Vec<&'static mut [u8]>>{
+    if iovcnt > IOV_MAX {
+        return Err(SysError::EINVAL);
+    }
     let token = current_user_token();
-    let buf = translated_refmut(token, buf);
-    *buf =  StatFs {
-        f_type: 0x2011BAB0 as i64,
-        f_bsize: vfs::BLOCK_SIZE as i64,
-        f_blocks: 1 << 27,
-        f_bfree: 1 << 26,
-        f_bavail: 1 << 20,
-        f_files: 1 << 10,
-        f_ffree: 1 << 9,
-        f_fsid: [0; 2],
-        f_namelen: 1 << 8,
-        f_frsize: 1 << 9,
-        f_flags: 1 << 1 as i64,
-        f_spare: [0; 4],
-    };
-    Ok(0)
+    let mut bufs = Vec::with_capacity(iovcnt);
+    let mut total: usize = 0;
+    for i in 0..iovcnt {
+        let iovec = *translated_ref(token, unsafe { iov.add(i) });
+        total = total.checked_add(iovec.len).filter(|total| *total <= isize::MAX as usize).ok_or(SysError::EINVAL)?;
+        if iovec.len != 0 {
+            bufs.push(translated_byte_buffer(token, iovec.base as *mut u8, iovec.len));
+        }
+    }
+    Ok(bufs)
 }
 
-pub fn sys_faccessat(dirfd:isize,path:*const u8,_mode:usize,flags:i32)->SysResult<isize>{
-    let path = parse_fd_path(dirfd, path)?;
-    if flags == 0x100 as i32 {//no symlink now
-        return Err(SysError::EINVAL);
+/// 位置参数: 负数表示使用并推进文件偏移 (preadv2/pwritev2 的 -1), 其余为绝对位置
+fn rw_offset(offset:isize,allow_current:bool)->SysResult<Option<usize>>{
+    match offset {
+        -1 if allow_current => Ok(None),
+        offset if offset < 0 => Err(SysError::EINVAL),
+        offset => Ok(Some(offset as usize)),
     }
-    let _file = open_file(path.as_str(), OpenFlags::empty())?;
-    return Ok(0);
 }
 
-pub fn sys_lseek(fd:isize,offset:isize,whence:usize)->SysResult<isize>{
-    let task = current_task().unwrap();
-    let inner = task.inner_exclusive_access();
-    let fdtable = inner.fd_table.lock();
-    let file = fdtable.get(fd as usize)?;
-    let _file = file.file();
-    drop(fdtable);
-    match whence{
-        0=>{
-            return _file.seek(offset as i64, SeekFlags::SEEK_SET);
-        },
-        1=>{
-            return _file.seek(offset as i64, SeekFlags::SEEK_CUR);
-        },
-        2=>{
-            return _file.seek(offset as i64, SeekFlags::SEEK_END);
-        },
-        _=>{}
+fn check_rwf(flags:u32)->SysResult<()>{
+    if flags & !(RWF_HIPRI | RWF_DSYNC | RWF_SYNC | RWF_NOWAIT | RWF_APPEND) != 0 {
+        return Err(SysError::EOPNOTSUPP);
     }
-    return Err(SysError::EINVAL);
+    Ok(())
 }
 
-pub fn sys_utimensat(dirfd:isize,path:*const u8,times:*const crate::task::TimeSpec,flags:i32)->SysResult<isize>{
-    let inode;
-    if path.is_null(){
-        match  dirfd {
-            AT_FDCWD=>{
-                return Err(SysError::EINVAL);
-            }
-            _=>{
-                let task = current_task().unwrap();
-                let inner = task.inner_exclusive_access();
-                inode = inner.fd_table.lock().get_file(dirfd as usize)?.get_dentry().get_inode()?;
-            }
-        }
-    }
-    else{
-        let path = parse_fd_path(dirfd, path)?;
-        let flags = OpenFlags::from_bits(flags as u32);
-        if flags.is_none(){
-            return Err(SysError::EINVAL);
-        }
-        inode = open_file(path.as_str(), flags.unwrap())?.get_dentry().get_inode()?
+/// 依次读入各段缓冲区, `offset` 为 None 时从文件偏移读. 读到短的一段就停
+fn do_preadv(file:&Arc<dyn File>,bufs:Vec<&'static mut [u8]>,offset:Option<usize>,flags:u32)->SysResult<usize>{
+    if file.get_dentry().get_inode()?.get_meta().mode & InodeMode::TYPE_MASK == InodeMode::DIR {
+        return Err(SysError::EISDIR);
     }
-    let current = Time::now();
-    let mut inner = inode.get_meta().inner.lock();
-    let timespec_now = TimeSpec{
-        sec:current.to_sec(),
-        usec:current.to_usec()
-    };
-    if times.is_null(){
-        inner.atime = timespec_now;
-        inner.mtime = timespec_now;
-        inner.ctime = timespec_now;
+    // 普通文件的读不会阻塞, 只有按文件偏移读管道之类时 RWF_NOWAIT 才有意义
+    if flags & RWF_NOWAIT != 0 && offset.is_none() && file.poll(PollEvents::POLLIN).is_empty() {
+        return Err(SysError::EAGAIN);
     }
-    else{
-        const UTIME_NOW: usize = 0x3fffffff;
-        const UTIME_OMIT: usize = 0x3ffffffe;
-        let token = current_user_token();
-        let times1 = translated_ref(token, times);
-        let times2;
-        unsafe {
-            times2 = translated_ref(token, times.add(1));
-        }
-        match times1.usec{
-            UTIME_NOW=>{
-                inner.atime = timespec_now;
-            }
-            UTIME_OMIT=>{}
-            _=>{
-                inner.atime = *times1;
+    let mut total = 0;
+    for buf in bufs {
+        let len = buf.len();
+        let ret = match offset {
+            Some(offset) => file.pread(offset + total, buf),
+            None => file.read_checked(buf),
+        };
+        match ret {
+            Ok(read_size) => {
+                total += read_size;
+                if read_size < len {
+                    break;
+                }
             }
+            Err(e) if total == 0 => return Err(e),
+            Err(_) => break,
         }
-        match times2.usec{
-            UTIME_NOW=>{
-                inner.mtime = timespec_now;
-            }
-            UTIME_OMIT=>{}
-            _=>{
-                inner.mtime = *times2;
+    }
+    Ok(total)
+}
+
+/// 依次写出各段缓冲区. 带 RWF_APPEND, 或按位置写 O_APPEND 打开的文件时
+/// (与 Linux 相同) 一律追加到文件末尾
+fn do_pwritev(file:&Arc<dyn File>,bufs:Vec<&'static mut [u8]>,offset:Option<usize>,flags:u32)->SysResult<usize>{
+    if flags & RWF_NOWAIT != 0 && offset.is_none() && file.poll(PollEvents::POLLOUT).is_empty() {
+        return Err(SysError::EAGAIN);
+    }
+    let append = flags & RWF_APPEND != 0
+        || (offset.is_some() && file.get_inner().flags.lock().contains(OpenFlags::APPEND));
+    let mut total = 0;
+    for buf in bufs {
+        let pos = if append { None } else { offset.map(|offset| offset + total) };
+        let allowed = match fsize_allowed(file, pos, buf.len()) {
+            Ok(allowed) => allowed,
+            Err(e) if total == 0 => return Err(e),
+            Err(_) => break,
+        };
+        let buf = &buf[..allowed];
+        let len = buf.len();
+        let ret = match offset {
+            _ if append => file.append(buf).map(|(write_size, end)| {
+                if offset.is_none() {
+                    *file.get_offset() = end;
+                }
+                write_size
+            }),
+            Some(offset) => file.pwrite(offset + total, buf),
+            None => file.write_checked(buf),
+        };
+        match ret {
+            Ok(write_size) => {
+                total += write_size;
+                if write_size < len {
+                    break;
+                }
             }
+            Err(e) if total == 0 => return Err(e),
+            Err(_) => break,
         }
-        inner.ctime = timespec_now;
     }
-    Ok(0)
+    Ok(sync_after_write(file, total, flags))
 }
-const F_DUPFD:isize = 0;
-const F_DUPFD_CLOEXEC:isize = 1030;
-const F_GETFD:isize = 1;
-const F_SETFD:isize = 2;
-const F_GETFL:isize = 3;
-const F_SETFL:isize = 4;
-//F_UNIMPL,
-pub fn sys_fcntl(fd:isize,op:isize,arg:usize)->SysResult<isize>{
-    let task = current_task().unwrap();
-    let inner = task.inner_exclusive_access();
-    let mut fdtable = inner.fd_table.lock();
-    match op{
-        F_DUPFD=>{
-            fdtable.dup_with_arg(fd as usize, arg,OpenFlags::empty())
-        }
-        F_DUPFD_CLOEXEC=>{
-            fdtable.dup_with_arg(fd as usize, arg,OpenFlags::CLOEXEC)
-        }
-        F_GETFD=>{
-            let fd = fdtable.get(fd as usize)?;
-            return Ok(fd.get_flags().bits() as isize);
-        }
-        F_SETFD=>{
-            let fd = fdtable.get_mut(fd as usize)?;
-            fd.set_flags(FdFlags::from(OpenFlags::from_bits_retain(arg as u32)));
-            return Ok(0);
-        }
-        F_GETFL=>{
-            let file = fdtable.get(fd as usize)?;
-            let file = file.file();
-            let flag = file.get_inner().flags.lock();
-            return Ok(flag.bits() as isize);
-        }
-        F_SETFL=>{
-            let file = fdtable.get(fd as usize)?;
-            let file = file.file();
-            *file.get_inner().flags.lock() = OpenFlags::from_bits_retain(arg as u32);
-            return Ok(0);
-        }
-        _ =>{
-            return Ok(0);
+
+/// O_SYNC/O_DSYNC 打开的文件, 或带 RWF_SYNC/RWF_DSYNC 的写, 写完后落盘.
+/// 数据已经写进文件, 落盘失败也返回写入的字节数, 什么都没写时不落盘
+fn sync_after_write(file:&Arc<dyn File>,written:usize,flags:u32)->usize{
+    if written == 0 {
+        return 0;
+    }
+    // OpenFlags::SYNC 包含 DSYNC 位
+    if flags & (RWF_SYNC | RWF_DSYNC) != 0 || file.get_inner().flags.lock().contains(OpenFlags::DSYNC) {
+        if let Err(e) = file.get_dentry().get_superblock().sync_fs() {
+            log::warn!("sync after write failed: {:?}", e);
         }
     }
+    written
 }
 
-pub fn sys_sendfile(outfd:isize,infd:isize,offset:*mut usize,count:usize)->SysResult<isize>{
-    let token = current_user_token();
-    let task = current_task().unwrap();
-    let inner = task.inner_exclusive_access();
-    let table = inner.fd_table.lock();
-    let (outfile,infile) = (table.get_file(outfd as usize)?,table.get_file(infd as usize)?);
-    if !infile.readable() || !outfile.writable() {
-        return Err(SysError::EBADF);
+/// 按 RLIMIT_FSIZE 算出从 `pos` (None 表示文件偏移或追加时的末尾) 开始最多能写多少.
+/// 一个字节都不能写时发 SIGXFSZ 并返回 EFBIG, 只对普通文件生效
+fn fsize_allowed(file:&Arc<dyn File>,pos:Option<usize>,len:usize)->SysResult<usize>{
+    let limit = current_task().unwrap().inner_exclusive_access().fsize_limit.rlimit_cur;
+    if limit == usize::MAX || len == 0 {
+        return Ok(len);
     }
-    let mut buf = vec![0 as u8; count];
-    let len;
-    if offset.is_null(){
-        len = infile.read(&mut buf);
+    let inode = file.get_dentry().get_inode()?;
+    if inode.get_meta().mode & InodeMode::TYPE_MASK != InodeMode::FILE {
+        return Ok(len);
     }
-    else{
-        let offset = translated_refmut(token, offset);
-        len = infile.read_at(*offset, &mut buf);
-        *offset = *offset + len;
+    let pos = match pos {
+        Some(pos) => pos,
+        None if file.get_inner().flags.lock().contains(OpenFlags::APPEND) => inode.get_size() as usize,
+        None => *file.get_offset(),
+    };
+    if pos >= limit {
+        send_signal_to_current(SignalFlags::SIGXFSZ);
+        return Err(SysError::EFBIG);
     }
-    let ret = outfile.write(&buf[..len]);
-    return Ok(ret as isize);
+    Ok(len.min(limit - pos))
 }
 
-#[derive(Debug, Copy, Clone)]
-#[repr(C)]
+/// 文件要变大到 `size` 时检查 RLIMIT_FSIZE
+fn check_fsize(size:usize)->SysResult<()>{
+    if size > current_task().unwrap().inner_exclusive_access().fsize_limit.rlimit_cur {
+        send_signal_to_current(SignalFlags::SIGXFSZ);
+        return Err(SysError::EFBIG);
+    }
+    Ok(())
+}
+
+/// truncate/ftruncate 的公共部分
+fn do_truncate(dentry:&Arc<dyn Dentry>,length:isize)->SysResult<isize>{
+    if length < 0 {
+        return Err(SysError::EINVAL);
+    }
+    let inode = dentry.get_inode()?;
+    let file_type = inode.get_meta().mode & InodeMode::TYPE_MASK;
+    if file_type == InodeMode::DIR {
+        return Err(SysError::EISDIR);
+    }
+    if file_type != InodeMode::FILE {
+        return Err(SysError::EINVAL);
+    }
+    let length = length as usize;
+    if length > inode.get_size() as usize {
+        check_fsize(length)?;
+    }
+    // 和追加写互斥, 免得追加写取到的大小过期
+    let _guard = inode.get_meta().write_lock.lock();
+    inode.truncate(length)?;
+    Ok(0)
+}
+
+pub fn sys_truncate(path:*const u8,length:isize)->SysResult<isize>{
+    let token = current_user_token();
+    let path = translated_str(token, path);
+    let base = dirfd_base(AT_FDCWD, &path)?;
+    let dentry = lookup_at(base, &path, true)?;
+    if dentry.get_inode()?.get_meta().mode & InodeMode::TYPE_MASK == InodeMode::DIR {
+        return Err(SysError::EISDIR);
+    }
+    vfs::check_writable(&dentry)?;
+    vfs::inode_permission(&dentry.get_inode()?, &current_fs_cred(), vfs::MAY_WRITE)?;
+    do_truncate(&dentry, length)
+}
+
+pub fn sys_ftruncate(fd:isize,length:isize)->SysResult<isize>{
+    let task = current_task().unwrap();
+    let file = task.inner_exclusive_access().fd_table.lock().get_file(fd as usize)?;
+    if !file.writable() {
+        return Err(SysError::EINVAL);
+    }
+    do_truncate(&file.get_dentry(), length)
+}
+
+/// fallocate 的 mode
+const FALLOC_FL_KEEP_SIZE: i32 = 0x01;
+const FALLOC_FL_PUNCH_HOLE: i32 = 0x02;
+const FALLOC_FL_ZERO_RANGE: i32 = 0x10;
+/// 清零时每次写入的长度
+const ZERO_CHUNK: usize = 64 * 1024;
+
+/// 把 [start, end) 写成 0
+fn zero_range(file:&Arc<dyn File>,start:usize,end:usize)->SysResult<()>{
+    let zeros = vec![0u8; ZERO_CHUNK.min(end.saturating_sub(start))];
+    let mut pos = start;
+    while pos < end {
+        let len = zeros.len().min(end - pos);
+        file.pwrite(pos, &zeros[..len])?;
+        pos += len;
+    }
+    Ok(())
+}
+
+/// 没有真正的预留和打洞: 需要分配的块直接写 0 分配出来, 打洞和清零区间都写成 0.
+/// 带 FALLOC_FL_KEEP_SIZE 时超出文件末尾的部分不预留
+pub fn sys_fallocate(fd:isize,mode:i32,offset:isize,len:isize)->SysResult<isize>{
+    if offset < 0 || len <= 0 {
+        return Err(SysError::EINVAL);
+    }
+    if mode & !(FALLOC_FL_KEEP_SIZE | FALLOC_FL_PUNCH_HOLE | FALLOC_FL_ZERO_RANGE) != 0 {
+        return Err(SysError::EOPNOTSUPP);
+    }
+    let punch = mode & FALLOC_FL_PUNCH_HOLE != 0;
+    let zero = mode & FALLOC_FL_ZERO_RANGE != 0;
+    let keep_size = mode & FALLOC_FL_KEEP_SIZE != 0;
+    if (punch && !keep_size) || (punch && zero) {
+        return Err(SysError::EINVAL);
+    }
+    let task = current_task().unwrap();
+    let file = task.inner_exclusive_access().fd_table.lock().get_file(fd as usize)?;
+    if !file.writable() {
+        return Err(SysError::EBADF);
+    }
+    let inode = file.get_dentry().get_inode()?;
+    let file_type = inode.get_meta().mode & InodeMode::TYPE_MASK;
+    if file_type == InodeMode::DIR {
+        return Err(SysError::EISDIR);
+    }
+    if file_type == InodeMode::FIFO {
+        return Err(SysError::ESPIPE);
+    }
+    if file_type != InodeMode::FILE {
+        return Err(SysError::ENODEV);
+    }
+    let (start, end) = (offset as usize, (offset as usize).checked_add(len as usize).ok_or(SysError::EFBIG)?);
+    if end > isize::MAX as usize {
+        return Err(SysError::EFBIG);
+    }
+    if !keep_size && end > inode.get_size() as usize {
+        check_fsize(end)?;
+    }
+    let _guard = inode.get_meta().write_lock.lock();
+    let size = inode.get_size() as usize;
+    if punch || zero {
+        zero_range(&file, start, end.min(size))?;
+    }
+    if !keep_size && end > size {
+        inode.truncate(end)?;
+    }
+    Ok(0)
+}
+
+pub fn sys_sync()->SysResult<isize>{
+    vfs::sync_filesystems();
+    Ok(0)
+}
+
+/// 把文件所在的文件系统刷到设备上. 没有按文件的脏数据跟踪, 整个文件系统一起刷
+fn sync_file(fd:isize)->SysResult<isize>{
+    let task = current_task().unwrap();
+    let file = task.inner_exclusive_access().fd_table.lock().get_file(fd as usize)?;
+    let file_type = file.get_dentry().get_inode()?.get_meta().mode & InodeMode::TYPE_MASK;
+    if file_type == InodeMode::FIFO || file_type == InodeMode::SOCKET {
+        return Err(SysError::EINVAL);
+    }
+    file.get_dentry().get_superblock().sync_fs()?;
+    Ok(0)
+}
+
+pub fn sys_fsync(fd:isize)->SysResult<isize>{
+    sync_file(fd)
+}
+
+/// 元数据和数据一起落盘, 与 fsync 相同
+pub fn sys_fdatasync(fd:isize)->SysResult<isize>{
+    sync_file(fd)
+}
+
+pub fn sys_syncfs(fd:isize)->SysResult<isize>{
+    let task = current_task().unwrap();
+    let file = task.inner_exclusive_access().fd_table.lock().get_file(fd as usize)?;
+    file.get_dentry().get_superblock().sync_fs()?;
+    Ok(0)
+}
+
+/// sync_file_range 的 flags
+const SYNC_FILE_RANGE_WAIT_BEFORE: u32 = 1;
+const SYNC_FILE_RANGE_WRITE: u32 = 2;
+const SYNC_FILE_RANGE_WAIT_AFTER: u32 = 4;
+
+/// 回写是同步完成的, 没有在途的 I/O 可等; 带 SYNC_FILE_RANGE_WRITE 时刷整个文件系统
+pub fn sys_sync_file_range(fd:isize,offset:isize,nbytes:isize,flags:u32)->SysResult<isize>{
+    if flags & !(SYNC_FILE_RANGE_WAIT_BEFORE | SYNC_FILE_RANGE_WRITE | SYNC_FILE_RANGE_WAIT_AFTER) != 0 {
+        return Err(SysError::EINVAL);
+    }
+    if offset < 0 || nbytes < 0 || offset.checked_add(nbytes).is_none() {
+        return Err(SysError::EINVAL);
+    }
+    let task = current_task().unwrap();
+    let file = task.inner_exclusive_access().fd_table.lock().get_file(fd as usize)?;
+    let file_type = file.get_dentry().get_inode()?.get_meta().mode & InodeMode::TYPE_MASK;
+    if file_type != InodeMode::FILE && file_type != InodeMode::DIR && file_type != InodeMode::LINK && file_type != InodeMode::BLOCK {
+        return Err(SysError::ESPIPE);
+    }
+    if flags & SYNC_FILE_RANGE_WRITE != 0 {
+        file.get_dentry().get_superblock().sync_fs()?;
+    }
+    Ok(0)
+}
+
+pub fn sys_pread64(fd:isize,buf:*mut u8,count:usize,offset:isize)->SysResult<isize>{
+    let file = rw_file(fd, true)?;
+    let offset = rw_offset(offset, false)?;
+    let bufs = vec![translated_byte_buffer(current_user_token(), buf, count)];
+    Ok(do_preadv(&file, bufs, offset, 0)? as isize)
+}
+
+pub fn sys_pwrite64(fd:isize,buf:*const u8,count:usize,offset:isize)->SysResult<isize>{
+    let file = rw_file(fd, false)?;
+    let offset = rw_offset(offset, false)?;
+    let bufs = vec![translated_byte_buffer(current_user_token(), buf as *mut u8, count)];
+    Ok(do_pwritev(&file, bufs, offset, 0)? as isize)
+}
+
+/// 64 位下 pos_l 即完整的偏移, pos_h 不用
+pub fn sys_preadv(fd:isize,iov:*const IoVec,iovcnt:usize,offset:isize)->SysResult<isize>{
+    let file = rw_file(fd, true)?;
+    let offset = rw_offset(offset, false)?;
+    let bufs = user_iovecs(iov, iovcnt)?;
+    Ok(do_preadv(&file, bufs, offset, 0)? as isize)
+}
+
+pub fn sys_pwritev(fd:isize,iov:*const IoVec,iovcnt:usize,offset:isize)->SysResult<isize>{
+    let file = rw_file(fd, false)?;
+    let offset = rw_offset(offset, false)?;
+    let bufs = user_iovecs(iov, iovcnt)?;
+    Ok(do_pwritev(&file, bufs, offset, 0)? as isize)
+}
+
+/// offset 为 -1 时和 readv 一样使用文件偏移
+pub fn sys_preadv2(fd:isize,iov:*const IoVec,iovcnt:usize,offset:isize,flags:u32)->SysResult<isize>{
+    check_rwf(flags)?;
+    let file = rw_file(fd, true)?;
+    let offset = rw_offset(offset, true)?;
+    let bufs = user_iovecs(iov, iovcnt)?;
+    Ok(do_preadv(&file, bufs, offset, flags)? as isize)
+}
+
+pub fn sys_pwritev2(fd:isize,iov:*const IoVec,iovcnt:usize,offset:isize,flags:u32)->SysResult<isize>{
+    check_rwf(flags)?;
+    let file = rw_file(fd, false)?;
+    let offset = rw_offset(offset, true)?;
+    let bufs = user_iovecs(iov, iovcnt)?;
+    Ok(do_pwritev(&file, bufs, offset, flags)? as isize)
+}
+
+/// statfs 的 f_flags 中表示其余位有效的标志
+const ST_VALID: isize = 0x20;
+/// statfs 的 f_flags 中的 ST_RELATIME, 与 MS_RELATIME 数值不同
+const ST_RELATIME: isize = 0x1000;
+/// 在 f_flags 中数值与 MS_* 相同的挂载标志
+const ST_SAME_AS_MS: MountFlags = MountFlags::MS_RDONLY.union(MountFlags::MS_NOSUID)
+    .union(MountFlags::MS_NODEV).union(MountFlags::MS_NOEXEC).union(MountFlags::MS_SYNCHRONOUS)
+    .union(MountFlags::MS_NOATIME).union(MountFlags::MS_NODIRATIME);
+
+/// 取 `dentry` 所在文件系统的 statfs, f_flags 按所在挂载的标志填写
+fn do_statfs(dentry: &Arc<dyn Dentry>, buf: *mut StatFs) -> SysResult<isize> {
+    let mut stat = match pseudo_statfs(dentry) {
+        Some(stat) => stat,
+        None => dentry.get_superblock().statfs()?,
+    };
+    let flags = vfs::mount_flags(dentry);
+    stat.f_flags = ST_VALID | (flags & ST_SAME_AS_MS).bits() as isize;
+    if flags.contains(MountFlags::MS_RELATIME) {
+        stat.f_flags |= ST_RELATIME;
+    }
+    *translated_refmut(current_user_token(), buf) = stat;
+    Ok(0)
+}
+
+pub fn sys_statfs(path:*const u8,buf:*mut StatFs)->SysResult<isize>{
+    let path = translated_str(current_user_token(), path);
+    let base = dirfd_base(AT_FDCWD, &path)?;
+    let dentry = lookup_at(base, &path, true)?;
+    do_statfs(&dentry, buf)
+}
+
+pub fn sys_fstatfs(fd:usize,buf:*mut StatFs)->SysResult<isize>{
+    let task = current_task().unwrap();
+    let file = task.inner_exclusive_access().fd_table.lock().get_file(fd)?;
+    do_statfs(&file.get_dentry(), buf)
+}
+
+/// faccessat 的 mode
+const F_OK: usize = 0;
+const X_OK: usize = 1;
+const W_OK: usize = 2;
+const R_OK: usize = 4;
+/// 用有效用户而不是实际用户检查
+const AT_EACCESS: i32 = 0x200;
+
+pub fn sys_faccessat(dirfd:isize,path:*const u8,mode:usize,flags:i32)->SysResult<isize>{
+    if mode & !(R_OK | W_OK | X_OK) != 0 || flags & !(AT_EACCESS | AT_SYMLINK_NOFOLLOW | AT_EMPTY_PATH) != 0 {
+        return Err(SysError::EINVAL);
+    }
+    let token = current_user_token();
+    let path = translated_str(token, path);
+    let dentry = lookup_dirfd(dirfd, &path, flags)?;
+    if mode == F_OK {
+        return Ok(0);
+    }
+    if mode & W_OK != 0 {
+        vfs::check_writable(&dentry)?;
+    }
+    let cred = {
+        let task = current_task().unwrap();
+        let inner = task.inner_exclusive_access();
+        if flags & AT_EACCESS != 0 {
+            inner.cred.fs_cred()
+        } else {
+            inner.cred.real_fs_cred()
+        }
+    };
+    // R_OK/W_OK/X_OK 与 MAY_READ/MAY_WRITE/MAY_EXEC 的取值相同
+    vfs::inode_permission(&dentry.get_inode()?, &cred, mode as u32)?;
+    return Ok(0);
+}
+
+/// chmod: 只有属主和 root 能修改, 不在文件属组里的普通用户不能设置 setgid 位
+fn do_chmod(dentry: &Arc<dyn Dentry>, mode: u32) -> SysResult<isize> {
+    vfs::check_writable(dentry)?;
+    let cred = current_fs_cred();
+    let inode = dentry.get_inode()?;
+    let (uid, gid) = {
+        let inner = inode.get_meta().inner.lock();
+        (inner.uid, inner.gid)
+    };
+    if !cred.is_root() && cred.uid != uid {
+        return Err(SysError::EPERM);
+    }
+    let mut perm = InodeMode::from_bits_truncate(mode & 0o7777);
+    if !cred.is_root() && !cred.in_group(gid) {
+        perm.remove(InodeMode::SET_GID);
+    }
+    inode.set_perm(perm)?;
+    Ok(0)
+}
+
+/// chown: 只有 root 能改属主, 属主只能把属组改成自己所在的组.
+/// 普通文件改变归属后清除 setuid 和(组可执行时的)setgid 位
+fn do_chown(dentry: &Arc<dyn Dentry>, uid: u32, gid: u32) -> SysResult<isize> {
+    vfs::check_writable(dentry)?;
+    let cred = current_fs_cred();
+    let inode = dentry.get_inode()?;
+    let (mode, old_uid, old_gid) = {
+        let inner = inode.get_meta().inner.lock();
+        (inner.mode, inner.uid, inner.gid)
+    };
+    // -1 表示不修改
+    let new_uid = if uid == u32::MAX { old_uid } else { uid };
+    let new_gid = if gid == u32::MAX { old_gid } else { gid };
+    if !cred.is_root() {
+        if cred.uid != old_uid || new_uid != old_uid {
+            return Err(SysError::EPERM);
+        }
+        if new_gid != old_gid && !cred.in_group(new_gid) {
+            return Err(SysError::EPERM);
+        }
+    }
+    inode.set_owner(new_uid, new_gid)?;
+    if mode & InodeMode::TYPE_MASK == InodeMode::FILE && (uid != u32::MAX || gid != u32::MAX) {
+        let mut perm = mode.difference(InodeMode::TYPE_MASK | InodeMode::SET_UID);
+        if mode.contains(InodeMode::GROUP_EXEC) {
+            perm.remove(InodeMode::SET_GID);
+        }
+        if perm != mode.difference(InodeMode::TYPE_MASK) {
+            inode.set_perm(perm)?;
+        }
+    }
+    Ok(0)
+}
+
+/// fd 对应的目录项
+fn fd_dentry(fd: usize) -> SysResult<Arc<dyn Dentry>> {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let dentry = inner.fd_table.lock().get_file(fd)?.get_dentry();
+    Ok(dentry)
+}
+
+pub fn sys_fchmodat(dirfd:isize,path:*const u8,mode:u32,flags:i32)->SysResult<isize>{
+    let token = current_user_token();
+    let path = translated_str(token, path);
+    let dentry = lookup_dirfd(dirfd, &path, flags)?;
+    // Linux 不支持修改符号链接本身的权限
+    if vfs::is_symlink(&dentry) {
+        return Err(SysError::EOPNOTSUPP);
+    }
+    do_chmod(&dentry, mode)
+}
+
+pub fn sys_fchmod(fd:usize,mode:u32)->SysResult<isize>{
+    do_chmod(&fd_dentry(fd)?, mode)
+}
+
+pub fn sys_fchownat(dirfd:isize,path:*const u8,uid:u32,gid:u32,flags:i32)->SysResult<isize>{
+    if flags & !(AT_SYMLINK_NOFOLLOW | AT_EMPTY_PATH) != 0 {
+        return Err(SysError::EINVAL);
+    }
+    let token = current_user_token();
+    let path = translated_str(token, path);
+    let dentry = lookup_dirfd(dirfd, &path, flags)?;
+    do_chown(&dentry, uid, gid)
+}
+
+pub fn sys_fchown(fd:usize,uid:u32,gid:u32)->SysResult<isize>{
+    do_chown(&fd_dentry(fd)?, uid, gid)
+}
+
+/// 扩展属性名和值的长度上限, 与 Linux 的 XATTR_NAME_MAX/XATTR_SIZE_MAX 相同
+const XATTR_NAME_MAX: usize = 255;
+const XATTR_SIZE_MAX: usize = 65536;
+
+/// 按命名空间检查能否读写扩展属性 `name`: trusted. 只有 root 能访问, security. 只有 root 能改,
+/// user. 只能用在普通文件和目录上并按文件的读写权限检查. 不认识的命名空间由文件系统返回 EOPNOTSUPP
+fn xattr_permission(dentry: &Arc<dyn Dentry>, name: &str, write: bool) -> SysResult<()> {
+    if write {
+        vfs::check_writable(dentry)?;
+    }
+    let cred = current_fs_cred();
+    let denied = if write { SysError::EPERM } else { SysError::ENODATA };
+    if name.starts_with("trusted.") {
+        return if cred.is_root() { Ok(()) } else { Err(denied) };
+    }
+    if name.starts_with("security.") {
+        return if !write || cred.is_root() { Ok(()) } else { Err(SysError::EPERM) };
+    }
+    let inode = dentry.get_inode()?;
+    if name.starts_with("user.") {
+        let (mode, uid) = {
+            let inner = inode.get_meta().inner.lock();
+            (inner.mode, inner.uid)
+        };
+        let file_type = mode & InodeMode::TYPE_MASK;
+        if file_type != InodeMode::FILE && file_type != InodeMode::DIR {
+            return Err(denied);
+        }
+        // 和删除文件一样, 粘滞目录上的 user. 属性只有属主能改
+        if write && file_type == InodeMode::DIR && mode.contains(InodeMode::STICKY) && !cred.is_root() && cred.uid != uid {
+            return Err(SysError::EPERM);
+        }
+    }
+    vfs::inode_permission(&inode, &cred, if write { vfs::MAY_WRITE } else { vfs::MAY_READ })
+}
+
+/// 取出用户给的属性名, 空名字和过长的名字返回 ERANGE
+fn xattr_name(name: *const u8) -> SysResult<String> {
+    let name = translated_str(current_user_token(), name);
+    if name.is_empty() || name.len() > XATTR_NAME_MAX {
+        return Err(SysError::ERANGE);
+    }
+    Ok(name)
+}
+
+/// 属性值和名字列表所在的用户缓冲区, 长度为 0 时不访问 `ptr`
+fn xattr_user_buffer(ptr: *mut u8, len: usize) -> &'static mut [u8] {
+    if len == 0 {
+        return &mut [];
+    }
+    let task = current_task().unwrap();
+    let memory_set = task.inner_exclusive_access().memory_set.clone();
+    safe_translated_byte_buffer(memory_set, ptr, len)
+}
+
+fn do_setxattr(dentry: &Arc<dyn Dentry>, name: *const u8, value: *const u8, size: usize, flags: i32) -> SysResult<isize> {
+    let flags = XattrFlags::from_bits(flags).ok_or(SysError::EINVAL)?;
+    let name = xattr_name(name)?;
+    if size > XATTR_SIZE_MAX {
+        return Err(SysError::E2BIG);
+    }
+    xattr_permission(dentry, &name, true)?;
+    let value = xattr_user_buffer(value as *mut u8, size).to_vec();
+    dentry.get_inode()?.set_xattr(&name, &value, flags)?;
+    Ok(0)
+}
+
+/// `size` 为 0 时只返回值的长度
+fn do_getxattr(dentry: &Arc<dyn Dentry>, name: *const u8, value: *mut u8, size: usize) -> SysResult<isize> {
+    let name = xattr_name(name)?;
+    xattr_permission(dentry, &name, false)?;
+    let data = dentry.get_inode()?.get_xattr(&name)?;
+    if size != 0 {
+        if data.len() > size {
+            return Err(SysError::ERANGE);
+        }
+        xattr_user_buffer(value, data.len()).copy_from_slice(&data);
+    }
+    Ok(data.len() as isize)
+}
+
+/// 名字以 \0 分隔依次放进 `list`, `size` 为 0 时只返回需要的长度. 非 root 看不到 trusted. 属性
+fn do_listxattr(dentry: &Arc<dyn Dentry>, list: *mut u8, size: usize) -> SysResult<isize> {
+    let names = match dentry.get_inode()?.list_xattr() {
+        Err(SysError::EOPNOTSUPP) => Vec::new(),
+        r => r?,
+    };
+    let root = current_fs_cred().is_root();
+    let mut data = Vec::new();
+    for name in names.iter().filter(|name| root || !name.starts_with("trusted.")) {
+        data.extend_from_slice(name.as_bytes());
+        data.push(0);
+    }
+    if size != 0 {
+        if data.len() > size {
+            return Err(SysError::ERANGE);
+        }
+        xattr_user_buffer(list, data.len()).copy_from_slice(&data);
+    }
+    Ok(data.len() as isize)
+}
+
+fn do_removexattr(dentry: &Arc<dyn Dentry>, name: *const u8) -> SysResult<isize> {
+    let name = xattr_name(name)?;
+    xattr_permission(dentry, &name, true)?;
+    dentry.get_inode()?.remove_xattr(&name)?;
+    Ok(0)
+}
+
+/// xattr 系列调用的路径参数, l 开头的调用不跟随末尾的符号链接
+fn xattr_path(path: *const u8, follow: bool) -> SysResult<Arc<dyn Dentry>> {
+    let path = translated_str(current_user_token(), path);
+    lookup_at(None, &path, follow)
+}
+
+pub fn sys_setxattr(path:*const u8,name:*const u8,value:*const u8,size:usize,flags:i32)->SysResult<isize>{
+    do_setxattr(&xattr_path(path, true)?, name, value, size, flags)
+}
+
+pub fn sys_lsetxattr(path:*const u8,name:*const u8,value:*const u8,size:usize,flags:i32)->SysResult<isize>{
+    do_setxattr(&xattr_path(path, false)?, name, value, size, flags)
+}
+
+pub fn sys_fsetxattr(fd:usize,name:*const u8,value:*const u8,size:usize,flags:i32)->SysResult<isize>{
+    do_setxattr(&fd_dentry(fd)?, name, value, size, flags)
+}
+
+pub fn sys_getxattr(path:*const u8,name:*const u8,value:*mut u8,size:usize)->SysResult<isize>{
+    do_getxattr(&xattr_path(path, true)?, name, value, size)
+}
+
+pub fn sys_lgetxattr(path:*const u8,name:*const u8,value:*mut u8,size:usize)->SysResult<isize>{
+    do_getxattr(&xattr_path(path, false)?, name, value, size)
+}
+
+pub fn sys_fgetxattr(fd:usize,name:*const u8,value:*mut u8,size:usize)->SysResult<isize>{
+    do_getxattr(&fd_dentry(fd)?, name, value, size)
+}
+
+pub fn sys_listxattr(path:*const u8,list:*mut u8,size:usize)->SysResult<isize>{
+    do_listxattr(&xattr_path(path, true)?, list, size)
+}
+
+pub fn sys_llistxattr(path:*const u8,list:*mut u8,size:usize)->SysResult<isize>{
+    do_listxattr(&xattr_path(path, false)?, list, size)
+}
+
+pub fn sys_flistxattr(fd:usize,list:*mut u8,size:usize)->SysResult<isize>{
+    do_listxattr(&fd_dentry(fd)?, list, size)
+}
+
+pub fn sys_removexattr(path:*const u8,name:*const u8)->SysResult<isize>{
+    do_removexattr(&xattr_path(path, true)?, name)
+}
+
+pub fn sys_lremovexattr(path:*const u8,name:*const u8)->SysResult<isize>{
+    do_removexattr(&xattr_path(path, false)?, name)
+}
+
+pub fn sys_fremovexattr(fd:usize,name:*const u8)->SysResult<isize>{
+    do_removexattr(&fd_dentry(fd)?, name)
+}
+
+pub fn sys_lseek(fd:isize,offset:isize,whence:usize)->SysResult<isize>{
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let fdtable = inner.fd_table.lock();
+    let file = fdtable.get(fd as usize)?;
+    let _file = file.file();
+    drop(fdtable);
+    match whence{
+        0=>{
+            return _file.seek(offset as i64, SeekFlags::SEEK_SET);
+        },
+        1=>{
+            return _file.seek(offset as i64, SeekFlags::SEEK_CUR);
+        },
+        2=>{
+            return _file.seek(offset as i64, SeekFlags::SEEK_END);
+        },
+        _=>{}
+    }
+    return Err(SysError::EINVAL);
+}
+
+pub fn sys_utimensat(dirfd:isize,path:*const u8,times:*const crate::task::TimeSpec,flags:i32)->SysResult<isize>{
+    let dentry;
+    if path.is_null(){
+        match  dirfd {
+            AT_FDCWD=>{
+                return Err(SysError::EINVAL);
+            }
+            _=>{
+                let task = current_task().unwrap();
+                let inner = task.inner_exclusive_access();
+                dentry = inner.fd_table.lock().get_file(dirfd as usize)?.get_dentry();
+            }
+        }
+    }
+    else{
+        if flags & !(AT_SYMLINK_NOFOLLOW | AT_EMPTY_PATH) != 0 {
+            return Err(SysError::EINVAL);
+        }
+        let path = translated_str(current_user_token(), path);
+        dentry = lookup_dirfd(dirfd, &path, flags)?;
+    }
+    vfs::check_writable(&dentry)?;
+    let inode = dentry.get_inode()?;
+    let current = Time::now();
+    let timespec_now = TimeSpec{
+        sec:current.to_sec(),
+        usec:current.to_usec()
+    };
+    if times.is_null(){
+        inode.set_times(Some(timespec_now), Some(timespec_now), timespec_now)?;
+    }
+    else{
+        const UTIME_NOW: usize = 0x3fffffff;
+        const UTIME_OMIT: usize = 0x3ffffffe;
+        let token = current_user_token();
+        let times1 = translated_ref(token, times);
+        let times2;
+        unsafe {
+            times2 = translated_ref(token, times.add(1));
+        }
+        let pick = |time: &TimeSpec| match time.usec {
+            UTIME_NOW => Some(timespec_now),
+            UTIME_OMIT => None,
+            _ => Some(*time),
+        };
+        inode.set_times(pick(times1), pick(times2), timespec_now)?;
+    }
+    Ok(0)
+}
+const F_DUPFD:isize = 0;
+const F_DUPFD_CLOEXEC:isize = 1030;
+const F_GETFD:isize = 1;
+const F_SETFD:isize = 2;
+const F_GETFL:isize = 3;
+const F_SETFL:isize = 4;
+const F_SETPIPE_SZ:isize = 1031;
+const F_GETPIPE_SZ:isize = 1032;
+const F_ADD_SEALS:isize = 1033;
+const F_GET_SEALS:isize = 1034;
+const F_GETLK:isize = 5;
+const F_SETLK:isize = 6;
+const F_SETLKW:isize = 7;
+const F_OFD_GETLK:isize = 36;
+const F_OFD_SETLK:isize = 37;
+const F_OFD_SETLKW:isize = 38;
+const F_RDLCK:i16 = 0;
+const F_WRLCK:i16 = 1;
+const F_UNLCK:i16 = 2;
+
+/// struct flock
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Flock {
+    pub l_type: i16,
+    pub l_whence: i16,
+    pub l_start: i64,
+    pub l_len: i64,
+    pub l_pid: i32,
+}
+
+/// F_GETLK/F_SETLK/F_SETLKW 及 OFD 版本, 可能阻塞, 不能持有描述符表的锁
+fn fcntl_lock(fd:usize,op:isize,arg:*mut Flock)->SysResult<isize>{
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let pid = task.getpid();
+    let file = task.inner_exclusive_access().fd_table.lock().get_file(fd)?;
+    let flock = *translated_ref(token, arg as *const Flock);
+    let kind = match op {
+        F_OFD_GETLK | F_OFD_SETLK | F_OFD_SETLKW => {
+            if flock.l_pid != 0 {
+                return Err(SysError::EINVAL);
+            }
+            LockKind::Ofd
+        }
+        _ => LockKind::Posix,
+    };
+    let exclusive = match flock.l_type {
+        F_RDLCK => Some(false),
+        F_WRLCK => Some(true),
+        F_UNLCK => None,
+        _ => return Err(SysError::EINVAL),
+    };
+    let base = match flock.l_whence {
+        0 => 0,
+        1 => *file.get_offset() as i64,
+        2 => file.get_dentry().get_inode()?.get_size() as i64,
+        _ => return Err(SysError::EINVAL),
+    };
+    let mut start = base.checked_add(flock.l_start).ok_or(SysError::EOVERFLOW)?;
+    // l_len 为负时锁住 [start+len, start), 为 0 时一直到文件末尾
+    let end = if flock.l_len > 0 {
+        start.checked_add(flock.l_len - 1).ok_or(SysError::EOVERFLOW)? as u64
+    } else if flock.l_len < 0 {
+        let end = start - 1;
+        start += flock.l_len;
+        end as u64
+    } else {
+        u64::MAX
+    };
+    if start < 0 {
+        return Err(SysError::EINVAL);
+    }
+    let start = start as u64;
+    if op == F_GETLK || op == F_OFD_GETLK {
+        let exclusive = exclusive.ok_or(SysError::EINVAL)?;
+        let mut result = Flock { l_type: F_UNLCK, ..flock };
+        if let Some((held_exclusive, held_start, held_end, held_pid)) =
+            test_record_lock(&file, kind, pid, exclusive, start, end)?
+        {
+            result = Flock {
+                l_type: if held_exclusive { F_WRLCK } else { F_RDLCK },
+                l_whence: 0,
+                l_start: held_start as i64,
+                l_len: if held_end == u64::MAX { 0 } else { (held_end - held_start + 1) as i64 },
+                l_pid: held_pid as i32,
+            };
+        }
+        *translated_refmut(token, arg) = result;
+        return Ok(0);
+    }
+    match exclusive {
+        Some(false) if !file.readable() => return Err(SysError::EBADF),
+        Some(true) if !file.writable() => return Err(SysError::EBADF),
+        _ => {}
+    }
+    let wait = op == F_SETLKW || op == F_OFD_SETLKW;
+    set_record_lock(&file, kind, pid, exclusive, start, end, wait)?;
+    Ok(0)
+}
+
+/// flock(2)
+pub fn sys_flock(fd:usize,op:u32)->SysResult<isize>{
+    let task = current_task().unwrap();
+    let file = task.inner_exclusive_access().fd_table.lock().get_file(fd)?;
+    flock(&file, op)?;
+    Ok(0)
+}
+//F_UNIMPL,
+/// 是否有任务以可写的共享方式映射了 `inode`
+fn has_writable_shared_mapping(inode: &Arc<dyn Inode>) -> bool {
+    all_tasks().iter().any(|task| {
+        let memory_set = task.inner_exclusive_access().memory_set.clone();
+        let memory_set = memory_set.lock();
+        memory_set.areas.iter().any(|area| {
+            area.mmap_flag.contains(MmapFlags::MAP_SHARED)
+                && area.map_perm.contains(MapPermission::W)
+                && area.map_file.as_ref().is_some_and(|file| {
+                    file.get_dentry()
+                        .get_inode()
+                        .is_ok_and(|mapped| Arc::as_ptr(&mapped) as *const () == Arc::as_ptr(inode) as *const ())
+                })
+        })
+    })
+}
+
+/// F_ADD_SEALS. 还有可写的共享映射时不能封印写, 返回 EBUSY
+fn add_seals(fd: usize, arg: u32) -> SysResult<isize> {
+    let file = current_file(fd)?;
+    let add = FileSeals::from_bits(arg).ok_or(SysError::EINVAL)?;
+    let inode = file.get_dentry().get_inode()?;
+    if inode.get_meta().inner.lock().seals.is_none() {
+        return Err(SysError::EINVAL);
+    }
+    if !file.writable() {
+        return Err(SysError::EPERM);
+    }
+    if add.contains(FileSeals::WRITE) && has_writable_shared_mapping(&inode) {
+        return Err(SysError::EBUSY);
+    }
+    let mut meta = inode.get_meta().inner.lock();
+    let seals = meta.seals.as_mut().ok_or(SysError::EINVAL)?;
+    if seals.contains(FileSeals::SEAL) {
+        return Err(SysError::EPERM);
+    }
+    *seals |= add;
+    Ok(0)
+}
+
+pub fn sys_fcntl(fd:isize,op:isize,arg:usize)->SysResult<isize>{
+    match op {
+        F_GETLK | F_SETLK | F_SETLKW | F_OFD_GETLK | F_OFD_SETLK | F_OFD_SETLKW => {
+            return fcntl_lock(fd as usize, op, arg as *mut Flock);
+        }
+        // 要检查所有任务的地址空间, 不能持有当前任务的锁
+        F_ADD_SEALS => return add_seals(fd as usize, arg as u32),
+        _ => {}
+    }
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let mut fdtable = inner.fd_table.lock();
+    match op{
+        F_DUPFD=>{
+            fdtable.dup_with_arg(fd as usize, arg,OpenFlags::empty())
+        }
+        F_DUPFD_CLOEXEC=>{
+            fdtable.dup_with_arg(fd as usize, arg,OpenFlags::CLOEXEC)
+        }
+        F_GETFD=>{
+            let fd = fdtable.get(fd as usize)?;
+            return Ok(fd.get_flags().bits() as isize);
+        }
+        F_SETFD=>{
+            let fd = fdtable.get_mut(fd as usize)?;
+            fd.set_flags(FdFlags::from(OpenFlags::from_bits_retain(arg as u32)));
+            return Ok(0);
+        }
+        F_GETFL=>{
+            let file = fdtable.get(fd as usize)?;
+            let file = file.file();
+            let flag = file.get_inner().flags.lock();
+            return Ok(flag.bits() as isize);
+        }
+        F_SETFL=>{
+            let file = fdtable.get(fd as usize)?;
+            let file = file.file();
+            *file.get_inner().flags.lock() = OpenFlags::from_bits_retain(arg as u32);
+            return Ok(0);
+        }
+        F_GETPIPE_SZ=>{
+            let pipe = fdtable.get_file(fd as usize)?.downcast_arc::<Pipe>().map_err(|_| SysError::EBADF)?;
+            return Ok(pipe.capacity() as isize);
+        }
+        F_SETPIPE_SZ=>{
+            let pipe = fdtable.get_file(fd as usize)?.downcast_arc::<Pipe>().map_err(|_| SysError::EBADF)?;
+            let privileged = inner.cred.privileged();
+            return Ok(pipe.set_capacity(arg, privileged)? as isize);
+        }
+        F_GET_SEALS=>{
+            let inode = fdtable.get_file(fd as usize)?.get_dentry().get_inode()?;
+            let seals = inode.get_meta().inner.lock().seals.ok_or(SysError::EINVAL)?;
+            return Ok(seals.bits() as isize);
+        }
+        _ =>{
+            return Err(SysError::EINVAL);
+        }
+    }
+}
+
+pub fn sys_sendfile(outfd:isize,infd:isize,offset:*mut usize,count:usize)->SysResult<isize>{
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let table = inner.fd_table.lock();
+    let (outfile,infile) = (table.get_file(outfd as usize)?,table.get_file(infd as usize)?);
+    if !infile.readable() || !outfile.writable() {
+        return Err(SysError::EBADF);
+    }
+    let mut buf = vec![0 as u8; count];
+    let len;
+    if offset.is_null(){
+        len = infile.read(&mut buf);
+    }
+    else{
+        let offset = translated_refmut(token, offset);
+        len = infile.read_at(*offset, &mut buf);
+        *offset = *offset + len;
+    }
+    let ret = outfile.write(&buf[..len]);
+    return Ok(ret as isize);
+}
+
+/// splice/tee/vmsplice 不阻塞
+const SPLICE_F_NONBLOCK: u32 = 0x02;
+/// 一次 readv/writev/vmsplice 最多的 iovec 数
+const IOV_MAX: usize = 1024;
+
+/// splice 中非管道一端的偏移: 空指针表示使用文件自身的偏移
+fn splice_file_offset(token: usize, off: *mut i64) -> SysResult<Option<usize>> {
+    if off.is_null() {
+        return Ok(None);
+    }
+    let off = *translated_ref(token, off);
+    if off < 0 {
+        return Err(SysError::EINVAL);
+    }
+    Ok(Some(off as usize))
+}
+
+/// 在管道和文件(或另一个管道)之间搬运数据, 至少一端必须是管道
+pub fn sys_splice(fd_in:isize,off_in:*mut i64,fd_out:isize,off_out:*mut i64,len:usize,flags:u32)->SysResult<isize>{
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let (in_file, out_file) = {
+        let table = inner.fd_table.lock();
+        (table.get_file(fd_in as usize)?, table.get_file(fd_out as usize)?)
+    };
+    drop(inner);
+    if !in_file.readable() || !out_file.writable() {
+        return Err(SysError::EBADF);
+    }
+    let nonblock = flags & SPLICE_F_NONBLOCK != 0;
+    let in_pipe = in_file.clone().downcast_arc::<Pipe>().ok();
+    let out_pipe = out_file.clone().downcast_arc::<Pipe>().ok();
+    if (in_pipe.is_some() && !off_in.is_null()) || (out_pipe.is_some() && !off_out.is_null()) {
+        return Err(SysError::ESPIPE);
+    }
+    if len == 0 {
+        return Ok(0);
+    }
+    let moved = match (in_pipe, out_pipe) {
+        (Some(src), Some(dst)) => splice_pipe_to_pipe(&src, &dst, len, nonblock)?,
+        (None, Some(dst)) => {
+            let mut pos = splice_file_offset(token, off_in)?;
+            let moved = splice_file_to_pipe(&in_file, pos.as_mut(), &dst, len, nonblock)?;
+            if let Some(pos) = pos {
+                *translated_refmut(token, off_in) = pos as i64;
+            }
+            moved
+        }
+        (Some(src), None) => {
+            let mut pos = splice_file_offset(token, off_out)?;
+            let moved = splice_pipe_to_file(&src, &out_file, pos.as_mut(), len, nonblock)?;
+            if let Some(pos) = pos {
+                *translated_refmut(token, off_out) = pos as i64;
+            }
+            moved
+        }
+        (None, None) => return Err(SysError::EINVAL),
+    };
+    Ok(moved as isize)
+}
+
+/// 把一个管道中的数据复制到另一个管道, 不消耗源管道的数据
+pub fn sys_tee(fd_in:isize,fd_out:isize,len:usize,flags:u32)->SysResult<isize>{
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let (in_file, out_file) = {
+        let table = inner.fd_table.lock();
+        (table.get_file(fd_in as usize)?, table.get_file(fd_out as usize)?)
+    };
+    drop(inner);
+    if !in_file.readable() || !out_file.writable() {
+        return Err(SysError::EBADF);
+    }
+    let src = in_file.downcast_arc::<Pipe>().map_err(|_| SysError::EINVAL)?;
+    let dst = out_file.downcast_arc::<Pipe>().map_err(|_| SysError::EINVAL)?;
+    if len == 0 {
+        return Ok(0);
+    }
+    Ok(tee_pipe(&src, &dst, len, flags & SPLICE_F_NONBLOCK != 0)? as isize)
+}
+
+/// 用户缓冲区与管道之间的传输: 写端把 iovec 写入管道, 读端把管道读进 iovec
+pub fn sys_vmsplice(fd:isize,iov:*const IoVec,nr_segs:usize,flags:u32)->SysResult<isize>{
+    if nr_segs > IOV_MAX {
+        return Err(SysError::EINVAL);
+    }
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let file = inner.fd_table.lock().get_file(fd as usize)?;
+    drop(inner);
+    let pipe = file.downcast_arc::<Pipe>().map_err(|_| SysError::EBADF)?;
+    let nonblock = flags & SPLICE_F_NONBLOCK != 0;
+    let mut total = 0;
+    for i in 0..nr_segs {
+        let iovs = translated_ref(token, unsafe { iov.add(i) });
+        if iovs.len == 0 {
+            continue;
+        }
+        let buf = translated_byte_buffer(token, iovs.base as *mut u8, iovs.len);
+        let ret = if pipe.writable() {
+            pipe.pipe_write(buf, nonblock)
+        } else {
+            pipe.pipe_read(buf, nonblock)
+        };
+        match ret {
+            Ok(len) => {
+                total += len;
+                if len < iovs.len {
+                    break;
+                }
+            }
+            Err(e) if total == 0 => return Err(e),
+            Err(_) => break,
+        }
+    }
+    Ok(total as isize)
+}
+
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
 pub struct PollFd {
     /// file descriptor
     fd: i32,
@@ -730,69 +1783,450 @@ pub struct PollFd {
     revents: PollEvents,
 }
 
-pub fn sys_poll(fds:*mut PollFd,nfds:usize,_timeout:*const TimeSpec)->SysResult<isize>{
+/// 把 `src` 写到用户地址 `dst`, 可以跨页
+fn copy_to_user(token: usize, dst: *mut u8, src: &[u8]) {
+    let mut copied = 0;
+    for chunk in translated_byte_buffer(token, dst, src.len()) {
+        chunk.copy_from_slice(&src[copied..copied + chunk.len()]);
+        copied += chunk.len();
+    }
+}
+
+/// 相对超时换算成单调时钟上的截止时刻, 空指针表示永不超时
+fn timeout_deadline(token: usize, timeout: *const TimeSpec) -> SysResult<Option<usize>> {
+    if timeout.is_null() {
+        return Ok(None);
+    }
+    let timeout = *translated_ref(token, timeout);
+    if timeout.usec >= NSEC_PER_SEC {
+        return Err(SysError::EINVAL);
+    }
+    Ok(Some(monotonic_nsec() + timeout.to_usec()))
+}
+
+/// 等待期间临时替换信号掩码, 返回原来的掩码. SIGKILL 和 SIGSTOP 不能被屏蔽
+fn swap_sigmask(token: usize, sigmask: *const SignalFlags, sigsetsize: usize) -> SysResult<Option<SignalFlags>> {
+    if sigmask.is_null() {
+        return Ok(None);
+    }
+    if sigsetsize != mem::size_of::<SignalFlags>() {
+        return Err(SysError::EINVAL);
+    }
+    let mask = *translated_ref(token, sigmask) - (SignalFlags::SIGKILL | SignalFlags::SIGSTOP);
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    Ok(Some(mem::replace(&mut inner.signal_mask, mask)))
+}
+
+/// 等待结束后恢复掩码. 被信号打断时临时掩码要保留到信号递送之后,
+/// 否则刚才只因临时掩码放行的信号会在返回前又被屏蔽
+fn restore_sigmask<T>(old: Option<SignalFlags>, ret: &SysResult<T>) {
+    if let Some(old) = old {
+        let task = current_task().unwrap();
+        let mut inner = task.inner_exclusive_access();
+        if matches!(ret, Err(SysError::EINTR)) {
+            inner.saved_sigmask = Some(old);
+        } else {
+            inner.signal_mask = old;
+        }
+    }
+}
+
+pub fn sys_ppoll(fds:*mut PollFd,nfds:usize,timeout:*const TimeSpec,sigmask:*const SignalFlags,sigsetsize:usize)->SysResult<isize>{
+    if nfds > MAX_FD {
+        return Err(SysError::EINVAL);
+    }
+    let token = current_user_token();
+    let deadline = timeout_deadline(token, timeout)?;
+    let mut poll_fds: Vec<PollFd> = (0..nfds).map(|i| *translated_ref(token, unsafe { fds.add(i) })).collect();
+    // 负数 fd 忽略, 无效 fd 报告 POLLNVAL
+    let files: Vec<Option<SysResult<Arc<dyn File>>>> = {
+        let task = current_task().unwrap();
+        let inner = task.inner_exclusive_access();
+        let fd_table = inner.fd_table.lock();
+        poll_fds
+            .iter()
+            .map(|pfd| (pfd.fd >= 0).then(|| fd_table.get_file(pfd.fd as usize)))
+            .collect()
+    };
+    let waited: Vec<Arc<dyn File>> = files.iter().filter_map(|file| file.clone()?.ok()).collect();
+    let old_mask = swap_sigmask(token, sigmask, sigsetsize)?;
+    let ret = poll_wait(&waited, deadline, || {
+        let mut ready = 0;
+        for (pfd, file) in poll_fds.iter_mut().zip(files.iter()) {
+            pfd.revents = match file {
+                None => PollEvents::empty(),
+                Some(Err(_)) => PollEvents::POLLINVAL,
+                // 挂断和错误总会报告, 不需要请求
+                Some(Ok(file)) => file.poll(pfd.events) & (pfd.events | PollEvents::POLLHUP | PollEvents::POLLERR),
+            };
+            if !pfd.revents.is_empty() {
+                ready += 1;
+            }
+        }
+        ready
+    });
+    restore_sigmask(old_mask, &ret);
+    let ready = ret?;
+    for (i, pfd) in poll_fds.iter().enumerate() {
+        translated_refmut(token, unsafe { fds.add(i) }).revents = pfd.revents;
+    }
+    Ok(ready as isize)
+}
+
+/// pselect6 的第六个参数
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SigSetArg {
+    ss: *const SignalFlags,
+    ss_len: usize,
+}
+
+const FD_SETSIZE: usize = 1024;
+
+fn read_fd_set(token: usize, set: *mut u64, words: usize) -> Vec<u64> {
+    if set.is_null() {
+        return vec![0; words];
+    }
+    (0..words).map(|i| *translated_ref(token, unsafe { set.add(i) })).collect()
+}
+
+fn write_fd_set(token: usize, set: *mut u64, bits: &[u64]) {
+    if set.is_null() {
+        return;
+    }
+    for (i, word) in bits.iter().enumerate() {
+        *translated_refmut(token, unsafe { set.add(i) }) = *word;
+    }
+}
+
+pub fn sys_pselect6(nfds:usize,readfds:*mut u64,writefds:*mut u64,exceptfds:*mut u64,timeout:*mut TimeSpec,sigmask:*const SigSetArg)->SysResult<isize>{
+    if nfds > FD_SETSIZE {
+        return Err(SysError::EINVAL);
+    }
     let token = current_user_token();
-    let mut poll_fds:Vec<&mut PollFd> = Vec::new();
-    let mut ret = 0;
-    for _i in 0..nfds{
-        let fd = translated_refmut(token, fds);
-        poll_fds.push(fd);
-        unsafe {
-            let _ = fds.add(1);
-        }
-    }
-    loop{
-        for fd in poll_fds.iter_mut(){
-            fd.revents = PollEvents::empty();
-            if fd.fd < 0 {continue;}
-            let mut reti = 0;
-            let task = current_task().unwrap();
-            let inner = task.inner_exclusive_access();
-            let file = inner.fd_table.lock().get_file(fd.fd as usize);
-            if file.is_err(){
-                fd.revents = fd.revents | PollEvents::POLLINVAL;
-                reti = 1;
+    let deadline = timeout_deadline(token, timeout)?;
+    let words = (nfds + 63) / 64;
+    let want = [read_fd_set(token, readfds, words), read_fd_set(token, writefds, words), read_fd_set(token, exceptfds, words)];
+    let mut files: Vec<(usize, Arc<dyn File>)> = Vec::new();
+    {
+        let task = current_task().unwrap();
+        let inner = task.inner_exclusive_access();
+        let fd_table = inner.fd_table.lock();
+        for fd in 0..nfds {
+            let bit = 1u64 << (fd % 64);
+            if want.iter().any(|set| set[fd / 64] & bit != 0) {
+                files.push((fd, fd_table.get_file(fd)?));
             }
-            else{
-                let file = file.unwrap();
-                if fd.events.contains(PollEvents::POLLIN){ 
-                    drop(inner);
-                    if file.poll(PollEvents::POLLIN).contains(PollEvents::POLLIN){
-                        fd.revents = fd.revents | PollEvents::POLLIN;
-                        reti = 1;
-                    }
-                }
-                if fd.events.contains(PollEvents::POLLOUT){
-                    if file.poll(PollEvents::empty()).contains(PollEvents::POLLOUT){
-                        fd.revents = fd.revents | PollEvents::POLLOUT;
-                        reti = 1;
-                    }
+        }
+    }
+    let waited: Vec<Arc<dyn File>> = files.iter().map(|(_, file)| file.clone()).collect();
+    let in_set = PollEvents::POLLIN | PollEvents::POLLRDNORM | PollEvents::POLLRDBAND | PollEvents::POLLHUP | PollEvents::POLLERR;
+    let out_set = PollEvents::POLLOUT | PollEvents::POLLWRNORM | PollEvents::POLLWRBAND | PollEvents::POLLERR;
+    let ex_set = PollEvents::POLLPRI;
+    let mut result = [vec![0u64; words], vec![0u64; words], vec![0u64; words]];
+    let old_mask = if sigmask.is_null() {
+        None
+    } else {
+        let arg = *translated_ref(token, sigmask);
+        swap_sigmask(token, arg.ss, arg.ss_len)?
+    };
+    let ret = poll_wait(&waited, deadline, || {
+        let mut ready = 0;
+        for (fd, file) in files.iter() {
+            let bit = 1u64 << (fd % 64);
+            let revents = file.poll(in_set | out_set | ex_set);
+            for (i, mask) in [in_set, out_set, ex_set].iter().enumerate() {
+                let hit = want[i][fd / 64] & bit != 0 && revents.intersects(*mask);
+                if hit {
+                    result[i][fd / 64] |= bit;
+                    ready += 1;
+                } else {
+                    result[i][fd / 64] &= !bit;
                 }
             }
-            ret += reti;
         }
-        if ret != 0{
-            break;
+        ready
+    });
+    restore_sigmask(old_mask, &ret);
+    let ready = ret?;
+    write_fd_set(token, readfds, &result[0]);
+    write_fd_set(token, writefds, &result[1]);
+    write_fd_set(token, exceptfds, &result[2]);
+    // Linux 会写回剩余时间
+    if let Some(deadline) = deadline {
+        *translated_refmut(token, timeout) = TimeSpec::from_nsec(deadline.saturating_sub(monotonic_nsec()));
+    }
+    Ok(ready as isize)
+}
+
+const EPOLL_CTL_ADD: usize = 1;
+const EPOLL_CTL_DEL: usize = 2;
+const EPOLL_CTL_MOD: usize = 3;
+
+pub fn sys_epoll_create1(flags:u32)->SysResult<isize>{
+    let flags = OpenFlags::from_bits(flags).ok_or(SysError::EINVAL)?;
+    if !OpenFlags::CLOEXEC.contains(flags) {
+        return Err(SysError::EINVAL);
+    }
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let epoll = EpollFile::new(inner.cwd.get_superblock());
+    let fd = inner.fd_table.lock().insert(Some(Fd::new(epoll, FdFlags::from(flags))))?;
+    Ok(fd as isize)
+}
+
+fn get_epoll(epfd:usize)->SysResult<Arc<EpollFile>>{
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let file = inner.fd_table.lock().get_file(epfd)?;
+    file.downcast_arc::<EpollFile>().map_err(|_| SysError::EINVAL)
+}
+
+pub fn sys_epoll_ctl(epfd:usize,op:usize,fd:usize,event:*const EpollEvent)->SysResult<isize>{
+    let token = current_user_token();
+    let epoll = get_epoll(epfd)?;
+    let file = {
+        let task = current_task().unwrap();
+        let inner = task.inner_exclusive_access();
+        let file = inner.fd_table.lock().get_file(fd)?;
+        file
+    };
+    if fd == epfd {
+        return Err(SysError::EINVAL);
+    }
+    // 普通文件和目录总是就绪, Linux 不允许监视它们
+    let mode = file.get_dentry().get_inode()?.get_meta().mode & InodeMode::TYPE_MASK;
+    if mode == InodeMode::FILE || mode == InodeMode::DIR {
+        return Err(SysError::EPERM);
+    }
+    let (events, data) = match op {
+        EPOLL_CTL_ADD | EPOLL_CTL_MOD => {
+            let event = *translated_ref(token, event);
+            (EpollEvents::from_bits_truncate(event.events), event.data)
+        }
+        EPOLL_CTL_DEL => (EpollEvents::empty(), 0),
+        _ => return Err(SysError::EINVAL),
+    };
+    if events.contains(EpollEvents::EPOLLEXCLUSIVE) {
+        let allowed = EpollEvents::EPOLLIN | EpollEvents::EPOLLOUT | EpollEvents::EPOLLERR | EpollEvents::EPOLLHUP
+            | EpollEvents::EPOLLWAKEUP | EpollEvents::EPOLLET | EpollEvents::EPOLLEXCLUSIVE;
+        if op != EPOLL_CTL_ADD || !allowed.contains(events) || file.clone().downcast_arc::<EpollFile>().is_ok() {
+            return Err(SysError::EINVAL);
+        }
+    }
+    match op {
+        EPOLL_CTL_ADD => epoll.ctl_add(fd as i32, &file, events, data)?,
+        EPOLL_CTL_MOD => epoll.ctl_mod(fd as i32, &file, events, data)?,
+        _ => epoll.ctl_del(fd as i32, &file)?,
+    }
+    Ok(0)
+}
+
+fn epoll_wait(epfd:usize,events:*mut EpollEvent,maxevents:i32,deadline:Option<usize>,sigmask:*const SignalFlags,sigsetsize:usize)->SysResult<isize>{
+    if maxevents <= 0 || maxevents as usize > i32::MAX as usize / mem::size_of::<EpollEvent>() {
+        return Err(SysError::EINVAL);
+    }
+    let token = current_user_token();
+    let epoll = get_epoll(epfd)?;
+    let file: Arc<dyn File> = epoll.clone();
+    let old_mask = swap_sigmask(token, sigmask, sigsetsize)?;
+    let mut ready = Vec::new();
+    let ret = poll_wait(&[file], deadline, || {
+        ready = epoll.collect(maxevents as usize);
+        ready.len()
+    });
+    restore_sigmask(old_mask, &ret);
+    ret?;
+    let bytes = unsafe {
+        slice::from_raw_parts(ready.as_ptr() as *const u8, ready.len() * mem::size_of::<EpollEvent>())
+    };
+    copy_to_user(token, events as *mut u8, bytes);
+    Ok(ready.len() as isize)
+}
+
+pub fn sys_epoll_pwait(epfd:usize,events:*mut EpollEvent,maxevents:i32,timeout:isize,sigmask:*const SignalFlags,sigsetsize:usize)->SysResult<isize>{
+    // 毫秒, 负数表示永不超时
+    let deadline = (timeout >= 0).then(|| monotonic_nsec() + timeout as usize * 1_000_000);
+    epoll_wait(epfd, events, maxevents, deadline, sigmask, sigsetsize)
+}
+
+pub fn sys_epoll_pwait2(epfd:usize,events:*mut EpollEvent,maxevents:i32,timeout:*const TimeSpec,sigmask:*const SignalFlags,sigsetsize:usize)->SysResult<isize>{
+    let deadline = timeout_deadline(current_user_token(), timeout)?;
+    epoll_wait(epfd, events, maxevents, deadline, sigmask, sigsetsize)
+}
+
+pub fn sys_eventfd2(initval:u32,flags:u32)->SysResult<isize>{
+    let semaphore = flags & EFD_SEMAPHORE != 0;
+    let flags = OpenFlags::from_bits(flags & !EFD_SEMAPHORE).ok_or(SysError::EINVAL)?;
+    if !(OpenFlags::CLOEXEC | OpenFlags::NONBLOCK).contains(flags) {
+        return Err(SysError::EINVAL);
+    }
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let eventfd = EventFd::new(initval as u64, semaphore, inner.cwd.get_superblock());
+    *eventfd.get_inner().flags.lock() = OpenFlags::RDWR | (flags & OpenFlags::NONBLOCK);
+    let fd = inner.fd_table.lock().insert(Some(Fd::new(eventfd, FdFlags::from(flags))))?;
+    Ok(fd as isize)
+}
+
+pub fn sys_signalfd4(fd:isize,mask:*const SignalFlags,sizemask:usize,flags:u32)->SysResult<isize>{
+    if sizemask != mem::size_of::<SignalFlags>() {
+        return Err(SysError::EINVAL);
+    }
+    let flags = OpenFlags::from_bits(flags).ok_or(SysError::EINVAL)?;
+    if !(OpenFlags::CLOEXEC | OpenFlags::NONBLOCK).contains(flags) {
+        return Err(SysError::EINVAL);
+    }
+    // SIGKILL 和 SIGSTOP 不能经由 signalfd 接收
+    let mask = *translated_ref(current_user_token(), mask) - (SignalFlags::SIGKILL | SignalFlags::SIGSTOP);
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    if fd != -1 {
+        let file = inner.fd_table.lock().get_file(fd as usize)?;
+        file.downcast_arc::<SignalFd>().map_err(|_| SysError::EINVAL)?.set_mask(mask);
+        return Ok(fd);
+    }
+    let signalfd = SignalFd::new(mask, inner.cwd.get_superblock());
+    *signalfd.get_inner().flags.lock() = flags & OpenFlags::NONBLOCK;
+    let fd = inner.fd_table.lock().insert(Some(Fd::new(signalfd, FdFlags::from(flags))))?;
+    Ok(fd as isize)
+}
+
+/// struct itimerspec
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct ITimerSpec {
+    it_interval: TimeSpec,
+    it_value: TimeSpec,
+}
+
+impl ITimerSpec {
+    fn from_nsec(value: usize, interval: usize) -> Self {
+        Self {
+            it_interval: TimeSpec::from_nsec(interval),
+            it_value: TimeSpec::from_nsec(value),
         }
     }
-    return Ok(ret);
+}
+
+const TFD_TIMER_ABSTIME: u32 = 1;
+const TFD_TIMER_CANCEL_ON_SET: u32 = 2;
+
+pub fn sys_timerfd_create(clockid:usize,flags:u32)->SysResult<isize>{
+    match clockid {
+        CLOCK_REALTIME | CLOCK_MONOTONIC | CLOCK_BOOTTIME | CLOCK_REALTIME_ALARM | CLOCK_BOOTTIME_ALARM => {}
+        _ => return Err(SysError::EINVAL),
+    }
+    let flags = OpenFlags::from_bits(flags).ok_or(SysError::EINVAL)?;
+    if !(OpenFlags::CLOEXEC | OpenFlags::NONBLOCK).contains(flags) {
+        return Err(SysError::EINVAL);
+    }
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    if matches!(clockid, CLOCK_REALTIME_ALARM | CLOCK_BOOTTIME_ALARM) && !inner.cred.privileged() {
+        return Err(SysError::EPERM);
+    }
+    let timerfd = TimerFd::new(clockid, inner.cwd.get_superblock());
+    *timerfd.get_inner().flags.lock() = flags & OpenFlags::NONBLOCK;
+    let fd = inner.fd_table.lock().insert(Some(Fd::new(timerfd, FdFlags::from(flags))))?;
+    Ok(fd as isize)
+}
+
+fn get_timerfd(fd:usize)->SysResult<Arc<TimerFd>>{
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let file = inner.fd_table.lock().get_file(fd)?;
+    file.downcast_arc::<TimerFd>().map_err(|_| SysError::EINVAL)
+}
+
+pub fn sys_timerfd_settime(fd:usize,flags:u32,new_value:*const ITimerSpec,old_value:*mut ITimerSpec)->SysResult<isize>{
+    if flags & !(TFD_TIMER_ABSTIME | TFD_TIMER_CANCEL_ON_SET) != 0 {
+        return Err(SysError::EINVAL);
+    }
+    let token = current_user_token();
+    let timerfd = get_timerfd(fd)?;
+    let new_value = *translated_ref(token, new_value);
+    if new_value.it_value.usec >= NSEC_PER_SEC || new_value.it_interval.usec >= NSEC_PER_SEC {
+        return Err(SysError::EINVAL);
+    }
+    let value = new_value.it_value.to_usec();
+    let next = if value == 0 {
+        None
+    } else if flags & TFD_TIMER_ABSTIME != 0 {
+        // 换算成单调时钟上的时刻
+        let now = monotonic_nsec();
+        Some((value + now).saturating_sub(clock_now(timerfd.clock())?))
+    } else {
+        Some(monotonic_nsec() + value)
+    };
+    let (old_remaining, old_interval) = timerfd.settime(next, new_value.it_interval.to_usec());
+    if !old_value.is_null() {
+        *translated_refmut(token, old_value) = ITimerSpec::from_nsec(old_remaining, old_interval);
+    }
+    Ok(0)
+}
+
+pub fn sys_timerfd_gettime(fd:usize,curr_value:*mut ITimerSpec)->SysResult<isize>{
+    let timerfd = get_timerfd(fd)?;
+    let (remaining, interval) = timerfd.gettime();
+    *translated_refmut(current_user_token(), curr_value) = ITimerSpec::from_nsec(remaining, interval);
+    Ok(0)
+}
+
+const MFD_CLOEXEC: u32 = 0x1;
+const MFD_ALLOW_SEALING: u32 = 0x2;
+/// memfd 名字的最大长度, 加上 "memfd:" 前缀不超过 NAME_MAX
+const MFD_NAME_MAX: usize = 249;
+
+pub fn sys_memfd_create(name:*const u8,flags:u32)->SysResult<isize>{
+    // MFD_HUGETLB 等其他标志都不支持
+    if flags & !(MFD_CLOEXEC | MFD_ALLOW_SEALING) != 0 {
+        return Err(SysError::EINVAL);
+    }
+    let name = translated_str(current_user_token(), name);
+    if name.len() > MFD_NAME_MAX {
+        return Err(SysError::EINVAL);
+    }
+    let file = vfs::memfd_create(&name, flags & MFD_ALLOW_SEALING != 0);
+    let cred = current_fs_cred();
+    file.get_dentry().get_inode()?.set_owner(cred.uid, cred.gid)?;
+    let fd_flags = if flags & MFD_CLOEXEC != 0 { FdFlags::from(OpenFlags::CLOEXEC) } else { FdFlags::empty() };
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let fd = inner.fd_table.lock().insert(Some(Fd::new(file, fd_flags)))?;
+    Ok(fd as isize)
 }
 
 pub fn sys_renameat2(olddirfd:isize,oldpath:*const u8,newdirfd:isize,newpath:*const u8,flags:usize)->SysResult<isize>{
     let flags = RenameFlags::from_bits_retain(flags as i32);
-    let oldpath = parse_fd_path(olddirfd, oldpath)?;
-    let newpath = parse_fd_path(newdirfd, newpath)?;
-    let old_dentry = path_to_dentry(&oldpath)?;
-    let new_dentry;
-    let r = path_to_dentry(&newpath);
-    if r.is_ok(){
-        new_dentry = r.unwrap();
+    let token = current_user_token();
+    let oldpath = translated_str(token, oldpath);
+    let newpath = translated_str(token, newpath);
+    // rename 操作的是目录项本身, 两端都不跟随符号链接
+    let (old_father, old_name) = lookup_parent_at(dirfd_base(olddirfd, &oldpath)?, &oldpath)?;
+    let (new_father, new_name) = lookup_parent_at(dirfd_base(newdirfd, &newpath)?, &newpath)?;
+    if old_name == "." || old_name == ".." || new_name == "." || new_name == ".." {
+        return Err(SysError::EINVAL);
     }
-    else{
-        let mut name = String::new();
-        let father = path_to_father_dentry(&newpath, &mut name)?;
-        new_dentry = father.find_or_create(name.as_str(), *old_dentry.get_inode()?.get_meta()._type.lock());
+    let cred = current_fs_cred();
+    vfs::may_modify_dir(&old_father, &cred)?;
+    vfs::may_modify_dir(&new_father, &cred)?;
+    // 只读挂载上连目标目录项都不建
+    vfs::check_writable(&old_father)?;
+    vfs::check_writable(&new_father)?;
+    let old_dentry = old_father.lookup(old_name.as_str())?;
+    if vfs::lookup_mount(&old_dentry).is_some() {
+        return Err(SysError::EBUSY);
     }
+    vfs::check_sticky(&old_father, &old_dentry, &cred)?;
+    let new_dentry = match new_father.lookup(new_name.as_str()) {
+        Ok(dentry) => {
+            vfs::check_sticky(&new_father, &dentry, &cred)?;
+            dentry
+        }
+        Err(_) => new_father.find_or_create(new_name.as_str(), *old_dentry.get_inode()?.get_meta()._type.lock()),
+    };
     if let Err(e)= old_dentry.vfs_rename(&new_dentry, flags){
         return Err(e);
     }
@@ -825,10 +2259,10 @@ pub struct Statx {
     __spare2:[u64;14],
 }
 ///
-pub fn sys_statx(dirfd:isize,path:*const u8,_flags:i32,_mask:u32,statx:*mut Statx)->SysResult<isize>{
+pub fn sys_statx(dirfd:isize,path:*const u8,flags:i32,_mask:u32,statx:*mut Statx)->SysResult<isize>{
     let token = current_user_token();
-    let path = parse_fd_path(dirfd, path)?;
-    let dentry = path_to_dentry(path.as_str())?;
+    let path = translated_str(token, path);
+    let dentry = lookup_dirfd(dirfd, &path, flags)?;
     let attr = dentry.get_inode()?.get_attr()?;
     let statx = translated_refmut(token, statx);
     statx.stx_mask = 0;
@@ -0,0 +1,150 @@
+//! asm-generic 系统调用号, riscv64/loongarch64/aarch64 共用
+pub const SYSCALL_SETXATTR: usize = 5;
+pub const SYSCALL_LSETXATTR: usize = 6;
+pub const SYSCALL_FSETXATTR: usize = 7;
+pub const SYSCALL_GETXATTR: usize = 8;
+pub const SYSCALL_LGETXATTR: usize = 9;
+pub const SYSCALL_FGETXATTR: usize = 10;
+pub const SYSCALL_LISTXATTR: usize = 11;
+pub const SYSCALL_LLISTXATTR: usize = 12;
+pub const SYSCALL_FLISTXATTR: usize = 13;
+pub const SYSCALL_REMOVEXATTR: usize = 14;
+pub const SYSCALL_LREMOVEXATTR: usize = 15;
+pub const SYSCALL_FREMOVEXATTR: usize = 16;
+pub const SYSCALL_GETCWD: usize = 17;
+pub const SYSCALL_EVENTFD2: usize = 19;
+pub const SYSCALL_EPOLL_CREATE1: usize = 20;
+pub const SYSCALL_EPOLL_CTL: usize = 21;
+pub const SYSCALL_EPOLL_PWAIT: usize = 22;
+pub const SYSCALL_DUP: usize = 23;
+pub const SYSCALL_DUP3: usize = 24;
+pub const SYSCALL_FCNTL: usize = 25;
+pub const SYSCALL_IOCTL: usize = 29;
+pub const SYSCALL_FLOCK: usize = 32;
+pub const SYSCALL_MKNODAT: usize = 33;
+pub const SYSCALL_MKDIRAT: usize = 34;
+pub const SYSCALL_UNLINKAT: usize = 35;
+pub const SYSCALL_SYMLINKAT: usize = 36;
+pub const SYSCALL_LINKAT: usize = 37;
+pub const SYSCALL_UMOUNT: usize = 39;
+pub const SYSCALL_MOUNT: usize = 40;
+pub const SYSCALL_STATFS: usize = 43;
+pub const SYSCALL_FSTATFS: usize = 44;
+pub const SYSCALL_TRUNCATE: usize = 45;
+pub const SYSCALL_FTRUNCATE: usize = 46;
+pub const SYSCALL_FALLOCATE: usize = 47;
+pub const SYSCALL_FACCESSAT: usize = 48;
+pub const SYSCALL_CHDIR: usize = 49;
+pub const SYSCALL_CHROOT: usize = 51;
+pub const SYSCALL_FCHMOD: usize = 52;
+pub const SYSCALL_FCHMODAT: usize = 53;
+pub const SYSCALL_FCHOWNAT: usize = 54;
+pub const SYSCALL_FCHOWN: usize = 55;
+pub const SYSCALL_OPENAT: usize = 56;
+pub const SYSCALL_CLOSE: usize = 57;
+pub const SYSCALL_PIPE2: usize = 59;
+pub const SYSCALL_GETDENTS64: usize = 61;
+pub const SYSCALL_LSEEK: usize = 62;
+pub const SYSCALL_READ: usize = 63;
+pub const SYSCALL_WRITE: usize = 64;
+pub const SYSCALL_READV: usize = 65;
+pub const SYSCALL_WRITEV: usize = 66;
+pub const SYSCALL_PREAD64: usize = 67;
+pub const SYSCALL_PWRITE64: usize = 68;
+pub const SYSCALL_PREADV: usize = 69;
+pub const SYSCALL_PWRITEV: usize = 70;
+pub const SYSCALL_SENDFILE: usize = 71;
+pub const SYSCALL_PSELECT6: usize = 72;
+pub const SYSCALL_PPOLL: usize = 73;
+pub const SYSCALL_SIGNALFD4: usize = 74;
+pub const SYSCALL_VMSPLICE: usize = 75;
+pub const SYSCALL_SPLICE: usize = 76;
+pub const SYSCALL_TEE: usize = 77;
+pub const SYSCALL_READLINKAT: usize = 78;
+pub const SYSCALL_FSTATAT: usize = 79;
+pub const SYSCALL_FSTAT: usize = 80;
+pub const SYSCALL_SYNC: usize = 81;
+pub const SYSCALL_FSYNC: usize = 82;
+pub const SYSCALL_FDATASYNC: usize = 83;
+pub const SYSCALL_SYNC_FILE_RANGE: usize = 84;
+pub const SYSCALL_TIMERFD_CREATE: usize = 85;
+pub const SYSCALL_TIMERFD_SETTIME: usize = 86;
+pub const SYSCALL_TIMERFD_GETTIME: usize = 87;
+pub const SYSCALL_UTIMENSAT: usize = 88;
+pub const SYSCALL_EXIT: usize = 93;
+pub const SYSCALL_EXIT_GROUP: usize = 94;
+pub const SYSCALL_SET_TID_ADDRESS: usize = 96;
+pub const SYSCALL_FUTEX: usize = 98;
+pub const SYSCALL_SET_ROBUST_LIST: usize = 99;
+pub const SYSCALL_GET_ROBUST_LIST: usize = 100;
+pub const SYSCALL_NANOSLEEP: usize = 101;
+pub const SYSCALL_CLOCK_SETTIME: usize = 112;
+pub const SYSCALL_CLOCK_GETTIME: usize = 113;
+pub const SYSCALL_CLOCK_GETRES: usize = 114;
+pub const SYSCALL_CLOCK_NANOSLEEP: usize = 115;
+pub const SYSCALL_SYSLOG: usize = 116;
+pub const SYSCALL_YIELD: usize = 124;
+pub const SYSCALL_KILL: usize = 129;
+pub const SYSCALL_TKILL: usize = 130;
+pub const SYSCALL_TGKILL: usize = 131;
+pub const SYSCALL_SIGACTION: usize = 134;
+pub const SYSCALL_SIGPROCMASK: usize = 135;
+pub const SYSCALL_RT_SIGTIMEDWAIT: usize = 137;
+pub const SYSCALL_SIGRETURN: usize = 139;
+pub const SYSCALL_SETGID: usize = 144;
+pub const SYSCALL_SETUID: usize = 146;
+pub const SYSCALL_SETRESUID: usize = 147;
+pub const SYSCALL_GETRESUID: usize = 148;
+pub const SYSCALL_SETRESGID: usize = 149;
+pub const SYSCALL_GETRESGID: usize = 150;
+pub const SYSCALL_TIMES: usize = 153;
+pub const SYSCALL_SETPGID: usize = 154;
+pub const SYSCALL_GETPGID: usize = 155;
+pub const SYSCALL_SETSID: usize = 157;
+pub const SYSCALL_GETGROUPS: usize = 158;
+pub const SYSCALL_SETGROUPS: usize = 159;
+pub const SYSCALL_UNAME: usize = 160;
+pub const SYSCALL_UMASK: usize = 166;
+pub const SYSCALL_GETTIMEOFDAY: usize = 169;
+pub const SYSCALL_SETTIMEOFDAY: usize = 170;
+pub const SYSCALL_ADJTIMEX: usize = 171;
+pub const SYSCALL_GETPID: usize = 172;
+pub const SYSCALL_GETPPID: usize = 173;
+pub const SYSCALL_GETUID: usize = 174;
+pub const SYSCALL_GETEUID: usize = 175;
+pub const SYSCALL_GETGID: usize = 176;
+pub const SYSCALL_GETEGID: usize = 177;
+pub const SYSCALL_GETTID: usize = 178;
+pub const SYSCALL_SYSINFO: usize = 179;
+pub const SYSCALL_SOCKET: usize = 198;
+pub const SYSCALL_SOCKETPAIR: usize = 199;
+pub const SYSCALL_BIND: usize = 200;
+pub const SYSCALL_LISTEN: usize = 201;
+pub const SYSCALL_ACCEPT: usize = 202;
+pub const SYSCALL_CONNECT: usize = 203;
+pub const SYSCALL_GETSOCKNAME: usize = 204;
+pub const SYSCALL_GETPEERNAME: usize = 205;
+pub const SYSCALL_SENDTO: usize = 206;
+pub const SYSCALL_RECVFROM: usize = 207;
+pub const SYSCALL_SETSOCKOPT: usize = 208;
+pub const SYSCALL_SENDMSG: usize = 211;
+pub const SYSCALL_BRK: usize = 214;
+pub const SYSCALL_MUNMAP: usize = 215;
+pub const SYSCALL_MREMAP: usize = 216;
+pub const SYSCALL_CLONE: usize = 220;
+pub const SYSCALL_EXEC: usize = 221;
+pub const SYSCALL_MMAP: usize = 222;
+pub const SYSCALL_MPROTECT: usize = 226;
+pub const SYSCALL_MADVISE: usize = 233;
+pub const SYSCALL_WAITPID: usize = 260;
+pub const SYSCALL_PRLIMIT64: usize = 261;
+pub const SYSCALL_CLOCK_ADJTIME: usize = 266;
+pub const SYSCALL_SYNCFS: usize = 267;
+pub const SYSCALL_RENAMEAT2: usize = 276;
+pub const SYSCALL_GET_RANDOM: usize = 278;
+pub const SYSCALL_MEMFD_CREATE: usize = 279;
+pub const SYSCALL_PREADV2: usize = 286;
+pub const SYSCALL_PWRITEV2: usize = 287;
+pub const SYSCALL_STATX: usize = 291;
+pub const SYSCALL_CLONE3: usize = 435;
+pub const SYSCALL_EPOLL_PWAIT2: usize = 441;
@@ -0,0 +1,13 @@
+//! 系统调用号
+//!
+//! riscv64/loongarch64/aarch64 使用 asm-generic 的编号, x86_64 有自己的一套.
+//! 两边常量名保持一致, 系统调用表只引用常量名.
+#[cfg(not(target_arch = "x86_64"))]
+mod generic;
+#[cfg(not(target_arch = "x86_64"))]
+pub use generic::*;
+
+#[cfg(target_arch = "x86_64")]
+mod x86_64;
+#[cfg(target_arch = "x86_64")]
+pub use x86_64::*;
@@ -0,0 +1,150 @@
+//! x86_64 系统调用号
+pub const SYSCALL_READ: usize = 0;
+pub const SYSCALL_WRITE: usize = 1;
+pub const SYSCALL_CLOSE: usize = 3;
+pub const SYSCALL_FSTAT: usize = 5;
+pub const SYSCALL_LSEEK: usize = 8;
+pub const SYSCALL_MMAP: usize = 9;
+pub const SYSCALL_MPROTECT: usize = 10;
+pub const SYSCALL_MUNMAP: usize = 11;
+pub const SYSCALL_BRK: usize = 12;
+pub const SYSCALL_SIGACTION: usize = 13;
+pub const SYSCALL_SIGPROCMASK: usize = 14;
+pub const SYSCALL_SIGRETURN: usize = 15;
+pub const SYSCALL_IOCTL: usize = 16;
+pub const SYSCALL_PREAD64: usize = 17;
+pub const SYSCALL_PWRITE64: usize = 18;
+pub const SYSCALL_READV: usize = 19;
+pub const SYSCALL_WRITEV: usize = 20;
+pub const SYSCALL_YIELD: usize = 24;
+pub const SYSCALL_MREMAP: usize = 25;
+pub const SYSCALL_MADVISE: usize = 28;
+pub const SYSCALL_DUP: usize = 32;
+pub const SYSCALL_NANOSLEEP: usize = 35;
+pub const SYSCALL_GETPID: usize = 39;
+pub const SYSCALL_SENDFILE: usize = 40;
+pub const SYSCALL_SOCKET: usize = 41;
+pub const SYSCALL_CONNECT: usize = 42;
+pub const SYSCALL_ACCEPT: usize = 43;
+pub const SYSCALL_SENDTO: usize = 44;
+pub const SYSCALL_RECVFROM: usize = 45;
+pub const SYSCALL_SENDMSG: usize = 46;
+pub const SYSCALL_BIND: usize = 49;
+pub const SYSCALL_LISTEN: usize = 50;
+pub const SYSCALL_GETSOCKNAME: usize = 51;
+pub const SYSCALL_GETPEERNAME: usize = 52;
+pub const SYSCALL_SOCKETPAIR: usize = 53;
+pub const SYSCALL_SETSOCKOPT: usize = 54;
+pub const SYSCALL_CLONE: usize = 56;
+pub const SYSCALL_EXEC: usize = 59;
+pub const SYSCALL_EXIT: usize = 60;
+pub const SYSCALL_WAITPID: usize = 61;
+pub const SYSCALL_KILL: usize = 62;
+pub const SYSCALL_UNAME: usize = 63;
+pub const SYSCALL_FCNTL: usize = 72;
+pub const SYSCALL_FLOCK: usize = 73;
+pub const SYSCALL_FSYNC: usize = 74;
+pub const SYSCALL_FDATASYNC: usize = 75;
+pub const SYSCALL_TRUNCATE: usize = 76;
+pub const SYSCALL_FTRUNCATE: usize = 77;
+pub const SYSCALL_GETCWD: usize = 79;
+pub const SYSCALL_CHDIR: usize = 80;
+pub const SYSCALL_FCHMOD: usize = 91;
+pub const SYSCALL_FCHOWN: usize = 93;
+pub const SYSCALL_UMASK: usize = 95;
+pub const SYSCALL_GETTIMEOFDAY: usize = 96;
+pub const SYSCALL_SYSINFO: usize = 99;
+pub const SYSCALL_TIMES: usize = 100;
+pub const SYSCALL_GETUID: usize = 102;
+pub const SYSCALL_SYSLOG: usize = 103;
+pub const SYSCALL_GETGID: usize = 104;
+pub const SYSCALL_SETUID: usize = 105;
+pub const SYSCALL_SETGID: usize = 106;
+pub const SYSCALL_GETEUID: usize = 107;
+pub const SYSCALL_GETEGID: usize = 108;
+pub const SYSCALL_SETPGID: usize = 109;
+pub const SYSCALL_GETPPID: usize = 110;
+pub const SYSCALL_SETSID: usize = 112;
+pub const SYSCALL_GETGROUPS: usize = 115;
+pub const SYSCALL_SETGROUPS: usize = 116;
+pub const SYSCALL_SETRESUID: usize = 117;
+pub const SYSCALL_GETRESUID: usize = 118;
+pub const SYSCALL_SETRESGID: usize = 119;
+pub const SYSCALL_GETRESGID: usize = 120;
+pub const SYSCALL_GETPGID: usize = 121;
+pub const SYSCALL_RT_SIGTIMEDWAIT: usize = 128;
+pub const SYSCALL_STATFS: usize = 137;
+pub const SYSCALL_FSTATFS: usize = 138;
+pub const SYSCALL_ADJTIMEX: usize = 159;
+pub const SYSCALL_CHROOT: usize = 161;
+pub const SYSCALL_SYNC: usize = 162;
+pub const SYSCALL_SETTIMEOFDAY: usize = 164;
+pub const SYSCALL_MOUNT: usize = 165;
+pub const SYSCALL_UMOUNT: usize = 166;
+pub const SYSCALL_GETTID: usize = 186;
+pub const SYSCALL_SETXATTR: usize = 188;
+pub const SYSCALL_LSETXATTR: usize = 189;
+pub const SYSCALL_FSETXATTR: usize = 190;
+pub const SYSCALL_GETXATTR: usize = 191;
+pub const SYSCALL_LGETXATTR: usize = 192;
+pub const SYSCALL_FGETXATTR: usize = 193;
+pub const SYSCALL_LISTXATTR: usize = 194;
+pub const SYSCALL_LLISTXATTR: usize = 195;
+pub const SYSCALL_FLISTXATTR: usize = 196;
+pub const SYSCALL_REMOVEXATTR: usize = 197;
+pub const SYSCALL_LREMOVEXATTR: usize = 198;
+pub const SYSCALL_FREMOVEXATTR: usize = 199;
+pub const SYSCALL_TKILL: usize = 200;
+pub const SYSCALL_FUTEX: usize = 202;
+pub const SYSCALL_GETDENTS64: usize = 217;
+pub const SYSCALL_SET_TID_ADDRESS: usize = 218;
+pub const SYSCALL_CLOCK_SETTIME: usize = 227;
+pub const SYSCALL_CLOCK_GETTIME: usize = 228;
+pub const SYSCALL_CLOCK_GETRES: usize = 229;
+pub const SYSCALL_CLOCK_NANOSLEEP: usize = 230;
+pub const SYSCALL_EXIT_GROUP: usize = 231;
+pub const SYSCALL_EPOLL_CTL: usize = 233;
+pub const SYSCALL_TGKILL: usize = 234;
+pub const SYSCALL_OPENAT: usize = 257;
+pub const SYSCALL_MKDIRAT: usize = 258;
+pub const SYSCALL_MKNODAT: usize = 259;
+pub const SYSCALL_FCHOWNAT: usize = 260;
+pub const SYSCALL_FSTATAT: usize = 262;
+pub const SYSCALL_UNLINKAT: usize = 263;
+pub const SYSCALL_LINKAT: usize = 265;
+pub const SYSCALL_SYMLINKAT: usize = 266;
+pub const SYSCALL_READLINKAT: usize = 267;
+pub const SYSCALL_FCHMODAT: usize = 268;
+pub const SYSCALL_FACCESSAT: usize = 269;
+pub const SYSCALL_PSELECT6: usize = 270;
+pub const SYSCALL_PPOLL: usize = 271;
+pub const SYSCALL_SET_ROBUST_LIST: usize = 273;
+pub const SYSCALL_GET_ROBUST_LIST: usize = 274;
+pub const SYSCALL_SPLICE: usize = 275;
+pub const SYSCALL_TEE: usize = 276;
+pub const SYSCALL_SYNC_FILE_RANGE: usize = 277;
+pub const SYSCALL_VMSPLICE: usize = 278;
+pub const SYSCALL_UTIMENSAT: usize = 280;
+pub const SYSCALL_EPOLL_PWAIT: usize = 281;
+pub const SYSCALL_TIMERFD_CREATE: usize = 283;
+pub const SYSCALL_FALLOCATE: usize = 285;
+pub const SYSCALL_TIMERFD_SETTIME: usize = 286;
+pub const SYSCALL_TIMERFD_GETTIME: usize = 287;
+pub const SYSCALL_SIGNALFD4: usize = 289;
+pub const SYSCALL_EVENTFD2: usize = 290;
+pub const SYSCALL_EPOLL_CREATE1: usize = 291;
+pub const SYSCALL_DUP3: usize = 292;
+pub const SYSCALL_PIPE2: usize = 293;
+pub const SYSCALL_PREADV: usize = 295;
+pub const SYSCALL_PWRITEV: usize = 296;
+pub const SYSCALL_PRLIMIT64: usize = 302;
+pub const SYSCALL_CLOCK_ADJTIME: usize = 305;
+pub const SYSCALL_SYNCFS: usize = 306;
+pub const SYSCALL_RENAMEAT2: usize = 316;
+pub const SYSCALL_GET_RANDOM: usize = 318;
+pub const SYSCALL_MEMFD_CREATE: usize = 319;
+pub const SYSCALL_PREADV2: usize = 327;
+pub const SYSCALL_PWRITEV2: usize = 328;
+pub const SYSCALL_STATX: usize = 332;
+pub const SYSCALL_CLONE3: usize = 435;
+pub const SYSCALL_EPOLL_PWAIT2: usize = 441;
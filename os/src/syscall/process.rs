@@ -1,29 +1,33 @@
 use core::f32::consts::E;
 use core::ops::Add;
 
-use crate::fs::{open_file,path_to_dentry,path_to_father_dentry,create_file};
+use crate::fs::{open_file,path_to_dentry,path_to_father_dentry,create_file,current_fs_cred,release_posix_locks};
 use crate::mm::{frame_alloc, frame_dealloc, translated_ref, translated_refmut, translated_str, MapAreaType, MapType};
 use crate::task::{
     self, UNAME,add_task, current_task, current_user_token, 
     exit_current_and_run_next, suspend_current_and_run_next,SignalFlags,tid2task,remove_from_tid2task,
-    MAX_SIG,SigAction,check_pending_signals,FutexKey,futex_wait,futex_wake,futex_requeue,SigInfo,SigDetails
+    MAX_SIG,SigAction,check_pending_signals,check_privileged,SigInfo,SigDetails,wakeup_blocked_task,
+    FutexKey,futex_wait,futex_wake,futex_requeue,futex_wake_op,futex_lock_pi,futex_unlock_pi,futex_word,
+    FUTEX_BITSET_MATCH_ANY,ROBUST_LIST_HEAD_SIZE,NGROUPS_MAX,
 };
 use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use arch::time::{self, Time};
 use arch::{TrapFrameArgs, PAGE_SIZE};
-use crate::drivers::BLOCK_DEVICE;
-use vfs_defs::{DiskInodeType,OpenFlags,Dentry};
+use vfs_defs::{DiskInodeType,OpenFlags,Dentry,InodeMode,MountFlags};
 use config::{ USER_STACK_SIZE,RLimit,Resource};
 use arch::addr::{PhysPage, VirtAddr, VirtPage};
 use crate::mm::{MapPermission, MapArea, from_prot, VPNRange};
 use arch::pagetable::MappingSize;
 use crate::task::{Tms, Utsname, TimeSpec, SysInfo};
+use ::time::{
+    adjust_realtime_nsec, boottime_nsec, coarse_nsec, monotonic_nsec, realtime_nsec, set_realtime_nsec,
+    TimeVal, NSEC_PER_SEC, TAI_OFFSET_SEC, TICK_NSEC,
+};
 use bitflags::*;
 use system_result::{SysError,SysResult};
 use arch::pagetable::TLB;
-use crate::timer::add_futex_timer;
 
 const MODULE_LEVEL:log::Level = log::Level::Debug;
 #[allow(unused)]
@@ -89,17 +93,28 @@ pub fn sys_yield() -> SysResult<isize> {
     Ok(0)
 }
 
-pub fn sys_get_time(ts:*mut TimeSpec) -> SysResult<isize> {
+pub fn sys_gettimeofday(tv: *mut TimeVal) -> SysResult<isize> {
+    if tv.is_null(){
+        return Err(SysError::EINVAL);
+    }
     let token = current_user_token();
-    if ts.is_null(){
+    *translated_refmut(token, tv) = TimeVal::from_nsec(realtime_nsec());
+    Ok(0)
+}
+
+pub fn sys_settimeofday(tv: *const TimeVal) -> SysResult<isize> {
+    // 时区参数已被 Linux 废弃, 忽略
+    if tv.is_null(){
+        return Ok(0);
+    }
+    check_privileged()?;
+    let token = current_user_token();
+    let tv = *translated_ref(token, tv);
+    if tv.usec >= 1_000_000 {
         return Err(SysError::EINVAL);
     }
-    let ts = translated_refmut(token, ts);
-    let usec = Time::now().to_usec();
-    *ts = TimeSpec{
-        sec:usec/1000000,
-        usec,
-    };
+    set_realtime_nsec(tv.to_nsec());
+    crate::vdso::update();
     Ok(0)
 }
 
@@ -183,9 +198,32 @@ pub fn sys_exec(path: *const u8, mut args: *const usize) -> SysResult<isize> {
         }
     }
     let app_inode = open_file(path.as_str(), OpenFlags::RDONLY)?;
+    let app_dentry = app_inode.get_dentry();
+    let mount_flags = vfs::mount_flags(&app_dentry);
+    if mount_flags.contains(MountFlags::MS_NOEXEC) || app_dentry.is_dir() {
+        return Err(SysError::EACCES);
+    }
+    let inode = app_dentry.get_inode()?;
+    vfs::inode_permission(&inode, &current_fs_cred(), vfs::MAY_EXEC)?;
+    // setuid/setgid 程序以文件属主/属组的身份运行, nosuid 挂载上忽略这两位
+    let (mode, file_uid, file_gid) = {
+        let inner = inode.get_meta().inner.lock();
+        (inner.mode, inner.uid, inner.gid)
+    };
+    let honor = !mount_flags.contains(MountFlags::MS_NOSUID);
+    let new_uid = (honor && mode.contains(InodeMode::SET_UID)).then_some(file_uid);
+    let new_gid = (honor && mode.contains(InodeMode::SET_GID | InodeMode::GROUP_EXEC)).then_some(file_gid);
     let all_data = app_inode.read_all();
     let task = current_task().unwrap();
     task.exec(all_data.as_slice(),args_vec);
+    let mut inner = task.inner_exclusive_access();
+    inner.exe = Some(app_dentry);
+    inner.cred.exec_transition(new_uid, new_gid);
+    let closed = inner.fd_table.lock().close_on_exec();
+    drop(inner);
+    for fd in closed {
+        release_posix_locks(&fd.file(), task.getpid());
+    }
     Ok(0)
 }
 
@@ -245,12 +283,131 @@ pub fn sys_chdir(path: *const u8) -> SysResult<isize> {
     let token = current_user_token();
     let path = translated_str(token, path);
     let dentry = path_to_dentry(&path)?;
+    if !dentry.is_dir() {
+        return Err(SysError::ENOTDIR);
+    }
     let task = current_task().unwrap();
     let mut task_inner = task.inner_exclusive_access();
     task_inner.cwd = dentry;
     Ok(0)
 }
 
+pub fn sys_getuid() -> SysResult<isize> {
+    Ok(current_task().unwrap().inner_exclusive_access().cred.ruid as isize)
+}
+
+pub fn sys_geteuid() -> SysResult<isize> {
+    Ok(current_task().unwrap().inner_exclusive_access().cred.euid as isize)
+}
+
+pub fn sys_getgid() -> SysResult<isize> {
+    Ok(current_task().unwrap().inner_exclusive_access().cred.rgid as isize)
+}
+
+pub fn sys_getegid() -> SysResult<isize> {
+    Ok(current_task().unwrap().inner_exclusive_access().cred.egid as isize)
+}
+
+pub fn sys_setuid(uid: u32) -> SysResult<isize> {
+    current_task().unwrap().inner_exclusive_access().cred.setuid(uid)?;
+    Ok(0)
+}
+
+pub fn sys_setgid(gid: u32) -> SysResult<isize> {
+    current_task().unwrap().inner_exclusive_access().cred.setgid(gid)?;
+    Ok(0)
+}
+
+/// set*id 系列中 -1 表示保持不变
+fn id_arg(id: u32) -> Option<u32> {
+    (id != u32::MAX).then_some(id)
+}
+
+pub fn sys_setresuid(ruid: u32, euid: u32, suid: u32) -> SysResult<isize> {
+    let task = current_task().unwrap();
+    task.inner_exclusive_access().cred.setresuid(id_arg(ruid), id_arg(euid), id_arg(suid))?;
+    Ok(0)
+}
+
+pub fn sys_setresgid(rgid: u32, egid: u32, sgid: u32) -> SysResult<isize> {
+    let task = current_task().unwrap();
+    task.inner_exclusive_access().cred.setresgid(id_arg(rgid), id_arg(egid), id_arg(sgid))?;
+    Ok(0)
+}
+
+pub fn sys_getresuid(ruid: *mut u32, euid: *mut u32, suid: *mut u32) -> SysResult<isize> {
+    let token = current_user_token();
+    let cred = current_task().unwrap().inner_exclusive_access().cred.clone();
+    *translated_refmut(token, ruid) = cred.ruid;
+    *translated_refmut(token, euid) = cred.euid;
+    *translated_refmut(token, suid) = cred.suid;
+    Ok(0)
+}
+
+pub fn sys_getresgid(rgid: *mut u32, egid: *mut u32, sgid: *mut u32) -> SysResult<isize> {
+    let token = current_user_token();
+    let cred = current_task().unwrap().inner_exclusive_access().cred.clone();
+    *translated_refmut(token, rgid) = cred.rgid;
+    *translated_refmut(token, egid) = cred.egid;
+    *translated_refmut(token, sgid) = cred.sgid;
+    Ok(0)
+}
+
+/// `size` 为 0 时只返回附加组的数量
+pub fn sys_getgroups(size: i32, list: *mut u32) -> SysResult<isize> {
+    if size < 0 {
+        return Err(SysError::EINVAL);
+    }
+    let token = current_user_token();
+    let groups = current_task().unwrap().inner_exclusive_access().cred.groups.clone();
+    if size == 0 {
+        return Ok(groups.len() as isize);
+    }
+    if (size as usize) < groups.len() {
+        return Err(SysError::EINVAL);
+    }
+    for (i, gid) in groups.iter().enumerate() {
+        *translated_refmut(token, unsafe { list.add(i) }) = *gid;
+    }
+    Ok(groups.len() as isize)
+}
+
+pub fn sys_setgroups(size: usize, list: *const u32) -> SysResult<isize> {
+    if size > NGROUPS_MAX {
+        return Err(SysError::EINVAL);
+    }
+    let token = current_user_token();
+    let groups = (0..size)
+        .map(|i| *translated_ref(token, unsafe { list.add(i) }))
+        .collect();
+    current_task().unwrap().inner_exclusive_access().cred.setgroups(groups)?;
+    Ok(0)
+}
+
+/// 设置新建文件的权限掩码, 返回原来的掩码
+pub fn sys_umask(mask: u32) -> SysResult<isize> {
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    let old = task_inner.umask;
+    task_inner.umask = mask & 0o777;
+    Ok(old as isize)
+}
+
+/// 修改进程的根目录, 之后的绝对路径和 `..` 都以它为界
+pub fn sys_chroot(path: *const u8) -> SysResult<isize> {
+    check_privileged()?;
+    let token = current_user_token();
+    let path = translated_str(token, path);
+    let dentry = path_to_dentry(&path)?;
+    if !dentry.is_dir() {
+        return Err(SysError::ENOTDIR);
+    }
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    task_inner.root = dentry;
+    Ok(0)
+}
+
 
 pub fn sys_brk(new_brk:  usize) -> SysResult<isize> {
     log_debug!("brkarg:{:x}",new_brk);
@@ -367,26 +524,24 @@ struct timespec {
 	long   tv_nsec;       /* 纳秒, 范围在0~999999999 */
 };
 */
-pub fn sys_nanosleep(timespec:*const TimeSpec)->SysResult<isize>{
+pub fn sys_nanosleep(timespec:*const TimeSpec,remain:*mut TimeSpec)->SysResult<isize>{
     if timespec.is_null(){
         return Err(SysError::EINVAL);
     }
     let token = current_user_token();
-    let timespec = translated_ref(token, timespec);
-    if timespec.usec > 99999999{
+    let timespec = *translated_ref(token, timespec);
+    if timespec.usec >= NSEC_PER_SEC{
         return Err(SysError::EINVAL);
     }
-    let total_time = timespec.sec * 1000000000 + timespec.usec;
-    let start_time = Time::now().to_nsec();
-    loop{
-        let current_time = Time::now().to_nsec();
-        if current_time - start_time < total_time{
-            suspend_current_and_run_next();
-        }
-        else{
-            return Ok(0);
-        }
+    let deadline = monotonic_nsec() + timespec.to_usec();
+    if crate::timer::sleep_until(deadline) {
+        return Ok(0);
     }
+    // 被提前唤醒(信号)
+    if !remain.is_null() {
+        *translated_refmut(token, remain) = TimeSpec::from_nsec(deadline.saturating_sub(monotonic_nsec()));
+    }
+    Err(SysError::EINTR)
 }
 
 pub fn sys_getppid()->SysResult<isize>{
@@ -422,7 +577,7 @@ pub fn sys_prlimit64(pid: usize,resource: i32,new_limit: *const RLimit,old_limit
             return Err(SysError::ESRCH);
         }
     }
-    let inner = task.inner_exclusive_access();
+    let mut inner = task.inner_exclusive_access();
     let resource = Resource::new(resource);
     if resource.is_none(){
         return Err(SysError::EINVAL);
@@ -440,6 +595,9 @@ pub fn sys_prlimit64(pid: usize,resource: i32,new_limit: *const RLimit,old_limit
             Resource::NOFILE=>{
                 limit = inner.fd_table.lock().rlimit();
             }
+            Resource::FSIZE=>{
+                limit = inner.fsize_limit;
+            }
             _=>{
                 limit = RLimit{
                     rlimit_cur:0,
@@ -455,6 +613,12 @@ pub fn sys_prlimit64(pid: usize,resource: i32,new_limit: *const RLimit,old_limit
             Resource::NOFILE=>{
                 inner.fd_table.lock().set_rlimit(limit);
             },
+            Resource::FSIZE=>{
+                if limit.rlimit_cur > limit.rlimit_max {
+                    return Err(SysError::EINVAL);
+                }
+                inner.fsize_limit = limit;
+            },
             _=>{}
         }
     }
@@ -468,32 +632,145 @@ struct timespec {
 };
 */
 pub const CLOCK_REALTIME: usize = 0; //标准POSIX实时时钟
-pub const CLOCK_MONOTONIC: usize = 1; //POSIX时钟,以恒定速率运行;不会复位和调整,它的取值和CLOCK_REALTIME是一样的.
+pub const CLOCK_MONOTONIC: usize = 1; //POSIX时钟,以恒定速率运行;不会复位和调整
 pub const CLOCK_PROCESS_CPUTIME_ID: usize = 2;
 pub const CLOCK_THREAD_CPUTIME_ID: usize = 3; //是CPU中的硬件计时器中实现的.
+pub const CLOCK_MONOTONIC_RAW: usize = 4;
+pub const CLOCK_REALTIME_COARSE: usize = 5;
+pub const CLOCK_MONOTONIC_COARSE: usize = 6;
+pub const CLOCK_BOOTTIME: usize = 7;
+pub const CLOCK_REALTIME_ALARM: usize = 8;
+pub const CLOCK_BOOTTIME_ALARM: usize = 9;
+pub const CLOCK_TAI: usize = 11;
+
+/// 读取 clockid 对应时钟的当前值(纳秒)
+pub fn clock_now(clockid: usize) -> SysResult<usize> {
+    let nsec = match clockid {
+        CLOCK_REALTIME | CLOCK_REALTIME_ALARM => realtime_nsec(),
+        CLOCK_MONOTONIC | CLOCK_MONOTONIC_RAW => monotonic_nsec(),
+        CLOCK_BOOTTIME | CLOCK_BOOTTIME_ALARM => boottime_nsec(),
+        CLOCK_REALTIME_COARSE => coarse_nsec(realtime_nsec()),
+        CLOCK_MONOTONIC_COARSE => coarse_nsec(monotonic_nsec()),
+        CLOCK_TAI => realtime_nsec() + TAI_OFFSET_SEC * NSEC_PER_SEC,
+        CLOCK_PROCESS_CPUTIME_ID | CLOCK_THREAD_CPUTIME_ID => {
+            // 没有按任务统计 CPU 时间, 以任务创建以来经过的时间近似
+            let task = current_task().unwrap();
+            let start = task.inner_exclusive_access().tms.tms_cutime;
+            (Time::now().to_msec() - start) * 1_000_000
+        }
+        _ => return Err(SysError::EINVAL),
+    };
+    Ok(nsec)
+}
 
 pub fn sys_clock_gettime(clockid: usize, tp: *mut TimeSpec) -> SysResult<isize> {
     if tp.is_null() {
         return Err(SysError::EINVAL);
     }
+    let now = clock_now(clockid)?;
+    let token = current_user_token();
+    *translated_refmut(token, tp) = TimeSpec::from_nsec(now);
+    Ok(0)
+}
+
+pub fn sys_clock_settime(clockid: usize, tp: *const TimeSpec) -> SysResult<isize> {
+    if tp.is_null() {
+        return Err(SysError::EFAULT);
+    }
+    let token = current_user_token();
+    let tp = *translated_ref(token, tp);
+    if tp.usec >= NSEC_PER_SEC {
+        return Err(SysError::EINVAL);
+    }
     match clockid {
-        CLOCK_REALTIME | CLOCK_MONOTONIC => {
-            let token = current_user_token();
-            let tp_ref = translated_refmut(token, tp);
-            tp_ref.sec = Time::now().to_sec();
-            tp_ref.usec = Time::now().to_usec();
-        }
-        CLOCK_PROCESS_CPUTIME_ID | CLOCK_THREAD_CPUTIME_ID => {
-            panic!("CLOCK_PROCESS_CPUTIME_ID/CLOCK_THREAD_CPUTIME_ID unsupported!");
-        }
-        _ => {
-            //panic!("unsupported clock_id!");
-            return Ok(0);
+        CLOCK_REALTIME => {
+            check_privileged()?;
+            set_realtime_nsec(tp.to_usec());
+            crate::vdso::update();
+            Ok(0)
         }
+        _ => Err(SysError::EINVAL),
+    }
+}
+
+pub fn sys_clock_getres(clockid: usize, res: *mut TimeSpec) -> SysResult<isize> {
+    clock_now(clockid)?;
+    if res.is_null() {
+        return Ok(0);
     }
+    let nsec = match clockid {
+        CLOCK_REALTIME_COARSE | CLOCK_MONOTONIC_COARSE => TICK_NSEC,
+        _ => 1,
+    };
+    let token = current_user_token();
+    *translated_refmut(token, res) = TimeSpec::from_nsec(nsec);
     Ok(0)
 }
 
+/// struct timex, 只关心 modes 与 time 字段
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Timex {
+    pub modes: u32,
+    pub offset: isize,
+    pub freq: isize,
+    pub maxerror: isize,
+    pub esterror: isize,
+    pub status: i32,
+    pub constant: isize,
+    pub precision: isize,
+    pub tolerance: isize,
+    pub time: TimeVal,
+    pub tick: isize,
+    pub ppsfreq: isize,
+    pub jitter: isize,
+    pub shift: i32,
+    pub stabil: isize,
+    pub jitcnt: isize,
+    pub calcnt: isize,
+    pub errcnt: isize,
+    pub stbcnt: isize,
+    pub tai: i32,
+    pub __padding: [i32; 11],
+}
+
+const ADJ_SETOFFSET: u32 = 0x0100;
+const ADJ_NANO: u32 = 0x2000;
+const TIME_OK: isize = 0;
+
+pub fn sys_clock_adjtime(clockid: usize, buf: *mut Timex) -> SysResult<isize> {
+    if clockid != CLOCK_REALTIME {
+        return Err(SysError::EINVAL);
+    }
+    if buf.is_null() {
+        return Err(SysError::EFAULT);
+    }
+    let token = current_user_token();
+    let tx = translated_refmut(token, buf);
+    if tx.modes & ADJ_SETOFFSET != 0 {
+        check_privileged()?;
+        // time.usec 在 ADJ_NANO 下为纳秒, 否则为微秒
+        let sub = if tx.modes & ADJ_NANO != 0 { 1 } else { 1000 };
+        let delta = tx.time.sec as isize as i64 * NSEC_PER_SEC as i64
+            + tx.time.usec as i64 * sub;
+        adjust_realtime_nsec(delta);
+        crate::vdso::update();
+    }
+    let now = realtime_nsec();
+    tx.time = if tx.modes & ADJ_NANO != 0 {
+        TimeVal { sec: now / NSEC_PER_SEC, usec: now % NSEC_PER_SEC }
+    } else {
+        TimeVal::from_nsec(now)
+    };
+    tx.tick = (TICK_NSEC / 1000) as isize;
+    tx.tai = TAI_OFFSET_SEC as i32;
+    Ok(TIME_OK)
+}
+
+pub fn sys_adjtimex(buf: *mut Timex) -> SysResult<isize> {
+    sys_clock_adjtime(CLOCK_REALTIME, buf)
+}
+
 pub fn sys_exit_group(exit_code: i32) -> ! { //退出线程组，但没有子线程
     exit_current_and_run_next((exit_code & 0xFF) << 8);//posix标准退出码
     panic!("Unreachable in sys_exit!");
@@ -601,7 +878,12 @@ pub fn sys_kill(pid: usize, signal: usize) -> SysResult<isize> {
             println!("[kernel] sys_kill: Adding signal {} to pid {}", signal, pid);
             let mut inner = process.inner_exclusive_access();
             inner.signals |= flag;
+            let masked = inner.signal_mask.contains(flag);
             drop(inner);
+            // 打断阻塞中的 futex 等待和睡眠
+            if !masked {
+                wakeup_blocked_task(process);
+            }
             Ok(0)
         } else {
             println!("[kernel] sys_kill: Invalid signal {}", signal);
@@ -808,7 +1090,11 @@ pub fn sys_tgkill(tgid: isize, tid: isize, sig: usize) -> SysResult<isize> {
             code:SigInfo::TKILL,
             details: SigDetails::Kill { pid: task.getpid() },
         });
+        let masked = inner.signal_mask.contains(flag);
         drop(inner);
+        if !masked {
+            wakeup_blocked_task(task);
+        }
         Ok(0)
     } else {
     //    println!("[kernel] sys_tgkill: Invalid signal {}", sig);
@@ -847,7 +1133,11 @@ pub fn sys_tkill(tid: isize, sig: usize) -> SysResult<isize> {
             code:SigInfo::TKILL,
             details: SigDetails::Kill { pid: task.getpid() },
         });
+        let masked = inner.signal_mask.contains(flag);
         drop(inner);
+        if !masked {
+            wakeup_blocked_task(task);
+        }
         Ok(0)
     } else {
     //    println!("[kernel] sys_tgkill: Invalid signal {}", sig);
@@ -858,44 +1148,33 @@ pub fn sys_tkill(tid: isize, sig: usize) -> SysResult<isize> {
 pub fn sys_clock_nanosleep(clockid: usize,flags:usize,request:*const TimeSpec,remain:*mut TimeSpec)->SysResult<isize>{
     pub const TIMER_ABSTIME: usize = 1;
     match clockid {
-        CLOCK_REALTIME | CLOCK_MONOTONIC => {
+        CLOCK_REALTIME | CLOCK_MONOTONIC | CLOCK_BOOTTIME | CLOCK_TAI => {
             let token = current_user_token();
             let request = translated_ref(token, request);
+            if request.usec >= NSEC_PER_SEC {
+                return Err(SysError::EINVAL);
+            }
             let req= request.to_usec(); 
-            let total_time;
-            let mut rem:TimeSpec = TimeSpec{sec:0,usec:0};
-            if flags == TIMER_ABSTIME {
-                let current_time = Time::now().to_nsec();
+            // 统一换算成单调时钟上的截止时刻
+            let now = monotonic_nsec();
+            let deadline = if flags & TIMER_ABSTIME != 0 {
+                let current_time = clock_now(clockid)?;
                 // request time is absolutely
                 if req.le(&current_time) {
                     return Ok(0);
                 }
-                total_time = req - current_time;
+                now + (req - current_time)
             } else {
-                total_time = req;
+                now + req
             };
-            let start_time = Time::now().to_nsec();
-            loop{
-                let current_time = Time::now().to_nsec();
-                if current_time - start_time < total_time{
-                    rem.sec = (total_time - current_time + start_time) / 1000000000;
-                    rem.usec = (total_time - current_time + start_time) % 1000000000;
-                    suspend_current_and_run_next();
-                }
-                else{
-                    rem.sec = 0;
-                    rem.usec = 0;
-                    break;
-                }
+            if crate::timer::sleep_until(deadline) {
+                return Ok(0);
             }
-            if rem.sec == 0 &&rem.usec == 0 {
-                Ok(0)
-            } else {
-                if !remain.is_null() {
-                    *translated_refmut(token, remain) = rem;
-                }
-                Err(SysError::EINTR)
+            // 绝对时间的睡眠被打断时不回写剩余时间
+            if flags & TIMER_ABSTIME == 0 && !remain.is_null() {
+                *translated_refmut(token, remain) = TimeSpec::from_nsec(deadline.saturating_sub(monotonic_nsec()));
             }
+            Err(SysError::EINTR)
         }
         _ => {
             return Err(SysError::EINVAL);
@@ -904,13 +1183,17 @@ pub fn sys_clock_nanosleep(clockid: usize,flags:usize,request:*const TimeSpec,re
 }
 
 
-bitflags! {
-    pub struct FutexCmd:u32{
-        const FUTEX_WAIT = 0;
-        const FUTEX_WAKE = 1;
-        const FUTEX_REQUEUE = 3;
-    }
-}
+pub const FUTEX_WAIT: u32 = 0;
+pub const FUTEX_WAKE: u32 = 1;
+pub const FUTEX_REQUEUE: u32 = 3;
+pub const FUTEX_CMP_REQUEUE: u32 = 4;
+pub const FUTEX_WAKE_OP: u32 = 5;
+pub const FUTEX_LOCK_PI: u32 = 6;
+pub const FUTEX_UNLOCK_PI: u32 = 7;
+pub const FUTEX_TRYLOCK_PI: u32 = 8;
+pub const FUTEX_WAIT_BITSET: u32 = 9;
+pub const FUTEX_WAKE_BITSET: u32 = 10;
+pub const FUTEX_LOCK_PI2: u32 = 13;
 
 bitflags! {
     pub struct FutexOpt: u32 {
@@ -919,73 +1202,99 @@ bitflags! {
     }
 }
 
-
-pub fn sys_futex(uaddr1: *mut i32,futex_op: u32,val: i32,timeout: *const TimeSpec,uaddr2: *mut u32,_val3: i32,)->SysResult<isize>{
-    
-    let cmd = FutexCmd::from_bits_truncate(futex_op & 0x7f);
-    let opt = FutexOpt::from_bits_truncate(futex_op);
-    if uaddr1.align_offset(4) != 0 {
+/// 把 futex 的超时参数换算成单调时钟上的截止时刻
+fn futex_deadline(timeout: *const TimeSpec, relative: bool, realtime: bool) -> SysResult<Option<usize>> {
+    if timeout.is_null() {
+        return Ok(None);
+    }
+    let timeout = translated_ref(current_user_token(), timeout);
+    if timeout.usec >= NSEC_PER_SEC {
         return Err(SysError::EINVAL);
     }
-    let token = current_user_token();
-    let task = current_task().unwrap();
-  //  if task.gettid() != 2{
-  //      println!("uaddr:{:x} futexop:{} val:{} val3:{} tid:{} futexword:{}",uaddr1 as usize,futex_op,val,_val3,task.gettid(), *translated_refmut(token, uaddr1));
-  //  }
-    
-    let task_inner = task.inner_exclusive_access();
-    let pa = task_inner
-        .memory_set.lock().page_table.translate(VirtAddr::from(uaddr1 as usize))
-        .unwrap().0;
+    let now = monotonic_nsec();
+    if relative {
+        return Ok(Some(now + timeout.to_usec()));
+    }
+    let clock = if realtime { realtime_nsec() } else { now };
+    Ok(Some(now + timeout.to_usec().saturating_sub(clock)))
+}
 
+/// `timeout` 对 REQUEUE/CMP_REQUEUE/WAKE_OP 来说是第二个计数 val2
+pub fn sys_futex(uaddr1: *mut i32,futex_op: u32,val: i32,timeout: *const TimeSpec,uaddr2: *mut u32,val3: i32,)->SysResult<isize>{
+    let cmd = futex_op & 0x7f;
+    let opt = FutexOpt::from_bits_truncate(futex_op);
     let private = opt.contains(FutexOpt::FUTEX_PRIVATE_FLAG);
-    let key = if private {
-        FutexKey::new(pa, task.getpid())
-    } else {
-        FutexKey::new(pa, 0)
-    };
+    let realtime = opt.contains(FutexOpt::FUTEX_CLOCK_REALTIME);
+    if realtime && !matches!(cmd, FUTEX_WAIT | FUTEX_WAIT_BITSET | FUTEX_LOCK_PI2) {
+        return Err(SysError::ENOSYS);
+    }
+    let memory_set = current_task().unwrap().inner_exclusive_access().memory_set.clone();
+    let uaddr = uaddr1 as usize;
+    let word = futex_word(&memory_set, uaddr)?;
+    let key = FutexKey::new(&memory_set, uaddr, private)?;
+    let val2 = timeout as usize as u32 as i32;
     match cmd {
-        FutexCmd::FUTEX_WAIT => {
-            let futex_word = translated_refmut(token, uaddr1);
-            if *futex_word != val {
-                return Err(SysError::EAGAIN);
+        FUTEX_WAIT | FUTEX_WAIT_BITSET => {
+            let bitset = if cmd == FUTEX_WAIT { FUTEX_BITSET_MATCH_ANY } else { val3 as u32 };
+            if bitset == 0 {
+                return Err(SysError::EINVAL);
             }
-            if !timeout.is_null() {
-            //    println!("wait time out is not null");
-                let timeout = translated_ref(token, timeout);
-                let cur = Time::now().to_usec();
-                let tme = TimeSpec{
-                    sec:cur/1000_000_000 + timeout.sec,
-                    usec:cur%1000_000_000 + timeout.usec,
-                };
-                add_futex_timer(tme, current_task().unwrap());
+            // FUTEX_WAIT 的超时是相对值, WAIT_BITSET 是绝对值
+            let deadline = futex_deadline(timeout, cmd == FUTEX_WAIT, realtime)?;
+            futex_wait(key, word, val as u32, bitset, deadline)
+        }
+        FUTEX_WAKE | FUTEX_WAKE_BITSET => {
+            let bitset = if cmd == FUTEX_WAKE { FUTEX_BITSET_MATCH_ANY } else { val3 as u32 };
+            if bitset == 0 {
+                return Err(SysError::EINVAL);
             }
-            drop(task_inner);
-            drop(task);
-            futex_wait(key)
+            Ok(futex_wake(key, val.max(0) as usize, bitset) as isize)
         }
-        FutexCmd::FUTEX_WAKE => {
-            drop(task_inner);
-            drop(task);
-        //    *translated_refmut(token, uaddr1) = val;
-            Ok(futex_wake(key, val as usize) as isize)
+        FUTEX_REQUEUE | FUTEX_CMP_REQUEUE => {
+            if val < 0 || val2 < 0 {
+                return Err(SysError::EINVAL);
+            }
+            futex_word(&memory_set, uaddr2 as usize)?;
+            let new_key = FutexKey::new(&memory_set, uaddr2 as usize, private)?;
+            let cmp = (cmd == FUTEX_CMP_REQUEUE).then_some((word, val3 as u32));
+            Ok(futex_requeue(key, new_key, cmp, val as usize, val2 as usize)? as isize)
         }
-        FutexCmd::FUTEX_REQUEUE => {
-            let pa2 = task_inner
-                .memory_set.lock().page_table.translate(VirtAddr::from(uaddr2 as usize))
-                .ok_or(SysError::EINVAL)?;
-            let new_key = if private {
-                FutexKey::new(pa2.0, task.getpid())
-            } else {
-                FutexKey::new(pa2.0, 0)
-            };
-            drop(task_inner);
-            drop(task);
-            Ok(futex_requeue(key, val, new_key, timeout as i32) as isize)
+        FUTEX_WAKE_OP => {
+            let word2 = futex_word(&memory_set, uaddr2 as usize)?;
+            let key2 = FutexKey::new(&memory_set, uaddr2 as usize, private)?;
+            Ok(futex_wake_op(key, key2, word2, val.max(0) as usize, val2.max(0) as usize, val3 as u32)? as isize)
         }
-        _ => unimplemented!(),
+        FUTEX_LOCK_PI | FUTEX_LOCK_PI2 => {
+            // LOCK_PI 的超时是 CLOCK_REALTIME 绝对时间, LOCK_PI2 默认用 CLOCK_MONOTONIC
+            let deadline = futex_deadline(timeout, false, cmd == FUTEX_LOCK_PI || realtime)?;
+            futex_lock_pi(key, word, deadline, false)
+        }
+        FUTEX_TRYLOCK_PI => futex_lock_pi(key, word, None, true),
+        FUTEX_UNLOCK_PI => futex_unlock_pi(key, word),
+        // WAIT_REQUEUE_PI/CMP_REQUEUE_PI 新版 glibc 已不再使用
+        _ => Err(SysError::ENOSYS),
     }
+}
 
+pub fn sys_set_robust_list(head: usize, len: usize) -> SysResult<isize> {
+    if len != ROBUST_LIST_HEAD_SIZE {
+        return Err(SysError::EINVAL);
+    }
+    current_task().unwrap().inner_exclusive_access().robust_list = head;
+    Ok(0)
+}
+
+pub fn sys_get_robust_list(tid: usize, head_ptr: *mut usize, len_ptr: *mut usize) -> SysResult<isize> {
+    let task = if tid == 0 {
+        current_task().unwrap()
+    } else {
+        tid2task(tid).ok_or(SysError::ESRCH)?
+    };
+    let head = task.inner_exclusive_access().robust_list;
+    let token = current_user_token();
+    *translated_refmut(token, head_ptr) = head;
+    *translated_refmut(token, len_ptr) = ROBUST_LIST_HEAD_SIZE;
+    Ok(0)
 }
 
 #[allow(unused)]
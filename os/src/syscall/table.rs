@@ -0,0 +1,323 @@
+//! 系统调用表
+//!
+//! 每一项给出调用号, 名字以及一个把原始参数解码后调用 `sys_*` 实现的函数.
+//! 不在表中的调用号返回 ENOSYS.
+use super::fs::*;
+use super::nr::*;
+use super::process::*;
+use crate::fs::EpollEvent;
+use crate::task::{current_task, SignalFlags, SysInfo, TimeSpec, Tms, Utsname};
+use alloc::vec::Vec;
+use arch::addr::VirtAddr;
+use config::RLimit;
+use lazy_static::*;
+use system_result::{SysError, SysResult};
+use ::time::TimeVal;
+
+const MODULE_LEVEL:log::Level = log::Level::Debug;
+
+/// 系统调用表项
+pub struct SyscallEntry {
+    pub id: usize,
+    pub name: &'static str,
+    /// 解码参数并调用实现
+    pub handler: fn(&[usize; 6]) -> SysResult<isize>,
+}
+
+macro_rules! syscall_table {
+    ($($(#[$attr:meta])* $id:ident => $name:literal, $handler:expr;)*) => {
+        static SYSCALL_TABLE: &[SyscallEntry] = &[
+            $($(#[$attr])* SyscallEntry { id: $id, name: $name, handler: $handler },)*
+        ];
+    };
+}
+
+/// 尚未实现网络, 套接字相关调用假装成功
+fn socket_stub(_args: &[usize; 6]) -> SysResult<isize> {
+    Ok(0)
+}
+
+/// 没有用户与会话的概念, 直接返回成功
+fn noop(_args: &[usize; 6]) -> SysResult<isize> {
+    Ok(0)
+}
+
+syscall_table! {
+    SYSCALL_GETCWD => "getcwd", |args| sys_getcwd(args[0] as *mut u8, args[1]);
+    SYSCALL_DUP => "dup", |args| sys_dup(args[0]);
+    SYSCALL_DUP3 => "dup3", |args| sys_dup3(args[0], args[1], 0);
+    SYSCALL_FCNTL => "fcntl", |args| sys_fcntl(args[0] as isize, args[1] as isize, args[2]);
+    SYSCALL_TRUNCATE => "truncate", |args| sys_truncate(args[0] as *const u8, args[1] as isize);
+    SYSCALL_FTRUNCATE => "ftruncate", |args| sys_ftruncate(args[0] as isize, args[1] as isize);
+    SYSCALL_FALLOCATE => "fallocate", |args| sys_fallocate(args[0] as isize, args[1] as i32, args[2] as isize, args[3] as isize);
+    SYSCALL_SYNC => "sync", |_args| sys_sync();
+    SYSCALL_FSYNC => "fsync", |args| sys_fsync(args[0] as isize);
+    SYSCALL_FDATASYNC => "fdatasync", |args| sys_fdatasync(args[0] as isize);
+    SYSCALL_SYNCFS => "syncfs", |args| sys_syncfs(args[0] as isize);
+    SYSCALL_SYNC_FILE_RANGE => "sync_file_range", |args| sys_sync_file_range(args[0] as isize, args[1] as isize, args[2] as isize, args[3] as u32);
+    SYSCALL_FLOCK => "flock", |args| sys_flock(args[0], args[1] as u32);
+    SYSCALL_IOCTL => "ioctl", |args| sys_ioctl(args[0], args[1], args[2]);
+    SYSCALL_MKDIRAT => "mkdirat", |args| sys_mkdirat(args[0] as isize, args[1] as *const u8, args[2] as u32);
+    SYSCALL_UNLINKAT => "unlinkat", |args| sys_unlink(args[0] as isize, args[1] as *const u8, args[2] as u32);
+    SYSCALL_MKNODAT => "mknodat", |args| sys_mknodat(args[0] as isize, args[1] as *const u8, args[2] as u32, args[3] as u64);
+    SYSCALL_SYMLINKAT => "symlinkat", |args| sys_symlinkat(args[0] as *const u8, args[1] as isize, args[2] as *const u8);
+    SYSCALL_LINKAT => "linkat", |args| {
+        sys_link(args[0] as isize, args[1] as *const u8, args[2] as isize, args[3] as *const u8, args[4] as u32)
+    };
+    SYSCALL_UMOUNT => "umount2", |args| sys_umount(args[0] as *const u8, args[1] as u32);
+    SYSCALL_MOUNT => "mount", |args| {
+        sys_mount(args[0] as *const u8, args[1] as *const u8, args[2] as *const u8, args[3] as u32, args[4] as *const u8)
+    };
+    SYSCALL_STATFS => "statfs", |args| sys_statfs(args[0] as *const u8, args[1] as *mut vfs_defs::StatFs);
+    SYSCALL_FSTATFS => "fstatfs", |args| sys_fstatfs(args[0], args[1] as *mut vfs_defs::StatFs);
+    SYSCALL_SETXATTR => "setxattr", |args| sys_setxattr(args[0] as *const u8, args[1] as *const u8, args[2] as *const u8, args[3], args[4] as i32);
+    SYSCALL_LSETXATTR => "lsetxattr", |args| sys_lsetxattr(args[0] as *const u8, args[1] as *const u8, args[2] as *const u8, args[3], args[4] as i32);
+    SYSCALL_FSETXATTR => "fsetxattr", |args| sys_fsetxattr(args[0], args[1] as *const u8, args[2] as *const u8, args[3], args[4] as i32);
+    SYSCALL_GETXATTR => "getxattr", |args| sys_getxattr(args[0] as *const u8, args[1] as *const u8, args[2] as *mut u8, args[3]);
+    SYSCALL_LGETXATTR => "lgetxattr", |args| sys_lgetxattr(args[0] as *const u8, args[1] as *const u8, args[2] as *mut u8, args[3]);
+    SYSCALL_FGETXATTR => "fgetxattr", |args| sys_fgetxattr(args[0], args[1] as *const u8, args[2] as *mut u8, args[3]);
+    SYSCALL_LISTXATTR => "listxattr", |args| sys_listxattr(args[0] as *const u8, args[1] as *mut u8, args[2]);
+    SYSCALL_LLISTXATTR => "llistxattr", |args| sys_llistxattr(args[0] as *const u8, args[1] as *mut u8, args[2]);
+    SYSCALL_FLISTXATTR => "flistxattr", |args| sys_flistxattr(args[0], args[1] as *mut u8, args[2]);
+    SYSCALL_REMOVEXATTR => "removexattr", |args| sys_removexattr(args[0] as *const u8, args[1] as *const u8);
+    SYSCALL_LREMOVEXATTR => "lremovexattr", |args| sys_lremovexattr(args[0] as *const u8, args[1] as *const u8);
+    SYSCALL_FREMOVEXATTR => "fremovexattr", |args| sys_fremovexattr(args[0], args[1] as *const u8);
+    SYSCALL_FACCESSAT => "faccessat", |args| sys_faccessat(args[0] as isize, args[1] as *const u8, args[2], args[3] as i32);
+    SYSCALL_CHDIR => "chdir", |args| sys_chdir(args[0] as *const u8);
+    SYSCALL_CHROOT => "chroot", |args| sys_chroot(args[0] as *const u8);
+    SYSCALL_FCHMOD => "fchmod", |args| sys_fchmod(args[0], args[1] as u32);
+    SYSCALL_FCHMODAT => "fchmodat", |args| sys_fchmodat(args[0] as isize, args[1] as *const u8, args[2] as u32, args[3] as i32);
+    SYSCALL_FCHOWNAT => "fchownat", |args| {
+        sys_fchownat(args[0] as isize, args[1] as *const u8, args[2] as u32, args[3] as u32, args[4] as i32)
+    };
+    SYSCALL_FCHOWN => "fchown", |args| sys_fchown(args[0], args[1] as u32, args[2] as u32);
+    SYSCALL_OPENAT => "openat", |args| sys_openat(args[0] as isize, args[1] as *const u8, args[2] as u32, args[3] as u32);
+    SYSCALL_CLOSE => "close", |args| sys_close(args[0]);
+    SYSCALL_PIPE2 => "pipe2", |args| sys_pipe2(args[0] as *mut i32, args[1] as u32);
+    SYSCALL_GETDENTS64 => "getdents64", |args| sys_getdents(args[0], args[1] as *mut u8, args[2]);
+    SYSCALL_LSEEK => "lseek", |args| sys_lseek(args[0] as isize, args[1] as isize, args[2]);
+    SYSCALL_READ => "read", |args| sys_read(args[0], args[1] as *mut u8, args[2]);
+    SYSCALL_WRITE => "write", |args| sys_write(args[0], args[1] as *mut u8, args[2]);
+    SYSCALL_READV => "readv", |args| sys_readv(args[0] as isize, args[1] as *const IoVec, args[2]);
+    SYSCALL_WRITEV => "writev", |args| sys_writev(args[0] as isize, args[1] as *const IoVec, args[2]);
+    SYSCALL_PREAD64 => "pread64", |args| sys_pread64(args[0] as isize, args[1] as *mut u8, args[2], args[3] as isize);
+    SYSCALL_PWRITE64 => "pwrite64", |args| sys_pwrite64(args[0] as isize, args[1] as *const u8, args[2], args[3] as isize);
+    SYSCALL_PREADV => "preadv", |args| sys_preadv(args[0] as isize, args[1] as *const IoVec, args[2], args[3] as isize);
+    SYSCALL_PWRITEV => "pwritev", |args| sys_pwritev(args[0] as isize, args[1] as *const IoVec, args[2], args[3] as isize);
+    SYSCALL_PREADV2 => "preadv2", |args| sys_preadv2(args[0] as isize, args[1] as *const IoVec, args[2], args[3] as isize, args[5] as u32);
+    SYSCALL_PWRITEV2 => "pwritev2", |args| sys_pwritev2(args[0] as isize, args[1] as *const IoVec, args[2], args[3] as isize, args[5] as u32);
+    SYSCALL_SPLICE => "splice", |args| sys_splice(args[0] as isize, args[1] as *mut i64, args[2] as isize, args[3] as *mut i64, args[4], args[5] as u32);
+    SYSCALL_TEE => "tee", |args| sys_tee(args[0] as isize, args[1] as isize, args[2], args[3] as u32);
+    SYSCALL_VMSPLICE => "vmsplice", |args| sys_vmsplice(args[0] as isize, args[1] as *const IoVec, args[2], args[3] as u32);
+    SYSCALL_SENDFILE => "sendfile", |args| sys_sendfile(args[0] as isize, args[1] as isize, args[2] as *mut usize, args[3]);
+    SYSCALL_PPOLL => "ppoll", |args| sys_ppoll(args[0] as *mut PollFd, args[1], args[2] as *const TimeSpec, args[3] as *const SignalFlags, args[4]);
+    SYSCALL_PSELECT6 => "pselect6", |args| sys_pselect6(args[0], args[1] as *mut u64, args[2] as *mut u64, args[3] as *mut u64, args[4] as *mut TimeSpec, args[5] as *const SigSetArg);
+    SYSCALL_EPOLL_CREATE1 => "epoll_create1", |args| sys_epoll_create1(args[0] as u32);
+    SYSCALL_EPOLL_CTL => "epoll_ctl", |args| sys_epoll_ctl(args[0], args[1], args[2], args[3] as *const EpollEvent);
+    SYSCALL_EPOLL_PWAIT => "epoll_pwait", |args| sys_epoll_pwait(args[0], args[1] as *mut EpollEvent, args[2] as i32, args[3] as isize, args[4] as *const SignalFlags, args[5]);
+    SYSCALL_EPOLL_PWAIT2 => "epoll_pwait2", |args| sys_epoll_pwait2(args[0], args[1] as *mut EpollEvent, args[2] as i32, args[3] as *const TimeSpec, args[4] as *const SignalFlags, args[5]);
+    SYSCALL_EVENTFD2 => "eventfd2", |args| sys_eventfd2(args[0] as u32, args[1] as u32);
+    SYSCALL_SIGNALFD4 => "signalfd4", |args| sys_signalfd4(args[0] as isize, args[1] as *const SignalFlags, args[2], args[3] as u32);
+    SYSCALL_TIMERFD_CREATE => "timerfd_create", |args| sys_timerfd_create(args[0], args[1] as u32);
+    SYSCALL_TIMERFD_SETTIME => "timerfd_settime", |args| sys_timerfd_settime(args[0], args[1] as u32, args[2] as *const ITimerSpec, args[3] as *mut ITimerSpec);
+    SYSCALL_TIMERFD_GETTIME => "timerfd_gettime", |args| sys_timerfd_gettime(args[0], args[1] as *mut ITimerSpec);
+    SYSCALL_MEMFD_CREATE => "memfd_create", |args| sys_memfd_create(args[0] as *const u8, args[1] as u32);
+    SYSCALL_READLINKAT => "readlinkat", |args| {
+        sys_readlinkat(args[0] as isize, args[1] as *const u8, args[2] as *mut u8, args[3] as isize)
+    };
+    SYSCALL_FSTATAT => "newfstatat", |args| {
+        sys_fstatat(args[0], args[1] as *const u8, args[2] as *mut vfs_defs::Kstat, args[3] as i32)
+    };
+    SYSCALL_FSTAT => "fstat", |args| sys_fstat(args[0], args[1] as *mut vfs_defs::Kstat);
+    SYSCALL_UTIMENSAT => "utimensat", |args| {
+        sys_utimensat(args[0] as isize, args[1] as *const u8, args[2] as *const TimeSpec, args[3] as i32)
+    };
+    SYSCALL_EXIT => "exit", |args| {
+        log_debug!("syscall_exit exit code:{} tid:{}", args[0], current_task().unwrap().gettid());
+        sys_exit(args[0] as i32)
+    };
+    SYSCALL_EXIT_GROUP => "exit_group", |args| {
+        log_debug!("syscall_exit exit code:{} tid:{}", args[0], current_task().unwrap().gettid());
+        sys_exit_group(args[0] as i32)
+    };
+    SYSCALL_SET_TID_ADDRESS => "set_tid_address", |args| sys_set_tid_address(args[0]);
+    SYSCALL_FUTEX => "futex", |args| {
+        sys_futex(args[0] as *mut i32, args[1] as u32, args[2] as i32, args[3] as *const TimeSpec, args[4] as *mut u32, args[5] as i32)
+    };
+    SYSCALL_SET_ROBUST_LIST => "set_robust_list", |args| sys_set_robust_list(args[0], args[1]);
+    SYSCALL_GET_ROBUST_LIST => "get_robust_list", |args| {
+        sys_get_robust_list(args[0], args[1] as *mut usize, args[2] as *mut usize)
+    };
+    SYSCALL_NANOSLEEP => "nanosleep", |args| sys_nanosleep(args[0] as *const TimeSpec, args[1] as *mut TimeSpec);
+    SYSCALL_CLOCK_SETTIME => "clock_settime", |args| sys_clock_settime(args[0], args[1] as *const TimeSpec);
+    SYSCALL_CLOCK_GETTIME => "clock_gettime", |args| sys_clock_gettime(args[0], args[1] as *mut TimeSpec);
+    SYSCALL_CLOCK_GETRES => "clock_getres", |args| sys_clock_getres(args[0], args[1] as *mut TimeSpec);
+    SYSCALL_CLOCK_NANOSLEEP => "clock_nanosleep", |args| {
+        sys_clock_nanosleep(args[0], args[1], args[2] as *const TimeSpec, args[3] as *mut TimeSpec)
+    };
+    SYSCALL_SYSLOG => "syslog", |args| sys_log(args[0], args[1] as *mut u8, args[2]);
+    SYSCALL_YIELD => "sched_yield", |_args| sys_yield();
+    SYSCALL_KILL => "kill", |args| {
+        log_debug!("syscall_kill pid={} signal={}", args[0], args[1]);
+        sys_kill(args[0], args[1])
+    };
+    SYSCALL_TKILL => "tkill", |args| sys_tkill(args[0] as isize, args[1]);
+    SYSCALL_TGKILL => "tgkill", |args| sys_tgkill(args[0] as isize, args[1] as isize, args[2]);
+    SYSCALL_SIGACTION => "rt_sigaction", |args| sys_sigaction(args[0], args[1] as *const _, args[2] as *mut _);
+    SYSCALL_SIGPROCMASK => "rt_sigprocmask", |args| sys_sigprocmask(args[0] as i32, args[1] as *const _, args[2] as *mut _);
+    SYSCALL_RT_SIGTIMEDWAIT => "rt_sigtimedwait", noop;
+    SYSCALL_SIGRETURN => "rt_sigreturn", |_args| sys_sigreturn();
+    SYSCALL_SETGID => "setgid", |args| sys_setgid(args[0] as u32);
+    SYSCALL_SETUID => "setuid", |args| sys_setuid(args[0] as u32);
+    SYSCALL_SETRESUID => "setresuid", |args| sys_setresuid(args[0] as u32, args[1] as u32, args[2] as u32);
+    SYSCALL_GETRESUID => "getresuid", |args| sys_getresuid(args[0] as *mut u32, args[1] as *mut u32, args[2] as *mut u32);
+    SYSCALL_SETRESGID => "setresgid", |args| sys_setresgid(args[0] as u32, args[1] as u32, args[2] as u32);
+    SYSCALL_GETRESGID => "getresgid", |args| sys_getresgid(args[0] as *mut u32, args[1] as *mut u32, args[2] as *mut u32);
+    SYSCALL_GETGROUPS => "getgroups", |args| sys_getgroups(args[0] as i32, args[1] as *mut u32);
+    SYSCALL_SETGROUPS => "setgroups", |args| sys_setgroups(args[0], args[1] as *const u32);
+    SYSCALL_UMASK => "umask", |args| sys_umask(args[0] as u32);
+    SYSCALL_TIMES => "times", |args| sys_times(args[0] as *mut Tms);
+    SYSCALL_SETPGID => "setpgid", noop;
+    SYSCALL_GETPGID => "getpgid", noop;
+    SYSCALL_SETSID => "setsid", noop;
+    SYSCALL_UNAME => "uname", |args| sys_uname(args[0] as *mut Utsname);
+    SYSCALL_GETTIMEOFDAY => "gettimeofday", |args| sys_gettimeofday(args[0] as *mut TimeVal);
+    SYSCALL_SETTIMEOFDAY => "settimeofday", |args| sys_settimeofday(args[0] as *const TimeVal);
+    SYSCALL_ADJTIMEX => "adjtimex", |args| sys_adjtimex(args[0] as *mut Timex);
+    SYSCALL_GETPID => "getpid", |_args| sys_getpid();
+    SYSCALL_GETPPID => "getppid", |_args| sys_getppid();
+    SYSCALL_GETUID => "getuid", |_args| sys_getuid();
+    SYSCALL_GETEUID => "geteuid", |_args| sys_geteuid();
+    SYSCALL_GETGID => "getgid", |_args| sys_getgid();
+    SYSCALL_GETEGID => "getegid", |_args| sys_getegid();
+    SYSCALL_GETTID => "gettid", |_args| sys_gettid();
+    SYSCALL_SYSINFO => "sysinfo", |args| sys_info(args[0] as *mut SysInfo);
+    SYSCALL_SOCKET => "socket", socket_stub;
+    SYSCALL_SOCKETPAIR => "socketpair", socket_stub;
+    SYSCALL_BIND => "bind", socket_stub;
+    SYSCALL_LISTEN => "listen", socket_stub;
+    SYSCALL_ACCEPT => "accept", socket_stub;
+    SYSCALL_CONNECT => "connect", socket_stub;
+    SYSCALL_GETSOCKNAME => "getsockname", socket_stub;
+    SYSCALL_GETPEERNAME => "getpeername", socket_stub;
+    SYSCALL_SENDTO => "sendto", socket_stub;
+    SYSCALL_RECVFROM => "recvfrom", socket_stub;
+    SYSCALL_SETSOCKOPT => "setsockopt", socket_stub;
+    SYSCALL_SENDMSG => "sendmsg", socket_stub;
+    SYSCALL_BRK => "brk", |args| sys_brk(args[0]);
+    SYSCALL_MUNMAP => "munmap", |args| sys_munmap(args[0] as *mut usize, args[1]);
+    SYSCALL_MREMAP => "mremap", |_args| Err(SysError::EPERM);
+    // x86_64 的 clone 把 tls 放在最后
+    #[cfg(not(target_arch = "x86_64"))]
+    SYSCALL_CLONE => "clone", |args| {
+        sys_clone(args[0], args[1] as *const u8, args[2] as *mut i32, args[3] as *mut i32, args[4] as *mut i32)
+    };
+    #[cfg(target_arch = "x86_64")]
+    SYSCALL_CLONE => "clone", |args| {
+        sys_clone(args[0], args[1] as *const u8, args[2] as *mut i32, args[4] as *mut i32, args[3] as *mut i32)
+    };
+    SYSCALL_EXEC => "execve", |args| sys_exec(args[0] as *const u8, args[1] as *const usize);
+    SYSCALL_MMAP => "mmap", |args| {
+        sys_mmap(args[0] as *mut usize, args[1], args[2] as i32, args[3] as i32, args[4], args[5] as i32)
+    };
+    SYSCALL_MPROTECT => "mprotect", |args| sys_mprotect(VirtAddr::new(args[0]), args[1], args[2] as i32);
+    SYSCALL_MADVISE => "madvise", noop;
+    SYSCALL_WAITPID => "wait4", |args| sys_waitpid(args[0] as isize, args[1] as *mut i32);
+    SYSCALL_PRLIMIT64 => "prlimit64", |args| {
+        sys_prlimit64(args[0], args[1] as i32, args[2] as *const RLimit, args[3] as *mut RLimit)
+    };
+    SYSCALL_CLOCK_ADJTIME => "clock_adjtime", |args| sys_clock_adjtime(args[0], args[1] as *mut Timex);
+    SYSCALL_RENAMEAT2 => "renameat2", |args| {
+        sys_renameat2(args[0] as isize, args[1] as *const u8, args[2] as isize, args[3] as *const u8, args[4])
+    };
+    SYSCALL_GET_RANDOM => "getrandom", |args| sys_get_random(args[0] as *mut u8, args[1], args[2]);
+    SYSCALL_STATX => "statx", |args| {
+        sys_statx(args[0] as isize, args[1] as *const u8, args[2] as i32, args[3] as u32, args[4] as *mut Statx)
+    };
+    SYSCALL_CLONE3 => "clone3", |args| sys_clone3(args[0] as *const Clone3Args);
+}
+
+lazy_static! {
+    /// 按调用号索引的表
+    static ref SYSCALL_INDEX: Vec<Option<&'static SyscallEntry>> = {
+        let max = SYSCALL_TABLE.iter().map(|entry| entry.id).max().unwrap_or(0);
+        let mut index = alloc::vec![None; max + 1];
+        for entry in SYSCALL_TABLE {
+            index[entry.id] = Some(entry);
+        }
+        index
+    };
+}
+
+/// 查找调用号对应的表项
+pub fn lookup(syscall_id: usize) -> Option<&'static SyscallEntry> {
+    SYSCALL_INDEX.get(syscall_id).copied().flatten()
+}
+
+/// 调用号对应的名字, 未知调用号返回 "unknown"
+pub fn syscall_name(syscall_id: usize) -> &'static str {
+    lookup(syscall_id).map_or("unknown", |entry| entry.name)
+}
+
+/// 执行一个系统调用, 未实现的返回 ENOSYS
+pub fn dispatch(syscall_id: usize, args: &[usize; 6]) -> SysResult<isize> {
+    let Some(entry) = lookup(syscall_id) else {
+        log_warn!("unsupported syscall {}", syscall_id);
+        return Err(SysError::ENOSYS);
+    };
+    #[cfg(feature = "syscall-stats")]
+    let start = ::time::monotonic_nsec();
+    let result = (entry.handler)(args);
+    #[cfg(feature = "syscall-stats")]
+    stats::record(entry.id, ::time::monotonic_nsec() - start);
+    result
+}
+
+/// 每个系统调用的次数与累计耗时, 通过 /proc/syscalls 导出.
+/// 阻塞的调用会把睡眠时间也算进去.
+#[cfg(feature = "syscall-stats")]
+pub mod stats {
+    use super::SYSCALL_TABLE;
+    use alloc::string::String;
+    use core::fmt::Write;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    /// 覆盖所有架构的调用号
+    const MAX_SYSCALL: usize = 512;
+
+    struct SyscallStat {
+        count: AtomicUsize,
+        nsec: AtomicUsize,
+    }
+
+    #[allow(clippy::declare_interior_mutable_const)]
+    const STAT_INIT: SyscallStat = SyscallStat {
+        count: AtomicUsize::new(0),
+        nsec: AtomicUsize::new(0),
+    };
+
+    static STATS: [SyscallStat; MAX_SYSCALL] = [STAT_INIT; MAX_SYSCALL];
+
+    pub fn record(syscall_id: usize, nsec: usize) {
+        if let Some(stat) = STATS.get(syscall_id) {
+            stat.count.fetch_add(1, Ordering::Relaxed);
+            stat.nsec.fetch_add(nsec, Ordering::Relaxed);
+        }
+    }
+
+    /// /proc/syscalls 的内容: 调用号 名字 次数 累计纳秒
+    pub fn serialize() -> String {
+        let mut res = String::new();
+        for entry in SYSCALL_TABLE {
+            let Some(stat) = STATS.get(entry.id) else {
+                continue;
+            };
+            let count = stat.count.load(Ordering::Relaxed);
+            if count == 0 {
+                continue;
+            }
+            let _ = writeln!(res, "{:<4} {:<20} {:>10} {:>16}", entry.id, entry.name, count, stat.nsec.load(Ordering::Relaxed));
+        }
+        res
+    }
+}
@@ -12,6 +12,8 @@ pub const AT_PAGESZ: usize = 6;
 pub const AT_BASE: usize = 7;
 ///
 pub const AT_RANDOM: usize = 25;
+///
+pub const AT_SYSINFO_EHDR: usize = 33;
 
 
 #[derive(Copy, Clone)]
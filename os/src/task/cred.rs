@@ -0,0 +1,116 @@
+//! 任务的身份凭据
+//!
+//! 规则与 Linux 相同: euid 为 0 视为拥有 CAP_SETUID/CAP_SETGID,
+//! fsuid/fsgid 跟随 euid/egid 变化, 文件系统访问使用它们.
+use alloc::vec::Vec;
+use system_result::{SysError, SysResult};
+use vfs::FsCred;
+
+/// 附加组的最大数量
+pub const NGROUPS_MAX: usize = 65536;
+
+#[derive(Clone, Debug, Default)]
+pub struct Cred {
+    pub ruid: u32,
+    pub euid: u32,
+    pub suid: u32,
+    pub fsuid: u32,
+    pub rgid: u32,
+    pub egid: u32,
+    pub sgid: u32,
+    pub fsgid: u32,
+    /// 附加组
+    pub groups: Vec<u32>,
+}
+
+impl Cred {
+    /// init 进程以 root 身份运行
+    pub fn root() -> Self {
+        Self::default()
+    }
+    /// 是否可以任意修改 uid/gid
+    pub fn privileged(&self) -> bool {
+        self.euid == 0
+    }
+    /// 文件系统访问使用的身份
+    pub fn fs_cred(&self) -> FsCred {
+        FsCred {
+            uid: self.fsuid,
+            gid: self.fsgid,
+            groups: self.groups.clone(),
+        }
+    }
+    /// access(2) 用实际用户而不是有效用户做检查
+    pub fn real_fs_cred(&self) -> FsCred {
+        FsCred {
+            uid: self.ruid,
+            gid: self.rgid,
+            groups: self.groups.clone(),
+        }
+    }
+    pub fn setuid(&mut self, uid: u32) -> SysResult<()> {
+        if self.privileged() {
+            self.ruid = uid;
+            self.suid = uid;
+        } else if uid != self.ruid && uid != self.suid {
+            return Err(SysError::EPERM);
+        }
+        self.euid = uid;
+        self.fsuid = uid;
+        Ok(())
+    }
+    pub fn setgid(&mut self, gid: u32) -> SysResult<()> {
+        if self.privileged() {
+            self.rgid = gid;
+            self.sgid = gid;
+        } else if gid != self.rgid && gid != self.sgid {
+            return Err(SysError::EPERM);
+        }
+        self.egid = gid;
+        self.fsgid = gid;
+        Ok(())
+    }
+    /// `None` 表示不修改. 没有特权时新值只能取自当前的 ruid/euid/suid
+    pub fn setresuid(&mut self, ruid: Option<u32>, euid: Option<u32>, suid: Option<u32>) -> SysResult<()> {
+        let current = [self.ruid, self.euid, self.suid];
+        if !self.privileged() && [ruid, euid, suid].iter().flatten().any(|id| !current.contains(id)) {
+            return Err(SysError::EPERM);
+        }
+        self.ruid = ruid.unwrap_or(self.ruid);
+        self.euid = euid.unwrap_or(self.euid);
+        self.suid = suid.unwrap_or(self.suid);
+        self.fsuid = self.euid;
+        Ok(())
+    }
+    pub fn setresgid(&mut self, rgid: Option<u32>, egid: Option<u32>, sgid: Option<u32>) -> SysResult<()> {
+        let current = [self.rgid, self.egid, self.sgid];
+        if !self.privileged() && [rgid, egid, sgid].iter().flatten().any(|id| !current.contains(id)) {
+            return Err(SysError::EPERM);
+        }
+        self.rgid = rgid.unwrap_or(self.rgid);
+        self.egid = egid.unwrap_or(self.egid);
+        self.sgid = sgid.unwrap_or(self.sgid);
+        self.fsgid = self.egid;
+        Ok(())
+    }
+    pub fn setgroups(&mut self, groups: Vec<u32>) -> SysResult<()> {
+        if !self.privileged() {
+            return Err(SysError::EPERM);
+        }
+        self.groups = groups;
+        Ok(())
+    }
+    /// execve 时按文件的 setuid/setgid 位切换有效身份, 然后保存到 suid/sgid
+    pub fn exec_transition(&mut self, uid: Option<u32>, gid: Option<u32>) {
+        if let Some(uid) = uid {
+            self.euid = uid;
+        }
+        if let Some(gid) = gid {
+            self.egid = gid;
+        }
+        self.suid = self.euid;
+        self.sgid = self.egid;
+        self.fsuid = self.euid;
+        self.fsgid = self.egid;
+    }
+}
@@ -46,6 +46,14 @@ impl Fd{
         self.flags = flags;
     }
 }
+impl Drop for Fd{
+    fn drop(&mut self){
+        // 最后一个描述符关闭时释放打开文件上的 flock/OFD 锁
+        if Arc::strong_count(&self.file) == 1 {
+            crate::fs::release_file_locks(&self.file());
+        }
+    }
+}
 
 pub struct FdTable{
     pub fd_table: Vec<Option<Fd>>,
@@ -158,9 +166,10 @@ impl FdTable{
         }
         return Err(SysError::ENFILE);
     }
-    pub fn dup3(&mut self,old: usize, new: usize, _flags: usize)->SysResult<isize>{
+    /// 返回被顶替掉的 `new` 原来的描述符, 由调用者释放它的 POSIX 锁
+    pub fn dup3(&mut self,old: usize, new: usize, _flags: usize)->SysResult<Option<Fd>>{
         if old == new {
-            return Ok(new as isize);
+            return Ok(None);
         }
     
         // 检查文件描述符的有效性
@@ -170,6 +179,7 @@ impl FdTable{
         // 获取要复制的文件对象
         if let Some(file) = self.fd_table[old].clone() { // 使用 clone 提前获取文件对象
             
+            let mut replaced = None;
             if new >= self.fd_table.len() {
                 let cnt = new - self.fd_table.len() + 1;
                 for _ in 0..cnt {
@@ -182,14 +192,13 @@ impl FdTable{
             else if self.fd_table[new].is_some() {
                 // new位置有效，需要关闭文件
                 //sys_close(new); 被锁阻塞
-                self.fd_table[new].take();
+                replaced = self.fd_table[new].take();
             }
     
             // 复制文件对象的引用到新的位置
             self.fd_table[new] = Some(file);
     
-            // 返回新的文件描述符
-            Ok(new as isize)
+            Ok(replaced)
         } else {
             return Err(SysError::EBADF);
         }
@@ -223,6 +232,18 @@ impl FdTable{
             Ok(())
         }
     }
+    /// execve 时关闭带 CLOEXEC 的描述符, 返回关掉的描述符, 由调用者释放它们的 POSIX 锁
+    pub fn close_on_exec(&mut self) -> Vec<Fd> {
+        self.fd_table
+            .iter_mut()
+            .filter(|fd| fd.as_ref().is_some_and(|fd| fd.flags.contains(FdFlags::CLOEXEC)))
+            .filter_map(|fd| fd.take())
+            .collect()
+    }
+    /// 关闭所有描述符, 进程退出时调用
+    pub fn close_all(&mut self) {
+        self.fd_table.clear();
+    }
     pub fn rlimit(&self) -> RLimit {
         self.fd_table_rlimit
     }
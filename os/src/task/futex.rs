@@ -1,114 +1,415 @@
-use arch::addr::PhysAddr;
+//! futex
+//!
+//! 键的选取:
+//! - 带 FUTEX_PRIVATE_FLAG, 或者地址落在私有映射上: (地址空间, 虚拟地址)
+//! - 共享文件映射: (inode, 文件内偏移)
+//! - 共享匿名映射: 物理地址
+//!
+//! PI futex 的字里保存持有者 tid. 等待者阻塞前让持有者先运行(调度器没有优先级),
+//! 解锁时内核直接把锁交给最早的等待者.
+use arch::addr::VirtAddr;
+use arch::pagetable::MappingFlags;
+use arch::{TrapType, PAGE_SIZE};
 use alloc::{
     collections::{BTreeMap, VecDeque},
     sync::{Arc, Weak},
 };
-use system_result::SysResult;
-use super::{TaskControlBlock,block_current_and_run_next,current_task,wakeup_task};
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use system_result::{SysError,SysResult};
+use super::{TaskControlBlock,block_current_and_run_next,current_task,wakeup_blocked_task,boost_task,tid2task};
+use crate::mm::{MemorySet, MmapFlags};
+use crate::timer::{add_timer, cancel_timer, TimerEvent};
 use lazy_static::*;
 use sync::Mutex;
+use time::monotonic_nsec;
+
+/// 有线程在等待这把 PI/robust 锁
+pub const FUTEX_WAITERS: u32 = 0x8000_0000;
+/// 持有者退出时没有释放锁
+pub const FUTEX_OWNER_DIED: u32 = 0x4000_0000;
+pub const FUTEX_TID_MASK: u32 = 0x3fff_ffff;
+pub const FUTEX_BITSET_MATCH_ANY: u32 = u32::MAX;
+/// struct robust_list_head 的大小
+pub const ROBUST_LIST_HEAD_SIZE: usize = 3 * core::mem::size_of::<usize>();
+/// 遍历 robust list 的上限, 防止用户构造环
+const ROBUST_LIST_LIMIT: usize = 2048;
 
 lazy_static!{
-    pub static ref FUTEX_Q:Mutex<BTreeMap<FutexKey,FutexBucket>> = 
+    static ref FUTEX_Q:Mutex<BTreeMap<FutexKey,FutexBucket>> =
         Mutex::new(BTreeMap::new());
 }
 
-type FutexBucket = VecDeque<Weak<TaskControlBlock>>;
+type FutexBucket = VecDeque<Arc<FutexWaiter>>;
+
+struct FutexWaiter {
+    task: Weak<TaskControlBlock>,
+    tid: usize,
+    bitset: u32,
+    /// 当前所在的队列, requeue 时会改变
+    key: Mutex<FutexKey>,
+    /// 被 futex 操作唤醒(PI 锁则表示已经转交给它)
+    woken: AtomicBool,
+}
+
+impl FutexWaiter {
+    fn new(task: &Arc<TaskControlBlock>, key: FutexKey, bitset: u32) -> Arc<Self> {
+        Arc::new(Self {
+            task: Arc::downgrade(task),
+            tid: task.gettid(),
+            bitset,
+            key: Mutex::new(key),
+            woken: AtomicBool::new(false),
+        })
+    }
+
+    fn wake(&self) {
+        self.woken.store(true, Ordering::SeqCst);
+        if let Some(task) = self.task.upgrade() {
+            wakeup_blocked_task(task);
+        }
+    }
+}
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
-pub struct FutexKey{
-    paddr:PhysAddr,
-    pid:usize
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FutexKey{
+    Private { mm: usize, vaddr: usize },
+    File { inode: usize, offset: usize },
+    Shared { paddr: usize },
 }
 
 impl FutexKey{
-    pub fn new(paddr:PhysAddr,pid:usize)->Self{
-        Self{
-            paddr,pid
+    /// 计算 `uaddr` 处 futex 的键, 调用前需保证该页已经映射
+    pub fn new(memory_set: &Arc<Mutex<MemorySet>>, uaddr: usize, private: bool) -> SysResult<Self> {
+        let mm = Arc::as_ptr(memory_set) as usize;
+        if private {
+            return Ok(Self::Private { mm, vaddr: uaddr });
+        }
+        let memory_set = memory_set.lock();
+        let area = memory_set
+            .areas
+            .iter()
+            .find(|area| area.vpn_range.get_start().to_addr() <= uaddr && area.vpn_range.get_end().to_addr() > uaddr)
+            .ok_or(SysError::EFAULT)?;
+        if !area.mmap_flag.contains(MmapFlags::MAP_SHARED) {
+            return Ok(Self::Private { mm, vaddr: uaddr });
+        }
+        if let Some(file) = &area.map_file {
+            let inode = file.get_dentry().get_inode()?;
+            return Ok(Self::File {
+                inode: Arc::as_ptr(&inode) as *const () as usize,
+                offset: area.map_file_offset + uaddr - area.vpn_range.get_start().to_addr(),
+            });
+        }
+        match memory_set.page_table.translate(VirtAddr::from(uaddr)) {
+            Some((pa, _)) if pa.addr() != 0 => Ok(Self::Shared { paddr: pa.addr() }),
+            _ => Err(SysError::EFAULT),
         }
     }
 }
 
-pub fn futex_wait(futexkey:FutexKey)->SysResult<isize>{
-    let mut futex_q = FUTEX_Q.lock();
-    let task = current_task().unwrap();
-  //  println!("cur futex wait {} paddr:{:x} keypid:{}",task.getpid(),futexkey.paddr.addr(),futexkey.pid);
-    if let Some(bucket) = futex_q.get_mut(&futexkey) {
-        bucket.push_back(Arc::downgrade(&task));
-    } else {
-        futex_q.insert(futexkey, {
-            let mut bucket = VecDeque::new();
-            bucket.push_back(Arc::downgrade(&task));
-            bucket
-        });
+/// 取得用户地址上的对象, 缺页和写时复制先处理掉, 地址无效时返回 EFAULT
+fn user_ref<T>(memory_set: &Arc<Mutex<MemorySet>>, uaddr: usize) -> SysResult<&'static T> {
+    if uaddr == 0 || uaddr % core::mem::align_of::<T>() != 0 {
+        return Err(SysError::EINVAL);
+    }
+    // 不允许跨页
+    if uaddr / PAGE_SIZE != (uaddr + core::mem::size_of::<T>() - 1) / PAGE_SIZE {
+        return Err(SysError::EINVAL);
+    }
+    let mut memory_set = memory_set.lock();
+    match memory_set.page_table.translate(VirtAddr::from(uaddr)) {
+        Some((pa, flags)) if pa.addr() != 0 && flags.contains(MappingFlags::P) => {
+            if flags.contains(MappingFlags::cow) {
+                memory_set.handle_cow_addr(uaddr).map_err(|_| SysError::EFAULT)?;
+            }
+        }
+        _ => {
+            memory_set
+                .handle_lazy_addr(uaddr, TrapType::StorePageFault(uaddr))
+                .map_err(|_| SysError::EFAULT)?;
+        }
     }
+    Ok(unsafe { &*(uaddr as *const T) })
+}
+
+/// futex 字, 必须 4 字节对齐
+pub fn futex_word(memory_set: &Arc<Mutex<MemorySet>>, uaddr: usize) -> SysResult<&'static AtomicU32> {
+    user_ref(memory_set, uaddr)
+}
+
+/// 阻塞直到被唤醒, 超时或被信号打断. 返回时保证已离开等待队列
+fn sleep_on(task: Arc<TaskControlBlock>, waiter: &Arc<FutexWaiter>, deadline: Option<usize>) -> SysResult<isize> {
+    let timer = deadline.map(|deadline| add_timer(deadline, TimerEvent::Wake(Arc::downgrade(&task))));
     drop(task);
-    drop(futex_q);
     block_current_and_run_next();
-   // let task = current_task().unwrap();
-  //  let inner = task.inner_exclusive_access();
-   /*// woke by signal
-    if !inner
-        .signals.
-        .difference(task_inner.sig_mask)
-        .is_empty()
-    {cur futex wait 3 paddr:834d9368 keypid:3
-    cur futex wait 4 paddr:834d9368 keypid:4
-        return Err(SysErrNo::EINTR);
-    } */ 
-    Ok(0)
+    if let Some(timer) = timer {
+        cancel_timer(timer);
+    }
+    if waiter.woken.load(Ordering::SeqCst) {
+        return Ok(0);
+    }
+    let mut futex_q = FUTEX_Q.lock();
+    let key = *waiter.key.lock();
+    if let Some(bucket) = futex_q.get_mut(&key) {
+        bucket.retain(|w| !Arc::ptr_eq(w, waiter));
+        if bucket.is_empty() {
+            futex_q.remove(&key);
+        }
+    }
+    match deadline {
+        Some(deadline) if monotonic_nsec() >= deadline => Err(SysError::ETIMEDOUT),
+        _ => Err(SysError::EINTR),
+    }
+}
 
+/// futex 字仍等于 `val` 时在 `key` 上等待. `deadline` 为单调时钟纳秒
+pub fn futex_wait(key:FutexKey,word:&AtomicU32,val:u32,bitset:u32,deadline:Option<usize>)->SysResult<isize>{
+    let mut futex_q = FUTEX_Q.lock();
+    if word.load(Ordering::SeqCst) != val {
+        return Err(SysError::EAGAIN);
+    }
+    let task = current_task().unwrap();
+    let waiter = FutexWaiter::new(&task, key, bitset);
+    futex_q.entry(key).or_default().push_back(waiter.clone());
+    drop(futex_q);
+    sleep_on(task, &waiter, deadline)
 }
 
-pub fn futex_wake(futexkey:FutexKey,max_size:usize)->usize{
-    
+/// 唤醒 `key` 上最多 `max_size` 个与 `bitset` 相交的等待者
+pub fn futex_wake(key:FutexKey,max_size:usize,bitset:u32)->usize{
     let mut futex_q = FUTEX_Q.lock();
+    let Some(bucket) = futex_q.get_mut(&key) else {
+        return 0;
+    };
     let mut num = 0;
-    if let Some(queue) = futex_q.get_mut(&futexkey) {
-        loop {
-            if num >= max_size as usize {
-                break;
-            }
-            if let Some(weak_task) = queue.pop_front() {
-                if let Some(task) = weak_task.upgrade() {
-                    //debug!("wake up task {}", task.pid());
-                    wakeup_task(task);
-                    num += 1;
-                }
-            } else {
-                break;
-            }
+    bucket.retain(|waiter| {
+        if num < max_size && waiter.bitset & bitset != 0 {
+            waiter.wake();
+            num += 1;
+            false
+        } else {
+            true
         }
-    }//println!("futex wake paddr:{:x} keypid:{} waked:{}",futexkey.paddr.addr(),futexkey.pid,num); //futex wake paddr:8341e550 keypid:0
+    });
+    if bucket.is_empty() {
+        futex_q.remove(&key);
+    }
     num
 }
 
-pub fn futex_requeue(old_key: FutexKey, max_num: i32, new_key: FutexKey, max_num2: i32)->usize{
+/// 唤醒 `key` 上最多 `max_wake` 个等待者, 再把最多 `max_requeue` 个挪到 `new_key`.
+/// 给出 `cmp` 时先检查 futex 字. 返回两者之和
+pub fn futex_requeue(
+    key: FutexKey,
+    new_key: FutexKey,
+    cmp: Option<(&AtomicU32, u32)>,
+    max_wake: usize,
+    max_requeue: usize,
+)->SysResult<usize>{
     let mut futex_q = FUTEX_Q.lock();
+    if let Some((word, val)) = cmp {
+        if word.load(Ordering::SeqCst) != val {
+            return Err(SysError::EAGAIN);
+        }
+    }
+    let Some(mut bucket) = futex_q.remove(&key) else {
+        return Ok(0);
+    };
     let mut num = 0;
-    let mut num2 = 0;
-    let mut tmp = VecDeque::new();
-    if let Some(queue) = futex_q.get_mut(&old_key) {
-        while let Some(weak_task) = queue.pop_front() {
-            if let Some(task) = weak_task.upgrade() {
-                if num < max_num {
-                    wakeup_task(task);
-                    num += 1;
-                } else if num2 < max_num2 {
-                    tmp.push_back(Arc::downgrade(&task));
-                    num2 += 1;
-                }
+    while num < max_wake {
+        let Some(waiter) = bucket.pop_front() else {
+            break;
+        };
+        waiter.wake();
+        num += 1;
+    }
+    let mut moved = VecDeque::new();
+    while moved.len() < max_requeue {
+        let Some(waiter) = bucket.pop_front() else {
+            break;
+        };
+        *waiter.key.lock() = new_key;
+        moved.push_back(waiter);
+    }
+    if !bucket.is_empty() {
+        futex_q.insert(key, bucket);
+    }
+    num += moved.len();
+    if !moved.is_empty() {
+        futex_q.entry(new_key).or_default().extend(moved);
+    }
+    Ok(num)
+}
+
+/// FUTEX_WAKE_OP: 对 `word2` 做 `encoded_op` 描述的原子操作, 唤醒 `key` 上 `max_wake` 个等待者,
+/// 若旧值满足比较条件再唤醒 `key2` 上 `max_wake2` 个
+pub fn futex_wake_op(
+    key: FutexKey,
+    key2: FutexKey,
+    word2: &AtomicU32,
+    max_wake: usize,
+    max_wake2: usize,
+    encoded_op: u32,
+) -> SysResult<usize> {
+    const FUTEX_OP_OPARG_SHIFT: u32 = 8;
+    let op = encoded_op >> 28;
+    let cmp = (encoded_op >> 24) & 0xf;
+    // oparg 与 cmparg 都是 12 位有符号数
+    let mut oparg = ((encoded_op << 8) as i32 >> 20) as u32;
+    let cmparg = (encoded_op << 20) as i32 >> 20;
+    if op & FUTEX_OP_OPARG_SHIFT != 0 {
+        oparg = 1 << (oparg & 31);
+    }
+    if op & 7 > 4 || cmp > 5 {
+        return Err(SysError::ENOSYS);
+    }
+    let old = match op & 7 {
+        0 => word2.swap(oparg, Ordering::SeqCst),
+        1 => word2.fetch_add(oparg, Ordering::SeqCst),
+        2 => word2.fetch_or(oparg, Ordering::SeqCst),
+        3 => word2.fetch_and(!oparg, Ordering::SeqCst),
+        _ => word2.fetch_xor(oparg, Ordering::SeqCst),
+    } as i32;
+    let hit = match cmp {
+        0 => old == cmparg,
+        1 => old != cmparg,
+        2 => old < cmparg,
+        3 => old <= cmparg,
+        4 => old > cmparg,
+        _ => old >= cmparg,
+    };
+    let mut num = futex_wake(key, max_wake, FUTEX_BITSET_MATCH_ANY);
+    if hit {
+        num += futex_wake(key2, max_wake2, FUTEX_BITSET_MATCH_ANY);
+    }
+    Ok(num)
+}
+
+/// 把 PI 锁交给 `key` 上最早的等待者, `extra` 附加到新的 futex 字上. 没有等待者时返回 false
+fn pi_handoff(futex_q: &mut BTreeMap<FutexKey, FutexBucket>, key: FutexKey, word: &AtomicU32, extra: u32) -> bool {
+    let Some(bucket) = futex_q.get_mut(&key) else {
+        return false;
+    };
+    let Some(next) = bucket.pop_front() else {
+        return false;
+    };
+    let waiters = if bucket.is_empty() {
+        futex_q.remove(&key);
+        0
+    } else {
+        FUTEX_WAITERS
+    };
+    word.store(next.tid as u32 | extra | waiters, Ordering::SeqCst);
+    next.wake();
+    true
+}
+
+/// FUTEX_LOCK_PI / FUTEX_TRYLOCK_PI
+pub fn futex_lock_pi(key: FutexKey, word: &AtomicU32, deadline: Option<usize>, trylock: bool) -> SysResult<isize> {
+    let task = current_task().unwrap();
+    let tid = task.gettid() as u32;
+    let mut futex_q = FUTEX_Q.lock();
+    let owner = loop {
+        let cur = word.load(Ordering::SeqCst);
+        let owner = cur & FUTEX_TID_MASK;
+        if owner == tid {
+            return Err(SysError::EDEADLK);
+        }
+        if owner == 0 {
+            // 无主(包括持有者已死): 直接拿锁, OWNER_DIED 留给用户态处理
+            let waiters = if futex_q.contains_key(&key) { FUTEX_WAITERS } else { 0 };
+            let new = tid | (cur & FUTEX_OWNER_DIED) | waiters;
+            if word.compare_exchange(cur, new, Ordering::SeqCst, Ordering::SeqCst).is_ok() {
+                return Ok(0);
             }
+            continue;
+        }
+        if trylock {
+            return Err(SysError::EAGAIN);
+        }
+        let Some(owner) = tid2task(owner as usize) else {
+            return Err(SysError::ESRCH);
+        };
+        if word
+            .compare_exchange(cur, cur | FUTEX_WAITERS, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+        {
+            break owner;
         }
+    };
+    let waiter = FutexWaiter::new(&task, key, FUTEX_BITSET_MATCH_ANY);
+    futex_q.entry(key).or_default().push_back(waiter.clone());
+    drop(futex_q);
+    boost_task(&owner);
+    drop(owner);
+    // 被唤醒即表示锁已转交
+    sleep_on(task, &waiter, deadline)
+}
+
+/// FUTEX_UNLOCK_PI
+pub fn futex_unlock_pi(key: FutexKey, word: &AtomicU32) -> SysResult<isize> {
+    let tid = current_task().unwrap().gettid() as u32;
+    let mut futex_q = FUTEX_Q.lock();
+    if word.load(Ordering::SeqCst) & FUTEX_TID_MASK != tid {
+        return Err(SysError::EPERM);
     }
-    if !tmp.is_empty() {
-        futex_q
-            .entry(new_key)
-            .or_insert_with(VecDeque::new)
-            .extend(tmp);
+    if !pi_handoff(&mut futex_q, key, word, 0) {
+        word.store(0, Ordering::SeqCst);
     }
-    num as usize
-
+    Ok(0)
 }
 
+/// 线程退出时仍持有 `uaddr` 处的锁: 标记 OWNER_DIED 并让等待者继续
+fn handle_futex_death(memory_set: &Arc<Mutex<MemorySet>>, uaddr: usize, tid: usize, pi: bool) {
+    let Ok(word) = futex_word(memory_set, uaddr) else {
+        return;
+    };
+    let cur = loop {
+        let cur = word.load(Ordering::SeqCst);
+        if cur & FUTEX_TID_MASK != tid as u32 {
+            return;
+        }
+        let new = (cur & FUTEX_WAITERS) | FUTEX_OWNER_DIED;
+        if word.compare_exchange(cur, new, Ordering::SeqCst, Ordering::SeqCst).is_ok() {
+            break cur;
+        }
+    };
+    if cur & FUTEX_WAITERS == 0 {
+        return;
+    }
+    let Ok(key) = FutexKey::new(memory_set, uaddr, false) else {
+        return;
+    };
+    if pi {
+        let mut futex_q = FUTEX_Q.lock();
+        pi_handoff(&mut futex_q, key, word, FUTEX_OWNER_DIED);
+    } else {
+        futex_wake(key, 1, FUTEX_BITSET_MATCH_ANY);
+    }
+}
 
+/// 遍历线程的 robust list, 处理它退出时仍持有的锁
+///
+/// struct robust_list_head { next, futex_offset, list_op_pending }, 链表指针最低位表示 PI 锁
+pub fn exit_robust_list(memory_set: &Arc<Mutex<MemorySet>>, head: usize, tid: usize) {
+    let read = |addr: usize| user_ref::<AtomicUsize>(memory_set, addr).map(|v| v.load(Ordering::SeqCst));
+    let (Ok(mut entry), Ok(offset), Ok(pending)) = (read(head), read(head + 8), read(head + 16)) else {
+        return;
+    };
+    let offset = offset as isize;
+    let mut count = 0;
+    while entry & !1 != head && count < ROBUST_LIST_LIMIT {
+        // 先取下一项, 处理当前项后用户态可能已经释放它
+        let Ok(next) = read(entry & !1) else {
+            break;
+        };
+        if entry != pending {
+            handle_futex_death(memory_set, (entry & !1).wrapping_add_signed(offset), tid, entry & 1 != 0);
+        }
+        entry = next;
+        count += 1;
+    }
+    if pending != 0 {
+        handle_futex_death(memory_set, (pending & !1).wrapping_add_signed(offset), tid, pending & 1 != 0);
+    }
+}
@@ -4,6 +4,7 @@ use crate::sync::UPSafeCell;
 use alloc::collections::VecDeque;
 use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use lazy_static::*;
 use spin::Mutex;
 
@@ -36,6 +37,10 @@ impl TaskManager {
     pub fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
         self.ready_queue.pop_front()
     }
+    /// 是否有就绪任务
+    pub fn has_ready(&self) -> bool {
+        !self.ready_queue.is_empty()
+    }
     /// 根据 PID 查找任务（仅在就绪队列中查找）
     pub fn find_task_by_tid(&self, tid: usize) -> Option<Arc<TaskControlBlock>> {
         self.ready_queue.iter().find(|task| task.gettid() == tid).cloned()
@@ -45,6 +50,13 @@ impl TaskManager {
             self.block_queue.remove(pos);
         }
     }
+    /// 把就绪任务移到队首
+    pub fn boost(&mut self, tid: usize) {
+        if let Some(pos) = self.ready_queue.iter().position(|task| task.gettid() == tid) {
+            let task = self.ready_queue.remove(pos).unwrap();
+            self.ready_queue.push_front(task);
+        }
+    }
 }
 
 lazy_static! {
@@ -76,10 +88,26 @@ pub fn wakeup_task(task: Arc<TaskControlBlock>) {
     drop(task_inner);
     add_task(task);
 }
+/// 仅当任务仍处于阻塞状态时唤醒, 避免同一任务被定时器/信号/futex 重复加入就绪队列
+pub fn wakeup_blocked_task(task: Arc<TaskControlBlock>) -> bool {
+    if task.inner_exclusive_access().task_status != TaskStatus::Blocked {
+        return false;
+    }
+    wakeup_task(task);
+    true
+}
+/// 调度器没有优先级, 优先级继承表现为让持锁任务下一个运行
+pub fn boost_task(task: &Arc<TaskControlBlock>) {
+    TASK_MANAGER.lock().boost(task.gettid());
+}
 ///Interface offered to pop the first task
 pub fn fetch_task() -> Option<Arc<TaskControlBlock>> {
     TASK_MANAGER.lock().fetch()
 }
+/// 是否有就绪任务
+pub fn has_ready_task() -> bool {
+    TASK_MANAGER.lock().has_ready()
+}
 
 /// 根据 PID 查找任务控制块
 pub fn tid2task(tid: usize) -> Option<Arc<TaskControlBlock>> {
@@ -87,6 +115,11 @@ pub fn tid2task(tid: usize) -> Option<Arc<TaskControlBlock>> {
     map.get(&tid).map(Arc::clone)
 }
 
+/// 所有尚未回收的任务
+pub fn all_tasks() -> Vec<Arc<TaskControlBlock>> {
+    PID2TCB.lock().values().cloned().collect()
+}
+
 /// 将任务插入 PID2TCB 映射
 pub fn insert_into_tid2task(tid: usize, task: Arc<TaskControlBlock>) {
     PID2TCB.lock().insert(tid, task);
@@ -29,25 +29,28 @@ mod info;
 mod fdtable;
 mod action;
 mod futex;
+mod cred;
 
 use crate::fs::open_file;
-use crate::mm::{translated_refmut,safe_translated_refmut};
+use crate::mm::{checked_translated_byte_buffer, safe_translated_refmut};
 use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use arch::addr::PhysAddr;
-use arch::{shutdown, SIG_RETURN_ADDR};
+use arch::shutdown;
 use arch::KContext;
 use arch::TrapFrameArgs;
 use config::{USER_STACK_SIZE, USER_STACK_TOP};
 use lazy_static::*;
-pub use manager::{fetch_task, TaskManager,wakeup_task, tid2task, insert_into_tid2task, remove_from_tid2task,add_blocked_task};
+pub use manager::{fetch_task, has_ready_task, TaskManager,wakeup_task,wakeup_blocked_task,boost_task, tid2task, all_tasks, insert_into_tid2task, remove_from_tid2task,add_blocked_task};
 pub use signal::{SigActionFlags,SigDetails,UserContext,SignalStack,into_mcontext};
 pub use task::{TaskControlBlock, TaskStatus};
 pub use info::{Utsname,SysInfo,UNAME};
 pub use time::{Tms,TimeSpec};
 pub use fdtable::{FdTable,Fd,FdFlags};
-use vfs_defs::OpenFlags;
+pub use cred::{Cred,NGROUPS_MAX};
+use vfs_defs::{File, OpenFlags};
+use system_result::{SysError,SysResult};
 pub use manager::add_task;
 pub use manager::deb;
 //pub use pid::{pid_alloc,  PidAllocator, PidHandle};
@@ -58,13 +61,98 @@ pub use processor::{
 };
 pub use signal::{SignalFlags, SigAction,SigInfo};
 pub use aux::*;
-pub use futex::{FutexKey,futex_wait,futex_wake,futex_requeue};
+pub use futex::{
+    FutexKey, futex_wait, futex_wake, futex_requeue, futex_wake_op, futex_lock_pi, futex_unlock_pi,
+    futex_word, exit_robust_list, FUTEX_BITSET_MATCH_ANY, ROBUST_LIST_HEAD_SIZE,
+};
 
 
 
 const MODULE_LEVEL:log::Level = log::Level::Trace;
 pub const MAX_SIG: usize = 33;
 
+/// /proc/self/exe 的链接内容: 当前任务正在运行的可执行文件
+pub fn current_exe_path() -> SysResult<String> {
+    let task = current_task().ok_or(SysError::ENOENT)?;
+    let exe = task.inner_exclusive_access().exe.clone();
+    exe.map(|dentry| dentry.path()).ok_or(SysError::ENOENT)
+}
+
+/// 当前任务的文件描述符 `fd` 对应的打开文件
+pub fn current_file(fd: usize) -> SysResult<Arc<dyn File>> {
+    let task = current_task().ok_or(SysError::EBADF)?;
+    let inner = task.inner_exclusive_access();
+    let file = inner.fd_table.lock().get(fd)?.file();
+    Ok(file)
+}
+
+/// 从当前任务的用户地址 `src` 读满 `dst`, 地址无效时返回 EFAULT
+pub fn copy_from_user(src: usize, dst: &mut [u8]) -> SysResult<()> {
+    let memory_set = current_task().ok_or(SysError::EFAULT)?.inner_exclusive_access().memory_set.clone();
+    dst.copy_from_slice(checked_translated_byte_buffer(memory_set, src as *mut u8, dst.len(), false)?);
+    Ok(())
+}
+
+/// 把 `src` 写到当前任务的用户地址 `dst`, 地址无效时返回 EFAULT
+pub fn copy_to_user(dst: usize, src: &[u8]) -> SysResult<()> {
+    let memory_set = current_task().ok_or(SysError::EFAULT)?.inner_exclusive_access().memory_set.clone();
+    checked_translated_byte_buffer(memory_set, dst as *mut u8, src.len(), true)?.copy_from_slice(src);
+    Ok(())
+}
+
+/// 只有特权用户能做的操作先检查这里, 没有特权时返回 EPERM
+pub fn check_privileged() -> SysResult<()> {
+    let task = current_task().unwrap();
+    if task.inner_exclusive_access().cred.privileged() {
+        Ok(())
+    } else {
+        Err(SysError::EPERM)
+    }
+}
+
+/// 当前任务是否有未被屏蔽的待处理信号, 可中断的等待据此返回 EINTR
+pub fn current_has_pending_signal() -> bool {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    inner.signal_queue.iter().any(|info| {
+        SignalFlags::from_bits(1 << (info.signum - 1)).is_some_and(|flag| !inner.signal_mask.contains(flag))
+    })
+}
+
+/// 从当前任务的信号队列取出第一个属于 `mask` 的信号(signalfd 读取), 不再排队的信号同时清掉待处理位
+pub fn dequeue_signal(mask: SignalFlags) -> Option<SigInfo> {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let in_mask = |info: &SigInfo| SignalFlags::from_bits(1 << (info.signum - 1)).is_some_and(|flag| mask.contains(flag));
+    let pos = inner.signal_queue.iter().position(in_mask)?;
+    let info = inner.signal_queue.remove(pos);
+    if !inner.signal_queue.iter().any(|queued| queued.signum == info.signum) {
+        inner.signals.remove(SignalFlags::from_bits_truncate(1 << (info.signum - 1)));
+    }
+    Some(info)
+}
+
+/// 当前任务是否有排队中且属于 `mask` 的信号
+pub fn has_queued_signal(mask: SignalFlags) -> bool {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    inner.signal_queue.iter().any(|info| {
+        SignalFlags::from_bits(1 << (info.signum - 1)).is_some_and(|flag| mask.contains(flag))
+    })
+}
+
+/// 内核向当前任务发送信号, 比如向没有读者的管道写入时的 SIGPIPE
+pub fn send_signal_to_current(signal: SignalFlags) {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    inner.signals |= signal;
+    inner.signal_queue.push(SigInfo {
+        signum: signal.bits().trailing_zeros() as i32 + 1,
+        code: SigInfo::KERNEL,
+        details: SigDetails::None,
+    });
+}
+
 /// Suspend the current 'Running' task and run the next task in task list.
 pub fn suspend_current_and_run_next() {
     // There must be an application running.
@@ -126,17 +214,23 @@ pub fn exit_current_and_run_next(exit_code: i32) {
     // 移除 PID2TCB 中的引用
     remove_from_tid2task(tid);
     let mut inner = task.inner_exclusive_access();
-    if inner.tidaddress.clear_child_tid.is_some(){
-        let token = inner.get_user_token();
-        let addr = inner.tidaddress.clear_child_tid.unwrap();
-        *translated_refmut(token, addr as *mut i32) = 0;
-        let paddr = inner.memory_set.lock().page_table.translate(arch::addr::VirtAddr::from(addr as usize))
-        .unwrap().0;
-        let thread_shared_key = FutexKey::new(paddr, task.getpid());
-        futex_wake(thread_shared_key, 1);
-        let process_shared_key = FutexKey::new(paddr, 0);
-        futex_wake(process_shared_key, 1);
-
+    let memory_set = inner.memory_set.clone();
+    // 先处理 robust list 里仍持有的锁, 再清 clear_child_tid
+    if inner.robust_list != 0 {
+        exit_robust_list(&memory_set, inner.robust_list, tid);
+    }
+    if let Some(addr) = inner.tidaddress.clear_child_tid {
+        if let Ok(word) = futex_word(&memory_set, addr) {
+            word.store(0, core::sync::atomic::Ordering::SeqCst);
+            if let Ok(key) = FutexKey::new(&memory_set, addr, false) {
+                futex_wake(key, 1, FUTEX_BITSET_MATCH_ANY);
+            }
+        }
+    }
+    // 最后一个使用这张描述符表的任务退出时关闭所有文件, 并释放进程的 POSIX 锁
+    if Arc::strong_count(&inner.fd_table) == 1 {
+        inner.fd_table.lock().close_all();
+        crate::fs::release_process_locks(task.getpid());
     }
     inner.task_status = TaskStatus::Zombie;
     inner.exit_code = exit_code;
@@ -275,6 +369,16 @@ pub fn check_pending_signals() {
     }
 }
 
+/// 返回用户态前, 没有被信号处理函数接手的暂存掩码在这里恢复
+pub fn restore_saved_sigmask() {
+    if let Some(task) = current_task() {
+        let mut task_inner = task.inner_exclusive_access();
+        if let Some(mask) = task_inner.saved_sigmask.take() {
+            task_inner.signal_mask = mask;
+        }
+    }
+}
+
 /// 处理内核态信号
 pub fn call_kernel_signal_handler(_sig: usize, signal: SignalFlags) {
     let task = current_task().unwrap();
@@ -314,7 +418,8 @@ pub fn call_user_signal_handler(sig: i32, _signal: SignalFlags,info:SigInfo) {
 
     // 保存当前的 trap 上下文
     task_inner.trap_ctx_backup = Some(task_inner.get_trap_cx().clone());
-    task_inner.signal_mask_backup = task_inner.signal_mask;
+    // ppoll 等被打断时, 处理函数返回后要回到调用前的掩码
+    task_inner.signal_mask_backup = task_inner.saved_sigmask.take().unwrap_or(task_inner.signal_mask);
 
     // 设置信号掩码
     let signal_mask = task_inner.signal_actions.lock().table[sig as usize].mask;
@@ -363,7 +468,7 @@ pub fn call_user_signal_handler(sig: i32, _signal: SignalFlags,info:SigInfo) {
         trap_ctx[TrapFrameArgs::RA] = if sig_table.table[sig as usize].flags.contains(SigActionFlags::RESTORER){
             sig_table.table[sig as usize].restore
         } else {
-            SIG_RETURN_ADDR as usize
+            crate::vdso::sigreturn_addr()
         };
     }
 
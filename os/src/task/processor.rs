@@ -1,5 +1,5 @@
 //!Implementation of [`Processor`] and Intersection of control flow
-use super::{fetch_task, TaskStatus};
+use super::{fetch_task, has_ready_task, TaskStatus};
 use super::TaskControlBlock;
 use crate::sync::UPSafeCell;
 use alloc::sync::Arc;
@@ -68,6 +68,19 @@ pub fn run_tasks() {
         //    }
             handle_signals(); // 确保每次调度时处理信号
             unsafe { context_switch_pt(idle_task_cx_ptr, next_task_cx_ptr, token) }
+        } else {
+            drop(processor);
+            // 没有就绪任务: 先处理到期定时器, 仍然没有就停掉时间片等下一个定时器
+            crate::timer::handle_expired();
+            if !has_ready_task() {
+                crate::timer::set_next_event(false);
+                arch::wait_for_interrupt();
+                // 内核态不开中断, 唤醒我们的外部中断在这里认领
+                crate::drivers::handle_irq();
+                crate::vdso::update();
+                crate::timer::handle_expired();
+                crate::timer::set_next_event(true);
+            }
         }
     }
 }
@@ -1,5 +1,5 @@
 //!Implementation of [`TaskControlBlock`]
-use super::{current_task, tid_alloc, FdTable, SigInfo, TidAddress, TidHandle, TimeSpec, Tms};
+use super::{current_task, tid_alloc, Cred, FdTable, SigInfo, TidAddress, TidHandle, TimeSpec, Tms};
 use super::aux::*;
 use config::{KERNEL_STACK_SIZE, USER_MMAP_TOP, USER_STACK_SIZE,RLimit,MAX_FD};
 use crate::fs::{Stdin, Stdout};
@@ -82,10 +82,15 @@ pub struct TaskControlBlockInner {
     pub frozen: bool,
     pub signal_mask: SignalFlags,      // 信号掩码
     pub signal_mask_backup: SignalFlags, // 保存原始信号掩码
+    pub saved_sigmask: Option<SignalFlags>, // ppoll 等被信号打断时暂存的原掩码, 递送信号后恢复
     pub signal_actions: Arc<Mutex<SignalActions>>, // 信号处理函数表
     pub handling_sig: isize,           // 当前正在处理的信号
     pub trap_ctx_backup: Option<TrapFrame>, // 添加 trap_ctx_backup 字段
     pub cwd:Arc<dyn Dentry>,//工作目录
+    pub root:Arc<dyn Dentry>,//根目录, chroot 可修改
+    pub exe:Option<Arc<dyn Dentry>>,//正在运行的可执行文件, /proc/self/exe 指向它
+    pub cred: Cred,//uid/gid 等身份
+    pub umask: u32,//新建文件时去掉的权限位
     pub heap_top: usize,
     pub heap_bottom: usize, //brk收缩判断
     pub stack_bottom: usize,
@@ -93,6 +98,10 @@ pub struct TaskControlBlockInner {
     pub tms: Tms,
 
     pub tidaddress:TidAddress,
+    /// set_robust_list 登记的 robust_list_head 地址, 0 表示没有
+    pub robust_list: usize,
+    /// RLIMIT_FSIZE, 文件不能被写或截断到超过它
+    pub fsize_limit: RLimit,
     //pub mmap_top: usize,
 }
 fn task_entry() {
@@ -162,12 +171,17 @@ impl TaskControlBlock {
                     exit_code: 0,
                     fd_table: Arc::new(Mutex::new(FdTable::new())),
                     cwd:get_root_dentry(),
+                    root:get_root_dentry(),
+                    exe: None,
+                    cred: Cred::root(),
+                    umask: 0o022,
                     kernel_stack: kstack,
                     signals: Default::default(),  // 使用 Default::default() 初始化 signals
                     killed: false,
                     frozen: false,
                     signal_mask: SignalFlags::empty(),
                     signal_mask_backup: SignalFlags::empty(),
+                    saved_sigmask: None,
                     signal_actions: Arc::new(Mutex::new(SignalActions::new())),
                     handling_sig: -1,
                     heap_top: heap_top,
@@ -177,6 +191,8 @@ impl TaskControlBlock {
                     tms: Tms::new(),
                     //mmap_top: USER_MMAP_TOP,
                     tidaddress:TidAddress::new(),
+                    robust_list: 0,
+                    fsize_limit: RLimit { rlimit_cur: usize::MAX, rlimit_max: usize::MAX },
                     trap_ctx_backup: None, // 初始化 trap_ctx_backup
                     signal_queue: Vec::new(),
                     }
@@ -275,6 +291,12 @@ impl TaskControlBlock {
         aux.a_type = AT_RANDOM;
         aux.a_val = rd_pos;
         self.push_into_user_stack(memory_set.clone(),&mut user_sp,aux);
+
+        if let Some(ehdr) = crate::vdso::sysinfo_ehdr() {
+            aux.a_type = AT_SYSINFO_EHDR;
+            aux.a_val = ehdr;
+            self.push_into_user_stack(memory_set.clone(),&mut user_sp,aux);
+        }
         let dl_entry = memory_set.lock().load_interp(elf_data);
         if dl_entry.is_some(){
             aux.a_type = AT_BASE;
@@ -317,6 +339,8 @@ impl TaskControlBlock {
         let mut inner = self.inner_exclusive_access();
         // substitute memory_set
         inner.memory_set = memory_set;
+        // 旧地址空间里的 robust list 已经失效
+        inner.robust_list = 0;
         // update trap_cx ppn
         // FIXME: This is a temporary solution
         inner.trap_cx = TrapFrame::new();
@@ -412,12 +436,17 @@ impl TaskControlBlock {
                     exit_code: 0,
                     fd_table,
                     cwd:parent_inner.cwd.clone(),
+                    root:parent_inner.root.clone(),
+                    exe:parent_inner.exe.clone(),
+                    cred:parent_inner.cred.clone(),
+                    umask:parent_inner.umask,
                     kernel_stack: kstack,
                     signals: Default::default(),  // 使用 Default::default() 初始化 signals
                     killed: false,
                     frozen: false,
                     signal_mask,
                     signal_mask_backup: SignalFlags::empty(),
+                    saved_sigmask: None,
                     signal_actions,
                     handling_sig: -1,
                     heap_top: parent_inner.heap_top,
@@ -427,6 +456,8 @@ impl TaskControlBlock {
                     tms: Tms::from_other_task(&parent_inner.tms),
                     //mmap_top: parent_inner.mmap_top,
                     tidaddress,
+                    robust_list: 0,
+                    fsize_limit: parent_inner.fsize_limit,
                     trap_ctx_backup: None, // 初始化 trap_ctx_backup
                     signal_queue: Vec::new(),
                 })
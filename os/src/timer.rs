@@ -1,88 +1,233 @@
-use crate::task::TaskControlBlock;
-use crate::task::wakeup_task;
-use crate::task::TimeSpec;
+//! 内核定时器
+//!
+//! 定时器挂在分层时间轮上: 共 `WHEEL_LEVELS` 层, 每层 64 个槽, 第 0 层每槽 1ms,
+//! 往上每层粒度放大 64 倍. 第 0 层转完一圈时把上一层对应槽里的定时器重新插入, 逐级下放.
+//! 硬件定时器按单次模式编程到下一个到期时间; 有任务运行时再加上时间片上限,
+//! 空闲时只等下一个定时器(或者什么都不等).
+use crate::task::{current_task, block_current_and_run_next, wakeup_blocked_task, TaskControlBlock};
 use alloc::{
-    collections::BinaryHeap,
+    collections::BTreeMap,
     sync::{Arc, Weak},
+    vec::Vec,
 };
-use core::cmp::Ordering;
+use arch::time::Time;
 use lazy_static::*;
 use sync::Mutex;
-use arch::time::{self, Time};
+use time::{monotonic_nsec, NSEC_PER_SEC};
 
+/// 第 0 层每个槽的粒度
+const TICK_RES_NSEC: usize = 1_000_000;
+/// 抢占时间片
+const TIME_SLICE_NSEC: usize = NSEC_PER_SEC / 100;
+const WHEEL_BITS: usize = 6;
+const WHEEL_SIZE: usize = 1 << WHEEL_BITS;
+const WHEEL_MASK: usize = WHEEL_SIZE - 1;
+/// 64^5 ms 约 12 天, 更远的定时器先放在最高层, 下放时再重新计算
+const WHEEL_LEVELS: usize = 5;
 
-#[derive(Debug, PartialEq, Eq)]
-pub enum TimerType {
-    Futex,
-    StoppedTask,
+/// 定时器句柄, 用于取消
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TimerId(usize);
+
+/// 定时器到期回调, 在定时器锁外调用, 可以重新添加定时器
+pub trait TimerHandler: Send + Sync {
+    fn expire(&self, now: usize);
 }
 
-pub struct TimerCondVar {
-    pub expire: TimeSpec,
-    pub task: Weak<TaskControlBlock>,
-    pub kind: TimerType,
+/// 到期后要做的事
+pub enum TimerEvent {
+    /// 唤醒阻塞在此定时器上的任务
+    Wake(Weak<TaskControlBlock>),
+    /// 调用回调, 对象已释放则忽略
+    Callback(Weak<dyn TimerHandler>),
 }
-impl PartialEq for TimerCondVar {
-    fn eq(&self, other: &Self) -> bool {
-        self.expire.sec == other.expire.sec && self.expire.usec == other.expire.usec
-    }
+
+struct Timer {
+    id: TimerId,
+    /// 到期时刻, 单调时钟纳秒
+    expire: usize,
+    event: TimerEvent,
 }
-impl Eq for TimerCondVar {}
-impl PartialOrd for TimerCondVar {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        let a = -(self.expire.to_usec() as isize);
-        let b = -(other.expire.to_usec() as isize);
-        Some(a.cmp(&b))
-    }
+
+struct TimerWheel {
+    /// 已处理到的 tick
+    now: usize,
+    slots: [[Vec<Timer>; WHEEL_SIZE]; WHEEL_LEVELS],
+    /// 定时器当前所在的 (层, 槽)
+    index: BTreeMap<TimerId, (usize, usize)>,
+    next_id: usize,
 }
-impl Ord for TimerCondVar {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap()
-    }
+
+fn to_tick(nsec: usize) -> usize {
+    (nsec + TICK_RES_NSEC - 1) / TICK_RES_NSEC
 }
 
-lazy_static!{
-    pub static ref TIMERS: Mutex<BinaryHeap<TimerCondVar>> =
-        Mutex::new(BinaryHeap::<TimerCondVar>::new());
+impl TimerWheel {
+    fn new() -> Self {
+        Self {
+            now: monotonic_nsec() / TICK_RES_NSEC,
+            slots: core::array::from_fn(|_| core::array::from_fn(|_| Vec::new())),
+            index: BTreeMap::new(),
+            next_id: 1,
+        }
+    }
+
+    fn insert(&mut self, timer: Timer) {
+        let expire = to_tick(timer.expire).max(self.now);
+        let delta = expire - self.now;
+        let mut level = 0;
+        while level + 1 < WHEEL_LEVELS && delta >= 1 << (WHEEL_BITS * (level + 1)) {
+            level += 1;
+        }
+        // 超出最高层范围的放在最远的槽, 下放时会重新插入
+        let max_delta = (1 << (WHEEL_BITS * WHEEL_LEVELS)) - 1;
+        let expire = self.now + delta.min(max_delta);
+        let slot = (expire >> (WHEEL_BITS * level)) & WHEEL_MASK;
+        self.index.insert(timer.id, (level, slot));
+        self.slots[level][slot].push(timer);
+    }
+
+    fn remove(&mut self, id: TimerId) -> Option<Timer> {
+        let (level, slot) = self.index.remove(&id)?;
+        let bucket = &mut self.slots[level][slot];
+        let pos = bucket.iter().position(|t| t.id == id)?;
+        Some(bucket.swap_remove(pos))
+    }
+
+    /// 把 `level` 层当前槽里的定时器重新插入, 让它们落到更低层
+    fn cascade(&mut self, level: usize) {
+        let slot = (self.now >> (WHEEL_BITS * level)) & WHEEL_MASK;
+        let timers = core::mem::take(&mut self.slots[level][slot]);
+        for timer in timers {
+            self.index.remove(&timer.id);
+            self.insert(timer);
+        }
+    }
+
+    /// 推进到 `target` tick, 把到期(不晚于 `now_ns`)的定时器放进 `expired`
+    fn advance(&mut self, target: usize, now_ns: usize, expired: &mut Vec<Timer>) {
+        while self.now < target {
+            if self.index.is_empty() {
+                self.now = target;
+                break;
+            }
+            // 跳过第 0 层的空槽, 但不越过下一次下放的边界
+            let boundary = (self.now | WHEEL_MASK) + 1;
+            let mut next = self.now + 1;
+            while next < boundary.min(target) && self.slots[0][next & WHEEL_MASK].is_empty() {
+                next += 1;
+            }
+            self.now = next;
+            let mut level = 1;
+            while level < WHEEL_LEVELS && self.now & ((1 << (WHEEL_BITS * level)) - 1) == 0 {
+                level += 1;
+            }
+            for l in (1..level).rev() {
+                self.cascade(l);
+            }
+            self.collect(now_ns, expired);
+        }
+        self.collect(now_ns, expired);
+    }
+
+    fn collect(&mut self, now_ns: usize, expired: &mut Vec<Timer>) {
+        let slot = self.now & WHEEL_MASK;
+        let bucket = core::mem::take(&mut self.slots[0][slot]);
+        for timer in bucket {
+            if timer.expire <= now_ns {
+                self.index.remove(&timer.id);
+                expired.push(timer);
+            } else {
+                self.slots[0][slot].push(timer);
+            }
+        }
+    }
+
+    /// 最早的到期时刻
+    fn next_expire(&self) -> Option<usize> {
+        self.slots
+            .iter()
+            .flat_map(|level| level.iter())
+            .flat_map(|bucket| bucket.iter())
+            .map(|timer| timer.expire)
+            .min()
+    }
 }
 
+lazy_static! {
+    static ref TIMER_WHEEL: Mutex<TimerWheel> = Mutex::new(TimerWheel::new());
+}
 
-pub fn add_futex_timer(expire: TimeSpec, task: Arc<TaskControlBlock>) {
-    let mut timers = TIMERS.lock();
-    timers.push(TimerCondVar {
-        expire,
-        task: Arc::downgrade(&task),
-        kind: TimerType::Futex,
-    });
+/// 添加一个在单调时钟 `expire` 纳秒到期的定时器
+pub fn add_timer(expire: usize, event: TimerEvent) -> TimerId {
+    let mut wheel = TIMER_WHEEL.lock();
+    let id = TimerId(wheel.next_id);
+    wheel.next_id += 1;
+    wheel.insert(Timer { id, expire, event });
+    drop(wheel);
+    id
 }
 
-pub fn add_stopped_task_timer(expire: TimeSpec, task: Arc<TaskControlBlock>) {
-    let mut timers = TIMERS.lock();
-    timers.push(TimerCondVar {
-        expire,
-        task: Arc::downgrade(&task),
-        kind: TimerType::StoppedTask,
-    });
+/// 取消尚未到期的定时器, 已经到期则什么也不做
+pub fn cancel_timer(id: TimerId) {
+    TIMER_WHEEL.lock().remove(id);
 }
 
-pub fn check_futex_timer() {
-    let mut timers = TIMERS.lock();
-    let current = Time::now().to_nsec();
-    while let Some(timer) = timers.peek() {
-        // debug!("expire={:?}, current={:?}", timer.expire, current);
-        if timer.expire.to_usec() <= current {
-            if let Some(task) = timer.task.upgrade() {
-                if timer.kind == TimerType::Futex {
-                    // 调用 wakeup_task 唤醒超时线程
-                    wakeup_task(Arc::clone(&task));
-                } else if timer.kind == TimerType::StoppedTask {
-                    todo!()
-                    // wakeup_stopped_task(Arc::clone(&task));
+/// 处理所有已到期的定时器
+pub fn handle_expired() {
+    let now = monotonic_nsec();
+    let mut expired = Vec::new();
+    TIMER_WHEEL.lock().advance(now / TICK_RES_NSEC, now, &mut expired);
+    for timer in expired {
+        match timer.event {
+            TimerEvent::Wake(task) => {
+                // 任务可能已被其他事件唤醒
+                if let Some(task) = task.upgrade() {
+                    wakeup_blocked_task(task);
+                }
+            }
+            TimerEvent::Callback(handler) => {
+                if let Some(handler) = handler.upgrade() {
+                    handler.expire(now);
                 }
             }
-            timers.pop();
-        } else {
-            break;
         }
     }
 }
+
+/// 时钟中断: 处理到期定时器, 并按时间片重新编程硬件定时器
+pub fn handle_timer_irq() {
+    handle_expired();
+    set_next_event(true);
+}
+
+/// 编程下一次时钟中断. `busy` 为真时最多等一个时间片, 否则只等下一个定时器
+pub fn set_next_event(busy: bool) {
+    let now = monotonic_nsec();
+    let mut deadline = TIMER_WHEEL.lock().next_expire();
+    if busy {
+        deadline = Some(deadline.map_or(now + TIME_SLICE_NSEC, |d| d.min(now + TIME_SLICE_NSEC)));
+    }
+    match deadline {
+        Some(deadline) => {
+            let freq = Time::get_freq();
+            let delta = deadline.saturating_sub(now);
+            let ticks = delta / NSEC_PER_SEC * freq + delta % NSEC_PER_SEC * freq / NSEC_PER_SEC;
+            Time::set_deadline(Time::from_raw(Time::now().raw() + ticks.max(1)));
+        }
+        None => Time::clear_deadline(),
+    }
+}
+
+/// 阻塞当前任务直到单调时钟 `deadline` 纳秒. 返回时若尚未到期说明被提前唤醒
+pub fn sleep_until(deadline: usize) -> bool {
+    if monotonic_nsec() >= deadline {
+        return true;
+    }
+    let task = current_task().unwrap();
+    let id = add_timer(deadline, TimerEvent::Wake(Arc::downgrade(&task)));
+    drop(task);
+    block_current_and_run_next();
+    cancel_timer(id);
+    monotonic_nsec() >= deadline
+}
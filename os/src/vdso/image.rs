@@ -0,0 +1,229 @@
+//! vDSO ELF 映像的构建
+use crate::mm::{frame_alloc, FrameTracker};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use arch::PAGE_SIZE;
+use core::mem::size_of;
+#[cfg(target_arch = "loongarch64")]
+use super::loongarch64 as imp;
+#[cfg(target_arch = "riscv64")]
+use super::riscv64 as imp;
+
+/// 代码在映像中的偏移
+const TEXT_OFFSET: usize = PAGE_SIZE;
+
+const SONAME: &str = "linux-vdso.so.1";
+
+extern "C" {
+    fn __vdso_text_start();
+    fn __vdso_text_end();
+    fn __vdso_clock_gettime();
+    fn __vdso_gettimeofday();
+    fn __vdso_clock_getres();
+    fn __vdso_getcpu();
+    fn __vdso_rt_sigreturn();
+}
+
+/// 导出符号及其在代码中的偏移
+fn symbols() -> [(&'static str, usize); 5] {
+    let start = __vdso_text_start as usize;
+    [
+        ("__vdso_clock_gettime", __vdso_clock_gettime as usize - start),
+        ("__vdso_gettimeofday", __vdso_gettimeofday as usize - start),
+        ("__vdso_clock_getres", __vdso_clock_getres as usize - start),
+        ("__vdso_getcpu", __vdso_getcpu as usize - start),
+        ("__vdso_rt_sigreturn", __vdso_rt_sigreturn as usize - start),
+    ]
+}
+
+fn text() -> &'static [u8] {
+    let start = __vdso_text_start as usize;
+    let end = __vdso_text_end as usize;
+    unsafe { core::slice::from_raw_parts(start as *const u8, end - start) }
+}
+
+/// 分配映像页并填入内容, 返回映像页与信号返回跳板的偏移
+pub fn build() -> (Vec<Arc<FrameTracker>>, usize) {
+    let text = text();
+    let pages = 1 + (text.len() + PAGE_SIZE - 1) / PAGE_SIZE;
+    let image: Vec<Arc<FrameTracker>> = (0..pages)
+        .map(|_| Arc::new(frame_alloc().expect("can't allocate vdso frame")))
+        .collect();
+    write_elf_header(image[0].ppn.get_buffer(), pages * PAGE_SIZE);
+    for (i, chunk) in text.chunks(PAGE_SIZE).enumerate() {
+        image[i + 1].ppn.get_buffer()[..chunk.len()].copy_from_slice(chunk);
+    }
+    (image, TEXT_OFFSET + symbols()[4].1)
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Elf64Ehdr {
+    e_ident: [u8; 16],
+    e_type: u16,
+    e_machine: u16,
+    e_version: u32,
+    e_entry: u64,
+    e_phoff: u64,
+    e_shoff: u64,
+    e_flags: u32,
+    e_ehsize: u16,
+    e_phentsize: u16,
+    e_phnum: u16,
+    e_shentsize: u16,
+    e_shnum: u16,
+    e_shstrndx: u16,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Elf64Phdr {
+    p_type: u32,
+    p_flags: u32,
+    p_offset: u64,
+    p_vaddr: u64,
+    p_paddr: u64,
+    p_filesz: u64,
+    p_memsz: u64,
+    p_align: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Elf64Sym {
+    st_name: u32,
+    st_info: u8,
+    st_other: u8,
+    st_shndx: u16,
+    st_value: u64,
+    st_size: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Elf64Dyn {
+    d_tag: u64,
+    d_val: u64,
+}
+
+const ET_DYN: u16 = 3;
+const PT_LOAD: u32 = 1;
+const PT_DYNAMIC: u32 = 2;
+const PF_X: u32 = 1;
+const PF_R: u32 = 4;
+const STB_GLOBAL_FUNC: u8 = 0x12;
+const DT_NULL: u64 = 0;
+const DT_HASH: u64 = 4;
+const DT_STRTAB: u64 = 5;
+const DT_SYMTAB: u64 = 6;
+const DT_STRSZ: u64 = 10;
+const DT_SYMENT: u64 = 11;
+const DT_SONAME: u64 = 14;
+
+fn put<T: Copy>(buf: &mut [u8], offset: usize, val: T) {
+    assert!(offset + size_of::<T>() <= buf.len());
+    unsafe { core::ptr::write_unaligned(buf.as_mut_ptr().add(offset) as *mut T, val) }
+}
+
+/// 在映像第一页写入 ELF 头, 程序头, 动态段, 符号表, 哈希表和字符串表.
+/// 映像按虚拟地址 0 链接, 文件偏移等于虚拟地址.
+fn write_elf_header(page: &mut [u8], image_size: usize) {
+    let syms = symbols();
+    let nsyms = syms.len() + 1;
+
+    let phoff = size_of::<Elf64Ehdr>();
+    let symoff = phoff + 2 * size_of::<Elf64Phdr>();
+    let hashoff = symoff + nsyms * size_of::<Elf64Sym>();
+    let dynoff = (hashoff + (3 + nsyms) * 4 + 7) & !7;
+    let ndyn = 7;
+    let stroff = dynoff + ndyn * size_of::<Elf64Dyn>();
+
+    // 字符串表: 空串, soname, 各符号名
+    let mut strtab: Vec<u8> = Vec::new();
+    strtab.push(0);
+    let soname = strtab.len();
+    strtab.extend_from_slice(SONAME.as_bytes());
+    strtab.push(0);
+    let mut names = Vec::new();
+    for (name, _) in syms.iter() {
+        names.push(strtab.len());
+        strtab.extend_from_slice(name.as_bytes());
+        strtab.push(0);
+    }
+    page[stroff..stroff + strtab.len()].copy_from_slice(&strtab);
+
+    let mut e_ident = [0u8; 16];
+    e_ident[..4].copy_from_slice(b"\x7fELF");
+    e_ident[4] = 2; // ELFCLASS64
+    e_ident[5] = 1; // ELFDATA2LSB
+    e_ident[6] = 1; // EV_CURRENT
+    put(page, 0, Elf64Ehdr {
+        e_ident,
+        e_type: ET_DYN,
+        e_machine: imp::EM_MACHINE,
+        e_version: 1,
+        e_entry: 0,
+        e_phoff: phoff as u64,
+        e_shoff: 0,
+        e_flags: imp::E_FLAGS,
+        e_ehsize: size_of::<Elf64Ehdr>() as u16,
+        e_phentsize: size_of::<Elf64Phdr>() as u16,
+        e_phnum: 2,
+        e_shentsize: 0,
+        e_shnum: 0,
+        e_shstrndx: 0,
+    });
+    put(page, phoff, Elf64Phdr {
+        p_type: PT_LOAD,
+        p_flags: PF_R | PF_X,
+        p_offset: 0,
+        p_vaddr: 0,
+        p_paddr: 0,
+        p_filesz: image_size as u64,
+        p_memsz: image_size as u64,
+        p_align: PAGE_SIZE as u64,
+    });
+    put(page, phoff + size_of::<Elf64Phdr>(), Elf64Phdr {
+        p_type: PT_DYNAMIC,
+        p_flags: PF_R,
+        p_offset: dynoff as u64,
+        p_vaddr: dynoff as u64,
+        p_paddr: dynoff as u64,
+        p_filesz: (ndyn * size_of::<Elf64Dyn>()) as u64,
+        p_memsz: (ndyn * size_of::<Elf64Dyn>()) as u64,
+        p_align: 8,
+    });
+
+    // 没有节头表, st_shndx 只需非 SHN_UNDEF 即可被动态链接器接受
+    for (i, (_, offset)) in syms.iter().enumerate() {
+        put(page, symoff + (i + 1) * size_of::<Elf64Sym>(), Elf64Sym {
+            st_name: names[i] as u32,
+            st_info: STB_GLOBAL_FUNC,
+            st_other: 0,
+            st_shndx: 1,
+            st_value: (TEXT_OFFSET + offset) as u64,
+            st_size: 0,
+        });
+    }
+
+    // 单个桶的 SysV 哈希表, 查找时沿链遍历全部符号
+    put(page, hashoff, 1u32);
+    put(page, hashoff + 4, nsyms as u32);
+    put(page, hashoff + 8, (nsyms - 1) as u32);
+    for i in 0..nsyms {
+        put(page, hashoff + 12 + i * 4, i.saturating_sub(1) as u32);
+    }
+
+    let dyns = [
+        (DT_HASH, hashoff),
+        (DT_STRTAB, stroff),
+        (DT_SYMTAB, symoff),
+        (DT_STRSZ, strtab.len()),
+        (DT_SYMENT, size_of::<Elf64Sym>()),
+        (DT_SONAME, soname),
+        (DT_NULL, 0),
+    ];
+    for (i, (tag, val)) in dyns.iter().enumerate() {
+        put(page, dynoff + i * size_of::<Elf64Dyn>(), Elf64Dyn { d_tag: *tag, d_val: *val as u64 });
+    }
+}
@@ -0,0 +1,169 @@
+//! LoongArch vDSO 代码
+//!
+//! 时钟分类($a2): 0 单调, 1 墙上, 2 TAI, 3 单调 coarse, 4 墙上 coarse
+use core::arch::global_asm;
+
+/// EM_LOONGARCH
+pub const EM_MACHINE: u16 = 258;
+/// EF_LOONGARCH_OBJABI_V1 | EF_LOONGARCH_ABI_DOUBLE_FLOAT
+pub const E_FLAGS: u32 = 0x43;
+
+global_asm!(
+    "
+    .section .text.vdso, \"ax\"
+    .balign 16
+
+    # 按 $a2 中的分类读取时钟, 结果(ns)放在 $t1; 使用 $t0-$t6, $a4
+    .macro READ_CLOCK
+        li.d    $t6, {data}
+    .Lretry\\@:
+        ld.w    $t5, $t6, 0
+        andi    $t0, $t5, 1
+        bnez    $t0, .Lretry\\@
+        dbar    0
+        li.d    $t0, 3
+        beq     $a2, $t0, .Lcoarse_mono\\@
+        li.d    $t0, 4
+        beq     $a2, $t0, .Lcoarse_real\\@
+        rdtime.d $t0, $zero
+        ld.d    $t2, $t6, 0x08
+        li.d    $a4, 1000000000
+        div.du  $t1, $t0, $t2
+        mod.du  $t3, $t0, $t2
+        mul.d   $t1, $t1, $a4
+        mul.d   $t3, $t3, $a4
+        div.du  $t3, $t3, $t2
+        add.d   $t1, $t1, $t3
+        beqz    $a2, .Ldone\\@
+        ld.d    $t0, $t6, 0x10
+        add.d   $t1, $t1, $t0
+        li.d    $t0, 1
+        beq     $a2, $t0, .Ldone\\@
+        ld.d    $t0, $t6, 0x30
+        add.d   $t1, $t1, $t0
+        b       .Ldone\\@
+    .Lcoarse_mono\\@:
+        ld.d    $t1, $t6, 0x18
+        b       .Ldone\\@
+    .Lcoarse_real\\@:
+        ld.d    $t1, $t6, 0x20
+    .Ldone\\@:
+        dbar    0
+        ld.w    $t0, $t6, 0
+        bne     $t0, $t5, .Lretry\\@
+    .endm
+
+    # 把 clockid($a0) 转成分类放进 $a2, 无法在用户态处理时跳到 fallback
+    .macro CLOCK_CLASS fallback
+        li.d    $a2, 1
+        beqz    $a0, .Lclass\\@
+        li.d    $t0, 8
+        beq     $a0, $t0, .Lclass\\@
+        li.d    $a2, 0
+        li.d    $t0, 1
+        beq     $a0, $t0, .Lclass\\@
+        li.d    $t0, 4
+        beq     $a0, $t0, .Lclass\\@
+        li.d    $t0, 7
+        beq     $a0, $t0, .Lclass\\@
+        li.d    $t0, 9
+        beq     $a0, $t0, .Lclass\\@
+        li.d    $a2, 2
+        li.d    $t0, 11
+        beq     $a0, $t0, .Lclass\\@
+        li.d    $a2, 3
+        li.d    $t0, 6
+        beq     $a0, $t0, .Lclass\\@
+        li.d    $a2, 4
+        li.d    $t0, 5
+        beq     $a0, $t0, .Lclass\\@
+        b       \\fallback
+    .Lclass\\@:
+    .endm
+
+    .globl __vdso_text_start
+__vdso_text_start:
+
+    # int __vdso_clock_gettime(clockid_t clk, struct timespec *ts)
+    .globl __vdso_clock_gettime
+__vdso_clock_gettime:
+    CLOCK_CLASS .Lcgt_syscall
+    READ_CLOCK
+    li.d    $a4, 1000000000
+    div.du  $t0, $t1, $a4
+    mod.du  $t2, $t1, $a4
+    st.d    $t0, $a1, 0
+    st.d    $t2, $a1, 8
+    move    $a0, $zero
+    jirl    $zero, $ra, 0
+.Lcgt_syscall:
+    li.d    $a7, 113
+    syscall 0
+    jirl    $zero, $ra, 0
+
+    # int __vdso_gettimeofday(struct timeval *tv, struct timezone *tz)
+    .globl __vdso_gettimeofday
+__vdso_gettimeofday:
+    beqz    $a1, .Lgtod_tv
+    st.w    $zero, $a1, 0
+    st.w    $zero, $a1, 4
+.Lgtod_tv:
+    beqz    $a0, .Lgtod_out
+    li.d    $a2, 1
+    READ_CLOCK
+    li.d    $a4, 1000000000
+    div.du  $t0, $t1, $a4
+    mod.du  $t2, $t1, $a4
+    li.d    $a4, 1000
+    div.du  $t2, $t2, $a4
+    st.d    $t0, $a0, 0
+    st.d    $t2, $a0, 8
+.Lgtod_out:
+    move    $a0, $zero
+    jirl    $zero, $ra, 0
+
+    # int __vdso_clock_getres(clockid_t clk, struct timespec *res)
+    .globl __vdso_clock_getres
+__vdso_clock_getres:
+    CLOCK_CLASS .Lcgr_syscall
+    beqz    $a1, .Lcgr_out
+    li.d    $t1, 1
+    li.d    $t0, 3
+    bltu    $a2, $t0, .Lcgr_store
+    li.d    $t6, {data}
+    ld.d    $t1, $t6, 0x28
+.Lcgr_store:
+    st.d    $zero, $a1, 0
+    st.d    $t1, $a1, 8
+.Lcgr_out:
+    move    $a0, $zero
+    jirl    $zero, $ra, 0
+.Lcgr_syscall:
+    li.d    $a7, 114
+    syscall 0
+    jirl    $zero, $ra, 0
+
+    # int __vdso_getcpu(unsigned *cpu, unsigned *node, void *cache)
+    # 内核只在 0 号核上调度
+    .globl __vdso_getcpu
+__vdso_getcpu:
+    beqz    $a0, .Lgetcpu_node
+    st.w    $zero, $a0, 0
+.Lgetcpu_node:
+    beqz    $a1, .Lgetcpu_out
+    st.w    $zero, $a1, 0
+.Lgetcpu_out:
+    move    $a0, $zero
+    jirl    $zero, $ra, 0
+
+    .globl __vdso_rt_sigreturn
+__vdso_rt_sigreturn:
+    li.d    $a7, 139
+    syscall 0
+
+    .globl __vdso_text_end
+__vdso_text_end:
+    .text
+    ",
+    data = const super::VDSO_DATA_ADDR,
+);
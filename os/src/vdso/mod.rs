@@ -0,0 +1,142 @@
+//! vDSO: a small shared object mapped into every user process
+//!
+//! 用户态布局:
+//! - `USER_VDSO_BASE`: 数据页, 只读, 内核在时钟中断和调整时间时更新
+//! - `USER_VDSO_BASE + PAGE_SIZE`: ELF 映像, 第一页是 ELF 头和动态符号表, 之后是代码
+//!
+//! 代码由 `global_asm!` 编进内核, 启动时拷贝进映像页; 它通过固定地址访问数据页,
+//! 因此无需重定位. 所有进程共享同一组物理页.
+#[cfg(any(target_arch = "riscv64", target_arch = "loongarch64"))]
+mod image;
+#[cfg(target_arch = "loongarch64")]
+mod loongarch64;
+#[cfg(target_arch = "riscv64")]
+mod riscv64;
+
+use crate::mm::{frame_alloc, FrameTracker, MapArea, MapAreaType, MapPermission, MapType, MemorySet};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use arch::addr::VirtPage;
+use arch::pagetable::MappingSize;
+use arch::{PAGE_SIZE, SIG_RETURN_ADDR};
+use config::USER_VDSO_BASE;
+use core::sync::atomic::{fence, AtomicU32, Ordering};
+use spin::Once;
+
+/// 数据页的用户地址
+pub const VDSO_DATA_ADDR: usize = USER_VDSO_BASE;
+/// ELF 映像的用户地址, 即 AT_SYSINFO_EHDR
+pub const VDSO_IMAGE_ADDR: usize = USER_VDSO_BASE + PAGE_SIZE;
+
+/// 内核与 vDSO 代码共享的数据, 字段偏移在汇编中写死, 修改时需同步
+#[repr(C)]
+pub struct VdsoData {
+    /// 顺序锁, 奇数表示内核正在更新
+    pub seq: AtomicU32,
+    _pad: u32,
+    /// 计数器频率 0x08
+    pub freq: u64,
+    /// 墙上时间相对单调时钟的偏移(ns) 0x10
+    pub realtime_offset: i64,
+    /// 上次时钟中断时的单调时间(ns) 0x18
+    pub coarse_mono: u64,
+    /// 上次时钟中断时的墙上时间(ns) 0x20
+    pub coarse_real: u64,
+    /// coarse 时钟精度(ns) 0x28
+    pub tick_nsec: u64,
+    /// TAI 相对 UTC 的偏移(ns) 0x30
+    pub tai_offset: u64,
+}
+
+struct Vdso {
+    data: Arc<FrameTracker>,
+    image: Vec<Arc<FrameTracker>>,
+    /// 信号返回跳板在映像中的偏移
+    sigreturn: usize,
+}
+
+static VDSO: Once<Vdso> = Once::new();
+
+/// 构建 vDSO 映像与数据页, 需在帧分配器初始化后调用
+#[cfg(any(target_arch = "riscv64", target_arch = "loongarch64"))]
+pub fn init() {
+    let (image, sigreturn) = image::build();
+    let data = Arc::new(frame_alloc().expect("can't allocate vdso frame"));
+    VDSO.call_once(|| Vdso { data, image, sigreturn });
+    #[cfg(target_arch = "riscv64")]
+    riscv64::enable_user_counter();
+    update();
+}
+
+/// 该架构没有 vDSO 代码, 信号返回仍使用 SIG_RETURN_ADDR 处的跳板
+#[cfg(not(any(target_arch = "riscv64", target_arch = "loongarch64")))]
+pub fn init() {}
+
+/// 把 vDSO 映射进用户地址空间
+pub fn map(memory_set: &mut MemorySet) {
+    let Some(vdso) = VDSO.get() else {
+        return;
+    };
+    let mut data_area = MapArea::new(
+        VDSO_DATA_ADDR.into(),
+        VDSO_IMAGE_ADDR.into(),
+        MapType::Framed,
+        MapPermission::R | MapPermission::U,
+        MapAreaType::Vdso,
+    );
+    map_shared(memory_set, &mut data_area, core::slice::from_ref(&vdso.data));
+    memory_set.areas.push(data_area);
+    let mut image_area = MapArea::new(
+        VDSO_IMAGE_ADDR.into(),
+        (VDSO_IMAGE_ADDR + vdso.image.len() * PAGE_SIZE).into(),
+        MapType::Framed,
+        MapPermission::R | MapPermission::X | MapPermission::U,
+        MapAreaType::Vdso,
+    );
+    map_shared(memory_set, &mut image_area, &vdso.image);
+    memory_set.areas.push(image_area);
+}
+
+fn map_shared(memory_set: &MemorySet, area: &mut MapArea, frames: &[Arc<FrameTracker>]) {
+    let start = area.vpn_range.get_start();
+    for (i, frame) in frames.iter().enumerate() {
+        let vpn: VirtPage = start + i;
+        memory_set.page_table.map_page(vpn, frame.ppn, area.map_perm.into(), MappingSize::Page4KB);
+        area.data_frames.insert(vpn, frame.clone());
+    }
+}
+
+/// AT_SYSINFO_EHDR 的值, 未启用 vDSO 时为 None
+pub fn sysinfo_ehdr() -> Option<usize> {
+    VDSO.get().map(|_| VDSO_IMAGE_ADDR)
+}
+
+/// 信号处理函数返回时跳转的地址
+pub fn sigreturn_addr() -> usize {
+    match VDSO.get() {
+        Some(vdso) => VDSO_IMAGE_ADDR + vdso.sigreturn,
+        None => SIG_RETURN_ADDR,
+    }
+}
+
+/// 刷新数据页, 在时钟中断以及墙上时间被修改后调用
+pub fn update() {
+    let Some(vdso) = VDSO.get() else {
+        return;
+    };
+    let data = unsafe { &mut *(vdso.data.ppn.get_buffer().as_mut_ptr() as *mut VdsoData) };
+    let seq = data.seq.load(Ordering::Relaxed);
+    data.seq.store(seq.wrapping_add(1), Ordering::Relaxed);
+    fence(Ordering::Release);
+    let mono = time::monotonic_nsec();
+    let offset = time::realtime_offset();
+    data.freq = arch::time::Time::get_freq() as u64;
+    data.realtime_offset = offset;
+    data.coarse_mono = time::coarse_nsec(mono) as u64;
+    data.coarse_real = time::coarse_nsec((mono as i64 + offset).max(0) as usize) as u64;
+    data.tick_nsec = time::TICK_NSEC as u64;
+    data.tai_offset = (time::TAI_OFFSET_SEC * time::NSEC_PER_SEC) as u64;
+    fence(Ordering::Release);
+    data.seq.store(seq.wrapping_add(2), Ordering::Relaxed);
+}
+
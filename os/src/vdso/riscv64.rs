@@ -0,0 +1,177 @@
+//! RISC-V vDSO 代码
+//!
+//! 时钟分类(a2): 0 单调, 1 墙上, 2 TAI, 3 单调 coarse, 4 墙上 coarse
+use core::arch::global_asm;
+
+/// EM_RISCV
+pub const EM_MACHINE: u16 = 243;
+/// EF_RISCV_RVC | EF_RISCV_FLOAT_ABI_DOUBLE
+pub const E_FLAGS: u32 = 0x5;
+
+/// 允许用户态执行 rdtime
+pub fn enable_user_counter() {
+    unsafe { core::arch::asm!("csrs scounteren, {}", in(reg) 1 << 1) };
+}
+
+global_asm!(
+    "
+    .section .text.vdso, \"ax\"
+    .option push
+    .option norelax
+    .balign 16
+
+    # 按 a2 中的分类读取时钟, 结果(ns)放在 t1; 使用 t0-t6, a4
+    .macro READ_CLOCK
+        li      t6, {data}
+    .Lretry\\@:
+        lw      t5, 0(t6)
+        andi    t0, t5, 1
+        bnez    t0, .Lretry\\@
+        fence   r, r
+        li      t0, 3
+        beq     a2, t0, .Lcoarse_mono\\@
+        li      t0, 4
+        beq     a2, t0, .Lcoarse_real\\@
+        rdtime  t0
+        ld      t2, 0x08(t6)
+        li      a4, 1000000000
+        divu    t1, t0, t2
+        remu    t3, t0, t2
+        mul     t1, t1, a4
+        mul     t3, t3, a4
+        divu    t3, t3, t2
+        add     t1, t1, t3
+        beqz    a2, .Ldone\\@
+        ld      t0, 0x10(t6)
+        add     t1, t1, t0
+        li      t0, 1
+        beq     a2, t0, .Ldone\\@
+        ld      t0, 0x30(t6)
+        add     t1, t1, t0
+        j       .Ldone\\@
+    .Lcoarse_mono\\@:
+        ld      t1, 0x18(t6)
+        j       .Ldone\\@
+    .Lcoarse_real\\@:
+        ld      t1, 0x20(t6)
+    .Ldone\\@:
+        fence   r, r
+        lw      t0, 0(t6)
+        bne     t0, t5, .Lretry\\@
+    .endm
+
+    # 把 clockid(a0) 转成分类放进 a2, 无法在用户态处理时跳到 fallback
+    .macro CLOCK_CLASS fallback
+        li      a2, 1
+        beqz    a0, .Lclass\\@
+        li      t0, 8
+        beq     a0, t0, .Lclass\\@
+        li      a2, 0
+        li      t0, 1
+        beq     a0, t0, .Lclass\\@
+        li      t0, 4
+        beq     a0, t0, .Lclass\\@
+        li      t0, 7
+        beq     a0, t0, .Lclass\\@
+        li      t0, 9
+        beq     a0, t0, .Lclass\\@
+        li      a2, 2
+        li      t0, 11
+        beq     a0, t0, .Lclass\\@
+        li      a2, 3
+        li      t0, 6
+        beq     a0, t0, .Lclass\\@
+        li      a2, 4
+        li      t0, 5
+        beq     a0, t0, .Lclass\\@
+        j       \\fallback
+    .Lclass\\@:
+    .endm
+
+    .globl __vdso_text_start
+__vdso_text_start:
+
+    # int __vdso_clock_gettime(clockid_t clk, struct timespec *ts)
+    .globl __vdso_clock_gettime
+__vdso_clock_gettime:
+    CLOCK_CLASS .Lcgt_syscall
+    READ_CLOCK
+    li      a4, 1000000000
+    divu    t0, t1, a4
+    remu    t2, t1, a4
+    sd      t0, 0(a1)
+    sd      t2, 8(a1)
+    li      a0, 0
+    ret
+.Lcgt_syscall:
+    li      a7, 113
+    ecall
+    ret
+
+    # int __vdso_gettimeofday(struct timeval *tv, struct timezone *tz)
+    .globl __vdso_gettimeofday
+__vdso_gettimeofday:
+    beqz    a1, .Lgtod_tv
+    sw      zero, 0(a1)
+    sw      zero, 4(a1)
+.Lgtod_tv:
+    beqz    a0, .Lgtod_out
+    li      a2, 1
+    READ_CLOCK
+    li      a4, 1000000000
+    divu    t0, t1, a4
+    remu    t2, t1, a4
+    li      a4, 1000
+    divu    t2, t2, a4
+    sd      t0, 0(a0)
+    sd      t2, 8(a0)
+.Lgtod_out:
+    li      a0, 0
+    ret
+
+    # int __vdso_clock_getres(clockid_t clk, struct timespec *res)
+    .globl __vdso_clock_getres
+__vdso_clock_getres:
+    CLOCK_CLASS .Lcgr_syscall
+    beqz    a1, .Lcgr_out
+    li      t1, 1
+    li      t0, 3
+    bltu    a2, t0, .Lcgr_store
+    li      t6, {data}
+    ld      t1, 0x28(t6)
+.Lcgr_store:
+    sd      zero, 0(a1)
+    sd      t1, 8(a1)
+.Lcgr_out:
+    li      a0, 0
+    ret
+.Lcgr_syscall:
+    li      a7, 114
+    ecall
+    ret
+
+    # int __vdso_getcpu(unsigned *cpu, unsigned *node, void *cache)
+    # 内核只在 0 号核上调度
+    .globl __vdso_getcpu
+__vdso_getcpu:
+    beqz    a0, .Lgetcpu_node
+    sw      zero, 0(a0)
+.Lgetcpu_node:
+    beqz    a1, .Lgetcpu_out
+    sw      zero, 0(a1)
+.Lgetcpu_out:
+    li      a0, 0
+    ret
+
+    .globl __vdso_rt_sigreturn
+__vdso_rt_sigreturn:
+    li      a7, 139
+    ecall
+
+    .globl __vdso_text_end
+__vdso_text_end:
+    .option pop
+    .text
+    ",
+    data = const super::VDSO_DATA_ADDR,
+);
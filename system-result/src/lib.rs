@@ -86,6 +86,10 @@ pub enum SysError {
     ENOTEMPTY = 39,
     /// Too many symbolic links encountered
     ELOOP = 40,
+    /// No data available
+    ENODATA = 61,
+    /// Value too large for defined data type
+    EOVERFLOW = 75,
     /// Socket operation on non-socket
     ENOTSOCK = 88,
     /// Unsupported
@@ -100,6 +104,8 @@ pub enum SysError {
     EISCONN = 106,
     /// The socket is not connected
     ENOTCONN = 107,
+    /// Connection timed out
+    ETIMEDOUT = 110,
     /// Connection refused
     ECONNREFUSED = 111,
     /// The socket is nonblocking and the connection cannot be completed
@@ -152,6 +158,8 @@ impl SysError {
             ENOSYS => "Invalid system call number",
             ENOTEMPTY => "Directory not empty",
             ELOOP => "Too many symbolic links encountered",
+            ENODATA => "No data available",
+            EOVERFLOW => "Value too large for defined data type",
             ENOTSOCK => "Socket operation on non-socket",
             ENOTCONN => "Transport endpoint is not connected",
             EOPNOTSUPP => "Unsupported Error",
@@ -159,6 +167,7 @@ impl SysError {
             EADDRINUSE => "Address already in use",
             EISCONN => "Transport endpoint is already connected",
             ECONNRESET => "Connection reset",
+            ETIMEDOUT => "Connection timed out",
             ECONNREFUSED => "Connection refused",
             EINPROGRESS => "Operation now in progress",
         }
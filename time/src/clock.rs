@@ -0,0 +1,57 @@
+//! Kernel clocks.
+//!
+//! The hardware counter only tells how long we have been up, so the wall
+//! clock is kept as an offset from it. The offset is seeded from the RTC at
+//! boot and moved by clock_settime/settimeofday/adjtimex afterwards.
+use core::sync::atomic::{AtomicI64, Ordering};
+use arch::time::Time;
+
+pub const NSEC_PER_SEC: usize = 1_000_000_000;
+/// Granularity of the `*_COARSE` clocks: one scheduler tick.
+pub const TICK_NSEC: usize = NSEC_PER_SEC / 100;
+/// TAI runs ahead of UTC by the leap seconds inserted so far.
+pub const TAI_OFFSET_SEC: usize = 37;
+
+/// Nanoseconds between the Unix epoch and boot.
+static REALTIME_OFFSET: AtomicI64 = AtomicI64::new(0);
+
+/// Nanoseconds since boot.
+///
+/// `Time::to_nsec` multiplies before dividing and overflows after a few
+/// minutes of uptime, so split the ticks into whole seconds first.
+pub fn monotonic_nsec() -> usize {
+    let ticks = Time::now().raw();
+    let freq = Time::get_freq();
+    ticks / freq * NSEC_PER_SEC + ticks % freq * NSEC_PER_SEC / freq
+}
+
+/// Nanoseconds since boot. Nothing suspends, so it matches the monotonic clock.
+pub fn boottime_nsec() -> usize {
+    monotonic_nsec()
+}
+
+/// Nanoseconds since the Unix epoch.
+pub fn realtime_nsec() -> usize {
+    let now = monotonic_nsec() as i64 + REALTIME_OFFSET.load(Ordering::Relaxed);
+    now.max(0) as usize
+}
+
+/// Nanoseconds to add to the monotonic clock to get the wall clock.
+pub fn realtime_offset() -> i64 {
+    REALTIME_OFFSET.load(Ordering::Relaxed)
+}
+
+/// Step the wall clock to `nsec` nanoseconds since the Unix epoch.
+pub fn set_realtime_nsec(nsec: usize) {
+    REALTIME_OFFSET.store(nsec as i64 - monotonic_nsec() as i64, Ordering::Relaxed);
+}
+
+/// Shift the wall clock by `delta` nanoseconds.
+pub fn adjust_realtime_nsec(delta: i64) {
+    REALTIME_OFFSET.fetch_add(delta, Ordering::Relaxed);
+}
+
+/// Round a clock reading down to the coarse clock granularity.
+pub fn coarse_nsec(nsec: usize) -> usize {
+    nsec - nsec % TICK_NSEC
+}
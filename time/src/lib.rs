@@ -3,6 +3,12 @@
 
 use arch::time::Time;
 
+mod clock;
+mod rtc;
+
+pub use clock::*;
+pub use rtc::RtcTime;
+
 #[derive(Debug, Clone, Copy, Default)]
 #[repr(C)]
 /// Describes times in seconds and microseconds.
@@ -17,6 +23,37 @@ impl TimeSpec{
     pub fn to_usec(&self)->usize{
         self.sec*1000_000_000+self.usec
     }
+    /// Build a `TimeSpec` from a nanosecond count.
+    pub fn from_nsec(nsec: usize) -> Self {
+        Self {
+            sec: nsec / NSEC_PER_SEC,
+            usec: nsec % NSEC_PER_SEC,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+/// `struct timeval` used by gettimeofday/settimeofday.
+pub struct TimeVal {
+    /// second
+    pub sec: usize,
+    /// microsecond
+    pub usec: usize,
+}
+
+impl TimeVal {
+    /// Build a `TimeVal` from a nanosecond count.
+    pub fn from_nsec(nsec: usize) -> Self {
+        Self {
+            sec: nsec / NSEC_PER_SEC,
+            usec: nsec % NSEC_PER_SEC / 1000,
+        }
+    }
+    /// Convert to nanoseconds.
+    pub fn to_nsec(&self) -> usize {
+        self.sec * NSEC_PER_SEC + self.usec * 1000
+    }
 }
 
 #[repr(C)]
@@ -0,0 +1,92 @@
+//! Broken-down calendar time as used by the RTC ioctls.
+
+const SECS_PER_DAY: i64 = 86400;
+
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+/// `struct rtc_time` from `<linux/rtc.h>`.
+pub struct RtcTime {
+    pub tm_sec: i32,
+    pub tm_min: i32,
+    pub tm_hour: i32,
+    /// Day of the month, 1-based.
+    pub tm_mday: i32,
+    /// Month, 0-based.
+    pub tm_mon: i32,
+    /// Years since 1900.
+    pub tm_year: i32,
+    pub tm_wday: i32,
+    pub tm_yday: i32,
+    pub tm_isdst: i32,
+}
+
+impl RtcTime {
+    /// Break seconds since the Unix epoch down into calendar fields.
+    pub fn from_unix_secs(secs: u64) -> Self {
+        let secs = secs as i64;
+        let days = secs.div_euclid(SECS_PER_DAY);
+        let rem = secs.rem_euclid(SECS_PER_DAY);
+        let (year, month, day) = civil_from_days(days);
+        let yday = days - days_from_civil(year, 1, 1);
+        Self {
+            tm_sec: (rem % 60) as i32,
+            tm_min: (rem / 60 % 60) as i32,
+            tm_hour: (rem / 3600) as i32,
+            tm_mday: day as i32,
+            tm_mon: month as i32 - 1,
+            tm_year: (year - 1900) as i32,
+            // 1970-01-01 was a Thursday
+            tm_wday: (days + 4).rem_euclid(7) as i32,
+            tm_yday: yday as i32,
+            tm_isdst: 0,
+        }
+    }
+
+    /// Seconds since the Unix epoch, or `None` if a field is out of range.
+    pub fn to_unix_secs(&self) -> Option<u64> {
+        if !(0..60).contains(&self.tm_sec)
+            || !(0..60).contains(&self.tm_min)
+            || !(0..24).contains(&self.tm_hour)
+            || !(1..=31).contains(&self.tm_mday)
+            || !(0..12).contains(&self.tm_mon)
+            || self.tm_year < 70
+        {
+            return None;
+        }
+        let days = days_from_civil(
+            self.tm_year as i64 + 1900,
+            self.tm_mon as i64 + 1,
+            self.tm_mday as i64,
+        );
+        let secs = days * SECS_PER_DAY
+            + self.tm_hour as i64 * 3600
+            + self.tm_min as i64 * 60
+            + self.tm_sec as i64;
+        Some(secs as u64)
+    }
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date (month is 1-based).
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Inverse of `days_from_civil`: (year, month, day) with a 1-based month.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
@@ -0,0 +1,128 @@
+use clap::{App, Arg};
+use vfat::{VfatFsType, VfatInode, VfatSuperBlock};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::sync::{Arc, Mutex};
+use vfs_defs::{Dentry, DiskInodeType, File as OtherFile, FileSystemType, Inode, MountFlags, OpenFlags, RenameFlags, SuperBlock};
+use system_result::SysError;
+use device::BlockDevice;
+use buffer::block_cache_sync_all;
+const BLOCK_SZ: usize = 512;
+use crate_interface::impl_interface;
+extern crate logger;
+use logger::*;
+use log::Record;
+struct LogIfImpl;
+
+#[impl_interface]
+impl LogIf for LogIfImpl{
+    fn print_log(record: &Record){
+        println!("{}: {}", record.level(), record.args());
+    }
+}
+#[derive(Debug)]
+struct BlockFile(Mutex<File>, usize);
+
+impl BlockDevice for BlockFile {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        let mut file = self.0.lock().unwrap();
+        file.seek(SeekFrom::Start((block_id * BLOCK_SZ) as u64))
+            .expect("Error when seeking!");
+        assert_eq!(file.read(buf).unwrap(), BLOCK_SZ, "Not a complete block!");
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        let mut file = self.0.lock().unwrap();
+        file.seek(SeekFrom::Start((block_id * BLOCK_SZ) as u64))
+            .expect("Error when seeking!");
+        assert_eq!(file.write(buf).unwrap(), BLOCK_SZ, "Not a complete block!");
+    }
+    fn num_blocks(&self) -> usize {
+        self.1
+    }
+}
+
+/// 对一个 mkfs.vfat 做出来的镜像跑一遍基本操作, 跑完可以用 `fsck.vfat -n` 检查镜像
+fn main() {
+    let matches = App::new("vfat tester")
+        .arg(
+            Arg::with_name("image")
+                .short("i")
+                .long("image")
+                .takes_value(true)
+                .help("FAT image made by mkfs.vfat"),
+        )
+        .get_matches();
+    let path = matches.value_of("image").unwrap();
+    let f = OpenOptions::new().read(true).write(true).open(path).unwrap();
+    let blocks = f.metadata().unwrap().len() as usize / BLOCK_SZ;
+    let dev: Arc<dyn BlockDevice> = Arc::new(BlockFile(Mutex::new(f), blocks));
+    logger::init_logger();
+
+    let root = Arc::new(VfatFsType::new())
+        .mount("/", None, MountFlags::empty(), Some(dev))
+        .unwrap();
+    let sb = root.get_superblock().downcast_arc::<VfatSuperBlock>().map_err(|_| SysError::ENOENT).unwrap();
+    println!("fat type: {:?}, free clusters: {}", sb.fs.bpb.fat_type, sb.free_clusters());
+
+    // 目录和长文件名
+    let dir = root.create("Some Directory", DiskInodeType::Directory).unwrap();
+    assert!(dir.get_inode().unwrap().is_dir());
+    let long_name = "a rather long file name with spaces.tar.gz";
+    let file = dir.create(long_name, DiskInodeType::File).unwrap();
+    let short = dir.create("README.TXT", DiskInodeType::File).unwrap();
+    assert!(dir.create("readme.txt", DiskInodeType::File).is_err());
+
+    // 跨好几个簇的读写
+    let data: Vec<u8> = (0..100_000u32).map(|i| (i * 7 % 251) as u8).collect();
+    let f = file.clone().open(OpenFlags::RDWR);
+    assert_eq!(f.pwrite(0, &data).unwrap(), data.len());
+    let mut back = vec![0u8; data.len()];
+    assert_eq!(f.read_at(0, &mut back), data.len());
+    assert!(back == data);
+    drop(f);
+    let f = short.clone().open(OpenFlags::RDWR);
+    assert_eq!(f.pwrite(5000, b"hole").unwrap(), 4);
+    let mut head = [1u8; 16];
+    f.read_at(0, &mut head);
+    assert!(head.iter().all(|&b| b == 0));
+    drop(f);
+
+    // 截断
+    let inode = file.get_inode().unwrap().downcast_arc::<VfatInode>().map_err(|_| SysError::ENOENT).unwrap();
+    inode.truncate(10).unwrap();
+    assert_eq!(inode.get_size(), 10);
+    inode.truncate(70_000).unwrap();
+    assert_eq!(inode.get_size(), 70_000);
+
+    // 改名: 同目录, 跨目录, 只改大小写
+    let moved = root.new_child("moved to root.tar.gz");
+    root.add_child(moved.clone());
+    file.vfs_rename(&moved, RenameFlags::empty()).unwrap();
+    let upper = dir.new_child("Readme.Txt");
+    dir.add_child(upper.clone());
+    short.vfs_rename(&upper, RenameFlags::empty()).unwrap();
+    let names: Vec<String> = sb.fs.read_dir(0).unwrap().into_iter().map(|e| e.name).collect();
+    println!("root: {:?}", names);
+    assert!(names.iter().any(|n| n == "moved to root.tar.gz"));
+
+    // 删除, 非空目录删不掉
+    let sub = dir.create("sub", DiskInodeType::Directory).unwrap();
+    assert!(root.unlink(&dir).is_err());
+    dir.unlink(&sub).unwrap();
+    dir.unlink(&upper).unwrap();
+
+    // 建很多文件让目录长出新簇
+    for i in 0..300 {
+        dir.create(&format!("file number {}", i), DiskInodeType::File).unwrap();
+    }
+    assert_eq!(sb.fs.read_dir(dir.get_inode().unwrap().downcast_arc::<VfatInode>().map_err(|_| SysError::ENOENT).unwrap().dir_cluster()).unwrap().len(), 300);
+
+    sb.sync_fs().unwrap();
+    println!("free clusters: {}", sb.free_clusters());
+    drop(file);
+    drop(moved);
+    drop(dir);
+    drop(root);
+    block_cache_sync_all();
+}
@@ -0,0 +1,194 @@
+use alloc::{string::String, sync::Arc};
+use system_result::{SysError, SysResult};
+use vfs_defs::{
+    alloc_dentry, Dentry, DentryInner, DentryState, DiskInodeType, File, FileInner, OpenFlags, RenameFlags,
+};
+
+use crate::file::VfatFile;
+use crate::inode::{now_secs, VfatInode};
+use crate::layout::{ShortEntry, ATTR_ARCHIVE, ATTR_DIRECTORY};
+use crate::superblock::VfatSuperBlock;
+
+pub struct VfatDentry {
+    inner: DentryInner,
+}
+
+impl VfatDentry {
+    pub fn new(inner: DentryInner) -> Self {
+        Self { inner }
+    }
+    fn superblock(&self) -> SysResult<Arc<VfatSuperBlock>> {
+        self.get_superblock()
+            .downcast_arc::<VfatSuperBlock>()
+            .map_err(|_| SysError::ENOENT)
+    }
+}
+
+fn vfat_inode(dentry: &dyn Dentry) -> SysResult<Arc<VfatInode>> {
+    dentry
+        .get_inode()?
+        .downcast_arc::<VfatInode>()
+        .map_err(|_| SysError::ENOENT)
+}
+
+impl Dentry for VfatDentry {
+    fn get_inner(&self) -> &DentryInner {
+        &self.inner
+    }
+    fn self_arc(self: Arc<Self>) -> Arc<dyn Dentry> {
+        self.clone()
+    }
+    fn concrete_create(self: Arc<Self>, name: &str, _type: DiskInodeType) -> SysResult<Arc<dyn Dentry>> {
+        let sb = self.superblock()?;
+        let fs = &sb.fs;
+        let dir = vfat_inode(self.as_ref())?;
+        let child = self.clone().get_child(name).ok_or(SysError::ENOENT)?;
+        let _guard = sb.dir_lock.lock();
+        let mut short = ShortEntry::default();
+        short.set_all_times(now_secs());
+        match _type {
+            DiskInodeType::File => short.attr = ATTR_ARCHIVE,
+            DiskInodeType::Directory => {
+                // 新目录至少有一个簇, 放 `.` 和 `..`
+                let cluster = fs.alloc_cluster(None)?;
+                short.attr = ATTR_DIRECTORY;
+                short.first_cluster = cluster;
+                fs.init_dir(cluster, dir.dir_cluster(), &short);
+            }
+            DiskInodeType::None => return Err(SysError::EISDIR),
+        }
+        let entry = fs.add_entry(dir.dir_cluster(), name, short).inspect_err(|_| {
+            if short.first_cluster != 0 {
+                fs.free_clusters(&[short.first_cluster]);
+            }
+        })?;
+        child.set_inode(Arc::new(VfatInode::from_entry(sb.clone(), &entry)));
+        *child.get_state() = DentryState::Valid;
+        Ok(child)
+    }
+    fn concrete_lookup(self: Arc<Self>, name: &str) -> SysResult<Arc<dyn Dentry>> {
+        let sb = self.superblock()?;
+        let dir = vfat_inode(self.as_ref())?;
+        let entry = sb.fs.find_entry(dir.dir_cluster(), name)?.ok_or(SysError::ENOENT)?;
+        let child = self.clone().get_child(name).ok_or(SysError::ENOENT)?;
+        child.set_inode(Arc::new(VfatInode::from_entry(sb.clone(), &entry)));
+        Ok(child)
+    }
+    fn concrete_new_child(self: Arc<Self>, name: &str) -> Arc<dyn Dentry> {
+        let parent: Arc<dyn Dentry> = self.clone();
+        let child = Arc::new(VfatDentry::new(DentryInner::new(String::from(name), self.get_superblock(), Some(parent.clone()))));
+        alloc_dentry(Some(&parent), name, child.clone());
+        child
+    }
+    /// FAT 没有硬链接. mkdir 时 VFS 会链接 `.` 和 `..`, 它们在 `init_dir` 里已经写好了
+    fn concrete_link(self: Arc<Self>, new: &Arc<dyn Dentry>) -> SysResult<()> {
+        match new.get_name_str() {
+            "." | ".." => Ok(()),
+            _ => Err(SysError::EPERM),
+        }
+    }
+    fn concrete_unlink(self: Arc<Self>, old: &Arc<dyn Dentry>) -> SysResult<()> {
+        let sb = self.superblock()?;
+        let fs = &sb.fs;
+        let dir = vfat_inode(self.as_ref())?;
+        let inode = vfat_inode(old.as_ref())?;
+        let _guard = sb.dir_lock.lock();
+        let entry = fs.find_entry(dir.dir_cluster(), old.get_name_str())?.ok_or(SysError::ENOENT)?;
+        if entry.short.is_dir() && !fs.dir_is_empty(entry.short.first_cluster)? {
+            return Err(SysError::ENOTEMPTY);
+        }
+        fs.remove_entry(&entry);
+        inode.release()?;
+        self.get_inner().children.lock().remove(old.get_name_str());
+        Ok(())
+    }
+    /// `self` 是要改名的目录项. 先写新目录项再删旧的, 中途出错时文件还在原处
+    fn concrete_rename(self: Arc<Self>, new: Arc<dyn Dentry>, flags: RenameFlags) -> SysResult<()> {
+        if flags.intersects(RenameFlags::RENAME_EXCHANGE | RenameFlags::RENAME_WHITEOUT) {
+            return Err(SysError::EINVAL);
+        }
+        let sb = self.superblock()?;
+        let fs = &sb.fs;
+        let old_dir = vfat_inode(self.get_father().ok_or(SysError::EBUSY)?.as_ref())?;
+        let new_dir = vfat_inode(new.get_father().ok_or(SysError::EBUSY)?.as_ref())?;
+        let inode = vfat_inode(self.as_ref())?;
+        let _guard = sb.dir_lock.lock();
+        let old_entry = fs.find_entry(old_dir.dir_cluster(), self.get_name_str())?.ok_or(SysError::ENOENT)?;
+        // 只改大小写时新旧名字指向同一个目录项
+        let same_entry = !new.has_no_inode() && Arc::ptr_eq(&vfat_inode(new.as_ref())?, &inode);
+        if same_entry && self.get_name_str() == new.get_name_str() {
+            return Ok(());
+        }
+        if !new.has_no_inode() && !same_entry {
+            let target = vfat_inode(new.as_ref())?;
+            match (inode.is_dir(), target.is_dir()) {
+                (false, true) => return Err(SysError::EISDIR),
+                (true, false) => return Err(SysError::ENOTDIR),
+                _ => {}
+            }
+            let target_entry = fs.find_entry(new_dir.dir_cluster(), new.get_name_str())?.ok_or(SysError::ENOENT)?;
+            if target.is_dir() && !fs.dir_is_empty(target.dir_cluster())? {
+                return Err(SysError::ENOTEMPTY);
+            }
+            fs.remove_entry(&target_entry);
+            target.release()?;
+        }
+        let new_entry = if same_entry {
+            fs.remove_entry(&old_entry);
+            fs.add_entry(new_dir.dir_cluster(), new.get_name_str(), old_entry.short)?
+        } else {
+            let new_entry = fs.add_entry(new_dir.dir_cluster(), new.get_name_str(), old_entry.short)?;
+            fs.remove_entry(&old_entry);
+            new_entry
+        };
+        if inode.is_dir() && old_dir.dir_cluster() != new_dir.dir_cluster() {
+            fs.set_dotdot(inode.dir_cluster(), new_dir.dir_cluster());
+        }
+        inode.set_entry_pos(new_entry.pos);
+        new.set_inode(inode);
+        *new.get_state() = DentryState::Valid;
+        *self.inner.inode.lock() = None;
+        Ok(())
+    }
+    fn concrete_getchild(self: Arc<Self>, name: &str) -> Option<Arc<dyn Dentry>> {
+        let sb = self.superblock().ok()?;
+        let dir = vfat_inode(self.as_ref()).ok()?;
+        sb.fs.find_entry(dir.dir_cluster(), name).ok()??;
+        Some(self.concrete_new_child(name))
+    }
+    fn open(self: Arc<Self>, flags: OpenFlags) -> Arc<dyn File> {
+        let len = self.get_inode().map_or(0, |inode| inode.get_size());
+        let file = Arc::new(VfatFile::new(FileInner::new(self)));
+        if flags.contains(OpenFlags::APPEND) {
+            *file.get_offset() = len as usize;
+        }
+        *file.get_inner().flags.lock() = flags;
+        file
+    }
+    /// 一次读完目录, 给还不在内存里的文件建目录项
+    fn load_dir(self: Arc<Self>) -> SysResult<()> {
+        let sb = self.superblock()?;
+        let dir = vfat_inode(self.as_ref())?;
+        for entry in sb.fs.read_dir(dir.dir_cluster())? {
+            let existing = self.get_inner().children.lock().get(&entry.name).and_then(|child| child.upgrade());
+            let child = match existing {
+                Some(child) if !child.has_no_inode() => continue,
+                Some(child) => child,
+                None => {
+                    let child = self.clone().concrete_new_child(&entry.name);
+                    self.add_child(child.clone());
+                    child
+                }
+            };
+            child.set_inode(Arc::new(VfatInode::from_entry(sb.clone(), &entry)));
+            *child.get_state() = DentryState::Valid;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for VfatDentry {
+    fn drop(&mut self) {
+        self.on_drop();
+    }
+}
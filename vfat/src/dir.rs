@@ -0,0 +1,299 @@
+//! 目录: 目录项的遍历、查找、插入和删除
+//!
+//! 目录就是一串 32 字节的目录项. 长文件名存在紧挨着短目录项前面的若干个长文件名项里,
+//! 序号倒着排, 每项带有短文件名的校验和.
+use alloc::{
+    collections::BTreeSet,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use system_result::{SysError, SysResult};
+
+use crate::fat::FatFs;
+use crate::layout::{
+    check_long_name, lfn_checksum, lfn_count, lfn_encode, lfn_parse, short_name_basis, short_name_exact,
+    short_name_with_tail, FatType, ShortEntry, ATTR_DIRECTORY, ATTR_LONG_NAME, ATTR_VOLUME_ID, DIR_ENTRY_SZ,
+    ENTRY_DELETED, ENTRY_END,
+};
+
+/// 目录最多 65536 项, 见 FAT 规范
+const MAX_DIR_ENTRIES: usize = 65536;
+/// 数字尾巴最大到 ~999999
+const MAX_TAIL: usize = 999_999;
+
+/// 目录里的一个文件
+#[derive(Clone, Debug)]
+pub struct DirEntry {
+    /// 文件名, 有长文件名时是长文件名
+    pub name: String,
+    /// 短目录项
+    pub short: ShortEntry,
+    /// 短目录项在设备上的位置
+    pub pos: u64,
+    /// 占用的所有目录项 (长文件名项和短目录项) 的位置
+    pub slots: Vec<u64>,
+}
+
+impl DirEntry {
+    /// 文件名是否与 `name` 相同, 与 Windows 一样不区分 ASCII 大小写
+    pub fn matches(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name) || self.short.display_name().eq_ignore_ascii_case(name)
+    }
+}
+
+/// 读长文件名时的中间状态
+struct LfnState {
+    chars: Vec<u16>,
+    slots: Vec<u64>,
+    /// 下一个应该出现的序号
+    next: u8,
+    checksum: u8,
+}
+
+impl FatFs {
+    /// 目录在设备上的各段 (起始字节, 长度). `first_cluster` 为 0 表示根目录
+    fn dir_extents(&self, first_cluster: u32) -> SysResult<Vec<(u64, usize)>> {
+        let first = match first_cluster {
+            0 if self.bpb.fat_type == FatType::Fat32 => self.bpb.root_cluster,
+            0 => {
+                let len = self.bpb.root_entries as usize * DIR_ENTRY_SZ;
+                return Ok(vec![(self.bpb.root_dir_offset(), len)]);
+            }
+            cluster => cluster,
+        };
+        let cluster_size = self.cluster_size();
+        Ok(self
+            .chain(first)?
+            .into_iter()
+            .map(|cluster| (self.cluster_pos(cluster), cluster_size))
+            .collect())
+    }
+
+    /// 目录里所有目录项的 (位置, 内容), 到结束标记为止
+    fn dir_slots(&self, first_cluster: u32) -> SysResult<Vec<(u64, [u8; DIR_ENTRY_SZ])>> {
+        let mut slots = Vec::new();
+        for (start, len) in self.dir_extents(first_cluster)? {
+            let mut data = vec![0u8; len];
+            self.read_bytes(start, &mut data);
+            for (i, raw) in data.chunks_exact(DIR_ENTRY_SZ).enumerate() {
+                if raw[0] == ENTRY_END {
+                    return Ok(slots);
+                }
+                slots.push((start + (i * DIR_ENTRY_SZ) as u64, raw.try_into().unwrap()));
+            }
+        }
+        Ok(slots)
+    }
+
+    /// 目录中的文件, 不含 `.`、`..` 和卷标
+    pub fn read_dir(&self, first_cluster: u32) -> SysResult<Vec<DirEntry>> {
+        let mut entries = Vec::new();
+        let mut lfn: Option<LfnState> = None;
+        for (pos, raw) in self.dir_slots(first_cluster)? {
+            if raw[0] == ENTRY_DELETED {
+                lfn = None;
+                continue;
+            }
+            if raw[11] & 0x3f == ATTR_LONG_NAME {
+                let (ord, last, checksum, chars) = lfn_parse(&raw);
+                if last {
+                    lfn = Some(LfnState {
+                        chars: vec![0; ord as usize * chars.len()],
+                        slots: Vec::new(),
+                        next: ord,
+                        checksum,
+                    });
+                }
+                // 序号或校验和对不上的长文件名项是孤立的, 丢掉
+                match lfn.as_mut() {
+                    Some(state) if state.next == ord && state.checksum == checksum && ord != 0 => {
+                        let start = (ord as usize - 1) * chars.len();
+                        state.chars[start..start + chars.len()].copy_from_slice(&chars);
+                        state.slots.push(pos);
+                        state.next -= 1;
+                    }
+                    _ => lfn = None,
+                }
+                continue;
+            }
+            let mut short = ShortEntry::parse(&raw);
+            let state = lfn.take();
+            // FAT12/16 的目录项里簇号高 16 位没有意义
+            if self.bpb.fat_type != FatType::Fat32 {
+                short.first_cluster &= 0xffff;
+            }
+            if short.attr & ATTR_VOLUME_ID != 0 || short.name[0] == b'.' {
+                continue;
+            }
+            let mut slots = Vec::new();
+            let long = state.filter(|s| s.next == 0 && s.checksum == lfn_checksum(&short.name)).and_then(|s| {
+                slots = s.slots;
+                let end = s.chars.iter().position(|&c| c == 0).unwrap_or(s.chars.len());
+                String::from_utf16(&s.chars[..end]).ok()
+            });
+            let name = match long {
+                Some(name) if !name.is_empty() => name,
+                _ => {
+                    slots.clear();
+                    short.display_name()
+                }
+            };
+            slots.push(pos);
+            entries.push(DirEntry { name, short, pos, slots });
+        }
+        Ok(entries)
+    }
+
+    /// 在目录里按名字找文件
+    pub fn find_entry(&self, first_cluster: u32, name: &str) -> SysResult<Option<DirEntry>> {
+        Ok(self.read_dir(first_cluster)?.into_iter().find(|entry| entry.matches(name)))
+    }
+
+    /// 目录里除了 `.` 和 `..` 是否没有别的文件
+    pub fn dir_is_empty(&self, first_cluster: u32) -> SysResult<bool> {
+        Ok(self.read_dir(first_cluster)?.is_empty())
+    }
+
+    /// 在目录里新建名为 `name` 的目录项, `short` 中除文件名外的字段由调用者填好
+    pub fn add_entry(&self, first_cluster: u32, name: &str, mut short: ShortEntry) -> SysResult<DirEntry> {
+        let utf16 = check_long_name(name)?;
+        if self.find_entry(first_cluster, name)?.is_some() {
+            return Err(SysError::EEXIST);
+        }
+        let slots = self.dir_slots(first_cluster)?;
+        let shorts: BTreeSet<[u8; 11]> = slots
+            .iter()
+            .filter(|(_, raw)| raw[0] != ENTRY_DELETED && raw[11] & 0x3f != ATTR_LONG_NAME)
+            .map(|(_, raw)| ShortEntry::parse(raw).name)
+            .collect();
+        // 本身是 8.3 名字的不需要长文件名, 只差大小写的用 NT 标志记下
+        let lfn = match short_name_exact(name) {
+            Some((exact, case)) if !shorts.contains(&exact) => {
+                short.name = exact;
+                short.case = case;
+                None
+            }
+            _ => {
+                let (base, ext) = short_name_basis(name);
+                let base = if base.is_empty() { b"_".to_vec() } else { base };
+                short.name = (1..=MAX_TAIL)
+                    .map(|n| short_name_with_tail(&base, &ext, n))
+                    .find(|candidate| !shorts.contains(candidate))
+                    .ok_or(SysError::ENOSPC)?;
+                short.case = 0;
+                Some(utf16)
+            }
+        };
+        let needed = lfn.as_ref().map_or(0, |name| lfn_count(name)) + 1;
+        let positions = self.find_free_slots(first_cluster, &slots, needed)?;
+        if let Some(utf16) = lfn.as_ref() {
+            let checksum = lfn_checksum(&short.name);
+            let count = lfn_count(utf16);
+            for (i, &pos) in positions[..count].iter().enumerate() {
+                self.write_bytes(pos, &lfn_encode(utf16, count - i, checksum));
+            }
+        }
+        let pos = positions[needed - 1];
+        self.write_bytes(pos, &short.encode());
+        Ok(DirEntry {
+            name: name.to_string(),
+            short,
+            pos,
+            slots: positions,
+        })
+    }
+
+    /// 找 `needed` 个连续的空闲目录项, 不够时给目录加簇
+    fn find_free_slots(
+        &self,
+        first_cluster: u32,
+        slots: &[(u64, [u8; DIR_ENTRY_SZ])],
+        needed: usize,
+    ) -> SysResult<Vec<u64>> {
+        let mut run = Vec::new();
+        for (pos, raw) in slots.iter() {
+            if raw[0] == ENTRY_DELETED {
+                run.push(*pos);
+                if run.len() == needed {
+                    return Ok(run);
+                }
+            } else {
+                run.clear();
+            }
+        }
+        // 结束标记之后的目录项都是空闲的
+        let used = slots.len();
+        let mut all = Vec::new();
+        for (start, len) in self.dir_extents(first_cluster)? {
+            all.extend((0..len / DIR_ENTRY_SZ).map(|i| start + (i * DIR_ENTRY_SZ) as u64));
+        }
+        for &pos in all.iter().skip(used) {
+            run.push(pos);
+            if run.len() == needed {
+                return Ok(run);
+            }
+        }
+        // 固定的根目录不能扩展
+        if first_cluster == 0 && self.bpb.fat_type != FatType::Fat32 {
+            return Err(SysError::ENOSPC);
+        }
+        let first = if first_cluster == 0 { self.bpb.root_cluster } else { first_cluster };
+        let mut last = *self.chain(first)?.last().ok_or(SysError::EIO)?;
+        let per_cluster = self.cluster_size() / DIR_ENTRY_SZ;
+        while run.len() < needed {
+            if all.len() + per_cluster > MAX_DIR_ENTRIES {
+                return Err(SysError::ENOSPC);
+            }
+            let cluster = self.alloc_cluster(Some(last))?;
+            self.zero_bytes(self.cluster_pos(cluster), self.cluster_size());
+            let start = self.cluster_pos(cluster);
+            for i in 0..per_cluster {
+                all.push(start + (i * DIR_ENTRY_SZ) as u64);
+                if run.len() < needed {
+                    run.push(start + (i * DIR_ENTRY_SZ) as u64);
+                }
+            }
+            last = cluster;
+        }
+        Ok(run)
+    }
+
+    /// 删除目录项, 不释放文件的簇
+    pub fn remove_entry(&self, entry: &DirEntry) {
+        for &pos in entry.slots.iter() {
+            self.write_bytes(pos, &[ENTRY_DELETED]);
+        }
+    }
+
+    /// 读出 `pos` 处的短目录项, 修改后写回
+    pub fn update_entry(&self, pos: u64, f: impl FnOnce(&mut ShortEntry)) {
+        let mut raw = [0u8; DIR_ENTRY_SZ];
+        self.read_bytes(pos, &mut raw);
+        let mut short = ShortEntry::parse(&raw);
+        f(&mut short);
+        self.write_bytes(pos, &short.encode());
+    }
+
+    /// 初始化新目录的第一个簇: 清零后写入 `.` 和 `..`. 父目录是根目录时 `..` 的簇号记为 0
+    pub fn init_dir(&self, cluster: u32, parent_cluster: u32, template: &ShortEntry) {
+        let start = self.cluster_pos(cluster);
+        self.zero_bytes(start, self.cluster_size());
+        let mut dot = *template;
+        dot.attr = ATTR_DIRECTORY;
+        dot.case = 0;
+        dot.size = 0;
+        dot.name = *b".          ";
+        dot.first_cluster = cluster;
+        self.write_bytes(start, &dot.encode());
+        dot.name = *b"..         ";
+        dot.first_cluster = parent_cluster;
+        self.write_bytes(start + DIR_ENTRY_SZ as u64, &dot.encode());
+    }
+
+    /// 目录被移到别的父目录下后, 改写它的 `..`
+    pub fn set_dotdot(&self, cluster: u32, parent_cluster: u32) {
+        let pos = self.cluster_pos(cluster) + DIR_ENTRY_SZ as u64;
+        self.update_entry(pos, |short| short.first_cluster = parent_cluster);
+    }
+}
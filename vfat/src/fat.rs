@@ -0,0 +1,302 @@
+//! 设备上的一个 FAT 文件系统: 按字节读写设备, FAT 表项和簇链的分配释放
+use alloc::{sync::Arc, vec, vec::Vec};
+use buffer::{block_cache_sync_dev, get_block_cache, DataBlock};
+use config::DISK_BLOCK_SZ;
+use device::BlockDevice;
+use sync::Mutex;
+use system_result::{SysError, SysResult};
+
+use crate::layout::{BootSector, FatType, FsInfo};
+
+/// FSInfo 中表示 "未知" 的值
+const FSINFO_UNKNOWN: u32 = 0xffff_ffff;
+
+/// 簇分配的状态
+struct AllocState {
+    /// 下次从这里开始找空闲簇
+    next_free: u32,
+    /// 空闲簇数, 未统计时为 None
+    free_count: Option<u32>,
+    /// FSInfo 需要写回
+    dirty: bool,
+}
+
+/// 一个 FAT12/16/32 文件系统
+pub struct FatFs {
+    dev: Arc<dyn BlockDevice>,
+    /// 引导扇区的参数
+    pub bpb: BootSector,
+    alloc: Mutex<AllocState>,
+}
+
+impl FatFs {
+    /// 读出引导扇区, 不是 FAT 或者设备比文件系统小时返回 EINVAL
+    pub fn open(dev: Arc<dyn BlockDevice>) -> SysResult<Self> {
+        let mut sector = vec![0u8; DISK_BLOCK_SZ];
+        dev.read_block(0, &mut sector);
+        let bpb = BootSector::parse(&sector).ok_or(SysError::EINVAL)?;
+        let blocks = dev.num_blocks() as u64;
+        if blocks != 0 && blocks * (DISK_BLOCK_SZ as u64) < bpb.total_bytes() {
+            return Err(SysError::EINVAL);
+        }
+        let fs = Self {
+            dev,
+            bpb,
+            alloc: Mutex::new(AllocState {
+                next_free: 2,
+                free_count: None,
+                dirty: false,
+            }),
+        };
+        if let Some((free, next)) = fs.read_fsinfo() {
+            let mut alloc = fs.alloc.lock();
+            if fs.is_data_cluster(next) {
+                alloc.next_free = next;
+            }
+            if free <= bpb.cluster_count {
+                alloc.free_count = Some(free);
+            }
+        }
+        Ok(fs)
+    }
+
+    /// 所在的块设备
+    pub fn device(&self) -> &Arc<dyn BlockDevice> {
+        &self.dev
+    }
+
+    /// 从设备的 `pos` 字节处读满 `buf`
+    pub fn read_bytes(&self, pos: u64, buf: &mut [u8]) {
+        let mut done = 0;
+        while done < buf.len() {
+            let cur = pos as usize + done;
+            let offset = cur % DISK_BLOCK_SZ;
+            let len = (DISK_BLOCK_SZ - offset).min(buf.len() - done);
+            get_block_cache(cur / DISK_BLOCK_SZ, self.dev.clone())
+                .lock()
+                .read(0, |block: &DataBlock| buf[done..done + len].copy_from_slice(&block[offset..offset + len]));
+            done += len;
+        }
+    }
+
+    /// 把 `data` 写到设备的 `pos` 字节处
+    pub fn write_bytes(&self, pos: u64, data: &[u8]) {
+        let mut done = 0;
+        while done < data.len() {
+            let cur = pos as usize + done;
+            let offset = cur % DISK_BLOCK_SZ;
+            let len = (DISK_BLOCK_SZ - offset).min(data.len() - done);
+            get_block_cache(cur / DISK_BLOCK_SZ, self.dev.clone())
+                .lock()
+                .modify(0, |block: &mut DataBlock| block[offset..offset + len].copy_from_slice(&data[done..done + len]));
+            done += len;
+        }
+    }
+
+    /// 把 [pos, pos + len) 清零
+    pub fn zero_bytes(&self, pos: u64, len: usize) {
+        let zeros = [0u8; DISK_BLOCK_SZ];
+        let mut done = 0;
+        while done < len {
+            let n = (len - done).min(DISK_BLOCK_SZ - (pos as usize + done) % DISK_BLOCK_SZ);
+            self.write_bytes(pos + done as u64, &zeros[..n]);
+            done += n;
+        }
+    }
+
+    /// 簇大小
+    pub fn cluster_size(&self) -> usize {
+        self.bpb.cluster_size()
+    }
+
+    /// 簇在设备上的起始字节
+    pub fn cluster_pos(&self, cluster: u32) -> u64 {
+        self.bpb.data_offset() + (cluster - 2) as u64 * self.cluster_size() as u64
+    }
+
+    /// 是否为数据区里的簇号
+    pub fn is_data_cluster(&self, cluster: u32) -> bool {
+        cluster >= 2 && cluster < self.bpb.cluster_count + 2
+    }
+
+    /// 簇链结束标记
+    fn eoc(&self) -> u32 {
+        match self.bpb.fat_type {
+            FatType::Fat12 => 0xfff,
+            FatType::Fat16 => 0xffff,
+            FatType::Fat32 => 0x0fff_ffff,
+        }
+    }
+
+    fn is_eoc(&self, value: u32) -> bool {
+        value >= self.eoc() - 7
+    }
+
+    /// 第 `index` 个 FAT 中 `cluster` 表项的位置
+    fn fat_entry_pos(&self, index: u32, cluster: u32) -> u64 {
+        let offset = match self.bpb.fat_type {
+            FatType::Fat12 => cluster as u64 + cluster as u64 / 2,
+            FatType::Fat16 => cluster as u64 * 2,
+            FatType::Fat32 => cluster as u64 * 4,
+        };
+        self.bpb.fat_offset(index) + offset
+    }
+
+    /// 读第一个 FAT 中 `cluster` 的表项
+    pub fn fat_entry(&self, cluster: u32) -> u32 {
+        let pos = self.fat_entry_pos(0, cluster);
+        match self.bpb.fat_type {
+            FatType::Fat12 => {
+                let mut raw = [0u8; 2];
+                self.read_bytes(pos, &mut raw);
+                let value = u16::from_le_bytes(raw) as u32;
+                if cluster & 1 == 1 { value >> 4 } else { value & 0xfff }
+            }
+            FatType::Fat16 => {
+                let mut raw = [0u8; 2];
+                self.read_bytes(pos, &mut raw);
+                u16::from_le_bytes(raw) as u32
+            }
+            FatType::Fat32 => {
+                let mut raw = [0u8; 4];
+                self.read_bytes(pos, &mut raw);
+                u32::from_le_bytes(raw) & 0x0fff_ffff
+            }
+        }
+    }
+
+    /// 改写所有 FAT 副本中 `cluster` 的表项
+    fn set_fat_entry(&self, cluster: u32, value: u32) {
+        for index in 0..self.bpb.num_fats {
+            let pos = self.fat_entry_pos(index, cluster);
+            match self.bpb.fat_type {
+                FatType::Fat12 => {
+                    let mut raw = [0u8; 2];
+                    self.read_bytes(pos, &mut raw);
+                    let old = u16::from_le_bytes(raw);
+                    let new = if cluster & 1 == 1 {
+                        (old & 0x000f) | ((value as u16) << 4)
+                    } else {
+                        (old & 0xf000) | (value as u16 & 0x0fff)
+                    };
+                    self.write_bytes(pos, &new.to_le_bytes());
+                }
+                FatType::Fat16 => self.write_bytes(pos, &(value as u16).to_le_bytes()),
+                FatType::Fat32 => {
+                    // 高 4 位保留, 保持原样
+                    let mut raw = [0u8; 4];
+                    self.read_bytes(pos, &mut raw);
+                    let new = (u32::from_le_bytes(raw) & 0xf000_0000) | (value & 0x0fff_ffff);
+                    self.write_bytes(pos, &new.to_le_bytes());
+                }
+            }
+        }
+    }
+
+    /// 从 `first` 开始的簇链, `first` 为 0 时为空. 链里有空闲簇, 坏簇或者成环时返回 EIO
+    pub fn chain(&self, first: u32) -> SysResult<Vec<u32>> {
+        let mut clusters = Vec::new();
+        let mut cur = first;
+        while cur != 0 {
+            if !self.is_data_cluster(cur) || clusters.len() > self.bpb.cluster_count as usize {
+                return Err(SysError::EIO);
+            }
+            clusters.push(cur);
+            let next = self.fat_entry(cur);
+            if self.is_eoc(next) {
+                break;
+            }
+            if next == 0 {
+                return Err(SysError::EIO);
+            }
+            cur = next;
+        }
+        Ok(clusters)
+    }
+
+    /// 分配一个空闲簇并接在 `prev` 后面, 新簇标记为链尾
+    pub fn alloc_cluster(&self, prev: Option<u32>) -> SysResult<u32> {
+        let mut alloc = self.alloc.lock();
+        if alloc.free_count == Some(0) {
+            return Err(SysError::ENOSPC);
+        }
+        let count = self.bpb.cluster_count;
+        let start = alloc.next_free;
+        let cluster = (0..count)
+            .map(|i| 2 + (start - 2 + i) % count)
+            .find(|&cluster| self.fat_entry(cluster) == 0)
+            .ok_or(SysError::ENOSPC)?;
+        self.set_fat_entry(cluster, self.eoc());
+        if let Some(prev) = prev {
+            self.set_fat_entry(prev, cluster);
+        }
+        alloc.next_free = if cluster + 1 < count + 2 { cluster + 1 } else { 2 };
+        alloc.free_count = alloc.free_count.map(|free| free - 1);
+        alloc.dirty = true;
+        Ok(cluster)
+    }
+
+    /// 释放一串簇
+    pub fn free_clusters(&self, clusters: &[u32]) {
+        if clusters.is_empty() {
+            return;
+        }
+        let mut alloc = self.alloc.lock();
+        for &cluster in clusters {
+            self.set_fat_entry(cluster, 0);
+        }
+        alloc.free_count = alloc.free_count.map(|free| free + clusters.len() as u32);
+        alloc.dirty = true;
+    }
+
+    /// 把簇链截成前 `keep` 个, 释放其余的
+    pub fn truncate_chain(&self, clusters: &[u32], keep: usize) {
+        if keep >= clusters.len() {
+            return;
+        }
+        if keep > 0 {
+            self.set_fat_entry(clusters[keep - 1], self.eoc());
+        }
+        self.free_clusters(&clusters[keep..]);
+    }
+
+    /// 空闲簇数, 第一次调用时扫描整个 FAT
+    pub fn free_count(&self) -> u32 {
+        let mut alloc = self.alloc.lock();
+        if let Some(free) = alloc.free_count {
+            return free;
+        }
+        let free = (2..self.bpb.cluster_count + 2)
+            .filter(|&cluster| self.fat_entry(cluster) == 0)
+            .count() as u32;
+        alloc.free_count = Some(free);
+        alloc.dirty = true;
+        free
+    }
+
+    fn read_fsinfo(&self) -> Option<(u32, u32)> {
+        if self.bpb.fat_type != FatType::Fat32 || self.bpb.fsinfo_sector == 0 {
+            return None;
+        }
+        let mut sector = vec![0u8; 512];
+        self.read_bytes(self.fsinfo_pos(), &mut sector);
+        FsInfo::parse(&sector)
+    }
+
+    fn fsinfo_pos(&self) -> u64 {
+        self.bpb.fsinfo_sector as u64 * self.bpb.bytes_per_sector as u64
+    }
+
+    /// 写回 FSInfo 和块缓存
+    pub fn sync(&self) {
+        let mut alloc = self.alloc.lock();
+        if alloc.dirty && self.read_fsinfo().is_some() {
+            let free = alloc.free_count.unwrap_or(FSINFO_UNKNOWN);
+            self.write_bytes(self.fsinfo_pos() + FsInfo::FREE_COUNT as u64, &free.to_le_bytes());
+            self.write_bytes(self.fsinfo_pos() + FsInfo::NEXT_FREE as u64, &alloc.next_free.to_le_bytes());
+        }
+        alloc.dirty = false;
+        drop(alloc);
+        block_cache_sync_dev(&self.dev);
+    }
+}
@@ -0,0 +1,57 @@
+use alloc::sync::Arc;
+use system_result::{SysError, SysResult};
+use vfs_defs::{File, FileInner, OpenFlags, PollEvents};
+
+use crate::inode::VfatInode;
+
+pub struct VfatFile {
+    inner: FileInner,
+}
+
+impl VfatFile {
+    pub fn new(inner: FileInner) -> Self {
+        Self { inner }
+    }
+    fn inode(&self) -> SysResult<Arc<VfatInode>> {
+        self.get_dentry()
+            .get_inode()?
+            .downcast_arc::<VfatInode>()
+            .map_err(|_| SysError::ENOENT)
+    }
+}
+
+impl File for VfatFile {
+    fn get_inner(&self) -> &FileInner {
+        &self.inner
+    }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        self.inode().map_or(0, |inode| inode.read_at(offset, buf))
+    }
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        self.inode().and_then(|inode| inode.write_at(offset, buf)).unwrap_or(0)
+    }
+    fn pwrite(&self, offset: usize, buf: &[u8]) -> SysResult<usize> {
+        self.inode()?.write_at(offset, buf)
+    }
+    /// 盘满时可能只写进去一部分, 不能走默认的 `write`
+    fn write_checked(&self, buf: &[u8]) -> SysResult<usize> {
+        let mut offset = self.get_offset();
+        if self.get_inner().flags.lock().contains(OpenFlags::APPEND) {
+            let (written, end) = self.append(buf)?;
+            *offset = end;
+            return Ok(written);
+        }
+        let written = self.pwrite(*offset, buf)?;
+        *offset += written;
+        Ok(written)
+    }
+    fn readable(&self) -> bool {
+        self.get_inner().flags.lock().read_write().0
+    }
+    fn writable(&self) -> bool {
+        self.get_inner().flags.lock().read_write().1
+    }
+    fn poll(&self, _events: PollEvents) -> PollEvents {
+        PollEvents::POLLIN | PollEvents::POLLOUT
+    }
+}
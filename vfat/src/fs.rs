@@ -0,0 +1,44 @@
+use alloc::{string::{String, ToString}, sync::Arc};
+use device::BlockDevice;
+use system_result::SysResult;
+use vfs_defs::{Dentry, DentryInner, DentryState, FileSystemType, FileSystemTypeInner, MountFlags, SuperBlock, SuperBlockInner};
+
+use crate::{dentry::VfatDentry, inode::VfatInode, superblock::VfatSuperBlock};
+
+pub struct VfatFsType {
+    inner: FileSystemTypeInner,
+}
+
+impl VfatFsType {
+    pub fn new() -> Self {
+        Self {
+            inner: FileSystemTypeInner::new(String::from("vfat")),
+        }
+    }
+}
+
+impl FileSystemType for VfatFsType {
+    fn get_inner(&self) -> &FileSystemTypeInner {
+        &self.inner
+    }
+    fn mount(
+        self: Arc<Self>,
+        name: &str,
+        parent: Option<Arc<dyn Dentry>>,
+        _flags: MountFlags,
+        device: Option<Arc<dyn BlockDevice>>,
+    ) -> SysResult<Arc<dyn Dentry>> {
+        let inner = SuperBlockInner::new(device, self.clone());
+        let superblock = Arc::new(VfatSuperBlock::new(inner)?);
+        let root_inode = Arc::new(VfatInode::new_root(superblock.clone()));
+        let root_dentry = Arc::new(VfatDentry::new(DentryInner::new(name.to_string(), superblock.clone(), parent)));
+        root_dentry.set_inode(root_inode);
+        *root_dentry.get_state() = DentryState::Valid;
+        superblock.set_root_dentry(root_dentry.clone());
+        self.add_superblock(&root_dentry.path(), superblock);
+        Ok(root_dentry)
+    }
+    fn umount(self: Arc<Self>, path: &str, _flags: MountFlags) -> SysResult<()> {
+        self.remove_superblock(path)
+    }
+}
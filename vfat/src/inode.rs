@@ -0,0 +1,364 @@
+//! FAT 上的文件和目录. FAT 没有 inode, 文件的元数据都在它的短目录项里
+use alloc::{sync::Arc, vec::Vec};
+use sync::{Mutex, MutexGuard};
+use system_result::{SysError, SysResult};
+use time::{realtime_nsec, TimeSpec, NSEC_PER_SEC};
+use vfs_defs::{DiskInodeType, Inode, InodeMeta, InodeMode, Kstat, SuperBlock};
+
+use crate::dir::DirEntry;
+use crate::fat::FatFs;
+use crate::layout::{fat_to_unix, ATTR_READ_ONLY, DIR_ENTRY_SZ};
+use crate::superblock::VfatSuperBlock;
+
+/// FAT 不保存权限, 所有文件都按 0755 呈现, 带只读属性的去掉写权限
+const DEFAULT_PERM: u32 = 0o755;
+const WRITE_PERM: u32 = 0o222;
+/// 根目录没有目录项, 用固定的 inode 号
+const ROOT_INO: usize = 1;
+
+/// 现在的 Unix 时间
+pub(crate) fn now_secs() -> u64 {
+    (realtime_nsec() / NSEC_PER_SEC) as u64
+}
+
+fn perm_of(attr: u8) -> u32 {
+    if attr & ATTR_READ_ONLY != 0 {
+        DEFAULT_PERM & !WRITE_PERM
+    } else {
+        DEFAULT_PERM
+    }
+}
+
+pub(crate) struct VfatInodeInner {
+    /// 起始簇, 空文件为 0, 根目录也记为 0
+    first_cluster: u32,
+    size: u32,
+    attr: u8,
+    /// 短目录项的位置, 根目录没有目录项
+    entry: Option<u64>,
+    /// 缓存的簇链, 只有这个 inode 会改它
+    clusters: Option<Vec<u32>>,
+}
+
+pub struct VfatInode {
+    meta: InodeMeta,
+    inner: Mutex<VfatInodeInner>,
+}
+
+impl VfatInode {
+    /// 根目录
+    pub fn new_root(superblock: Arc<dyn SuperBlock>) -> Self {
+        let meta = InodeMeta::new(InodeMode::DIR | InodeMode::from_bits_truncate(DEFAULT_PERM), ROOT_INO, superblock);
+        let inode = Self {
+            meta,
+            inner: Mutex::new(VfatInodeInner {
+                first_cluster: 0,
+                size: 0,
+                attr: 0,
+                entry: None,
+                clusters: None,
+            }),
+        };
+        inode.set_type(DiskInodeType::Directory);
+        inode
+    }
+
+    /// 由目录项建立 inode, 以短目录项在盘上的位置作为 inode 号
+    pub fn from_entry(superblock: Arc<dyn SuperBlock>, entry: &DirEntry) -> Self {
+        let short = &entry.short;
+        let (file_type, disk_type) = if short.is_dir() {
+            (InodeMode::DIR, DiskInodeType::Directory)
+        } else {
+            (InodeMode::FILE, DiskInodeType::File)
+        };
+        let mode = file_type | InodeMode::from_bits_truncate(perm_of(short.attr));
+        let meta = InodeMeta::new(mode, entry.pos as usize / DIR_ENTRY_SZ, superblock);
+        {
+            let mut inner = meta.inner.lock();
+            let mtime = fat_to_unix(short.mdate, short.mtime, 0) as usize;
+            inner.mtime = TimeSpec { sec: mtime, usec: 0 };
+            inner.ctime = inner.mtime;
+            inner.atime = TimeSpec { sec: fat_to_unix(short.adate, 0, 0) as usize, usec: 0 };
+            inner.link = 1;
+        }
+        let inode = Self {
+            meta,
+            inner: Mutex::new(VfatInodeInner {
+                first_cluster: short.first_cluster,
+                size: if short.is_dir() { 0 } else { short.size },
+                attr: short.attr,
+                entry: Some(entry.pos),
+                clusters: None,
+            }),
+        };
+        inode.set_type(disk_type);
+        inode
+    }
+
+    fn superblock(&self) -> SysResult<Arc<VfatSuperBlock>> {
+        self.meta
+            .superblock
+            .upgrade()
+            .ok_or(SysError::ENOENT)?
+            .downcast_arc::<VfatSuperBlock>()
+            .map_err(|_| SysError::ENOENT)
+    }
+
+    /// 目录内容的起始簇, 根目录为 0
+    pub fn dir_cluster(&self) -> u32 {
+        self.inner.lock().first_cluster
+    }
+
+    /// 短目录项现在的位置
+    pub fn entry_pos(&self) -> Option<u64> {
+        self.inner.lock().entry
+    }
+
+    /// 改名后目录项换了位置
+    pub fn set_entry_pos(&self, pos: u64) {
+        self.inner.lock().entry = Some(pos);
+    }
+
+    /// 目录项已经删掉, 释放文件的所有簇
+    pub fn release(&self) -> SysResult<()> {
+        let sb = self.superblock()?;
+        let mut inner = self.inner.lock();
+        let clusters = sb.fs.chain(inner.first_cluster)?;
+        sb.fs.free_clusters(&clusters);
+        inner.first_cluster = 0;
+        inner.size = 0;
+        inner.entry = None;
+        inner.clusters = None;
+        Ok(())
+    }
+
+    fn clusters<'a>(fs: &FatFs, inner: &'a mut VfatInodeInner) -> SysResult<&'a mut Vec<u32>> {
+        if inner.clusters.is_none() {
+            inner.clusters = Some(fs.chain(inner.first_cluster)?);
+        }
+        Ok(inner.clusters.as_mut().unwrap())
+    }
+
+    /// 把簇链加长到能装下 `bytes` 字节, 空间不够时分配到哪算哪并返回 ENOSPC
+    fn reserve(fs: &FatFs, inner: &mut VfatInodeInner, bytes: usize) -> SysResult<()> {
+        let needed = bytes.div_ceil(fs.cluster_size());
+        let mut first = inner.first_cluster;
+        let clusters = Self::clusters(fs, inner)?;
+        let mut result = Ok(());
+        while clusters.len() < needed {
+            match fs.alloc_cluster(clusters.last().copied()) {
+                Ok(cluster) => {
+                    if clusters.is_empty() {
+                        first = cluster;
+                    }
+                    clusters.push(cluster);
+                }
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        inner.first_cluster = first;
+        result
+    }
+
+    /// 按文件内的偏移访问数据, `f` 收到每段的 (设备位置, 在本次访问中的偏移, 长度)
+    fn for_each_extent(fs: &FatFs, clusters: &[u32], offset: usize, len: usize, mut f: impl FnMut(u64, usize, usize)) {
+        let cluster_size = fs.cluster_size();
+        let mut done = 0;
+        while done < len {
+            let pos = offset + done;
+            let Some(&cluster) = clusters.get(pos / cluster_size) else {
+                break;
+            };
+            let in_cluster = pos % cluster_size;
+            let n = (cluster_size - in_cluster).min(len - done);
+            f(fs.cluster_pos(cluster) + in_cluster as u64, done, n);
+            done += n;
+        }
+    }
+
+    /// 把大小、起始簇和属性写回目录项, `modified` 时同时更新修改时间
+    fn write_entry(&self, fs: &FatFs, inner: &VfatInodeInner, modified: bool) {
+        let Some(pos) = inner.entry else {
+            return;
+        };
+        let now = now_secs();
+        let is_dir = self.is_dir();
+        fs.update_entry(pos, |short| {
+            short.first_cluster = inner.first_cluster;
+            short.attr = inner.attr;
+            if !is_dir {
+                short.size = inner.size;
+            }
+            if modified {
+                short.touch(now);
+            }
+        });
+        if modified {
+            let mut meta = self.meta.inner.lock();
+            meta.mtime = TimeSpec { sec: now as usize, usec: 0 };
+            meta.ctime = meta.mtime;
+        }
+    }
+
+    fn lock_inner(&self) -> MutexGuard<VfatInodeInner> {
+        self.inner.lock()
+    }
+
+    /// 从 `offset` 处读文件
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let Ok(sb) = self.superblock() else {
+            return 0;
+        };
+        let mut inner = self.lock_inner();
+        let size = inner.size as usize;
+        if offset >= size {
+            return 0;
+        }
+        let len = buf.len().min(size - offset);
+        let Ok(clusters) = Self::clusters(&sb.fs, &mut inner) else {
+            return 0;
+        };
+        let mut read = 0;
+        Self::for_each_extent(&sb.fs, clusters, offset, len, |pos, done, n| {
+            sb.fs.read_bytes(pos, &mut buf[done..done + n]);
+            read = done + n;
+        });
+        read
+    }
+
+    /// 从 `offset` 处写文件, 中间的空洞补 0. 空间不够时尽量多写, 一点也写不下才返回 ENOSPC
+    pub fn write_at(&self, offset: usize, buf: &[u8]) -> SysResult<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let end = offset.checked_add(buf.len()).ok_or(SysError::EFBIG)?;
+        // 目录项里的大小只有 32 位
+        if end > u32::MAX as usize {
+            return Err(SysError::EFBIG);
+        }
+        let sb = self.superblock()?;
+        let fs = &sb.fs;
+        let mut inner = self.lock_inner();
+        let reserved = Self::reserve(fs, &mut inner, end);
+        let old_size = inner.size as usize;
+        let capacity = Self::clusters(fs, &mut inner)?.len() * fs.cluster_size();
+        if capacity <= offset {
+            self.write_entry(fs, &inner, false);
+            return reserved.map(|_| 0);
+        }
+        let end = end.min(capacity);
+        let clusters = Self::clusters(fs, &mut inner)?;
+        if offset > old_size {
+            Self::for_each_extent(fs, clusters, old_size, offset - old_size, |pos, _, n| fs.zero_bytes(pos, n));
+        }
+        Self::for_each_extent(fs, clusters, offset, end - offset, |pos, done, n| {
+            fs.write_bytes(pos, &buf[done..done + n])
+        });
+        inner.size = inner.size.max(end as u32);
+        self.write_entry(fs, &inner, true);
+        Ok(end - offset)
+    }
+}
+
+impl Inode for VfatInode {
+    fn get_meta(&self) -> &InodeMeta {
+        &self.meta
+    }
+    fn get_attr(&self) -> SysResult<Kstat> {
+        let sb = self.superblock()?;
+        let cluster_size = sb.fs.cluster_size();
+        let inner = self.lock_inner();
+        let blocks = if self.is_dir() {
+            sb.fs.chain(inner.first_cluster).map_or(0, |clusters| clusters.len())
+        } else {
+            (inner.size as usize).div_ceil(cluster_size)
+        };
+        let meta = self.meta.inner.lock();
+        Ok(Kstat {
+            st_dev: 0,
+            st_ino: self.meta.ino as u64,
+            st_mode: meta.mode.bits(),
+            st_nlink: 1,
+            st_uid: 0,
+            st_gid: 0,
+            st_rdev: 0,
+            __pad: 0,
+            st_size: inner.size as u64,
+            st_blksize: cluster_size as u32,
+            __pad2: 0,
+            st_blocks: (blocks * cluster_size / 512) as u64,
+            st_atime_sec: meta.atime.sec as u64,
+            st_atime_nsec: 1000 * meta.atime.usec as u64,
+            st_mtime_sec: meta.mtime.sec as u64,
+            st_mtime_nsec: 1000 * meta.mtime.usec as u64,
+            st_ctime_sec: meta.ctime.sec as u64,
+            st_ctime_nsec: 1000 * meta.ctime.usec as u64,
+            unused: 0,
+        })
+    }
+    fn load_from_disk(&self) {}
+    fn get_size(&self) -> u32 {
+        self.lock_inner().size
+    }
+    fn clear(&self) {}
+    /// 只有写权限能保存下来, 对应目录项的只读属性
+    fn set_perm(&self, perm: InodeMode) -> SysResult<()> {
+        let sb = self.superblock()?;
+        let mut inner = self.lock_inner();
+        if perm.contains(InodeMode::OWNER_WRITE) {
+            inner.attr &= !ATTR_READ_ONLY;
+        } else {
+            inner.attr |= ATTR_READ_ONLY;
+        }
+        self.write_entry(&sb.fs, &inner, false);
+        let mut meta = self.meta.inner.lock();
+        meta.mode = (meta.mode & InodeMode::TYPE_MASK) | InodeMode::from_bits_truncate(perm_of(inner.attr));
+        Ok(())
+    }
+    /// FAT 没有属主, 所有文件都属于 root
+    fn set_owner(&self, uid: u32, gid: u32) -> SysResult<()> {
+        if uid == 0 && gid == 0 {
+            Ok(())
+        } else {
+            Err(SysError::EPERM)
+        }
+    }
+    fn truncate(&self, size: usize) -> SysResult<()> {
+        if size > u32::MAX as usize {
+            return Err(SysError::EFBIG);
+        }
+        let sb = self.superblock()?;
+        let fs = &sb.fs;
+        let mut inner = self.lock_inner();
+        let old_size = inner.size as usize;
+        if size < old_size {
+            let keep = size.div_ceil(fs.cluster_size());
+            let clusters = Self::clusters(fs, &mut inner)?;
+            fs.truncate_chain(clusters, keep);
+            clusters.truncate(keep);
+            if keep == 0 {
+                inner.first_cluster = 0;
+            }
+        } else if size > old_size {
+            let had = Self::clusters(fs, &mut inner)?.len();
+            if let Err(e) = Self::reserve(fs, &mut inner, size) {
+                // 扩不到要求的大小就不扩, 把这次分配的簇还回去
+                let clusters = Self::clusters(fs, &mut inner)?;
+                fs.truncate_chain(clusters, had);
+                clusters.truncate(had);
+                if had == 0 {
+                    inner.first_cluster = 0;
+                }
+                return Err(e);
+            }
+            let clusters = Self::clusters(fs, &mut inner)?;
+            Self::for_each_extent(fs, clusters, old_size, size - old_size, |pos, _, n| fs.zero_bytes(pos, n));
+        }
+        inner.size = size as u32;
+        self.write_entry(fs, &inner, true);
+        Ok(())
+    }
+}
@@ -0,0 +1,539 @@
+//! FAT 的磁盘格式: 引导扇区 (BPB), 短目录项与长文件名 (VFAT LFN) 目录项
+use alloc::{string::String, vec::Vec};
+use system_result::{SysError, SysResult};
+use time::RtcTime;
+
+/// 只读
+pub const ATTR_READ_ONLY: u8 = 0x01;
+/// 隐藏
+pub const ATTR_HIDDEN: u8 = 0x02;
+/// 系统文件
+pub const ATTR_SYSTEM: u8 = 0x04;
+/// 卷标
+pub const ATTR_VOLUME_ID: u8 = 0x08;
+/// 目录
+pub const ATTR_DIRECTORY: u8 = 0x10;
+/// 归档, 新建和修改过的文件都带这一位
+pub const ATTR_ARCHIVE: u8 = 0x20;
+/// 长文件名项的属性
+pub const ATTR_LONG_NAME: u8 = ATTR_READ_ONLY | ATTR_HIDDEN | ATTR_SYSTEM | ATTR_VOLUME_ID;
+
+/// 目录项大小
+pub const DIR_ENTRY_SZ: usize = 32;
+/// 已删除的目录项
+pub const ENTRY_DELETED: u8 = 0xe5;
+/// 目录结束, 之后的目录项都未使用
+pub const ENTRY_END: u8 = 0x00;
+/// 文件名首字节本来是 0xE5 时存成 0x05
+const ENTRY_KANJI: u8 = 0x05;
+
+/// NT 保留字节: 主文件名全小写
+const CASE_LOWER_BASE: u8 = 0x08;
+/// NT 保留字节: 扩展名全小写
+const CASE_LOWER_EXT: u8 = 0x10;
+
+/// 长文件名项序号里表示 "最后一项" 的位
+const LFN_LAST: u8 = 0x40;
+/// 每个长文件名项存放的 UTF-16 字符数
+const LFN_CHARS: usize = 13;
+/// 长文件名项中各段字符的偏移和个数
+const LFN_SLICES: [(usize, usize); 3] = [(1, 5), (14, 6), (28, 2)];
+/// 长文件名最多 255 个 UTF-16 字符
+pub const LFN_MAX: usize = 255;
+
+fn le16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buf[offset], buf[offset + 1]])
+}
+
+fn le32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+/// FAT 表项宽度
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FatType {
+    /// 12 位
+    Fat12,
+    /// 16 位
+    Fat16,
+    /// 32 位, 高 4 位保留
+    Fat32,
+}
+
+/// 引导扇区里的 BIOS 参数块
+#[derive(Clone, Copy, Debug)]
+pub struct BootSector {
+    /// 扇区大小
+    pub bytes_per_sector: u32,
+    /// 每簇扇区数
+    pub sectors_per_cluster: u32,
+    /// 保留扇区数, 第一个 FAT 紧跟其后
+    pub reserved_sectors: u32,
+    /// FAT 的份数
+    pub num_fats: u32,
+    /// FAT12/16 固定根目录的目录项数
+    pub root_entries: u32,
+    /// 总扇区数
+    pub total_sectors: u32,
+    /// 每个 FAT 占的扇区数
+    pub fat_sectors: u32,
+    /// FAT32 根目录的起始簇
+    pub root_cluster: u32,
+    /// FAT32 FSInfo 扇区号, 没有时为 0
+    pub fsinfo_sector: u32,
+    /// 由数据簇数决定的 FAT 类型
+    pub fat_type: FatType,
+    /// 数据区的簇数, 簇号从 2 开始
+    pub cluster_count: u32,
+}
+
+impl BootSector {
+    /// 解析并校验引导扇区, 不是 FAT 时返回 None
+    pub fn parse(sector: &[u8]) -> Option<Self> {
+        if sector[510] != 0x55 || sector[511] != 0xaa {
+            return None;
+        }
+        let bytes_per_sector = le16(sector, 11) as u32;
+        let sectors_per_cluster = sector[13] as u32;
+        let reserved_sectors = le16(sector, 14) as u32;
+        let num_fats = sector[16] as u32;
+        let root_entries = le16(sector, 17) as u32;
+        if !matches!(bytes_per_sector, 512 | 1024 | 2048 | 4096)
+            || !sectors_per_cluster.is_power_of_two()
+            || reserved_sectors == 0
+            || num_fats == 0
+        {
+            return None;
+        }
+        let total_sectors = match le16(sector, 19) {
+            0 => le32(sector, 32),
+            n => n as u32,
+        };
+        let fat16_sectors = le16(sector, 22) as u32;
+        let fat_sectors = match fat16_sectors {
+            0 => le32(sector, 36),
+            n => n,
+        };
+        if total_sectors == 0 || fat_sectors == 0 {
+            return None;
+        }
+        let root_dir_sectors = (root_entries * DIR_ENTRY_SZ as u32).div_ceil(bytes_per_sector);
+        let data_start = reserved_sectors + num_fats * fat_sectors + root_dir_sectors;
+        if data_start >= total_sectors {
+            return None;
+        }
+        let cluster_count = (total_sectors - data_start) / sectors_per_cluster;
+        // 微软的规范: FAT 类型只由簇数决定
+        let fat_type = match cluster_count {
+            0..=4084 => FatType::Fat12,
+            4085..=65524 => FatType::Fat16,
+            _ => FatType::Fat32,
+        };
+        let bits = match fat_type {
+            FatType::Fat12 => 12,
+            FatType::Fat16 => 16,
+            FatType::Fat32 => 32,
+        };
+        // FAT 要能装下所有簇的表项
+        if (fat_sectors as u64 * bytes_per_sector as u64 * 8) / bits < cluster_count as u64 + 2 {
+            return None;
+        }
+        let (root_cluster, fsinfo_sector) = if fat_type == FatType::Fat32 {
+            if fat16_sectors != 0 || root_entries != 0 {
+                return None;
+            }
+            (le32(sector, 44), le16(sector, 48) as u32)
+        } else {
+            if root_entries == 0 {
+                return None;
+            }
+            (0, 0)
+        };
+        Some(Self {
+            bytes_per_sector,
+            sectors_per_cluster,
+            reserved_sectors,
+            num_fats,
+            root_entries,
+            total_sectors,
+            fat_sectors,
+            root_cluster,
+            fsinfo_sector,
+            fat_type,
+            cluster_count,
+        })
+    }
+    /// 簇大小
+    pub fn cluster_size(&self) -> usize {
+        (self.bytes_per_sector * self.sectors_per_cluster) as usize
+    }
+    /// 第 `index` 个 FAT 的起始字节
+    pub fn fat_offset(&self, index: u32) -> u64 {
+        (self.reserved_sectors + index * self.fat_sectors) as u64 * self.bytes_per_sector as u64
+    }
+    /// FAT12/16 固定根目录的起始字节
+    pub fn root_dir_offset(&self) -> u64 {
+        self.fat_offset(self.num_fats)
+    }
+    /// 数据区的起始字节, 即 2 号簇
+    pub fn data_offset(&self) -> u64 {
+        self.root_dir_offset() + (self.root_entries as u64 * DIR_ENTRY_SZ as u64).div_ceil(self.bytes_per_sector as u64) * self.bytes_per_sector as u64
+    }
+    /// 文件系统的字节数
+    pub fn total_bytes(&self) -> u64 {
+        self.total_sectors as u64 * self.bytes_per_sector as u64
+    }
+}
+
+/// FAT32 的 FSInfo 扇区: 空闲簇数和下一个空闲簇的提示
+pub struct FsInfo;
+
+impl FsInfo {
+    const LEAD_SIG: u32 = 0x4161_5252;
+    const STRUCT_SIG: u32 = 0x6141_7272;
+    const TRAIL_SIG: u32 = 0xaa55_0000;
+    /// 空闲簇数的偏移
+    pub const FREE_COUNT: usize = 488;
+    /// 下一个空闲簇的偏移
+    pub const NEXT_FREE: usize = 492;
+    /// 取出 (空闲簇数, 下一个空闲簇), 值为 0xFFFFFFFF 表示未知
+    pub fn parse(sector: &[u8]) -> Option<(u32, u32)> {
+        if le32(sector, 0) != Self::LEAD_SIG || le32(sector, 484) != Self::STRUCT_SIG || le32(sector, 508) != Self::TRAIL_SIG {
+            return None;
+        }
+        Some((le32(sector, Self::FREE_COUNT), le32(sector, Self::NEXT_FREE)))
+    }
+}
+
+/// 短目录项 (8.3 目录项)
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ShortEntry {
+    /// 主文件名和扩展名, 空格填充
+    pub name: [u8; 11],
+    /// 属性
+    pub attr: u8,
+    /// NT 大小写标志
+    pub case: u8,
+    /// 创建时间的 10 毫秒数 (0..200)
+    pub ctime_cs: u8,
+    /// 创建时间
+    pub ctime: u16,
+    /// 创建日期
+    pub cdate: u16,
+    /// 访问日期
+    pub adate: u16,
+    /// 修改时间
+    pub mtime: u16,
+    /// 修改日期
+    pub mdate: u16,
+    /// 起始簇, 空文件为 0
+    pub first_cluster: u32,
+    /// 文件大小, 目录为 0
+    pub size: u32,
+}
+
+impl ShortEntry {
+    /// 从 32 字节的目录项解析
+    pub fn parse(raw: &[u8]) -> Self {
+        let mut name = [0u8; 11];
+        name.copy_from_slice(&raw[..11]);
+        if name[0] == ENTRY_KANJI {
+            name[0] = ENTRY_DELETED;
+        }
+        Self {
+            name,
+            attr: raw[11],
+            case: raw[12],
+            ctime_cs: raw[13],
+            ctime: le16(raw, 14),
+            cdate: le16(raw, 16),
+            adate: le16(raw, 18),
+            mtime: le16(raw, 22),
+            mdate: le16(raw, 24),
+            first_cluster: ((le16(raw, 20) as u32) << 16) | le16(raw, 26) as u32,
+            size: le32(raw, 28),
+        }
+    }
+    /// 编码成 32 字节的目录项
+    pub fn encode(&self) -> [u8; DIR_ENTRY_SZ] {
+        let mut raw = [0u8; DIR_ENTRY_SZ];
+        raw[..11].copy_from_slice(&self.name);
+        if raw[0] == ENTRY_DELETED {
+            raw[0] = ENTRY_KANJI;
+        }
+        raw[11] = self.attr;
+        raw[12] = self.case;
+        raw[13] = self.ctime_cs;
+        raw[14..16].copy_from_slice(&self.ctime.to_le_bytes());
+        raw[16..18].copy_from_slice(&self.cdate.to_le_bytes());
+        raw[18..20].copy_from_slice(&self.adate.to_le_bytes());
+        raw[20..22].copy_from_slice(&((self.first_cluster >> 16) as u16).to_le_bytes());
+        raw[22..24].copy_from_slice(&self.mtime.to_le_bytes());
+        raw[24..26].copy_from_slice(&self.mdate.to_le_bytes());
+        raw[26..28].copy_from_slice(&(self.first_cluster as u16).to_le_bytes());
+        raw[28..32].copy_from_slice(&self.size.to_le_bytes());
+        raw
+    }
+    /// 是否为目录
+    pub fn is_dir(&self) -> bool {
+        self.attr & ATTR_DIRECTORY != 0
+    }
+    /// 按大小写标志还原的 "NAME.EXT" 形式的文件名
+    pub fn display_name(&self) -> String {
+        let mut name = String::new();
+        let lower = |c: u8, flag: u8| if self.case & flag != 0 { c.to_ascii_lowercase() } else { c };
+        for &c in self.name[..8].iter().take_while(|&&c| c != b' ') {
+            name.push(lower(c, CASE_LOWER_BASE) as char);
+        }
+        if self.name[8] != b' ' {
+            name.push('.');
+            for &c in self.name[8..].iter().take_while(|&&c| c != b' ') {
+                name.push(lower(c, CASE_LOWER_EXT) as char);
+            }
+        }
+        name
+    }
+    /// 把创建、修改和访问时间都设为 `secs`
+    pub fn set_all_times(&mut self, secs: u64) {
+        let (date, time, cs) = unix_to_fat(secs);
+        (self.cdate, self.ctime, self.ctime_cs) = (date, time, cs);
+        (self.mdate, self.mtime) = (date, time);
+        self.adate = date;
+    }
+    /// 记下一次修改
+    pub fn touch(&mut self, secs: u64) {
+        let (date, time, _) = unix_to_fat(secs);
+        (self.mdate, self.mtime) = (date, time);
+        self.adate = date;
+        self.attr |= ATTR_ARCHIVE;
+    }
+}
+
+/// 长文件名项的校验和, 由对应短文件名算出
+pub fn lfn_checksum(short: &[u8; 11]) -> u8 {
+    short
+        .iter()
+        .fold(0u8, |sum, &c| ((sum & 1) << 7).wrapping_add(sum >> 1).wrapping_add(c))
+}
+
+/// 长文件名项: (序号, 是否为最后一项, 校验和, 13 个字符)
+pub fn lfn_parse(raw: &[u8]) -> (u8, bool, u8, [u16; LFN_CHARS]) {
+    let mut chars = [0u16; LFN_CHARS];
+    let mut i = 0;
+    for (offset, count) in LFN_SLICES {
+        for k in 0..count {
+            chars[i] = le16(raw, offset + 2 * k);
+            i += 1;
+        }
+    }
+    (raw[0] & !LFN_LAST, raw[0] & LFN_LAST != 0, raw[13], chars)
+}
+
+/// 长文件名需要的目录项个数
+pub fn lfn_count(name: &[u16]) -> usize {
+    name.len().div_ceil(LFN_CHARS)
+}
+
+/// 编码第 `ord` 个 (从 1 开始) 长文件名项, 名字末尾补一个 0 再用 0xFFFF 填满
+pub fn lfn_encode(name: &[u16], ord: usize, checksum: u8) -> [u8; DIR_ENTRY_SZ] {
+    let mut raw = [0u8; DIR_ENTRY_SZ];
+    raw[0] = ord as u8 | if ord == lfn_count(name) { LFN_LAST } else { 0 };
+    raw[11] = ATTR_LONG_NAME;
+    raw[13] = checksum;
+    let start = (ord - 1) * LFN_CHARS;
+    let mut i = 0;
+    for (offset, count) in LFN_SLICES {
+        for k in 0..count {
+            let c = match start + i {
+                pos if pos < name.len() => name[pos],
+                pos if pos == name.len() => 0,
+                _ => 0xffff,
+            };
+            raw[offset + 2 * k..offset + 2 * k + 2].copy_from_slice(&c.to_le_bytes());
+            i += 1;
+        }
+    }
+    raw
+}
+
+/// 检查长文件名并转成 UTF-16
+pub fn check_long_name(name: &str) -> SysResult<Vec<u16>> {
+    if name.is_empty() || name == "." || name == ".." {
+        return Err(SysError::EINVAL);
+    }
+    // Windows 会吞掉末尾的点和空格, 留着它们会得到打不开的文件
+    if name.ends_with('.') || name.ends_with(' ') {
+        return Err(SysError::EINVAL);
+    }
+    if name.chars().any(|c| (c as u32) < 0x20 || "\"*/:<>?\\|".contains(c)) {
+        return Err(SysError::EINVAL);
+    }
+    let utf16: Vec<u16> = name.encode_utf16().collect();
+    if utf16.len() > LFN_MAX {
+        return Err(SysError::ENAMETOOLONG);
+    }
+    Ok(utf16)
+}
+
+/// 短文件名允许的标点
+fn is_short_punct(c: u8) -> bool {
+    b"!#$%&'()-@^_`{}~".contains(&c)
+}
+
+/// 一段 (主文件名或扩展名) 的大小写: None 表示大小写混合, 必须用长文件名
+fn part_case(part: &[u8]) -> Option<bool> {
+    let has_lower = part.iter().any(|c| c.is_ascii_lowercase());
+    let has_upper = part.iter().any(|c| c.is_ascii_uppercase());
+    match (has_lower, has_upper) {
+        (true, true) => None,
+        (lower, _) => Some(lower),
+    }
+}
+
+/// `name` 本身就是合法的 8.3 文件名时, 返回短文件名和 NT 大小写标志
+pub fn short_name_exact(name: &str) -> Option<([u8; 11], u8)> {
+    let bytes = name.as_bytes();
+    let (base, ext) = match name.rfind('.') {
+        Some(dot) => (&bytes[..dot], &bytes[dot + 1..]),
+        None => (bytes, &bytes[..0]),
+    };
+    if base.is_empty() || base.len() > 8 || ext.len() > 3 || (name.contains('.') && ext.is_empty()) {
+        return None;
+    }
+    let valid = |c: &u8| c.is_ascii_alphanumeric() || is_short_punct(*c);
+    if !base.iter().all(valid) || !ext.iter().all(valid) {
+        return None;
+    }
+    let mut case = 0;
+    if part_case(base)? {
+        case |= CASE_LOWER_BASE;
+    }
+    if part_case(ext)? {
+        case |= CASE_LOWER_EXT;
+    }
+    let mut short = [b' '; 11];
+    for (i, c) in base.iter().enumerate() {
+        short[i] = c.to_ascii_uppercase();
+    }
+    for (i, c) in ext.iter().enumerate() {
+        short[8 + i] = c.to_ascii_uppercase();
+    }
+    Some((short, case))
+}
+
+/// 由长文件名生成短文件名的基础部分: 转大写, 去掉空格和多余的点, 非法字符换成下划线.
+/// 返回 (主文件名, 扩展名)
+pub fn short_name_basis(name: &str) -> (Vec<u8>, Vec<u8>) {
+    let convert = |s: &str, max: usize| -> Vec<u8> {
+        s.chars()
+            .filter(|&c| c != ' ' && c != '.')
+            .map(|c| match c {
+                c if c.is_ascii_alphanumeric() => c.to_ascii_uppercase() as u8,
+                c if c.is_ascii() && is_short_punct(c as u8) => c as u8,
+                _ => b'_',
+            })
+            .take(max)
+            .collect()
+    };
+    let trimmed = name.trim_start_matches('.');
+    match trimmed.rfind('.') {
+        Some(dot) => (convert(&trimmed[..dot], 8), convert(&trimmed[dot + 1..], 3)),
+        None => (convert(trimmed, 8), Vec::new()),
+    }
+}
+
+/// 带数字尾巴的短文件名, 如 LONGFI~1.TXT
+pub fn short_name_with_tail(base: &[u8], ext: &[u8], n: usize) -> [u8; 11] {
+    let mut tail = [0u8; 8];
+    let mut len = 0;
+    let mut v = n;
+    while v > 0 {
+        tail[len] = b'0' + (v % 10) as u8;
+        v /= 10;
+        len += 1;
+    }
+    tail[len] = b'~';
+    len += 1;
+    tail[..len].reverse();
+    let keep = base.len().min(8 - len);
+    let mut short = [b' '; 11];
+    short[..keep].copy_from_slice(&base[..keep]);
+    short[keep..keep + len].copy_from_slice(&tail[..len]);
+    short[8..8 + ext.len()].copy_from_slice(ext);
+    short
+}
+
+/// Unix 时间转成 FAT 的 (日期, 时间, 10 毫秒数), 早于 1980 年的记为 1980-01-01
+pub fn unix_to_fat(secs: u64) -> (u16, u16, u8) {
+    let tm = RtcTime::from_unix_secs(secs);
+    if tm.tm_year < 80 {
+        return ((1 << 5) | 1, 0, 0);
+    }
+    let year = (tm.tm_year - 80).min(127) as u16;
+    let date = (year << 9) | (((tm.tm_mon + 1) as u16) << 5) | tm.tm_mday as u16;
+    let time = ((tm.tm_hour as u16) << 11) | ((tm.tm_min as u16) << 5) | (tm.tm_sec as u16 / 2);
+    (date, time, (tm.tm_sec % 2 * 100) as u8)
+}
+
+/// FAT 的日期和时间转成 Unix 时间, 日期无效时为 0
+pub fn fat_to_unix(date: u16, time: u16, cs: u8) -> u64 {
+    let tm = RtcTime {
+        tm_sec: ((time & 0x1f) * 2) as i32 + (cs / 100) as i32,
+        tm_min: ((time >> 5) & 0x3f) as i32,
+        tm_hour: (time >> 11) as i32,
+        tm_mday: (date & 0x1f) as i32,
+        tm_mon: ((date >> 5) & 0xf) as i32 - 1,
+        tm_year: (date >> 9) as i32 + 80,
+        ..Default::default()
+    };
+    tm.to_unix_secs().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lfn_checksum_values() {
+        assert_eq!(lfn_checksum(b"           "), 0xf7);
+        assert_eq!(lfn_checksum(b"FOO     BAR"), 0x53);
+        assert_eq!(lfn_checksum(b"LONGFI~1TXT"), 0xd4);
+    }
+
+    #[test]
+    fn lfn_entry_carries_checksum() {
+        let name: Vec<u16> = "a rather long file name.txt".encode_utf16().collect();
+        let checksum = lfn_checksum(b"ARATHE~1TXT");
+        assert_eq!(lfn_count(&name), 3);
+        let raw = lfn_encode(&name, 3, checksum);
+        let (ord, last, sum, chars) = lfn_parse(&raw);
+        assert_eq!((ord, last, sum), (3, true, checksum));
+        assert_eq!(chars[..1], name[26..]);
+        // 名字后面是一个 0, 其余用 0xFFFF 填满
+        assert_eq!(chars[1], 0);
+        assert!(chars[2..].iter().all(|&c| c == 0xffff));
+        assert!(!lfn_parse(&lfn_encode(&name, 1, checksum)).1);
+    }
+
+    #[test]
+    fn short_name_tails() {
+        assert_eq!(&short_name_with_tail(b"LONGFILE", b"TXT", 1), b"LONGFI~1TXT");
+        assert_eq!(&short_name_with_tail(b"LONGFILE", b"TXT", 10), b"LONGF~10TXT");
+        assert_eq!(&short_name_with_tail(b"LONGFILE", b"", 123456), b"L~123456   ");
+        // 主文件名够短时尾巴直接接在后面
+        assert_eq!(&short_name_with_tail(b"AB", b"C", 2), b"AB~2    C  ");
+    }
+
+    #[test]
+    fn short_name_bases() {
+        let basis = |name: &str| {
+            let (base, ext) = short_name_basis(name);
+            (String::from_utf8(base).unwrap(), String::from_utf8(ext).unwrap())
+        };
+        assert_eq!(basis("long file name.txt.bak"), ("LONGFILE".into(), "BAK".into()));
+        assert_eq!(basis(".bashrc"), ("BASHRC".into(), String::new()));
+        assert_eq!(basis("a+b.jpeg"), ("A_B".into(), "JPE".into()));
+        assert_eq!(short_name_exact("readme.md"), Some((*b"README  MD ", CASE_LOWER_BASE | CASE_LOWER_EXT)));
+        assert_eq!(short_name_exact("ReadMe.md"), None);
+    }
+}
@@ -0,0 +1,19 @@
+//! FAT12/16/32 文件系统, 支持 VFAT 长文件名
+#![no_std]
+extern crate alloc;
+
+mod dentry;
+mod dir;
+mod fat;
+mod file;
+mod fs;
+mod inode;
+mod layout;
+mod superblock;
+
+pub use dentry::VfatDentry;
+pub use file::VfatFile;
+pub use fs::VfatFsType;
+pub use inode::VfatInode;
+pub use layout::FatType;
+pub use superblock::VfatSuperBlock;
@@ -0,0 +1,58 @@
+use sync::Mutex;
+use system_result::{SysError, SysResult};
+use vfs_defs::{StatFs, SuperBlock, SuperBlockInner};
+
+use crate::fat::FatFs;
+
+/// statfs 报告的 f_type, 和 Linux 的 MSDOS_SUPER_MAGIC 一致
+const MSDOS_SUPER_MAGIC: i64 = 0x4d44;
+/// 长文件名的最大长度
+const NAME_MAX: isize = 255;
+
+pub struct VfatSuperBlock {
+    inner: SuperBlockInner,
+    pub fs: FatFs,
+    /// 改目录内容 (新建、删除、改名) 时持有, 防止两个操作抢到同一组空闲目录项
+    pub dir_lock: Mutex<()>,
+}
+
+impl VfatSuperBlock {
+    pub fn new(inner: SuperBlockInner) -> SysResult<Self> {
+        let dev = inner.dev.clone().ok_or(SysError::EINVAL)?;
+        let fs = FatFs::open(dev)?;
+        Ok(Self {
+            inner,
+            fs,
+            dir_lock: Mutex::new(()),
+        })
+    }
+    /// 空闲簇数
+    pub fn free_clusters(&self) -> u32 {
+        self.fs.free_count()
+    }
+}
+
+impl SuperBlock for VfatSuperBlock {
+    fn get_inner(&self) -> &SuperBlockInner {
+        &self.inner
+    }
+    fn sync_fs(&self) -> SysResult<()> {
+        self.fs.sync();
+        Ok(())
+    }
+    fn statfs(&self) -> SysResult<StatFs> {
+        // 和 Linux 一样以簇为块, FAT 没有 inode 表所以文件数报 0
+        let cluster_size = self.fs.cluster_size() as i64;
+        let free = self.free_clusters() as u64;
+        Ok(StatFs {
+            f_type: MSDOS_SUPER_MAGIC,
+            f_bsize: cluster_size,
+            f_blocks: self.fs.bpb.cluster_count as u64,
+            f_bfree: free,
+            f_bavail: free,
+            f_namelen: NAME_MAX,
+            f_frsize: cluster_size as isize,
+            ..Default::default()
+        })
+    }
+}
@@ -3,8 +3,9 @@ use alloc::{
     string::String,
     sync::{Arc, Weak},
 };
-use crate::{inode::{DiskInodeType, Inode}, SuperBlock,intenal_to_leaf,dcache_lookup,dcache_drop};
+use crate::{inode::{DiskInodeType, Inode, InodeMode}, SuperBlock,intenal_to_leaf,dcache_lookup,dcache_drop};
 use sync::{Mutex,MutexGuard};
+use downcast_rs::{impl_downcast, DowncastSync};
 use system_result::{SysError,SysResult};
 use super::{File,OpenFlags,RenameFlags};
 const MODULE_LEVEL:log::Level = log::Level::Debug;
@@ -53,7 +54,7 @@ impl DentryInner{
     }
 }
 ///
-pub trait Dentry: Send + Sync {
+pub trait Dentry: Send + Sync + DowncastSync {
     ///
     fn get_inner(&self) -> &DentryInner;
     /// If the dentry itself has a negative child with `name`, it will create an
@@ -71,6 +72,16 @@ pub trait Dentry: Send + Sync {
     fn concrete_rename(self: Arc<Self>, new: Arc<dyn Dentry>, flags: RenameFlags) -> SysResult<()>;
     ///
     fn concrete_getchild(self:Arc<Self>, name: &str) -> Option<Arc<dyn Dentry>>;
+    /// Turn the negative child `name` into a symbolic link to `target`.
+    /// File systems without symlink support keep the default.
+    fn concrete_symlink(self: Arc<Self>, _name: &str, _target: &str) -> SysResult<Arc<dyn Dentry>> {
+        Err(SysError::EPERM)
+    }
+    /// Turn the negative child `name` into a FIFO, socket or device node.
+    /// `mode` carries both the file type and the permission bits.
+    fn concrete_mknod(self: Arc<Self>, _name: &str, _mode: InodeMode, _rdev: u64) -> SysResult<Arc<dyn Dentry>> {
+        Err(SysError::EPERM)
+    }
     /// get a clone of self inode
     fn get_inode(&self) -> SysResult<Arc<dyn Inode>> {
         self.get_inner()
@@ -152,6 +163,21 @@ pub trait Dentry: Send + Sync {
             String::from("/")
         }
     }
+    /// 相对所在文件系统根目录的路径, 文件系统按这个路径访问磁盘
+    fn fs_path(&self) -> String {
+        let sb = self.get_inner().superblock.as_ptr() as *const ();
+        match self.get_father() {
+            Some(p) if p.get_inner().superblock.as_ptr() as *const () == sb => {
+                let p_path = p.fs_path();
+                if p_path == "/" {
+                    p_path + self.get_name_str()
+                } else {
+                    p_path + "/" + self.get_name_str()
+                }
+            }
+            _ => String::from("/"),
+        }
+    }
     ///
     fn open(self:Arc<Self>,flags:OpenFlags)->Arc<dyn File>;    
     ///
@@ -180,6 +206,8 @@ pub trait Dentry: Send + Sync {
     
 }
 
+impl_downcast!(sync Dentry);
+
 impl dyn Dentry{    
     ///
     pub fn has_no_inode(&self) -> bool {
@@ -237,6 +265,36 @@ impl dyn Dentry{
         drop(state);
         Ok(child)
     }
+    /// Create a symbolic link `name` pointing to `target` under current dentry
+    pub fn symlink(self: &Arc<Self>, name: &str, target: &str) -> SysResult<Arc<dyn Dentry>> {
+        if !self.get_inode()?.is_dir() {
+            return Err(SysError::ENOTDIR);
+        }
+        if self.lookup(name).is_ok() {
+            return Err(SysError::EEXIST);
+        }
+        let child = self.find_or_create(name, DiskInodeType::File);
+        self.clone().concrete_symlink(name, target)?;
+        let mut state = self.get_state();
+        *state = DentryState::Dirty;
+        drop(state);
+        Ok(child)
+    }
+    /// Create a special file `name` under current dentry
+    pub fn mknod(self: &Arc<Self>, name: &str, mode: InodeMode, rdev: u64) -> SysResult<Arc<dyn Dentry>> {
+        if !self.get_inode()?.is_dir() {
+            return Err(SysError::ENOTDIR);
+        }
+        if self.lookup(name).is_ok() {
+            return Err(SysError::EEXIST);
+        }
+        let child = self.find_or_create(name, DiskInodeType::File);
+        self.clone().concrete_mknod(name, mode, rdev)?;
+        let mut state = self.get_state();
+        *state = DentryState::Dirty;
+        drop(state);
+        Ok(child)
+    }
     ///link self inode to new dentry
     pub fn link(self: &Arc<Self>, new: &Arc<dyn Dentry>) -> SysResult<()> {
         if self.has_no_inode() {
@@ -1,11 +1,12 @@
 use alloc::sync::Arc;
 use core::sync::atomic::{AtomicUsize,Ordering};
 use sync::{Mutex, MutexGuard};
-use super::{Dentry,Inode,DentryState,PollEvents};
+use super::{Dentry,Inode,DentryState,PollEvents,PollQueue};
 use bitflags::*;
 use alloc::vec::Vec;
 use system_result::{SysResult,SysError};
 use crate::Kstat;
+use downcast_rs::{impl_downcast, DowncastSync};
 const MODULE_LEVEL:log::Level = log::Level::Debug;
 
 bitflags! {
@@ -110,7 +111,7 @@ impl FileInner{
 }
 
 ///
-pub trait File: Send + Sync{
+pub trait File: Send + Sync + DowncastSync{
     ///
     fn get_inner(&self)->&FileInner;
 
@@ -140,11 +141,48 @@ pub trait File: Send + Sync{
     /// Write `buf` to file
     fn write(&self, buf: &[u8]) -> usize{
         let mut offset = self.get_offset();
+        if self.get_inner().flags.lock().contains(OpenFlags::APPEND) {
+            return match self.append(buf) {
+                Ok((write_size, end)) => {
+                    *offset = end;
+                    write_size
+                }
+                Err(_) => 0,
+            };
+        }
         let write_size = self.write_at(*offset, buf);
         assert_eq!(write_size, buf.len());
         *offset += write_size;
         write_size
     }
+    /// Read at `offset` without touching the file offset. Files that
+    /// cannot seek (pipes, terminals, anonymous files) return ESPIPE.
+    fn pread(&self, offset: usize, buf: &mut [u8]) -> SysResult<usize> {
+        Ok(self.read_at(offset, buf))
+    }
+    /// Write at `offset` without touching the file offset, see [`File::pread`]
+    fn pwrite(&self, offset: usize, buf: &[u8]) -> SysResult<usize> {
+        Ok(self.write_at(offset, buf))
+    }
+    /// Write at the end of file. Reading the size and writing happen under
+    /// the inode write lock so that concurrent appenders don't overlap.
+    /// Returns the bytes written and the new end of file.
+    fn append(&self, buf: &[u8]) -> SysResult<(usize, usize)> {
+        let inode = self.get_dentry().get_inode()?;
+        let _guard = inode.get_meta().write_lock.lock();
+        let size = inode.get_size() as usize;
+        let write_size = self.pwrite(size, buf)?;
+        Ok((write_size, size + write_size))
+    }
+    /// Read with an error channel. Files that can fail with EAGAIN, EPIPE
+    /// or EINTR (pipes and the like) override this, others keep the default.
+    fn read_checked(&self, buf: &mut [u8]) -> SysResult<usize> {
+        Ok(self.read(buf))
+    }
+    /// Write with an error channel, see [`File::read_checked`]
+    fn write_checked(&self, buf: &[u8]) -> SysResult<usize> {
+        Ok(self.write(buf))
+    }
     /// Read all data inside a inode into vector
     fn read_all(&self) -> Vec<u8> {
         let mut offset = self.get_offset();
@@ -201,8 +239,19 @@ pub trait File: Send + Sync{
     }
     ///
     fn poll(&self, events: PollEvents) -> PollEvents;
+    /// Queue notified whenever the result of `poll` may change. Files that
+    /// cannot notify keep the default and are re-polled by their waiters.
+    fn poll_queue(&self) -> Option<&PollQueue> {
+        None
+    }
+    /// Device specific control. `arg` is an unchecked user address, copy it with `vfs::read_user`/`vfs::write_user`.
+    fn ioctl(&self, _cmd: usize, _arg: usize) -> SysResult<isize> {
+        Err(SysError::ENOTTY)
+    }
 }
 
+impl_downcast!(sync File);
+
 impl dyn File{
 
     
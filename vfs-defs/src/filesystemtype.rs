@@ -7,10 +7,45 @@ use super::SuperBlock;
 use sync::Mutex;
 ///
 bitflags::bitflags! {
-    ///
-    #[derive(Debug)]
+    /// mount(2) 的 flags, 取值与 Linux 一致
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub struct MountFlags:u32 {
-}
+        /// 只读挂载
+        const MS_RDONLY = 1;
+        /// 忽略 set-user-ID/set-group-ID 位
+        const MS_NOSUID = 1 << 1;
+        /// 禁止访问设备文件
+        const MS_NODEV = 1 << 2;
+        /// 禁止执行程序
+        const MS_NOEXEC = 1 << 3;
+        ///
+        const MS_SYNCHRONOUS = 1 << 4;
+        /// 修改已有挂载点的选项
+        const MS_REMOUNT = 1 << 5;
+        ///
+        const MS_MANDLOCK = 1 << 6;
+        ///
+        const MS_DIRSYNC = 1 << 7;
+        ///
+        const MS_NOATIME = 1 << 10;
+        ///
+        const MS_NODIRATIME = 1 << 11;
+        /// 把已有目录树绑定到另一处
+        const MS_BIND = 1 << 12;
+        ///
+        const MS_MOVE = 1 << 13;
+        ///
+        const MS_REC = 1 << 14;
+        ///
+        const MS_SILENT = 1 << 15;
+        ///
+        const MS_RELATIME = 1 << 21;
+        /// 挂载点自身保存的选项, 其余位只影响本次操作
+        const MS_PER_MOUNT = Self::MS_RDONLY.bits() | Self::MS_NOSUID.bits()
+            | Self::MS_NODEV.bits() | Self::MS_NOEXEC.bits() | Self::MS_SYNCHRONOUS.bits()
+            | Self::MS_DIRSYNC.bits() | Self::MS_NOATIME.bits() | Self::MS_NODIRATIME.bits()
+            | Self::MS_RELATIME.bits();
+    }
 }
 
 ///
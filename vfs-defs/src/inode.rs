@@ -1,9 +1,11 @@
 use sync::{Mutex, MutexGuard};
 use alloc::sync::{Weak,Arc};
+use alloc::string::String;
+use alloc::vec::Vec;
 use downcast_rs::{impl_downcast, DowncastSync};
-use system_result::SysResult;
+use system_result::{SysError, SysResult};
 use time::*;
-use crate::Kstat;
+use crate::{Kstat, XattrFlags};
 
 use super::SuperBlock;
 /// Type of a disk inode
@@ -123,6 +125,8 @@ pub struct InodeMeta {
     pub _type:Mutex<DiskInodeType>,
     ///
     pub mode:InodeMode,
+    /// 追加写时持有, 保证取文件大小和写入之间不被其他写者插入
+    pub write_lock:Mutex<()>,
 }
 ///
 pub struct InodeMetaInner {
@@ -136,31 +140,84 @@ pub struct InodeMetaInner {
     pub mtime: TimeSpec,
     /// Last status change time.
     pub ctime: TimeSpec, 
-
+    /// 类型和权限位, chmod 只改权限位
+    pub mode: InodeMode,
+    /// 属主
+    pub uid: u32,
+    /// 属组
+    pub gid: u32,
+    /// 字符/块设备节点对应的设备号
+    pub rdev: u64,
+    /// memfd 的封印, 不支持封印的文件为 None
+    pub seals: Option<FileSeals>,
 }
 impl InodeMetaInner{
     ///
-    pub fn new()->Self{
+    pub fn new(mode:InodeMode)->Self{
         Self{
             size:0,
             link:0,
             atime:TimeSpec::default(),
             mtime:TimeSpec::default(),
-            ctime:TimeSpec::default()
+            ctime:TimeSpec::default(),
+            mode,
+            uid:0,
+            gid:0,
+            rdev:0,
+            seals:None,
         }
     }
 }
 
+bitflags::bitflags! {
+    /// fcntl(F_ADD_SEALS) 的封印
+    #[derive(Debug, Clone, Copy, Eq, PartialEq)]
+    pub struct FileSeals: u32 {
+        /// 不能再添加封印
+        const SEAL = 0x1;
+        /// 不能缩小
+        const SHRINK = 0x2;
+        /// 不能增大
+        const GROW = 0x4;
+        /// 不能写
+        const WRITE = 0x8;
+        /// 已有的可写映射仍可写, 之后不能再写
+        const FUTURE_WRITE = 0x10;
+    }
+}
+
+/// 按 glibc 的 dev_t 编码组合主次设备号
+pub fn makedev(major: u32, minor: u32) -> u64 {
+    let (major, minor) = (major as u64, minor as u64);
+    ((major & 0xffff_f000) << 32) | ((major & 0xfff) << 8) | ((minor & 0xffff_ff00) << 12) | (minor & 0xff)
+}
+/// 取出主设备号
+pub fn major(dev: u64) -> u32 {
+    (((dev >> 32) & 0xffff_f000) | ((dev >> 8) & 0xfff)) as u32
+}
+/// 取出次设备号
+pub fn minor(dev: u64) -> u32 {
+    (((dev >> 12) & 0xffff_ff00) | (dev & 0xff)) as u32
+}
+
 impl InodeMeta {
     ///
+    /// 没有给出权限位的 inode(内核建的设备和目录)按 0o777 处理
     pub fn new(mode:InodeMode,ino:usize,superblock:Arc<dyn SuperBlock>)->Self{
+        let perm_mask = InodeMode::all().difference(InodeMode::TYPE_MASK);
+        let full_mode = if mode.intersects(perm_mask) {
+            mode
+        } else {
+            mode | InodeMode::OWNER_MASK | InodeMode::GROUP_MASK | InodeMode::OTHER_MASK
+        };
         Self{
             ino,
             superblock:Arc::downgrade(&superblock),
-            inner:Mutex::new(InodeMetaInner::new()),
+            inner:Mutex::new(InodeMetaInner::new(full_mode)),
             state:Mutex::new(InodeState::Invalid),
             _type:Mutex::new(DiskInodeType::None),
-            mode
+            mode,
+            write_lock:Mutex::new(()),
         }
     }
 }
@@ -208,6 +265,57 @@ pub trait Inode: Send + Sync+ DowncastSync {
 
     ///
     fn clear(&self);
+    /// chmod: 保留文件类型, 把权限位(含 setuid/setgid/sticky)换成 `perm`
+    fn set_perm(&self, perm: InodeMode) -> SysResult<()> {
+        let mut inner = self.get_meta().inner.lock();
+        inner.mode = (inner.mode & InodeMode::TYPE_MASK) | perm.difference(InodeMode::TYPE_MASK);
+        Ok(())
+    }
+    /// chown
+    fn set_owner(&self, uid: u32, gid: u32) -> SysResult<()> {
+        let mut inner = self.get_meta().inner.lock();
+        inner.uid = uid;
+        inner.gid = gid;
+        Ok(())
+    }
+    /// utimensat: 改访问时间和修改时间, None 的保持不变, ctime 总是更新
+    fn set_times(&self, atime: Option<TimeSpec>, mtime: Option<TimeSpec>, ctime: TimeSpec) -> SysResult<()> {
+        let mut inner = self.get_meta().inner.lock();
+        if let Some(atime) = atime {
+            inner.atime = atime;
+        }
+        if let Some(mtime) = mtime {
+            inner.mtime = mtime;
+        }
+        inner.ctime = ctime;
+        Ok(())
+    }
+    /// 把普通文件的大小改成 `size`: 缩小时释放多出的块, 变大时多出的部分读出来是 0.
+    /// 不支持改变大小的 inode 返回 EINVAL
+    fn truncate(&self, _size: usize) -> SysResult<()> {
+        Err(SysError::EINVAL)
+    }
+    /// 符号链接的内容, 其他类型的文件返回 EINVAL
+    fn read_link(&self) -> SysResult<String> {
+        Err(SysError::EINVAL)
+    }
+    /// 扩展属性 `name` (带命名空间前缀) 的值, 不存在时返回 ENODATA.
+    /// 命名空间的权限由调用者检查, 不支持扩展属性的文件系统返回 EOPNOTSUPP
+    fn get_xattr(&self, _name: &str) -> SysResult<Vec<u8>> {
+        Err(SysError::EOPNOTSUPP)
+    }
+    /// 新建或替换扩展属性, `flags` 的检查见 [`XattrFlags::check`]
+    fn set_xattr(&self, _name: &str, _value: &[u8], _flags: XattrFlags) -> SysResult<()> {
+        Err(SysError::EOPNOTSUPP)
+    }
+    /// 所有扩展属性的名字
+    fn list_xattr(&self) -> SysResult<Vec<String>> {
+        Err(SysError::EOPNOTSUPP)
+    }
+    /// 删除扩展属性, 不存在时返回 ENODATA
+    fn remove_xattr(&self, _name: &str) -> SysResult<()> {
+        Err(SysError::EOPNOTSUPP)
+    }
 }
 impl dyn Inode{
 
@@ -8,14 +8,17 @@ mod superblock;
 mod filesystemtype;
 mod file;
 mod dentry_cache;
+mod poll;
 #[macro_use]
 extern crate logger;
 pub use filesystemtype::{FileSystemType,FileSystemTypeInner,MountFlags};
 pub use dentry::{Dentry,DentryInner,DentryState};
 pub use superblock::{SuperBlock,SuperBlockInner};
-pub use inode::{Inode,InodeMeta,InodeMetaInner,DiskInodeType,InodeState,InodeMode};
+pub use inode::{Inode,InodeMeta,InodeMetaInner,DiskInodeType,InodeState,InodeMode,FileSeals,makedev,major,minor};
 pub use file::{File,FileInner,OpenFlags,UserBuffer,UserBufferIterator,SeekFlags};
+pub use poll::{PollQueue,PollWaiter};
 pub use dentry_cache::{DENTRY_CACHE_MANAGER,alloc_dentry,intenal_to_leaf,dcache_lookup,dcache_drop,dcache_sync_call};
+use system_result::{SysError, SysResult};
 #[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
 #[repr(C)]
 ///
@@ -106,6 +109,16 @@ bitflags::bitflags! {
         const POLLHUP = 0x010;
         /// Invalid poll request.
         const POLLINVAL = 0x020;
+        /// Normal data may be read.
+        const POLLRDNORM = 0x040;
+        /// Priority data may be read.
+        const POLLRDBAND = 0x080;
+        /// Writing normal data will not block.
+        const POLLWRNORM = 0x100;
+        /// Priority data may be written.
+        const POLLWRBAND = 0x200;
+        /// Peer closed its writing half.
+        const POLLRDHUP = 0x2000;
     }
 }
 
@@ -122,6 +135,30 @@ bitflags::bitflags! {
     }
 }
 
+bitflags::bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    /// setxattr 的 flags
+    pub struct XattrFlags: i32 {
+        /// 属性已存在时返回 EEXIST
+        const CREATE = 1;
+        /// 属性不存在时返回 ENODATA
+        const REPLACE = 2;
+    }
+}
+
+impl XattrFlags {
+    /// 按属性是否已经存在检查 CREATE/REPLACE
+    pub fn check(self, exists: bool) -> SysResult<()> {
+        if exists && self.contains(Self::CREATE) {
+            return Err(SysError::EEXIST);
+        }
+        if !exists && self.contains(Self::REPLACE) {
+            return Err(SysError::ENODATA);
+        }
+        Ok(())
+    }
+}
+
 use lazy_static::*;
 use sync::Mutex;
 ///Pid Allocator struct
@@ -0,0 +1,77 @@
+//! 文件就绪通知
+//!
+//! 文件状态可能变化时调用 [`PollQueue::notify`], 挂在上面的等待方
+//! (阻塞在 poll 里的任务、epoll 实例)被唤醒后重新调用 `File::poll` 确认.
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use sync::Mutex;
+use crate::PollEvents;
+
+/// 等待文件事件的一方
+pub trait PollWaiter: Send + Sync {
+    /// 文件上可能发生了 `events`
+    fn wake(&self, events: PollEvents);
+}
+
+struct PollEntry {
+    /// 等待方的地址, 用于去重和摘除
+    key: usize,
+    waiter: Weak<dyn PollWaiter>,
+    exclusive: bool,
+}
+
+/// 文件的等待队列
+pub struct PollQueue {
+    waiters: Mutex<Vec<PollEntry>>,
+}
+
+impl PollQueue {
+    /// 空队列
+    pub const fn new() -> Self {
+        Self {
+            waiters: Mutex::new(Vec::new()),
+        }
+    }
+    /// 挂上等待方, 重复挂同一个等待方不会被唤醒两次.
+    /// `exclusive` 的等待方每次通知只唤醒其中第一个(EPOLLEXCLUSIVE)
+    pub fn register(&self, waiter: &Arc<dyn PollWaiter>, exclusive: bool) {
+        let mut waiters = self.waiters.lock();
+        if waiters.iter().any(|entry| entry.key == waiter_key(waiter)) {
+            return;
+        }
+        waiters.push(PollEntry {
+            key: waiter_key(waiter),
+            waiter: Arc::downgrade(waiter),
+            exclusive,
+        });
+    }
+    /// 摘下等待方
+    pub fn unregister(&self, waiter: &Arc<dyn PollWaiter>) {
+        self.waiters.lock().retain(|entry| entry.key != waiter_key(waiter));
+    }
+    /// 唤醒等待方, 回调在队列锁外执行
+    pub fn notify(&self, events: PollEvents) {
+        let targets: Vec<Arc<dyn PollWaiter>> = {
+            let mut waiters = self.waiters.lock();
+            waiters.retain(|entry| entry.waiter.strong_count() > 0);
+            let mut exclusive_woken = false;
+            waiters
+                .iter()
+                .filter(|entry| {
+                    if !entry.exclusive {
+                        return true;
+                    }
+                    !core::mem::replace(&mut exclusive_woken, true)
+                })
+                .filter_map(|entry| entry.waiter.upgrade())
+                .collect()
+        };
+        for waiter in targets {
+            waiter.wake(events);
+        }
+    }
+}
+
+fn waiter_key(waiter: &Arc<dyn PollWaiter>) -> usize {
+    Arc::as_ptr(waiter) as *const () as usize
+}
@@ -1,8 +1,9 @@
 use alloc::sync::{Weak,Arc};
 use device::BlockDevice;
-use super::{FileSystemType,Dentry,Inode};
+use super::{FileSystemType,Dentry,Inode,StatFs};
 use downcast_rs::{impl_downcast, DowncastSync};
 use sync::Once;
+use system_result::{SysError,SysResult};
 ///
 pub struct SuperBlockInner{
     ///
@@ -22,6 +23,14 @@ pub trait SuperBlock : Send + Sync + DowncastSync {
     fn set_root_dentry(&self, root_dentry: Arc<dyn Dentry>) {
         self.get_inner().root.call_once(|| root_dentry);
     }
+    /// 把这个文件系统的脏数据和元数据写回设备, 没有后备设备的文件系统什么也不做
+    fn sync_fs(&self) -> SysResult<()> {
+        Ok(())
+    }
+    /// 返回这个文件系统的容量和使用情况, f_flags 由调用者按挂载标志填写
+    fn statfs(&self) -> SysResult<StatFs> {
+        Err(SysError::ENOSYS)
+    }
 }
 
 
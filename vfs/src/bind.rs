@@ -0,0 +1,209 @@
+//! 绑定挂载的目录树
+//!
+//! 绑定挂载有自己的一套目录项, 每个目录项对应源目录树中的同名目录项并与它共用 inode,
+//! 目录操作都转给源目录项. 经过挂载点走到的目录项沿父目录向上会遇到绑定挂载的根,
+//! 所以 [`crate::mount_of`] 找到的是绑定挂载自己的记录, 两处的挂载选项互不影响.
+use alloc::{string::String, sync::Arc, vec::Vec};
+use system_result::{SysError, SysResult};
+use vfs_defs::{
+    alloc_dentry, Dentry, DentryInner, DentryState, DiskInodeType, File, FileInner, InodeMode, OpenFlags,
+    PollEvents, PollQueue, RenameFlags,
+};
+
+pub struct BindDentry {
+    inner: DentryInner,
+    /// 源目录树中对应的目录项
+    real: Arc<dyn Dentry>,
+}
+
+impl BindDentry {
+    /// 绑定挂载的根: 名字和父目录取自挂载点, 内容取自 `real`
+    pub fn new_root(real: &Arc<dyn Dentry>, mountpoint: &Arc<dyn Dentry>) -> Arc<dyn Dentry> {
+        let root = Self::new(mountpoint.get_name_str(), real.clone(), mountpoint.get_father());
+        *root.get_state() = DentryState::Valid;
+        root
+    }
+    fn new(name: &str, real: Arc<dyn Dentry>, father: Option<Arc<dyn Dentry>>) -> Arc<dyn Dentry> {
+        let dentry = Arc::new(Self {
+            inner: DentryInner::new(String::from(name), real.get_superblock(), father),
+            real,
+        });
+        dentry.sync_inode();
+        dentry
+    }
+    /// 源目录项变成有 inode 之后跟上
+    fn sync_inode(&self) {
+        *self.inner.inode.lock() = self.real.get_inode().ok();
+    }
+    /// 已在内存中的子目录项, 按源目录项刷新 inode
+    fn child(self: &Arc<Self>, name: &str) -> SysResult<Arc<dyn Dentry>> {
+        let child = self.clone().get_child(name).ok_or(SysError::ENOENT)?;
+        bind_dentry(&child)?.sync_inode();
+        *child.get_state() = DentryState::Valid;
+        Ok(child)
+    }
+}
+
+fn bind_dentry(dentry: &Arc<dyn Dentry>) -> SysResult<Arc<BindDentry>> {
+    dentry.clone().downcast_arc::<BindDentry>().map_err(|_| SysError::EXDEV)
+}
+
+/// 同一挂载里的另一个目录项在源目录树中的对应项, 跨挂载时返回 EXDEV
+fn real_of(dentry: &Arc<dyn Dentry>) -> SysResult<Arc<dyn Dentry>> {
+    Ok(bind_dentry(dentry)?.real.clone())
+}
+
+impl Dentry for BindDentry {
+    fn get_inner(&self) -> &DentryInner {
+        &self.inner
+    }
+    fn self_arc(self: Arc<Self>) -> Arc<dyn Dentry> {
+        self
+    }
+    fn concrete_create(self: Arc<Self>, name: &str, _type: DiskInodeType) -> SysResult<Arc<dyn Dentry>> {
+        self.real.create(name, _type)?;
+        self.child(name)
+    }
+    fn concrete_symlink(self: Arc<Self>, name: &str, target: &str) -> SysResult<Arc<dyn Dentry>> {
+        self.real.symlink(name, target)?;
+        self.child(name)
+    }
+    fn concrete_mknod(self: Arc<Self>, name: &str, mode: InodeMode, rdev: u64) -> SysResult<Arc<dyn Dentry>> {
+        self.real.mknod(name, mode, rdev)?;
+        self.child(name)
+    }
+    /// lookup 持有子目录项的状态锁, 这里只设置 inode
+    fn concrete_lookup(self: Arc<Self>, name: &str) -> SysResult<Arc<dyn Dentry>> {
+        let real = self.real.lookup(name)?;
+        let child = self.get_inner().children.lock().get(name).and_then(|child| child.upgrade());
+        let child = child.ok_or(SysError::ENOENT)?;
+        child.set_inode(real.get_inode()?);
+        Ok(child)
+    }
+    fn concrete_new_child(self: Arc<Self>, name: &str) -> Arc<dyn Dentry> {
+        let parent: Arc<dyn Dentry> = self.clone();
+        let real = self.real.find_or_create(name, DiskInodeType::File);
+        let child = Self::new(name, real, Some(parent.clone()));
+        alloc_dentry(Some(&parent), name, child.clone());
+        child
+    }
+    fn concrete_getchild(self: Arc<Self>, name: &str) -> Option<Arc<dyn Dentry>> {
+        let real = self.real.lookup(name).ok()?;
+        let parent: Arc<dyn Dentry> = self.clone();
+        let child = Self::new(name, real, Some(parent.clone()));
+        *child.get_state() = DentryState::Valid;
+        alloc_dentry(Some(&parent), name, child.clone());
+        Some(child)
+    }
+    /// 链接数由 VFS 在共用的 inode 上改, 这里直接调源文件系统的实现
+    fn concrete_link(self: Arc<Self>, new: &Arc<dyn Dentry>) -> SysResult<()> {
+        let new_real = real_of(new)?;
+        self.real.clone().concrete_link(&new_real)?;
+        bind_dentry(new)?.sync_inode();
+        *new.get_state() = DentryState::Valid;
+        Ok(())
+    }
+    fn concrete_unlink(self: Arc<Self>, old: &Arc<dyn Dentry>) -> SysResult<()> {
+        let old = bind_dentry(old)?;
+        self.real.clone().concrete_unlink(&old.real)?;
+        old.sync_inode();
+        self.get_inner().children.lock().remove(old.get_name_str());
+        Ok(())
+    }
+    /// 源文件系统改名时在两个目录项之间搬 inode, 这里跟着源目录项刷新
+    fn concrete_rename(self: Arc<Self>, new: Arc<dyn Dentry>, flags: RenameFlags) -> SysResult<()> {
+        let new = bind_dentry(&new)?;
+        self.real.vfs_rename(&new.real, flags)?;
+        new.sync_inode();
+        *new.get_state() = DentryState::Valid;
+        self.sync_inode();
+        Ok(())
+    }
+    fn open(self: Arc<Self>, flags: OpenFlags) -> Arc<dyn File> {
+        let real = self.real.clone().open(flags);
+        let file = Arc::new(BindFile {
+            inner: FileInner::new(self.clone()),
+            real,
+        });
+        if flags.contains(OpenFlags::APPEND) {
+            *file.get_offset() = self.get_inode().map_or(0, |inode| inode.get_size() as usize);
+        }
+        *file.get_inner().flags.lock() = flags;
+        file
+    }
+    /// 给源目录里还不在内存中的文件建目录项
+    fn load_dir(self: Arc<Self>) -> SysResult<()> {
+        self.real.clone().load_dir()?;
+        let names: Vec<String> = self
+            .real
+            .get_inner()
+            .children
+            .lock()
+            .iter()
+            .filter(|(_, child)| child.upgrade().is_some_and(|child| !child.has_no_inode()))
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in names {
+            if let Some(child) = self.clone().get_child(&name) {
+                bind_dentry(&child)?.sync_inode();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for BindDentry {
+    fn drop(&mut self) {
+        self.on_drop();
+    }
+}
+
+/// 经绑定挂载打开的文件, 读写转给源目录项上打开的文件, 目录项是绑定挂载的
+pub struct BindFile {
+    inner: FileInner,
+    real: Arc<dyn File>,
+}
+
+impl File for BindFile {
+    fn get_inner(&self) -> &FileInner {
+        &self.inner
+    }
+    fn readable(&self) -> bool {
+        self.real.readable()
+    }
+    fn writable(&self) -> bool {
+        self.real.writable()
+    }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        self.real.read_at(offset, buf)
+    }
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        self.real.write_at(offset, buf)
+    }
+    fn pread(&self, offset: usize, buf: &mut [u8]) -> SysResult<usize> {
+        self.real.pread(offset, buf)
+    }
+    fn pwrite(&self, offset: usize, buf: &[u8]) -> SysResult<usize> {
+        self.real.pwrite(offset, buf)
+    }
+    fn write_checked(&self, buf: &[u8]) -> SysResult<usize> {
+        let mut offset = self.get_offset();
+        if self.get_inner().flags.lock().contains(OpenFlags::APPEND) {
+            let (written, end) = self.append(buf)?;
+            *offset = end;
+            return Ok(written);
+        }
+        let written = self.pwrite(*offset, buf)?;
+        *offset += written;
+        Ok(written)
+    }
+    fn poll(&self, events: PollEvents) -> PollEvents {
+        self.real.poll(events)
+    }
+    fn poll_queue(&self) -> Option<&PollQueue> {
+        self.real.poll_queue()
+    }
+    fn ioctl(&self, cmd: usize, arg: usize) -> SysResult<isize> {
+        self.real.ioctl(cmd, arg)
+    }
+}
@@ -0,0 +1,235 @@
+//! 块设备文件 /dev/vdX, 按字节偏移直接读写底层设备
+use alloc::{
+    string::String,
+    sync::Arc,
+    vec,
+};
+use core::cmp;
+use config::DISK_BLOCK_SZ;
+use device::BlockDevice;
+use system_result::{SysError, SysResult};
+use crate::write_user;
+use vfs_defs::{
+    Dentry, DentryInner, File, FileInner, Inode, InodeMeta, InodeMode, SuperBlock,Kstat,OpenFlags,DiskInodeType,RenameFlags,ino_alloc,
+};
+
+
+pub struct BlockDevDentry {
+    inner: DentryInner,
+    dev: Arc<dyn BlockDevice>,
+}
+
+impl BlockDevDentry {
+    pub fn new(
+        name: &str,
+        dev: Arc<dyn BlockDevice>,
+        super_block: Arc<dyn SuperBlock>,
+        parent: Option<Arc<dyn Dentry>>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            inner: DentryInner::new(String::from(name), super_block, parent),
+            dev,
+        })
+    }
+}
+
+impl Dentry for BlockDevDentry {
+    fn get_inner(&self) -> &DentryInner {
+        &self.inner
+    }
+
+    fn open(self: Arc<Self>,flags:OpenFlags) -> Arc<dyn File> {
+        let dev = self.dev.clone();
+        let ret = Arc::new(BlockDevFile {
+            inner: FileInner::new(self),
+            dev,
+        });
+        *ret.get_inner().flags.lock() = flags;
+        ret
+    }
+
+    fn concrete_lookup(self: Arc<Self>, _name: &str) -> SysResult<Arc<dyn Dentry>> {
+        Err(SysError::ENOTDIR)
+    }
+
+    fn concrete_create(self: Arc<Self>, _name: &str, _type:DiskInodeType) -> SysResult<Arc<dyn Dentry>> {
+        Err(SysError::ENOTDIR)
+    }
+
+    fn concrete_unlink(self: Arc<Self>, _old: &Arc<dyn Dentry>) -> SysResult<()> {
+        Err(SysError::ENOTDIR)
+    }
+    fn concrete_new_child(self: Arc<Self>, _name: &str) -> Arc<dyn Dentry> {
+        unimplemented!()
+    }
+    fn concrete_link(self: Arc<Self>, _new: &Arc<dyn Dentry>) -> SysResult<()> {
+        Err(SysError::ENOTDIR)
+    }
+    fn concrete_rename(self: Arc<Self>, _new: Arc<dyn Dentry>, _flags: RenameFlags) -> SysResult<()> {
+        Err(SysError::ENOTDIR)
+    }
+    fn concrete_getchild(self:Arc<Self>, _name: &str) -> Option<Arc<dyn Dentry>> {
+        None
+    }
+    fn self_arc(self:Arc<Self>) -> Arc<dyn Dentry> {
+        self.clone()
+    }
+    fn load_dir(self:Arc<Self>)->SysResult<()> {
+        Err(SysError::ENOTDIR)
+    }
+}
+
+pub struct BlockDevInode {
+    meta: InodeMeta,
+    pub dev: Arc<dyn BlockDevice>,
+}
+
+impl BlockDevInode {
+    pub fn new(super_block: Arc<dyn SuperBlock>, dev: Arc<dyn BlockDevice>) -> Arc<Self> {
+        let ret = Arc::new(Self {
+            meta: InodeMeta::new(InodeMode::BLOCK,ino_alloc(), super_block),
+            dev,
+        });
+        *ret.meta._type.lock() = DiskInodeType::File;
+        ret
+    }
+}
+
+impl Inode for BlockDevInode {
+    fn get_meta(&self) -> &InodeMeta {
+        &self.meta
+    }
+
+    fn get_attr(&self) -> SysResult<Kstat> {
+        let inner = self.meta.inner.lock();
+        let mode = inner.mode.bits();
+        Ok(Kstat {
+            st_dev: 0,
+            st_ino: self.meta.ino as u64,
+            st_mode: mode,
+            st_nlink: 1,
+            st_uid: inner.uid,
+            st_gid: inner.gid,
+            st_rdev: inner.rdev,
+            __pad: 0,
+            st_size: (self.dev.num_blocks() * DISK_BLOCK_SZ) as u64,
+            st_blksize: DISK_BLOCK_SZ as u32,
+            __pad2: 0,
+            st_blocks: 0,
+            st_atime_sec:inner.atime.sec as u64,
+            st_atime_nsec:inner.atime.usec as u64,
+            st_mtime_sec:inner.mtime.sec as u64,
+            st_mtime_nsec:inner.mtime.usec as u64,
+            st_ctime_sec:inner.ctime.sec as u64,
+            st_ctime_nsec:inner.ctime.usec as u64,
+            unused: 0,
+        })
+    }
+    fn load_from_disk(&self) {
+
+    }
+    fn get_size(&self) -> u32 {
+        0
+    }
+    fn clear(&self) {
+
+    }
+}
+
+/// 只读标志
+const BLKROGET: usize = 0x125e;
+/// 扇区大小
+const BLKSSZGET: usize = 0x1268;
+/// 设备的字节数
+const BLKGETSIZE64: usize = 0x80081272;
+
+pub struct BlockDevFile {
+    inner: FileInner,
+    dev: Arc<dyn BlockDevice>,
+}
+
+impl BlockDevFile {
+    /// 设备的字节数, 容量未知时不限制
+    fn capacity(&self) -> usize {
+        match self.dev.num_blocks() {
+            0 => usize::MAX,
+            blocks => blocks * DISK_BLOCK_SZ,
+        }
+    }
+}
+
+impl File for BlockDevFile {
+    fn get_inner(&self) -> &FileInner {
+        &self.inner
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let len = cmp::min(buf.len(), self.capacity().saturating_sub(offset));
+        let buf = &mut buf[..len];
+        let mut block = vec![0u8; DISK_BLOCK_SZ];
+        let mut done = 0;
+        while done < buf.len() {
+            let pos = offset + done;
+            let in_block = pos % DISK_BLOCK_SZ;
+            let whole = (buf.len() - done) / DISK_BLOCK_SZ * DISK_BLOCK_SZ;
+            // 对齐的整块一次读完
+            if in_block == 0 && whole > 0 {
+                self.dev.read_blocks(pos / DISK_BLOCK_SZ, &mut buf[done..done + whole]);
+                done += whole;
+                continue;
+            }
+            let len = cmp::min(DISK_BLOCK_SZ - in_block, buf.len() - done);
+            self.dev.read_block(pos / DISK_BLOCK_SZ, &mut block);
+            buf[done..done + len].copy_from_slice(&block[in_block..in_block + len]);
+            done += len;
+        }
+        done
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        let len = cmp::min(buf.len(), self.capacity().saturating_sub(offset));
+        let buf = &buf[..len];
+        let mut block = vec![0u8; DISK_BLOCK_SZ];
+        let mut done = 0;
+        while done < buf.len() {
+            let pos = offset + done;
+            let in_block = pos % DISK_BLOCK_SZ;
+            let whole = (buf.len() - done) / DISK_BLOCK_SZ * DISK_BLOCK_SZ;
+            if in_block == 0 && whole > 0 {
+                self.dev.write_blocks(pos / DISK_BLOCK_SZ, &buf[done..done + whole]);
+                done += whole;
+                continue;
+            }
+            let len = cmp::min(DISK_BLOCK_SZ - in_block, buf.len() - done);
+            // 不满一块时先读出原内容
+            if len < DISK_BLOCK_SZ {
+                self.dev.read_block(pos / DISK_BLOCK_SZ, &mut block);
+            }
+            block[in_block..in_block + len].copy_from_slice(&buf[done..done + len]);
+            self.dev.write_block(pos / DISK_BLOCK_SZ, &block);
+            done += len;
+        }
+        done
+    }
+    fn readable(&self) -> bool {
+        true
+    }
+    fn writable(&self) -> bool {
+        true
+    }
+    fn poll(&self, _events: vfs_defs::PollEvents) -> vfs_defs::PollEvents {
+        vfs_defs::PollEvents::POLLOUT | vfs_defs::PollEvents::POLLIN
+    }
+    fn ioctl(&self, cmd: usize, arg: usize) -> SysResult<isize> {
+        match cmd {
+            BLKROGET => write_user(arg, &(self.dev.read_only() as i32))?,
+            BLKSSZGET => write_user(arg, &(DISK_BLOCK_SZ as i32))?,
+            BLKGETSIZE64 => write_user(arg, &((self.dev.num_blocks() * DISK_BLOCK_SZ) as u64))?,
+            _ => {
+                let lo = super::find_loop(&self.dev).ok_or(SysError::ENOTTY)?;
+                return lo.ioctl(cmd, arg);
+            }
+        }
+        Ok(0)
+    }
+}
@@ -0,0 +1,616 @@
+//! loop 设备: 把普通文件当作块设备.
+//! /dev/loop-control 分配和回收设备号, /dev/loopN 用 ioctl 绑定和解绑后备文件
+use alloc::{
+    format,
+    string::String,
+    sync::Arc,
+    vec::Vec,
+};
+use config::DISK_BLOCK_SZ;
+use device::BlockDevice;
+use sync::Mutex;
+use system_result::{SysError, SysResult};
+use vfs_defs::{
+    Dentry, DentryInner, File, FileInner, Inode, InodeMeta, InodeMode, SuperBlock, Kstat, OpenFlags, DiskInodeType,
+    RenameFlags, ino_alloc, makedev,
+};
+
+use super::{add_block_node, new_block_node, remove_block_node};
+use crate::{read_user, write_user};
+
+/// loop 设备的主设备号
+pub const LOOP_MAJOR: u32 = 7;
+/// 启动时预先建好的 loop 设备个数
+const LOOP_PRECREATE: usize = 8;
+/// 最多的 loop 设备个数
+const LOOP_MAX: usize = 256;
+
+/// /dev/loopN 上的请求
+const LOOP_SET_FD: usize = 0x4c00;
+const LOOP_CLR_FD: usize = 0x4c01;
+const LOOP_SET_STATUS64: usize = 0x4c04;
+const LOOP_GET_STATUS64: usize = 0x4c05;
+const LOOP_SET_CAPACITY: usize = 0x4c07;
+const LOOP_SET_DIRECT_IO: usize = 0x4c08;
+const LOOP_SET_BLOCK_SIZE: usize = 0x4c09;
+const LOOP_CONFIGURE: usize = 0x4c0a;
+/// /dev/loop-control 上的请求
+const LOOP_CTL_ADD: usize = 0x4c80;
+const LOOP_CTL_REMOVE: usize = 0x4c81;
+const LOOP_CTL_GET_FREE: usize = 0x4c82;
+
+/// lo_flags
+pub const LO_FLAGS_READ_ONLY: u32 = 1;
+pub const LO_FLAGS_AUTOCLEAR: u32 = 4;
+pub const LO_FLAGS_PARTSCAN: u32 = 8;
+pub const LO_FLAGS_DIRECT_IO: u32 = 16;
+/// LOOP_SET_STATUS64 能改的标志
+const LO_FLAGS_SETTABLE: u32 = LO_FLAGS_AUTOCLEAR | LO_FLAGS_PARTSCAN | LO_FLAGS_DIRECT_IO;
+/// LOOP_SET_STATUS64 能清的标志
+const LO_FLAGS_CLEARABLE: u32 = LO_FLAGS_AUTOCLEAR | LO_FLAGS_DIRECT_IO;
+
+const LO_NAME_SIZE: usize = 64;
+
+/// struct loop_info64
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct LoopInfo64 {
+    pub lo_device: u64,
+    pub lo_inode: u64,
+    pub lo_rdevice: u64,
+    pub lo_offset: u64,
+    pub lo_sizelimit: u64,
+    pub lo_number: u32,
+    pub lo_encrypt_type: u32,
+    pub lo_encrypt_key_size: u32,
+    pub lo_flags: u32,
+    pub lo_file_name: [u8; LO_NAME_SIZE],
+    pub lo_crypt_name: [u8; LO_NAME_SIZE],
+    pub lo_encrypt_key: [u8; 32],
+    pub lo_init: [u64; 2],
+}
+
+/// struct loop_config
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct LoopConfig {
+    fd: u32,
+    block_size: u32,
+    info: LoopInfo64,
+    reserved: [u64; 8],
+}
+
+/// 由文件描述符取得打开的文件, 由内核在启动时登记
+pub type FdResolver = fn(usize) -> SysResult<Arc<dyn File>>;
+
+static FD_RESOLVER: Mutex<Option<FdResolver>> = Mutex::new(None);
+
+/// 登记 LOOP_SET_FD 等请求解析文件描述符的方法
+pub fn register_fd_resolver(resolver: FdResolver) {
+    *FD_RESOLVER.lock() = Some(resolver);
+}
+
+fn resolve_fd(fd: usize) -> SysResult<Arc<dyn File>> {
+    let resolver = (*FD_RESOLVER.lock()).ok_or(SysError::EBADF)?;
+    resolver(fd)
+}
+
+/// 设备绑定的后备文件
+struct Backing {
+    file: Arc<dyn File>,
+    /// 设备在文件中的起点
+    offset: usize,
+    /// 设备最大字节数, 0 表示到文件末尾
+    sizelimit: usize,
+    flags: u32,
+    file_name: [u8; LO_NAME_SIZE],
+    /// PARTSCAN 扫出来的分区设备名
+    partitions: Vec<String>,
+}
+
+/// /dev/loopN
+pub struct LoopDevice {
+    number: usize,
+    backing: Mutex<Option<Backing>>,
+}
+
+static LOOP_DEVICES: Mutex<Vec<Option<Arc<LoopDevice>>>> = Mutex::new(Vec::new());
+
+impl LoopDevice {
+    fn name(&self) -> String {
+        format!("loop{}", self.number)
+    }
+
+    fn as_block_device(self: &Arc<Self>) -> Arc<dyn BlockDevice> {
+        self.clone()
+    }
+
+    fn bound(&self) -> bool {
+        self.backing.lock().is_some()
+    }
+
+    /// 绑定后备文件. 只读打开的文件只能做只读设备
+    fn bind(self: &Arc<Self>, file: Arc<dyn File>, info: &LoopInfo64) -> SysResult<()> {
+        let inode = file.get_dentry().get_inode()?;
+        let file_type = inode.get_meta().mode & InodeMode::TYPE_MASK;
+        if file_type != InodeMode::FILE && file_type != InodeMode::BLOCK {
+            return Err(SysError::EINVAL);
+        }
+        let mut flags = info.lo_flags & (LO_FLAGS_READ_ONLY | LO_FLAGS_SETTABLE);
+        if !file.writable() {
+            flags |= LO_FLAGS_READ_ONLY;
+        }
+        let mut file_name = info.lo_file_name;
+        if file_name[0] == 0 {
+            let path = file.get_dentry().path();
+            let len = path.len().min(LO_NAME_SIZE - 1);
+            file_name[..len].copy_from_slice(&path.as_bytes()[..len]);
+        }
+        file_name[LO_NAME_SIZE - 1] = 0;
+        if self.bound() {
+            return Err(SysError::EBUSY);
+        }
+        // 设备号可能刚被别的文件用过, 先丢掉旧的缓存块. 未绑定时写回的块直接丢弃
+        buffer::block_cache_invalidate_dev(&self.as_block_device());
+        {
+            let mut backing = self.backing.lock();
+            if backing.is_some() {
+                return Err(SysError::EBUSY);
+            }
+            *backing = Some(Backing {
+                file,
+                offset: info.lo_offset as usize,
+                sizelimit: info.lo_sizelimit as usize,
+                flags,
+                file_name,
+                partitions: Vec::new(),
+            });
+        }
+        if flags & LO_FLAGS_PARTSCAN != 0 {
+            self.scan_partitions();
+        }
+        Ok(())
+    }
+
+    fn scan_partitions(self: &Arc<Self>) {
+        let parts = device::register_partitions(&self.name(), &self.as_block_device());
+        let mut names = Vec::new();
+        for (name, dev) in parts {
+            add_block_node(&name, dev, None);
+            names.push(name);
+        }
+        if let Some(backing) = self.backing.lock().as_mut() {
+            backing.partitions = names;
+        }
+    }
+
+    /// 解绑后备文件, 设备被挂载时返回 EBUSY
+    fn clear(self: &Arc<Self>) -> SysResult<()> {
+        let dev = self.as_block_device();
+        if !self.bound() {
+            return Err(SysError::ENXIO);
+        }
+        let partitions = self.backing.lock().as_ref().map(|backing| backing.partitions.clone()).unwrap_or_default();
+        let busy = crate::mount::device_mounted(&dev)
+            || partitions
+                .iter()
+                .filter_map(|name| device::find_block_device(name))
+                .any(|part| crate::mount::device_mounted(&part));
+        if busy {
+            return Err(SysError::EBUSY);
+        }
+        // 分区的脏块也要经过本设备写回, 先于本设备处理
+        for name in partitions {
+            if let Some(part) = device::unregister_block_device(&name) {
+                buffer::block_cache_invalidate_dev(&part);
+            }
+            remove_block_node(&name);
+        }
+        buffer::block_cache_sync_dev(&dev);
+        buffer::block_cache_invalidate_dev(&dev);
+        self.backing.lock().take();
+        Ok(())
+    }
+
+    fn get_status(&self) -> SysResult<LoopInfo64> {
+        let backing = self.backing.lock();
+        let backing = backing.as_ref().ok_or(SysError::ENXIO)?;
+        let attr = backing.file.get_dentry().get_inode()?.get_attr()?;
+        Ok(LoopInfo64 {
+            lo_device: attr.st_dev,
+            lo_inode: attr.st_ino,
+            lo_rdevice: attr.st_rdev,
+            lo_offset: backing.offset as u64,
+            lo_sizelimit: backing.sizelimit as u64,
+            lo_number: self.number as u32,
+            lo_encrypt_type: 0,
+            lo_encrypt_key_size: 0,
+            lo_flags: backing.flags,
+            lo_file_name: backing.file_name,
+            lo_crypt_name: [0; LO_NAME_SIZE],
+            lo_encrypt_key: [0; 32],
+            lo_init: [0; 2],
+        })
+    }
+
+    fn set_status(self: &Arc<Self>, info: &LoopInfo64) -> SysResult<()> {
+        if !self.bound() {
+            return Err(SysError::ENXIO);
+        }
+        let dev = self.as_block_device();
+        // 改位置之前把脏块按旧的位置写回去
+        buffer::block_cache_sync_dev(&dev);
+        let (resized, scan) = {
+            let mut backing = self.backing.lock();
+            let backing = backing.as_mut().ok_or(SysError::ENXIO)?;
+            let old_flags = backing.flags;
+            let flags = (old_flags & !LO_FLAGS_CLEARABLE) | (info.lo_flags & LO_FLAGS_SETTABLE);
+            let resized = backing.offset != info.lo_offset as usize || backing.sizelimit != info.lo_sizelimit as usize;
+            backing.offset = info.lo_offset as usize;
+            backing.sizelimit = info.lo_sizelimit as usize;
+            backing.flags = flags;
+            if info.lo_file_name[0] != 0 {
+                backing.file_name = info.lo_file_name;
+                backing.file_name[LO_NAME_SIZE - 1] = 0;
+            }
+            (resized, flags & LO_FLAGS_PARTSCAN != 0 && old_flags & LO_FLAGS_PARTSCAN == 0)
+        };
+        if resized {
+            buffer::block_cache_invalidate_dev(&dev);
+        }
+        if scan {
+            self.scan_partitions();
+        }
+        Ok(())
+    }
+
+    /// /dev/loopN 上的 ioctl
+    pub fn ioctl(self: &Arc<Self>, cmd: usize, arg: usize) -> SysResult<isize> {
+        match cmd {
+            LOOP_SET_FD => {
+                let file = resolve_fd(arg)?;
+                let info: LoopInfo64 = unsafe { core::mem::zeroed() };
+                self.bind(file, &info)?;
+            }
+            LOOP_CONFIGURE => {
+                let config: LoopConfig = read_user(arg)?;
+                if config.block_size != 0 && config.block_size as usize != DISK_BLOCK_SZ {
+                    return Err(SysError::EINVAL);
+                }
+                let file = resolve_fd(config.fd as usize)?;
+                self.bind(file, &config.info)?;
+            }
+            LOOP_CLR_FD => self.clear()?,
+            LOOP_GET_STATUS64 => {
+                let info = self.get_status()?;
+                write_user(arg, &info)?;
+            }
+            LOOP_SET_STATUS64 => {
+                let info: LoopInfo64 = read_user(arg)?;
+                self.set_status(&info)?;
+            }
+            // 容量每次按后备文件的大小现算, 也总是直接读写后备文件
+            LOOP_SET_CAPACITY | LOOP_SET_DIRECT_IO => {
+                if !self.bound() {
+                    return Err(SysError::ENXIO);
+                }
+            }
+            LOOP_SET_BLOCK_SIZE => {
+                if arg != DISK_BLOCK_SZ {
+                    return Err(SysError::EINVAL);
+                }
+            }
+            _ => return Err(SysError::ENOTTY),
+        }
+        Ok(0)
+    }
+
+    /// 取出读写要用的后备文件、起点和大小; 不持 backing 锁做 I/O,
+    /// 否则后备文件所在文件系统的读写会和 ioctl/flush 互相卡住
+    fn io_target(&self, write: bool) -> Option<(Arc<dyn File>, usize, usize)> {
+        let (file, offset, sizelimit) = {
+            let backing = self.backing.lock();
+            let backing = backing.as_ref()?;
+            if write && backing.flags & LO_FLAGS_READ_ONLY != 0 {
+                return None;
+            }
+            (backing.file.clone(), backing.offset, backing.sizelimit)
+        };
+        let end = file.get_dentry().get_inode().map_or(0, |inode| inode.get_size() as usize);
+        let size = end.saturating_sub(offset);
+        let size = match sizelimit {
+            0 => size,
+            limit => size.min(limit),
+        };
+        Some((file, offset, size))
+    }
+
+    /// 卸载后自动解绑带 LO_FLAGS_AUTOCLEAR 的设备
+    fn autoclear(self: &Arc<Self>) {
+        let autoclear = self
+            .backing
+            .lock()
+            .as_ref()
+            .is_some_and(|backing| backing.flags & LO_FLAGS_AUTOCLEAR != 0);
+        if autoclear {
+            let _ = self.clear();
+        }
+    }
+}
+
+impl BlockDevice for LoopDevice {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        self.read_blocks(block_id, buf)
+    }
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        self.write_blocks(block_id, buf)
+    }
+    fn read_blocks(&self, start_block: usize, buf: &mut [u8]) {
+        let Some((file, offset, size)) = self.io_target(false) else {
+            buf.fill(0);
+            return;
+        };
+        let pos = start_block * DISK_BLOCK_SZ;
+        let len = buf.len().min(size.saturating_sub(pos));
+        let read = file.read_at(offset + pos, &mut buf[..len]);
+        // 文件末尾之后读出 0
+        buf[read..].fill(0);
+    }
+    fn write_blocks(&self, start_block: usize, buf: &[u8]) {
+        // 只读设备已经拒绝了读写挂载, 这里的写直接丢弃
+        let Some((file, offset, size)) = self.io_target(true) else {
+            return;
+        };
+        let pos = start_block * DISK_BLOCK_SZ;
+        let len = buf.len().min(size.saturating_sub(pos));
+        file.write_at(offset + pos, &buf[..len]);
+    }
+    fn num_blocks(&self) -> usize {
+        self.io_target(false).map_or(0, |(_, _, size)| size / DISK_BLOCK_SZ)
+    }
+    fn read_only(&self) -> bool {
+        self.backing
+            .lock()
+            .as_ref()
+            .is_some_and(|backing| backing.flags & LO_FLAGS_READ_ONLY != 0)
+    }
+    fn flush(&self) {
+        let file = self.backing.lock().as_ref().map(|backing| backing.file.clone());
+        if let Some(file) = file {
+            let _ = file.get_dentry().get_superblock().sync_fs();
+        }
+    }
+}
+
+/// 新建 /dev/loopN
+pub fn create_loop(number: usize) -> SysResult<Arc<LoopDevice>> {
+    let lo = new_loop(number)?;
+    add_block_node(&lo.name(), lo.as_block_device(), Some(makedev(LOOP_MAJOR, number as u32)));
+    Ok(lo)
+}
+
+/// devfs 初始化时预先建好 loop0 .. loop7, 此时 /dev 还查不到, 直接挂在 `dir` 下
+pub(super) fn precreate_loops(dir: &Arc<dyn Dentry>) {
+    for number in 0..LOOP_PRECREATE {
+        if let Ok(lo) = new_loop(number) {
+            new_block_node(dir, &lo.name(), lo.as_block_device(), Some(makedev(LOOP_MAJOR, number as u32)));
+        }
+    }
+}
+
+fn new_loop(number: usize) -> SysResult<Arc<LoopDevice>> {
+    if number >= LOOP_MAX {
+        return Err(SysError::EINVAL);
+    }
+    let mut devices = LOOP_DEVICES.lock();
+    if devices.len() <= number {
+        devices.resize(number + 1, None);
+    }
+    if devices[number].is_some() {
+        return Err(SysError::EEXIST);
+    }
+    let lo = Arc::new(LoopDevice {
+        number,
+        backing: Mutex::new(None),
+    });
+    devices[number] = Some(lo.clone());
+    drop(devices);
+    device::register_block_device(&lo.name(), lo.as_block_device());
+    Ok(lo)
+}
+
+/// 块设备是 loop 设备时返回它
+pub fn find_loop(dev: &Arc<dyn BlockDevice>) -> Option<Arc<LoopDevice>> {
+    let key = Arc::as_ptr(dev) as *const ();
+    LOOP_DEVICES
+        .lock()
+        .iter()
+        .flatten()
+        .find(|lo| Arc::as_ptr(lo) as *const () == key)
+        .cloned()
+}
+
+/// 文件系统卸载后调用
+pub fn loop_autoclear(dev: &Arc<dyn BlockDevice>) {
+    if let Some(lo) = find_loop(dev) {
+        lo.autoclear();
+    }
+}
+
+fn loop_control_ioctl(cmd: usize, arg: usize) -> SysResult<isize> {
+    match cmd {
+        LOOP_CTL_GET_FREE => {
+            let free = LOOP_DEVICES.lock().iter().flatten().find(|lo| !lo.bound()).cloned();
+            let lo = match free {
+                Some(lo) => lo,
+                None => {
+                    let number = {
+                        let devices = LOOP_DEVICES.lock();
+                        devices.iter().position(Option::is_none).unwrap_or(devices.len())
+                    };
+                    create_loop(number)?
+                }
+            };
+            Ok(lo.number as isize)
+        }
+        LOOP_CTL_ADD => Ok(create_loop(arg)?.number as isize),
+        LOOP_CTL_REMOVE => {
+            let mut devices = LOOP_DEVICES.lock();
+            let lo = devices.get(arg).cloned().flatten().ok_or(SysError::ENODEV)?;
+            if lo.bound() {
+                return Err(SysError::EBUSY);
+            }
+            devices[arg] = None;
+            drop(devices);
+            device::unregister_block_device(&lo.name());
+            remove_block_node(&lo.name());
+            Ok(arg as isize)
+        }
+        _ => Err(SysError::ENOTTY),
+    }
+}
+
+pub struct LoopControlDentry {
+    inner: DentryInner,
+}
+
+impl LoopControlDentry {
+    pub fn new(
+        name: &str,
+        super_block: Arc<dyn SuperBlock>,
+        parent: Option<Arc<dyn Dentry>>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            inner: DentryInner::new(String::from(name), super_block, parent),
+        })
+    }
+}
+
+impl Dentry for LoopControlDentry {
+    fn get_inner(&self) -> &DentryInner {
+        &self.inner
+    }
+
+    fn open(self: Arc<Self>,flags:OpenFlags) -> Arc<dyn File> {
+        let ret = Arc::new(LoopControlFile {
+            inner: FileInner::new(self),
+        });
+        *ret.get_inner().flags.lock() = flags;
+        ret
+    }
+
+    fn concrete_lookup(self: Arc<Self>, _name: &str) -> SysResult<Arc<dyn Dentry>> {
+        Err(SysError::ENOTDIR)
+    }
+
+    fn concrete_create(self: Arc<Self>, _name: &str, _type:DiskInodeType) -> SysResult<Arc<dyn Dentry>> {
+        Err(SysError::ENOTDIR)
+    }
+
+    fn concrete_unlink(self: Arc<Self>, _old: &Arc<dyn Dentry>) -> SysResult<()> {
+        Err(SysError::ENOTDIR)
+    }
+    fn concrete_new_child(self: Arc<Self>, _name: &str) -> Arc<dyn Dentry> {
+        unimplemented!()
+    }
+    fn concrete_link(self: Arc<Self>, _new: &Arc<dyn Dentry>) -> SysResult<()> {
+        Err(SysError::ENOTDIR)
+    }
+    fn concrete_rename(self: Arc<Self>, _new: Arc<dyn Dentry>, _flags: RenameFlags) -> SysResult<()> {
+        Err(SysError::ENOTDIR)
+    }
+    fn concrete_getchild(self:Arc<Self>, _name: &str) -> Option<Arc<dyn Dentry>> {
+        None
+    }
+    fn self_arc(self:Arc<Self>) -> Arc<dyn Dentry> {
+        self.clone()
+    }
+    fn load_dir(self:Arc<Self>)->SysResult<()> {
+        Err(SysError::ENOTDIR)
+    }
+}
+
+pub struct LoopControlInode {
+    meta: InodeMeta,
+}
+
+impl LoopControlInode {
+    pub fn new(super_block: Arc<dyn SuperBlock>) -> Arc<Self> {
+        let ret = Arc::new(Self {
+            meta: InodeMeta::new(InodeMode::CHAR,ino_alloc(), super_block),
+        });
+        *ret.meta._type.lock() = DiskInodeType::File;
+        ret
+    }
+}
+
+impl Inode for LoopControlInode {
+    fn get_meta(&self) -> &InodeMeta {
+        &self.meta
+    }
+
+    fn get_attr(&self) -> SysResult<Kstat> {
+        let inner = self.meta.inner.lock();
+        let mode = inner.mode.bits();
+        Ok(Kstat {
+            st_dev: 0,
+            st_ino: self.meta.ino as u64,
+            st_mode: mode,
+            st_nlink: 1,
+            st_uid: inner.uid,
+            st_gid: inner.gid,
+            st_rdev: inner.rdev,
+            __pad: 0,
+            st_size: 0,
+            st_blksize: DISK_BLOCK_SZ as u32,
+            __pad2: 0,
+            st_blocks: 0,
+            st_atime_sec:inner.atime.sec as u64,
+            st_atime_nsec:inner.atime.usec as u64,
+            st_mtime_sec:inner.mtime.sec as u64,
+            st_mtime_nsec:inner.mtime.usec as u64,
+            st_ctime_sec:inner.ctime.sec as u64,
+            st_ctime_nsec:inner.ctime.usec as u64,
+            unused: 0,
+        })
+    }
+    fn load_from_disk(&self) {
+
+    }
+    fn get_size(&self) -> u32 {
+        0
+    }
+    fn clear(&self) {
+
+    }
+}
+
+pub struct LoopControlFile {
+    inner: FileInner,
+}
+
+impl File for LoopControlFile {
+    fn get_inner(&self) -> &FileInner {
+        &self.inner
+    }
+
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> usize {
+        0
+    }
+
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> usize {
+        0
+    }
+    fn readable(&self) -> bool {
+        true
+    }
+    fn writable(&self) -> bool {
+        true
+    }
+    fn poll(&self, _events: vfs_defs::PollEvents) -> vfs_defs::PollEvents {
+        vfs_defs::PollEvents::POLLOUT | vfs_defs::PollEvents::POLLIN
+    }
+    fn ioctl(&self, cmd: usize, arg: usize) -> SysResult<isize> {
+        loop_control_ioctl(cmd, arg)
+    }
+}
@@ -1,22 +1,47 @@
-use vfs_defs::{FileSystemType,FileSystemTypeInner,SuperBlock,SuperBlockInner,Dentry,MountFlags,InodeMode,DentryState};
-use alloc::{string::String, sync::Arc};
+use vfs_defs::{FileSystemType,FileSystemTypeInner,SuperBlock,SuperBlockInner,Dentry,MountFlags,InodeMode,DentryState,makedev,StatFs};
+use alloc::{collections::BTreeMap, string::String, sync::Arc};
+use sync::Mutex;
 use device::BlockDevice;
-use crate::get_root_dentry;
+use crate::{lookup_path,NAME_MAX};
+use crate::tmpfs::TMPFS_MAGIC;
 
-use super::{MemDentry,MemInode,add_vfs_dentry};
+use super::{MemDentry,MemInode,add_vfs_dentry,remove_vfs_dentry};
+use core::sync::atomic::{AtomicU32, Ordering};
 use system_result::SysResult;
 mod tty;
+mod blockdev;
 mod cpu_dma_latency;
 mod null;
 mod rtc;
 mod urandom;
 mod zero;
+mod loopdev;
 
+pub use blockdev::BlockDevInode;
+use blockdev::BlockDevDentry;
 use cpu_dma_latency::{CpuDmaLatencyDentry,CpuDmaLatencyInode};
 use null::{NullDentry,NullInode};
 use rtc::{RtcDentry,RtcInode};
 use urandom::{UrandomDentry,UrandomInode};
 use zero::{ZeroDentry,ZeroInode};
+use loopdev::{LoopControlDentry,LoopControlInode};
+pub use loopdev::{create_loop,find_loop,loop_autoclear,register_fd_resolver,LoopDevice};
+
+/// 设备号到 devfs 节点的映射, 其它文件系统上的设备节点打开时据此找到驱动
+static DEVICES: Mutex<BTreeMap<u64, Arc<dyn Dentry>>> = Mutex::new(BTreeMap::new());
+
+/// 给已经设置好 inode 的设备节点分配设备号
+pub fn register_device(rdev: u64, dentry: Arc<dyn Dentry>) {
+    if let Ok(inode) = dentry.get_inode() {
+        inode.get_meta().inner.lock().rdev = rdev;
+    }
+    DEVICES.lock().insert(rdev, dentry);
+}
+
+/// 按设备号找到驱动对应的节点
+pub fn find_device(rdev: u64) -> Option<Arc<dyn Dentry>> {
+    DEVICES.lock().get(&rdev).cloned()
+}
 
 pub fn init_devfs(root_dentry: Arc<dyn Dentry>) -> SysResult<()> {
     let sb = root_dentry.get_superblock();
@@ -26,6 +51,7 @@ pub fn init_devfs(root_dentry: Arc<dyn Dentry>) -> SysResult<()> {
     zero_dentry.set_inode(zero_inode);
     *zero_dentry.get_state() = DentryState::Valid;
     root_dentry.add_child(zero_dentry.clone());
+    register_device(makedev(1, 5), zero_dentry.clone());
     add_vfs_dentry(zero_dentry);
 
     let null_dentry = NullDentry::new("null", sb.clone(), Some(root_dentry.clone()));
@@ -33,6 +59,7 @@ pub fn init_devfs(root_dentry: Arc<dyn Dentry>) -> SysResult<()> {
     null_dentry.set_inode(null_inode);
     *null_dentry.get_state() = DentryState::Valid;
     root_dentry.add_child(null_dentry.clone());
+    register_device(makedev(1, 3), null_dentry.clone());
     add_vfs_dentry(null_dentry);
 
     let rtc_dentry = RtcDentry::new("rtc", sb.clone(), Some(root_dentry.clone()));
@@ -40,6 +67,7 @@ pub fn init_devfs(root_dentry: Arc<dyn Dentry>) -> SysResult<()> {
     rtc_dentry.set_inode(rtc_inode);
     *rtc_dentry.get_state() = DentryState::Valid;
     root_dentry.add_child(rtc_dentry.clone());
+    register_device(makedev(254, 0), rtc_dentry.clone());
     add_vfs_dentry(rtc_dentry);
 
     let cpu_dma_latency_dentry =
@@ -48,6 +76,7 @@ pub fn init_devfs(root_dentry: Arc<dyn Dentry>) -> SysResult<()> {
     cpu_dma_latency_dentry.set_inode(cpu_dma_latency_inode);
     *cpu_dma_latency_dentry.get_state() = DentryState::Valid;
     root_dentry.add_child(cpu_dma_latency_dentry.clone());
+    register_device(makedev(10, 62), cpu_dma_latency_dentry.clone());
     add_vfs_dentry(cpu_dma_latency_dentry);
 
     let urandom_dentry = UrandomDentry::new("urandom", sb.clone(), Some(root_dentry.clone()));
@@ -55,7 +84,22 @@ pub fn init_devfs(root_dentry: Arc<dyn Dentry>) -> SysResult<()> {
     urandom_dentry.set_inode(urandom_inode);
     *urandom_dentry.get_state() = DentryState::Valid;
     root_dentry.add_child(urandom_dentry.clone());
+    register_device(makedev(1, 9), urandom_dentry.clone());
     add_vfs_dentry(urandom_dentry);
+    // virtio 块设备, 每个盘预留 16 个次设备号给分区
+    for (name, dev) in device::block_devices() {
+        let rdev = block_minor(&name).map(|minor| makedev(253, minor));
+        new_block_node(&root_dentry, &name, dev, rdev);
+    }
+
+    let loop_control_dentry = LoopControlDentry::new("loop-control", sb.clone(), Some(root_dentry.clone()));
+    let loop_control_inode = LoopControlInode::new(sb.clone());
+    loop_control_dentry.set_inode(loop_control_inode);
+    *loop_control_dentry.get_state() = DentryState::Valid;
+    root_dentry.add_child(loop_control_dentry.clone());
+    register_device(makedev(10, 237), loop_control_dentry.clone());
+    add_vfs_dentry(loop_control_dentry);
+    loopdev::precreate_loops(&root_dentry);
 /* 
     let tty_dentry = TtyDentry::new("tty", sb.clone(), Some(root_dentry.clone()));
     root_dentry.insert(tty_dentry.clone());
@@ -75,13 +119,62 @@ pub fn init_devfs(root_dentry: Arc<dyn Dentry>) -> SysResult<()> {
     Ok(())
 }
 
+/// 没有固定设备号的块设备 (如 loop 设备上的分区) 用的主设备号
+const BLOCK_EXT_MAJOR: u32 = 259;
+static NEXT_EXT_MINOR: AtomicU32 = AtomicU32::new(0);
+
+fn new_block_node(dir: &Arc<dyn Dentry>, name: &str, dev: Arc<dyn BlockDevice>, rdev: Option<u64>) {
+    let sb = dir.get_superblock();
+    let blk_dentry = BlockDevDentry::new(name, dev.clone(), sb.clone(), Some(dir.clone()));
+    let blk_inode = BlockDevInode::new(sb, dev);
+    blk_dentry.set_inode(blk_inode);
+    *blk_dentry.get_state() = DentryState::Valid;
+    dir.add_child(blk_dentry.clone());
+    let rdev = rdev.unwrap_or_else(|| makedev(BLOCK_EXT_MAJOR, NEXT_EXT_MINOR.fetch_add(1, Ordering::Relaxed)));
+    register_device(rdev, blk_dentry.clone());
+    add_vfs_dentry(blk_dentry);
+}
+
+/// 运行时在 /dev 下新建块设备节点, `rdev` 为 None 时分配一个扩展设备号
+pub(crate) fn add_block_node(name: &str, dev: Arc<dyn BlockDevice>, rdev: Option<u64>) {
+    if let Ok(dir) = lookup_path("/dev") {
+        new_block_node(&dir, name, dev, rdev);
+    }
+}
+
+/// 删掉 /dev 下的块设备节点
+pub(crate) fn remove_block_node(name: &str) {
+    let Ok(dir) = lookup_path("/dev") else {
+        return;
+    };
+    let removed = dir.get_inner().children.lock().remove(name).and_then(|child| child.upgrade());
+    if let Some(dentry) = removed {
+        if let Ok(inode) = dentry.get_inode() {
+            let rdev = inode.get_meta().inner.lock().rdev;
+            DEVICES.lock().remove(&rdev);
+        }
+        remove_vfs_dentry(&dentry);
+    }
+}
+
+/// vdX 和它的分区 vdXN 的次设备号: 盘号 * 16 + 分区号
+fn block_minor(name: &str) -> Option<u32> {
+    let rest = name.strip_prefix("vd")?;
+    let disk = rest.bytes().next().filter(u8::is_ascii_lowercase)? - b'a';
+    let part = match &rest[1..] {
+        "" => 0,
+        number => number.parse::<u32>().ok().filter(|&n| n < 16)?,
+    };
+    Some(disk as u32 * 16 + part)
+}
+
 pub fn add_tty(ttydentry:Arc<dyn Dentry>,ttyinode:Arc<dyn vfs_defs::Inode>){
-    let root_dentry = get_root_dentry();
-    let dev = root_dentry.lookup("dev").unwrap();
+    let dev = lookup_path("/dev").unwrap();
     *ttyinode.get_meta()._type.lock() = vfs_defs::DiskInodeType::File;
     ttydentry.set_inode(ttyinode);
     *ttydentry.get_state() = DentryState::Valid;
     dev.add_child(ttydentry.clone());
+    register_device(makedev(5, 0), ttydentry.clone());
     add_vfs_dentry(ttydentry);
 }
 
@@ -120,10 +213,10 @@ impl FileSystemType for DevFsType {
         Ok(root_dentry)
     }
     fn umount(self:Arc<Self>,
-            _path:&str,
+            path:&str,
             _flags:MountFlags
         )->system_result::SysResult<()> {
-        unimplemented!()
+        self.remove_superblock(path)
     }
 
 }
@@ -147,4 +240,14 @@ impl SuperBlock for DevSuperBlock {
     fn get_inner(&self) -> &SuperBlockInner{
         &self.inner
     }
+    /// 和 Linux 的 devtmpfs 一样报成 tmpfs, 设备节点不占空间
+    fn statfs(&self) -> SysResult<StatFs> {
+        Ok(StatFs {
+            f_type: TMPFS_MAGIC,
+            f_bsize: 4096,
+            f_namelen: NAME_MAX as isize,
+            f_frsize: 4096,
+            ..Default::default()
+        })
+    }
 }
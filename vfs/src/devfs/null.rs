@@ -77,7 +77,7 @@ impl NullInode {
     pub fn new(super_block: Arc<dyn SuperBlock>, _size: usize) -> Arc<Self> {
         let size = DISK_BLOCK_SZ;
         let ret = Arc::new(Self {
-            meta: InodeMeta::new(InodeMode::FILE,ino_alloc(), super_block),
+            meta: InodeMeta::new(InodeMode::CHAR,ino_alloc(), super_block),
         });
         *ret.meta._type.lock() = DiskInodeType::File;
         ret.get_meta().inner.lock().size = size as u32;
@@ -92,16 +92,16 @@ impl Inode for NullInode {
 
     fn get_attr(&self) -> SysResult<Kstat> {
         let inner = self.meta.inner.lock();
-        let mode = self.meta.mode.bits();
+        let mode = inner.mode.bits();
         let len = inner.size;
         Ok(Kstat {
             st_dev: 0,
             st_ino: self.meta.ino as u64,
             st_mode: mode,
             st_nlink: 1,
-            st_uid: 0,
-            st_gid: 0,
-            st_rdev: 0,
+            st_uid: inner.uid,
+            st_gid: inner.gid,
+            st_rdev: inner.rdev,
             __pad: 0,
             st_size: len as u64,
             st_blksize: 512,
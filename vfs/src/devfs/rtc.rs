@@ -7,6 +7,18 @@ use system_result::{SysError, SysResult};
 use vfs_defs::{
     Dentry, DentryInner, File, FileInner, Inode, InodeMeta, InodeMode, SuperBlock,Kstat,OpenFlags,DiskInodeType,RenameFlags,ino_alloc,
 };
+use time::{RtcTime, NSEC_PER_SEC};
+use crate::{read_user, write_user};
+
+/// ioctl requests from <linux/rtc.h>
+const RTC_AIE_ON: usize = 0x7001;
+const RTC_AIE_OFF: usize = 0x7002;
+const RTC_UIE_ON: usize = 0x7003;
+const RTC_UIE_OFF: usize = 0x7004;
+const RTC_PIE_ON: usize = 0x7005;
+const RTC_PIE_OFF: usize = 0x7006;
+const RTC_RD_TIME: usize = 0x80247009;
+const RTC_SET_TIME: usize = 0x4024700a;
 
 
 pub struct RtcDentry {
@@ -77,7 +89,7 @@ impl RtcInode {
     pub fn new(super_block: Arc<dyn SuperBlock>, _size: usize) -> Arc<Self> {
         let size = DISK_BLOCK_SZ;
         let ret = Arc::new(Self {
-            meta: InodeMeta::new(InodeMode::FILE,ino_alloc(), super_block),
+            meta: InodeMeta::new(InodeMode::CHAR,ino_alloc(), super_block),
         });
         *ret.meta._type.lock() = DiskInodeType::File;
         ret.get_meta().inner.lock().size = size as u32;
@@ -92,16 +104,16 @@ impl Inode for RtcInode {
 
     fn get_attr(&self) -> SysResult<Kstat> {
         let inner = self.meta.inner.lock();
-        let mode = self.meta.mode.bits();
+        let mode = inner.mode.bits();
         let len = inner.size;
         Ok(Kstat {
             st_dev: 0,
             st_ino: self.meta.ino as u64,
             st_mode: mode,
             st_nlink: 1,
-            st_uid: 0,
-            st_gid: 0,
-            st_rdev: 0,
+            st_uid: inner.uid,
+            st_gid: inner.gid,
+            st_rdev: inner.rdev,
             __pad: 0,
             st_size: len as u64,
             st_blksize: 512,
@@ -154,4 +166,23 @@ impl File for RtcFile {
     fn poll(&self, _events: vfs_defs::PollEvents) -> vfs_defs::PollEvents {
         vfs_defs::PollEvents::POLLOUT | vfs_defs::PollEvents::POLLIN
     }
+    fn ioctl(&self, cmd: usize, arg: usize) -> SysResult<isize> {
+        let rtc = device::RTC_DEVICE.get().ok_or(SysError::ENODEV)?;
+        match cmd {
+            RTC_RD_TIME => {
+                let tm = RtcTime::from_unix_secs(rtc.read_time() / NSEC_PER_SEC as u64);
+                write_user(arg, &tm)?;
+                Ok(0)
+            }
+            RTC_SET_TIME => {
+                let tm: RtcTime = read_user(arg)?;
+                let secs = tm.to_unix_secs().ok_or(SysError::EINVAL)?;
+                rtc.set_time(secs * NSEC_PER_SEC as u64);
+                Ok(0)
+            }
+            // 没有中断支持, 开关请求直接忽略
+            RTC_AIE_ON | RTC_AIE_OFF | RTC_UIE_ON | RTC_UIE_OFF | RTC_PIE_ON | RTC_PIE_OFF => Ok(0),
+            _ => Err(SysError::ENOTTY),
+        }
+    }
 }
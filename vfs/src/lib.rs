@@ -4,22 +4,39 @@ pub mod devfs;
 mod procfs;
 mod memfs;
 mod tmpfs;
+mod overlay;
+mod bind;
+mod ninep;
+mod mount;
+mod namei;
+mod perm;
+mod uaccess;
 //mod fdtable;
 extern crate alloc;
 use alloc::{collections::BTreeMap, string::{String, ToString}, sync::Arc,vec::Vec};
 use system_result::{SysResult,SysError};
 use easy_fs::EfsFsType;
 use ext4::Ext4ImplFsType;
-use devfs::{DevFsType,init_devfs};
-use procfs::{ProcFsType,init_procfs};
+use vfat::VfatFsType;
+use devfs::DevFsType;
+use procfs::ProcFsType;
 use tmpfs::TmpFsType;
+use overlay::OverlayFsType;
+use ninep::NinePFsType;
+pub use tmpfs::memfd_create;
 use lazy_static::lazy_static;
 use sync::{Mutex,Once};
 use vfs_defs::{FileSystemType, MountFlags,Dentry};
 use device::BLOCK_DEVICE;
 pub use ext4::BLOCK_SIZE;
 use memfs::{MemFile,MemInode,MemDentry};
-pub use devfs::add_tty;
+pub use devfs::{add_tty,register_device,find_device,register_fd_resolver};
+pub use procfs::{register_proc_file,register_exe_resolver};
+pub use devfs::BlockDevInode;
+pub use mount::{Mount,do_mount,do_bind,do_remount,do_umount,find_mount,lookup_mount,mount_of,mount_flags,check_writable,probe_fs,sync_filesystems};
+pub use uaccess::{register_user_copy,read_user,write_user};
+pub use perm::{FsCred,inode_permission,check_sticky,may_modify_dir,MAY_EXEC,MAY_WRITE,MAY_READ};
+pub use namei::{namei,namei_parent,namei_create,lookup_path,is_symlink,NAME_MAX,PATH_MAX,MAX_SYMLINKS};
 
 lazy_static!{
     pub static ref FILE_SYSTEMS:Mutex<FileSystemManager> =
@@ -60,26 +77,28 @@ pub fn register_all_fs(){
     let mut file_systems = FILE_SYSTEMS.lock();
     let _ = file_systems.register_fs("EasyFs".to_string(), Arc::new(EfsFsType::new()));
     let _ = file_systems.register_fs("Ext4".to_string(), Arc::new(Ext4ImplFsType::new()));
+    let _ = file_systems.register_fs("vfat".to_string(), Arc::new(VfatFsType::new()));
     let _ = file_systems.register_fs("tmpfs".to_string(), TmpFsType::new());
     let _ = file_systems.register_fs("procfs".to_string(), ProcFsType::new());
     let _ = file_systems.register_fs("devfs".to_string(), DevFsType::new());
+    let _ = file_systems.register_fs("overlay".to_string(), OverlayFsType::new());
+    let _ = file_systems.register_fs("9p".to_string(), NinePFsType::new());
 }
 
 pub fn init(){
     register_all_fs();
-    let root_fs = FILE_SYSTEMS.lock().file_systems.get(ROOT_FS).unwrap().clone();
-    let root_dentry = root_fs.mount("/", None,MountFlags::empty(),Some(BLOCK_DEVICE.get().unwrap().clone())).unwrap();
-    
-    let dev_fs = FILE_SYSTEMS.lock().file_systems.get("devfs").unwrap().clone();
-    let dev_dentry = dev_fs.mount("dev", Some(root_dentry.clone()), MountFlags::empty(), None).unwrap();
-    init_devfs(dev_dentry);
-    
-    let proc_fs = FILE_SYSTEMS.lock().file_systems.get("procfs").unwrap().clone();
-    let proc_dentry = proc_fs.mount("proc", Some(root_dentry.clone()), MountFlags::empty(), None).unwrap();
-    init_procfs(proc_dentry);
+    let root_dev = BLOCK_DEVICE.get().unwrap().clone();
+    // 根设备在块设备表里的名字, 用作 /proc/mounts 中的挂载源
+    let root_source = device::block_devices()
+        .into_iter()
+        .find(|(_, dev)| Arc::as_ptr(dev) as *const () == Arc::as_ptr(&root_dev) as *const ())
+        .map_or("rootfs".to_string(), |(name, _)| "/dev/".to_string() + &name);
+    let root_dentry = mount::mount_root(&root_source, ROOT_FS, root_dev).unwrap();
 
-    let tmp_fs = FILE_SYSTEMS.lock().file_systems.get("tmpfs").unwrap().clone();
-    let _dev_dentry = tmp_fs.mount("tmp", Some(root_dentry.clone()), MountFlags::empty(), None).unwrap();
+    let nosuid_noexec = MountFlags::MS_NOSUID | MountFlags::MS_NOEXEC;
+    mount::mount_at("devtmpfs", "devfs", &root_dentry, "dev", MountFlags::MS_NOSUID).unwrap();
+    mount::mount_at("proc", "procfs", &root_dentry, "proc", nosuid_noexec | MountFlags::MS_NODEV).unwrap();
+    mount::mount_at("tmpfs", "tmpfs", &root_dentry, "tmp", MountFlags::MS_NOSUID | MountFlags::MS_NODEV).unwrap();
 
     ROOT_DENTRY.call_once(|| root_dentry);
 
@@ -100,4 +119,8 @@ lazy_static!{
 
 pub fn add_vfs_dentry(dent:Arc<dyn Dentry>){
     VFS_DENTRY.lock().dentry.push(dent);
+}
+
+pub fn remove_vfs_dentry(dent:&Arc<dyn Dentry>){
+    VFS_DENTRY.lock().dentry.retain(|d| Arc::as_ptr(d) as *const () != Arc::as_ptr(dent) as *const ());
 }
\ No newline at end of file
@@ -41,6 +41,22 @@ impl Dentry for MemDentry{
         add_vfs_dentry(child.clone());
         Ok(child)
     }
+    fn concrete_symlink(self: Arc<Self>, name: &str, target: &str) -> SysResult<Arc<dyn Dentry>> {
+        let child = self.clone().get_child(name).ok_or(SysError::ENOENT)?;
+        let inode = MemInode::new_symlink(target, self.get_superblock().clone());
+        child.set_inode(inode);
+        *child.get_state() = DentryState::Valid;
+        add_vfs_dentry(child.clone());
+        Ok(child)
+    }
+    fn concrete_mknod(self: Arc<Self>, name: &str, mode: InodeMode, rdev: u64) -> SysResult<Arc<dyn Dentry>> {
+        let child = self.clone().get_child(name).ok_or(SysError::ENOENT)?;
+        let inode = MemInode::new_special(mode, rdev, self.get_superblock().clone());
+        child.set_inode(inode);
+        *child.get_state() = DentryState::Valid;
+        add_vfs_dentry(child.clone());
+        Ok(child)
+    }
     fn concrete_unlink(self: Arc<Self>, old: &Arc<dyn Dentry>) -> SysResult<()> {
         self.get_inner().children.lock().remove(old.get_name_str()).ok_or(SysError::ENOENT).map(|_| ())
     }
@@ -1,6 +1,6 @@
-use vfs_defs::{FileInner,File,PollEvents};
+use vfs_defs::{FileInner,File,OpenFlags,PollEvents};
 use super::MemInode;
-use system_result::SysError;
+use system_result::{SysError,SysResult};
 
 pub struct MemFile{
     inner:FileInner
@@ -23,10 +23,30 @@ impl File for MemFile{
         inode.read(offset, buf)
 
     }
+    /// 被封印禁止的写一个字节也不写
     fn write_at(&self, offset: usize, buf: &[u8])->usize {
         let inode = self.get_dentry().get_inode().unwrap().downcast_arc::<MemInode>().map_err(|_| SysError::ENOENT).unwrap();
+        if inode.check_seals(offset, buf.len()).is_err() {
+            return 0;
+        }
         inode.write(offset, buf)
     }
+    fn pwrite(&self, offset: usize, buf: &[u8]) -> SysResult<usize> {
+        let inode = self.get_dentry().get_inode()?.downcast_arc::<MemInode>().map_err(|_| SysError::ENOENT)?;
+        inode.check_seals(offset, buf.len())?;
+        Ok(inode.write(offset, buf))
+    }
+    fn write_checked(&self, buf: &[u8]) -> SysResult<usize> {
+        let mut offset = self.get_offset();
+        if self.get_inner().flags.lock().contains(OpenFlags::APPEND) {
+            let (write_size, end) = self.append(buf)?;
+            *offset = end;
+            return Ok(write_size);
+        }
+        let write_size = self.pwrite(*offset, buf)?;
+        *offset += write_size;
+        Ok(write_size)
+    }
     fn readable(&self) -> bool {
         let (readable,_writable) = self.get_inner().flags.lock().read_write();
         readable
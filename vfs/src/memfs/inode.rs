@@ -1,23 +1,55 @@
-use vfs_defs::{InodeMeta,Kstat,Inode,InodeMode,ino_alloc,SuperBlock};
+use vfs_defs::{InodeMeta,Kstat,Inode,InodeMode,DiskInodeType,FileSeals,ino_alloc,SuperBlock,XattrFlags};
+use alloc::collections::BTreeMap;
 use alloc::vec::Vec;
+use alloc::string::String;
+use system_result::{SysError,SysResult};
 use alloc::sync::Arc;
 use sync::Mutex;
 
 pub struct MemInode{
     meta:InodeMeta,
-    data:Mutex<Vec<u8>>
+    data:Mutex<Vec<u8>>,
+    /// 扩展属性只放在内存里
+    xattrs:Mutex<BTreeMap<String,Vec<u8>>>
 }
 
 impl MemInode{
     pub fn new(mode:InodeMode,superblock:Arc<dyn SuperBlock>)->Arc<Self>{
         let ret = Arc::new(Self{
             meta:InodeMeta::new(mode, ino_alloc(), superblock),
-            data:Mutex::new(Vec::new())
+            data:Mutex::new(Vec::new()),
+            xattrs:Mutex::new(BTreeMap::new())
         });
         let _type = mode.into();
         *ret.meta._type.lock() = _type;
         ret
     }
+    /// FIFO、套接字或设备节点, `mode` 里的权限位原样保留
+    pub fn new_special(mode:InodeMode,rdev:u64,superblock:Arc<dyn SuperBlock>)->Arc<Self>{
+        let ret = Arc::new(Self{
+            meta:InodeMeta::new(mode, ino_alloc(), superblock),
+            data:Mutex::new(Vec::new()),
+            xattrs:Mutex::new(BTreeMap::new())
+        });
+        *ret.meta._type.lock() = DiskInodeType::File;
+        let mut inner = ret.meta.inner.lock();
+        inner.mode = mode;
+        inner.rdev = rdev;
+        drop(inner);
+        ret
+    }
+    /// 内容为 `target` 的符号链接
+    pub fn new_symlink(target:&str,superblock:Arc<dyn SuperBlock>)->Arc<Self>{
+        let mode = InodeMode::LINK | InodeMode::OWNER_MASK | InodeMode::GROUP_MASK | InodeMode::OTHER_MASK;
+        let ret = Arc::new(Self{
+            meta:InodeMeta::new(mode, ino_alloc(), superblock),
+            data:Mutex::new(Vec::from(target.as_bytes())),
+            xattrs:Mutex::new(BTreeMap::new())
+        });
+        *ret.meta._type.lock() = DiskInodeType::File;
+        ret.meta.inner.lock().size = target.len() as u32;
+        ret
+    }
     pub fn read(self:Arc<Self>,offset:usize,buf:&mut [u8])->usize{
         let data = self.data.lock();
         let available = data.len().saturating_sub(offset);
@@ -38,11 +70,43 @@ impl MemInode{
         for (i, &byte) in buf.iter().enumerate() {
             data[offset + i] = byte;
         }
+        self.meta.inner.lock().size = data.len() as u32;
         return buf.len();
     }
+    /// 在 `offset` 处写 `len` 字节是否被封印禁止
+    pub fn check_seals(&self,offset:usize,len:usize)->SysResult<()>{
+        let seals = match self.meta.inner.lock().seals {
+            Some(seals) => seals,
+            None => return Ok(()),
+        };
+        if len == 0 {
+            return Ok(());
+        }
+        if seals.intersects(FileSeals::WRITE | FileSeals::FUTURE_WRITE) {
+            return Err(SysError::EPERM);
+        }
+        if seals.contains(FileSeals::GROW) && offset + len > self.data.lock().len() {
+            return Err(SysError::EPERM);
+        }
+        Ok(())
+    }
 }
 
 impl Inode for MemInode{
+    fn truncate(&self,size:usize)->SysResult<()>{
+        let mut data = self.data.lock();
+        let mut inner = self.meta.inner.lock();
+        if let Some(seals) = inner.seals {
+            if (size < data.len() && seals.contains(FileSeals::SHRINK))
+                || (size > data.len() && seals.contains(FileSeals::GROW))
+            {
+                return Err(SysError::EPERM);
+            }
+        }
+        data.resize(size, 0);
+        inner.size = size as u32;
+        Ok(())
+    }
     fn get_meta(&self) -> &InodeMeta {
         &self.meta
     }
@@ -54,16 +118,16 @@ impl Inode for MemInode{
     }
     fn get_attr(&self)->system_result::SysResult<Kstat> {
         let inner = self.meta.inner.lock();
-        let mode = self.meta.mode.bits();
+        let mode = inner.mode.bits();
         let len = inner.size;
         Ok(Kstat {
             st_dev: 0,
             st_ino: self.meta.ino as u64,
             st_mode: mode,
             st_nlink: 1,
-            st_uid: 0,
-            st_gid: 0,
-            st_rdev: 0,
+            st_uid: inner.uid,
+            st_gid: inner.gid,
+            st_rdev: inner.rdev,
             __pad: 0,
             st_size: len as u64,
             st_blksize: 512,
@@ -81,4 +145,25 @@ impl Inode for MemInode{
     fn get_size(&self) -> u32 {
         self.data.lock().len() as u32
     }
+    fn read_link(&self) -> SysResult<String> {
+        if self.meta.mode & InodeMode::TYPE_MASK != InodeMode::LINK {
+            return Err(SysError::EINVAL);
+        }
+        String::from_utf8(self.data.lock().clone()).map_err(|_| SysError::EINVAL)
+    }
+    fn get_xattr(&self, name: &str) -> SysResult<Vec<u8>> {
+        self.xattrs.lock().get(name).cloned().ok_or(SysError::ENODATA)
+    }
+    fn set_xattr(&self, name: &str, value: &[u8], flags: XattrFlags) -> SysResult<()> {
+        let mut xattrs = self.xattrs.lock();
+        flags.check(xattrs.contains_key(name))?;
+        xattrs.insert(String::from(name), Vec::from(value));
+        Ok(())
+    }
+    fn list_xattr(&self) -> SysResult<Vec<String>> {
+        Ok(self.xattrs.lock().keys().cloned().collect())
+    }
+    fn remove_xattr(&self, name: &str) -> SysResult<()> {
+        self.xattrs.lock().remove(name).map(|_| ()).ok_or(SysError::ENODATA)
+    }
 }
\ No newline at end of file
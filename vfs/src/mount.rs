@@ -0,0 +1,399 @@
+//! 挂载表
+//!
+//! 每次成功的 mount(2) 在这里留一条记录: 挂载源, 挂载点, 文件系统和挂载选项.
+//! 挂载点目录项本身不变, 路径解析查到它时再通过挂载表转到挂载的根目录,
+//! 见 [`crate::namei`].
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use device::BlockDevice;
+use sync::Mutex;
+use system_result::{SysError, SysResult};
+use vfs_defs::{Dentry, DentryState, DiskInodeType, FileSystemType, MountFlags};
+
+use super::{remove_vfs_dentry, FILE_SYSTEMS};
+use crate::bind::BindDentry;
+use crate::devfs::init_devfs;
+use crate::ninep::mount_9p;
+use crate::overlay::mount_overlay;
+use crate::procfs::init_procfs;
+
+/// mount(2) 使用的文件系统名 -> 内部注册名
+const FS_NAMES: &[(&str, &str)] = &[
+    ("ext4", "Ext4"),
+    ("ext3", "Ext4"),
+    ("ext2", "Ext4"),
+    ("vfat", "vfat"),
+    ("msdos", "vfat"),
+    ("tmpfs", "tmpfs"),
+    ("proc", "procfs"),
+    ("devtmpfs", "devfs"),
+    ("easyfs", "EasyFs"),
+    ("overlay", "overlay"),
+    ("9p", "9p"),
+];
+
+/// 需要块设备的文件系统
+const BLOCK_FS: &[&str] = &["Ext4", "vfat", "EasyFs"];
+
+pub struct Mount {
+    /// 挂载源, 如 /dev/vda
+    pub source: String,
+    /// 挂载点绝对路径
+    pub path: String,
+    pub fs: Arc<dyn FileSystemType>,
+    /// 挂载的根目录项
+    pub root: Arc<dyn Dentry>,
+    pub flags: Mutex<MountFlags>,
+    /// 被覆盖的目录项, 根文件系统没有
+    pub mountpoint: Option<Arc<dyn Dentry>>,
+    /// 挂载点所在的挂载
+    pub parent: Option<Arc<Mount>>,
+    /// 绑定挂载与源共享文件系统, 卸载时不通知文件系统
+    bind: bool,
+    /// mount(2) 的 data 参数, 在 /proc/mounts 中跟在挂载选项后面
+    data: String,
+}
+
+static MOUNTS: Mutex<Vec<Arc<Mount>>> = Mutex::new(Vec::new());
+
+fn same_dentry(a: &Arc<dyn Dentry>, b: &Arc<dyn Dentry>) -> bool {
+    Arc::as_ptr(a) as *const () == Arc::as_ptr(b) as *const ()
+}
+
+/// 内部注册名对应的 mount(2) 文件系统名
+fn linux_fs_name(name: &str) -> &str {
+    FS_NAMES
+        .iter()
+        .find(|(_, internal)| *internal == name)
+        .map_or(name, |(linux, _)| *linux)
+}
+
+/// 按超级块魔数猜测块设备上的文件系统
+pub fn probe_fs(dev: &Arc<dyn BlockDevice>) -> Option<&'static str> {
+    let mut block = vec![0u8; 512];
+    // ext2/3/4: 超级块位于 1024 字节处, s_magic 在其中偏移 0x38
+    dev.read_block(2, &mut block);
+    if u16::from_le_bytes([block[0x38], block[0x39]]) == 0xEF53 {
+        return Some("Ext4");
+    }
+    dev.read_block(0, &mut block);
+    if block[510] == 0x55 && block[511] == 0xAA
+        && (&block[82..87] == b"FAT32" || &block[54..57] == b"FAT")
+    {
+        return Some("vfat");
+    }
+    None
+}
+
+impl Mount {
+    /// `dentry` 是否位于本挂载之下
+    pub fn contains(&self, dentry: &Arc<dyn Dentry>) -> bool {
+        let mut cur = Some(dentry.clone());
+        while let Some(d) = cur {
+            if same_dentry(&d, &self.root) {
+                return true;
+            }
+            cur = d.get_father();
+        }
+        false
+    }
+
+    fn options(&self) -> String {
+        let flags = *self.flags.lock();
+        let mut opts = String::from(if flags.contains(MountFlags::MS_RDONLY) { "ro" } else { "rw" });
+        for (flag, name) in [
+            (MountFlags::MS_NOSUID, "nosuid"),
+            (MountFlags::MS_NODEV, "nodev"),
+            (MountFlags::MS_NOEXEC, "noexec"),
+            (MountFlags::MS_SYNCHRONOUS, "sync"),
+            (MountFlags::MS_DIRSYNC, "dirsync"),
+            (MountFlags::MS_NODIRATIME, "nodiratime"),
+        ] {
+            if flags.contains(flag) {
+                opts += ",";
+                opts += name;
+            }
+        }
+        opts += if flags.contains(MountFlags::MS_NOATIME) { ",noatime" } else { ",relatime" };
+        if !self.data.is_empty() {
+            opts += ",";
+            opts += &self.data;
+        }
+        opts
+    }
+}
+
+fn mount_fs(
+    source: &str,
+    fs: Arc<dyn FileSystemType>,
+    mountpoint: Option<Arc<dyn Dentry>>,
+    flags: MountFlags,
+    device: Option<Arc<dyn BlockDevice>>,
+    data: &str,
+) -> SysResult<Arc<dyn Dentry>> {
+    let flags = flags & MountFlags::MS_PER_MOUNT;
+    let parent = mountpoint.as_ref().and_then(|m| m.get_father());
+    let name = mountpoint.as_ref().map_or(String::from("/"), |m| m.get_name_string());
+    // 有的文件系统会把根目录项挂进父目录的 children, 挂载点要保持原样
+    let prev = parent
+        .as_ref()
+        .and_then(|p| p.get_inner().children.lock().get(&name).cloned());
+    let root = match fs.get_inner().name.as_str() {
+        // overlay 的各层在 data 里给出
+        "overlay" => mount_overlay(fs.clone(), &name, parent.clone(), flags, data)?,
+        // 挂载源是 9P 通道的挂载标签
+        "9p" => mount_9p(fs.clone(), &name, parent.clone(), source, data)?,
+        _ => fs.clone().mount(&name, parent.clone(), flags, device)?,
+    };
+    *root.get_state() = DentryState::Valid;
+    if let Some(parent) = parent.as_ref() {
+        let mut children = parent.get_inner().children.lock();
+        match prev {
+            Some(prev) => children.insert(name.clone(), prev),
+            None => children.remove(&name),
+        };
+    }
+    match fs.get_inner().name.as_str() {
+        "procfs" => init_procfs(root.clone())?,
+        "devfs" => init_devfs(root.clone())?,
+        _ => {}
+    }
+    let parent_mount = mountpoint.as_ref().and_then(mount_of);
+    MOUNTS.lock().push(Arc::new(Mount {
+        source: source.to_string(),
+        path: mountpoint.as_ref().map_or(String::from("/"), |m| m.path()),
+        fs,
+        root: root.clone(),
+        flags: Mutex::new(flags),
+        mountpoint,
+        parent: parent_mount,
+        bind: false,
+        data: data.to_string(),
+    }));
+    Ok(root)
+}
+
+/// 挂载根文件系统
+pub fn mount_root(source: &str, fstype: &str, device: Arc<dyn BlockDevice>) -> SysResult<Arc<dyn Dentry>> {
+    let fs = FILE_SYSTEMS.lock().find_fs(&fstype.to_string()).ok_or(SysError::ENODEV)?;
+    mount_fs(source, fs, None, MountFlags::empty(), Some(device), "")
+}
+
+/// 内核初始化时在 `parent` 下挂载, 挂载点在磁盘上不存在时建一个占位目录项
+pub fn mount_at(
+    source: &str,
+    fstype: &str,
+    parent: &Arc<dyn Dentry>,
+    name: &str,
+    flags: MountFlags,
+) -> SysResult<Arc<dyn Dentry>> {
+    let fs = FILE_SYSTEMS.lock().find_fs(&fstype.to_string()).ok_or(SysError::ENODEV)?;
+    let mountpoint = match parent.lookup(name) {
+        Ok(dentry) => dentry,
+        Err(_) => parent.find_or_create(name, DiskInodeType::Directory),
+    };
+    mount_fs(source, fs, Some(mountpoint), flags, None, "")
+}
+
+/// mount(2): 在目录 `target` 上挂载 `fstype`, `fstype` 为 "auto" 时按超级块探测.
+/// `device` 是解析 `source` 得到的块设备, 只有需要设备的文件系统才会检查它.
+/// `data` 是文件系统自己的挂载选项
+pub fn do_mount(
+    source: &str,
+    target: &Arc<dyn Dentry>,
+    fstype: &str,
+    flags: MountFlags,
+    device: SysResult<Arc<dyn BlockDevice>>,
+    data: &str,
+) -> SysResult<()> {
+    if !target.is_dir() {
+        return Err(SysError::ENOTDIR);
+    }
+    let internal = if fstype == "auto" {
+        probe_fs(device.as_ref().map_err(|e| *e)?).ok_or(SysError::EINVAL)?
+    } else {
+        FS_NAMES
+            .iter()
+            .find(|(linux, _)| *linux == fstype)
+            .map_or(fstype, |(_, internal)| *internal)
+    };
+    let fs = FILE_SYSTEMS.lock().find_fs(&internal.to_string()).ok_or(SysError::ENODEV)?;
+    let device = if BLOCK_FS.contains(&internal) {
+        let device = device?;
+        // 超级块不对时文件系统驱动可能直接崩溃, 先核对一下
+        if internal != "EasyFs" && probe_fs(&device) != Some(internal) {
+            return Err(SysError::EINVAL);
+        }
+        // 只读的设备 (如只读的 loop 设备) 只能只读挂载
+        if device.read_only() && !flags.contains(MountFlags::MS_RDONLY) {
+            return Err(SysError::EACCES);
+        }
+        Some(device)
+    } else {
+        None
+    };
+    mount_fs(source, fs, Some(target.clone()), flags, device, data)?;
+    Ok(())
+}
+
+/// mount(2) MS_BIND: 让 `target` 处显示 `source` 的目录树.
+/// 绑定挂载有自己的一套目录项, 挂载选项只作用于经 `target` 访问到的文件
+pub fn do_bind(source: &Arc<dyn Dentry>, target: &Arc<dyn Dentry>, flags: MountFlags) -> SysResult<()> {
+    if source.is_dir() != target.is_dir() {
+        return Err(if target.is_dir() { SysError::ENOTDIR } else { SysError::EISDIR });
+    }
+    let fs = source
+        .get_superblock()
+        .get_inner()
+        ._type
+        .upgrade()
+        .ok_or(SysError::EINVAL)?;
+    let parent = mount_of(target);
+    MOUNTS.lock().push(Arc::new(Mount {
+        source: source.path(),
+        path: target.path(),
+        fs,
+        root: BindDentry::new_root(source, target),
+        flags: Mutex::new(flags & MountFlags::MS_PER_MOUNT),
+        mountpoint: Some(target.clone()),
+        parent,
+        bind: true,
+        data: String::new(),
+    }));
+    Ok(())
+}
+
+/// 挂在 `mountpoint` 上的最上层挂载
+pub fn lookup_mount(mountpoint: &Arc<dyn Dentry>) -> Option<Arc<Mount>> {
+    MOUNTS
+        .lock()
+        .iter()
+        .rev()
+        .find(|m| m.mountpoint.as_ref().is_some_and(|mp| same_dentry(mp, mountpoint)))
+        .cloned()
+}
+
+/// 以 `root` 为根的挂载, 即 `root` 是某个挂载点时返回它的挂载记录.
+/// 同一目录上叠加挂载时返回最上层的
+pub fn find_mount(root: &Arc<dyn Dentry>) -> Option<Arc<Mount>> {
+    MOUNTS
+        .lock()
+        .iter()
+        .rev()
+        .find(|m| same_dentry(&m.root, root))
+        .cloned()
+}
+
+/// mount(2) MS_REMOUNT: 修改挂载点的选项
+pub fn do_remount(target: &Arc<dyn Dentry>, flags: MountFlags) -> SysResult<()> {
+    let mount = find_mount(target).ok_or(SysError::EINVAL)?;
+    *mount.flags.lock() = flags & MountFlags::MS_PER_MOUNT;
+    Ok(())
+}
+
+/// `mount` 在挂载表中的位置.
+/// 根文件系统和下面还挂着别的文件系统的挂载点都不能卸载
+fn umount_pos(mounts: &[Arc<Mount>], mount: &Arc<Mount>) -> SysResult<usize> {
+    let pos = mounts
+        .iter()
+        .position(|m| Arc::ptr_eq(m, mount))
+        .ok_or(SysError::EINVAL)?;
+    if mount.mountpoint.is_none()
+        || mounts
+            .iter()
+            .any(|m| m.parent.as_ref().is_some_and(|p| Arc::ptr_eq(p, mount)))
+    {
+        return Err(SysError::EBUSY);
+    }
+    Ok(pos)
+}
+
+/// umount(2). 调用者应先确认没有进程在使用这个挂载
+pub fn do_umount(mount: &Arc<Mount>, flags: MountFlags) -> SysResult<()> {
+    umount_pos(&MOUNTS.lock(), mount)?;
+    // 写回要做块 I/O, 不持 MOUNTS 锁, 写完再重新检查一遍
+    if !mount.bind {
+        mount.root.get_superblock().sync_fs()?;
+    }
+    let mut mounts = MOUNTS.lock();
+    let pos = umount_pos(&mounts, mount)?;
+    let mut device = None;
+    if !mount.bind {
+        device = mount.root.get_superblock().get_inner().dev.clone();
+        mount.fs.clone().umount(&mount.path, flags)?;
+    }
+    mounts.remove(pos);
+    drop(mounts);
+    remove_vfs_dentry(&mount.root);
+    if let Some(device) = device {
+        crate::devfs::loop_autoclear(&device);
+    }
+    Ok(())
+}
+
+/// 块设备上是否挂着文件系统
+pub(crate) fn device_mounted(dev: &Arc<dyn BlockDevice>) -> bool {
+    let key = Arc::as_ptr(dev) as *const ();
+    MOUNTS.lock().iter().filter(|m| !m.bind).any(|m| {
+        m.root
+            .get_superblock()
+            .get_inner()
+            .dev
+            .as_ref()
+            .is_some_and(|d| Arc::as_ptr(d) as *const () == key)
+    })
+}
+
+/// sync(2): 写回所有挂载的文件系统, 绑定挂载和源是同一个文件系统, 不重复写
+pub fn sync_filesystems() {
+    let mounts: Vec<Arc<Mount>> = MOUNTS.lock().iter().filter(|m| !m.bind).cloned().collect();
+    for m in mounts {
+        let _ = m.root.get_superblock().sync_fs();
+    }
+}
+
+/// `dentry` 所在的挂载
+pub fn mount_of(dentry: &Arc<dyn Dentry>) -> Option<Arc<Mount>> {
+    let mounts = MOUNTS.lock();
+    let mut cur = Some(dentry.clone());
+    while let Some(d) = cur {
+        if let Some(m) = mounts.iter().rev().find(|m| same_dentry(&m.root, &d)) {
+            return Some(m.clone());
+        }
+        cur = d.get_father();
+    }
+    None
+}
+
+/// `dentry` 所在挂载的选项
+pub fn mount_flags(dentry: &Arc<dyn Dentry>) -> MountFlags {
+    mount_of(dentry).map_or(MountFlags::empty(), |m| *m.flags.lock())
+}
+
+/// 修改 `dentry` 前检查它是否在只读挂载上
+pub fn check_writable(dentry: &Arc<dyn Dentry>) -> SysResult<()> {
+    if mount_flags(dentry).contains(MountFlags::MS_RDONLY) {
+        return Err(SysError::EROFS);
+    }
+    Ok(())
+}
+
+/// /proc/mounts 的内容
+pub fn list_mounts() -> String {
+    let mut res = String::new();
+    for m in MOUNTS.lock().iter() {
+        res += &format!(
+            "{} {} {} {} 0 0\n",
+            m.source,
+            m.path,
+            linux_fs_name(&m.fs.get_inner().name),
+            m.options()
+        );
+    }
+    res
+}
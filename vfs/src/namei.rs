@@ -0,0 +1,248 @@
+//! 路径解析
+//!
+//! 从起点目录逐个分量查找目录项. 查到的目录项若是挂载点, 转到挂载在上面的根目录;
+//! `..` 在挂载的根目录处先回到挂载点再向上, 在进程根目录处停住.
+//! 符号链接按内容展开, 总展开次数不超过 [`MAX_SYMLINKS`].
+//! 经过的每个目录都要有搜索权限.
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+};
+use system_result::{SysError, SysResult};
+use vfs_defs::{Dentry, InodeMode};
+
+use crate::get_root_dentry;
+use crate::mount::{lookup_mount, mount_of, Mount};
+use crate::perm::{inode_permission, FsCred, MAY_EXEC};
+
+/// 单个文件名的最大长度
+pub const NAME_MAX: usize = 255;
+/// 路径的最大长度(含结尾的 0)
+pub const PATH_MAX: usize = 4096;
+/// 一次解析中最多展开的符号链接数
+pub const MAX_SYMLINKS: usize = 40;
+
+fn same_dentry(a: &Arc<dyn Dentry>, b: &Arc<dyn Dentry>) -> bool {
+    Arc::as_ptr(a) as *const () == Arc::as_ptr(b) as *const ()
+}
+
+/// 目录项是否为符号链接
+pub fn is_symlink(dentry: &Arc<dyn Dentry>) -> bool {
+    dentry
+        .get_inode()
+        .is_ok_and(|inode| inode.get_meta().mode & InodeMode::TYPE_MASK == InodeMode::LINK)
+}
+
+struct Walker {
+    /// 进程根目录, `..` 不会越过它
+    root: Arc<dyn Dentry>,
+    cur: Arc<dyn Dentry>,
+    /// `cur` 所在的挂载
+    mnt: Option<Arc<Mount>>,
+    /// 已展开的符号链接数
+    links: usize,
+    cred: FsCred,
+}
+
+impl Walker {
+    fn new(root: &Arc<dyn Dentry>, start: &Arc<dyn Dentry>, path: &str, cred: &FsCred) -> SysResult<Self> {
+        if path.len() >= PATH_MAX {
+            return Err(SysError::ENAMETOOLONG);
+        }
+        let cur = if path.starts_with('/') { root.clone() } else { start.clone() };
+        Ok(Self {
+            root: root.clone(),
+            mnt: mount_of(&cur),
+            cur,
+            links: 0,
+            cred: cred.clone(),
+        })
+    }
+
+    /// 如果当前目录项上挂载了文件系统, 进入最上层挂载的根目录
+    fn cross_mounts(&mut self) {
+        while let Some(m) = lookup_mount(&self.cur) {
+            self.cur = m.root.clone();
+            self.mnt = Some(m);
+        }
+    }
+
+    fn dotdot(&mut self) {
+        loop {
+            if same_dentry(&self.cur, &self.root) {
+                return;
+            }
+            let at_mount_root = self.mnt.as_ref().is_some_and(|m| same_dentry(&m.root, &self.cur));
+            if at_mount_root {
+                let m = self.mnt.take().unwrap();
+                match m.mountpoint.clone() {
+                    Some(mountpoint) => {
+                        self.cur = mountpoint;
+                        self.mnt = m.parent.clone();
+                        continue;
+                    }
+                    // 全局根目录
+                    None => {
+                        self.mnt = Some(m);
+                        return;
+                    }
+                }
+            }
+            if let Some(father) = self.cur.get_father() {
+                self.cur = father;
+            }
+            self.cross_mounts();
+            return;
+        }
+    }
+
+    /// 在当前目录下查找一个分量, `follow` 为真时展开符号链接
+    fn step(&mut self, name: &str, follow: bool) -> SysResult<()> {
+        if name.len() > NAME_MAX {
+            return Err(SysError::ENAMETOOLONG);
+        }
+        let inode = self.cur.get_inode()?;
+        if !inode.is_dir() {
+            return Err(SysError::ENOTDIR);
+        }
+        inode_permission(&inode, &self.cred, MAY_EXEC)?;
+        match name {
+            "." => return Ok(()),
+            ".." => {
+                self.dotdot();
+                return Ok(());
+            }
+            _ => {}
+        }
+        let parent = self.cur.clone();
+        let parent_mnt = self.mnt.clone();
+        // 挂载点可能只是内核建的占位目录项, 先看挂载表再去文件系统里查
+        let child = match parent.clone().get_child(name) {
+            Some(child) if lookup_mount(&child).is_some() => child,
+            _ => parent.lookup(name)?,
+        };
+        self.cur = child;
+        self.cross_mounts();
+        if follow && is_symlink(&self.cur) {
+            self.follow_link(parent, parent_mnt)?;
+        }
+        Ok(())
+    }
+
+    /// 展开当前的符号链接, 相对路径的链接从 `dir` 开始解析
+    fn follow_link(&mut self, dir: Arc<dyn Dentry>, dir_mnt: Option<Arc<Mount>>) -> SysResult<()> {
+        self.links += 1;
+        if self.links > MAX_SYMLINKS {
+            return Err(SysError::ELOOP);
+        }
+        let target = self.cur.get_inode()?.read_link()?;
+        if target.is_empty() {
+            return Err(SysError::ENOENT);
+        }
+        if target.starts_with('/') {
+            self.cur = self.root.clone();
+            self.mnt = mount_of(&self.cur);
+        } else {
+            self.cur = dir;
+            self.mnt = dir_mnt;
+        }
+        self.walk(&target, true)
+    }
+
+    /// 解析整条路径. 末尾是否展开符号链接由 `follow_last` 决定, 以 `/` 结尾时总会展开
+    fn walk(&mut self, path: &str, follow_last: bool) -> SysResult<()> {
+        let must_dir = path.ends_with('/');
+        let mut parts = path.split('/').filter(|s| !s.is_empty()).peekable();
+        while let Some(name) = parts.next() {
+            let last = parts.peek().is_none();
+            self.step(name, !last || follow_last || must_dir)?;
+        }
+        if must_dir && !self.cur.get_inode()?.is_dir() {
+            return Err(SysError::ENOTDIR);
+        }
+        Ok(())
+    }
+}
+
+/// 从 `start` 解析 `path`(绝对路径从 `root` 开始), 返回最终的目录项.
+/// `follow` 为假时末尾的符号链接本身被返回
+pub fn namei(
+    root: &Arc<dyn Dentry>,
+    start: &Arc<dyn Dentry>,
+    path: &str,
+    follow: bool,
+    cred: &FsCred,
+) -> SysResult<Arc<dyn Dentry>> {
+    if path.is_empty() {
+        return Err(SysError::ENOENT);
+    }
+    let mut walker = Walker::new(root, start, path, cred)?;
+    walker.walk(path, follow)?;
+    Ok(walker.cur)
+}
+
+/// 解析 `path` 的父目录, 返回父目录和最后一个分量.
+/// 路径只有 `/` 时最后一个分量为 "."
+pub fn namei_parent(
+    root: &Arc<dyn Dentry>,
+    start: &Arc<dyn Dentry>,
+    path: &str,
+    cred: &FsCred,
+) -> SysResult<(Arc<dyn Dentry>, String)> {
+    if path.is_empty() {
+        return Err(SysError::ENOENT);
+    }
+    let mut walker = Walker::new(root, start, path, cred)?;
+    let trimmed = path.trim_end_matches('/');
+    let (dir, name) = match trimmed.rfind('/') {
+        Some(pos) => (&trimmed[..pos + 1], &trimmed[pos + 1..]),
+        None => ("", trimmed),
+    };
+    if name.len() > NAME_MAX {
+        return Err(SysError::ENAMETOOLONG);
+    }
+    walker.walk(dir, true)?;
+    if !walker.cur.get_inode()?.is_dir() {
+        return Err(SysError::ENOTDIR);
+    }
+    let name = if name.is_empty() { "." } else { name };
+    Ok((walker.cur, name.to_string()))
+}
+
+/// O_CREAT 打开时解析要新建的位置: 最后一个分量是悬空的符号链接时,
+/// 沿链接找到真正要新建的父目录和文件名. 返回的文件名可能已经存在(并发创建)
+pub fn namei_create(
+    root: &Arc<dyn Dentry>,
+    start: &Arc<dyn Dentry>,
+    path: &str,
+    cred: &FsCred,
+) -> SysResult<(Arc<dyn Dentry>, String)> {
+    let (mut parent, mut name) = namei_parent(root, start, path, cred)?;
+    let mut links = 0;
+    loop {
+        let child = match parent.lookup(&name) {
+            Ok(child) => child,
+            Err(SysError::ENOENT) => return Ok((parent, name)),
+            Err(e) => return Err(e),
+        };
+        if !is_symlink(&child) {
+            return Ok((parent, name));
+        }
+        links += 1;
+        if links > MAX_SYMLINKS {
+            return Err(SysError::ELOOP);
+        }
+        let target = child.get_inode()?.read_link()?;
+        if target.is_empty() {
+            return Err(SysError::ENOENT);
+        }
+        // 相对路径的链接从链接所在目录开始解析
+        (parent, name) = namei_parent(root, &parent, &target, cred)?;
+    }
+}
+
+/// 内核自己使用的路径解析, 从全局根目录开始
+pub fn lookup_path(path: &str) -> SysResult<Arc<dyn Dentry>> {
+    let root = get_root_dentry();
+    namei(&root, &root, path, true, &FsCred::root())
+}
@@ -0,0 +1,514 @@
+//! 9P2000.L 客户端: 消息的编码解码和 fid 管理.
+//!
+//! 请求一个接一个地发给通道, 所有请求都用同一个 tag. 每个 [`Fid`] 对应服务器上的一个 fid,
+//! 丢弃时发 Tclunk 释放它.
+use alloc::{string::String, sync::Arc, vec, vec::Vec};
+use device::NinePDevice;
+use sync::Mutex;
+use system_result::{SysError, SysResult};
+use vfs_defs::StatFs;
+
+const RLERROR: u8 = 7;
+const TSTATFS: u8 = 8;
+const TLOPEN: u8 = 12;
+const TLCREATE: u8 = 14;
+const TSYMLINK: u8 = 16;
+const TMKNOD: u8 = 18;
+const TREADLINK: u8 = 22;
+const TGETATTR: u8 = 24;
+const TSETATTR: u8 = 26;
+const TREADDIR: u8 = 40;
+const TLINK: u8 = 70;
+const TMKDIR: u8 = 72;
+const TRENAMEAT: u8 = 74;
+const TUNLINKAT: u8 = 76;
+const TVERSION: u8 = 100;
+const TATTACH: u8 = 104;
+const TWALK: u8 = 110;
+const TREAD: u8 = 116;
+const TWRITE: u8 = 118;
+const TCLUNK: u8 = 120;
+
+const NOTAG: u16 = !0;
+const NOFID: u32 = !0;
+/// 协议版本
+pub const VERSION: &str = "9P2000.L";
+/// Tread/Twrite 消息中数据前面的部分, 每次读写的数据不能超过 msize 减去它
+const IO_HEADER_LEN: usize = 24;
+/// Rreaddir 中每项除名字外的长度
+const DIRENT_HEADER_LEN: usize = 24;
+/// Tgetattr 的 request_mask: 所有基本属性
+const GETATTR_BASIC: u64 = 0x7ff;
+/// Tunlinkat 的 flags: 删除的是目录
+pub const AT_REMOVEDIR: u32 = 0x200;
+/// Tlopen 的 flags, 取值与 Linux 一致
+pub const O_RDONLY: u32 = 0;
+pub const O_WRONLY: u32 = 1;
+pub const O_RDWR: u32 = 2;
+pub const O_DIRECTORY: u32 = 0o200000;
+
+/// Tsetattr 的 valid: 要修改哪些属性
+pub const SETATTR_MODE: u32 = 0x1;
+pub const SETATTR_UID: u32 = 0x2;
+pub const SETATTR_GID: u32 = 0x4;
+pub const SETATTR_SIZE: u32 = 0x8;
+
+/// Rgetattr 返回的属性
+pub struct Attr {
+    /// qid.path, 服务器上文件的唯一标识
+    pub ino: u64,
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub nlink: u64,
+    pub rdev: u64,
+    pub size: u64,
+    pub blksize: u64,
+    pub blocks: u64,
+    /// (秒, 纳秒)
+    pub atime: (u64, u64),
+    pub mtime: (u64, u64),
+    pub ctime: (u64, u64),
+}
+
+/// Tsetattr 的参数, 只有 `valid` 中对应的位有效
+#[derive(Default)]
+pub struct SetAttr {
+    pub valid: u32,
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+}
+
+/// Rreaddir 中的一项
+pub struct DirEntry {
+    /// 读下一项时的偏移
+    pub offset: u64,
+    pub name: String,
+}
+
+/// 正在编码的请求
+struct Msg {
+    buf: Vec<u8>,
+}
+
+impl Msg {
+    fn new(ty: u8) -> Self {
+        let mut msg = Self { buf: Vec::new() };
+        msg.u32(0).u8(ty).u16(0);
+        msg
+    }
+    fn u8(&mut self, v: u8) -> &mut Self {
+        self.buf.push(v);
+        self
+    }
+    fn u16(&mut self, v: u16) -> &mut Self {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+    fn u32(&mut self, v: u32) -> &mut Self {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+    fn u64(&mut self, v: u64) -> &mut Self {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+    fn str(&mut self, s: &str) -> &mut Self {
+        self.u16(s.len() as u16);
+        self.buf.extend_from_slice(s.as_bytes());
+        self
+    }
+    /// 填上消息长度和 tag
+    fn finish(&mut self, tag: u16) -> &[u8] {
+        let len = self.buf.len() as u32;
+        self.buf[0..4].copy_from_slice(&len.to_le_bytes());
+        self.buf[5..7].copy_from_slice(&tag.to_le_bytes());
+        &self.buf
+    }
+}
+
+/// 正在解码的应答, 越界时返回 EIO
+struct Reply<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reply<'a> {
+    fn bytes(&mut self, len: usize) -> SysResult<&'a [u8]> {
+        let bytes = self.buf.get(self.pos..self.pos + len).ok_or(SysError::EIO)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+    fn u8(&mut self) -> SysResult<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+    fn u16(&mut self) -> SysResult<u16> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+    fn u32(&mut self) -> SysResult<u32> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+    fn u64(&mut self) -> SysResult<u64> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+    fn str(&mut self) -> SysResult<String> {
+        let len = self.u16()? as usize;
+        Ok(String::from_utf8_lossy(self.bytes(len)?).into_owned())
+    }
+    /// qid 由类型、版本和路径组成, 只有路径用得到
+    fn qid(&mut self) -> SysResult<u64> {
+        self.bytes(5)?;
+        self.u64()
+    }
+    fn time(&mut self) -> SysResult<(u64, u64)> {
+        Ok((self.u64()?, self.u64()?))
+    }
+}
+
+/// Rlerror 中的错误码. 这里用不到的错误都当作 EIO
+fn lerror(ecode: u32) -> SysError {
+    use SysError::*;
+    [
+        EPERM, ENOENT, EIO, ENXIO, EBADF, EAGAIN, ENOMEM, EACCES, EBUSY, EEXIST, EXDEV, ENODEV, ENOTDIR,
+        EISDIR, EINVAL, ENFILE, EMFILE, ETXTBSY, EFBIG, ENOSPC, ESPIPE, EROFS, EMLINK, ERANGE,
+        ENAMETOOLONG, ENOSYS, ENOTEMPTY, ELOOP, EOVERFLOW, EOPNOTSUPP,
+    ]
+    .into_iter()
+    .find(|e| *e as u32 == ecode)
+    .unwrap_or(EIO)
+}
+
+/// 与一个 9P 服务器的连接
+pub struct Client {
+    dev: Arc<dyn NinePDevice>,
+    /// 协商后的消息最大长度
+    msize: usize,
+    /// 应答缓冲区, 持有它的请求独占通道
+    reply: Mutex<Vec<u8>>,
+    /// 空闲的 fid 和下一个没用过的 fid
+    fids: Mutex<(Vec<u32>, u32)>,
+}
+
+impl Client {
+    /// Tversion 协商消息长度和协议版本, `msize` 为 0 时用通道允许的最大值
+    pub fn connect(dev: Arc<dyn NinePDevice>, msize: usize) -> SysResult<Arc<Self>> {
+        let msize = match msize {
+            0 => dev.max_message(),
+            msize => msize.min(dev.max_message()),
+        };
+        let mut client = Self {
+            dev,
+            msize,
+            reply: Mutex::new(vec![0; msize]),
+            fids: Mutex::new((Vec::new(), 0)),
+        };
+        let mut msg = Msg::new(TVERSION);
+        msg.u32(msize as u32).str(VERSION);
+        let (msize, version) = client.rpc_tag(&mut msg, NOTAG, |r| Ok((r.u32()? as usize, r.str()?)))?;
+        if version != VERSION || msize <= IO_HEADER_LEN || msize > client.msize {
+            return Err(SysError::EINVAL);
+        }
+        client.msize = msize;
+        Ok(Arc::new(client))
+    }
+
+    /// Tattach 取得导出目录 `aname` 的 fid
+    pub fn attach(self: &Arc<Self>, aname: &str) -> SysResult<Fid> {
+        let fid = self.alloc_fid();
+        let mut msg = Msg::new(TATTACH);
+        msg.u32(fid.id).u32(NOFID).str("root").str(aname).u32(0);
+        self.rpc(&mut msg, |r| r.qid())?;
+        Ok(fid.into_fid())
+    }
+
+    /// 每次读写的最大数据量
+    fn iounit(&self) -> usize {
+        self.msize - IO_HEADER_LEN
+    }
+
+    fn rpc<T>(&self, msg: &mut Msg, parse: impl FnOnce(&mut Reply) -> SysResult<T>) -> SysResult<T> {
+        self.rpc_tag(msg, 0, parse)
+    }
+
+    /// 发出请求并解析应答的消息体, 服务器出错时返回 Rlerror 中的错误
+    fn rpc_tag<T>(&self, msg: &mut Msg, tag: u16, parse: impl FnOnce(&mut Reply) -> SysResult<T>) -> SysResult<T> {
+        let ty = msg.buf[4];
+        let mut buf = self.reply.lock();
+        let len = self.dev.request(msg.finish(tag), &mut buf).ok_or(SysError::EIO)?;
+        let mut reply = Reply { buf: &buf[..len], pos: 0 };
+        let size = reply.u32()? as usize;
+        let rty = reply.u8()?;
+        reply.u16()?;
+        if size != len {
+            return Err(SysError::EIO);
+        }
+        match rty {
+            RLERROR => Err(lerror(reply.u32()?)),
+            _ if rty == ty + 1 => parse(&mut reply),
+            _ => Err(SysError::EIO),
+        }
+    }
+
+    /// 取一个空闲的 fid 号, 请求失败时丢弃它会把号还回去
+    fn alloc_fid(self: &Arc<Self>) -> NewFid {
+        let mut fids = self.fids.lock();
+        let id = fids.0.pop().unwrap_or_else(|| {
+            fids.1 += 1;
+            fids.1 - 1
+        });
+        NewFid {
+            client: self.clone(),
+            id,
+        }
+    }
+
+    fn free_fid(&self, id: u32) {
+        self.fids.lock().0.push(id);
+    }
+}
+
+/// 已经分配了号但服务器上还没有建立的 fid
+struct NewFid {
+    client: Arc<Client>,
+    id: u32,
+}
+
+impl NewFid {
+    /// 服务器上已经建立, 之后由 [`Fid`] 负责 clunk
+    fn into_fid(self) -> Fid {
+        let fid = Fid {
+            client: self.client.clone(),
+            id: self.id,
+        };
+        core::mem::forget(self);
+        fid
+    }
+}
+
+impl Drop for NewFid {
+    fn drop(&mut self) {
+        self.client.free_fid(self.id);
+    }
+}
+
+/// 服务器上的一个 fid
+pub struct Fid {
+    client: Arc<Client>,
+    id: u32,
+}
+
+impl Fid {
+    fn rpc<T>(&self, msg: &mut Msg, parse: impl FnOnce(&mut Reply) -> SysResult<T>) -> SysResult<T> {
+        self.client.rpc(msg, parse)
+    }
+
+    /// Twalk: 从这里沿 `names` 走下去, 得到一个新的 fid. `names` 为空时复制这个 fid
+    pub fn walk(&self, names: &[&str]) -> SysResult<Fid> {
+        let new = self.client.alloc_fid();
+        let mut msg = Msg::new(TWALK);
+        msg.u32(self.id).u32(new.id).u16(names.len() as u16);
+        for name in names {
+            msg.str(name);
+        }
+        // 没有走完时服务器不建立新的 fid
+        let walked = self.rpc(&mut msg, |r| r.u16())?;
+        if walked as usize != names.len() {
+            return Err(SysError::ENOENT);
+        }
+        Ok(new.into_fid())
+    }
+
+    /// Tlopen: 打开这个 fid 以便读写, 之后不能再 walk
+    pub fn lopen(&self, flags: u32) -> SysResult<()> {
+        let mut msg = Msg::new(TLOPEN);
+        msg.u32(self.id).u32(flags);
+        self.rpc(&mut msg, |r| r.qid()).map(|_| ())
+    }
+
+    /// Tlcreate: 在这个目录里新建普通文件. 成功后这个 fid 变成打开的新文件
+    pub fn lcreate(&self, name: &str, flags: u32, mode: u32, gid: u32) -> SysResult<()> {
+        let mut msg = Msg::new(TLCREATE);
+        msg.u32(self.id).str(name).u32(flags).u32(mode).u32(gid);
+        self.rpc(&mut msg, |r| r.qid()).map(|_| ())
+    }
+
+    /// Tmkdir: 在这个目录里新建目录
+    pub fn mkdir(&self, name: &str, mode: u32, gid: u32) -> SysResult<()> {
+        let mut msg = Msg::new(TMKDIR);
+        msg.u32(self.id).str(name).u32(mode).u32(gid);
+        self.rpc(&mut msg, |r| r.qid()).map(|_| ())
+    }
+
+    /// Tsymlink: 在这个目录里新建指向 `target` 的符号链接
+    pub fn symlink(&self, name: &str, target: &str, gid: u32) -> SysResult<()> {
+        let mut msg = Msg::new(TSYMLINK);
+        msg.u32(self.id).str(name).str(target).u32(gid);
+        self.rpc(&mut msg, |r| r.qid()).map(|_| ())
+    }
+
+    /// Tmknod: 在这个目录里新建设备文件、管道或套接字
+    pub fn mknod(&self, name: &str, mode: u32, major: u32, minor: u32, gid: u32) -> SysResult<()> {
+        let mut msg = Msg::new(TMKNOD);
+        msg.u32(self.id).str(name).u32(mode).u32(major).u32(minor).u32(gid);
+        self.rpc(&mut msg, |r| r.qid()).map(|_| ())
+    }
+
+    /// Treadlink
+    pub fn readlink(&self) -> SysResult<String> {
+        let mut msg = Msg::new(TREADLINK);
+        msg.u32(self.id);
+        self.rpc(&mut msg, |r| r.str())
+    }
+
+    /// Tstatfs: 这个 fid 所在的宿主机文件系统的容量, f_type 也是宿主机的
+    pub fn statfs(&self) -> SysResult<StatFs> {
+        let mut msg = Msg::new(TSTATFS);
+        msg.u32(self.id);
+        self.rpc(&mut msg, |r| {
+            let f_type = r.u32()? as i64;
+            let f_bsize = r.u32()? as i64;
+            Ok(StatFs {
+                f_type,
+                f_bsize,
+                f_blocks: r.u64()?,
+                f_bfree: r.u64()?,
+                f_bavail: r.u64()?,
+                f_files: r.u64()?,
+                f_ffree: r.u64()?,
+                f_fsid: {
+                    let fsid = r.u64()?;
+                    [fsid as i32, (fsid >> 32) as i32]
+                },
+                f_namelen: r.u32()? as isize,
+                f_frsize: f_bsize as isize,
+                ..Default::default()
+            })
+        })
+    }
+
+    /// Tgetattr
+    pub fn getattr(&self) -> SysResult<Attr> {
+        let mut msg = Msg::new(TGETATTR);
+        msg.u32(self.id).u64(GETATTR_BASIC);
+        self.rpc(&mut msg, |r| {
+            r.u64()?;
+            Ok(Attr {
+                ino: r.qid()?,
+                mode: r.u32()?,
+                uid: r.u32()?,
+                gid: r.u32()?,
+                nlink: r.u64()?,
+                rdev: r.u64()?,
+                size: r.u64()?,
+                blksize: r.u64()?,
+                blocks: r.u64()?,
+                atime: r.time()?,
+                mtime: r.time()?,
+                ctime: r.time()?,
+            })
+        })
+    }
+
+    /// Tsetattr, 时间戳由服务器更新
+    pub fn setattr(&self, attr: &SetAttr) -> SysResult<()> {
+        let mut msg = Msg::new(TSETATTR);
+        msg.u32(self.id).u32(attr.valid).u32(attr.mode).u32(attr.uid).u32(attr.gid).u64(attr.size);
+        msg.u64(0).u64(0).u64(0).u64(0);
+        self.rpc(&mut msg, |_| Ok(()))
+    }
+
+    /// Treaddir: 从 `offset` 起读一批目录项, 读完时返回空
+    pub fn readdir(&self, offset: u64) -> SysResult<Vec<DirEntry>> {
+        let mut msg = Msg::new(TREADDIR);
+        msg.u32(self.id).u64(offset).u32(self.client.iounit() as u32);
+        self.rpc(&mut msg, |r| {
+            let end = r.u32()? as usize + r.pos;
+            let mut entries = Vec::new();
+            while r.pos + DIRENT_HEADER_LEN <= end {
+                r.qid()?;
+                let offset = r.u64()?;
+                r.u8()?;
+                let name = r.str()?;
+                entries.push(DirEntry { offset, name });
+            }
+            Ok(entries)
+        })
+    }
+
+    /// Tread: 从 `offset` 起读满 `buf` 或读到文件末尾, 读到一部分后出错时返回已读的长度
+    pub fn read(&self, offset: usize, buf: &mut [u8]) -> SysResult<usize> {
+        let mut done = 0;
+        for chunk in buf.chunks_mut(self.client.iounit()) {
+            let mut msg = Msg::new(TREAD);
+            msg.u32(self.id).u64((offset + done) as u64).u32(chunk.len() as u32);
+            let n = match self.rpc(&mut msg, |r| {
+                let count = r.u32()? as usize;
+                let data = r.bytes(count.min(chunk.len()))?;
+                chunk[..data.len()].copy_from_slice(data);
+                Ok(data.len())
+            }) {
+                Ok(n) => n,
+                Err(_) if done > 0 => break,
+                Err(e) => return Err(e),
+            };
+            done += n;
+            if n < chunk.len() {
+                break;
+            }
+        }
+        Ok(done)
+    }
+
+    /// Twrite: 从 `offset` 起写入 `buf`, 服务器少写或写到一部分后出错时返回已写入的长度
+    pub fn write(&self, offset: usize, buf: &[u8]) -> SysResult<usize> {
+        let mut done = 0;
+        for chunk in buf.chunks(self.client.iounit()) {
+            let mut msg = Msg::new(TWRITE);
+            msg.u32(self.id).u64((offset + done) as u64).u32(chunk.len() as u32);
+            msg.buf.extend_from_slice(chunk);
+            let n = match self.rpc(&mut msg, |r| r.u32()) {
+                Ok(n) => n as usize,
+                Err(_) if done > 0 => break,
+                Err(e) => return Err(e),
+            };
+            done += n;
+            if n < chunk.len() {
+                break;
+            }
+        }
+        Ok(done)
+    }
+
+    /// Tlink: 在目录 `self` 里建立指向 `target` 的硬链接 `name`
+    pub fn link(&self, target: &Fid, name: &str) -> SysResult<()> {
+        let mut msg = Msg::new(TLINK);
+        msg.u32(self.id).u32(target.id).str(name);
+        self.rpc(&mut msg, |_| Ok(()))
+    }
+
+    /// Trenameat: 把目录 `self` 里的 `old_name` 移到目录 `new_dir` 里的 `new_name`
+    pub fn renameat(&self, old_name: &str, new_dir: &Fid, new_name: &str) -> SysResult<()> {
+        let mut msg = Msg::new(TRENAMEAT);
+        msg.u32(self.id).str(old_name).u32(new_dir.id).str(new_name);
+        self.rpc(&mut msg, |_| Ok(()))
+    }
+
+    /// Tunlinkat: 删除目录 `self` 里的 `name`, 删除目录时 `flags` 为 [`AT_REMOVEDIR`]
+    pub fn unlinkat(&self, name: &str, flags: u32) -> SysResult<()> {
+        let mut msg = Msg::new(TUNLINKAT);
+        msg.u32(self.id).str(name).u32(flags);
+        self.rpc(&mut msg, |_| Ok(()))
+    }
+}
+
+impl Drop for Fid {
+    fn drop(&mut self) {
+        let mut msg = Msg::new(TCLUNK);
+        msg.u32(self.id);
+        // 即使 clunk 失败, 服务器也已经释放了这个 fid
+        let _ = self.rpc(&mut msg, |_| Ok(()));
+        self.client.free_fid(self.id);
+    }
+}
@@ -0,0 +1,195 @@
+use alloc::{string::String, sync::Arc};
+use system_result::{SysError, SysResult};
+use vfs_defs::{
+    alloc_dentry, major, minor, Dentry, DentryInner, DentryState, DiskInodeType, File, FileInner, Inode, InodeMode,
+    OpenFlags, RenameFlags,
+};
+
+use super::client::{AT_REMOVEDIR, O_DIRECTORY, O_RDONLY, O_RDWR, O_WRONLY};
+use super::{NinePFile, NinePInode};
+
+pub struct NinePDentry {
+    inner: DentryInner,
+}
+
+impl NinePDentry {
+    pub fn new(inner: DentryInner) -> Self {
+        Self { inner }
+    }
+}
+
+fn ninep_inode(dentry: &dyn Dentry) -> SysResult<Arc<NinePInode>> {
+    dentry
+        .get_inode()?
+        .downcast_arc::<NinePInode>()
+        .map_err(|_| SysError::ENOENT)
+}
+
+/// 从目录 `dir` walk 到 `child`, 给它设置 inode. 目录项的状态由调用者设置
+fn fill(dir: &NinePInode, child: &Arc<dyn Dentry>) -> SysResult<()> {
+    let fid = dir.fid().walk(&[child.get_name_str()])?;
+    let attr = fid.getattr()?;
+    child.set_inode(NinePInode::new(child.get_superblock(), fid, &attr));
+    Ok(())
+}
+
+/// 新建的文件先由 VFS 放进父目录, 这里取出来设置 inode
+fn fill_new(parent: Arc<NinePDentry>, dir: &NinePInode, name: &str) -> SysResult<Arc<dyn Dentry>> {
+    let child = parent.get_child(name).ok_or(SysError::ENOENT)?;
+    fill(dir, &child)?;
+    *child.get_state() = DentryState::Valid;
+    Ok(child)
+}
+
+impl Dentry for NinePDentry {
+    fn get_inner(&self) -> &DentryInner {
+        &self.inner
+    }
+    fn self_arc(self: Arc<Self>) -> Arc<dyn Dentry> {
+        self.clone()
+    }
+    /// 权限和属主由调用者随后设置
+    fn concrete_create(self: Arc<Self>, name: &str, _type: DiskInodeType) -> SysResult<Arc<dyn Dentry>> {
+        let dir = ninep_inode(self.as_ref())?;
+        match _type {
+            DiskInodeType::File => {
+                // Tlcreate 把 fid 变成新文件的, 用一个复制出来的
+                let fid = dir.fid().walk(&[])?;
+                fid.lcreate(name, O_RDWR, 0o644, 0)?;
+            }
+            DiskInodeType::Directory => dir.fid().mkdir(name, 0o755, 0)?,
+            DiskInodeType::None => return Err(SysError::EINVAL),
+        }
+        fill_new(self, &dir, name)
+    }
+    fn concrete_symlink(self: Arc<Self>, name: &str, target: &str) -> SysResult<Arc<dyn Dentry>> {
+        let dir = ninep_inode(self.as_ref())?;
+        dir.fid().symlink(name, target, 0)?;
+        fill_new(self, &dir, name)
+    }
+    fn concrete_mknod(self: Arc<Self>, name: &str, mode: InodeMode, rdev: u64) -> SysResult<Arc<dyn Dentry>> {
+        let dir = ninep_inode(self.as_ref())?;
+        dir.fid().mknod(name, mode.bits(), major(rdev), minor(rdev), 0)?;
+        fill_new(self, &dir, name)
+    }
+    /// lookup 持有子目录项的状态锁, 状态由它设置
+    fn concrete_lookup(self: Arc<Self>, name: &str) -> SysResult<Arc<dyn Dentry>> {
+        let dir = ninep_inode(self.as_ref())?;
+        let child = self.get_child(name).ok_or(SysError::ENOENT)?;
+        fill(&dir, &child)?;
+        Ok(child)
+    }
+    fn concrete_new_child(self: Arc<Self>, name: &str) -> Arc<dyn Dentry> {
+        let parent: Arc<dyn Dentry> = self.clone();
+        let child = Arc::new(NinePDentry::new(DentryInner::new(String::from(name), self.get_superblock(), Some(parent.clone()))));
+        alloc_dentry(Some(&parent), name, child.clone());
+        child
+    }
+    /// `self` 是已有的文件. mkdir 时 VFS 链接的 `.` 和 `..` 由服务器自己维护
+    fn concrete_link(self: Arc<Self>, new: &Arc<dyn Dentry>) -> SysResult<()> {
+        let name = new.get_name_str();
+        if name == "." || name == ".." {
+            return Ok(());
+        }
+        let inode = ninep_inode(self.as_ref())?;
+        let dir = ninep_inode(new.get_father().ok_or(SysError::ENOENT)?.as_ref())?;
+        dir.fid().link(inode.fid(), name)?;
+        fill(&dir, new)?;
+        *new.get_state() = DentryState::Valid;
+        Ok(())
+    }
+    fn concrete_unlink(self: Arc<Self>, old: &Arc<dyn Dentry>) -> SysResult<()> {
+        let dir = ninep_inode(self.as_ref())?;
+        let flags = if old.get_inode()?.is_dir() { AT_REMOVEDIR } else { 0 };
+        dir.fid().unlinkat(old.get_name_str(), flags)?;
+        self.get_inner().children.lock().remove(old.get_name_str());
+        Ok(())
+    }
+    /// `self` 是要改名的目录项. 服务器会让已有的 fid 跟着文件走, inode 直接挂到新目录项上
+    fn concrete_rename(self: Arc<Self>, new: Arc<dyn Dentry>, flags: RenameFlags) -> SysResult<()> {
+        if flags.intersects(RenameFlags::RENAME_EXCHANGE | RenameFlags::RENAME_WHITEOUT) {
+            return Err(SysError::EINVAL);
+        }
+        let old_dir = ninep_inode(self.get_father().ok_or(SysError::EBUSY)?.as_ref())?;
+        let new_dir = ninep_inode(new.get_father().ok_or(SysError::EBUSY)?.as_ref())?;
+        let inode = ninep_inode(self.as_ref())?;
+        old_dir.fid().renameat(self.get_name_str(), new_dir.fid(), new.get_name_str())?;
+        new.set_inode(inode);
+        *new.get_state() = DentryState::Valid;
+        *self.inner.inode.lock() = None;
+        Ok(())
+    }
+    fn concrete_getchild(self: Arc<Self>, name: &str) -> Option<Arc<dyn Dentry>> {
+        let dir = ninep_inode(self.as_ref()).ok()?;
+        let fid = dir.fid().walk(&[name]).ok()?;
+        let attr = fid.getattr().ok()?;
+        let child = self.concrete_new_child(name);
+        child.set_inode(NinePInode::new(child.get_superblock(), fid, &attr));
+        *child.get_state() = DentryState::Valid;
+        Some(child)
+    }
+    /// 只有普通文件在服务器上打开, 其他文件不经过这里读写
+    fn open(self: Arc<Self>, flags: OpenFlags) -> Arc<dyn File> {
+        let inode = ninep_inode(self.as_ref());
+        let fid = inode.and_then(|inode| {
+            if inode.get_meta().mode & InodeMode::TYPE_MASK != InodeMode::FILE {
+                return Err(SysError::EISDIR);
+            }
+            let mode = match flags.read_write() {
+                (true, true) => O_RDWR,
+                (false, true) => O_WRONLY,
+                _ => O_RDONLY,
+            };
+            let fid = inode.fid().walk(&[])?;
+            fid.lopen(mode)?;
+            Ok(fid)
+        });
+        let file = Arc::new(NinePFile::new(FileInner::new(self), fid));
+        if flags.contains(OpenFlags::APPEND) {
+            let len = file.get_dentry().get_inode().map_or(0, |inode| inode.get_size());
+            *file.get_offset() = len as usize;
+        }
+        *file.get_inner().flags.lock() = flags;
+        file
+    }
+    /// 一次读完目录, 给还不在内存里的文件建目录项
+    fn load_dir(self: Arc<Self>) -> SysResult<()> {
+        let dir = ninep_inode(self.as_ref())?;
+        let fid = dir.fid().walk(&[])?;
+        fid.lopen(O_RDONLY | O_DIRECTORY)?;
+        let mut offset = 0;
+        loop {
+            let entries = fid.readdir(offset)?;
+            let Some(last) = entries.last() else {
+                break;
+            };
+            offset = last.offset;
+            for entry in entries.iter().filter(|e| e.name != "." && e.name != "..") {
+                let existing = self.get_inner().children.lock().get(&entry.name).and_then(|child| child.upgrade());
+                let child = match existing {
+                    Some(child) if !child.has_no_inode() => continue,
+                    Some(child) => child,
+                    None => {
+                        let child = self.clone().concrete_new_child(&entry.name);
+                        self.add_child(child.clone());
+                        child
+                    }
+                };
+                // 读目录和 walk 之间文件可能已经在宿主机上被删掉了
+                match fill(&dir, &child) {
+                    Ok(()) => *child.get_state() = DentryState::Valid,
+                    Err(_) => {
+                        self.get_inner().children.lock().remove(&entry.name);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for NinePDentry {
+    fn drop(&mut self) {
+        self.on_drop();
+    }
+}
@@ -0,0 +1,65 @@
+use system_result::SysResult;
+use vfs_defs::{File, FileInner, OpenFlags, PollEvents};
+
+use super::client::Fid;
+
+/// 9P 上打开的文件. 读写用打开时另外 walk 出来并 Tlopen 过的 fid
+pub struct NinePFile {
+    inner: FileInner,
+    /// 打开失败或不是普通文件时为错误, 读写时返回它
+    fid: SysResult<Fid>,
+}
+
+impl NinePFile {
+    pub fn new(inner: FileInner, fid: SysResult<Fid>) -> Self {
+        Self { inner, fid }
+    }
+    fn fid(&self) -> SysResult<&Fid> {
+        self.fid.as_ref().map_err(|e| *e)
+    }
+}
+
+impl File for NinePFile {
+    fn get_inner(&self) -> &FileInner {
+        &self.inner
+    }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        self.pread(offset, buf).unwrap_or(0)
+    }
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        self.pwrite(offset, buf).unwrap_or(0)
+    }
+    fn pread(&self, offset: usize, buf: &mut [u8]) -> SysResult<usize> {
+        self.fid()?.read(offset, buf)
+    }
+    fn pwrite(&self, offset: usize, buf: &[u8]) -> SysResult<usize> {
+        self.fid()?.write(offset, buf)
+    }
+    fn read_checked(&self, buf: &mut [u8]) -> SysResult<usize> {
+        let mut offset = self.get_offset();
+        let read = self.pread(*offset, buf)?;
+        *offset += read;
+        Ok(read)
+    }
+    /// 宿主机上的盘满了时可能只写进去一部分, 不能走默认的 `write`
+    fn write_checked(&self, buf: &[u8]) -> SysResult<usize> {
+        let mut offset = self.get_offset();
+        if self.get_inner().flags.lock().contains(OpenFlags::APPEND) {
+            let (written, end) = self.append(buf)?;
+            *offset = end;
+            return Ok(written);
+        }
+        let written = self.pwrite(*offset, buf)?;
+        *offset += written;
+        Ok(written)
+    }
+    fn readable(&self) -> bool {
+        self.get_inner().flags.lock().read_write().0
+    }
+    fn writable(&self) -> bool {
+        self.get_inner().flags.lock().read_write().1
+    }
+    fn poll(&self, _events: PollEvents) -> PollEvents {
+        PollEvents::POLLIN | PollEvents::POLLOUT
+    }
+}
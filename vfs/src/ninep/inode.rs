@@ -0,0 +1,121 @@
+use alloc::{string::String, sync::Arc};
+use system_result::SysResult;
+use time::TimeSpec;
+use vfs_defs::{Inode, InodeMeta, InodeMode, Kstat, SuperBlock};
+
+use super::client::{Attr, Fid, SetAttr, SETATTR_GID, SETATTR_MODE, SETATTR_SIZE, SETATTR_UID};
+
+/// 9P 上的 inode. 属性以服务器为准, 每次取属性都重新问服务器, 宿主机上的修改马上可见
+pub struct NinePInode {
+    meta: InodeMeta,
+    /// 指向这个文件的 fid, 只用来 walk 和取属性, 读写时另外打开
+    fid: Fid,
+}
+
+impl NinePInode {
+    pub fn new(superblock: Arc<dyn SuperBlock>, fid: Fid, attr: &Attr) -> Arc<Self> {
+        let mode = InodeMode::from_bits_truncate(attr.mode);
+        let inode = Arc::new(Self {
+            meta: InodeMeta::new(mode, attr.ino as usize, superblock),
+            fid,
+        });
+        inode.set_type(mode.to_type());
+        inode.update(attr);
+        inode
+    }
+    pub fn fid(&self) -> &Fid {
+        &self.fid
+    }
+    /// 用服务器返回的属性更新内存里的副本, 权限检查用的是这份副本
+    fn update(&self, attr: &Attr) {
+        let time = |(sec, nsec): (u64, u64)| TimeSpec {
+            sec: sec as usize,
+            usec: nsec as usize,
+        };
+        let mut inner = self.meta.inner.lock();
+        inner.size = attr.size as u32;
+        inner.link = attr.nlink as u32;
+        inner.atime = time(attr.atime);
+        inner.mtime = time(attr.mtime);
+        inner.ctime = time(attr.ctime);
+        inner.mode = InodeMode::from_bits_truncate(attr.mode);
+        inner.uid = attr.uid;
+        inner.gid = attr.gid;
+        inner.rdev = attr.rdev;
+    }
+    /// 重新取属性
+    fn refresh(&self) -> SysResult<Attr> {
+        let attr = self.fid.getattr()?;
+        self.update(&attr);
+        Ok(attr)
+    }
+    fn setattr(&self, attr: SetAttr) -> SysResult<()> {
+        self.fid.setattr(&attr)?;
+        self.refresh().map(|_| ())
+    }
+}
+
+impl Inode for NinePInode {
+    fn load_from_disk(&self) {}
+    fn get_meta(&self) -> &InodeMeta {
+        &self.meta
+    }
+    fn get_attr(&self) -> SysResult<Kstat> {
+        let attr = self.refresh()?;
+        Ok(Kstat {
+            st_dev: 0,
+            st_ino: attr.ino,
+            st_mode: attr.mode,
+            st_nlink: attr.nlink as u32,
+            st_uid: attr.uid,
+            st_gid: attr.gid,
+            st_rdev: attr.rdev,
+            __pad: 0,
+            st_size: attr.size,
+            st_blksize: attr.blksize as u32,
+            __pad2: 0,
+            st_blocks: attr.blocks,
+            st_atime_sec: attr.atime.0,
+            st_atime_nsec: attr.atime.1,
+            st_mtime_sec: attr.mtime.0,
+            st_mtime_nsec: attr.mtime.1,
+            st_ctime_sec: attr.ctime.0,
+            st_ctime_nsec: attr.ctime.1,
+            unused: 0,
+        })
+    }
+    fn get_size(&self) -> u32 {
+        match self.refresh() {
+            Ok(attr) => attr.size as u32,
+            Err(_) => self.meta.inner.lock().size,
+        }
+    }
+    fn clear(&self) {}
+    /// 服务器按 chmod(2) 处理, 文件类型位不起作用
+    fn set_perm(&self, perm: InodeMode) -> SysResult<()> {
+        let mode = (self.meta.mode & InodeMode::TYPE_MASK) | perm.difference(InodeMode::TYPE_MASK);
+        self.setattr(SetAttr {
+            valid: SETATTR_MODE,
+            mode: mode.bits(),
+            ..Default::default()
+        })
+    }
+    fn set_owner(&self, uid: u32, gid: u32) -> SysResult<()> {
+        self.setattr(SetAttr {
+            valid: SETATTR_UID | SETATTR_GID,
+            uid,
+            gid,
+            ..Default::default()
+        })
+    }
+    fn truncate(&self, size: usize) -> SysResult<()> {
+        self.setattr(SetAttr {
+            valid: SETATTR_SIZE,
+            size: size as u64,
+            ..Default::default()
+        })
+    }
+    fn read_link(&self) -> SysResult<String> {
+        self.fid.readlink()
+    }
+}
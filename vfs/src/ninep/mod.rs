@@ -0,0 +1,125 @@
+//! 9P2000.L 文件系统
+//!
+//! 通过 virtio-9p 之类的通道访问宿主机上的目录, 用法与 Linux 相同:
+//! `mount -t 9p -o trans=virtio,version=9p2000.L <挂载标签> <目录>`, 挂载源是通道的挂载标签.
+//! 不做缓存, 每次取属性和读写都发给服务器, 宿主机上的修改马上可见.
+//!
+//! 挂载选项 trans 只支持 virtio, version 只支持 9p2000.L; aname 是要挂载的导出目录,
+//! msize 限制消息长度. cache、access 等选项不影响这里的实现, 直接忽略.
+use alloc::{string::String, sync::Arc};
+use device::{find_9p_device, BlockDevice};
+use system_result::{SysError, SysResult};
+use vfs_defs::{
+    Dentry, DentryInner, DentryState, DiskInodeType, FileSystemType, FileSystemTypeInner, InodeMode, MountFlags,
+    StatFs, SuperBlock, SuperBlockInner,
+};
+
+mod client;
+mod dentry;
+mod file;
+mod inode;
+
+use client::Client;
+pub use dentry::NinePDentry;
+pub use file::NinePFile;
+pub use inode::NinePInode;
+
+pub struct NinePFsType {
+    inner: FileSystemTypeInner,
+}
+
+impl NinePFsType {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            inner: FileSystemTypeInner::new(String::from("9p")),
+        })
+    }
+}
+
+impl FileSystemType for NinePFsType {
+    fn get_inner(&self) -> &FileSystemTypeInner {
+        &self.inner
+    }
+    /// 通道和选项只能从挂载源和挂载选项得到, 见 [`mount_9p`]
+    fn mount(
+        self: Arc<Self>,
+        _name: &str,
+        _parent: Option<Arc<dyn Dentry>>,
+        _flags: MountFlags,
+        _device: Option<Arc<dyn BlockDevice>>,
+    ) -> SysResult<Arc<dyn Dentry>> {
+        Err(SysError::EINVAL)
+    }
+    fn umount(self: Arc<Self>, path: &str, _flags: MountFlags) -> SysResult<()> {
+        self.remove_superblock(path)
+    }
+}
+
+/// 数据都在服务器上, 写操作直接发给服务器, 没有要写回的东西
+pub struct NinePSuperBlock {
+    inner: SuperBlockInner,
+}
+
+impl SuperBlock for NinePSuperBlock {
+    fn get_inner(&self) -> &SuperBlockInner {
+        &self.inner
+    }
+    /// 用根目录的 fid 向服务器查询
+    fn statfs(&self) -> SysResult<StatFs> {
+        let root = self.inner.root.get().ok_or(SysError::EIO)?;
+        let inode = root.get_inode()?.downcast_arc::<NinePInode>().map_err(|_| SysError::EIO)?;
+        inode.fid().statfs()
+    }
+}
+
+/// 挂载选项
+struct NinePOptions {
+    aname: String,
+    /// 为 0 时用通道允许的最大值
+    msize: usize,
+}
+
+fn parse_options(data: &str) -> SysResult<NinePOptions> {
+    let mut opts = NinePOptions {
+        aname: String::new(),
+        msize: 0,
+    };
+    for opt in data.split(',').filter(|s| !s.is_empty()) {
+        let (key, value) = opt.split_once('=').unwrap_or((opt, ""));
+        match key {
+            "trans" if value != "virtio" => return Err(SysError::EINVAL),
+            "version" if !value.eq_ignore_ascii_case(client::VERSION) => return Err(SysError::EINVAL),
+            "aname" => opts.aname = String::from(value),
+            "msize" => opts.msize = value.parse().map_err(|_| SysError::EINVAL)?,
+            _ => {}
+        }
+    }
+    Ok(opts)
+}
+
+/// 连接挂载标签为 `source` 的通道, 返回导出目录的根目录项
+pub(crate) fn mount_9p(
+    fs: Arc<dyn FileSystemType>,
+    name: &str,
+    parent: Option<Arc<dyn Dentry>>,
+    source: &str,
+    data: &str,
+) -> SysResult<Arc<dyn Dentry>> {
+    let opts = parse_options(data)?;
+    let dev = find_9p_device(source).ok_or(SysError::ENOENT)?;
+    let client = Client::connect(dev, opts.msize)?;
+    let fid = client.attach(&opts.aname)?;
+    let attr = fid.getattr()?;
+    if InodeMode::from_bits_truncate(attr.mode).to_type() != DiskInodeType::Directory {
+        return Err(SysError::ENOTDIR);
+    }
+    let superblock = Arc::new(NinePSuperBlock {
+        inner: SuperBlockInner::new(None, fs.clone()),
+    });
+    let root: Arc<dyn Dentry> = Arc::new(NinePDentry::new(DentryInner::new(String::from(name), superblock.clone(), parent)));
+    root.set_inode(NinePInode::new(superblock.clone(), fid, &attr));
+    *root.get_state() = DentryState::Valid;
+    superblock.set_root_dentry(root.clone());
+    fs.add_superblock(&root.path(), superblock);
+    Ok(root)
+}
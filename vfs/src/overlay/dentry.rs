@@ -0,0 +1,224 @@
+use alloc::{string::String, sync::Arc, vec::Vec};
+use system_result::{SysError, SysResult};
+use vfs_defs::{
+    alloc_dentry, Dentry, DentryInner, DentryState, DiskInodeType, File, FileInner, Inode, InodeMode, OpenFlags,
+    RenameFlags, SuperBlock,
+};
+
+use super::{
+    clear_whiteouts, copy_up, lookup_layers, lower_has, make_opaque, make_whiteout, merged_names, ovl_inode,
+    real_lookup, remove_whiteout, OvlFile, OvlInode,
+};
+
+pub struct OvlDentry {
+    inner: DentryInner,
+}
+
+impl OvlDentry {
+    pub fn new(name: &str, superblock: Arc<dyn SuperBlock>, father: Option<Arc<dyn Dentry>>) -> Arc<Self> {
+        Arc::new(Self {
+            inner: DentryInner::new(String::from(name), superblock, father),
+        })
+    }
+    fn this(self: &Arc<Self>) -> Arc<dyn Dentry> {
+        self.clone()
+    }
+    /// 新建文件 `name`: 由 `make` 建在上层的父目录里. 那里的同名白化文件先删掉,
+    /// 新目录盖在被删掉的下层文件上时要标记为不透明
+    fn create_upper(
+        self: &Arc<Self>,
+        name: &str,
+        make: impl FnOnce(&Arc<dyn Dentry>) -> SysResult<Arc<dyn Dentry>>,
+    ) -> SysResult<Arc<dyn Dentry>> {
+        let upper_parent = copy_up(&self.this())?;
+        let child = self.clone().get_child(name).ok_or(SysError::ENOENT)?;
+        let whiteout = remove_whiteout(&upper_parent, name)?;
+        let upper = make(&upper_parent).inspect_err(|_| {
+            if whiteout {
+                let _ = make_whiteout(&upper_parent, name);
+            }
+        })?;
+        if whiteout && upper.is_dir() {
+            make_opaque(&upper)?;
+        }
+        fill(&child, Some(upper), Vec::new())?;
+        Ok(child)
+    }
+}
+
+/// 让 overlay 目录项 `child` 指向各层的目录项
+fn fill(child: &Arc<dyn Dentry>, upper: Option<Arc<dyn Dentry>>, lower: Vec<Arc<dyn Dentry>>) -> SysResult<()> {
+    child.set_inode(OvlInode::new(child.get_superblock(), upper, lower, child)?);
+    *child.get_state() = DentryState::Valid;
+    Ok(())
+}
+
+impl Dentry for OvlDentry {
+    fn get_inner(&self) -> &DentryInner {
+        &self.inner
+    }
+    fn self_arc(self: Arc<Self>) -> Arc<dyn Dentry> {
+        self.clone()
+    }
+    fn concrete_create(self: Arc<Self>, name: &str, _type: DiskInodeType) -> SysResult<Arc<dyn Dentry>> {
+        self.create_upper(name, |dir| dir.create(name, _type))
+    }
+    fn concrete_symlink(self: Arc<Self>, name: &str, target: &str) -> SysResult<Arc<dyn Dentry>> {
+        self.create_upper(name, |dir| dir.symlink(name, target))
+    }
+    fn concrete_mknod(self: Arc<Self>, name: &str, mode: InodeMode, rdev: u64) -> SysResult<Arc<dyn Dentry>> {
+        self.create_upper(name, |dir| dir.mknod(name, mode, rdev))
+    }
+    fn concrete_lookup(self: Arc<Self>, name: &str) -> SysResult<Arc<dyn Dentry>> {
+        let dir = ovl_inode(&self.this())?;
+        let (upper, lower) = lookup_layers(&dir, name).ok_or(SysError::ENOENT)?;
+        let child = self.clone().get_child(name).ok_or(SysError::ENOENT)?;
+        // lookup 持有子目录项的状态锁, 状态由它设置
+        child.set_inode(OvlInode::new(child.get_superblock(), upper, lower, &child)?);
+        Ok(child)
+    }
+    fn concrete_new_child(self: Arc<Self>, name: &str) -> Arc<dyn Dentry> {
+        let parent = self.this();
+        let child: Arc<dyn Dentry> = Self::new(name, self.get_superblock(), Some(parent.clone()));
+        alloc_dentry(Some(&parent), name, child.clone());
+        child
+    }
+    /// 硬链接建在上层, 源文件先复制上去. mkdir 时 VFS 链接的 `.` 和 `..` 由上层自己维护
+    fn concrete_link(self: Arc<Self>, new: &Arc<dyn Dentry>) -> SysResult<()> {
+        let name = new.get_name_str();
+        if name == "." || name == ".." {
+            return Ok(());
+        }
+        let upper = copy_up(&self.this())?;
+        let upper_parent = copy_up(&new.get_father().ok_or(SysError::ENOENT)?)?;
+        let whiteout = remove_whiteout(&upper_parent, name)?;
+        let target = upper_parent.find_or_create(name, DiskInodeType::File);
+        upper.link(&target).inspect_err(|_| {
+            if whiteout {
+                let _ = make_whiteout(&upper_parent, name);
+            }
+        })?;
+        // 有的文件系统链接后不给新目录项设置 inode, 重新查一次
+        let target = real_lookup(&upper_parent, name).ok_or(SysError::EIO)?;
+        fill(new, Some(target), Vec::new())
+    }
+    /// 删除上层的文件, 下层还有同名文件时留下白化文件遮住它
+    fn concrete_unlink(self: Arc<Self>, old: &Arc<dyn Dentry>) -> SysResult<()> {
+        let dir = ovl_inode(&self.this())?;
+        let inode = ovl_inode(old)?;
+        let name = old.get_name_str();
+        if inode.is_dir() && !merged_names(&inode)?.is_empty() {
+            return Err(SysError::ENOTEMPTY);
+        }
+        let upper_parent = copy_up(&self.this())?;
+        if let Some(upper) = inode.upper() {
+            if inode.is_dir() {
+                clear_whiteouts(&upper)?;
+            }
+            upper_parent.unlink(&upper)?;
+        }
+        if lower_has(&dir, name) {
+            make_whiteout(&upper_parent, name)?;
+        }
+        self.get_inner().children.lock().remove(name);
+        Ok(())
+    }
+    /// 在上层改名, 原位置下层还有同名文件时留下白化文件.
+    /// 与 Linux 未开启 redirect_dir 时一样, 与下层合并的目录不能改名, 返回 EXDEV
+    fn concrete_rename(self: Arc<Self>, new: Arc<dyn Dentry>, flags: RenameFlags) -> SysResult<()> {
+        if flags.intersects(RenameFlags::RENAME_EXCHANGE | RenameFlags::RENAME_WHITEOUT) {
+            return Err(SysError::EINVAL);
+        }
+        let inode = ovl_inode(&self.this())?;
+        let is_dir = inode.is_dir();
+        if is_dir && !inode.lower().is_empty() {
+            return Err(SysError::EXDEV);
+        }
+        let old_parent = self.get_father().ok_or(SysError::EBUSY)?;
+        let new_parent = new.get_father().ok_or(SysError::EBUSY)?;
+        let (old_dir, new_dir) = (ovl_inode(&old_parent)?, ovl_inode(&new_parent)?);
+        let (old_name, new_name) = (self.get_name_str(), new.get_name_str());
+        if !new.has_no_inode() {
+            let target = ovl_inode(&new)?;
+            match (is_dir, target.is_dir()) {
+                (false, true) => return Err(SysError::EISDIR),
+                (true, false) => return Err(SysError::ENOTDIR),
+                (true, true) if !merged_names(&target)?.is_empty() => return Err(SysError::ENOTEMPTY),
+                _ => {}
+            }
+            if let (true, Some(target_upper)) = (is_dir, target.upper()) {
+                clear_whiteouts(&target_upper)?;
+            }
+        }
+        let upper = copy_up(&self.this())?;
+        let old_upper_parent = copy_up(&old_parent)?;
+        let new_upper_parent = copy_up(&new_parent)?;
+        let whiteout = remove_whiteout(&new_upper_parent, new_name)?;
+        let upper_new = match real_lookup(&new_upper_parent, new_name) {
+            Some(existing) => existing,
+            None => {
+                let ty = if is_dir { DiskInodeType::Directory } else { DiskInodeType::File };
+                new_upper_parent.find_or_create(new_name, ty)
+            }
+        };
+        upper.vfs_rename(&upper_new, RenameFlags::empty()).inspect_err(|_| {
+            if whiteout {
+                let _ = make_whiteout(&new_upper_parent, new_name);
+            }
+        })?;
+        let upper_new = real_lookup(&new_upper_parent, new_name).ok_or(SysError::EIO)?;
+        if lower_has(&old_dir, old_name) {
+            make_whiteout(&old_upper_parent, old_name)?;
+        }
+        // 目录移到下层有同名文件的位置上, 不能与下层合并
+        if is_dir && (whiteout || lower_has(&new_dir, new_name)) {
+            make_opaque(&upper_new)?;
+        }
+        fill(&new, Some(upper_new), Vec::new())?;
+        *self.inner.inode.lock() = None;
+        old_parent.get_inner().children.lock().remove(old_name);
+        Ok(())
+    }
+    fn concrete_getchild(self: Arc<Self>, name: &str) -> Option<Arc<dyn Dentry>> {
+        let dir = ovl_inode(&self.this()).ok()?;
+        let (upper, lower) = lookup_layers(&dir, name)?;
+        let child = self.concrete_new_child(name);
+        fill(&child, upper, lower).ok()?;
+        Some(child)
+    }
+    fn open(self: Arc<Self>, flags: OpenFlags) -> Arc<dyn File> {
+        let inode = ovl_inode(&self.this()).unwrap();
+        let real = inode.real_dentry().open(flags);
+        let file = Arc::new(OvlFile::new(FileInner::new(self), real));
+        if flags.contains(OpenFlags::APPEND) {
+            *file.get_offset() = inode.get_size() as usize;
+        }
+        *file.get_inner().flags.lock() = flags;
+        file
+    }
+    /// 合并各层的目录内容, 给还不在内存里的文件建目录项
+    fn load_dir(self: Arc<Self>) -> SysResult<()> {
+        let dir = ovl_inode(&self.this())?;
+        for name in merged_names(&dir)? {
+            let existing = self.get_inner().children.lock().get(&name).and_then(|child| child.upgrade());
+            match existing {
+                Some(child) if !child.has_no_inode() => {}
+                Some(child) => {
+                    if let Some((upper, lower)) = lookup_layers(&dir, &name) {
+                        fill(&child, upper, lower)?;
+                    }
+                }
+                None => {
+                    let _ = self.clone().get_child(&name);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for OvlDentry {
+    fn drop(&mut self) {
+        self.on_drop();
+    }
+}
@@ -0,0 +1,78 @@
+use alloc::sync::Arc;
+use sync::Mutex;
+use system_result::SysResult;
+use vfs_defs::{File, FileInner, OpenFlags, PollEvents};
+
+use super::{copy_up, same_dentry};
+
+/// overlay 上打开的文件, 读写转给某一层上打开的文件.
+/// 打开时文件还在下层的, 第一次写的时候复制到上层, 之后改为读写上层的文件
+pub struct OvlFile {
+    inner: FileInner,
+    real: Mutex<Arc<dyn File>>,
+}
+
+impl OvlFile {
+    pub fn new(inner: FileInner, real: Arc<dyn File>) -> Self {
+        Self {
+            inner,
+            real: Mutex::new(real),
+        }
+    }
+    fn real(&self) -> Arc<dyn File> {
+        self.real.lock().clone()
+    }
+    /// 写之前先复制到上层, 返回上层上打开的文件
+    fn writer(&self) -> SysResult<Arc<dyn File>> {
+        let upper = copy_up(&self.get_dentry())?;
+        let mut real = self.real.lock();
+        if !same_dentry(&real.get_dentry(), &upper) {
+            let flags = *self.get_inner().flags.lock() & !(OpenFlags::CREATE | OpenFlags::EXCL | OpenFlags::TRUNC);
+            *real = upper.open(flags);
+        }
+        Ok(real.clone())
+    }
+}
+
+impl File for OvlFile {
+    fn get_inner(&self) -> &FileInner {
+        &self.inner
+    }
+    fn readable(&self) -> bool {
+        let (readable, _writable) = self.get_inner().flags.lock().read_write();
+        readable
+    }
+    fn writable(&self) -> bool {
+        let (_readable, writable) = self.get_inner().flags.lock().read_write();
+        writable
+    }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        self.real().read_at(offset, buf)
+    }
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        self.writer().map_or(0, |real| real.write_at(offset, buf))
+    }
+    fn pread(&self, offset: usize, buf: &mut [u8]) -> SysResult<usize> {
+        self.real().pread(offset, buf)
+    }
+    fn pwrite(&self, offset: usize, buf: &[u8]) -> SysResult<usize> {
+        self.writer()?.pwrite(offset, buf)
+    }
+    fn write_checked(&self, buf: &[u8]) -> SysResult<usize> {
+        let mut offset = self.get_offset();
+        if self.get_inner().flags.lock().contains(OpenFlags::APPEND) {
+            let (written, end) = self.append(buf)?;
+            *offset = end;
+            return Ok(written);
+        }
+        let written = self.pwrite(*offset, buf)?;
+        *offset += written;
+        Ok(written)
+    }
+    fn poll(&self, events: PollEvents) -> PollEvents {
+        self.real().poll(events)
+    }
+    fn ioctl(&self, cmd: usize, arg: usize) -> SysResult<isize> {
+        self.real().ioctl(cmd, arg)
+    }
+}
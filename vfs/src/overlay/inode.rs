@@ -0,0 +1,135 @@
+use alloc::{
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use sync::Mutex;
+use system_result::{SysError, SysResult};
+use time::TimeSpec;
+use vfs_defs::{Dentry, Inode, InodeMeta, InodeMode, Kstat, SuperBlock, XattrFlags};
+
+use super::copy_up;
+
+/// overlay 的 inode. 内容和属性都转给当前可见的那一层, 自己的元数据只是一份副本, 供权限检查使用
+pub struct OvlInode {
+    meta: InodeMeta,
+    /// 上层的目录项, 复制到上层之前为 None
+    upper: Mutex<Option<Arc<dyn Dentry>>>,
+    /// 下层的目录项, 从上往下. 只有目录会有多个
+    lower: Vec<Arc<dyn Dentry>>,
+    /// 使用这个 inode 的 overlay 目录项, copy-up 时从它找父目录
+    alias: Weak<dyn Dentry>,
+}
+
+impl OvlInode {
+    pub fn new(
+        superblock: Arc<dyn SuperBlock>,
+        upper: Option<Arc<dyn Dentry>>,
+        lower: Vec<Arc<dyn Dentry>>,
+        alias: &Arc<dyn Dentry>,
+    ) -> SysResult<Arc<Self>> {
+        let real = upper.as_ref().or(lower.first()).ok_or(SysError::ENOENT)?.get_inode()?;
+        let real_meta = real.get_meta();
+        let inode = Arc::new(Self {
+            meta: InodeMeta::new(real_meta.mode, real_meta.ino, superblock),
+            upper: Mutex::new(upper),
+            lower,
+            alias: Arc::downgrade(alias),
+        });
+        inode.set_type(*real_meta._type.lock());
+        inode.refresh();
+        Ok(inode)
+    }
+    /// 上层的目录项
+    pub fn upper(&self) -> Option<Arc<dyn Dentry>> {
+        self.upper.lock().clone()
+    }
+    /// 下层的目录项, 从上往下
+    pub fn lower(&self) -> &[Arc<dyn Dentry>] {
+        &self.lower
+    }
+    /// 当前可见的那一层的目录项
+    pub fn real_dentry(&self) -> Arc<dyn Dentry> {
+        self.upper().unwrap_or_else(|| self.lower[0].clone())
+    }
+    pub(super) fn set_upper(&self, upper: Arc<dyn Dentry>) {
+        *self.upper.lock() = Some(upper);
+    }
+    fn real(&self) -> SysResult<Arc<dyn Inode>> {
+        self.real_dentry().get_inode()
+    }
+    /// 复制到上层, 返回上层的 inode
+    fn upper_inode(&self) -> SysResult<Arc<dyn Inode>> {
+        let alias = self.alias.upgrade().ok_or(SysError::ENOENT)?;
+        copy_up(&alias)?.get_inode()
+    }
+    /// 从当前可见的那一层重新取属性
+    fn refresh(&self) {
+        let Ok(real) = self.real() else {
+            return;
+        };
+        let src = real.get_meta().inner.lock();
+        let mut dst = self.meta.inner.lock();
+        dst.size = src.size;
+        dst.link = src.link;
+        dst.atime = src.atime;
+        dst.mtime = src.mtime;
+        dst.ctime = src.ctime;
+        dst.mode = src.mode;
+        dst.uid = src.uid;
+        dst.gid = src.gid;
+        dst.rdev = src.rdev;
+    }
+}
+
+impl Inode for OvlInode {
+    fn load_from_disk(&self) {}
+    fn get_meta(&self) -> &InodeMeta {
+        &self.meta
+    }
+    fn get_attr(&self) -> SysResult<Kstat> {
+        let mut attr = self.real()?.get_attr()?;
+        attr.st_ino = self.meta.ino as u64;
+        Ok(attr)
+    }
+    fn get_size(&self) -> u32 {
+        self.real().map_or(0, |real| real.get_size())
+    }
+    fn clear(&self) {}
+    fn set_perm(&self, perm: InodeMode) -> SysResult<()> {
+        self.upper_inode()?.set_perm(perm)?;
+        self.refresh();
+        Ok(())
+    }
+    fn set_owner(&self, uid: u32, gid: u32) -> SysResult<()> {
+        self.upper_inode()?.set_owner(uid, gid)?;
+        self.refresh();
+        Ok(())
+    }
+    /// 时间也是上层的属性, 先复制到上层再改
+    fn set_times(&self, atime: Option<TimeSpec>, mtime: Option<TimeSpec>, ctime: TimeSpec) -> SysResult<()> {
+        self.upper_inode()?.set_times(atime, mtime, ctime)?;
+        self.refresh();
+        Ok(())
+    }
+    fn truncate(&self, size: usize) -> SysResult<()> {
+        self.upper_inode()?.truncate(size)?;
+        self.refresh();
+        Ok(())
+    }
+    fn read_link(&self) -> SysResult<String> {
+        self.real()?.read_link()
+    }
+    fn get_xattr(&self, name: &str) -> SysResult<Vec<u8>> {
+        self.real()?.get_xattr(name)
+    }
+    fn set_xattr(&self, name: &str, value: &[u8], flags: XattrFlags) -> SysResult<()> {
+        self.upper_inode()?.set_xattr(name, value, flags)
+    }
+    fn list_xattr(&self) -> SysResult<Vec<String>> {
+        self.real()?.list_xattr()
+    }
+    fn remove_xattr(&self, name: &str) -> SysResult<()> {
+        self.upper_inode()?.remove_xattr(name)
+    }
+}
@@ -0,0 +1,417 @@
+//! overlay 文件系统
+//!
+//! 把一个可写的上层目录叠在一个或多个只读的下层目录上. 查找时从上往下找, 第一个找到的为准;
+//! 上层的白化文件 (设备号为 0/0 的字符设备) 遮住下层的同名文件, 目录里有 [`OPAQUE_NAME`]
+//! 的目录是不透明的, 不再与更下层的同名目录合并. 下层的文件被修改前先连同各级父目录一起
+//! 复制到上层 (copy-up), 下层本身永远不会被修改.
+//!
+//! 各层在挂载选项里给出: `lowerdir=/a:/b,upperdir=/u,workdir=/w`, lowerdir 从上往下排列.
+//! 没有 upperdir 时整个 overlay 只读. workdir 必须与 upperdir 在同一个文件系统上:
+//! copy-up 先在 workdir 里建好完整的副本再改名到上层, 中途失败时删掉副本, 上层不会留下半个文件.
+use alloc::{
+    collections::BTreeSet,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use device::BlockDevice;
+use sync::Mutex;
+use system_result::{SysError, SysResult};
+use vfs_defs::{
+    Dentry, DentryState, DiskInodeType, FileSystemType, FileSystemTypeInner, Inode, InodeMode, MountFlags,
+    OpenFlags, RenameFlags, StatFs, SuperBlock, SuperBlockInner, XattrFlags,
+};
+
+use crate::mount::check_writable;
+use crate::namei::lookup_path;
+
+mod dentry;
+mod file;
+mod inode;
+
+pub use dentry::OvlDentry;
+pub use file::OvlFile;
+pub use inode::OvlInode;
+
+/// 不透明目录的标记. Linux 记在扩展属性 trusted.overlay.opaque 里, 这里用目录中的一个白化文件
+pub const OPAQUE_NAME: &str = ".wh..wh..opq";
+/// statfs 报告的 f_type, 和 Linux 的 OVERLAYFS_SUPER_MAGIC 一致
+const OVERLAYFS_SUPER_MAGIC: i64 = 0x794c7630;
+/// copy-up 时每次复制的长度
+const COPY_CHUNK: usize = 64 * 1024;
+/// copy-up 时 workdir 里副本的名字, copy-up 串行进行, 同一时刻只有一个
+const COPY_UP_TMP: &str = "#copyup";
+
+pub struct OverlayFsType {
+    inner: FileSystemTypeInner,
+}
+
+impl OverlayFsType {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            inner: FileSystemTypeInner::new(String::from("overlay")),
+        })
+    }
+}
+
+impl FileSystemType for OverlayFsType {
+    fn get_inner(&self) -> &FileSystemTypeInner {
+        &self.inner
+    }
+    /// 各层只能从挂载选项得到, 见 [`mount_overlay`]
+    fn mount(
+        self: Arc<Self>,
+        _name: &str,
+        _parent: Option<Arc<dyn Dentry>>,
+        _flags: MountFlags,
+        _device: Option<Arc<dyn BlockDevice>>,
+    ) -> SysResult<Arc<dyn Dentry>> {
+        Err(SysError::EINVAL)
+    }
+    fn umount(self: Arc<Self>, path: &str, _flags: MountFlags) -> SysResult<()> {
+        self.remove_superblock(path)
+    }
+}
+
+pub struct OvlSuperBlock {
+    inner: SuperBlockInner,
+    /// 上层目录, 只读的 overlay 没有
+    upper: Option<Arc<dyn Dentry>>,
+    /// 下层目录, 从上往下
+    lower: Vec<Arc<dyn Dentry>>,
+    /// 工作目录, 与上层同时存在
+    work: Option<Arc<dyn Dentry>>,
+    /// copy-up 时持有, 防止同一个文件被复制两次
+    copy_up_lock: Mutex<()>,
+}
+
+impl SuperBlock for OvlSuperBlock {
+    fn get_inner(&self) -> &SuperBlockInner {
+        &self.inner
+    }
+    /// 下层不会被修改, 只写回上层
+    fn sync_fs(&self) -> SysResult<()> {
+        match self.upper.as_ref() {
+            Some(upper) => upper.get_superblock().sync_fs(),
+            None => Ok(()),
+        }
+    }
+    /// 容量取新文件所在的层: 有上层时是上层, 否则是最上面的下层
+    fn statfs(&self) -> SysResult<StatFs> {
+        let layer = self.upper.as_ref().or(self.lower.first()).ok_or(SysError::EIO)?;
+        let mut stat = layer.get_superblock().statfs()?;
+        stat.f_type = OVERLAYFS_SUPER_MAGIC;
+        Ok(stat)
+    }
+}
+
+/// 挂载选项中的各层路径
+struct OvlOptions {
+    lower: Vec<String>,
+    upper: Option<String>,
+    work: Option<String>,
+}
+
+fn parse_options(data: &str) -> SysResult<OvlOptions> {
+    let mut opts = OvlOptions {
+        lower: Vec::new(),
+        upper: None,
+        work: None,
+    };
+    for opt in data.split(',').filter(|s| !s.is_empty()) {
+        let (key, value) = opt.split_once('=').ok_or(SysError::EINVAL)?;
+        match key {
+            "lowerdir" => {
+                opts.lower = value.split(':').filter(|s| !s.is_empty()).map(|s| s.to_string()).collect();
+            }
+            "upperdir" => opts.upper = Some(value.to_string()),
+            "workdir" => opts.work = Some(value.to_string()),
+            _ => return Err(SysError::EINVAL),
+        }
+    }
+    Ok(opts)
+}
+
+/// 作为一层的目录
+fn layer_dir(path: &str) -> SysResult<Arc<dyn Dentry>> {
+    let dentry = lookup_path(path)?;
+    if !dentry.get_inode()?.is_dir() {
+        return Err(SysError::ENOTDIR);
+    }
+    Ok(dentry)
+}
+
+pub(super) fn same_dentry(a: &Arc<dyn Dentry>, b: &Arc<dyn Dentry>) -> bool {
+    Arc::as_ptr(a) as *const () == Arc::as_ptr(b) as *const ()
+}
+
+/// 按挂载选项 `data` 建立 overlay, 返回它的根目录项
+pub(crate) fn mount_overlay(
+    fs: Arc<dyn FileSystemType>,
+    name: &str,
+    parent: Option<Arc<dyn Dentry>>,
+    flags: MountFlags,
+    data: &str,
+) -> SysResult<Arc<dyn Dentry>> {
+    let opts = parse_options(data)?;
+    if opts.lower.is_empty() {
+        return Err(SysError::EINVAL);
+    }
+    let lower = opts.lower.iter().map(|path| layer_dir(path)).collect::<SysResult<Vec<_>>>()?;
+    let (upper, work) = match (opts.upper, opts.work) {
+        (Some(upper), Some(work)) => {
+            let (upper, work) = (layer_dir(&upper)?, layer_dir(&work)?);
+            let same_fs = Arc::as_ptr(&upper.get_superblock()) as *const ()
+                == Arc::as_ptr(&work.get_superblock()) as *const ();
+            if !same_fs || same_dentry(&upper, &work) {
+                return Err(SysError::EINVAL);
+            }
+            // 上层在只读挂载上时 overlay 也只能只读
+            if !flags.contains(MountFlags::MS_RDONLY) {
+                check_writable(&upper)?;
+            }
+            (Some(upper), Some(work))
+        }
+        (None, None) => (None, None),
+        _ => return Err(SysError::EINVAL),
+    };
+    let superblock = Arc::new(OvlSuperBlock {
+        inner: SuperBlockInner::new(None, fs.clone()),
+        upper,
+        lower,
+        work,
+        copy_up_lock: Mutex::new(()),
+    });
+    let root: Arc<dyn Dentry> = OvlDentry::new(name, superblock.clone(), parent);
+    let root_inode = OvlInode::new(superblock.clone(), superblock.upper.clone(), superblock.lower.clone(), &root)?;
+    root.set_inode(root_inode);
+    *root.get_state() = DentryState::Valid;
+    superblock.set_root_dentry(root.clone());
+    fs.add_superblock(&root.path(), superblock);
+    Ok(root)
+}
+
+pub(super) fn ovl_inode(dentry: &Arc<dyn Dentry>) -> SysResult<Arc<OvlInode>> {
+    dentry.get_inode()?.downcast_arc::<OvlInode>().map_err(|_| SysError::ENOENT)
+}
+
+fn ovl_superblock(dentry: &Arc<dyn Dentry>) -> SysResult<Arc<OvlSuperBlock>> {
+    dentry.get_superblock().downcast_arc::<OvlSuperBlock>().map_err(|_| SysError::ENOENT)
+}
+
+/// 是否为白化文件
+pub(super) fn is_whiteout(dentry: &Arc<dyn Dentry>) -> bool {
+    dentry.get_inode().is_ok_and(|inode| {
+        let inner = inode.get_meta().inner.lock();
+        inner.mode & InodeMode::TYPE_MASK == InodeMode::CHAR && inner.rdev == 0
+    })
+}
+
+/// 在某一层的目录 `dir` 里找 `name`
+pub(super) fn real_lookup(dir: &Arc<dyn Dentry>, name: &str) -> Option<Arc<dyn Dentry>> {
+    dir.lookup(name).ok().filter(|dentry| !dentry.has_no_inode())
+}
+
+/// 某一层的目录 `dir` 里的所有文件
+fn real_children(dir: &Arc<dyn Dentry>) -> SysResult<Vec<Arc<dyn Dentry>>> {
+    dir.clone().load_dir()?;
+    let children: Vec<Arc<dyn Dentry>> =
+        dir.get_inner().children.lock().values().filter_map(|child| child.upgrade()).collect();
+    Ok(children.into_iter().filter(|child| !child.has_no_inode()).collect())
+}
+
+fn is_opaque(dir: &Arc<dyn Dentry>) -> bool {
+    real_lookup(dir, OPAQUE_NAME).is_some()
+}
+
+/// 在 overlay 目录 `dir` 的各层里找 `name`, 返回 (上层, 下层) 的目录项. 被白化或不存在时返回 None.
+/// 上层是普通文件或不透明目录时不看下层; 下层只有目录会叠在一起, 遇到非目录或不透明目录就停下
+pub(super) fn lookup_layers(
+    dir: &OvlInode,
+    name: &str,
+) -> Option<(Option<Arc<dyn Dentry>>, Vec<Arc<dyn Dentry>>)> {
+    if name == OPAQUE_NAME {
+        return None;
+    }
+    let mut upper = None;
+    if let Some(found) = dir.upper().and_then(|upper_dir| real_lookup(&upper_dir, name)) {
+        if is_whiteout(&found) {
+            return None;
+        }
+        if !found.is_dir() || is_opaque(&found) {
+            return Some((Some(found), Vec::new()));
+        }
+        upper = Some(found);
+    }
+    let mut lower = Vec::new();
+    for lower_dir in dir.lower() {
+        let Some(found) = real_lookup(lower_dir, name) else {
+            continue;
+        };
+        if is_whiteout(&found) {
+            break;
+        }
+        let found_dir = found.is_dir();
+        // 上面已经有同名目录时, 下面的非目录被遮住
+        if (upper.is_some() || !lower.is_empty()) && !found_dir {
+            break;
+        }
+        let stop = !found_dir || is_opaque(&found);
+        lower.push(found);
+        if stop {
+            break;
+        }
+    }
+    if upper.is_none() && lower.is_empty() {
+        return None;
+    }
+    Some((upper, lower))
+}
+
+/// overlay 目录合并后的文件名
+pub(super) fn merged_names(dir: &OvlInode) -> SysResult<Vec<String>> {
+    let mut seen = BTreeSet::new();
+    let mut names = Vec::new();
+    for layer in dir.upper().iter().chain(dir.lower()) {
+        for child in real_children(layer)? {
+            let name = child.get_name_string();
+            if name == OPAQUE_NAME || !seen.insert(name.clone()) {
+                continue;
+            }
+            // 白化文件也记入 seen, 遮住更下层的同名文件
+            if !is_whiteout(&child) {
+                names.push(name);
+            }
+        }
+    }
+    Ok(names)
+}
+
+/// 删除或移走 `dir` 里的 `name` 后, 下层是否还有同名文件会露出来
+pub(super) fn lower_has(dir: &OvlInode, name: &str) -> bool {
+    dir.lower()
+        .iter()
+        .find_map(|lower_dir| real_lookup(lower_dir, name))
+        .is_some_and(|found| !is_whiteout(&found))
+}
+
+/// 在上层目录 `dir` 里建白化文件 `name`
+pub(super) fn make_whiteout(dir: &Arc<dyn Dentry>, name: &str) -> SysResult<()> {
+    dir.mknod(name, InodeMode::CHAR, 0).map(|_| ())
+}
+
+/// 删掉上层目录 `dir` 里的白化文件 `name`, 返回原来是否有
+pub(super) fn remove_whiteout(dir: &Arc<dyn Dentry>, name: &str) -> SysResult<bool> {
+    match real_lookup(dir, name) {
+        Some(whiteout) if is_whiteout(&whiteout) => {
+            dir.unlink(&whiteout)?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// 把上层目录标记为不透明
+pub(super) fn make_opaque(dir: &Arc<dyn Dentry>) -> SysResult<()> {
+    make_whiteout(dir, OPAQUE_NAME)
+}
+
+/// 删除合并后为空的上层目录前, 清掉里面的白化文件
+pub(super) fn clear_whiteouts(dir: &Arc<dyn Dentry>) -> SysResult<()> {
+    for child in real_children(dir)? {
+        if !is_whiteout(&child) && child.get_name_str() != OPAQUE_NAME {
+            return Err(SysError::ENOTEMPTY);
+        }
+        dir.unlink(&child)?;
+    }
+    Ok(())
+}
+
+/// 把 overlay 目录项 `dentry` 复制到上层, 父目录先复制. 返回上层的目录项
+pub(super) fn copy_up(dentry: &Arc<dyn Dentry>) -> SysResult<Arc<dyn Dentry>> {
+    let inode = ovl_inode(dentry)?;
+    if let Some(upper) = inode.upper() {
+        return Ok(upper);
+    }
+    let superblock = ovl_superblock(dentry)?;
+    let work = superblock.work.clone().ok_or(SysError::EROFS)?;
+    let parent = dentry.get_father().ok_or(SysError::EROFS)?;
+    let upper_parent = copy_up(&parent)?;
+    let _guard = superblock.copy_up_lock.lock();
+    if let Some(upper) = inode.upper() {
+        return Ok(upper);
+    }
+    let lower = inode.real_dentry();
+    let real = lower.get_inode()?;
+    let mode = real.get_meta().inner.lock().mode;
+    // 上次没清掉的副本
+    if let Some(stale) = real_lookup(&work, COPY_UP_TMP) {
+        work.unlink(&stale)?;
+    }
+    let (tmp, ty) = match mode & InodeMode::TYPE_MASK {
+        InodeMode::DIR => (work.create(COPY_UP_TMP, DiskInodeType::Directory)?, DiskInodeType::Directory),
+        InodeMode::FILE => (work.create(COPY_UP_TMP, DiskInodeType::File)?, DiskInodeType::File),
+        InodeMode::LINK => (work.symlink(COPY_UP_TMP, &real.read_link()?)?, DiskInodeType::File),
+        _ => (work.mknod(COPY_UP_TMP, mode, real.get_meta().inner.lock().rdev)?, DiskInodeType::File),
+    };
+    let name = dentry.get_name_str();
+    let moved = copy_attrs(&lower, &real, &tmp).and_then(|_| {
+        let target = upper_parent.find_or_create(name, ty);
+        tmp.vfs_rename(&target, RenameFlags::empty())
+    });
+    if let Err(e) = moved {
+        let _ = work.unlink(&tmp);
+        return Err(e);
+    }
+    let upper = real_lookup(&upper_parent, name).ok_or(SysError::EIO)?;
+    inode.set_upper(upper.clone());
+    Ok(upper)
+}
+
+/// 把下层文件的内容、属性和扩展属性复制到 workdir 里的副本 `copy`
+fn copy_attrs(lower: &Arc<dyn Dentry>, real: &Arc<dyn Inode>, copy: &Arc<dyn Dentry>) -> SysResult<()> {
+    let (mode, uid, gid, atime, mtime) = {
+        let inner = real.get_meta().inner.lock();
+        (inner.mode, inner.uid, inner.gid, inner.atime, inner.mtime)
+    };
+    if mode & InodeMode::TYPE_MASK == InodeMode::FILE {
+        copy_data(lower, copy)?;
+    }
+    let copy_inode = copy.get_inode()?;
+    copy_inode.set_perm(mode)?;
+    copy_inode.set_owner(uid, gid)?;
+    // 扩展属性一起复制, 有一层不支持扩展属性时跳过
+    match real.list_xattr() {
+        Ok(names) => {
+            for name in names {
+                match copy_inode.set_xattr(&name, &real.get_xattr(&name)?, XattrFlags::empty()) {
+                    Err(SysError::EOPNOTSUPP) => break,
+                    r => r?,
+                }
+            }
+        }
+        Err(SysError::EOPNOTSUPP) => {}
+        Err(e) => return Err(e),
+    }
+    let mut inner = copy_inode.get_meta().inner.lock();
+    inner.atime = atime;
+    inner.mtime = mtime;
+    Ok(())
+}
+
+/// 把下层文件的内容复制到副本
+fn copy_data(lower: &Arc<dyn Dentry>, upper: &Arc<dyn Dentry>) -> SysResult<()> {
+    let src = lower.clone().open(OpenFlags::RDONLY);
+    let dst = upper.clone().open(OpenFlags::WRONLY);
+    let mut buf = vec![0u8; COPY_CHUNK];
+    let mut offset = 0;
+    loop {
+        let len = src.read_at(offset, &mut buf);
+        if len == 0 {
+            return Ok(());
+        }
+        dst.pwrite(offset, &buf[..len])?;
+        offset += len;
+    }
+}
@@ -0,0 +1,93 @@
+//! UNIX 权限检查
+//!
+//! vfs 不认识内核的任务结构, 调用方把当前任务用于文件系统访问的身份
+//! (fsuid/fsgid/附加组)整理成 [`FsCred`] 传进来.
+use alloc::{sync::Arc, vec::Vec};
+use system_result::{SysError, SysResult};
+use vfs_defs::{Dentry, Inode, InodeMode};
+
+/// 可执行/可搜索
+pub const MAY_EXEC: u32 = 1;
+/// 可写
+pub const MAY_WRITE: u32 = 2;
+/// 可读
+pub const MAY_READ: u32 = 4;
+
+/// 文件系统访问使用的身份
+#[derive(Clone, Debug)]
+pub struct FsCred {
+    pub uid: u32,
+    pub gid: u32,
+    pub groups: Vec<u32>,
+}
+
+impl FsCred {
+    /// 内核自己访问文件时使用的 root 身份
+    pub fn root() -> Self {
+        Self {
+            uid: 0,
+            gid: 0,
+            groups: Vec::new(),
+        }
+    }
+    pub fn is_root(&self) -> bool {
+        self.uid == 0
+    }
+    pub fn in_group(&self, gid: u32) -> bool {
+        self.gid == gid || self.groups.contains(&gid)
+    }
+}
+
+/// 按属主/属组/其他人的顺序取出适用的 rwx 位, root 只在执行时受 x 位限制
+pub fn inode_permission(inode: &Arc<dyn Inode>, cred: &FsCred, mask: u32) -> SysResult<()> {
+    let (mode, uid, gid) = {
+        let inner = inode.get_meta().inner.lock();
+        (inner.mode, inner.uid, inner.gid)
+    };
+    let is_dir = mode & InodeMode::TYPE_MASK == InodeMode::DIR;
+    if cred.is_root() {
+        // 普通文件至少要有一个 x 位才能被 root 执行
+        let any_exec = InodeMode::OWNER_EXEC | InodeMode::GROUP_EXEC | InodeMode::OTHER_EXEC;
+        if mask & MAY_EXEC == 0 || is_dir || mode.intersects(any_exec) {
+            return Ok(());
+        }
+        return Err(SysError::EACCES);
+    }
+    let perm = mode.bits();
+    let granted = if cred.uid == uid {
+        (perm >> 6) & 7
+    } else if cred.in_group(gid) {
+        (perm >> 3) & 7
+    } else {
+        perm & 7
+    };
+    if granted & mask == mask {
+        Ok(())
+    } else {
+        Err(SysError::EACCES)
+    }
+}
+
+/// 在带粘滞位的目录里, 只有文件属主、目录属主和 root 能删除或改名
+pub fn check_sticky(dir: &Arc<dyn Dentry>, victim: &Arc<dyn Dentry>, cred: &FsCred) -> SysResult<()> {
+    if cred.is_root() {
+        return Ok(());
+    }
+    let (dir_mode, dir_uid) = {
+        let inode = dir.get_inode()?;
+        let inner = inode.get_meta().inner.lock();
+        (inner.mode, inner.uid)
+    };
+    if !dir_mode.contains(InodeMode::STICKY) || dir_uid == cred.uid {
+        return Ok(());
+    }
+    if victim.get_inode()?.get_meta().inner.lock().uid == cred.uid {
+        return Ok(());
+    }
+    Err(SysError::EPERM)
+}
+
+/// 在 `dir` 中新建、删除目录项需要写和搜索权限
+pub fn may_modify_dir(dir: &Arc<dyn Dentry>, cred: &FsCred) -> SysResult<()> {
+    inode_permission(&dir.get_inode()?, cred, MAY_WRITE | MAY_EXEC)
+}
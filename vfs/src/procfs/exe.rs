@@ -7,7 +7,17 @@ use system_result::{SysError, SysResult};
 use vfs_defs::{
     Dentry, DentryInner, File, FileInner, Inode, InodeMeta, InodeMode, SuperBlock,Kstat,OpenFlags,DiskInodeType,RenameFlags,ino_alloc,
 };
-use config::DISK_BLOCK_SZ;
+use sync::Mutex;
+
+/// 给出当前进程可执行文件的路径, vfs 不依赖内核本体, 由内核在 `vfs::init` 之前登记
+pub type ExeResolver = fn() -> SysResult<String>;
+
+static EXE_RESOLVER: Mutex<Option<ExeResolver>> = Mutex::new(None);
+
+/// 登记 /proc/self/exe 指向的路径的来源
+pub fn register_exe_resolver(resolver: ExeResolver) {
+    *EXE_RESOLVER.lock() = Some(resolver);
+}
 
 pub struct ExeDentry {
     inner: DentryInner,
@@ -73,13 +83,12 @@ pub struct ExeInode {
 }
 
 impl ExeInode {
-    pub fn new(super_block: Arc<dyn SuperBlock>, _size: usize) -> Arc<Self> {
-        let size = DISK_BLOCK_SZ;
+    pub fn new(super_block: Arc<dyn SuperBlock>) -> Arc<Self> {
+        let mode = InodeMode::LINK | InodeMode::OWNER_MASK | InodeMode::GROUP_MASK | InodeMode::OTHER_MASK;
         let ret = Arc::new(Self {
-            meta: InodeMeta::new(InodeMode::FILE,ino_alloc(), super_block),
+            meta: InodeMeta::new(mode,ino_alloc(), super_block),
         });
         *ret.meta._type.lock() = DiskInodeType::File;
-        ret.get_meta().inner.lock().size = size as u32;
         ret
     }
 }
@@ -90,16 +99,16 @@ impl Inode for ExeInode {
     }
 
     fn get_attr(&self) -> SysResult<Kstat> {
+        let len = self.get_size();
         let inner = self.meta.inner.lock();
-        let mode = self.meta.mode.bits();
-        let len = inner.size;
+        let mode = inner.mode.bits();
         Ok(Kstat {
             st_dev: 0,
             st_ino: self.meta.ino as u64,
             st_mode: mode,
             st_nlink: 1,
-            st_uid: 0,
-            st_gid: 0,
+            st_uid: inner.uid,
+            st_gid: inner.gid,
             st_rdev: 0,
             __pad: 0,
             st_size: len as u64,
@@ -119,7 +128,11 @@ impl Inode for ExeInode {
         
     }
     fn get_size(&self) -> u32 {
-        self.meta.inner.lock().size
+        self.read_link().map_or(0, |path| path.len() as u32)
+    }
+    fn read_link(&self) -> SysResult<String> {
+        let resolver = (*EXE_RESOLVER.lock()).ok_or(SysError::ENOENT)?;
+        resolver()
     }
     fn clear(&self) {
         
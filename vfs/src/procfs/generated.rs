@@ -0,0 +1,185 @@
+//! 内容由内核其他模块在读取时生成的 procfs 文件
+//!
+//! vfs 不依赖内核本体, 需要导出统计信息的模块在 `vfs::init` 之前调用
+//! [`register_proc_file`] 登记文件名和生成函数.
+use alloc::{
+    string::String,
+    sync::Arc,
+    vec::Vec,
+};
+use core::cmp;
+
+use system_result::{SysError, SysResult};
+use vfs_defs::{
+    Dentry, DentryInner, File, FileInner, Inode, InodeMeta, InodeMode, SuperBlock,Kstat,OpenFlags,DiskInodeType,RenameFlags,ino_alloc,
+};
+
+use sync::Mutex;
+
+/// 生成文件内容的函数
+pub type ProcGenerator = fn() -> String;
+
+pub static PROC_GENERATED: Mutex<Vec<(&'static str, ProcGenerator)>> = Mutex::new(Vec::new());
+
+/// 在 /proc 下登记一个只读文件, 每次读取时调用 `generator` 生成内容
+pub fn register_proc_file(name: &'static str, generator: ProcGenerator) {
+    PROC_GENERATED.lock().push((name, generator));
+}
+
+pub struct GeneratedDentry {
+    inner: DentryInner,
+    generator: ProcGenerator,
+}
+
+impl GeneratedDentry {
+    pub fn new(
+        name: &str,
+        generator: ProcGenerator,
+        super_block: Arc<dyn SuperBlock>,
+        parent: Option<Arc<dyn Dentry>>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            inner: DentryInner::new(String::from(name), super_block, parent),
+            generator,
+        })
+    }
+}
+
+impl Dentry for GeneratedDentry {
+    fn get_inner(&self) -> &DentryInner {
+        &self.inner
+    }
+
+    fn open(self: Arc<Self>,flags:OpenFlags) -> Arc<dyn File> {
+        let generator = self.generator;
+        let ret = Arc::new(GeneratedFile {
+            inner: FileInner::new(self),
+            generator,
+        });
+        *ret.get_inner().flags.lock() = flags;
+        ret
+    }
+
+    fn concrete_lookup(self: Arc<Self>, _name: &str) -> SysResult<Arc<dyn Dentry>> {
+        Err(SysError::ENOTDIR)
+    }
+
+    fn concrete_create(self: Arc<Self>, _name: &str, _type:DiskInodeType) -> SysResult<Arc<dyn Dentry>> {
+        Err(SysError::ENOTDIR)
+    }
+
+    fn concrete_unlink(self: Arc<Self>, _old: &Arc<dyn Dentry>) -> SysResult<()> {
+        Err(SysError::ENOTDIR)
+    }
+    fn concrete_new_child(self: Arc<Self>, _name: &str) -> Arc<dyn Dentry> {
+        unimplemented!()
+    }
+    fn concrete_link(self: Arc<Self>, _new: &Arc<dyn Dentry>) -> SysResult<()> {
+        Err(SysError::ENOTDIR)
+    }
+    fn concrete_rename(self: Arc<Self>, _new: Arc<dyn Dentry>, _flags: RenameFlags) -> SysResult<()> {
+        Err(SysError::ENOTDIR)
+    }
+    fn concrete_getchild(self:Arc<Self>, _name: &str) -> Option<Arc<dyn Dentry>> {
+        None
+    }
+    fn self_arc(self:Arc<Self>) -> Arc<dyn Dentry> {
+        self.clone()
+    }
+    fn load_dir(self:Arc<Self>)->SysResult<()> {
+        Err(SysError::ENOTDIR)
+    }
+}
+
+pub struct GeneratedInode {
+    meta: InodeMeta,
+    generator: ProcGenerator,
+}
+
+impl GeneratedInode {
+    pub fn new(super_block: Arc<dyn SuperBlock>, generator: ProcGenerator) -> Arc<Self> {
+        let ret = Arc::new(Self {
+            meta: InodeMeta::new(InodeMode::FILE,ino_alloc(), super_block),
+            generator,
+        });
+        *ret.meta._type.lock() = DiskInodeType::File;
+        ret
+    }
+}
+
+impl Inode for GeneratedInode {
+    fn get_meta(&self) -> &InodeMeta {
+        &self.meta
+    }
+
+    fn get_attr(&self) -> SysResult<Kstat> {
+        let inner = self.meta.inner.lock();
+        let mode = inner.mode.bits();
+        let len = self.get_size();
+        Ok(Kstat {
+            st_dev: 0,
+            st_ino: self.meta.ino as u64,
+            st_mode: mode,
+            st_nlink: 1,
+            st_uid: inner.uid,
+            st_gid: inner.gid,
+            st_rdev: 0,
+            __pad: 0,
+            st_size: len as u64,
+            st_blksize: 512,
+            __pad2: 0,
+            st_blocks: (len / 512) as u64,
+            st_atime_sec:inner.atime.sec as u64,
+            st_atime_nsec:inner.atime.usec as u64,
+            st_mtime_sec:inner.mtime.sec as u64,
+            st_mtime_nsec:inner.mtime.usec as u64,
+            st_ctime_sec:inner.ctime.sec as u64,
+            st_ctime_nsec:inner.ctime.usec as u64,
+            unused: 0,
+        })
+    }
+    fn load_from_disk(&self) {
+
+    }
+    /// 内容随时在变, 每次都重新生成
+    fn get_size(&self) -> u32 {
+        (self.generator)().len() as u32
+    }
+    fn clear(&self) {
+
+    }
+}
+
+pub struct GeneratedFile {
+    inner: FileInner,
+    generator: ProcGenerator,
+}
+
+impl File for GeneratedFile {
+    fn get_inner(&self) -> &FileInner {
+        &self.inner
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let content = (self.generator)();
+        if offset >= content.len() {
+            return 0;
+        }
+        let len = cmp::min(content.len() - offset, buf.len());
+        buf[..len].copy_from_slice(&content.as_bytes()[offset..offset + len]);
+        len
+    }
+
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> usize {
+        0
+    }
+    fn readable(&self) -> bool {
+        true
+    }
+    fn writable(&self) -> bool {
+        false
+    }
+    fn poll(&self, _events: vfs_defs::PollEvents) -> vfs_defs::PollEvents {
+        vfs_defs::PollEvents::POLLIN
+    }
+}
@@ -1,13 +1,17 @@
 mod meminfo;
 mod mounts;
 mod exe;
-use vfs_defs::{FileSystemType,FileSystemTypeInner,SuperBlock,SuperBlockInner,Dentry,MountFlags,InodeMode,DiskInodeType,OpenFlags,DentryState};
+mod generated;
+use vfs_defs::{FileSystemType,FileSystemTypeInner,SuperBlock,SuperBlockInner,Dentry,MountFlags,InodeMode,DiskInodeType,OpenFlags,DentryState,StatFs};
 use alloc::{string::String, sync::Arc};
 use device::BlockDevice;
-use super::{MemDentry,MemInode,add_vfs_dentry,FILE_SYSTEMS};
+use super::{MemDentry,MemInode,add_vfs_dentry,NAME_MAX};
 use meminfo::{MemInfoDentry,MemInfoInode};
 use mounts::{MountsInode,MountsDentry};
 use exe::{ExeInode,ExeDentry};
+use generated::{GeneratedDentry,GeneratedInode,PROC_GENERATED};
+pub use generated::register_proc_file;
+pub use exe::register_exe_resolver;
 use system_result::SysResult;
 
 pub fn init_procfs(root_dentry: Arc<dyn Dentry>) -> SysResult<()> {
@@ -55,7 +59,7 @@ pub fn init_procfs(root_dentry: Arc<dyn Dentry>) -> SysResult<()> {
 
     let exe_dentry: Arc<dyn Dentry> =
         ExeDentry::new(root_dentry.get_superblock(), Some(root_dentry.clone()));
-    let exe_inode = ExeInode::new(root_dentry.get_superblock(), 0);
+    let exe_inode = ExeInode::new(root_dentry.get_superblock());
     exe_dentry.set_inode(exe_inode);
     *exe_dentry.get_state() = DentryState::Valid;
     self_dentry.add_child(exe_dentry.clone());
@@ -63,6 +67,19 @@ pub fn init_procfs(root_dentry: Arc<dyn Dentry>) -> SysResult<()> {
     root_dentry.add_child(self_dentry.clone());
     add_vfs_dentry(self_dentry);
 
+    for &(name, generator) in PROC_GENERATED.lock().iter() {
+        let dentry = GeneratedDentry::new(
+            name,
+            generator,
+            root_dentry.get_superblock(),
+            Some(root_dentry.clone()),
+        );
+        dentry.set_inode(GeneratedInode::new(root_dentry.get_superblock(), generator));
+        *dentry.get_state() = DentryState::Valid;
+        root_dentry.add_child(dentry.clone());
+        add_vfs_dentry(dentry);
+    }
+
     Ok(())
 }
 
@@ -101,14 +118,17 @@ impl FileSystemType for ProcFsType {
         Ok(root_dentry)
     }
     fn umount(self:Arc<Self>,
-            _path:&str,
+            path:&str,
             _flags:MountFlags
         )->system_result::SysResult<()> {
-        unimplemented!()
+        self.remove_superblock(path)
     }
 
 }
 
+/// statfs 报告的 f_type, 和 Linux 的 PROC_SUPER_MAGIC 一致
+const PROC_SUPER_MAGIC: i64 = 0x9fa0;
+
 pub struct ProcSuperBlock {
     inner: SuperBlockInner,
 }
@@ -128,4 +148,14 @@ impl SuperBlock for ProcSuperBlock {
     fn get_inner(&self) -> &SuperBlockInner{
         &self.inner
     }
+    /// 内容都是现算的, 不占空间
+    fn statfs(&self) -> SysResult<StatFs> {
+        Ok(StatFs {
+            f_type: PROC_SUPER_MAGIC,
+            f_bsize: 4096,
+            f_namelen: NAME_MAX as isize,
+            f_frsize: 4096,
+            ..Default::default()
+        })
+    }
 }
@@ -1,5 +1,5 @@
 use alloc::{
-    string::{String, ToString},
+    string::String,
     sync::Arc,
 };
 use core::cmp;
@@ -8,7 +8,7 @@ use system_result::{SysError, SysResult};
 use vfs_defs::{
     Dentry, DentryInner, File, FileInner, Inode, InodeMeta, InodeMode, SuperBlock,Kstat,OpenFlags,DiskInodeType,RenameFlags,ino_alloc,
 };
-use super::FILE_SYSTEMS;
+use crate::mount::list_mounts;
 
 
 pub struct MountsDentry {
@@ -94,15 +94,15 @@ impl Inode for MountsInode {
 
     fn get_attr(&self) -> SysResult<Kstat> {
         let inner = self.meta.inner.lock();
-        let mode = self.meta.mode.bits();
-        let len = inner.size;
+        let mode = inner.mode.bits();
+        let len = self.get_size();
         Ok(Kstat {
             st_dev: 0,
             st_ino: self.meta.ino as u64,
             st_mode: mode,
             st_nlink: 1,
-            st_uid: 0,
-            st_gid: 0,
+            st_uid: inner.uid,
+            st_gid: inner.gid,
             st_rdev: 0,
             __pad: 0,
             st_size: len as u64,
@@ -122,7 +122,7 @@ impl Inode for MountsInode {
         
     }
     fn get_size(&self) -> u32 {
-        self.meta.inner.lock().size
+        list_mounts().len() as u32
     }
     fn clear(&self) {
         
@@ -133,18 +133,6 @@ pub struct MountsFile {
     inner: FileInner,
 }
 
-pub fn list_mounts() -> String {
-    let mut res = "".to_string();
-    let fss = FILE_SYSTEMS.lock();
-    for (_fstype, fs) in fss.file_systems.iter() {
-        let supers = fs.get_inner().superblocks.lock();
-        for (_mount_path, _sb) in supers.iter() {
-            res += "proc /proc proc rw,nosuid,nodev,noexec,relatime 0 0\n"
-        }
-    }
-    res
-}
-
 impl File for MountsFile {
     fn get_inner(&self) -> &FileInner {
         &self.inner
@@ -152,6 +140,9 @@ impl File for MountsFile {
 
     fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
         let mounts = list_mounts();
+        if offset >= mounts.len() {
+            return 0;
+        }
         let len = cmp::min(mounts.len() - offset, buf.len());
         buf[..len].copy_from_slice(&mounts.as_bytes()[offset..offset + len]);
         len
@@ -1,7 +1,18 @@
-use vfs_defs::{FileSystemType,FileSystemTypeInner,SuperBlock,SuperBlockInner,Dentry,MountFlags,InodeMode,DentryState};
-use alloc::{string::String, sync::Arc};
+use vfs_defs::{FileSystemType,FileSystemTypeInner,SuperBlock,SuperBlockInner,Dentry,MountFlags,InodeMode,DentryState,File,FileSeals,OpenFlags,StatFs};
+use alloc::{collections::BTreeSet, format, string::String, sync::Arc, vec::Vec};
+use config::KERNEL_HEAP_SIZE;
+use system_result::SysResult;
 use device::BlockDevice;
-use super::{MemDentry,MemInode,add_vfs_dentry};
+use sync::Once;
+use super::{MemDentry,MemInode,add_vfs_dentry,VFS_DENTRY,NAME_MAX};
+
+/// statfs 报告的 f_type, 和 Linux 的 TMPFS_MAGIC 一致
+pub(crate) const TMPFS_MAGIC: i64 = 0x01021994;
+/// statfs 的块大小
+const TMPFS_BLOCK_SIZE: usize = 4096;
+/// 文件内容放在内核堆上, 和 Linux 默认一样把容量报成内存 (这里是内核堆) 的一半.
+/// 只用于 statfs, 写入时不检查
+const TMPFS_BLOCKS: u64 = (KERNEL_HEAP_SIZE / 2 / TMPFS_BLOCK_SIZE) as u64;
 
 pub struct TmpFsType {
     inner: FileSystemTypeInner,
@@ -38,10 +49,10 @@ impl FileSystemType for TmpFsType {
         Ok(root_dentry)
     }
     fn umount(self:Arc<Self>,
-            _path:&str,
+            path:&str,
             _flags:MountFlags
         )->system_result::SysResult<()> {
-        unimplemented!()
+        self.remove_superblock(path)
     }
 
 }
@@ -65,4 +76,57 @@ impl SuperBlock for TmpSuperBlock {
     fn get_inner(&self) -> &SuperBlockInner{
         &self.inner
     }
+    fn statfs(&self) -> SysResult<StatFs> {
+        // tmpfs 的目录项都挂在 VFS_DENTRY 上, 从中挑出属于本文件系统的, 硬链接按 inode 去重
+        let me = self as *const Self as *const ();
+        let dentries: Vec<Arc<dyn Dentry>> = VFS_DENTRY.lock().dentry.clone();
+        let mut seen = BTreeSet::new();
+        let mut used = 0u64;
+        for dentry in dentries {
+            if dentry.get_inner().superblock.as_ptr() as *const () != me {
+                continue;
+            }
+            if let Ok(inode) = dentry.get_inode() {
+                if seen.insert(Arc::as_ptr(&inode) as *const () as usize) {
+                    used += (inode.get_size() as u64).div_ceil(TMPFS_BLOCK_SIZE as u64);
+                }
+            }
+        }
+        let free = TMPFS_BLOCKS.saturating_sub(used);
+        let files = seen.len() as u64;
+        Ok(StatFs {
+            f_type: TMPFS_MAGIC,
+            f_bsize: TMPFS_BLOCK_SIZE as i64,
+            f_blocks: TMPFS_BLOCKS,
+            f_bfree: free,
+            f_bavail: free,
+            f_files: TMPFS_BLOCKS,
+            f_ffree: TMPFS_BLOCKS.saturating_sub(files),
+            f_namelen: NAME_MAX as isize,
+            f_frsize: TMPFS_BLOCK_SIZE as isize,
+            ..Default::default()
+        })
+    }
+}
+
+/// memfd 所在的内部 tmpfs 及其根目录, 不挂载到任何地方.
+/// 目录项只弱引用超级块, 这里替它持有
+static MEMFD_ROOT: Once<(Arc<dyn SuperBlock>, Arc<dyn Dentry>)> = Once::new();
+
+/// 新建一个 memfd: 内部 tmpfs 上一个不在任何目录里的普通文件, 路径显示为 `/memfd:name`.
+/// 不允许封印时初始封印为 F_SEAL_SEAL
+pub fn memfd_create(name: &str, allow_sealing: bool) -> Arc<dyn File> {
+    let (superblock, root) = MEMFD_ROOT.call_once(|| {
+        let superblock: Arc<dyn SuperBlock> = TmpSuperBlock::new(None, TmpFsType::new());
+        let root: Arc<dyn Dentry> = MemDentry::new("/", superblock.clone(), None);
+        root.set_inode(MemInode::new(InodeMode::DIR, superblock.clone()));
+        *root.get_state() = DentryState::Valid;
+        (superblock, root)
+    });
+    let dentry = MemDentry::new(&format!("memfd:{}", name), superblock.clone(), Some(root.clone()));
+    let inode = MemInode::new(InodeMode::FILE, superblock.clone());
+    inode.get_meta().inner.lock().seals = Some(if allow_sealing { FileSeals::empty() } else { FileSeals::SEAL });
+    dentry.set_inode(inode);
+    *dentry.get_state() = DentryState::Valid;
+    dentry.open(OpenFlags::RDWR)
 }
@@ -0,0 +1,40 @@
+//! ioctl 的参数是用户地址, 先复制到内核再用, 地址无效时返回 EFAULT.
+//! vfs 不依赖内核本体, 复制的方法由内核在 `vfs::init` 之前登记
+use core::mem::{size_of, MaybeUninit};
+use core::slice;
+use sync::Mutex;
+use system_result::{SysError, SysResult};
+
+/// 从当前任务的用户地址读到内核缓冲区
+pub type CopyFromUser = fn(usize, &mut [u8]) -> SysResult<()>;
+/// 把内核缓冲区写到当前任务的用户地址
+pub type CopyToUser = fn(usize, &[u8]) -> SysResult<()>;
+
+static USER_COPY: Mutex<Option<(CopyFromUser, CopyToUser)>> = Mutex::new(None);
+
+/// 登记访问用户内存的方法
+pub fn register_user_copy(copy_from: CopyFromUser, copy_to: CopyToUser) {
+    *USER_COPY.lock() = Some((copy_from, copy_to));
+}
+
+/// 从用户地址 `addr` 读出一个 `T`, `T` 须是任意字节都合法的 `repr(C)` 结构
+pub fn read_user<T: Copy>(addr: usize) -> SysResult<T> {
+    let (copy_from, _) = (*USER_COPY.lock()).ok_or(SysError::EFAULT)?;
+    if addr == 0 {
+        return Err(SysError::EFAULT);
+    }
+    let mut val = MaybeUninit::<T>::zeroed();
+    let buf = unsafe { slice::from_raw_parts_mut(val.as_mut_ptr() as *mut u8, size_of::<T>()) };
+    copy_from(addr, buf)?;
+    Ok(unsafe { val.assume_init() })
+}
+
+/// 把 `val` 写到用户地址 `addr`
+pub fn write_user<T: Copy>(addr: usize, val: &T) -> SysResult<()> {
+    let (_, copy_to) = (*USER_COPY.lock()).ok_or(SysError::EFAULT)?;
+    if addr == 0 {
+        return Err(SysError::EFAULT);
+    }
+    let buf = unsafe { slice::from_raw_parts(val as *const T as *const u8, size_of::<T>()) };
+    copy_to(addr, buf)
+}